@@ -0,0 +1,197 @@
+use async_channel::{Receiver, Sender};
+use revolt_database::{
+    actions::messages,
+    events::{client::EventV1, server::ClientMessage},
+    util::{idempotency::IdempotencyKey, ratelimit::Ratelimit, reference::Reference},
+    Database,
+};
+use revolt_models::v0;
+use revolt_result::{create_error, Result};
+
+use crate::connection::Outgoing;
+
+/// Execute commands sent by a client in the order they were received
+///
+/// Runs separately from the connection so that events keep
+/// flowing to the client while commands wait on the database.
+pub async fn worker(
+    db: &'static Database,
+    user_id: String,
+    session_id: String,
+    commands: Receiver<ClientMessage>,
+    outgoing: Sender<Outgoing>,
+) {
+    while let Ok(message) = commands.recv().await {
+        if let Some(event) = execute(db, &user_id, &session_id, message).await {
+            if outgoing.send(Outgoing::Event(event)).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Execute a command sent by the client on behalf of the given user
+///
+/// Returns a result correlated to the command's nonce, or None
+/// if the message was not a command.
+///
+/// Commands draw from the same per-session buckets as the API routes
+/// they mirror: `messaging` for creating or changing messages and
+/// `channels` for acknowledging them.
+pub async fn execute(
+    db: &Database,
+    user_id: &str,
+    session_id: &str,
+    message: ClientMessage,
+) -> Option<EventV1> {
+    let (nonce, bucket, channel) = match &message {
+        ClientMessage::SendMessage { nonce, channel, .. }
+        | ClientMessage::EditMessage { nonce, channel, .. }
+        | ClientMessage::React { nonce, channel, .. } => {
+            (nonce.clone(), "messaging", channel.as_str())
+        }
+        ClientMessage::Ack { nonce, channel, .. } => (nonce.clone(), "channels", channel.as_str()),
+        _ => return None,
+    };
+
+    let result = match Ratelimit::deduct(session_id, (bucket, Some(channel))) {
+        Err(ratelimit) => Err(create_error!(Ratelimited {
+            retry_after: ratelimit.reset
+        })),
+        Ok(_) => run(db, user_id, message).await,
+    };
+
+    Some(match result {
+        Ok(message) => EventV1::CommandResult {
+            nonce,
+            message,
+            error: None,
+        },
+        Err(error) => EventV1::CommandResult {
+            nonce,
+            message: None,
+            error: Some(error),
+        },
+    })
+}
+
+/// Run a command using the same logic as the API
+async fn run(db: &Database, user_id: &str, message: ClientMessage) -> Result<Option<v0::Message>> {
+    // Fetch the user fresh as permissions may have changed since we connected.
+    let user = db.fetch_user(user_id).await?;
+
+    match message {
+        ClientMessage::SendMessage {
+            nonce,
+            channel,
+            data,
+        } => messages::send_message(
+            db,
+            &user,
+            &Reference::from_unchecked(channel),
+            data,
+            IdempotencyKey::from_nonce(nonce).await?,
+        )
+        .await
+        .map(Some),
+        ClientMessage::EditMessage {
+            channel,
+            message,
+            data,
+            ..
+        } => messages::edit_message(
+            db,
+            &user,
+            &Reference::from_unchecked(channel),
+            &Reference::from_unchecked(message),
+            data,
+        )
+        .await
+        .map(Some),
+        ClientMessage::Ack {
+            channel, message, ..
+        } => messages::ack_message(
+            db,
+            &user,
+            &Reference::from_unchecked(channel),
+            &Reference::from_unchecked(message),
+        )
+        .await
+        .map(|_| None),
+        ClientMessage::React {
+            channel,
+            message,
+            emoji,
+            ..
+        } => messages::react_message(
+            db,
+            &user,
+            &Reference::from_unchecked(channel),
+            &Reference::from_unchecked(message),
+            &Reference::from_unchecked(emoji),
+        )
+        .await
+        .map(|_| None),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revolt_database::{
+        events::{client::EventV1, server::ClientMessage},
+        util::ratelimit::resolve_bucket_limit,
+        Database,
+    };
+    use revolt_result::ErrorType;
+
+    use super::execute;
+
+    fn react(channel: &str) -> ClientMessage {
+        ClientMessage::React {
+            nonce: "nonce".to_string(),
+            channel: channel.to_string(),
+            message: "message".to_string(),
+            emoji: "emoji".to_string(),
+        }
+    }
+
+    fn ack(channel: &str) -> ClientMessage {
+        ClientMessage::Ack {
+            nonce: "nonce".to_string(),
+            channel: channel.to_string(),
+            message: "message".to_string(),
+        }
+    }
+
+    async fn is_ratelimited(db: &Database, message: ClientMessage) -> bool {
+        match execute(db, "user", "commands_session", message).await {
+            Some(EventV1::CommandResult {
+                error: Some(error), ..
+            }) => matches!(error.error_type, ErrorType::Ratelimited { .. }),
+            _ => false,
+        }
+    }
+
+    #[async_std::test]
+    async fn shares_messaging_bucket_per_channel() {
+        let db = Database::Reference(Default::default());
+        for _ in 0..resolve_bucket_limit("messaging") {
+            assert!(!is_ratelimited(&db, react("channel")).await);
+        }
+
+        assert!(is_ratelimited(&db, react("channel")).await);
+        assert!(!is_ratelimited(&db, react("other")).await);
+    }
+
+    #[async_std::test]
+    async fn acks_share_channels_bucket() {
+        let db = Database::Reference(Default::default());
+        for _ in 0..resolve_bucket_limit("channels") {
+            assert!(!is_ratelimited(&db, ack("acked")).await);
+        }
+
+        assert!(is_ratelimited(&db, ack("acked")).await);
+        assert!(!is_ratelimited(&db, react("acked")).await);
+    }
+}
@@ -1,4 +1,7 @@
+use std::{io::Write, sync::Mutex};
+
 use async_tungstenite::tungstenite::{handshake, Message};
+use flate2::write::ZlibEncoder;
 use futures::channel::oneshot::Sender;
 use revolt_result::{create_error, Result};
 use serde::{Deserialize, Serialize};
@@ -10,26 +13,95 @@ pub enum ProtocolFormat {
     Msgpack,
 }
 
+/// Enumeration of supported transport compression methods
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolCompression {
+    None,
+    ZlibStream,
+    ZstdStream,
+}
+
+/// Compression context shared across all messages sent on a connection
+///
+/// Each message is flushed to a block boundary so the client can
+/// decompress it immediately, while the dictionary carries over
+/// from previous messages for the lifetime of the connection.
+enum Compressor {
+    Zlib(ZlibEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Compressor {
+    /// Create a new compression context for the given method
+    fn new(compression: ProtocolCompression) -> std::io::Result<Option<Self>> {
+        Ok(match compression {
+            ProtocolCompression::None => None,
+            ProtocolCompression::ZlibStream => Some(Compressor::Zlib(ZlibEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            ))),
+            ProtocolCompression::ZstdStream => Some(Compressor::Zstd(
+                zstd::stream::write::Encoder::new(Vec::new(), 0)?,
+            )),
+        })
+    }
+
+    /// Compress a single message and flush it out of the stream
+    fn compress(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compressor::Zlib(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Compressor::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Compressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compressor::Zlib(_) => write!(f, "Compressor::Zlib"),
+            Compressor::Zstd(_) => write!(f, "Compressor::Zstd"),
+        }
+    }
+}
+
 /// User-provided protocol configuration
 #[derive(Debug)]
 pub struct ProtocolConfiguration {
     protocol_version: i32,
     format: ProtocolFormat,
+    compression: ProtocolCompression,
+    compressor: Option<Mutex<Compressor>>,
     session_token: Option<String>,
+    intents: Option<u32>,
 }
 
 impl ProtocolConfiguration {
     /// Create a new protocol configuration object from provided data
+    ///
+    /// Fails if the requested compression context could not be created.
     pub fn from(
         protocol_version: i32,
         format: ProtocolFormat,
+        compression: ProtocolCompression,
         session_token: Option<String>,
-    ) -> Self {
-        Self {
+        intents: Option<u32>,
+    ) -> std::io::Result<Self> {
+        Ok(Self {
             protocol_version,
             format,
+            compression,
+            compressor: Compressor::new(compression)?.map(Mutex::new),
             session_token,
-        }
+            intents,
+        })
     }
 
     /// Decode some WebSocket message into a T: Deserialize using the client's specified protocol format
@@ -53,14 +125,29 @@ impl ProtocolConfiguration {
     }
 
     /// Encode T: Serialize into a WebSocket message using the client's specified protocol format
+    ///
+    /// If the client requested compression, the message is always sent as binary
+    /// and must be written to the socket in the same order that it was encoded.
     pub fn encode<T: Serialize>(&self, data: &T) -> Message {
-        match self.format {
+        let message = match self.format {
             ProtocolFormat::Json => {
                 Message::Text(serde_json::to_string(data).expect("Failed to serialise (as json)."))
             }
             ProtocolFormat::Msgpack => Message::Binary(
                 rmp_serde::to_vec_named(data).expect("Failed to serialise (as msgpack)."),
             ),
+        };
+
+        if let Some(compressor) = &self.compressor {
+            Message::Binary(
+                compressor
+                    .lock()
+                    .expect("Compressor mutex was poisoned.")
+                    .compress(&message.into_data())
+                    .expect("Failed to compress message."),
+            )
+        } else {
+            message
         }
     }
 
@@ -74,6 +161,16 @@ impl ProtocolConfiguration {
         &self.session_token
     }
 
+    /// Set the requested gateway intents
+    pub fn set_intents(&mut self, intents: u32) {
+        self.intents.replace(intents);
+    }
+
+    /// Get the requested gateway intents
+    pub fn get_intents(&self) -> Option<u32> {
+        self.intents
+    }
+
     /// Get the protocol version specified
     pub fn get_protocol_version(&self) -> i32 {
         self.protocol_version
@@ -83,6 +180,11 @@ impl ProtocolConfiguration {
     pub fn get_protocol_format(&self) -> &ProtocolFormat {
         &self.format
     }
+
+    /// Get the transport compression specified
+    pub fn get_protocol_compression(&self) -> ProtocolCompression {
+        self.compression
+    }
 }
 
 /// Object holding one side of a channel for receiving the parsed information
@@ -111,7 +213,9 @@ impl handshake::server::Callback for WebsocketHandshakeCallback {
         // Set default values for the protocol.
         let mut protocol_version = 1;
         let mut format = ProtocolFormat::Json;
+        let mut compression = ProtocolCompression::None;
         let mut session_token = None;
+        let mut intents = None;
 
         // Parse and map parameters from key-value to known variables.
         for (key, value) in params {
@@ -126,25 +230,103 @@ impl handshake::server::Callback for WebsocketHandshakeCallback {
                     "msgpack" => format = ProtocolFormat::Msgpack,
                     _ => {}
                 },
+                "compress" => match value {
+                    "zlib-stream" => compression = ProtocolCompression::ZlibStream,
+                    "zstd-stream" => compression = ProtocolCompression::ZstdStream,
+                    _ => {}
+                },
                 "token" => session_token = Some(value.into()),
+                "intents" => {
+                    if let Ok(value) = value.parse() {
+                        intents = Some(value);
+                    }
+                }
                 _ => {}
             }
         }
 
+        // Refuse the connection rather than silently falling back to no compression.
+        let config = match ProtocolConfiguration::from(
+            protocol_version,
+            format,
+            compression,
+            session_token,
+            intents,
+        ) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("Failed to create {compression:?} compressor: {err:?}");
+                let mut response = handshake::server::ErrorResponse::new(None);
+                *response.status_mut() =
+                    async_tungstenite::tungstenite::http::StatusCode::INTERNAL_SERVER_ERROR;
+                return Err(response);
+            }
+        };
+
         // Send configuration information back from this callback.
         // We have to use a channel as this function does not borrow mutably.
-        if self
-            .sender
-            .send(ProtocolConfiguration {
-                protocol_version,
-                format,
-                session_token,
-            })
-            .is_ok()
-        {
+        if self.sender.send(config).is_ok() {
             Ok(response)
         } else {
             Err(handshake::server::ErrorResponse::new(None))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use zstd::stream::raw::Operation;
+
+    use super::{Compressor, ProtocolCompression};
+
+    /// Compress a series of messages and feed them through a single decoder,
+    /// as a client would, checking each one comes out whole on its own
+    fn round_trip(compression: ProtocolCompression, mut decode: impl FnMut(&[u8]) -> Vec<u8>) {
+        let mut compressor = Compressor::new(compression)
+            .expect("compressor")
+            .expect("compression enabled");
+
+        for message in [
+            &b"{\"type\":\"Ready\"}"[..],
+            &b"{\"type\":\"Pong\",\"data\":0}"[..],
+            &b"{\"type\":\"Pong\",\"data\":0}"[..],
+        ] {
+            let compressed = compressor.compress(message).expect("compress");
+            assert!(!compressed.is_empty());
+            assert_eq!(decode(&compressed), message);
+        }
+    }
+
+    #[test]
+    fn zlib_stream_round_trip() {
+        let mut decoder = flate2::Decompress::new(true);
+        round_trip(ProtocolCompression::ZlibStream, |input| {
+            let mut output = Vec::with_capacity(1024);
+            decoder
+                .decompress_vec(input, &mut output, flate2::FlushDecompress::Sync)
+                .expect("decompress");
+            output
+        });
+    }
+
+    #[test]
+    fn zstd_stream_round_trip() {
+        let mut decoder = zstd::stream::raw::Decoder::new().expect("decoder");
+        round_trip(ProtocolCompression::ZstdStream, |input| {
+            let mut output = vec![0; 1024];
+            let status = decoder
+                .run_on_buffers(input, &mut output)
+                .expect("decompress");
+            assert_eq!(status.bytes_read, input.len());
+            output.truncate(status.bytes_written);
+            output
+        });
+    }
+
+    #[test]
+    fn no_compression() {
+        assert!(Compressor::new(ProtocolCompression::None)
+            .expect("compressor")
+            .is_none());
+    }
+}
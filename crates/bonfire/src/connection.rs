@@ -0,0 +1,439 @@
+use std::time::Instant;
+
+use async_channel::{Receiver, Sender};
+use async_std::task::{sleep, spawn};
+use authifier::AuthifierEvent;
+use futures::{pin_mut, select, FutureExt};
+use revolt_database::{
+    events::{
+        client::EventV1,
+        intents::{GatewayIntent, GatewayIntents, PRIVILEGED_INTENTS},
+        server::ClientMessage,
+    },
+    Database, User, UserHint,
+};
+use revolt_presence::{create_session, delete_session, heartbeat_session, set_session_idle};
+use revolt_result::{create_error, Error, Result};
+use serde::Serialize;
+
+use crate::commands;
+use crate::events::state::{State, SubscriptionStateChange};
+use crate::presence::{self, CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+use crate::router::get_router;
+
+/// Number of messages which may be queued for a client before we wait on it
+pub const OUTGOING_BUFFER: usize = 64;
+
+/// Message sent to the client
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Outgoing {
+    Event(EventV1),
+    Error(Error),
+}
+
+/// Tracks whether a client is still talking to us
+///
+/// Clients are expected to ping periodically, any message they send
+/// counts as a sign of life and keeps their presence session alive.
+struct Liveness {
+    last_seen: Instant,
+    last_heartbeat: Instant,
+}
+
+impl Liveness {
+    fn new(now: Instant) -> Liveness {
+        Liveness {
+            last_seen: now,
+            last_heartbeat: now,
+        }
+    }
+
+    /// Record a message from the client
+    ///
+    /// Returns whether the presence session is due to be kept alive.
+    fn seen(&mut self, now: Instant) -> bool {
+        self.last_seen = now;
+        if now.saturating_duration_since(self.last_heartbeat) >= HEARTBEAT_INTERVAL {
+            self.last_heartbeat = now;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Point at which the client is considered gone
+    fn deadline(&self) -> Instant {
+        self.last_seen + CLIENT_TIMEOUT
+    }
+}
+
+/// Authenticate a client using its session token
+///
+/// This also resolves which events the client will receive,
+/// bots must be granted privileged intents before they can use them.
+pub async fn authenticate(
+    db: &Database,
+    token: &str,
+    intents: Option<u32>,
+) -> Result<(User, String, GatewayIntents)> {
+    let (user, session_id) = User::from_token(db, token, UserHint::Any).await?;
+
+    let granted_privileged_intents = if user.bot.is_some() {
+        db.fetch_bot(&user.id)
+            .await?
+            .privileged_intents
+            .unwrap_or_default() as u32
+    } else {
+        PRIVILEGED_INTENTS
+    };
+
+    let intents = GatewayIntents::resolve(intents, granted_privileged_intents)
+        .map_err(|intents| create_error!(DisallowedIntents { intents }))?;
+
+    Ok((user, session_id, intents))
+}
+
+/// Serve an authenticated client until it goes away
+///
+/// Events are pushed to `outgoing` already filtered for this client,
+/// messages from the client are read from `incoming`. The client is
+/// considered gone once either channel has been closed.
+pub async fn serve(
+    db: &'static Database,
+    peer: &str,
+    mut state: State,
+    incoming: Receiver<ClientMessage>,
+    outgoing: Sender<Outgoing>,
+) {
+    let user_id = state.cache.user_id.clone();
+
+    // Notify client we have authenticated.
+    if outgoing
+        .send(Outgoing::Event(EventV1::Authenticated))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    // Download required data to local cache and send Ready payload.
+    let ready_payload = match state.generate_ready_payload(db).await {
+        Ok(ready_payload) => ready_payload,
+        Err(err) => {
+            sentry::capture_error(&err);
+            return;
+        }
+    };
+
+    if outgoing.send(Outgoing::Event(ready_payload)).await.is_err() {
+        return;
+    }
+
+    // Create presence session.
+    let (first_session, session_id) = create_session(&user_id, 0).await;
+
+    // If this was the first session, notify other users that we just went online.
+    if first_session {
+        state.broadcast_presence_change(true).await;
+    }
+
+    // New sessions are active, so the user may no longer be idle.
+    if set_session_idle(&user_id, session_id, false).await == Some(false) {
+        state.broadcast_idle_change(false).await;
+    }
+
+    run(db, peer, &mut state, session_id, incoming, &outgoing).await;
+
+    // Closing the session counts as it going idle, which may leave only idle sessions.
+    let now_idle = set_session_idle(&user_id, session_id, true).await;
+
+    // Clean up presence session.
+    let last_session = delete_session(&user_id, session_id).await;
+
+    // If this was the last session, notify other users that we just went offline.
+    if last_session {
+        presence::went_offline(db, &user_id).await;
+    } else if now_idle == Some(true) {
+        state.broadcast_idle_change(true).await;
+    }
+}
+
+/// Relay events to the client and handle its messages
+async fn run(
+    db: &'static Database,
+    peer: &str,
+    state: &mut State,
+    session_id: u32,
+    incoming: Receiver<ClientMessage>,
+    outgoing: &Sender<Outgoing>,
+) {
+    // Register with the process-wide event router.
+    let connection = get_router().connect();
+
+    // Commands wait on the database, so they are run alongside the connection.
+    let (commands, commands_r) = async_channel::bounded(OUTGOING_BUFFER);
+    spawn(commands::worker(
+        db,
+        state.cache.user_id.clone(),
+        state.session_id.clone(),
+        commands_r,
+        outgoing.clone(),
+    ));
+
+    let mut liveness = Liveness::new(Instant::now());
+
+    'out: loop {
+        // Check for state changes for subscriptions.
+        match state.apply_state().await {
+            SubscriptionStateChange::Reset => {
+                if let Err(err) = connection.unsubscribe_all().await {
+                    error!("Unsubscribe all failed: {err:?}");
+                    sentry::capture_error(&err);
+                    break 'out;
+                }
+
+                let subscribed = state.subscribed.read().await;
+                for id in subscribed.iter() {
+                    if let Err(err) = connection.subscribe(id.clone()).await {
+                        error!("Subscribe failed: {err:?}");
+                        sentry::capture_error(&err);
+                        break 'out;
+                    }
+                }
+
+                #[cfg(debug_assertions)]
+                info!("{peer} has reset their subscriptions");
+            }
+            SubscriptionStateChange::Change { add, remove } => {
+                for id in remove {
+                    #[cfg(debug_assertions)]
+                    info!("{peer} unsubscribing from {id}");
+
+                    if let Err(err) = connection.unsubscribe(id).await {
+                        error!("Unsubscribe failed: {err:?}");
+                        sentry::capture_error(&err);
+                        break 'out;
+                    }
+                }
+
+                for id in add {
+                    #[cfg(debug_assertions)]
+                    info!("{peer} subscribing to {id}");
+
+                    if let Err(err) = connection.subscribe(id).await {
+                        error!("Subscribe failed: {err:?}");
+                        sentry::capture_error(&err);
+                        break 'out;
+                    }
+                }
+            }
+            SubscriptionStateChange::None => {}
+        }
+
+        let timeout = liveness
+            .deadline()
+            .saturating_duration_since(Instant::now());
+        let t1 = connection.receiver.recv().fuse();
+        let t2 = incoming.recv().fuse();
+        let t3 = sleep(timeout).fuse();
+
+        pin_mut!(t1, t2, t3);
+
+        select! {
+            _ = t3 => {
+                // The client has stopped pinging us, the connection is likely dead.
+                info!("User {peer} timed out");
+                break 'out;
+            },
+            message = t2 => {
+                let Ok(message) = message else {
+                    break 'out;
+                };
+
+                // Keep our presence session alive, if it had expired
+                // then we need to let everyone know we're back.
+                if liveness.seen(Instant::now())
+                    && heartbeat_session(&state.cache.user_id, session_id).await
+                {
+                    state.broadcast_presence_change(true).await;
+                }
+
+                if !handle_message(db, state, session_id, outgoing, &commands, message).await {
+                    break 'out;
+                }
+            },
+            message = t1 => {
+                // Handle incoming events, the router will close the
+                // channel if the connection to the broker is interrupted.
+                let Ok((id, mut event)) = message else {
+                    break 'out;
+                };
+
+                // Events published to several of our topics, or published
+                // again by the outbox relay, should only be handled once.
+                if state.cache.seen_events.put(id.clone(), ()).is_some() {
+                    continue;
+                }
+
+                if let EventV1::Auth(auth) = &event {
+                    if let AuthifierEvent::DeleteSession { session_id, .. } = auth {
+                        if &state.session_id == session_id {
+                            event = EventV1::Logout;
+                        }
+                    } else if let AuthifierEvent::DeleteAllSessions {
+                        exclude_session_id, ..
+                    } = auth
+                    {
+                        if let Some(excluded) = exclude_session_id {
+                            if &state.session_id != excluded {
+                                event = EventV1::Logout;
+                            }
+                        } else {
+                            event = EventV1::Logout;
+                        }
+                    }
+                } else {
+                    let should_send = state.handle_incoming_event_v1(db, &id, &mut event).await;
+                    if !should_send {
+                        continue;
+                    }
+                }
+
+                let is_logout = matches!(event, EventV1::Logout);
+                if outgoing.send(Outgoing::Event(event)).await.is_err() {
+                    break 'out;
+                }
+
+                if is_logout {
+                    info!("User {peer} received log out event!");
+                    break 'out;
+                }
+            }
+        }
+    }
+
+    connection.close().await;
+}
+
+/// Handle a message sent by the client
+///
+/// Returns false if the client can no longer be written to.
+async fn handle_message(
+    db: &'static Database,
+    state: &mut State,
+    session_id: u32,
+    outgoing: &Sender<Outgoing>,
+    commands: &Sender<ClientMessage>,
+    message: ClientMessage,
+) -> bool {
+    let response = match message {
+        ClientMessage::BeginTyping { channel } => {
+            if state.subscribed.read().await.contains(&channel) {
+                EventV1::ChannelStartTyping {
+                    id: channel.clone(),
+                    user: state.cache.user_id.clone(),
+                }
+                .p(channel)
+                .await;
+            }
+
+            None
+        }
+        ClientMessage::EndTyping { channel } => {
+            if state.subscribed.read().await.contains(&channel) {
+                EventV1::ChannelStopTyping {
+                    id: channel.clone(),
+                    user: state.cache.user_id.clone(),
+                }
+                .p(channel)
+                .await;
+            }
+
+            None
+        }
+        ClientMessage::Subscribe { server_id } => {
+            // Member events are only delivered with the relevant intent,
+            // subscriptions are adjusted the next time we apply state.
+            if state.intents.has(GatewayIntent::ServerMembers) {
+                state.active_servers.lock().await.insert(server_id, ());
+            }
+
+            None
+        }
+        ClientMessage::SubscribeMemberList { channel, ranges } => {
+            match state.subscribe_member_list(db, channel, ranges).await {
+                Ok(event) => event.map(Outgoing::Event),
+                Err(err) => Some(Outgoing::Error(err)),
+            }
+        }
+        ClientMessage::Idle { idle } => {
+            if let Some(idle) = set_session_idle(&state.cache.user_id, session_id, idle).await {
+                state.broadcast_idle_change(idle).await;
+            }
+
+            None
+        }
+        ClientMessage::SetActivities { activities } => {
+            let result = match db.fetch_user(&state.cache.user_id).await {
+                Ok(mut user) => user.set_activities(db, activities).await,
+                Err(err) => Err(err),
+            };
+
+            result.err().map(Outgoing::Error)
+        }
+        ClientMessage::Ping { data, responded } => {
+            if responded.is_none() {
+                Some(Outgoing::Event(EventV1::Pong { data }))
+            } else {
+                None
+            }
+        }
+        command @ (ClientMessage::SendMessage { .. }
+        | ClientMessage::EditMessage { .. }
+        | ClientMessage::Ack { .. }
+        | ClientMessage::React { .. }) => {
+            // Results are sent to the client by the command worker.
+            if commands.send(command).await.is_err() {
+                return false;
+            }
+
+            None
+        }
+        ClientMessage::Authenticate { .. } => None,
+    };
+
+    match response {
+        Some(response) => outgoing.send(response).await.is_ok(),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Liveness;
+    use crate::presence::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+
+    #[test]
+    fn times_out_silent_clients() {
+        let start = Instant::now();
+        let mut liveness = Liveness::new(start);
+        assert_eq!(liveness.deadline(), start + CLIENT_TIMEOUT);
+
+        let later = start + Duration::from_secs(5);
+        liveness.seen(later);
+        assert_eq!(liveness.deadline(), later + CLIENT_TIMEOUT);
+    }
+
+    #[test]
+    fn throttles_heartbeats() {
+        let start = Instant::now();
+        let mut liveness = Liveness::new(start);
+        assert!(!liveness.seen(start + Duration::from_secs(1)));
+        assert!(liveness.seen(start + HEARTBEAT_INTERVAL));
+        assert!(!liveness.seen(start + HEARTBEAT_INTERVAL + Duration::from_secs(1)));
+        assert!(liveness.seen(start + HEARTBEAT_INTERVAL * 2));
+    }
+}
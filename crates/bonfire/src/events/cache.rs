@@ -0,0 +1,215 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, RwLock},
+};
+
+use once_cell::sync::Lazy;
+use revolt_database::{events::client::EventV1, Channel, Member, MemberCompositeKey, Server, User};
+
+/// Process-wide cache shared by every connection
+pub static GLOBAL_CACHE: Lazy<GlobalCache> = Lazy::new(GlobalCache::default);
+
+/// Cached object alongside the number of connections interested in it
+struct Entry<T> {
+    value: Arc<T>,
+    references: usize,
+}
+
+/// Reference-counted map of cached objects
+pub struct EntityMap<K, T>(RwLock<HashMap<K, Entry<T>>>);
+
+impl<K, T> Default for EntityMap<K, T> {
+    fn default() -> Self {
+        EntityMap(RwLock::new(HashMap::new()))
+    }
+}
+
+impl<K: Hash + Eq, T: Clone> EntityMap<K, T> {
+    /// Get an object by its key
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<T>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0
+            .read()
+            .expect("cache lock poisoned")
+            .get(key)
+            .map(|entry| entry.value.clone())
+    }
+
+    /// Register interest in an object, inserting it if it is not already cached
+    ///
+    /// If the object is already cached, the existing copy is kept as
+    /// it has been kept up to date by incoming events.
+    pub fn retain(&self, key: K, value: T) {
+        self.0
+            .write()
+            .expect("cache lock poisoned")
+            .entry(key)
+            .or_insert_with(|| Entry {
+                value: Arc::new(value),
+                references: 0,
+            })
+            .references += 1;
+    }
+
+    /// Release interest in an object, evicting it if nobody else is interested
+    pub fn release<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut map = self.0.write().expect("cache lock poisoned");
+        if let Some(entry) = map.get_mut(key) {
+            entry.references = entry.references.saturating_sub(1);
+            if entry.references == 0 {
+                map.remove(key);
+            }
+        }
+    }
+
+    /// Mutate a cached object in place
+    pub fn update<Q, F>(&self, key: &Q, f: F)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut T),
+    {
+        if let Some(entry) = self.0.write().expect("cache lock poisoned").get_mut(key) {
+            f(Arc::make_mut(&mut entry.value));
+        }
+    }
+}
+
+/// Global cache of objects which connections are interested in
+///
+/// Objects are inserted by connections as they learn about them and are
+/// evicted once the last interested connection releases them. Changes
+/// are applied exactly once per incoming event by the event router.
+#[derive(Default)]
+pub struct GlobalCache {
+    pub users: EntityMap<String, User>,
+    pub channels: EntityMap<String, Channel>,
+    pub members: EntityMap<MemberCompositeKey, Member>,
+    pub servers: EntityMap<String, Server>,
+}
+
+impl GlobalCache {
+    /// Apply changes described by an event to cached objects
+    pub fn apply(&self, event: &EventV1) {
+        match event {
+            EventV1::Bulk { v } => {
+                for event in v {
+                    self.apply(event);
+                }
+            }
+            EventV1::ChannelUpdate {
+                id, data, clear, ..
+            } => self.channels.update(id, |channel| {
+                for field in clear {
+                    channel.remove_field(&field.clone().into());
+                }
+
+                channel.apply_options(data.clone().into());
+            }),
+            EventV1::ServerUpdate {
+                id, data, clear, ..
+            } => self.servers.update(id, |server| {
+                for field in clear {
+                    server.remove_field(&field.clone().into());
+                }
+
+                server.apply_options(data.clone().into());
+            }),
+            EventV1::ServerRoleUpdate {
+                id,
+                role_id,
+                data,
+                clear,
+                ..
+            } => self.servers.update(id, |server| {
+                if let Some(role) = server.roles.get_mut(role_id) {
+                    for field in clear {
+                        role.remove_field(&field.clone().into());
+                    }
+
+                    role.apply_options(data.clone().into());
+                }
+            }),
+            EventV1::ServerRoleDelete { id, role_id } => self.servers.update(id, |server| {
+                server.roles.remove(role_id);
+            }),
+            EventV1::ServerMemberUpdate { id, data, clear } => {
+                self.members.update(&MemberCompositeKey::from(id.clone()), |member| {
+                    for field in clear {
+                        member.remove_field(&field.clone().into());
+                    }
+
+                    member.apply_options(data.clone().into());
+                })
+            }
+            EventV1::UserUpdate {
+                id, data, clear, ..
+            } => self.users.update(id, |user| {
+                for field in clear {
+                    user.remove_field(&field.clone().into());
+                }
+
+                user.apply_options(data.clone().into());
+            }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EntityMap;
+
+    #[test]
+    fn keeps_objects_while_referenced() {
+        let map: EntityMap<String, String> = EntityMap::default();
+        map.retain("id".to_string(), "first".to_string());
+        map.retain("id".to_string(), "second".to_string());
+
+        // The copy which was already cached is kept.
+        assert_eq!(map.get("id").as_deref(), Some(&"first".to_string()));
+
+        map.release("id");
+        assert!(map.get("id").is_some());
+
+        map.release("id");
+        assert!(map.get("id").is_none());
+
+        // Releasing more often than retained does nothing.
+        map.release("id");
+        map.retain("id".to_string(), "third".to_string());
+        assert_eq!(map.get("id").as_deref(), Some(&"third".to_string()));
+    }
+
+    #[test]
+    fn updates_apply_to_the_cached_copy() {
+        let map: EntityMap<String, Vec<u32>> = EntityMap::default();
+        map.retain("id".to_string(), vec![1]);
+        map.retain("id".to_string(), vec![]);
+
+        let before = map.get("id").unwrap();
+        map.update("id", |value| value.push(2));
+
+        // Existing handles are left untouched, new lookups see the change.
+        assert_eq!(*before, vec![1]);
+        assert_eq!(*map.get("id").unwrap(), vec![1, 2]);
+
+        map.release("id");
+        map.update("id", |value| value.push(3));
+        assert_eq!(*map.get("id").unwrap(), vec![1, 2, 3]);
+
+        map.release("id");
+        map.update("missing", |value| value.push(4));
+        assert!(map.get("id").is_none());
+        assert!(map.get("missing").is_none());
+    }
+}
@@ -1,15 +1,22 @@
 use std::collections::HashSet;
 
 use revolt_database::{
-    events::client::EventV1, util::permissions::DatabasePermissionQuery, Channel, Database, Member,
-    MemberCompositeKey, Presence, RelationshipStatus,
+    events::{client::EventV1, intents::GatewayIntent},
+    util::permissions::DatabasePermissionQuery,
+    Channel, Database, Member, MemberCompositeKey, RelationshipStatus, UnreadCounts,
 };
 use revolt_models::v0;
 use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
-use revolt_presence::filter_online;
-use revolt_result::Result;
+use revolt_presence::{filter_idle, filter_online};
+use revolt_result::{create_error, Result};
 
-use super::state::{Cache, State};
+use crate::presence;
+
+use super::{
+    cache::GLOBAL_CACHE,
+    member_list::MemberList,
+    state::{Cache, State},
+};
 
 /// Cache Manager
 impl Cache {
@@ -17,18 +24,17 @@ impl Cache {
     pub async fn can_view_channel(&self, db: &Database, channel: &Channel) -> bool {
         match &channel {
             Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
-                let member = self.members.get(server);
-                let server = self.servers.get(server);
-                let mut query =
-                    DatabasePermissionQuery::new(db, self.users.get(&self.user_id).unwrap())
-                        .channel(channel);
+                let member = self.get_member(server);
+                let server = self.get_server(server);
+                let user = self.get_self();
+                let mut query = DatabasePermissionQuery::new(db, &user).channel(channel);
                 // let mut perms = perms(self.users.get(&self.user_id).unwrap()).channel(channel);
 
-                if let Some(member) = member {
+                if let Some(member) = &member {
                     query = query.member(member);
                 }
 
-                if let Some(server) = server {
+                if let Some(server) = &server {
                     query = query.server(server);
                 }
 
@@ -58,7 +64,7 @@ impl Cache {
 
     /// Check whether we can subscribe to another user
     pub fn can_subscribe_to_user(&self, user_id: &str) -> bool {
-        if let Some(user) = self.users.get(&self.user_id) {
+        if let Some(user) = self.get_user(&self.user_id) {
             match user.relationship_with(user_id) {
                 RelationshipStatus::Friend
                 | RelationshipStatus::Incoming
@@ -66,8 +72,12 @@ impl Cache {
                 | RelationshipStatus::User => true,
                 _ => {
                     let user_id = &user_id.to_string();
-                    for channel in self.channels.values() {
-                        match channel {
+                    for channel_id in &self.channels {
+                        let Some(channel) = GLOBAL_CACHE.channels.get(channel_id) else {
+                            continue;
+                        };
+
+                        match &*channel {
                             Channel::DirectMessage { recipients, .. }
                             | Channel::Group { recipients, .. } => {
                                 if recipients.contains(user_id) {
@@ -103,15 +113,15 @@ impl State {
 
         // Fetch all memberships with their corresponding servers.
         let members: Vec<Member> = db.fetch_all_memberships(&user.id).await?;
-        self.cache.members = members
-            .iter()
-            .cloned()
-            .map(|x| (x.id.server.clone(), x))
-            .collect();
+        for member in &members {
+            self.cache.insert_member(member.clone());
+        }
 
         let server_ids: Vec<String> = members.iter().map(|x| x.id.server.clone()).collect();
         let servers = db.fetch_servers(&server_ids).await?;
-        self.cache.servers = servers.iter().cloned().map(|x| (x.id.clone(), x)).collect();
+        for server in &servers {
+            self.cache.insert_server(server.clone());
+        }
 
         // Collect channel ids from servers.
         let mut channel_ids = vec![];
@@ -137,7 +147,9 @@ impl State {
         }
 
         // Fetch presence data for known users.
-        let online_ids = filter_online(&user_ids.iter().cloned().collect::<Vec<String>>()).await;
+        let user_ids: Vec<String> = user_ids.into_iter().collect();
+        let online_ids = filter_online(&user_ids).await;
+        let idle_ids = filter_idle(&user_ids).await;
 
         // Fetch user data.
         let users = db
@@ -159,23 +171,39 @@ impl State {
             )
             .await?;
 
-        // Copy data into local state cache.
-        self.cache.users = users.iter().cloned().map(|x| (x.id.clone(), x)).collect();
-        self.cache
-            .users
-            .insert(self.cache.user_id.clone(), user.clone());
-        self.cache.channels = channels
-            .iter()
-            .cloned()
-            .map(|x| (x.id().to_string(), x))
-            .collect();
+        // Register interest in data with the global cache.
+        for user in &users {
+            self.cache.insert_user(user.clone());
+        }
+
+        for channel in &channels {
+            self.cache.insert_channel(channel.clone());
+        }
+
+        // Count unread messages in every channel we can see.
+        let unreads = if self.cache.is_bot {
+            None
+        } else {
+            let unreads = db.fetch_unreads(&user.id).await?;
+            let counts = UnreadCounts::fetch(db, &user.id, &channels, &unreads).await?;
+            let summary = counts.summarise(unreads);
+            self.unreads = Some(counts);
+            Some(summary)
+        };
 
         // Make all users appear from our perspective.
+        let has_presence = self.intents.has(GatewayIntent::Presence);
         let mut users: Vec<v0::User> = users
             .into_iter()
             .map(|other_user| {
-                let is_online = online_ids.contains(&other_user.id);
-                other_user.into_known(&user, is_online)
+                let is_online = has_presence && online_ids.contains(&other_user.id);
+                let is_idle = has_presence && idle_ids.contains(&other_user.id);
+                let mut other_user = other_user.into_known(&user, is_online, is_idle);
+                if !has_presence {
+                    other_user.status = None;
+                }
+
+                other_user
             })
             .collect();
 
@@ -193,7 +221,7 @@ impl State {
         for server in &servers {
             self.insert_subscription(server.id.clone()).await;
 
-            if self.cache.is_bot {
+            if self.cache.is_bot && self.intents.has(GatewayIntent::ServerMembers) {
                 self.insert_subscription(format!("{}u", server.id)).await;
             }
         }
@@ -202,30 +230,52 @@ impl State {
             self.insert_subscription(channel.id().to_string()).await;
         }
 
+        // Trim the payload down to what this connection requested.
+        let (servers, members, emojis) = if self.intents.has(GatewayIntent::Servers) {
+            (servers, members, emojis)
+        } else {
+            (vec![], vec![], vec![])
+        };
+
+        let channels = channels
+            .into_iter()
+            .filter(|channel| match channel {
+                Channel::TextChannel { .. } | Channel::VoiceChannel { .. } => {
+                    self.intents.has(GatewayIntent::Servers)
+                }
+                _ => self.intents.has(GatewayIntent::DirectMessages),
+            })
+            .collect::<Vec<Channel>>();
+
         Ok(EventV1::Ready {
             users,
             servers: servers.into_iter().map(Into::into).collect(),
             channels: channels.into_iter().map(Into::into).collect(),
             members: members.into_iter().map(Into::into).collect(),
             emojis: emojis.into_iter().map(Into::into).collect(),
+            unreads,
         })
     }
 
     /// Re-determine the currently accessible server channels
     pub async fn recalculate_server(&mut self, db: &Database, id: &str, event: &mut EventV1) {
-        if let Some(server) = self.cache.servers.get(id) {
+        if let Some(server) = self.cache.get_server(id) {
             let mut channel_ids = HashSet::new();
             let mut added_channels = vec![];
             let mut removed_channels = vec![];
 
             let id = &id.to_string();
-            for (channel_id, channel) in &self.cache.channels {
-                match channel {
+            for channel_id in &self.cache.channels {
+                let Some(channel) = GLOBAL_CACHE.channels.get(channel_id) else {
+                    continue;
+                };
+
+                match &*channel {
                     Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
                         if server == id {
                             channel_ids.insert(channel_id.clone());
 
-                            if self.cache.can_view_channel(db, channel).await {
+                            if self.cache.can_view_channel(db, &channel).await {
                                 added_channels.push(channel_id.clone());
                             } else {
                                 removed_channels.push(channel_id.clone());
@@ -246,34 +296,35 @@ impl State {
 
             for id in removed_channels {
                 self.remove_subscription(&id).await;
-                self.cache.channels.remove(&id);
+                self.cache.remove_channel(&id);
 
                 bulk_events.push(EventV1::ChannelDelete { id });
             }
 
-            // * NOTE: currently all channels should be cached
-            // * provided that a server was loaded from payload
-            let unknowns = known_ids
-                .difference(&channel_ids)
-                .cloned()
-                .collect::<Vec<String>>();
+            // Channels we could not previously view may already
+            // be cached on behalf of other connections.
+            let mut channels = vec![];
+            let mut unknowns = vec![];
+            for id in known_ids.difference(&channel_ids) {
+                match GLOBAL_CACHE.channels.get(id) {
+                    Some(channel) => channels.push((*channel).clone()),
+                    None => unknowns.push(id.clone()),
+                }
+            }
 
             if !unknowns.is_empty() {
-                if let Ok(channels) = db.fetch_channels(&unknowns).await {
-                    let viewable_channels =
-                        self.cache.filter_accessible_channels(db, channels).await;
-
-                    for channel in viewable_channels {
-                        self.cache
-                            .channels
-                            .insert(channel.id().to_string(), channel.clone());
-
-                        self.insert_subscription(channel.id().to_string()).await;
-                        bulk_events.push(EventV1::ChannelCreate(channel.into()));
-                    }
+                if let Ok(mut fetched) = db.fetch_channels(&unknowns).await {
+                    channels.append(&mut fetched);
                 }
             }
 
+            let viewable_channels = self.cache.filter_accessible_channels(db, channels).await;
+            for channel in viewable_channels {
+                self.insert_subscription(channel.id().to_string()).await;
+                bulk_events.push(EventV1::ChannelCreate(channel.clone().into()));
+                self.cache.insert_channel(channel);
+            }
+
             if !bulk_events.is_empty() {
                 let mut new_event = EventV1::Bulk { v: bulk_events };
                 std::mem::swap(&mut new_event, event);
@@ -285,33 +336,178 @@ impl State {
         }
     }
 
-    /// Push presence change to the user and all associated server topics
-    pub async fn broadcast_presence_change(&self, target: bool) {
-        if if let Some(status) = &self.cache.users.get(&self.cache.user_id).unwrap().status {
-            status.presence != Some(Presence::Invisible)
-        } else {
-            true
-        } {
-            let event = EventV1::UserUpdate {
-                id: self.cache.user_id.clone(),
-                data: v0::PartialUser {
-                    online: Some(target),
-                    ..Default::default()
-                },
-                clear: vec![],
-                event_id: Some(ulid::Ulid::new().to_string()),
-            };
+    /// Subscribe to a range of the member list for a channel
+    ///
+    /// Only one member list may be open at a time, subscribing to
+    /// a different channel will replace the existing member list.
+    /// Providing no ranges will close the member list.
+    pub async fn subscribe_member_list(
+        &mut self,
+        db: &Database,
+        channel_id: String,
+        ranges: Vec<(usize, usize)>,
+    ) -> Result<Option<EventV1>> {
+        if ranges.is_empty() {
+            self.member_list = None;
+            return Ok(None);
+        }
 
-            for server in self.cache.servers.keys() {
-                event.clone().p(server.clone()).await;
-            }
+        if !self.intents.has(GatewayIntent::ServerMembers) {
+            return Err(create_error!(InvalidOperation));
+        }
+
+        MemberList::validate_ranges(&ranges)?;
 
-            event.p(self.cache.user_id.clone()).await;
+        let perspective = self.cache.get_self();
+        if let Some(member_list) = &mut self.member_list {
+            if member_list.channel_id == channel_id {
+                return Ok(Some(member_list.set_ranges(&perspective, ranges).await));
+            }
         }
+
+        let channel = self
+            .cache
+            .get_channel(&channel_id)
+            .ok_or_else(|| create_error!(UnknownChannel))?;
+
+        let server = match &*channel {
+            Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => self
+                .cache
+                .get_server(server)
+                .ok_or_else(|| create_error!(UnknownServer))?,
+            _ => return Err(create_error!(InvalidOperation)),
+        };
+
+        let (member_list, event) =
+            MemberList::new(db, &perspective, &server, &channel, ranges).await?;
+
+        self.insert_subscription(format!("{}u", member_list.server_id))
+            .await;
+        self.member_list = Some(member_list);
+
+        Ok(Some(event))
+    }
+
+    /// Apply an incoming event to the open member list
+    async fn update_member_list(
+        &mut self,
+        db: &Database,
+        id: &str,
+        event: &EventV1,
+    ) -> Option<EventV1> {
+        let member_list = self.member_list.as_mut()?;
+        let (Some(server), Some(channel)) = (
+            self.cache.get_server(&member_list.server_id),
+            self.cache.get_channel(&member_list.channel_id),
+        ) else {
+            // We've lost access to the server or channel.
+            self.member_list = None;
+            return None;
+        };
+
+        let perspective = self.cache.get_self();
+        member_list
+            .handle_event(db, &perspective, &server, &channel, id, event)
+            .await
+    }
+
+    /// Keep unread counts up to date with new messages and acknowledgements
+    async fn update_unreads(&mut self, db: &Database, event: &EventV1) -> Option<EventV1> {
+        let unreads = self.unreads.as_mut()?;
+        let channel = match event {
+            EventV1::Message(message) => {
+                if message.author == self.cache.user_id {
+                    return None;
+                }
+
+                let channel = self.cache.get_channel(&message.channel)?;
+                let mentioned = message
+                    .mentions
+                    .as_ref()
+                    .is_some_and(|mentions| mentions.contains(&self.cache.user_id));
+
+                unreads.add_message(&channel, &message.id, mentioned);
+                channel
+            }
+            EventV1::ChannelAck {
+                id,
+                user,
+                message_id,
+            } if user == &self.cache.user_id => {
+                let channel = self.cache.get_channel(id)?;
+                if let Err(err) = unreads.acknowledge(db, &channel, message_id).await {
+                    error!("Failed to count unread messages in {id}: {err:?}");
+                    return None;
+                }
+
+                channel
+            }
+            EventV1::MessageDelete { channel: id, .. }
+            | EventV1::BulkMessageDelete { channel: id, .. } => {
+                let ids = match event {
+                    EventV1::MessageDelete { id: message, .. } => std::slice::from_ref(message),
+                    EventV1::BulkMessageDelete { ids, .. } => ids.as_slice(),
+                    _ => return None,
+                };
+
+                // Deleting messages which were already read changes nothing.
+                let channel = self.cache.get_channel(id)?;
+                match unreads.delete_messages(db, &channel, ids).await {
+                    Ok(true) => channel,
+                    Ok(false) => return None,
+                    Err(err) => {
+                        error!("Failed to count unread messages in {id}: {err:?}");
+                        return None;
+                    }
+                }
+            }
+            EventV1::NotificationSettingsUpdate { id, .. } if id == &self.cache.user_id => {
+                // Muting a channel takes it out of its server's count from now on.
+                if let Ok(settings) = db.fetch_notification_settings(id).await {
+                    unreads.set_settings(settings);
+                }
+
+                return None;
+            }
+            EventV1::ChannelDelete { id } => {
+                unreads.remove_channel(id);
+                return None;
+            }
+            _ => return None,
+        };
+
+        let count = unreads.channel(&channel.id());
+        Some(EventV1::ChannelUnreadUpdate {
+            id: channel.id(),
+            count: count.count as u32,
+            mentions: count.mentions as u32,
+            server: match &*channel {
+                Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
+                    Some(unreads.server(server))
+                }
+                _ => None,
+            },
+        })
+    }
+
+    /// Push presence change to the user and all associated server topics
+    pub async fn broadcast_presence_change(&self, target: bool) {
+        presence::broadcast_presence_change(&self.cache.get_self(), &self.cache.servers, target)
+            .await;
+    }
+
+    /// Push change in whether we are idle to the user and all associated server topics
+    pub async fn broadcast_idle_change(&self, idle: bool) {
+        presence::broadcast_idle_change(&self.cache.get_self(), &self.cache.servers, idle).await;
     }
 
     /// Handle an incoming event for protocol version 1
-    pub async fn handle_incoming_event_v1(&mut self, db: &Database, event: &mut EventV1) -> bool {
+    pub async fn handle_incoming_event_v1(
+        &mut self,
+        db: &Database,
+        id: &str,
+        event: &mut EventV1,
+    ) -> bool {
         /* Superseded by private topics.
           if match event {
             EventV1::UserRelationship { id, .. }
@@ -330,6 +526,10 @@ impl State {
             return false;
         }*/
 
+        // Determine whether this connection wants the event before
+        // the cache is updated, but still process it regardless.
+        let is_requested = self.is_event_requested(event);
+
         // An event may trigger recalculation of an entire server's permission.
         // Keep track of whether we need to do anything.
         let mut queue_server = None;
@@ -340,49 +540,38 @@ impl State {
 
         match event {
             EventV1::ChannelCreate(channel) => {
-                let id = channel.id().to_string();
-                self.insert_subscription(id.clone()).await;
-                self.cache.channels.insert(id, channel.clone().into());
+                self.insert_subscription(channel.id().to_string()).await;
+                self.cache.insert_channel(channel.clone().into());
             }
-            EventV1::ChannelUpdate {
-                id, data, clear, ..
-            } => {
-                let could_view: bool = if let Some(channel) = self.cache.channels.get(id) {
-                    self.cache.can_view_channel(db, channel).await
-                } else {
-                    false
+            EventV1::ChannelUpdate { id, .. } => {
+                // The global cache has already applied this update,
+                // we only track channels which we were able to view.
+                let id = id.clone();
+                let could_view = self.cache.channels.contains(&id);
+
+                let channel = match GLOBAL_CACHE.channels.get(&id) {
+                    Some(channel) => Some((*channel).clone()),
+                    None => db.fetch_channel(&id).await.ok(),
                 };
 
-                if let Some(channel) = self.cache.channels.get_mut(id) {
-                    for field in clear {
-                        channel.remove_field(&field.clone().into());
-                    }
-
-                    channel.apply_options(data.clone().into());
-                }
-
-                if !self.cache.channels.contains_key(id) {
-                    if let Ok(channel) = db.fetch_channel(id).await {
-                        self.cache.channels.insert(id.clone(), channel);
-                    }
-                }
-
-                if let Some(channel) = self.cache.channels.get(id) {
-                    let can_view = self.cache.can_view_channel(db, channel).await;
+                if let Some(channel) = channel {
+                    let can_view = self.cache.can_view_channel(db, &channel).await;
                     if could_view != can_view {
                         if can_view {
-                            queue_add = Some(id.clone());
                             *event = EventV1::ChannelCreate(channel.clone().into());
+                            self.cache.insert_channel(channel);
+                            queue_add = Some(id);
                         } else {
-                            queue_remove = Some(id.clone());
                             *event = EventV1::ChannelDelete { id: id.clone() };
+                            self.cache.remove_channel(&id);
+                            queue_remove = Some(id);
                         }
                     }
                 }
             }
             EventV1::ChannelDelete { id } => {
                 self.remove_subscription(id).await;
-                self.cache.channels.remove(id);
+                self.cache.remove_channel(id);
             }
             EventV1::ChannelGroupJoin { user, .. } => {
                 self.insert_subscription(user.clone()).await;
@@ -403,39 +592,26 @@ impl State {
             } => {
                 self.insert_subscription(id.clone()).await;
 
-                if self.cache.is_bot {
+                if self.cache.is_bot && self.intents.has(GatewayIntent::ServerMembers) {
                     self.insert_subscription(format!("{}u", id)).await;
                 }
 
-                self.cache.servers.insert(id.clone(), server.clone().into());
-                let member = Member {
+                self.cache.insert_server(server.clone().into());
+                self.cache.insert_member(Member {
                     id: MemberCompositeKey {
                         server: server.id.clone(),
                         user: self.cache.user_id.clone(),
                     },
                     ..Default::default()
-                };
-                self.cache.members.insert(id.clone(), member);
+                });
 
                 for channel in channels {
-                    self.cache
-                        .channels
-                        .insert(channel.id().to_string(), channel.clone().into());
+                    self.cache.insert_channel(channel.clone().into());
                 }
 
                 queue_server = Some(id.clone());
             }
-            EventV1::ServerUpdate {
-                id, data, clear, ..
-            } => {
-                if let Some(server) = self.cache.servers.get_mut(id) {
-                    for field in clear {
-                        server.remove_field(&field.clone().into());
-                    }
-
-                    server.apply_options(data.clone().into());
-                }
-
+            EventV1::ServerUpdate { id, data, .. } => {
                 if data.default_permissions.is_some() {
                     queue_server = Some(id.clone());
                 }
@@ -447,60 +623,38 @@ impl State {
                 if user == &self.cache.user_id {
                     self.remove_subscription(id).await;
 
-                    if let Some(server) = self.cache.servers.remove(id) {
+                    if let Some(server) = self.cache.remove_server(id) {
                         for channel in &server.channels {
                             self.remove_subscription(channel).await;
-                            self.cache.channels.remove(channel);
+                            self.cache.remove_channel(channel);
                         }
                     }
-                    self.cache.members.remove(id);
+                    self.cache.remove_member(id);
                 }
             }
             EventV1::ServerDelete { id } => {
                 self.remove_subscription(id).await;
 
-                if let Some(server) = self.cache.servers.remove(id) {
+                if let Some(server) = self.cache.remove_server(id) {
                     for channel in &server.channels {
                         self.remove_subscription(channel).await;
-                        self.cache.channels.remove(channel);
+                        self.cache.remove_channel(channel);
                     }
                 }
-                self.cache.members.remove(id);
+                self.cache.remove_member(id);
             }
             EventV1::ServerMemberUpdate { id, data, clear } => {
                 if id.user == self.cache.user_id {
-                    if let Some(member) = self.cache.members.get_mut(&id.server) {
-                        for field in &clear.clone() {
-                            member.remove_field(&field.clone().into());
-                        }
-
-                        member.apply_options(data.clone().into());
-                    }
-
                     if data.roles.is_some() || clear.contains(&v0::FieldsMember::Roles) {
                         queue_server = Some(id.server.clone());
                     }
                 }
             }
             EventV1::ServerRoleUpdate {
-                id,
-                role_id,
-                data,
-                clear,
-                ..
+                id, role_id, data, ..
             } => {
-                if let Some(server) = self.cache.servers.get_mut(id) {
-                    if let Some(role) = server.roles.get_mut(role_id) {
-                        for field in &clear.clone() {
-                            role.remove_field(&field.clone().into());
-                        }
-
-                        role.apply_options(data.clone().into());
-                    }
-                }
-
                 if data.rank.is_some() || data.permissions.is_some() {
-                    if let Some(member) = self.cache.members.get(id) {
+                    if let Some(member) = self.cache.get_member(id) {
                         if member.roles.contains(role_id) {
                             queue_server = Some(id.clone());
                         }
@@ -508,30 +662,15 @@ impl State {
                 }
             }
             EventV1::ServerRoleDelete { id, role_id } => {
-                if let Some(server) = self.cache.servers.get_mut(id) {
-                    server.roles.remove(role_id);
-                }
-
-                if let Some(member) = self.cache.members.get(id) {
+                if let Some(member) = self.cache.get_member(id) {
                     if member.roles.contains(role_id) {
                         queue_server = Some(id.clone());
                     }
                 }
             }
 
-            EventV1::UserUpdate { event_id, .. } => {
-                if let Some(id) = event_id {
-                    if self.cache.seen_events.contains(id) {
-                        return false;
-                    }
-
-                    self.cache.seen_events.put(id.to_string(), ());
-                }
-
-                *event_id = None;
-            }
             EventV1::UserRelationship { id, user, .. } => {
-                self.cache.users.insert(id.clone(), user.clone().into());
+                self.cache.insert_user(user.clone().into());
 
                 if self.cache.can_subscribe_to_user(id) {
                     self.insert_subscription(id.clone()).await;
@@ -546,9 +685,7 @@ impl State {
                 if let Some(user) = &mut message.user {
                     user.relationship = self
                         .cache
-                        .users
-                        .get(&self.cache.user_id)
-                        .expect("missing self?")
+                        .get_self()
                         .relationship_with(&message.author)
                         .into();
                 }
@@ -557,6 +694,10 @@ impl State {
             _ => {}
         }
 
+        // Keep the open member list and unread counts in sync.
+        let member_list_update = self.update_member_list(db, id, event).await;
+        let unread_update = self.update_unreads(db, event).await;
+
         // Calculate server permissions if requested.
         if let Some(server_id) = queue_server {
             self.recalculate_server(db, &server_id, event).await;
@@ -571,6 +712,30 @@ impl State {
             self.remove_subscription(&id).await;
         }
 
-        true
+        // Send member list and unread changes even if the event itself was not requested.
+        let mut updates: Vec<EventV1> = [member_list_update, unread_update]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if !updates.is_empty() {
+            if !is_requested {
+                *event = if updates.len() == 1 {
+                    updates.remove(0)
+                } else {
+                    EventV1::Bulk { v: updates }
+                };
+            } else if let EventV1::Bulk { v } = event {
+                v.append(&mut updates);
+            } else {
+                let original = std::mem::replace(event, EventV1::Bulk { v: vec![] });
+                updates.insert(0, original);
+                *event = EventV1::Bulk { v: updates };
+            }
+
+            return true;
+        }
+
+        is_requested
     }
 }
@@ -0,0 +1,127 @@
+use revolt_database::{
+    events::{
+        client::EventV1,
+        intents::{GatewayIntent, GatewayIntents},
+    },
+    Channel,
+};
+use revolt_models::v0;
+
+use super::state::State;
+
+/// Intent Filter
+impl State {
+    /// Check whether an event in a given channel was requested by this connection
+    fn is_channel_event_requested(
+        &self,
+        channel_id: &str,
+        server_intent: GatewayIntent,
+        direct_intent: GatewayIntent,
+    ) -> bool {
+        match self.cache.get_channel(channel_id).as_deref() {
+            Some(Channel::TextChannel { .. } | Channel::VoiceChannel { .. }) => {
+                self.intents.has(server_intent)
+            }
+            Some(_) => self.intents.has(direct_intent),
+            None => self.intents.has(server_intent) || self.intents.has(direct_intent),
+        }
+    }
+
+    /// Check whether an event should be sent to this connection
+    ///
+    /// This may strip fields the connection did not ask for, such as presence
+    /// information on user updates, and must be called before the cache has
+    /// been updated so that deleted objects can still be classified.
+    pub fn is_event_requested(&self, event: &mut EventV1) -> bool {
+        let intents: GatewayIntents = self.intents;
+
+        match event {
+            EventV1::Bulk { v } => {
+                v.retain_mut(|event| self.is_event_requested(event));
+                !v.is_empty()
+            }
+
+            EventV1::Message(v0::Message { channel, .. })
+            | EventV1::MessageUpdate { channel, .. }
+            | EventV1::MessageAppend { channel, .. }
+            | EventV1::MessageDelete { channel, .. }
+            | EventV1::BulkMessageDelete { channel, .. } => self.is_channel_event_requested(
+                channel,
+                GatewayIntent::ServerMessages,
+                GatewayIntent::DirectMessages,
+            ),
+            EventV1::MessageReact { .. }
+            | EventV1::MessageUnreact { .. }
+            | EventV1::MessageRemoveReaction { .. } => {
+                intents.has(GatewayIntent::MessageReactions)
+            }
+
+            EventV1::ServerCreate { .. }
+            | EventV1::ServerUpdate { .. }
+            | EventV1::ServerDelete { .. }
+            | EventV1::ServerRoleUpdate { .. }
+            | EventV1::ServerRoleDelete { .. }
+            | EventV1::EmojiCreate(_)
+            | EventV1::EmojiDelete { .. } => intents.has(GatewayIntent::Servers),
+            EventV1::ServerMemberJoin { user, .. } | EventV1::ServerMemberLeave { user, .. } => {
+                if user == &self.cache.user_id {
+                    intents.has(GatewayIntent::Servers)
+                } else {
+                    intents.has(GatewayIntent::ServerMembers)
+                }
+            }
+            EventV1::ServerMemberUpdate { id, .. } => {
+                if id.user == self.cache.user_id {
+                    intents.has(GatewayIntent::Servers)
+                } else {
+                    intents.has(GatewayIntent::ServerMembers)
+                }
+            }
+
+            EventV1::ChannelCreate(channel) => match channel {
+                v0::Channel::TextChannel { .. } | v0::Channel::VoiceChannel { .. } => {
+                    intents.has(GatewayIntent::Servers)
+                }
+                _ => intents.has(GatewayIntent::DirectMessages),
+            },
+            EventV1::ChannelUpdate { id, .. } | EventV1::ChannelDelete { id } => self
+                .is_channel_event_requested(
+                    id,
+                    GatewayIntent::Servers,
+                    GatewayIntent::DirectMessages,
+                ),
+            EventV1::ChannelGroupJoin { .. } | EventV1::ChannelGroupLeave { .. } => {
+                intents.has(GatewayIntent::DirectMessages)
+            }
+            EventV1::ChannelStartTyping { .. } | EventV1::ChannelStopTyping { .. } => {
+                intents.has(GatewayIntent::Typing)
+            }
+
+            EventV1::WebhookCreate(_)
+            | EventV1::WebhookUpdate { .. }
+            | EventV1::WebhookDelete { .. } => intents.has(GatewayIntent::Webhooks),
+
+            EventV1::UserUpdate {
+                id, data, clear, ..
+            } => {
+                if id == &self.cache.user_id || intents.has(GatewayIntent::Presence) {
+                    return true;
+                }
+
+                // Strip presence information but keep any other changes.
+                data.online = None;
+                data.status = None;
+                clear.retain(|field| {
+                    !matches!(
+                        field,
+                        v0::FieldsUser::StatusText | v0::FieldsUser::StatusPresence
+                    )
+                });
+
+                data != &v0::PartialUser::default() || !clear.is_empty()
+            }
+
+            _ => true,
+        }
+    }
+}
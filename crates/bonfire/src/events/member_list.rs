@@ -0,0 +1,1225 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Weak},
+};
+
+use async_std::sync::Mutex;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use revolt_database::{
+    events::client::{EventV1, MemberListGroup, MemberListItem, MemberListOp},
+    util::permissions::DatabasePermissionQuery,
+    Channel, Database, Member, Presence, Server, User,
+};
+use revolt_models::v0::FieldsChannel;
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_presence::{filter_idle, filter_online, is_idle, is_online};
+use revolt_result::{create_error, Result};
+
+/// Process-wide member lists, shared by every connection viewing the same channel
+pub static MEMBER_LISTS: Lazy<MemberLists> = Lazy::new(MemberLists::default);
+
+/// Maximum number of ranges a connection may subscribe to
+const MAX_RANGES: usize = 5;
+
+/// Maximum number of items in a single range
+const MAX_RANGE_SIZE: usize = 100;
+
+/// Number of recent changes kept for connections which have fallen behind
+const HISTORY_SIZE: usize = 256;
+
+/// Number of recent event ids to remember, so each event is applied once
+const SEEN_EVENTS: usize = 1_000;
+
+/// Number of operations past which connections are told to sync again instead
+const MAX_OPS: usize = MAX_RANGES * MAX_RANGE_SIZE;
+
+/// Group a member is sorted into
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum GroupKey {
+    /// Hoisted role, ordered by its rank and then its id
+    Role(i64, String),
+    /// Online members without a hoisted role
+    Online,
+    /// Offline members
+    Offline,
+}
+
+impl GroupKey {
+    /// Id of the group as presented to the client
+    fn id(&self) -> String {
+        match self {
+            GroupKey::Role(_, id) => id.clone(),
+            GroupKey::Online => "online".to_string(),
+            GroupKey::Offline => "offline".to_string(),
+        }
+    }
+}
+
+/// Sortable position of a single row in the member list
+///
+/// Group headers have no member and therefore sort before their members.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RowKey {
+    group: GroupKey,
+    /// Sort name and user id
+    member: Option<(String, String)>,
+}
+
+/// Row as it was when a change was made, rendered for each connection as it is sent
+#[derive(Clone)]
+enum Item {
+    Group(MemberListGroup),
+    Member {
+        member: Member,
+        user: User,
+        online: bool,
+        idle: bool,
+    },
+}
+
+impl Item {
+    /// Present the row from the perspective of a given user
+    fn render(&self, perspective: &User) -> MemberListItem {
+        match self {
+            Item::Group(group) => MemberListItem::Group(group.clone()),
+            Item::Member {
+                member,
+                user,
+                online,
+                idle,
+            } => MemberListItem::Member {
+                member: member.clone().into(),
+                user: user.clone().into_known(perspective, *online, *idle),
+            },
+        }
+    }
+}
+
+/// Single change to the list, relative to the list as it was just before
+#[derive(Clone)]
+enum Op {
+    Insert { index: usize, item: Item },
+    Update { index: usize, item: Item },
+    Delete { index: usize },
+}
+
+impl Op {
+    /// Present the change from the perspective of a given user
+    fn render(&self, perspective: &User) -> MemberListOp {
+        match self {
+            Op::Insert { index, item } => MemberListOp::Insert {
+                index: *index,
+                item: item.render(perspective),
+            },
+            Op::Update { index, item } => MemberListOp::Update {
+                index: *index,
+                item: item.render(perspective),
+            },
+            Op::Delete { index } => MemberListOp::Delete { index: *index },
+        }
+    }
+}
+
+/// Changes made to the list by a single event
+enum Change {
+    Ops(Vec<Op>),
+    /// Too much changed at once, connections should sync their ranges again
+    Resync,
+}
+
+/// Everything which decides whether a member can see the channel
+#[derive(PartialEq, Eq, Hash)]
+struct ViewKey {
+    privileged: bool,
+    owner: bool,
+    timed_out: bool,
+    roles: Vec<String>,
+}
+
+/// Registry of member lists which are currently open
+///
+/// Lists are loaded by the first connection to open them and are
+/// dropped along with the last connection which has them open.
+#[derive(Default)]
+pub struct MemberLists(std::sync::Mutex<HashMap<String, Weak<Mutex<SharedMemberList>>>>);
+
+impl MemberLists {
+    /// Get the list for a channel, creating an empty one if nobody has it open
+    fn get_or_create(&self, server_id: &str, channel_id: &str) -> Arc<Mutex<SharedMemberList>> {
+        let mut lists = self.0.lock().expect("member list lock poisoned");
+        if let Some(list) = lists.get(channel_id).and_then(Weak::upgrade) {
+            return list;
+        }
+
+        let list = Arc::new(Mutex::new(SharedMemberList::new(server_id, channel_id)));
+        lists.insert(channel_id.to_string(), Arc::downgrade(&list));
+        list
+    }
+
+    /// Forget a list if the given reference is the last one
+    fn release(&self, channel_id: &str, list: &Arc<Mutex<SharedMemberList>>) {
+        let mut lists = self.0.lock().expect("member list lock poisoned");
+        if Arc::strong_count(list) == 1 {
+            lists.remove(channel_id);
+        }
+    }
+}
+
+/// Member sidebar for a single channel, kept up to date once for every connection
+///
+/// Every change is recorded as a new version, connections remember which
+/// version they last sent to their client and catch up from the history.
+pub struct SharedMemberList {
+    server_id: String,
+    channel_id: String,
+    loaded: bool,
+
+    rows: Vec<RowKey>,
+    placements: HashMap<String, RowKey>,
+    group_counts: HashMap<GroupKey, usize>,
+
+    members: HashMap<String, Member>,
+    users: HashMap<String, User>,
+    online: HashSet<String>,
+    idle: HashSet<String>,
+
+    /// Members holding each role
+    role_members: HashMap<String, HashSet<String>>,
+    /// Whether members with a given set of roles can see the channel
+    visibility: HashMap<ViewKey, bool>,
+
+    version: u64,
+    history: VecDeque<(u64, Change)>,
+    seen: LruCache<String, ()>,
+}
+
+impl SharedMemberList {
+    /// Create an empty list which has yet to be loaded
+    fn new(server_id: &str, channel_id: &str) -> SharedMemberList {
+        SharedMemberList {
+            server_id: server_id.to_string(),
+            channel_id: channel_id.to_string(),
+            loaded: false,
+
+            rows: vec![],
+            placements: HashMap::new(),
+            group_counts: HashMap::new(),
+
+            members: HashMap::new(),
+            users: HashMap::new(),
+            online: HashSet::new(),
+            idle: HashSet::new(),
+
+            role_members: HashMap::new(),
+            visibility: HashMap::new(),
+
+            version: 0,
+            history: VecDeque::new(),
+            seen: LruCache::new(SEEN_EVENTS),
+        }
+    }
+
+    /// Fetch every member of the server and lay out the list
+    async fn load(&mut self, db: &Database, server: &Server, channel: &Channel) -> Result<()> {
+        let members = db.fetch_all_members(&server.id).await?;
+        let user_ids: Vec<String> = members.iter().map(|x| x.id.user.clone()).collect();
+        let users = db.fetch_users(&user_ids).await?;
+        let online = filter_online(&user_ids).await;
+        let idle = filter_idle(&user_ids).await;
+
+        self.populate(db, server, channel, members, users, online, idle)
+            .await;
+        Ok(())
+    }
+
+    /// Lay out the list from a full set of members
+    #[allow(clippy::too_many_arguments)]
+    async fn populate(
+        &mut self,
+        db: &Database,
+        server: &Server,
+        channel: &Channel,
+        members: Vec<Member>,
+        users: Vec<User>,
+        online: HashSet<String>,
+        idle: HashSet<String>,
+    ) {
+        for member in &members {
+            self.index_roles(member);
+        }
+
+        self.members = members
+            .into_iter()
+            .map(|member| (member.id.user.clone(), member))
+            .collect();
+        self.users = users
+            .into_iter()
+            .map(|user| (user.id.clone(), user))
+            .collect();
+        self.online = online;
+        self.idle = idle;
+
+        let user_ids: Vec<String> = self.members.keys().cloned().collect();
+        for user_id in user_ids {
+            if self.can_view(db, server, channel, &user_id).await {
+                if let Some(row) = self.row_for(server, &user_id) {
+                    self.placements.insert(user_id, row);
+                }
+            }
+        }
+
+        for row in self.placements.values() {
+            *self.group_counts.entry(row.group.clone()).or_default() += 1;
+        }
+
+        self.rows = self.placements.values().cloned().collect();
+        self.rows
+            .extend(self.group_counts.keys().map(|group| RowKey {
+                group: group.clone(),
+                member: None,
+            }));
+        self.rows.sort();
+        self.loaded = true;
+    }
+
+    /// Record which roles a member holds
+    fn index_roles(&mut self, member: &Member) {
+        for role in &member.roles {
+            self.role_members
+                .entry(role.clone())
+                .or_default()
+                .insert(member.id.user.clone());
+        }
+    }
+
+    /// Forget which roles a member holds
+    fn unindex_roles(&mut self, user_id: &str) {
+        if let Some(member) = self.members.get(user_id) {
+            for role in &member.roles {
+                if let Some(holders) = self.role_members.get_mut(role) {
+                    holders.remove(user_id);
+                }
+            }
+        }
+    }
+
+    /// Check whether a member can view the channel this list is for
+    ///
+    /// The result only depends on a handful of properties of the member,
+    /// so it is computed once for each distinct set of roles.
+    async fn can_view(
+        &mut self,
+        db: &Database,
+        server: &Server,
+        channel: &Channel,
+        user_id: &str,
+    ) -> bool {
+        let (Some(member), Some(user)) = (self.members.get(user_id), self.users.get(user_id))
+        else {
+            return false;
+        };
+
+        let mut roles = member.roles.clone();
+        roles.sort();
+
+        let key = ViewKey {
+            privileged: user.privileged,
+            owner: server.owner == user.id,
+            timed_out: member.in_timeout(),
+            roles,
+        };
+
+        if let Some(can_view) = self.visibility.get(&key) {
+            return *can_view;
+        }
+
+        let mut query = DatabasePermissionQuery::new(db, user)
+            .server(server)
+            .channel(channel)
+            .member(member);
+
+        let can_view = calculate_channel_permissions(&mut query)
+            .await
+            .has_channel_permission(ChannelPermission::ViewChannel);
+
+        self.visibility.insert(key, can_view);
+        can_view
+    }
+
+    /// Check whether a user should appear online to others
+    fn is_online(&self, user_id: &str) -> bool {
+        self.online.contains(user_id)
+            && !matches!(
+                self.users.get(user_id).and_then(|user| user.status.as_ref()),
+                Some(status) if status.presence == Some(Presence::Invisible)
+            )
+    }
+
+    /// Check whether all of a user's sessions are idle
+    fn is_idle(&self, user_id: &str) -> bool {
+        self.is_online(user_id) && self.idle.contains(user_id)
+    }
+
+    /// Work out where a member belongs in the list
+    fn row_for(&self, server: &Server, user_id: &str) -> Option<RowKey> {
+        let member = self.members.get(user_id)?;
+        let user = self.users.get(user_id)?;
+
+        let group = if self.is_online(user_id) {
+            member
+                .roles
+                .iter()
+                .filter_map(|id| {
+                    server
+                        .roles
+                        .get(id)
+                        .filter(|role| role.hoist)
+                        .map(|role| GroupKey::Role(role.rank, id.clone()))
+                })
+                .min()
+                .unwrap_or(GroupKey::Online)
+        } else {
+            GroupKey::Offline
+        };
+
+        let name = member
+            .nickname
+            .as_ref()
+            .or(user.display_name.as_ref())
+            .unwrap_or(&user.username)
+            .to_lowercase();
+
+        Some(RowKey {
+            group,
+            member: Some((name, user_id.to_string())),
+        })
+    }
+
+    /// Insert a row, returning the index it was placed at
+    fn insert_row(&mut self, row: RowKey) -> usize {
+        let index = self.rows.binary_search(&row).unwrap_or_else(|index| index);
+        self.rows.insert(index, row);
+        index
+    }
+
+    /// Remove a row, returning the index it was removed from
+    fn remove_row(&mut self, row: &RowKey) -> Option<usize> {
+        let index = self.rows.binary_search(row).ok()?;
+        self.rows.remove(index);
+        Some(index)
+    }
+
+    /// Find the index of a group header
+    fn group_index(&self, group: &GroupKey) -> Option<usize> {
+        self.rows
+            .binary_search(&RowKey {
+                group: group.clone(),
+                member: None,
+            })
+            .ok()
+    }
+
+    /// Remove a member from the list
+    fn unplace(&mut self, user_id: &str) -> Vec<Op> {
+        let mut ops = vec![];
+        let Some(row) = self.placements.remove(user_id) else {
+            return ops;
+        };
+
+        if let Some(index) = self.remove_row(&row) {
+            ops.push(Op::Delete { index });
+        }
+
+        let count = self.group_counts.entry(row.group.clone()).or_default();
+        *count = count.saturating_sub(1);
+
+        if *count == 0 {
+            self.group_counts.remove(&row.group);
+            if let Some(index) = self.remove_row(&RowKey {
+                group: row.group,
+                member: None,
+            }) {
+                ops.push(Op::Delete { index });
+            }
+        } else if let Some(index) = self.group_index(&row.group) {
+            ops.push(Op::Update {
+                index,
+                item: self.item(index),
+            });
+        }
+
+        ops
+    }
+
+    /// Add or move a member within the list
+    fn place(&mut self, server: &Server, user_id: &str) -> Vec<Op> {
+        let Some(row) = self.row_for(server, user_id) else {
+            return self.unplace(user_id);
+        };
+
+        // If the member has not moved, just send the new data.
+        if self.placements.get(user_id) == Some(&row) {
+            return match self.rows.binary_search(&row) {
+                Ok(index) => vec![Op::Update {
+                    index,
+                    item: self.item(index),
+                }],
+                Err(_) => vec![],
+            };
+        }
+
+        let mut ops = self.unplace(user_id);
+        let count = self.group_counts.entry(row.group.clone()).or_default();
+        *count += 1;
+
+        if *count == 1 {
+            let index = self.insert_row(RowKey {
+                group: row.group.clone(),
+                member: None,
+            });
+
+            ops.push(Op::Insert {
+                index,
+                item: self.item(index),
+            });
+        } else if let Some(index) = self.group_index(&row.group) {
+            ops.push(Op::Update {
+                index,
+                item: self.item(index),
+            });
+        }
+
+        self.placements.insert(user_id.to_string(), row.clone());
+        let index = self.insert_row(row);
+        ops.push(Op::Insert {
+            index,
+            item: self.item(index),
+        });
+
+        ops
+    }
+
+    /// Re-check where some members belong after their permissions or roles changed
+    ///
+    /// Members whose position is unaffected produce no operations.
+    async fn revisit(
+        &mut self,
+        db: &Database,
+        server: &Server,
+        channel: &Channel,
+        user_ids: Vec<String>,
+    ) -> Vec<Op> {
+        let mut ops = vec![];
+        for user_id in user_ids {
+            if self.can_view(db, server, channel, &user_id).await {
+                if self.placements.get(&user_id) != self.row_for(server, &user_id).as_ref() {
+                    ops.append(&mut self.place(server, &user_id));
+                }
+            } else {
+                ops.append(&mut self.unplace(&user_id));
+            }
+        }
+
+        ops
+    }
+
+    /// Snapshot the row at a given index
+    fn item(&self, index: usize) -> Item {
+        let row = &self.rows[index];
+        match &row.member {
+            Some((_, user_id)) => Item::Member {
+                member: self.members[user_id].clone(),
+                user: self.users[user_id].clone(),
+                online: self.is_online(user_id),
+                idle: self.is_idle(user_id),
+            },
+            None => Item::Group(MemberListGroup {
+                id: row.group.id(),
+                count: self
+                    .group_counts
+                    .get(&row.group)
+                    .copied()
+                    .unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Generate sync operations for every given range
+    fn sync(&self, perspective: &User, ranges: &[(usize, usize)]) -> Vec<MemberListOp> {
+        ranges
+            .iter()
+            .map(|&(start, end)| MemberListOp::Sync {
+                range: (start, end),
+                items: (start..=end)
+                    .take_while(|index| *index < self.rows.len())
+                    .map(|index| self.item(index).render(perspective))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Collect every change made after a given version
+    ///
+    /// Connections which have fallen too far behind, or which missed
+    /// a change too large to describe, are sent their ranges again.
+    fn changes_since(
+        &self,
+        perspective: &User,
+        ranges: &[(usize, usize)],
+        version: u64,
+    ) -> Vec<MemberListOp> {
+        let missed = self.history.iter().skip_while(|(v, _)| *v <= version);
+        let is_contiguous = self
+            .history
+            .front()
+            .map_or(false, |(oldest, _)| *oldest <= version + 1);
+
+        if !is_contiguous
+            || missed
+                .clone()
+                .any(|(_, change)| matches!(change, Change::Resync))
+        {
+            return self.sync(perspective, ranges);
+        }
+
+        missed
+            .filter_map(|(_, change)| match change {
+                Change::Ops(ops) => Some(ops),
+                Change::Resync => None,
+            })
+            .flatten()
+            .map(|op| op.render(perspective))
+            .collect()
+    }
+
+    /// Record a set of changes as a new version
+    fn push(&mut self, ops: Vec<Op>) {
+        if ops.is_empty() {
+            return;
+        }
+
+        let change = if ops.len() > MAX_OPS {
+            Change::Resync
+        } else {
+            Change::Ops(ops)
+        };
+
+        self.version += 1;
+        self.history.push_back((self.version, change));
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+
+    /// Wrap operations into an event, dropping those the client does not care about
+    ///
+    /// Changes past the end of the last subscribed range do not shift
+    /// anything the client holds, so they are never sent.
+    fn update_event(&self, ranges: &[(usize, usize)], ops: Vec<MemberListOp>) -> EventV1 {
+        let limit = ranges.iter().map(|(_, end)| *end).max();
+        let ops = ops
+            .into_iter()
+            .filter(|op| match op {
+                MemberListOp::Sync { .. } => true,
+                MemberListOp::Insert { index, .. }
+                | MemberListOp::Update { index, .. }
+                | MemberListOp::Delete { index } => limit.map_or(false, |limit| *index <= limit),
+            })
+            .collect();
+
+        let groups = self
+            .rows
+            .iter()
+            .filter(|row| row.member.is_none())
+            .map(|row| MemberListGroup {
+                id: row.group.id(),
+                count: self
+                    .group_counts
+                    .get(&row.group)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        EventV1::MemberListUpdate {
+            id: self.channel_id.clone(),
+            member_count: self.placements.len(),
+            online_count: self
+                .placements
+                .values()
+                .filter(|row| row.group != GroupKey::Offline)
+                .count(),
+            groups,
+            ops,
+        }
+    }
+
+    /// Apply an incoming event to the list, unless it has already been applied
+    async fn apply(
+        &mut self,
+        db: &Database,
+        server: &Server,
+        channel: &Channel,
+        id: &str,
+        event: &EventV1,
+    ) {
+        if self.seen.put(id.to_string(), ()).is_some() {
+            return;
+        }
+
+        let ops = match event {
+            EventV1::ServerMemberJoin { id, user } if id == &self.server_id => {
+                let (Ok(member), Ok(user)) =
+                    (db.fetch_member(id, user).await, db.fetch_user(user).await)
+                else {
+                    return;
+                };
+
+                let user_id = user.id.clone();
+                if is_online(&user_id).await {
+                    self.online.insert(user_id.clone());
+                }
+
+                if is_idle(&user_id).await {
+                    self.idle.insert(user_id.clone());
+                }
+
+                self.unindex_roles(&user_id);
+                self.index_roles(&member);
+                self.members.insert(user_id.clone(), member);
+                self.users.insert(user_id.clone(), user);
+
+                if self.can_view(db, server, channel, &user_id).await {
+                    self.place(server, &user_id)
+                } else {
+                    return;
+                }
+            }
+            EventV1::ServerMemberLeave { id, user } if id == &self.server_id => {
+                let ops = self.unplace(user);
+                self.unindex_roles(user);
+                self.members.remove(user);
+                self.users.remove(user);
+                self.online.remove(user);
+                self.idle.remove(user);
+                ops
+            }
+            EventV1::ServerMemberUpdate { id, data, clear } if id.server == self.server_id => {
+                if !self.members.contains_key(&id.user) {
+                    return;
+                }
+
+                self.unindex_roles(&id.user);
+                let member = self.members.get_mut(&id.user).expect("member is present");
+                for field in clear {
+                    member.remove_field(&field.clone().into());
+                }
+
+                member.apply_options(data.clone().into());
+                let member = member.clone();
+                self.index_roles(&member);
+
+                if self.can_view(db, server, channel, &id.user).await {
+                    self.place(server, &id.user)
+                } else {
+                    self.unplace(&id.user)
+                }
+            }
+            EventV1::UserUpdate {
+                id, data, clear, ..
+            } => {
+                let Some(user) = self.users.get_mut(id) else {
+                    return;
+                };
+
+                for field in clear {
+                    user.remove_field(&field.clone().into());
+                }
+
+                user.apply_options(data.clone().into());
+
+                match data.online {
+                    Some(true) => {
+                        self.online.insert(id.clone());
+                    }
+                    Some(false) => {
+                        self.online.remove(id);
+                    }
+                    None => {}
+                }
+
+                match data.idle {
+                    Some(true) => {
+                        self.idle.insert(id.clone());
+                    }
+                    Some(false) => {
+                        self.idle.remove(id);
+                    }
+                    None => {}
+                }
+
+                if self.placements.contains_key(id) {
+                    self.place(server, id)
+                } else {
+                    return;
+                }
+            }
+            EventV1::ServerUpdate { id, data, .. } if id == &self.server_id => {
+                // Only a change in ownership or default permissions affects everyone.
+                if data.default_permissions.is_none() && data.owner.is_none() {
+                    return;
+                }
+
+                self.visibility.clear();
+                let user_ids: Vec<String> = self.members.keys().cloned().collect();
+                self.revisit(db, server, channel, user_ids).await
+            }
+            EventV1::ServerRoleUpdate {
+                id, role_id, data, ..
+            } if id == &self.server_id => {
+                if data.permissions.is_some() {
+                    self.visibility.clear();
+                } else if data.hoist.is_none() && data.rank.is_none() {
+                    return;
+                }
+
+                let user_ids: Vec<String> = self
+                    .role_members
+                    .get(role_id)
+                    .map(|holders| holders.iter().cloned().collect())
+                    .unwrap_or_default();
+
+                self.revisit(db, server, channel, user_ids).await
+            }
+            EventV1::ServerRoleDelete { id, role_id } if id == &self.server_id => {
+                self.visibility.clear();
+                let user_ids: Vec<String> = self
+                    .role_members
+                    .remove(role_id)
+                    .map(|holders| holders.into_iter().collect())
+                    .unwrap_or_default();
+
+                self.revisit(db, server, channel, user_ids).await
+            }
+            EventV1::ChannelUpdate { id, data, clear } if id == &self.channel_id => {
+                if data.default_permissions.is_none()
+                    && data.role_permissions.is_none()
+                    && !clear.contains(&FieldsChannel::DefaultPermissions)
+                {
+                    return;
+                }
+
+                self.visibility.clear();
+                let user_ids: Vec<String> = self.members.keys().cloned().collect();
+                self.revisit(db, server, channel, user_ids).await
+            }
+            _ => return,
+        };
+
+        self.push(ops);
+    }
+}
+
+/// Connection's view into a shared member list
+pub struct MemberList {
+    pub server_id: String,
+    pub channel_id: String,
+    ranges: Vec<(usize, usize)>,
+    /// Version of the shared list the client has last been sent
+    version: u64,
+    shared: Arc<Mutex<SharedMemberList>>,
+}
+
+impl MemberList {
+    /// Check the ranges requested by a client are within limits
+    pub fn validate_ranges(ranges: &[(usize, usize)]) -> Result<()> {
+        if ranges.len() > MAX_RANGES
+            || ranges
+                .iter()
+                .any(|(start, end)| start > end || end - start >= MAX_RANGE_SIZE)
+        {
+            Err(create_error!(InvalidOperation))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Open the member list for a channel and generate the initial sync
+    ///
+    /// The list is only loaded from the database if no other connection has it open.
+    pub async fn new(
+        db: &Database,
+        perspective: &User,
+        server: &Server,
+        channel: &Channel,
+        ranges: Vec<(usize, usize)>,
+    ) -> Result<(MemberList, EventV1)> {
+        let mut member_list = MemberList {
+            server_id: server.id.clone(),
+            channel_id: channel.id(),
+            ranges,
+            version: 0,
+            shared: MEMBER_LISTS.get_or_create(&server.id, &channel.id()),
+        };
+
+        let mut list = member_list.shared.lock().await;
+        if !list.loaded {
+            list.load(db, server, channel).await?;
+        }
+
+        let version = list.version;
+        let event = list.update_event(
+            &member_list.ranges,
+            list.sync(perspective, &member_list.ranges),
+        );
+
+        drop(list);
+        member_list.version = version;
+        Ok((member_list, event))
+    }
+
+    /// Change the subscribed ranges and generate a sync for them
+    pub async fn set_ranges(&mut self, perspective: &User, ranges: Vec<(usize, usize)>) -> EventV1 {
+        let list = self.shared.lock().await;
+        self.ranges = ranges;
+        self.version = list.version;
+        list.update_event(&self.ranges, list.sync(perspective, &self.ranges))
+    }
+
+    /// Apply an incoming event to the member list, returning an update for the client if anything changed
+    ///
+    /// The event is only applied to the shared list by the first connection
+    /// to see it, every connection then catches up on whatever it missed.
+    pub async fn handle_event(
+        &mut self,
+        db: &Database,
+        perspective: &User,
+        server: &Server,
+        channel: &Channel,
+        id: &str,
+        event: &EventV1,
+    ) -> Option<EventV1> {
+        let mut list = self.shared.lock().await;
+        list.apply(db, server, channel, id, event).await;
+
+        if list.version == self.version {
+            return None;
+        }
+
+        let ops = list.changes_since(perspective, &self.ranges, self.version);
+        self.version = list.version;
+
+        let event = list.update_event(&self.ranges, ops);
+        if matches!(&event, EventV1::MemberListUpdate { ops, .. } if ops.is_empty()) {
+            None
+        } else {
+            Some(event)
+        }
+    }
+}
+
+impl Drop for MemberList {
+    fn drop(&mut self) {
+        MEMBER_LISTS.release(&self.channel_id, &self.shared);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use async_std::sync::Mutex;
+    use revolt_database::{
+        events::client::{EventV1, MemberListItem, MemberListOp},
+        Channel, Database, Member, MemberCompositeKey, Role, Server, User,
+    };
+    use revolt_models::v0;
+    use revolt_permissions::{ChannelPermission, OverrideField, DEFAULT_PERMISSION_SERVER};
+
+    use super::{MemberList, SharedMemberList, MEMBER_LISTS};
+
+    fn server() -> Server {
+        Server {
+            id: "server".to_string(),
+            owner: "owner".to_string(),
+            name: "Server".to_string(),
+            description: None,
+            channels: vec!["channel".to_string()],
+            categories: None,
+            system_messages: None,
+            roles: HashMap::from([(
+                "moderator".to_string(),
+                Role {
+                    name: "Moderator".to_string(),
+                    permissions: OverrideField { a: 0, d: 0 },
+                    colour: None,
+                    hoist: true,
+                    rank: 0,
+                },
+            )]),
+            default_permissions: *DEFAULT_PERMISSION_SERVER as i64,
+            icon: None,
+            banner: None,
+            flags: None,
+            nsfw: false,
+            analytics: false,
+            discoverable: false,
+        }
+    }
+
+    fn channel(role_permissions: HashMap<String, OverrideField>) -> Channel {
+        Channel::TextChannel {
+            id: "channel".to_string(),
+            server: "server".to_string(),
+            name: "general".to_string(),
+            description: None,
+            icon: None,
+            last_message_id: None,
+            default_permissions: None,
+            role_permissions,
+            nsfw: false,
+        }
+    }
+
+    fn member(user: &str, roles: &[&str]) -> Member {
+        Member {
+            id: MemberCompositeKey {
+                server: "server".to_string(),
+                user: user.to_string(),
+            },
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn user(id: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Load a list with a hoisted moderator, an online member and an offline member
+    async fn shared_list(db: &Database) -> Arc<Mutex<SharedMemberList>> {
+        let mut list = SharedMemberList::new("server", "channel");
+        list.populate(
+            db,
+            &server(),
+            &channel(HashMap::new()),
+            vec![
+                member("alice", &["moderator"]),
+                member("bob", &[]),
+                member("carol", &[]),
+            ],
+            vec![user("alice"), user("bob"), user("carol")],
+            HashSet::from(["alice".to_string(), "bob".to_string()]),
+            HashSet::new(),
+        )
+        .await;
+
+        Arc::new(Mutex::new(list))
+    }
+
+    fn connection(shared: &Arc<Mutex<SharedMemberList>>) -> MemberList {
+        MemberList {
+            server_id: "server".to_string(),
+            channel_id: "channel".to_string(),
+            ranges: vec![(0, 99)],
+            version: 0,
+            shared: shared.clone(),
+        }
+    }
+
+    /// Describe each row as either a group id or a user id
+    fn rows(ops: &[MemberListOp]) -> Vec<String> {
+        match &ops[0] {
+            MemberListOp::Sync { items, .. } => items
+                .iter()
+                .map(|item| match item {
+                    MemberListItem::Group(group) => format!("{}:{}", group.id, group.count),
+                    MemberListItem::Member { user, .. } => user.id.clone(),
+                })
+                .collect(),
+            _ => panic!("expected a sync"),
+        }
+    }
+
+    fn ops(event: Option<EventV1>) -> Vec<MemberListOp> {
+        match event {
+            Some(EventV1::MemberListUpdate { ops, .. }) => ops,
+            _ => panic!("expected a member list update"),
+        }
+    }
+
+    #[async_std::test]
+    async fn lays_out_members() {
+        let db = Database::Reference(Default::default());
+        let shared = shared_list(&db).await;
+        let list = shared.lock().await;
+
+        assert_eq!(
+            rows(&list.sync(&user("viewer"), &[(0, 99)])),
+            vec![
+                "moderator:1",
+                "alice",
+                "online:1",
+                "bob",
+                "offline:1",
+                "carol"
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn applies_each_event_once() {
+        let db = Database::Reference(Default::default());
+        let shared = shared_list(&db).await;
+        let mut first = connection(&shared);
+        let mut second = connection(&shared);
+
+        let event = EventV1::UserUpdate {
+            id: "carol".to_string(),
+            data: v0::PartialUser {
+                online: Some(true),
+                ..Default::default()
+            },
+            clear: vec![],
+        };
+
+        let (server, channel) = (server(), channel(HashMap::new()));
+        let first_ops = ops(first
+            .handle_event(&db, &user("viewer"), &server, &channel, "event", &event)
+            .await);
+
+        let second_ops = ops(second
+            .handle_event(&db, &user("viewer"), &server, &channel, "event", &event)
+            .await);
+
+        // Both connections are sent the same change, but it was only applied once.
+        assert!(!first_ops.is_empty());
+        assert_eq!(
+            serde_json::to_value(&first_ops).unwrap(),
+            serde_json::to_value(&second_ops).unwrap()
+        );
+
+        let list = shared.lock().await;
+        assert_eq!(list.version, 1);
+        assert_eq!(
+            rows(&list.sync(&user("viewer"), &[(0, 99)])),
+            vec!["moderator:1", "alice", "online:2", "bob", "carol"]
+        );
+    }
+
+    #[async_std::test]
+    async fn catches_up_on_missed_changes() {
+        let db = Database::Reference(Default::default());
+        let shared = shared_list(&db).await;
+        let mut first = connection(&shared);
+        let mut second = connection(&shared);
+
+        let (server, channel) = (server(), channel(HashMap::new()));
+        let offline = EventV1::UserUpdate {
+            id: "bob".to_string(),
+            data: v0::PartialUser {
+                online: Some(false),
+                ..Default::default()
+            },
+            clear: vec![],
+        };
+
+        first
+            .handle_event(&db, &user("viewer"), &server, &channel, "offline", &offline)
+            .await;
+
+        // An unrelated event still brings the other connection up to date.
+        let unrelated = EventV1::ChannelStartTyping {
+            id: "channel".to_string(),
+            user: "alice".to_string(),
+        };
+
+        let ops = ops(second
+            .handle_event(
+                &db,
+                &user("viewer"),
+                &server,
+                &channel,
+                "typing",
+                &unrelated,
+            )
+            .await);
+
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, MemberListOp::Delete { .. })));
+        assert_eq!(second.version, first.version);
+    }
+
+    #[async_std::test]
+    async fn role_permission_changes_only_touch_affected_members() {
+        let db = Database::Reference(Default::default());
+        let shared = shared_list(&db).await;
+        let mut connection = connection(&shared);
+
+        let role_permissions = HashMap::from([(
+            "moderator".to_string(),
+            OverrideField {
+                a: 0,
+                d: ChannelPermission::ViewChannel as i64,
+            },
+        )]);
+
+        let event = EventV1::ChannelUpdate {
+            id: "channel".to_string(),
+            data: v0::PartialChannel {
+                role_permissions: Some(role_permissions.clone()),
+                ..Default::default()
+            },
+            clear: vec![],
+        };
+
+        let ops = ops(connection
+            .handle_event(
+                &db,
+                &user("viewer"),
+                &server(),
+                &channel(role_permissions),
+                "event",
+                &event,
+            )
+            .await);
+
+        // Only the moderator and their group header are removed.
+        assert!(matches!(
+            ops.as_slice(),
+            [
+                MemberListOp::Delete { index: 1 },
+                MemberListOp::Delete { index: 0 }
+            ]
+        ));
+
+        let list = shared.lock().await;
+        assert_eq!(
+            rows(&list.sync(&user("viewer"), &[(0, 99)])),
+            vec!["online:1", "bob", "offline:1", "carol"]
+        );
+    }
+
+    #[async_std::test]
+    async fn releases_lists_with_the_last_connection() {
+        let first = MEMBER_LISTS.get_or_create("server", "released");
+        let second = MEMBER_LISTS.get_or_create("server", "released");
+        assert!(Arc::ptr_eq(&first, &second));
+
+        drop(second);
+        MEMBER_LISTS.release("released", &first);
+        drop(first);
+
+        let lists = MEMBER_LISTS.0.lock().unwrap();
+        assert!(!lists.contains_key("released"));
+    }
+}
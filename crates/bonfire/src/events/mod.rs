@@ -1,2 +1,5 @@
+pub mod cache;
 pub mod r#impl;
+pub mod intents;
+pub mod member_list;
 pub mod state;
@@ -1,13 +1,14 @@
-use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
-    time::Duration,
-};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use async_std::sync::{Mutex, RwLock};
 use lru::LruCache;
 use lru_time_cache::{LruCache as LruTimeCache, TimedEntry};
-use revolt_database::{Channel, Member, Server, User};
+use revolt_database::{
+    events::intents::GatewayIntents, Channel, Member, MemberCompositeKey, Server, UnreadCounts,
+    User,
+};
+
+use super::{cache::GLOBAL_CACHE, member_list::MemberList};
 
 /// Enumeration representing some change in subscriptions
 pub enum SubscriptionStateChange {
@@ -22,26 +23,23 @@ pub enum SubscriptionStateChange {
     },
 }
 
-/// Dumb per-state cache implementation
+/// Per-connection view into the global cache
 ///
-/// Ideally this would use a global cache that
-/// allows for mutations and could use Rc<> to
-/// track usage. If Rc<> == 1, then it only
-/// remains in global cache, hence should be
-/// dropped.
+/// Connections only keep track of which objects they are
+/// interested in, the objects themselves live in the global
+/// cache and are released once the connection is dropped.
 ///
-/// ------------------------------------------------
-/// We can strip these objects to core information!!
-/// ------------------------------------------------
+/// Server channels are only tracked while they are viewable,
+/// so `channels` doubles as the result of permission checks.
 #[derive(Debug)]
 pub struct Cache {
     pub user_id: String,
     pub is_bot: bool,
 
-    pub users: HashMap<String, User>,
-    pub channels: HashMap<String, Channel>,
-    pub members: HashMap<String, Member>,
-    pub servers: HashMap<String, Server>,
+    pub users: HashSet<String>,
+    pub channels: HashSet<String>,
+    pub members: HashSet<String>,
+    pub servers: HashSet<String>,
 
     pub seen_events: LruCache<String, ()>,
 }
@@ -57,7 +55,132 @@ impl Default for Cache {
             members: Default::default(),
             servers: Default::default(),
 
-            seen_events: LruCache::new(20),
+            seen_events: LruCache::new(1_000),
+        }
+    }
+}
+
+impl Cache {
+    /// Get a user we know about
+    pub fn get_user(&self, id: &str) -> Option<Arc<User>> {
+        if self.users.contains(id) {
+            GLOBAL_CACHE.users.get(id)
+        } else {
+            None
+        }
+    }
+
+    /// Get the current user
+    pub fn get_self(&self) -> Arc<User> {
+        self.get_user(&self.user_id).expect("missing self?")
+    }
+
+    /// Get a channel we can view
+    pub fn get_channel(&self, id: &str) -> Option<Arc<Channel>> {
+        if self.channels.contains(id) {
+            GLOBAL_CACHE.channels.get(id)
+        } else {
+            None
+        }
+    }
+
+    /// Get our membership of a server
+    pub fn get_member(&self, server_id: &str) -> Option<Arc<Member>> {
+        if self.members.contains(server_id) {
+            GLOBAL_CACHE.members.get(&MemberCompositeKey {
+                server: server_id.to_string(),
+                user: self.user_id.clone(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get a server we are in
+    pub fn get_server(&self, id: &str) -> Option<Arc<Server>> {
+        if self.servers.contains(id) {
+            GLOBAL_CACHE.servers.get(id)
+        } else {
+            None
+        }
+    }
+
+    /// Start tracking a user
+    pub fn insert_user(&mut self, user: User) {
+        if self.users.insert(user.id.clone()) {
+            GLOBAL_CACHE.users.retain(user.id.clone(), user);
+        }
+    }
+
+    /// Start tracking a channel
+    pub fn insert_channel(&mut self, channel: Channel) {
+        let id = channel.id().to_string();
+        if self.channels.insert(id.clone()) {
+            GLOBAL_CACHE.channels.retain(id, channel);
+        }
+    }
+
+    /// Start tracking our membership of a server
+    pub fn insert_member(&mut self, member: Member) {
+        if self.members.insert(member.id.server.clone()) {
+            GLOBAL_CACHE.members.retain(member.id.clone(), member);
+        }
+    }
+
+    /// Start tracking a server
+    pub fn insert_server(&mut self, server: Server) {
+        if self.servers.insert(server.id.clone()) {
+            GLOBAL_CACHE.servers.retain(server.id.clone(), server);
+        }
+    }
+
+    /// Stop tracking a channel
+    pub fn remove_channel(&mut self, id: &str) {
+        if self.channels.remove(id) {
+            GLOBAL_CACHE.channels.release(id);
+        }
+    }
+
+    /// Stop tracking our membership of a server
+    pub fn remove_member(&mut self, server_id: &str) {
+        if self.members.remove(server_id) {
+            GLOBAL_CACHE.members.release(&MemberCompositeKey {
+                server: server_id.to_string(),
+                user: self.user_id.clone(),
+            });
+        }
+    }
+
+    /// Stop tracking a server
+    pub fn remove_server(&mut self, id: &str) -> Option<Arc<Server>> {
+        let server = self.get_server(id);
+        if self.servers.remove(id) {
+            GLOBAL_CACHE.servers.release(id);
+        }
+
+        server
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        for id in &self.users {
+            GLOBAL_CACHE.users.release(id);
+        }
+
+        for id in &self.channels {
+            GLOBAL_CACHE.channels.release(id);
+        }
+
+        for server in &self.members {
+            GLOBAL_CACHE.members.release(&MemberCompositeKey {
+                server: server.clone(),
+                user: self.user_id.clone(),
+            });
+        }
+
+        for id in &self.servers {
+            GLOBAL_CACHE.servers.release(id);
         }
     }
 }
@@ -68,7 +191,10 @@ pub struct State {
 
     pub session_id: String,
     pub private_topic: String,
+    pub intents: GatewayIntents,
     pub state: SubscriptionStateChange,
+    pub member_list: Option<MemberList>,
+    pub unreads: Option<UnreadCounts>,
 
     pub subscribed: Arc<RwLock<HashSet<String>>>,
     pub active_servers: Arc<Mutex<LruTimeCache<String, ()>>>,
@@ -76,7 +202,7 @@ pub struct State {
 
 impl State {
     /// Create state from User
-    pub fn from(user: User, session_id: String) -> State {
+    pub fn from(user: User, session_id: String, intents: GatewayIntents) -> State {
         let mut subscribed = HashSet::new();
         let private_topic = format!("{}!", user.id);
         subscribed.insert(private_topic.clone());
@@ -87,7 +213,7 @@ impl State {
             ..Default::default()
         };
 
-        cache.users.insert(user.id.clone(), user);
+        cache.insert_user(user);
 
         State {
             cache,
@@ -98,7 +224,10 @@ impl State {
             ))),
             session_id,
             private_topic,
+            intents,
             state: SubscriptionStateChange::Reset,
+            member_list: None,
+            unreads: None,
         }
     }
 
@@ -130,6 +259,13 @@ impl State {
                         self.insert_subscription(k).await;
                     }
                     Server::Unsubscribe(k) => {
+                        // Keep receiving member events while a member list is open.
+                        if let Some(member_list) = &self.member_list {
+                            if k == format!("{}u", member_list.server_id) {
+                                continue;
+                            }
+                        }
+
                         self.remove_subscription(&k).await;
                     }
                 }
@@ -154,7 +290,7 @@ impl State {
 
     /// Clone the active user
     pub fn clone_user(&self) -> User {
-        self.cache.users.get(&self.cache.user_id).unwrap().clone()
+        (*self.cache.get_self()).clone()
     }
 
     /// Reset the current state
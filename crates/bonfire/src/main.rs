@@ -9,7 +9,12 @@ extern crate log;
 pub mod config;
 pub mod events;
 
+mod commands;
+mod connection;
 mod database;
+mod presence;
+mod router;
+mod sse;
 mod websocket;
 
 #[async_std::main]
@@ -17,9 +22,19 @@ async fn main() {
     // Configure requirements for Bonfire.
     revolt_config::configure!(events);
     database::connect().await;
+    router::init().await;
 
     // Clean up the current region information.
-    clear_region(None).await;
+    let offline = clear_region(None).await;
+    async_std::task::spawn(presence::sweeper(database::get_db(), offline));
+
+    // Relay events written to the outbox, this is safe to run alongside other relays.
+    async_std::task::spawn(revolt_database::tasks::outbox::worker(database::get_db()));
+
+    // Serve event streams for clients which can't use WebSockets.
+    // By default, we bind to port 9001 on all interfaces.
+    let sse_bind = env::var("SSE_HOST").unwrap_or_else(|_| "0.0.0.0:9001".into());
+    async_std::task::spawn(sse::listen(sse_bind));
 
     // Setup a TCP listener to accept WebSocket connections on.
     // By default, we bind to port 9000 on all interfaces.
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use async_std::task::sleep;
+use revolt_database::{events::client::EventV1, Database, Presence, User};
+use revolt_models::v0;
+use revolt_presence::{sweep_expired_sessions, SESSION_TTL};
+
+/// Minimum time between keeping a session alive, clients may ping more often than this
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(SESSION_TTL.as_secs() / 6);
+
+/// How long a client may stay silent before it is disconnected
+///
+/// Matches the session lifetime, so the client is dropped around
+/// the same time as other users would see it go offline.
+pub const CLIENT_TIMEOUT: Duration = SESSION_TTL;
+
+/// How often to look for sessions which have expired
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Publish an update to the user's presence to the user and all associated server topics
+async fn publish<'a>(
+    user: &User,
+    servers: impl IntoIterator<Item = &'a String>,
+    data: v0::PartialUser,
+) {
+    if let Some(status) = &user.status {
+        if status.presence == Some(Presence::Invisible) {
+            return;
+        }
+    }
+
+    let event = EventV1::UserUpdate {
+        id: user.id.clone(),
+        data,
+        clear: vec![],
+    };
+
+    let mut topics: Vec<String> = servers.into_iter().cloned().collect();
+    topics.push(user.id.clone());
+    event.p_many(topics).await;
+}
+
+/// Push presence change to the user and all associated server topics
+pub async fn broadcast_presence_change<'a>(
+    user: &User,
+    servers: impl IntoIterator<Item = &'a String>,
+    online: bool,
+) {
+    publish(
+        user,
+        servers,
+        v0::PartialUser {
+            online: Some(online),
+            last_seen: if !online && user.share_last_seen {
+                user.last_seen
+            } else {
+                None
+            },
+            ..Default::default()
+        },
+    )
+    .await;
+}
+
+/// Push change in whether the user is idle to the user and all associated server topics
+pub async fn broadcast_idle_change<'a>(
+    user: &User,
+    servers: impl IntoIterator<Item = &'a String>,
+    idle: bool,
+) {
+    publish(
+        user,
+        servers,
+        v0::PartialUser {
+            idle: Some(idle),
+            ..Default::default()
+        },
+    )
+    .await;
+}
+
+/// Record that a user has gone offline and let everyone know
+pub async fn went_offline(db: &Database, user_id: &str) {
+    let mut user = match db.fetch_user(user_id).await {
+        Ok(user) => user,
+        Err(err) => {
+            error!("Failed to fetch user {user_id} going offline: {err:?}");
+            return;
+        }
+    };
+
+    if let Err(err) = user.mark_last_seen(db).await {
+        error!("Failed to record last seen for {user_id}: {err:?}");
+    }
+
+    let servers: Vec<String> = db
+        .fetch_all_memberships(user_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|member| member.id.server)
+        .collect();
+
+    broadcast_presence_change(&user, &servers, false).await;
+}
+
+/// Notify users who went offline when the region was cleared,
+/// then periodically sweep sessions which stopped sending heartbeats.
+pub async fn sweeper(db: &'static Database, offline: Vec<String>) {
+    for user_id in offline {
+        went_offline(db, &user_id).await;
+    }
+
+    loop {
+        sleep(SWEEP_INTERVAL).await;
+
+        for user_id in sweep_expired_sessions().await {
+            info!("Presence session for {user_id} expired.");
+            went_offline(db, &user_id).await;
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+use async_std::task::spawn;
+use lru::LruCache;
+use once_cell::sync::OnceCell;
+use revolt_database::events::{
+    broker::{broker, BrokerEvent, EventSubscriber},
+    client::EventV1,
+};
+use revolt_result::Result;
+
+use crate::events::cache::GLOBAL_CACHE;
+
+static ROUTER: OnceCell<Router> = OnceCell::new();
+
+/// Number of recent event ids to remember for de-duplication
+const SEEN_EVENTS: usize = 10_000;
+
+/// Connections and the topics they are subscribed to
+#[derive(Default)]
+struct Subscriptions {
+    connections: HashMap<usize, async_channel::Sender<(String, EventV1)>>,
+    topics: HashMap<String, HashSet<usize>>,
+    /// Topics the broker subscriber is currently subscribed to
+    active: HashSet<String>,
+    /// Topics whose broker subscription is being changed
+    changing: HashSet<String>,
+}
+
+/// Process-wide event router
+///
+/// Holds a single broker subscriber for the entire process, each incoming
+/// event is applied to the global cache exactly once before being handed
+/// to every connection subscribed to the relevant topic.
+///
+/// Events are handed over alongside their id, as the same event may arrive
+/// on several topics and connections must de-duplicate it themselves.
+///
+/// No lock is held while talking to the broker, so connections are never
+/// held up by each other's subscription changes.
+pub struct Router {
+    subscriber: Box<dyn EventSubscriber>,
+    subscriptions: RwLock<Subscriptions>,
+    /// Ids of events which have already been applied to the global cache
+    seen: std::sync::Mutex<LruCache<String, ()>>,
+    next_id: AtomicUsize,
+}
+
+/// Connection registered with the event router
+pub struct RouterConnection {
+    id: usize,
+    pub receiver: async_channel::Receiver<(String, EventV1)>,
+}
+
+/// Connect the event router to the event broker and start dispatching events.
+pub async fn init() {
+    let subscriber = broker()
+        .await
+        .subscriber()
+        .await
+        .expect("Failed to create a subscriber");
+
+    if ROUTER.set(Router::new(subscriber)).is_err() {
+        panic!("couldn't set router")
+    }
+
+    spawn(async move {
+        let router = get_router();
+        while let Some(message) = router.subscriber.next().await {
+            match message {
+                BrokerEvent::Event { id, topic, event } => router.dispatch(id, topic, event),
+                BrokerEvent::Interrupted => {
+                    // We may have missed events, force every client to resync.
+                    router.reset().await;
+                }
+            }
+        }
+
+        error!("Event broker subscriber closed unexpectedly!");
+    });
+}
+
+/// Get a reference to the event router.
+pub fn get_router() -> &'static Router {
+    ROUTER.get().expect("Valid `Router`")
+}
+
+impl Router {
+    /// Create a router on top of a broker subscriber
+    fn new(subscriber: Box<dyn EventSubscriber>) -> Router {
+        Router {
+            subscriber,
+            subscriptions: Default::default(),
+            seen: std::sync::Mutex::new(LruCache::new(SEEN_EVENTS)),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Register a new connection
+    pub fn connect(&self) -> RouterConnection {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = async_channel::unbounded();
+
+        self.subscriptions
+            .write()
+            .expect("router lock poisoned")
+            .connections
+            .insert(id, sender);
+
+        RouterConnection { id, receiver }
+    }
+
+    /// Cache and fan out an incoming event
+    fn dispatch(&self, id: String, topic: String, event: EventV1) {
+        let is_new = self
+            .seen
+            .lock()
+            .expect("router lock poisoned")
+            .put(id.clone(), ())
+            .is_none();
+
+        if is_new {
+            GLOBAL_CACHE.apply(&event);
+        }
+
+        let subscriptions = self.subscriptions.read().expect("router lock poisoned");
+        if let Some(ids) = subscriptions.topics.get(&topic) {
+            for connection in ids {
+                if let Some(sender) = subscriptions.connections.get(connection) {
+                    sender.try_send((id.clone(), event.clone())).ok();
+                }
+            }
+        }
+    }
+
+    /// Drop every connection and all subscriptions
+    ///
+    /// Receivers are closed, so clients will disconnect and
+    /// rebuild their state from scratch when they reconnect.
+    async fn reset(&self) {
+        {
+            let mut subscriptions = self.subscriptions.write().expect("router lock poisoned");
+            subscriptions.connections.clear();
+            subscriptions.topics.clear();
+            subscriptions.active.clear();
+        }
+
+        if let Err(err) = self.subscriber.unsubscribe_all().await {
+            error!("Unsubscribe all failed: {err:?}");
+        }
+    }
+
+    /// Subscribe a connection to a topic
+    async fn subscribe(&self, connection: usize, topic: String) -> Result<()> {
+        {
+            let mut subscriptions = self.subscriptions.write().expect("router lock poisoned");
+            if !subscriptions.connections.contains_key(&connection) {
+                // We've been reset, don't hold onto anything.
+                return Ok(());
+            }
+
+            let ids = subscriptions.topics.entry(topic.clone()).or_default();
+            if !ids.insert(connection) || ids.len() > 1 {
+                return Ok(());
+            }
+        }
+
+        self.sync_topic(topic).await
+    }
+
+    /// Unsubscribe a connection from a topic
+    async fn unsubscribe(&self, connection: usize, topic: String) -> Result<()> {
+        {
+            let mut subscriptions = self.subscriptions.write().expect("router lock poisoned");
+            match subscriptions.topics.get_mut(&topic) {
+                Some(ids) if ids.remove(&connection) && ids.is_empty() => {
+                    subscriptions.topics.remove(&topic);
+                }
+                _ => return Ok(()),
+            }
+        }
+
+        self.sync_topic(topic).await
+    }
+
+    /// Unsubscribe a connection from every topic
+    async fn unsubscribe_all(&self, connection: usize) -> Result<()> {
+        let abandoned: Vec<String> = {
+            let mut subscriptions = self.subscriptions.write().expect("router lock poisoned");
+            let mut abandoned = vec![];
+            subscriptions.topics.retain(|topic, ids| {
+                if ids.remove(&connection) && ids.is_empty() {
+                    abandoned.push(topic.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+
+            abandoned
+        };
+
+        for topic in abandoned {
+            self.sync_topic(topic).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Bring the broker subscription for a topic in line with what connections want
+    ///
+    /// Only one task changes a given topic at a time. Any other task leaves its
+    /// change to that one, which checks the topic again after each round trip.
+    async fn sync_topic(&self, topic: String) -> Result<()> {
+        if !self
+            .subscriptions
+            .write()
+            .expect("router lock poisoned")
+            .changing
+            .insert(topic.clone())
+        {
+            return Ok(());
+        }
+
+        loop {
+            let wanted = {
+                let mut subscriptions = self.subscriptions.write().expect("router lock poisoned");
+                let wanted = subscriptions.topics.contains_key(&topic);
+                if wanted == subscriptions.active.contains(&topic) {
+                    subscriptions.changing.remove(&topic);
+                    return Ok(());
+                }
+
+                wanted
+            };
+
+            let result = if wanted {
+                self.subscriber.subscribe(topic.clone()).await
+            } else {
+                self.subscriber.unsubscribe(topic.clone()).await
+            };
+
+            let mut subscriptions = self.subscriptions.write().expect("router lock poisoned");
+            if let Err(err) = result {
+                subscriptions.changing.remove(&topic);
+                return Err(err);
+            }
+
+            if wanted {
+                subscriptions.active.insert(topic.clone());
+            } else {
+                subscriptions.active.remove(&topic);
+            }
+        }
+    }
+}
+
+impl RouterConnection {
+    /// Subscribe this connection to a topic
+    pub async fn subscribe(&self, topic: String) -> Result<()> {
+        get_router().subscribe(self.id, topic).await
+    }
+
+    /// Unsubscribe this connection from a topic
+    pub async fn unsubscribe(&self, topic: String) -> Result<()> {
+        get_router().unsubscribe(self.id, topic).await
+    }
+
+    /// Unsubscribe this connection from every topic
+    pub async fn unsubscribe_all(&self) -> Result<()> {
+        get_router().unsubscribe_all(self.id).await
+    }
+
+    /// Deregister this connection
+    pub async fn close(self) {
+        if let Err(err) = self.unsubscribe_all().await {
+            error!("Unsubscribe all failed: {err:?}");
+            sentry::capture_error(&err);
+        }
+
+        get_router()
+            .subscriptions
+            .write()
+            .expect("router lock poisoned")
+            .connections
+            .remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revolt_database::events::broker::{EventBroker, MemoryBroker};
+
+    use super::Router;
+
+    async fn router() -> Router {
+        Router::new(MemoryBroker::default().subscriber().await.unwrap())
+    }
+
+    fn is_active(router: &Router, topic: &str) -> bool {
+        router.subscriptions.read().unwrap().active.contains(topic)
+    }
+
+    #[async_std::test]
+    async fn subscribes_while_any_connection_is_interested() {
+        let router = router().await;
+        let first = router.connect();
+        let second = router.connect();
+
+        router
+            .subscribe(first.id, "topic".to_string())
+            .await
+            .unwrap();
+        router
+            .subscribe(second.id, "topic".to_string())
+            .await
+            .unwrap();
+        assert!(is_active(&router, "topic"));
+
+        router
+            .unsubscribe(first.id, "topic".to_string())
+            .await
+            .unwrap();
+        assert!(is_active(&router, "topic"));
+
+        router.unsubscribe_all(second.id).await.unwrap();
+        assert!(!is_active(&router, "topic"));
+        assert!(router.subscriptions.read().unwrap().changing.is_empty());
+    }
+
+    #[async_std::test]
+    async fn ignores_connections_after_reset() {
+        let router = router().await;
+        let connection = router.connect();
+        router.reset().await;
+
+        router
+            .subscribe(connection.id, "topic".to_string())
+            .await
+            .unwrap();
+        assert!(!is_active(&router, "topic"));
+    }
+}
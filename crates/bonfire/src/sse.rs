@@ -0,0 +1,317 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use async_std::future::timeout;
+use futures::join;
+use once_cell::sync::Lazy;
+use revolt_database::{events::server::ClientMessage, User, UserHint};
+use revolt_result::{create_error, Error};
+use serde::Deserialize;
+use tide::{
+    http::{headers::HeaderValue, Method},
+    security::{CorsMiddleware, Origin},
+    sse, Body, Request, Response, StatusCode,
+};
+use ulid::Ulid;
+
+use crate::connection::{self, Outgoing, OUTGOING_BUFFER};
+use crate::database::get_db;
+use crate::events::state::State;
+
+/// How often to send a heartbeat so dead streams are noticed
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Cookie which may carry the session token
+const SESSION_COOKIE: &str = "session_token";
+
+/// Open event streams keyed by session id
+///
+/// Each stream is tagged with a registration id so that a stream
+/// replaced by a newer one does not unregister its replacement.
+static STREAMS: Lazy<Mutex<HashMap<String, (String, async_channel::Sender<ClientMessage>)>>> =
+    Lazy::new(Default::default);
+
+/// Query parameters for opening an event stream
+#[derive(Deserialize)]
+struct StreamQuery {
+    intents: Option<u32>,
+}
+
+/// Start listening for event stream clients
+///
+/// Intended for clients which cannot hold a WebSocket open, such as those
+/// behind restrictive proxies. Events are received by opening a stream with
+/// `GET /` and messages are sent to the server with `POST /`. As with
+/// WebSockets, clients must keep posting pings or the stream is closed.
+pub async fn listen(bind: String) {
+    let config = revolt_config::config().await;
+    let app = app(&config.hosts.app);
+
+    info!("Listening for event streams on host {bind}");
+    if let Err(err) = app.listen(bind).await {
+        error!("Event stream listener failed: {err:?}");
+    }
+}
+
+/// Create the event stream server
+///
+/// Browsers cannot set headers when opening an event stream, so the
+/// session token may also be sent as a cookie. Cookies are attached to
+/// requests automatically, hence only the app may make cross-origin
+/// requests and messages must carry the token in a header.
+fn app(origin: &str) -> tide::Server<()> {
+    let mut app = tide::new();
+
+    app.with(
+        CorsMiddleware::new()
+            .allow_methods("GET, POST, OPTIONS".parse::<HeaderValue>().unwrap())
+            .allow_headers(
+                "content-type, x-session-token"
+                    .parse::<HeaderValue>()
+                    .unwrap(),
+            )
+            .allow_origin(Origin::from(origin.trim_end_matches('/')))
+            .allow_credentials(true),
+    );
+
+    app.at("/").get(stream).post(command);
+    app
+}
+
+/// Find the session token sent with a request
+///
+/// Cookies are only accepted when opening a stream, any site can post a
+/// form which the browser sends along with the user's cookies.
+fn session_token(req: &Request<()>) -> Option<String> {
+    let token = req
+        .header("x-session-token")
+        .map(|value| value.as_str().to_owned());
+
+    if req.method() == Method::Get {
+        token.or_else(|| {
+            req.cookie(SESSION_COOKIE)
+                .map(|cookie| cookie.value().to_owned())
+        })
+    } else {
+        token
+    }
+}
+
+/// Build a response carrying an error
+fn error_response(status: StatusCode, error: &Error) -> tide::Result {
+    Ok(Response::builder(status)
+        .body(Body::from_json(error)?)
+        .build())
+}
+
+/// Open a new event stream
+async fn stream(req: Request<()>) -> tide::Result {
+    let Some(token) = session_token(&req) else {
+        return error_response(StatusCode::Unauthorized, &create_error!(InvalidSession));
+    };
+
+    let query = match req.query::<StreamQuery>() {
+        Ok(query) => query,
+        Err(err) => {
+            return error_response(
+                StatusCode::BadRequest,
+                &create_error!(FailedValidation {
+                    error: err.to_string()
+                }),
+            )
+        }
+    };
+
+    let db = get_db();
+    let (user, session_id, intents) =
+        match connection::authenticate(db, &token, query.intents).await {
+            Ok(result) => result,
+            Err(err) => return error_response(StatusCode::Unauthorized, &err),
+        };
+
+    let peer = req.remote().unwrap_or("unknown").to_owned();
+    info!("User {peer} opened an event stream as @{}", user.username);
+
+    Ok(sse::upgrade(req, move |_, sender| {
+        let peer = peer.clone();
+        let session_id = session_id.clone();
+        let state = State::from(user.clone(), session_id.clone(), intents);
+
+        async move {
+            // Register this stream so messages can be posted to it,
+            // this replaces and closes any existing stream for the session.
+            let registration = Ulid::new().to_string();
+            let (incoming_s, incoming_r) = async_channel::unbounded();
+            let (outgoing_s, outgoing_r) = async_channel::bounded(OUTGOING_BUFFER);
+
+            STREAMS
+                .lock()
+                .expect("streams lock poisoned")
+                .insert(session_id.clone(), (registration.clone(), incoming_s));
+
+            join!(
+                connection::serve(db, &peer, state, incoming_r, outgoing_s),
+                writer(sender, outgoing_r, &session_id, &registration),
+            );
+
+            info!("User {peer} closed their event stream");
+            Ok(())
+        }
+    }))
+}
+
+/// Write messages from the connection to the event stream
+///
+/// Unregisters the stream once the client goes away, which
+/// in turn tells the connection to stop.
+async fn writer(
+    sender: sse::Sender,
+    outgoing: async_channel::Receiver<Outgoing>,
+    session_id: &str,
+    registration: &str,
+) {
+    loop {
+        let result = match timeout(HEARTBEAT_INTERVAL, outgoing.recv()).await {
+            Ok(Ok(message)) => {
+                let data = serde_json::to_string(&message).expect("Failed to serialise (as json).");
+                sender.send("message", data, None).await
+            }
+            Ok(Err(_)) => break,
+            Err(_) => sender.send("heartbeat", "", None).await,
+        };
+
+        if result.is_err() {
+            break;
+        }
+    }
+
+    let mut streams = STREAMS.lock().expect("streams lock poisoned");
+    if streams
+        .get(session_id)
+        .is_some_and(|(id, _)| id == registration)
+    {
+        streams.remove(session_id);
+    }
+}
+
+/// Send a message to an open event stream
+async fn command(mut req: Request<()>) -> tide::Result {
+    let Some(token) = session_token(&req) else {
+        return error_response(StatusCode::Unauthorized, &create_error!(InvalidSession));
+    };
+
+    let message: ClientMessage = match req.body_json().await {
+        Ok(message) => message,
+        Err(err) => {
+            return error_response(
+                StatusCode::BadRequest,
+                &create_error!(FailedValidation {
+                    error: err.to_string()
+                }),
+            )
+        }
+    };
+
+    // Streams are authenticated when they are opened.
+    if let ClientMessage::Authenticate { .. } = message {
+        return error_response(StatusCode::BadRequest, &create_error!(InvalidOperation));
+    }
+
+    let (_, session_id) = match User::from_token(get_db(), &token, UserHint::Any).await {
+        Ok(result) => result,
+        Err(err) => return error_response(StatusCode::Unauthorized, &err),
+    };
+
+    let sender = STREAMS
+        .lock()
+        .expect("streams lock poisoned")
+        .get(&session_id)
+        .map(|(_, sender)| sender.clone());
+
+    match sender {
+        Some(sender) if sender.send(message).await.is_ok() => {
+            Ok(Response::new(StatusCode::NoContent))
+        }
+        _ => error_response(StatusCode::NotFound, &create_error!(NotFound)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tide::http::{Method, Request, Response, StatusCode, Url};
+
+    use super::app;
+
+    const APP: &str = "https://app.revolt.test";
+
+    fn request(method: Method, url: &str) -> Request {
+        Request::new(method, Url::parse(url).unwrap())
+    }
+
+    fn authenticate() -> Request {
+        let mut req = request(Method::Post, "http://events.test/");
+        req.set_body(r#"{"type":"Authenticate","token":"token"}"#);
+        req.set_content_type("application/json".into());
+        req
+    }
+
+    async fn status(req: Request) -> StatusCode {
+        let res: Response = app(APP).respond(req).await.unwrap();
+        res.status()
+    }
+
+    #[async_std::test]
+    async fn requires_a_session_token() {
+        let req = request(Method::Get, "http://events.test/");
+        assert_eq!(status(req).await, StatusCode::Unauthorized);
+
+        // Tokens must not end up in URLs, which are routinely logged.
+        let req = request(Method::Get, "http://events.test/?token=token");
+        assert_eq!(status(req).await, StatusCode::Unauthorized);
+
+        assert_eq!(status(authenticate()).await, StatusCode::Unauthorized);
+    }
+
+    #[async_std::test]
+    async fn accepts_session_token_from_header() {
+        // Authenticate is refused once the token has been found.
+        let mut req = authenticate();
+        req.insert_header("x-session-token", "token");
+        assert_eq!(status(req).await, StatusCode::BadRequest);
+    }
+
+    #[async_std::test]
+    async fn rejects_messages_authenticated_by_cookie() {
+        let mut req = authenticate();
+        req.insert_header("cookie", "session_token=token");
+        assert_eq!(status(req).await, StatusCode::Unauthorized);
+
+        // Forms may be posted from any site with the user's cookies attached.
+        let mut req = request(Method::Post, "http://events.test/");
+        req.set_body(r#"{"type":"Ping","data":0}"#);
+        req.set_content_type("text/plain".into());
+        req.insert_header("cookie", "session_token=token");
+        assert_eq!(status(req).await, StatusCode::Unauthorized);
+    }
+
+    #[async_std::test]
+    async fn only_allows_the_app_origin() {
+        let mut req = request(Method::Options, "http://events.test/");
+        req.insert_header("origin", APP);
+        let res: Response = app(&format!("{APP}/")).respond(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(
+            res.header("access-control-allow-origin").unwrap().as_str(),
+            APP
+        );
+        assert_eq!(
+            res.header("access-control-allow-credentials")
+                .unwrap()
+                .as_str(),
+            "true"
+        );
+
+        let mut req = request(Method::Options, "http://events.test/");
+        req.insert_header("origin", "https://elsewhere.test");
+        assert_eq!(status(req).await, StatusCode::Unauthorized);
+    }
+}
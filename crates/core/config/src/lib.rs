@@ -36,9 +36,23 @@ static CONFIG_BUILDER: Lazy<RwLock<Config>> = Lazy::new(|| {
 #[derive(Deserialize, Debug, Clone)]
 pub struct Database {
     pub mongodb: String,
+    pub postgres: String,
+    pub sqlite: String,
     pub redis: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum EventBroker {
+    Redis,
+    Memory,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Events {
+    pub broker: EventBroker,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Hosts {
     pub app: String,
@@ -84,6 +98,11 @@ pub struct ApiApn {
     pub team_id: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiPush {
+    pub coalesce_window: u64,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiSecurityCaptcha {
     pub hcaptcha_key: String,
@@ -101,6 +120,74 @@ pub struct ApiSecurity {
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiWorkers {
     pub max_concurrent_connections: usize,
+    pub max_attempts: i32,
+    pub backoff_base: u64,
+    pub backoff_max: u64,
+    pub visibility_timeout: u64,
+    pub batch_size: i64,
+    pub dead_job_retention: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiCache {
+    pub capacity: usize,
+    pub ttl: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiEmailDigest {
+    pub offline_threshold: u64,
+    pub interval: u64,
+    pub max_messages: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiUnifiedPush {
+    pub timeout: u64,
+    pub max_failures: i32,
+    pub concurrency: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiEmbeds {
+    pub max_size: usize,
+    pub timeout: u64,
+    pub max_redirects: usize,
+    pub cache_capacity: usize,
+    pub cache_ttl: u64,
+    pub allowed_domains: Vec<String>,
+    pub denied_domains: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStorage {
+    Disabled,
+    Local,
+    S3,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiFilesLocal {
+    pub path: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiFilesS3 {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiFiles {
+    pub orphan_grace_period: u64,
+    pub sweep_interval: u64,
+    pub storage: FileStorage,
+    pub local: ApiFilesLocal,
+    pub s3: ApiFilesS3,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -110,8 +197,14 @@ pub struct Api {
     pub vapid: ApiVapid,
     pub fcm: ApiFcm,
     pub apn: ApiApn,
+    pub push: ApiPush,
     pub security: ApiSecurity,
     pub workers: ApiWorkers,
+    pub cache: ApiCache,
+    pub files: ApiFiles,
+    pub embeds: ApiEmbeds,
+    pub email_digest: ApiEmailDigest,
+    pub unified_push: ApiUnifiedPush,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -170,6 +263,7 @@ pub struct Sentry {
 #[derive(Deserialize, Debug, Clone)]
 pub struct Settings {
     pub database: Database,
+    pub events: Events,
     pub hosts: Hosts,
     pub api: Api,
     pub features: Features,
@@ -0,0 +1,240 @@
+use std::time::{Duration, SystemTime};
+
+use iso8601_timestamp::Timestamp;
+use revolt_models::v0::{self, Embed};
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission, PermissionQuery};
+use revolt_result::Result;
+use validator::Validate;
+
+use crate::{
+    tasks,
+    util::{
+        idempotency::IdempotencyKey, permissions::DatabasePermissionQuery, reference::Reference,
+    },
+    AllowedMentions, Database, Interactions, Message, PartialMessage, User,
+};
+
+/// Minimum account age before users may mention others in discoverable servers
+const MENTION_MINIMUM_AGE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Send a message to a channel
+pub async fn send_message(
+    db: &Database,
+    user: &User,
+    target: &Reference,
+    data: v0::DataMessageSend,
+    idempotency: IdempotencyKey,
+) -> Result<v0::Message> {
+    data.validate().map_err(|error| {
+        create_error!(FailedValidation {
+            error: error.to_string()
+        })
+    })?;
+
+    // Ensure we have permissions to send a message
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, user).channel(&channel);
+    let permissions = calculate_channel_permissions(&mut query).await;
+    permissions.throw_if_lacking_channel_permission(ChannelPermission::SendMessage)?;
+
+    // Verify permissions for masquerade
+    if let Some(masq) = &data.masquerade {
+        permissions.throw_if_lacking_channel_permission(ChannelPermission::Masquerade)?;
+
+        if masq.colour.is_some() {
+            permissions.throw_if_lacking_channel_permission(ChannelPermission::ManageRole)?;
+        }
+    }
+
+    // Check permissions for embeds
+    if data.embeds.as_ref().is_some_and(|v| !v.is_empty()) {
+        permissions.throw_if_lacking_channel_permission(ChannelPermission::SendEmbeds)?;
+    }
+
+    // Check permissions for files
+    if data.attachments.as_ref().is_some_and(|v| !v.is_empty()) {
+        permissions.throw_if_lacking_channel_permission(ChannelPermission::UploadFiles)?;
+    }
+
+    // Ensure interactions information is correct
+    if let Some(interactions) = &data.interactions {
+        let interactions: Interactions = interactions.clone().into();
+        interactions.validate(db, &permissions).await?;
+    }
+
+    // Disallow mentions for new users (TRUST-0: <12 hours age) in public servers
+    let allow_mentions = if let Some(server) = query.server_ref() {
+        if server.discoverable {
+            SystemTime::now()
+                .duration_since(ulid::Ulid::from_string(&user.id).unwrap().datetime())
+                .map_or(false, |age| age >= MENTION_MINIMUM_AGE)
+        } else {
+            true
+        }
+    } else {
+        true
+    };
+
+    // Create the message
+    let author: v0::User = user.clone().into(db, Some(user)).await;
+
+    // Make sure we have server member (edge case if server owner)
+    query.are_we_a_member().await;
+
+    // Create model user / members
+    let model_user = user
+        .clone()
+        .into_known_static(revolt_presence::is_online(&user.id).await);
+
+    let model_member: Option<v0::Member> = query
+        .member_ref()
+        .as_ref()
+        .map(|member| member.clone().into_owned().into());
+
+    Ok(Message::create_from_api(
+        db,
+        channel,
+        data,
+        v0::MessageAuthor::User(&author),
+        Some(model_user.clone()),
+        model_member.clone(),
+        user.limits().await,
+        idempotency,
+        permissions.has_channel_permission(ChannelPermission::SendEmbeds),
+        AllowedMentions {
+            users: allow_mentions,
+            roles: allow_mentions
+                && permissions.has_channel_permission(ChannelPermission::MentionRoles),
+            everyone: allow_mentions
+                && permissions.has_channel_permission(ChannelPermission::MentionEveryone),
+        },
+    )
+    .await?
+    .into_model(Some(model_user), model_member))
+}
+
+/// Edit a message previously sent by the user
+pub async fn edit_message(
+    db: &Database,
+    user: &User,
+    target: &Reference,
+    msg: &Reference,
+    edit: v0::DataEditMessage,
+) -> Result<v0::Message> {
+    edit.validate().map_err(|error| {
+        create_error!(FailedValidation {
+            error: error.to_string()
+        })
+    })?;
+
+    Message::validate_sum(
+        &edit.content,
+        edit.embeds.as_deref().unwrap_or_default(),
+        user.limits().await.message_length,
+    )?;
+
+    // Ensure we have permissions to send a message
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, user).channel(&channel);
+    let permissions = calculate_channel_permissions(&mut query).await;
+
+    permissions.throw_if_lacking_channel_permission(ChannelPermission::SendMessage)?;
+
+    let mut message = msg.as_message_in_channel(db, &channel.id()).await?;
+    if message.author != user.id {
+        return Err(create_error!(CannotEditMessage));
+    }
+
+    message.edited = Some(Timestamp::now_utc());
+    let mut partial = PartialMessage {
+        edited: message.edited,
+        ..Default::default()
+    };
+
+    // 1. Handle content update
+    if let Some(content) = &edit.content {
+        partial.content = Some(content.clone());
+    }
+
+    // 2. Clear any auto generated embeds
+    let mut new_embeds: Vec<Embed> = vec![];
+    if let Some(embeds) = &message.embeds {
+        for embed in embeds {
+            if let Embed::Text(embed) = embed {
+                new_embeds.push(Embed::Text(embed.clone()))
+            }
+        }
+    }
+
+    // 3. Replace if we are given new embeds
+    if let Some(embeds) = edit.embeds {
+        // Ensure we have permissions to send embeds
+        permissions.throw_if_lacking_channel_permission(ChannelPermission::SendEmbeds)?;
+
+        new_embeds.clear();
+
+        for embed in embeds {
+            new_embeds.push(message.create_embed(db, embed).await?);
+        }
+    }
+
+    partial.embeds = Some(new_embeds);
+
+    message.update(db, partial).await?;
+
+    // Queue up a task for processing embeds if the we have sufficient permissions
+    if permissions.has_channel_permission(ChannelPermission::SendEmbeds) {
+        if let Some(content) = edit.content {
+            tasks::process_embeds::queue(
+                db,
+                message.channel.to_string(),
+                message.id.to_string(),
+                content,
+            )
+            .await;
+        }
+    }
+
+    Ok(message.into_model(None, None))
+}
+
+/// Acknowledge a message in a channel
+pub async fn ack_message(
+    db: &Database,
+    user: &User,
+    target: &Reference,
+    message: &Reference,
+) -> Result<()> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
+
+    channel.ack(db, &user.id, &message.id).await
+}
+
+/// React to a message in a channel
+pub async fn react_message(
+    db: &Database,
+    user: &User,
+    target: &Reference,
+    msg: &Reference,
+    emoji: &Reference,
+) -> Result<()> {
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::React)?;
+
+    // Fetch relevant message
+    let message = msg.as_message_in_channel(db, &channel.id()).await?;
+
+    // Add the reaction
+    message.add_reaction(db, user, &emoji.id).await
+}
@@ -0,0 +1,6 @@
+//! Actions performed on behalf of users
+//!
+//! These are shared between the API and the events server so that
+//! both apply the same validation and permission checks.
+
+pub mod messages;
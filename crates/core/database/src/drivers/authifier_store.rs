@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use authifier::models::{Account, Invite, Session};
+use futures::lock::Mutex;
+use revolt_result::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::Database;
+
+/// Documents as they were last written to the database, by their id
+#[derive(Default)]
+struct Persisted {
+    accounts: HashMap<String, Value>,
+    sessions: HashMap<String, Value>,
+    invites: HashMap<String, Value>,
+}
+
+/// Accounts and sessions kept in a SQL database on behalf of Authifier
+///
+/// Authifier can only store its data in MongoDB or in memory. On the SQL
+/// drivers its in-memory store is filled from the `accounts`, `sessions` and
+/// `invites` tables on startup, and [AuthifierStore::persist] writes back
+/// whatever has changed since.
+///
+/// The API must be the only process changing these tables while it runs.
+/// MFA tickets only last a few minutes, so they are kept in memory.
+#[derive(Clone)]
+pub struct AuthifierStore {
+    db: Database,
+    authifier_db: authifier::Database,
+    persisted: Arc<Mutex<Persisted>>,
+}
+
+/// Serialise a document for comparison with what was last written
+fn to_document<T: Serialize>(document: &T, table: &'static str) -> Result<Value> {
+    serde_json::to_value(document).map_err(|_| create_database_error!("to_value", table))
+}
+
+impl AuthifierStore {
+    /// Load every account, session and invite into a new in-memory store
+    pub async fn load(db: Database) -> Result<AuthifierStore> {
+        let store = AuthifierStore {
+            db,
+            authifier_db: authifier::Database::default(),
+            persisted: Default::default(),
+        };
+
+        let mut persisted = store.persisted.lock().await;
+        for (id, account) in store.find::<Account>("accounts").await? {
+            store
+                .authifier_db
+                .save_account(&account)
+                .await
+                .map_err(|_| create_database_error!("save_account", "accounts"))?;
+            persisted
+                .accounts
+                .insert(id, to_document(&account, "accounts")?);
+        }
+
+        for (id, session) in store.find::<Session>("sessions").await? {
+            store
+                .authifier_db
+                .save_session(&session)
+                .await
+                .map_err(|_| create_database_error!("save_session", "sessions"))?;
+            persisted
+                .sessions
+                .insert(id, to_document(&session, "sessions")?);
+        }
+
+        for (id, invite) in store.find::<Invite>("invites").await? {
+            store
+                .authifier_db
+                .save_invite(&invite)
+                .await
+                .map_err(|_| create_database_error!("save_invite", "invites"))?;
+            persisted
+                .invites
+                .insert(id, to_document(&invite, "invites")?);
+        }
+
+        drop(persisted);
+        Ok(store)
+    }
+
+    /// Authifier database backed by this store
+    pub fn database(&self) -> authifier::Database {
+        self.authifier_db.clone()
+    }
+
+    /// Start tracking an account created since the store was loaded
+    pub async fn track_account(&self, id: &str) {
+        self.persisted
+            .lock()
+            .await
+            .accounts
+            .entry(id.to_string())
+            .or_insert(Value::Null);
+    }
+
+    /// Write back every account, session and invite which changed since they
+    /// were last written
+    ///
+    /// Every account is looked at, so this should only run after something may
+    /// have changed them.
+    pub async fn persist(&self) -> Result<()> {
+        let mut persisted = self.persisted.lock().await;
+
+        let mut accounts = HashMap::new();
+        let mut sessions = HashMap::new();
+        for id in persisted.accounts.keys() {
+            // Accounts which can't be found have been removed.
+            if let Ok(account) = self.authifier_db.find_account(id).await {
+                accounts.insert(id.clone(), to_document(&account, "accounts")?);
+            }
+
+            for session in self
+                .authifier_db
+                .find_sessions(id)
+                .await
+                .map_err(|_| create_database_error!("find_sessions", "sessions"))?
+            {
+                sessions.insert(session.id.clone(), to_document(&session, "sessions")?);
+            }
+        }
+
+        let mut invites = HashMap::new();
+        for id in persisted.invites.keys() {
+            if let Ok(invite) = self.authifier_db.find_invite(id).await {
+                invites.insert(id.clone(), to_document(&invite, "invites")?);
+            }
+        }
+
+        self.write_changes("accounts", &mut persisted.accounts, accounts)
+            .await?;
+        self.write_changes("sessions", &mut persisted.sessions, sessions)
+            .await?;
+        self.write_changes("invites", &mut persisted.invites, invites)
+            .await
+    }
+
+    /// Bring a table in line with the current documents, given what was last written to it
+    async fn write_changes(
+        &self,
+        table: &'static str,
+        persisted: &mut HashMap<String, Value>,
+        current: HashMap<String, Value>,
+    ) -> Result<()> {
+        let removed: Vec<String> = persisted
+            .keys()
+            .filter(|id| !current.contains_key(*id))
+            .cloned()
+            .collect();
+
+        for id in removed {
+            self.delete(table, &id).await?;
+            persisted.remove(&id);
+        }
+
+        for (id, document) in current {
+            if persisted.get(&id) != Some(&document) {
+                self.save(table, &id, document.clone()).await?;
+                persisted.insert(id, document);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetch every document in a table alongside its id
+    async fn find<T: DeserializeOwned>(&self, table: &'static str) -> Result<Vec<(String, T)>> {
+        let documents: Vec<Value> = match &self.db {
+            #[cfg(feature = "postgres")]
+            Database::Postgres(db) => db.find(table, json!({})).await,
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(db) => db.find(table, json!({})).await,
+            _ => Ok(vec![]),
+        }
+        .map_err(|_| create_database_error!("find", table))?;
+
+        documents
+            .into_iter()
+            .map(|document| {
+                let id = document["_id"].as_str().unwrap_or_default().to_string();
+                serde_json::from_value(document)
+                    .map(|document| (id, document))
+                    .map_err(|_| create_database_error!("from_value", table))
+            })
+            .collect()
+    }
+
+    /// Replace a document, creating it if it doesn't exist
+    async fn save(&self, table: &'static str, id: &str, document: Value) -> Result<()> {
+        match &self.db {
+            #[cfg(feature = "postgres")]
+            Database::Postgres(db) => {
+                db.upsert_one(table, json!(id), |existing| *existing = document)
+                    .await
+            }
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(db) => {
+                db.upsert_one(table, json!(id), |existing| *existing = document)
+                    .await
+            }
+            _ => Ok(()),
+        }
+        .map_err(|_| create_database_error!("upsert_one", table))
+    }
+
+    /// Delete a document by its id
+    async fn delete(&self, table: &'static str, id: &str) -> Result<()> {
+        match &self.db {
+            #[cfg(feature = "postgres")]
+            Database::Postgres(db) => db.delete_one_by_id(table, id).await.map(|_| ()),
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(db) => db.delete_one_by_id(table, id).await.map(|_| ()),
+            _ => Ok(()),
+        }
+        .map_err(|_| create_database_error!("delete_one", table))
+    }
+}
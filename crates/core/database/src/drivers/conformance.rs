@@ -0,0 +1,714 @@
+//! Scenarios every database driver must behave identically in
+//!
+//! Drivers are registered with [conformance_suite] at the bottom of this file,
+//! which generates one test per scenario against that driver.
+
+use std::future::Future;
+
+use revolt_models::v0::{MessageSort, PushNotification};
+use ulid::Ulid;
+
+use crate::{
+    AbstractChannelUnreads, AbstractMessages, AbstractMigrations, AbstractPushWindows,
+    AbstractUsers, Database, DatabaseInfo, FieldsUser, Message, MessageFilter, MessageQuery,
+    MessageTimePeriod, PartialMessage, PartialUser, Presence, User, UserStatus,
+};
+
+/// Connect to an empty database on the given driver and run a scenario against it
+///
+/// Drivers which depend on an external service only run when selected by `TEST_DB`.
+async fn run<F, Fut>(driver: &str, scenario: &str, test: F)
+where
+    F: FnOnce(Database) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    if driver != "REFERENCE" && std::env::var("TEST_DB").as_deref() != Ok(driver) {
+        return;
+    }
+
+    let db = DatabaseInfo::test_driver(
+        driver,
+        format!("revolt_conformance_{}_{scenario}", driver.to_lowercase()),
+    )
+    .await
+    .connect()
+    .await
+    .expect("Database connection failed.");
+
+    db.drop_database().await;
+    test(db.clone()).await;
+    db.drop_database().await;
+}
+
+/// Message ID which sorts after every ID generated with a smaller index
+fn message_id(index: u64) -> String {
+    Ulid::from_parts(1_700_000_000_000 + index, 0).to_string()
+}
+
+/// Insert a message into a channel and return its ID
+async fn insert_message(db: &Database, index: u64, channel: &str, content: &str) -> String {
+    let message = Message {
+        id: message_id(index),
+        channel: channel.to_string(),
+        author: format!("author_{}", index % 2),
+        content: Some(content.to_string()),
+        ..Default::default()
+    };
+
+    db.insert_message(&message).await.unwrap();
+    message.id
+}
+
+/// Fetch messages in a channel and return their IDs in the order given
+async fn fetch_ids(
+    db: &Database,
+    channel: &str,
+    limit: i64,
+    time_period: MessageTimePeriod,
+) -> Vec<String> {
+    db.fetch_messages(MessageQuery {
+        limit: Some(limit),
+        filter: MessageFilter {
+            channel: Some(channel.to_string()),
+            ..Default::default()
+        },
+        time_period,
+    })
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|message| message.id)
+    .collect()
+}
+
+/// Time period between two message IDs with the given sort
+fn absolute(
+    before: Option<&String>,
+    after: Option<&String>,
+    sort: MessageSort,
+) -> MessageTimePeriod {
+    MessageTimePeriod::Absolute {
+        before: before.cloned(),
+        after: after.cloned(),
+        sort: Some(sort),
+    }
+}
+
+async fn crud(db: Database) {
+    let id = insert_message(&db, 0, "channel", "Hello").await;
+    let other = insert_message(&db, 1, "channel", "World").await;
+
+    // IDs are unique
+    assert!(db
+        .insert_message(&Message {
+            id: id.clone(),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+    assert_eq!(
+        db.fetch_message(&id).await.unwrap().content.as_deref(),
+        Some("Hello")
+    );
+
+    // Unknown IDs are skipped
+    let mut found: Vec<String> = db
+        .fetch_messages_by_id(&[id.clone(), other.clone(), message_id(99)])
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|message| message.id)
+        .collect();
+    found.sort();
+    assert_eq!(found, vec![id.clone(), other.clone()]);
+
+    db.update_message(
+        &id,
+        &PartialMessage {
+            content: Some("Hello, world!".to_string()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let message = db.fetch_message(&id).await.unwrap();
+    assert_eq!(message.content.as_deref(), Some("Hello, world!"));
+    assert_eq!(message.channel, "channel");
+
+    db.delete_message(&id).await.unwrap();
+    assert!(db.fetch_message(&id).await.is_err());
+
+    // Only the given IDs in the given channel are deleted
+    let kept = insert_message(&db, 2, "other", "Elsewhere").await;
+    db.delete_messages("channel", &[other.clone(), kept.clone()])
+        .await
+        .unwrap();
+    assert!(db.fetch_message(&other).await.is_err());
+    assert!(db.fetch_message(&kept).await.is_ok());
+}
+
+async fn partial_updates(db: Database) {
+    let user = User {
+        id: Ulid::new().to_string(),
+        username: "Conformance".to_string(),
+        discriminator: "0001".to_string(),
+        display_name: Some("Before".to_string()),
+        status: Some(UserStatus {
+            text: Some("Testing".to_string()),
+            presence: Some(Presence::Busy),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    db.insert_user(&user).await.unwrap();
+
+    // Set and remove fields at the same time
+    db.update_user(
+        &user.id,
+        &PartialUser {
+            display_name: Some("After".to_string()),
+            ..Default::default()
+        },
+        vec![FieldsUser::StatusText],
+    )
+    .await
+    .unwrap();
+
+    let updated = db.fetch_user(&user.id).await.unwrap();
+    assert_eq!(updated.display_name.as_deref(), Some("After"));
+    assert_eq!(
+        updated.status,
+        Some(UserStatus {
+            text: None,
+            presence: Some(Presence::Busy),
+            ..Default::default()
+        })
+    );
+
+    // Remove a top level field, leaving everything else untouched
+    db.update_user(
+        &user.id,
+        &PartialUser {
+            badges: Some(1),
+            ..Default::default()
+        },
+        vec![FieldsUser::DisplayName],
+    )
+    .await
+    .unwrap();
+
+    let updated = db.fetch_user(&user.id).await.unwrap();
+    assert_eq!(updated.display_name, None);
+    assert_eq!(updated.badges, Some(1));
+    assert_eq!(updated.username, "Conformance");
+
+    // Usernames are matched regardless of case
+    assert_eq!(
+        db.fetch_user_by_username("conformance", "0001")
+            .await
+            .unwrap()
+            .id,
+        user.id
+    );
+}
+
+async fn pagination(db: Database) {
+    let ids: Vec<String> = futures::future::join_all(
+        (0..10).map(|index| insert_message(&db, index, "channel", "Message")),
+    )
+    .await;
+    insert_message(&db, 10, "other", "Message").await;
+
+    let latest = fetch_ids(&db, "channel", 3, absolute(None, None, MessageSort::Latest)).await;
+    assert_eq!(latest, vec![ids[9].clone(), ids[8].clone(), ids[7].clone()]);
+
+    let oldest = fetch_ids(&db, "channel", 3, absolute(None, None, MessageSort::Oldest)).await;
+    assert_eq!(oldest, ids[..3].to_vec());
+
+    // Bounds are exclusive
+    let before = fetch_ids(
+        &db,
+        "channel",
+        50,
+        absolute(Some(&ids[5]), None, MessageSort::Latest),
+    )
+    .await;
+    assert_eq!(before, ids[..5].iter().rev().cloned().collect::<Vec<_>>());
+
+    let after = fetch_ids(
+        &db,
+        "channel",
+        50,
+        absolute(None, Some(&ids[5]), MessageSort::Oldest),
+    )
+    .await;
+    assert_eq!(after, ids[6..].to_vec());
+
+    let between = fetch_ids(
+        &db,
+        "channel",
+        50,
+        absolute(Some(&ids[7]), Some(&ids[3]), MessageSort::Latest),
+    )
+    .await;
+    assert_eq!(
+        between,
+        vec![ids[6].clone(), ids[5].clone(), ids[4].clone()]
+    );
+
+    // Nothing past the last message
+    let past = fetch_ids(
+        &db,
+        "channel",
+        50,
+        absolute(None, Some(&ids[9]), MessageSort::Oldest),
+    )
+    .await;
+    assert!(past.is_empty());
+
+    // Relevance without a search falls back to latest
+    let relevance = fetch_ids(
+        &db,
+        "channel",
+        2,
+        absolute(None, None, MessageSort::Relevance),
+    )
+    .await;
+    assert_eq!(relevance, vec![ids[9].clone(), ids[8].clone()]);
+
+    // Filter by author
+    let by_author: Vec<String> = db
+        .fetch_messages(MessageQuery {
+            limit: Some(50),
+            filter: MessageFilter {
+                channel: Some("channel".to_string()),
+                author: Some("author_1".to_string()),
+                ..Default::default()
+            },
+            time_period: absolute(None, None, MessageSort::Oldest),
+        })
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|message| message.id)
+        .collect();
+    assert_eq!(
+        by_author,
+        ids.iter().skip(1).step_by(2).cloned().collect::<Vec<_>>()
+    );
+}
+
+async fn nearby(db: Database) {
+    let ids: Vec<String> = futures::future::join_all(
+        (0..10).map(|index| insert_message(&db, index, "channel", "Message")),
+    )
+    .await;
+
+    // Newer messages (including the target) come first, then older messages
+    let around = fetch_ids(
+        &db,
+        "channel",
+        4,
+        MessageTimePeriod::Relative {
+            nearby: ids[5].clone(),
+        },
+    )
+    .await;
+    assert_eq!(
+        around,
+        vec![
+            ids[5].clone(),
+            ids[6].clone(),
+            ids[7].clone(),
+            ids[4].clone(),
+            ids[3].clone()
+        ]
+    );
+
+    let start = fetch_ids(
+        &db,
+        "channel",
+        4,
+        MessageTimePeriod::Relative {
+            nearby: ids[0].clone(),
+        },
+    )
+    .await;
+    assert_eq!(start, ids[..3].to_vec());
+
+    let end = fetch_ids(
+        &db,
+        "channel",
+        4,
+        MessageTimePeriod::Relative {
+            nearby: ids[9].clone(),
+        },
+    )
+    .await;
+    assert_eq!(end, vec![ids[9].clone(), ids[8].clone(), ids[7].clone()]);
+}
+
+async fn search(db: Database) {
+    let first = insert_message(&db, 0, "channel", "Hello world").await;
+    let second = insert_message(&db, 1, "channel", "well hello there").await;
+    insert_message(&db, 2, "channel", "Goodbye world").await;
+    insert_message(&db, 3, "other", "Hello from elsewhere").await;
+
+    for sort in [
+        MessageSort::Latest,
+        MessageSort::Oldest,
+        MessageSort::Relevance,
+    ] {
+        let mut found: Vec<String> = db
+            .fetch_messages(MessageQuery {
+                limit: Some(50),
+                filter: MessageFilter {
+                    channel: Some("channel".to_string()),
+                    query: Some("HELLO".to_string()),
+                    ..Default::default()
+                },
+                time_period: absolute(None, None, sort.clone()),
+            })
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| message.id)
+            .collect();
+
+        if !matches!(sort, MessageSort::Relevance) {
+            assert!(found.windows(2).all(|pair| match sort {
+                MessageSort::Oldest => pair[0] < pair[1],
+                _ => pair[0] > pair[1],
+            }));
+        }
+
+        found.sort();
+        assert_eq!(found, vec![first.clone(), second.clone()]);
+    }
+}
+
+async fn reactions(db: Database) {
+    let id = insert_message(&db, 0, "channel", "React to me").await;
+
+    db.add_reaction(&id, "👍", "user_a").await.unwrap();
+    db.add_reaction(&id, "👍", "user_a").await.unwrap();
+    db.add_reaction(&id, "👍", "user_b").await.unwrap();
+    db.add_reaction(&id, "🎉", "user_a").await.unwrap();
+
+    let message = db.fetch_message(&id).await.unwrap();
+    assert_eq!(
+        message.reactions["👍"].iter().collect::<Vec<_>>(),
+        vec!["user_a", "user_b"]
+    );
+
+    db.remove_reaction(&id, "👍", "user_a").await.unwrap();
+    let message = db.fetch_message(&id).await.unwrap();
+    assert_eq!(
+        message.reactions["👍"].iter().collect::<Vec<_>>(),
+        vec!["user_b"]
+    );
+
+    db.clear_reaction(&id, "👍").await.unwrap();
+    let message = db.fetch_message(&id).await.unwrap();
+    assert!(!message.reactions.contains_key("👍"));
+    assert_eq!(
+        message.reactions["🎉"].iter().collect::<Vec<_>>(),
+        vec!["user_a"]
+    );
+}
+
+async fn unread_acks(db: Database) {
+    let first = message_id(0);
+    let second = message_id(1);
+    let read = message_id(2);
+
+    // Mentions accumulate
+    db.add_mention_to_unread("channel", "user", &[first.clone()])
+        .await
+        .unwrap();
+    db.add_mention_to_unread("channel", "user", &[second.clone()])
+        .await
+        .unwrap();
+    db.add_mention_to_unread("channel", "other_user", &[first.clone()])
+        .await
+        .unwrap();
+
+    let unreads = db.fetch_unreads("user").await.unwrap();
+    assert_eq!(unreads.len(), 1);
+    assert_eq!(unreads[0].id.channel, "channel");
+    assert_eq!(unreads[0].last_id, None);
+    assert_eq!(unreads[0].mentions, Some(vec![first, second]));
+
+    // Acknowledging clears mentions
+    db.acknowledge_message("channel", "user", &read)
+        .await
+        .unwrap();
+
+    let unreads = db.fetch_unreads("user").await.unwrap();
+    assert_eq!(unreads.len(), 1);
+    assert_eq!(unreads[0].last_id.as_deref(), Some(read.as_str()));
+    assert_eq!(unreads[0].mentions, None);
+
+    // Acknowledging many channels marks them as read up to now
+    db.acknowledge_channels("user", &["channel".to_string(), "new_channel".to_string()])
+        .await
+        .unwrap();
+
+    let mut unreads = db.fetch_unreads("user").await.unwrap();
+    unreads.sort_by(|a, b| a.id.channel.cmp(&b.id.channel));
+    assert_eq!(
+        unreads
+            .iter()
+            .map(|unread| unread.id.channel.as_str())
+            .collect::<Vec<_>>(),
+        vec!["channel", "new_channel"]
+    );
+
+    for unread in &unreads {
+        assert!(unread.last_id.as_ref().unwrap() > &read);
+        assert_eq!(unread.mentions, None);
+    }
+
+    // Other users are unaffected
+    let unreads = db.fetch_unreads("other_user").await.unwrap();
+    assert_eq!(unreads.len(), 1);
+    assert_eq!(unreads[0].mentions.as_ref().map(Vec::len), Some(1));
+}
+
+async fn unread_counts(db: Database) {
+    // Authors alternate between author_0 and author_1
+    for index in 0..6 {
+        insert_message(&db, index, "channel", "hello").await;
+    }
+    insert_message(&db, 6, "other_channel", "hello").await;
+
+    let count = |after: Option<String>, limit| {
+        let db = db.clone();
+        async move {
+            db.count_unread_messages("channel", "author_0", after.as_deref(), limit)
+                .await
+                .unwrap()
+        }
+    };
+
+    // Messages sent by the user aren't unread
+    assert_eq!(count(None, 100).await, 3);
+    assert_eq!(count(Some(message_id(1)), 100).await, 2);
+    assert_eq!(count(Some(message_id(5)), 100).await, 0);
+
+    // Counting stops at the limit
+    assert_eq!(count(None, 2).await, 2);
+
+    // Channels can be counted together, leaving out those with nothing unread
+    let channels = [
+        ("channel".to_string(), Some(message_id(1))),
+        ("other_channel".to_string(), None),
+        ("empty_channel".to_string(), None),
+    ];
+    let counts = db
+        .count_unread_messages_in_channels(&channels, "author_1", 100)
+        .await
+        .unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["channel"], 2);
+    assert_eq!(counts["other_channel"], 1);
+
+    let counts = db
+        .count_unread_messages_in_channels(&channels, "author_1", 1)
+        .await
+        .unwrap();
+    assert_eq!(counts["channel"], 1);
+}
+
+async fn push_dismissals(db: Database) {
+    let read = message_id(0);
+    db.acknowledge_message("read", "user", &read).await.unwrap();
+    db.add_mention_to_unread("mentioned", "user", &[message_id(1)])
+        .await
+        .unwrap();
+
+    // Notifications are recorded whether or not the channel has been read before
+    for channel in ["read", "new", "elsewhere"] {
+        db.mark_unread_notified(channel, "user").await.unwrap();
+    }
+    db.mark_unread_notified("read", "other_user").await.unwrap();
+
+    let mut notified = db
+        .take_notified_unreads(
+            "user",
+            &[
+                "read".to_string(),
+                "new".to_string(),
+                "mentioned".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+    notified.sort();
+    assert_eq!(notified, vec!["new", "read"]);
+
+    // Each notification is only dismissed once
+    assert!(db
+        .take_notified_unreads("user", &["read".to_string(), "new".to_string()])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Taking the record leaves the rest of the unread alone
+    let mut unreads = db.fetch_unreads("user").await.unwrap();
+    unreads.sort_by(|a, b| a.id.channel.cmp(&b.id.channel));
+    assert_eq!(
+        unreads
+            .iter()
+            .map(|unread| (unread.id.channel.as_str(), unread.notified))
+            .collect::<Vec<_>>(),
+        vec![
+            ("elsewhere", true),
+            ("mentioned", false),
+            ("new", false),
+            ("read", false)
+        ]
+    );
+    assert_eq!(unreads[3].last_id.as_deref(), Some(read.as_str()));
+    assert_eq!(unreads[1].mentions.as_ref().map(Vec::len), Some(1));
+
+    // Other users are unaffected
+    assert_eq!(
+        db.take_notified_unreads("other_user", &["read".to_string()])
+            .await
+            .unwrap(),
+        vec!["read"]
+    );
+}
+
+async fn push_windows(db: Database) {
+    let notification = |body: &str| PushNotification {
+        author: "author".to_string(),
+        icon: "icon".to_string(),
+        image: None,
+        body: body.to_string(),
+        tag: "channel".to_string(),
+        timestamp: 0,
+        url: "url".to_string(),
+    };
+    let users = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Windows open for users without one, duplicates are only counted once
+    let mut opened = db
+        .hold_push_notification(
+            "channel",
+            &users(&["a", "b", "a"]),
+            &notification("1"),
+            100,
+            0,
+        )
+        .await
+        .unwrap();
+    opened.sort();
+    assert_eq!(opened, vec!["a", "b"]);
+
+    // Users with an open window have further notifications held back
+    let mut opened = db
+        .hold_push_notification("channel", &users(&["a", "c"]), &notification("2"), 100, 0)
+        .await
+        .unwrap();
+    opened.sort();
+    assert_eq!(opened, vec!["c"]);
+    db.hold_push_notification("channel", &users(&["a"]), &notification("3"), 100, 0)
+        .await
+        .unwrap();
+
+    // Windows in other channels are separate
+    assert_eq!(
+        db.hold_push_notification("other_channel", &users(&["a"]), &notification("4"), 100, 0)
+            .await
+            .unwrap(),
+        vec!["a"]
+    );
+
+    // Closing summarises held notifications and removes windows with nothing held back
+    let closed = db
+        .close_push_windows("channel", &users(&["a", "b"]), 200)
+        .await
+        .unwrap();
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].id.user, "a");
+    assert_eq!(closed[0].count, 3);
+    assert_eq!(closed[0].pending, 0);
+    assert_eq!(closed[0].closes_at, 200);
+    assert_eq!(closed[0].latest, Some(notification("3")));
+
+    assert_eq!(
+        db.hold_push_notification("channel", &users(&["a", "b"]), &notification("5"), 300, 0)
+            .await
+            .unwrap(),
+        vec!["b"]
+    );
+
+    // Windows left open past their close are replaced
+    assert_eq!(
+        db.hold_push_notification("channel", &users(&["c"]), &notification("6"), 300, 150)
+            .await
+            .unwrap(),
+        vec!["c"]
+    );
+
+    // Dismissing discards whatever was held back
+    db.delete_push_window("channel", "a").await.unwrap();
+    assert!(db
+        .close_push_windows("channel", &users(&["a"]), 400)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        db.hold_push_notification("channel", &users(&["a"]), &notification("7"), 400, 0)
+            .await
+            .unwrap(),
+        vec!["a"]
+    );
+}
+
+/// Generate a test for every scenario against the given `TEST_DB` driver
+macro_rules! conformance_suite {
+    ( $name:ident, $driver:literal ) => {
+        conformance_suite!(
+            @scenarios $name,
+            $driver,
+            crud,
+            partial_updates,
+            pagination,
+            nearby,
+            search,
+            reactions,
+            unread_acks,
+            unread_counts,
+            push_dismissals,
+            push_windows
+        );
+    };
+    ( @scenarios $name:ident, $driver:literal, $( $scenario:ident ),+ ) => {
+        mod $name {
+            $(
+                #[async_std::test]
+                async fn $scenario() {
+                    super::run($driver, stringify!($scenario), super::$scenario).await;
+                }
+            )+
+        }
+    };
+}
+
+conformance_suite!(reference, "REFERENCE");
+conformance_suite!(mongodb, "MONGODB");
+#[cfg(feature = "postgres")]
+conformance_suite!(postgres, "POSTGRES");
+#[cfg(feature = "sqlite")]
+conformance_suite!(sqlite, "SQLITE");
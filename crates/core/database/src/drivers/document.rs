@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{prefix_keys, IntoDocumentPath};
+
+/// Convert a serialisation failure into a database error
+pub fn encode_error(error: serde_json::Error) -> sqlx::Error {
+    sqlx::Error::Encode(Box::new(error))
+}
+
+/// Changes to apply to a stored document, equivalent to `$unset` followed by `$set`
+pub struct PartialUpdate {
+    unset: Vec<String>,
+    set: HashMap<String, Value>,
+}
+
+impl PartialUpdate {
+    /// Prepare an update from a partial document and list of paths to unset
+    pub fn new<T: Serialize>(
+        partial: T,
+        remove: Vec<&dyn IntoDocumentPath>,
+        prefix: Option<String>,
+    ) -> serde_json::Result<PartialUpdate> {
+        let prefix = prefix.unwrap_or_default();
+        let set = if prefix.is_empty() {
+            serde_json::from_value::<Map<String, Value>>(serde_json::to_value(&partial)?)?
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .collect()
+        } else {
+            prefix_keys(&partial, &prefix)
+        };
+
+        let unset = remove
+            .into_iter()
+            .filter_map(|field| field.as_path())
+            .map(|path| prefix.clone() + path)
+            .collect();
+
+        Ok(PartialUpdate { unset, set })
+    }
+
+    /// Apply the changes to a document
+    pub fn apply(self, document: &mut Value) {
+        for path in &self.unset {
+            remove_path(document, path);
+        }
+
+        for (path, value) in self.set {
+            set_path(document, &path, value);
+        }
+    }
+}
+
+/// Set a value at a dot-separated path, creating any missing objects along the way
+pub fn set_path(document: &mut Value, path: &str, value: Value) {
+    let mut current = document;
+    let mut segments = path.split('.').peekable();
+
+    while let Some(segment) = segments.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+
+        let object = current.as_object_mut().expect("is an object");
+        if segments.peek().is_none() {
+            object.insert(segment.to_owned(), value);
+            return;
+        }
+
+        current = object
+            .entry(segment.to_owned())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Remove the value at a dot-separated path if it exists
+pub fn remove_path(document: &mut Value, path: &str) {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (
+            parent
+                .split('.')
+                .try_fold(document, |current, segment| current.get_mut(segment)),
+            key,
+        ),
+        None => (Some(document), path),
+    };
+
+    if let Some(Value::Object(object)) = parent {
+        object.remove(key);
+    }
+}
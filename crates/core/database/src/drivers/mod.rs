@@ -1,11 +1,39 @@
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod authifier_store;
+#[cfg(test)]
+mod conformance;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod document;
 mod mongodb;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod pool;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::ops::Deref;
+use std::sync::Mutex;
 
 use rand::Rng;
 use revolt_config::config;
+use revolt_result::Result;
+
+use crate::{events::client::EventV1, tasks, util::cache, OutboxEvent};
 
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub use self::authifier_store::*;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub use self::document::*;
 pub use self::mongodb::*;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub use self::pool::*;
+#[cfg(feature = "postgres")]
+pub use self::postgres::*;
 pub use self::reference::*;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::*;
 
 /// Database information to use to create a client
 pub enum DatabaseInfo {
@@ -19,6 +47,12 @@ pub enum DatabaseInfo {
     MongoDb { uri: String, database_name: String },
     /// Use existing MongoDB connection
     MongoDbFromClient(::mongodb::Client, String),
+    /// Connect to PostgreSQL
+    #[cfg(feature = "postgres")]
+    Postgres { uri: String, schema: String },
+    /// Open a SQLite database file, or `:memory:` for a temporary database
+    #[cfg(feature = "sqlite")]
+    Sqlite { path: String },
 }
 
 /// Database
@@ -28,9 +62,38 @@ pub enum Database {
     Reference(ReferenceDb),
     /// MongoDB database
     MongoDb(MongoDb),
+    /// PostgreSQL database
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresDb),
+    /// SQLite database
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteDb),
 }
 
 impl DatabaseInfo {
+    /// Database information for an empty testing database on the given driver
+    async fn test_driver(driver: &str, database_name: String) -> DatabaseInfo {
+        let config = config().await;
+
+        match driver {
+            "REFERENCE" => DatabaseInfo::Reference,
+            "MONGODB" => DatabaseInfo::MongoDb {
+                uri: config.database.mongodb,
+                database_name,
+            },
+            #[cfg(feature = "postgres")]
+            "POSTGRES" => DatabaseInfo::Postgres {
+                uri: config.database.postgres,
+                schema: database_name,
+            },
+            #[cfg(feature = "sqlite")]
+            "SQLITE" => DatabaseInfo::Sqlite {
+                path: ":memory:".to_string(),
+            },
+            _ => unreachable!("must specify REFERENCE, MONGODB, POSTGRES or SQLITE"),
+        }
+    }
+
     /// Create a database client from the given database information
     #[async_recursion]
     pub async fn connect(self) -> Result<Database, String> {
@@ -45,45 +108,231 @@ impl DatabaseInfo {
                     ))
                     .connect()
                     .await?
-                } else if !config.database.mongodb.is_empty() {
-                    DatabaseInfo::MongoDb {
-                        uri: config.database.mongodb,
-                        database_name: "revolt".to_string(),
-                    }
-                    .connect()
-                    .await?
                 } else {
-                    DatabaseInfo::Reference.connect().await?
-                }
-            }
-            DatabaseInfo::Test(database_name) => {
-                match std::env::var("TEST_DB")
-                    .expect("`TEST_DB` environment variable should be set to REFERENCE or MONGODB")
-                    .as_str()
-                {
-                    "REFERENCE" => DatabaseInfo::Reference.connect().await?,
-                    "MONGODB" => {
+                    #[cfg(feature = "postgres")]
+                    if !config.database.postgres.is_empty() {
+                        return DatabaseInfo::Postgres {
+                            uri: config.database.postgres,
+                            schema: "public".to_string(),
+                        }
+                        .connect()
+                        .await;
+                    }
+
+                    #[cfg(feature = "sqlite")]
+                    if !config.database.sqlite.is_empty() {
+                        return DatabaseInfo::Sqlite {
+                            path: config.database.sqlite,
+                        }
+                        .connect()
+                        .await;
+                    }
+
+                    if !config.database.mongodb.is_empty() {
                         DatabaseInfo::MongoDb {
                             uri: config.database.mongodb,
-                            database_name,
+                            database_name: "revolt".to_string(),
                         }
                         .connect()
                         .await?
+                    } else {
+                        DatabaseInfo::Reference.connect().await?
                     }
-                    _ => unreachable!("must specify REFERENCE or MONGODB"),
                 }
             }
+            DatabaseInfo::Test(database_name) => {
+                DatabaseInfo::test_driver(
+                    &std::env::var("TEST_DB").expect(
+                        "`TEST_DB` environment variable should be set to REFERENCE, MONGODB, POSTGRES or SQLITE",
+                    ),
+                    database_name,
+                )
+                .await
+                .connect()
+                .await?
+            }
             DatabaseInfo::Reference => Database::Reference(Default::default()),
             DatabaseInfo::MongoDb { uri, database_name } => {
                 let client = ::mongodb::Client::with_uri_str(uri)
                     .await
                     .map_err(|_| "Failed to init db connection.".to_string())?;
 
-                Database::MongoDb(MongoDb(client, database_name))
+                Database::MongoDb(MongoDb(client, database_name, None))
             }
             DatabaseInfo::MongoDbFromClient(client, database_name) => {
-                Database::MongoDb(MongoDb(client, database_name))
+                Database::MongoDb(MongoDb(client, database_name, None))
+            }
+            #[cfg(feature = "postgres")]
+            DatabaseInfo::Postgres { uri, schema } => {
+                // Schema names are limited in length, so keep the most specific part
+                let schema: String = schema
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                let schema = schema[schema.len().saturating_sub(63)..].to_string();
+
+                let search_path = schema.clone();
+                let pool = ::sqlx::postgres::PgPoolOptions::new()
+                    .after_connect(move |conn, _| {
+                        let search_path = search_path.clone();
+                        Box::pin(async move {
+                            ::sqlx::Executor::execute(
+                                conn,
+                                format!(
+                                    r#"CREATE SCHEMA IF NOT EXISTS "{search_path}"; SET search_path TO "{search_path}""#
+                                )
+                                .as_str(),
+                            )
+                            .await
+                            .map(|_| ())
+                        })
+                    })
+                    .connect(&uri)
+                    .await
+                    .map_err(|_| "Failed to init db connection.".to_string())?;
+
+                Database::Postgres(PostgresDb(pool.into(), schema))
+            }
+            #[cfg(feature = "sqlite")]
+            DatabaseInfo::Sqlite { path } => {
+                let in_memory = path == ":memory:";
+                let options = ::sqlx::sqlite::SqliteConnectOptions::new()
+                    .filename(&path)
+                    .create_if_missing(true)
+                    .journal_mode(::sqlx::sqlite::SqliteJournalMode::Wal)
+                    .busy_timeout(std::time::Duration::from_secs(5));
+
+                let mut pool = ::sqlx::sqlite::SqlitePoolOptions::new();
+                if in_memory {
+                    // An in-memory database only lives as long as its connection
+                    pool = pool
+                        .max_connections(1)
+                        .idle_timeout(None)
+                        .max_lifetime(None);
+                }
+
+                let pool = pool
+                    .connect_with(options)
+                    .await
+                    .map_err(|_| "Failed to init db connection.".to_string())?;
+
+                Database::Sqlite(SqliteDb(pool.into()))
             }
         })
     }
 }
+
+/// Database transaction
+///
+/// Operations run through the transaction only become visible once it is
+/// committed, dropping it without committing rolls them back. Events
+/// published through the outbox are written as part of the transaction.
+///
+/// The reference driver has no transactions: its writes apply immediately
+/// and are not rolled back, only outbox events wait for the commit.
+pub struct Transaction {
+    db: Database,
+    owned: bool,
+    events: Mutex<Vec<(EventV1, Vec<String>)>>,
+}
+
+impl Deref for Transaction {
+    type Target = Database;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+impl Database {
+    /// Start a transaction
+    ///
+    /// Joins the current transaction if this handle is already part of one.
+    pub async fn begin(&self) -> Result<Transaction> {
+        let (db, owned) = match self {
+            // Writes are not buffered, see `Transaction`
+            Database::Reference(db) => (Database::Reference(db.clone()), true),
+            Database::MongoDb(db) => (
+                Database::MongoDb(
+                    db.begin()
+                        .await
+                        .map_err(|_| create_database_error!("begin", "transaction"))?,
+                ),
+                db.2.is_none(),
+            ),
+            #[cfg(feature = "postgres")]
+            Database::Postgres(db) => (
+                Database::Postgres(PostgresDb(
+                    db.transaction("BEGIN")
+                        .await
+                        .map_err(|_| create_database_error!("begin", "transaction"))?,
+                    db.1.clone(),
+                )),
+                !db.in_transaction(),
+            ),
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(db) => (
+                Database::Sqlite(SqliteDb(
+                    db.transaction("BEGIN IMMEDIATE")
+                        .await
+                        .map_err(|_| create_database_error!("begin", "transaction"))?,
+                )),
+                !db.in_transaction(),
+            ),
+        };
+
+        Ok(Transaction {
+            db,
+            owned,
+            events: Mutex::new(vec![]),
+        })
+    }
+}
+
+impl Transaction {
+    /// Publish an event to the given topics once the transaction commits
+    pub fn publish(&self, event: EventV1, topics: Vec<String>) {
+        self.events
+            .lock()
+            .expect("lock is not poisoned")
+            .push((event, topics));
+    }
+
+    /// Write pending events to the outbox and commit the transaction
+    ///
+    /// A joined transaction only writes its events, the outermost commits.
+    pub async fn commit(self) -> Result<()> {
+        let events = std::mem::take(&mut *self.events.lock().expect("lock is not poisoned"));
+        for (event, topics) in &events {
+            self.db
+                .insert_outbox_event(&OutboxEvent::new(event.clone(), topics.clone()))
+                .await?;
+        }
+
+        if self.owned {
+            let committed = match &self.db {
+                Database::Reference(_) => true,
+                Database::MongoDb(db) => db.commit().await.is_ok(),
+                #[cfg(feature = "postgres")]
+                Database::Postgres(db) => db.commit().await.is_ok(),
+                #[cfg(feature = "sqlite")]
+                Database::Sqlite(db) => db.commit().await.is_ok(),
+            };
+
+            if !committed {
+                return Err(create_database_error!("commit", "transaction"));
+            }
+        }
+
+        // Don't wait for the relay to stop serving stale objects from this process.
+        for (event, _) in &events {
+            cache::invalidate(event);
+        }
+
+        if !events.is_empty() {
+            tasks::outbox::wake();
+        }
+
+        Ok(())
+    }
+}
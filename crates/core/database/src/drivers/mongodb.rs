@@ -1,20 +1,34 @@
+use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::sync::Arc;
 
+use async_lock::Mutex;
+use futures::stream::{self, BoxStream};
 use futures::StreamExt;
 use mongodb::bson::{doc, to_document, Document};
-use mongodb::error::Result;
-use mongodb::options::{FindOneOptions, FindOptions};
-use mongodb::results::{DeleteResult, InsertOneResult, UpdateResult};
+use mongodb::error::{ErrorKind, Result};
+use mongodb::options::{
+    AggregateOptions, CountOptions, DeleteOptions, FindOneAndUpdateOptions, FindOneOptions,
+    FindOptions, InsertManyOptions, InsertOneOptions, ReplaceOptions, UpdateModifications,
+    UpdateOptions,
+};
+use mongodb::results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult};
+use mongodb::ClientSession;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 database_derived!(
     #[cfg(feature = "mongodb")]
     /// MongoDB implementation
-    pub struct MongoDb(pub ::mongodb::Client, pub String);
+    ///
+    /// The third field is the session of the transaction this handle is part of.
+    pub struct MongoDb(pub ::mongodb::Client, pub String, pub Option<MongoSession>);
 );
 
+/// Session shared by handles taking part in a transaction
+pub type MongoSession = Arc<Mutex<ClientSession>>;
+
 impl Deref for MongoDb {
     type Target = mongodb::Client;
 
@@ -31,8 +45,40 @@ impl MongoDb {
     }
 
     /// Get a collection by its name
-    pub fn col<T>(&self, collection: &str) -> mongodb::Collection<T> {
-        self.db().collection(collection)
+    pub fn col<T>(&self, collection: &str) -> Collection<T> {
+        Collection {
+            collection: self.db().collection(collection),
+            session: self.2.clone(),
+        }
+    }
+
+    /// Start a transaction
+    ///
+    /// Joins the current transaction if this handle is already part of one,
+    /// deployments without transaction support carry on without one.
+    pub async fn begin(&self) -> Result<MongoDb> {
+        if self.2.is_some() {
+            return Ok(self.clone());
+        }
+
+        let mut session = self.start_session(None).await?;
+        match session.start_transaction(None).await {
+            Ok(()) => Ok(MongoDb(
+                self.0.clone(),
+                self.1.clone(),
+                Some(Arc::new(Mutex::new(session))),
+            )),
+            Err(err) if matches!(*err.kind, ErrorKind::Transaction { .. }) => Ok(self.clone()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Commit the transaction this handle is part of
+    pub async fn commit(&self) -> Result<()> {
+        match &self.2 {
+            Some(session) => session.lock().await.commit_transaction().await,
+            None => Ok(()),
+        }
     }
 
     /// Insert one document into a collection
@@ -220,6 +266,215 @@ impl MongoDb {
     }
 }
 
+/// Collection which runs every operation in the transaction, if there is one
+pub struct Collection<T> {
+    collection: mongodb::Collection<T>,
+    session: Option<MongoSession>,
+}
+
+impl<T> Deref for Collection<T> {
+    type Target = mongodb::Collection<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.collection
+    }
+}
+
+/// Run an operation with or without the session
+macro_rules! with_session {
+    ( $self:ident, $method:ident, $method_with_session:ident ( $( $arg:expr ),* ) ) => {
+        match &$self.session {
+            Some(session) => {
+                $self
+                    .collection
+                    .$method_with_session($( $arg ),*, &mut *session.lock().await)
+                    .await
+            }
+            None => $self.collection.$method($( $arg ),*).await,
+        }
+    };
+}
+
+impl<T> Collection<T> {
+    /// Insert one document
+    pub async fn insert_one(
+        &self,
+        doc: impl Borrow<T>,
+        options: impl Into<Option<InsertOneOptions>>,
+    ) -> Result<InsertOneResult>
+    where
+        T: Serialize,
+    {
+        with_session!(self, insert_one, insert_one_with_session(doc, options))
+    }
+
+    /// Insert many documents
+    pub async fn insert_many(
+        &self,
+        docs: impl IntoIterator<Item = impl Borrow<T>>,
+        options: impl Into<Option<InsertManyOptions>>,
+    ) -> Result<InsertManyResult>
+    where
+        T: Serialize,
+    {
+        with_session!(self, insert_many, insert_many_with_session(docs, options))
+    }
+
+    /// Replace one document
+    pub async fn replace_one(
+        &self,
+        query: Document,
+        replacement: impl Borrow<T>,
+        options: impl Into<Option<ReplaceOptions>>,
+    ) -> Result<UpdateResult>
+    where
+        T: Serialize,
+    {
+        with_session!(
+            self,
+            replace_one,
+            replace_one_with_session(query, replacement, options)
+        )
+    }
+
+    /// Update one document
+    pub async fn update_one(
+        &self,
+        query: Document,
+        update: impl Into<UpdateModifications>,
+        options: impl Into<Option<UpdateOptions>>,
+    ) -> Result<UpdateResult> {
+        with_session!(
+            self,
+            update_one,
+            update_one_with_session(query, update, options)
+        )
+    }
+
+    /// Update many documents
+    pub async fn update_many(
+        &self,
+        query: Document,
+        update: impl Into<UpdateModifications>,
+        options: impl Into<Option<UpdateOptions>>,
+    ) -> Result<UpdateResult> {
+        with_session!(
+            self,
+            update_many,
+            update_many_with_session(query, update, options)
+        )
+    }
+
+    /// Delete one document
+    pub async fn delete_one(
+        &self,
+        query: Document,
+        options: impl Into<Option<DeleteOptions>>,
+    ) -> Result<DeleteResult> {
+        with_session!(self, delete_one, delete_one_with_session(query, options))
+    }
+
+    /// Delete many documents
+    pub async fn delete_many(
+        &self,
+        query: Document,
+        options: impl Into<Option<DeleteOptions>>,
+    ) -> Result<DeleteResult> {
+        with_session!(self, delete_many, delete_many_with_session(query, options))
+    }
+
+    /// Count documents
+    pub async fn count_documents(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<CountOptions>>,
+    ) -> Result<u64> {
+        with_session!(
+            self,
+            count_documents,
+            count_documents_with_session(filter, options)
+        )
+    }
+
+    /// Find one document
+    pub async fn find_one(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOneOptions>>,
+    ) -> Result<Option<T>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        with_session!(self, find_one, find_one_with_session(filter, options))
+    }
+
+    /// Find and update one document
+    pub async fn find_one_and_update(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+        options: impl Into<Option<FindOneAndUpdateOptions>>,
+    ) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        with_session!(
+            self,
+            find_one_and_update,
+            find_one_and_update_with_session(filter, update, options)
+        )
+    }
+
+    /// Find documents
+    ///
+    /// Results are read up front when running in a transaction.
+    pub async fn find(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<BoxStream<'static, Result<T>>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync + 'static,
+    {
+        match &self.session {
+            Some(session) => {
+                let mut session = session.lock().await;
+                let mut cursor = self
+                    .collection
+                    .find_with_session(filter, options, &mut session)
+                    .await?;
+
+                let documents: Vec<_> = cursor.stream(&mut session).collect().await;
+                Ok(stream::iter(documents).boxed())
+            }
+            None => Ok(self.collection.find(filter, options).await?.boxed()),
+        }
+    }
+
+    /// Run an aggregation pipeline
+    ///
+    /// Results are read up front when running in a transaction.
+    pub async fn aggregate(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+        options: impl Into<Option<AggregateOptions>>,
+    ) -> Result<BoxStream<'static, Result<Document>>> {
+        match &self.session {
+            Some(session) => {
+                let mut session = session.lock().await;
+                let mut cursor = self
+                    .collection
+                    .aggregate_with_session(pipeline, options, &mut session)
+                    .await?;
+
+                let documents: Vec<_> = cursor.stream(&mut session).collect().await;
+                Ok(stream::iter(documents).boxed())
+            }
+            None => Ok(self.collection.aggregate(pipeline, options).await?.boxed()),
+        }
+    }
+}
+
 /// Just a string ID struct
 #[derive(Deserialize)]
 pub struct DocumentId {
@@ -0,0 +1,251 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use async_lock::{Mutex, MutexGuard};
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use sqlx::database::HasStatement;
+use sqlx::pool::PoolConnection;
+use sqlx::{Database, Describe, Either, Error, Execute, Executor, Pool, Result};
+
+/// Connection pool which may be bound to a transaction
+///
+/// Queries against a bound pool run on the transaction's connection,
+/// so every driver operation takes part in the transaction unchanged.
+#[derive(Debug)]
+pub struct SqlPool<DB: Database>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    pool: Pool<DB>,
+    transaction: Option<Arc<SharedConnection<DB>>>,
+}
+
+/// Connection holding an open transaction
+#[derive(Debug)]
+struct SharedConnection<DB: Database>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    conn: Mutex<Option<PoolConnection<DB>>>,
+}
+
+/// Connection checked out from a [SqlPool]
+pub enum SqlConnection<'a, DB: Database> {
+    /// Connection taken from the pool
+    Pooled(PoolConnection<DB>),
+    /// Connection of the transaction the pool is bound to
+    Shared(MutexGuard<'a, Option<PoolConnection<DB>>>),
+}
+
+impl<DB: Database> Clone for SqlPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    fn clone(&self) -> Self {
+        SqlPool {
+            pool: self.pool.clone(),
+            transaction: self.transaction.clone(),
+        }
+    }
+}
+
+impl<DB: Database> From<Pool<DB>> for SqlPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    fn from(pool: Pool<DB>) -> Self {
+        SqlPool {
+            pool,
+            transaction: None,
+        }
+    }
+}
+
+impl<DB: Database> Deref for SqlPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    type Target = Pool<DB>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+impl<DB: Database> SqlPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    /// Whether this pool is bound to a transaction
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Check out a connection, sharing the transaction's if the pool is bound to one
+    pub async fn acquire(&self) -> Result<SqlConnection<'_, DB>> {
+        match &self.transaction {
+            Some(transaction) => {
+                let conn = transaction.conn.lock().await;
+                if conn.is_none() {
+                    return Err(Error::PoolClosed);
+                }
+
+                Ok(SqlConnection::Shared(conn))
+            }
+            None => self.pool.acquire().await.map(SqlConnection::Pooled),
+        }
+    }
+
+    /// Start a transaction with the given statement
+    ///
+    /// If the pool is already bound to a transaction, a savepoint is taken instead.
+    /// Must be completed using [SqlPool::finish].
+    pub async fn begin_with(&self, statement: &str) -> Result<SqlConnection<'_, DB>> {
+        let mut conn = self.acquire().await?;
+        match conn {
+            SqlConnection::Pooled(_) => (&mut *conn).execute(statement).await?,
+            SqlConnection::Shared(_) => (&mut *conn).execute("SAVEPOINT nested").await?,
+        };
+
+        Ok(conn)
+    }
+
+    /// Commit or roll back a transaction started with [SqlPool::begin_with]
+    pub async fn finish<T>(mut conn: SqlConnection<'_, DB>, result: Result<T>) -> Result<T> {
+        let (commit, rollback) = match conn {
+            SqlConnection::Pooled(_) => ("COMMIT", "ROLLBACK"),
+            SqlConnection::Shared(_) => (
+                "RELEASE SAVEPOINT nested",
+                "ROLLBACK TO SAVEPOINT nested; RELEASE SAVEPOINT nested",
+            ),
+        };
+
+        match result {
+            Ok(value) => (&mut *conn).execute(commit).await.map(|_| value),
+            Err(err) => {
+                (&mut *conn).execute(rollback).await.ok();
+                Err(err)
+            }
+        }
+    }
+
+    /// Bind a new pool to a transaction started with the given statement
+    ///
+    /// Joins the existing transaction if this pool is already bound to one.
+    pub async fn transaction(&self, statement: &str) -> Result<SqlPool<DB>> {
+        if self.in_transaction() {
+            return Ok(self.clone());
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        (&mut *conn).execute(statement).await?;
+
+        Ok(SqlPool {
+            pool: self.pool.clone(),
+            transaction: Some(Arc::new(SharedConnection {
+                conn: Mutex::new(Some(conn)),
+            })),
+        })
+    }
+
+    /// Commit the transaction this pool is bound to
+    pub async fn commit(&self) -> Result<()> {
+        if let Some(transaction) = &self.transaction {
+            if let Some(mut conn) = transaction.conn.lock().await.take() {
+                (&mut *conn).execute("COMMIT").await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<DB: Database> Drop for SharedConnection<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    fn drop(&mut self) {
+        // Abandoned transactions are rolled back before the connection is reused.
+        if let Some(mut conn) = self.conn.get_mut().take() {
+            async_std::task::spawn(async move {
+                (&mut *conn).execute("ROLLBACK").await.ok();
+            });
+        }
+    }
+}
+
+impl<DB: Database> Deref for SqlConnection<'_, DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            SqlConnection::Pooled(conn) => conn,
+            SqlConnection::Shared(conn) => conn.as_ref().expect("checked on acquire"),
+        }
+    }
+}
+
+impl<DB: Database> DerefMut for SqlConnection<'_, DB> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            SqlConnection::Pooled(conn) => conn,
+            SqlConnection::Shared(conn) => conn.as_mut().expect("checked on acquire"),
+        }
+    }
+}
+
+impl<'p, DB: Database> Executor<'p> for &'_ SqlPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    type Database = DB;
+
+    fn fetch_many<'e, 'q: 'e, E: 'q>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<DB::QueryResult, DB::Row>>>
+    where
+        E: Execute<'q, Self::Database>,
+    {
+        let pool = self.clone();
+        stream::once(async move {
+            let results: Vec<_> = match pool.acquire().await {
+                Ok(mut conn) => (&mut *conn).fetch_many(query).collect().await,
+                Err(err) => vec![Err(err)],
+            };
+
+            stream::iter(results)
+        })
+        .flatten()
+        .boxed()
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E: 'q>(self, query: E) -> BoxFuture<'e, Result<Option<DB::Row>>>
+    where
+        E: Execute<'q, Self::Database>,
+    {
+        let pool = self.clone();
+        Box::pin(async move { (&mut *pool.acquire().await?).fetch_optional(query).await })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [DB::TypeInfo],
+    ) -> BoxFuture<'e, Result<<DB as HasStatement<'q>>::Statement>> {
+        let pool = self.clone();
+        Box::pin(async move {
+            (&mut *pool.acquire().await?)
+                .prepare_with(sql, parameters)
+                .await
+        })
+    }
+
+    #[doc(hidden)]
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<DB>>> {
+        let pool = self.clone();
+        Box::pin(async move { (&mut *pool.acquire().await?).describe(sql).await })
+    }
+}
@@ -0,0 +1,278 @@
+use std::ops::Deref;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder, Result};
+
+use crate::{encode_error, IntoDocumentPath, PartialUpdate, SqlPool};
+
+database_derived!(
+    #[cfg(feature = "postgres")]
+    /// PostgreSQL implementation
+    ///
+    /// Every collection is stored as a table of JSONB documents keyed by
+    /// their `_id`, the second field is the schema the tables live in.
+    pub struct PostgresDb(pub SqlPool<Postgres>, pub String);
+);
+
+impl Deref for PostgresDb {
+    type Target = SqlPool<Postgres>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[allow(dead_code)]
+impl PostgresDb {
+    /// Insert one document into a table
+    pub async fn insert_one<T: Serialize>(&self, table: &'static str, document: T) -> Result<()> {
+        let document = serde_json::to_value(document).map_err(encode_error)?;
+
+        sqlx::query(&format!("INSERT INTO {table} (id, data) VALUES ($1, $2)"))
+            .bind(Json(&document["_id"]))
+            .bind(Json(&document))
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+    }
+
+    /// Count documents which contain the given filter
+    pub async fn count_documents(&self, table: &'static str, filter: Value) -> Result<u64> {
+        sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {table} WHERE data @> $1"))
+            .bind(Json(filter))
+            .fetch_one(&self.0)
+            .await
+            .map(|count| count as u64)
+    }
+
+    /// Find multiple documents using a query which selects `data`
+    pub async fn find_by_query<T: DeserializeOwned>(
+        &self,
+        mut query: QueryBuilder<'_, Postgres>,
+    ) -> Result<Vec<T>> {
+        Ok(query
+            .build_query_scalar::<Json<Value>>()
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .filter_map(|Json(document)| {
+                if cfg!(debug_assertions) {
+                    // Hard fail on invalid documents
+                    Some(serde_json::from_value(document).unwrap())
+                } else {
+                    serde_json::from_value(document).ok()
+                }
+            })
+            .collect())
+    }
+
+    /// Find multiple documents which contain the given filter
+    pub async fn find<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        filter: Value,
+    ) -> Result<Vec<T>> {
+        let mut query = QueryBuilder::new(format!("SELECT data FROM {table} WHERE data @> "));
+        query.push_bind(Json(filter));
+        self.find_by_query(query).await
+    }
+
+    /// Find multiple documents where the given expression matches any of the values
+    pub async fn find_in<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        expression: &'static str,
+        values: &[String],
+    ) -> Result<Vec<T>> {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT data FROM {table} WHERE {expression} = ANY("
+        ));
+        query.push_bind(values.to_vec()).push(")");
+        self.find_by_query(query).await
+    }
+
+    /// Find one document which contains the given filter
+    pub async fn find_one<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        filter: Value,
+    ) -> Result<Option<T>> {
+        let mut query = QueryBuilder::new(format!("SELECT data FROM {table} WHERE data @> "));
+        query.push_bind(Json(filter)).push(" LIMIT 1");
+        Ok(self.find_by_query(query).await?.into_iter().next())
+    }
+
+    /// Find one document by its ID
+    pub async fn find_one_by_id<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        id: &str,
+    ) -> Result<Option<T>> {
+        let mut query = QueryBuilder::new(format!("SELECT data FROM {table} WHERE id = "));
+        query.push_bind(Json(id.to_owned()));
+        Ok(self.find_by_query(query).await?.into_iter().next())
+    }
+
+    /// Modify one document which contains the given filter
+    ///
+    /// The document is locked for the duration of the change.
+    /// Returns whether a document was found.
+    pub async fn modify_one<F>(&self, table: &'static str, filter: Value, modify: F) -> Result<bool>
+    where
+        F: FnOnce(&mut Value) + Send,
+    {
+        let mut conn = self.begin_with("BEGIN").await?;
+        let result = async {
+            let Some((id, Json(mut document))) = sqlx::query_as::<_, (Json<Value>, Json<Value>)>(
+                &format!("SELECT id, data FROM {table} WHERE data @> $1 LIMIT 1 FOR UPDATE"),
+            )
+            .bind(Json(filter))
+            .fetch_optional(&mut *conn)
+            .await?
+            else {
+                return Ok(false);
+            };
+
+            modify(&mut document);
+
+            sqlx::query(&format!("UPDATE {table} SET data = $1 WHERE id = $2"))
+                .bind(Json(document))
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+
+            Ok::<_, sqlx::Error>(true)
+        }
+        .await;
+
+        SqlPool::finish(conn, result).await
+    }
+
+    /// Modify every document which contains the given filter
+    ///
+    /// Returns the number of documents changed.
+    pub async fn modify_many<F>(
+        &self,
+        table: &'static str,
+        filter: Value,
+        mut modify: F,
+    ) -> Result<u64>
+    where
+        F: FnMut(&mut Value) + Send,
+    {
+        let mut conn = self.begin_with("BEGIN").await?;
+        let result = async {
+            let documents = sqlx::query_as::<_, (Json<Value>, Json<Value>)>(&format!(
+                "SELECT id, data FROM {table} WHERE data @> $1 FOR UPDATE"
+            ))
+            .bind(Json(filter))
+            .fetch_all(&mut *conn)
+            .await?;
+
+            let count = documents.len() as u64;
+            for (id, Json(mut document)) in documents {
+                modify(&mut document);
+
+                sqlx::query(&format!("UPDATE {table} SET data = $1 WHERE id = $2"))
+                    .bind(Json(document))
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+
+            Ok::<_, sqlx::Error>(count)
+        }
+        .await;
+
+        SqlPool::finish(conn, result).await
+    }
+
+    /// Modify one document by its ID, creating it first if it doesn't exist
+    pub async fn upsert_one<F>(&self, table: &'static str, id: Value, modify: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) + Send,
+    {
+        sqlx::query(&format!(
+            "INSERT INTO {table} (id, data) VALUES ($1, jsonb_build_object('_id', $1))
+             ON CONFLICT (id) DO NOTHING"
+        ))
+        .bind(Json(&id))
+        .execute(&self.0)
+        .await?;
+
+        self.modify_one(table, serde_json::json!({ "_id": id }), modify)
+            .await
+            .map(|_| ())
+    }
+
+    /// Update one document given a filter, partial document, and list of paths to unset
+    pub async fn update_one<P, T: Serialize>(
+        &self,
+        table: &'static str,
+        filter: Value,
+        partial: T,
+        remove: Vec<&dyn IntoDocumentPath>,
+        prefix: P,
+    ) -> Result<bool>
+    where
+        P: Into<Option<String>>,
+    {
+        let update = PartialUpdate::new(partial, remove, prefix.into()).map_err(encode_error)?;
+        self.modify_one(table, filter, move |document| update.apply(document))
+            .await
+    }
+
+    /// Update one document given an ID, partial document, and list of paths to unset
+    pub async fn update_one_by_id<P, T: Serialize>(
+        &self,
+        table: &'static str,
+        id: &str,
+        partial: T,
+        remove: Vec<&dyn IntoDocumentPath>,
+        prefix: P,
+    ) -> Result<bool>
+    where
+        P: Into<Option<String>>,
+    {
+        self.update_one(
+            table,
+            serde_json::json!({ "_id": id }),
+            partial,
+            remove,
+            prefix,
+        )
+        .await
+    }
+
+    /// Delete one document which contains the given filter
+    pub async fn delete_one(&self, table: &'static str, filter: Value) -> Result<u64> {
+        sqlx::query(&format!(
+            "DELETE FROM {table} WHERE id IN (SELECT id FROM {table} WHERE data @> $1 LIMIT 1)"
+        ))
+        .bind(Json(filter))
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    /// Delete one document by its ID
+    pub async fn delete_one_by_id(&self, table: &'static str, id: &str) -> Result<u64> {
+        sqlx::query(&format!("DELETE FROM {table} WHERE id = $1"))
+            .bind(Json(id))
+            .execute(&self.0)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Delete every document which contains the given filter
+    pub async fn delete_many(&self, table: &'static str, filter: Value) -> Result<u64> {
+        sqlx::query(&format!("DELETE FROM {table} WHERE data @> $1"))
+            .bind(Json(filter))
+            .execute(&self.0)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
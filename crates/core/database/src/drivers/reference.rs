@@ -3,8 +3,9 @@ use std::{collections::HashMap, sync::Arc};
 use futures::lock::Mutex;
 
 use crate::{
-    Bot, Channel, ChannelCompositeKey, ChannelUnread, Emoji, File, Invite, Member,
-    MemberCompositeKey, Message, RatelimitEvent, Report, Server, ServerBan, Snapshot, User,
+    Bot, Channel, ChannelCompositeKey, ChannelUnread, Emoji, File, Invite, Job, Member,
+    MemberCompositeKey, Message, MigrationRecord, NotificationSettings, OutboxEvent, PushWindow,
+    RatelimitEvent, Report, Server, ServerBan, Snapshot, UnifiedPushSubscription, User,
     UserSettings, Webhook,
 };
 
@@ -19,8 +20,14 @@ database_derived!(
         pub channel_webhooks: Arc<Mutex<HashMap<String, Webhook>>>,
         pub emojis: Arc<Mutex<HashMap<String, Emoji>>>,
         pub files: Arc<Mutex<HashMap<String, File>>>,
+        pub jobs: Arc<Mutex<HashMap<String, Job>>>,
         pub messages: Arc<Mutex<HashMap<String, Message>>>,
+        pub migration_history: Arc<Mutex<HashMap<String, MigrationRecord>>>,
+        pub notification_settings: Arc<Mutex<HashMap<String, NotificationSettings>>>,
+        pub outbox_events: Arc<Mutex<HashMap<String, OutboxEvent>>>,
+        pub push_windows: Arc<Mutex<HashMap<ChannelCompositeKey, PushWindow>>>,
         pub ratelimit_events: Arc<Mutex<HashMap<String, RatelimitEvent>>>,
+        pub unified_push_subscriptions: Arc<Mutex<HashMap<String, UnifiedPushSubscription>>>,
         pub user_settings: Arc<Mutex<HashMap<String, UserSettings>>>,
         pub users: Arc<Mutex<HashMap<String, User>>>,
         pub server_bans: Arc<Mutex<HashMap<MemberCompositeKey, ServerBan>>>,
@@ -0,0 +1,392 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{QueryBuilder, Result, Sqlite};
+
+use crate::{encode_error, IntoDocumentPath, PartialUpdate, SqlConnection, SqlPool};
+
+database_derived!(
+    #[cfg(feature = "sqlite")]
+    /// SQLite implementation
+    ///
+    /// Every collection is stored as a table of JSON documents keyed by their `_id`.
+    pub struct SqliteDb(pub SqlPool<Sqlite>);
+);
+
+impl Deref for SqliteDb {
+    type Target = SqlPool<Sqlite>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Key used to store a document's ID
+///
+/// Object keys are sorted so that composite keys always compare equal.
+pub fn document_key(id: &Value) -> String {
+    match id {
+        Value::Object(object) => serde_json::to_string(&object.iter().collect::<BTreeMap<_, _>>()),
+        id => serde_json::to_string(id),
+    }
+    .expect("JSON values always serialise")
+}
+
+/// Push conditions matching documents which contain the given filter
+///
+/// Equivalent to PostgreSQL's `@>` operator: objects match if every key
+/// matches and arrays match if every filter element is present.
+///
+/// Conditions on `_id` are applied to the key column so they can use its indexes.
+pub fn push_filter(query: &mut QueryBuilder<'_, Sqlite>, filter: &Value) {
+    let Value::Object(object) = filter else {
+        return push_filter_at(query, "data", "$", filter, 0);
+    };
+
+    for (key, value) in object {
+        match (key.as_str(), value) {
+            ("_id", Value::String(_)) => {
+                query.push(" AND id = ").push_bind(document_key(value));
+            }
+            ("_id", Value::Object(_)) => push_filter_at(query, "id", "$", value, 0),
+            _ => push_filter_at(query, "data", &format!("$.{key}"), value, 0),
+        }
+    }
+}
+
+/// Push conditions for the filter at the given path of a JSON source
+fn push_filter_at(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    source: &str,
+    path: &str,
+    filter: &Value,
+    depth: usize,
+) {
+    match filter {
+        Value::Object(object) => {
+            for (key, value) in object {
+                push_filter_at(query, source, &format!("{path}.{key}"), value, depth);
+            }
+        }
+        Value::Array(items) => {
+            let alias = format!("element_{depth}");
+            for item in items {
+                query.push(format!(
+                    " AND EXISTS (SELECT 1 FROM json_each({source}, '{path}') AS {alias} WHERE TRUE"
+                ));
+
+                if item.is_object() {
+                    push_filter_at(query, &format!("{alias}.value"), "$", item, depth + 1);
+                } else {
+                    query.push(format!(" AND {alias}.value = "));
+                    push_scalar(query, item);
+                }
+
+                query.push(")");
+            }
+        }
+        Value::Null => {
+            query.push(format!(" AND json_extract({source}, '{path}') IS NULL"));
+        }
+        scalar => {
+            query.push(format!(" AND json_extract({source}, '{path}') = "));
+            push_scalar(query, scalar);
+        }
+    }
+}
+
+/// Bind a scalar JSON value as its SQL equivalent
+fn push_scalar(query: &mut QueryBuilder<'_, Sqlite>, value: &Value) {
+    match value {
+        Value::Bool(value) => query.push_bind(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => query.push_bind(value),
+            None => query.push_bind(number.as_f64()),
+        },
+        Value::String(value) => query.push_bind(value.clone()),
+        value => query.push_bind(value.to_string()),
+    };
+}
+
+#[allow(dead_code)]
+impl SqliteDb {
+    /// Start a query against a table with the given filter applied
+    pub fn select(
+        &self,
+        columns: &str,
+        table: &'static str,
+        filter: &Value,
+    ) -> QueryBuilder<'static, Sqlite> {
+        let mut query = QueryBuilder::new(format!("SELECT {columns} FROM {table} WHERE TRUE"));
+        push_filter(&mut query, filter);
+        query
+    }
+
+    /// Insert one document into a table
+    pub async fn insert_one<T: Serialize>(&self, table: &'static str, document: T) -> Result<()> {
+        let document = serde_json::to_value(document).map_err(encode_error)?;
+
+        sqlx::query(&format!("INSERT INTO {table} (id, data) VALUES (?, ?)"))
+            .bind(document_key(&document["_id"]))
+            .bind(document.to_string())
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+    }
+
+    /// Count documents which contain the given filter
+    pub async fn count_documents(&self, table: &'static str, filter: Value) -> Result<u64> {
+        self.select("COUNT(*)", table, &filter)
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.0)
+            .await
+            .map(|count| count as u64)
+    }
+
+    /// Find multiple documents using a query which selects `data`
+    pub async fn find_by_query<T: DeserializeOwned>(
+        &self,
+        mut query: QueryBuilder<'_, Sqlite>,
+    ) -> Result<Vec<T>> {
+        Ok(query
+            .build_query_scalar::<String>()
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .filter_map(|document| {
+                if cfg!(debug_assertions) {
+                    // Hard fail on invalid documents
+                    Some(serde_json::from_str(&document).unwrap())
+                } else {
+                    serde_json::from_str(&document).ok()
+                }
+            })
+            .collect())
+    }
+
+    /// Find multiple documents which contain the given filter
+    pub async fn find<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        filter: Value,
+    ) -> Result<Vec<T>> {
+        self.find_by_query(self.select("data", table, &filter))
+            .await
+    }
+
+    /// Find multiple documents where the given expression matches any of the values
+    pub async fn find_in<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        expression: &'static str,
+        values: &[String],
+    ) -> Result<Vec<T>> {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT data FROM {table} WHERE {expression} IN (SELECT value FROM json_each("
+        ));
+        query
+            .push_bind(serde_json::to_string(values).map_err(encode_error)?)
+            .push("))");
+        self.find_by_query(query).await
+    }
+
+    /// Find one document which contains the given filter
+    pub async fn find_one<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        filter: Value,
+    ) -> Result<Option<T>> {
+        let mut query = self.select("data", table, &filter);
+        query.push(" LIMIT 1");
+        Ok(self.find_by_query(query).await?.into_iter().next())
+    }
+
+    /// Find one document by its ID
+    pub async fn find_one_by_id<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        id: &str,
+    ) -> Result<Option<T>> {
+        let mut query = QueryBuilder::new(format!("SELECT data FROM {table} WHERE id = "));
+        query.push_bind(document_key(&Value::String(id.to_owned())));
+        Ok(self.find_by_query(query).await?.into_iter().next())
+    }
+
+    /// Acquire a connection holding the write lock
+    ///
+    /// SQLite only allows one writer, so taking the lock up front avoids
+    /// failing part way through a read-modify-write.
+    pub async fn begin_immediate(&self) -> Result<SqlConnection<'_, Sqlite>> {
+        self.begin_with("BEGIN IMMEDIATE").await
+    }
+
+    /// Commit or roll back a transaction started with [SqliteDb::begin_immediate]
+    pub async fn finish<T>(conn: SqlConnection<'_, Sqlite>, result: Result<T>) -> Result<T> {
+        SqlPool::finish(conn, result).await
+    }
+
+    /// Modify documents which contain the given filter
+    async fn modify_where<F>(
+        &self,
+        table: &'static str,
+        filter: Value,
+        limit: Option<i64>,
+        mut modify: F,
+    ) -> Result<u64>
+    where
+        F: FnMut(&mut Value) + Send,
+    {
+        let mut conn = self.begin_immediate().await?;
+        let result = async {
+            let mut query = self.select("id, data", table, &filter);
+            if let Some(limit) = limit {
+                query.push(" LIMIT ").push_bind(limit);
+            }
+
+            let documents = query
+                .build_query_as::<(String, String)>()
+                .fetch_all(&mut *conn)
+                .await?;
+
+            let count = documents.len() as u64;
+            for (id, document) in documents {
+                let mut document: Value = serde_json::from_str(&document).map_err(encode_error)?;
+                modify(&mut document);
+
+                sqlx::query(&format!("UPDATE {table} SET data = ? WHERE id = ?"))
+                    .bind(document.to_string())
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+
+            Ok::<_, sqlx::Error>(count)
+        }
+        .await;
+
+        Self::finish(conn, result).await
+    }
+
+    /// Modify one document which contains the given filter
+    ///
+    /// Returns whether a document was found.
+    pub async fn modify_one<F>(&self, table: &'static str, filter: Value, modify: F) -> Result<bool>
+    where
+        F: FnOnce(&mut Value) + Send,
+    {
+        let mut modify = Some(modify);
+        self.modify_where(table, filter, Some(1), |document| {
+            if let Some(modify) = modify.take() {
+                modify(document)
+            }
+        })
+        .await
+        .map(|count| count > 0)
+    }
+
+    /// Modify every document which contains the given filter
+    ///
+    /// Returns the number of documents changed.
+    pub async fn modify_many<F>(&self, table: &'static str, filter: Value, modify: F) -> Result<u64>
+    where
+        F: FnMut(&mut Value) + Send,
+    {
+        self.modify_where(table, filter, None, modify).await
+    }
+
+    /// Modify one document by its ID, creating it first if it doesn't exist
+    pub async fn upsert_one<F>(&self, table: &'static str, id: Value, modify: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) + Send,
+    {
+        sqlx::query(&format!(
+            "INSERT INTO {table} (id, data) VALUES (?, json_object('_id', json(?)))
+             ON CONFLICT (id) DO NOTHING"
+        ))
+        .bind(document_key(&id))
+        .bind(id.to_string())
+        .execute(&self.0)
+        .await?;
+
+        self.modify_one(table, serde_json::json!({ "_id": id }), modify)
+            .await
+            .map(|_| ())
+    }
+
+    /// Update one document given a filter, partial document, and list of paths to unset
+    pub async fn update_one<P, T: Serialize>(
+        &self,
+        table: &'static str,
+        filter: Value,
+        partial: T,
+        remove: Vec<&dyn IntoDocumentPath>,
+        prefix: P,
+    ) -> Result<bool>
+    where
+        P: Into<Option<String>>,
+    {
+        let update = PartialUpdate::new(partial, remove, prefix.into()).map_err(encode_error)?;
+        self.modify_one(table, filter, move |document| update.apply(document))
+            .await
+    }
+
+    /// Update one document given an ID, partial document, and list of paths to unset
+    pub async fn update_one_by_id<P, T: Serialize>(
+        &self,
+        table: &'static str,
+        id: &str,
+        partial: T,
+        remove: Vec<&dyn IntoDocumentPath>,
+        prefix: P,
+    ) -> Result<bool>
+    where
+        P: Into<Option<String>>,
+    {
+        self.update_one(
+            table,
+            serde_json::json!({ "_id": id }),
+            partial,
+            remove,
+            prefix,
+        )
+        .await
+    }
+
+    /// Delete one document which contains the given filter
+    pub async fn delete_one(&self, table: &'static str, filter: Value) -> Result<u64> {
+        let mut query = QueryBuilder::new(format!("DELETE FROM {table} WHERE id IN ("));
+        query.push(format!("SELECT id FROM {table} WHERE TRUE"));
+        push_filter(&mut query, &filter);
+        query.push(" LIMIT 1)");
+
+        query
+            .build()
+            .execute(&self.0)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Delete one document by its ID
+    pub async fn delete_one_by_id(&self, table: &'static str, id: &str) -> Result<u64> {
+        sqlx::query(&format!("DELETE FROM {table} WHERE id = ?"))
+            .bind(document_key(&Value::String(id.to_owned())))
+            .execute(&self.0)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Delete every document which contains the given filter
+    pub async fn delete_many(&self, table: &'static str, filter: Value) -> Result<u64> {
+        let mut query = QueryBuilder::new(format!("DELETE FROM {table} WHERE TRUE"));
+        push_filter(&mut query, &filter);
+
+        query
+            .build()
+            .execute(&self.0)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
@@ -0,0 +1,88 @@
+use async_lock::OnceCell;
+use revolt_result::Result;
+
+use super::client::EventV1;
+
+mod memory;
+mod redis;
+
+pub use memory::MemoryBroker;
+pub use redis::RedisBroker;
+
+static BROKER: OnceCell<Box<dyn EventBroker>> = OnceCell::new();
+
+/// Event as it is sent through the broker
+///
+/// The same id is used when an event is published to several topics or
+/// published again after a failure, so subscribers can de-duplicate it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventEnvelope {
+    /// Unique event id
+    pub id: String,
+    /// Event payload
+    pub event: EventV1,
+}
+
+/// Message received from an event broker
+#[derive(Debug, Clone)]
+pub enum BrokerEvent {
+    /// Event published to a topic
+    Event {
+        id: String,
+        topic: String,
+        event: EventV1,
+    },
+    /// Connection to the broker was interrupted, events may have been lost
+    Interrupted,
+}
+
+/// Fan-out of events between publishers and subscribers
+#[async_trait]
+pub trait EventBroker: Sync + Send {
+    /// Publish an event to a topic
+    async fn publish(&self, id: String, topic: String, event: EventV1) -> Result<()>;
+
+    /// Create a new subscriber
+    async fn subscriber(&self) -> Result<Box<dyn EventSubscriber>>;
+}
+
+/// Subscription to one or more topics on an event broker
+#[async_trait]
+pub trait EventSubscriber: Sync + Send {
+    /// Subscribe to a topic
+    async fn subscribe(&self, topic: String) -> Result<()>;
+
+    /// Subscribe to every topic
+    ///
+    /// Intended for tests and tooling which need to observe all events.
+    async fn subscribe_all(&self) -> Result<()>;
+
+    /// Unsubscribe from a topic
+    async fn unsubscribe(&self, topic: String) -> Result<()>;
+
+    /// Unsubscribe from every topic
+    async fn unsubscribe_all(&self) -> Result<()>;
+
+    /// Wait for the next message, returns None once the subscriber has closed
+    async fn next(&self) -> Option<BrokerEvent>;
+
+    /// Close the subscriber
+    async fn close(&self);
+}
+
+/// Get the event broker for this process
+///
+/// The implementation is selected through `events.broker` in configuration.
+pub async fn broker() -> &'static dyn EventBroker {
+    BROKER
+        .get_or_init(|| async {
+            match revolt_config::config().await.events.broker {
+                revolt_config::EventBroker::Redis => {
+                    Box::new(RedisBroker) as Box<dyn EventBroker>
+                }
+                revolt_config::EventBroker::Memory => Box::<MemoryBroker>::default(),
+            }
+        })
+        .await
+        .as_ref()
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use async_lock::Mutex;
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use revolt_result::Result;
+
+use super::{BrokerEvent, EventBroker, EventSubscriber};
+use crate::events::client::EventV1;
+
+/// Subscribers and the topics they are interested in
+#[derive(Default)]
+struct Subscriptions {
+    subscribers: HashMap<usize, UnboundedSender<BrokerEvent>>,
+    topics: HashMap<String, HashSet<usize>>,
+    firehose: HashSet<usize>,
+}
+
+impl Subscriptions {
+    /// Remove all subscriptions held by a subscriber
+    fn unsubscribe_all(&mut self, id: usize) {
+        self.topics.retain(|_, ids| {
+            ids.remove(&id);
+            !ids.is_empty()
+        });
+
+        self.firehose.remove(&id);
+    }
+}
+
+/// In-process event broker
+///
+/// Events only reach subscribers within the same process, this is
+/// useful for tests and small deployments which don't run Redis.
+#[derive(Default)]
+pub struct MemoryBroker {
+    subscriptions: Arc<RwLock<Subscriptions>>,
+    next_id: AtomicUsize,
+}
+
+/// Subscriber to the in-process event broker
+pub struct MemorySubscriber {
+    id: usize,
+    subscriptions: Arc<RwLock<Subscriptions>>,
+    receiver: Mutex<UnboundedReceiver<BrokerEvent>>,
+}
+
+#[async_trait]
+impl EventBroker for MemoryBroker {
+    /// Publish an event to a topic
+    async fn publish(&self, id: String, topic: String, event: EventV1) -> Result<()> {
+        let subscriptions = self.subscriptions.read().expect("broker lock poisoned");
+        let ids = subscriptions
+            .topics
+            .get(&topic)
+            .into_iter()
+            .flatten()
+            .chain(subscriptions.firehose.iter())
+            .collect::<HashSet<&usize>>();
+
+        for id in ids {
+            if let Some(sender) = subscriptions.subscribers.get(id) {
+                sender
+                    .unbounded_send(BrokerEvent::Event {
+                        id: id.clone(),
+                        topic: topic.clone(),
+                        event: event.clone(),
+                    })
+                    .ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create a new subscriber
+    async fn subscriber(&self) -> Result<Box<dyn EventSubscriber>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = unbounded();
+
+        self.subscriptions
+            .write()
+            .expect("broker lock poisoned")
+            .subscribers
+            .insert(id, sender);
+
+        Ok(Box::new(MemorySubscriber {
+            id,
+            subscriptions: self.subscriptions.clone(),
+            receiver: Mutex::new(receiver),
+        }))
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for MemorySubscriber {
+    /// Subscribe to a topic
+    async fn subscribe(&self, topic: String) -> Result<()> {
+        self.subscriptions
+            .write()
+            .expect("broker lock poisoned")
+            .topics
+            .entry(topic)
+            .or_default()
+            .insert(self.id);
+
+        Ok(())
+    }
+
+    /// Subscribe to every topic
+    async fn subscribe_all(&self) -> Result<()> {
+        self.subscriptions
+            .write()
+            .expect("broker lock poisoned")
+            .firehose
+            .insert(self.id);
+
+        Ok(())
+    }
+
+    /// Unsubscribe from a topic
+    async fn unsubscribe(&self, topic: String) -> Result<()> {
+        let mut subscriptions = self.subscriptions.write().expect("broker lock poisoned");
+        if let Some(ids) = subscriptions.topics.get_mut(&topic) {
+            ids.remove(&self.id);
+            if ids.is_empty() {
+                subscriptions.topics.remove(&topic);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unsubscribe from every topic
+    async fn unsubscribe_all(&self) -> Result<()> {
+        self.subscriptions
+            .write()
+            .expect("broker lock poisoned")
+            .unsubscribe_all(self.id);
+
+        Ok(())
+    }
+
+    /// Wait for the next message
+    async fn next(&self) -> Option<BrokerEvent> {
+        self.receiver.lock().await.next().await
+    }
+
+    /// Close the subscriber
+    async fn close(&self) {
+        let mut subscriptions = self.subscriptions.write().expect("broker lock poisoned");
+        subscriptions.unsubscribe_all(self.id);
+        subscriptions.subscribers.remove(&self.id);
+    }
+}
+
+impl Drop for MemorySubscriber {
+    fn drop(&mut self) {
+        if let Ok(mut subscriptions) = self.subscriptions.write() {
+            subscriptions.unsubscribe_all(self.id);
+            subscriptions.subscribers.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{
+        broker::{BrokerEvent, EventBroker, MemoryBroker},
+        client::EventV1,
+    };
+
+    #[async_std::test]
+    async fn delivers_events_to_subscribed_topics() {
+        let broker = MemoryBroker::default();
+        let subscriber = broker.subscriber().await.unwrap();
+        subscriber.subscribe("channel".to_string()).await.unwrap();
+
+        broker
+            .publish("1".to_string(), "other".to_string(), EventV1::Logout)
+            .await
+            .unwrap();
+        broker
+            .publish(
+                "2".to_string(),
+                "channel".to_string(),
+                EventV1::Authenticated,
+            )
+            .await
+            .unwrap();
+
+        match subscriber.next().await {
+            Some(BrokerEvent::Event { id, topic, event }) => {
+                assert_eq!(id, "2");
+                assert_eq!(topic, "channel");
+                assert!(matches!(event, EventV1::Authenticated));
+            }
+            _ => unreachable!(),
+        }
+
+        subscriber.close().await;
+        assert!(subscriber.next().await.is_none());
+    }
+
+    #[async_std::test]
+    async fn delivers_all_events_to_firehose() {
+        let broker = MemoryBroker::default();
+        let subscriber = broker.subscriber().await.unwrap();
+        subscriber.subscribe_all().await.unwrap();
+
+        for topic in ["a", "b"] {
+            broker
+                .publish("1".to_string(), topic.to_string(), EventV1::Logout)
+                .await
+                .unwrap();
+        }
+
+        for expected in ["a", "b"] {
+            match subscriber.next().await {
+                Some(BrokerEvent::Event { topic, .. }) => assert_eq!(topic, expected),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
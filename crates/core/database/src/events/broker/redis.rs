@@ -0,0 +1,161 @@
+use async_lock::Mutex;
+use fred::{
+    clients::SubscriberClient,
+    error::RedisErrorKind,
+    interfaces::{ClientLike, EventInterface, PubsubInterface},
+    types::{Message, RedisConfig},
+};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver},
+    StreamExt,
+};
+use redis_kiss::{PayloadType, REDIS_PAYLOAD_TYPE, REDIS_URI};
+use revolt_result::{create_error, Result};
+
+use super::{BrokerEvent, EventBroker, EventEnvelope, EventSubscriber};
+use crate::events::client::EventV1;
+
+/// Event broker backed by Redis pub/sub
+pub struct RedisBroker;
+
+/// Subscriber to Redis pub/sub
+pub struct RedisSubscriber {
+    client: SubscriberClient,
+    receiver: Mutex<UnboundedReceiver<BrokerEvent>>,
+}
+
+/// Decode an event from a Redis message
+fn decode(message: &Message) -> Option<EventEnvelope> {
+    match *REDIS_PAYLOAD_TYPE {
+        PayloadType::Json => message
+            .value
+            .as_str()
+            .and_then(|s| serde_json::from_str::<EventEnvelope>(s.as_ref()).ok()),
+        PayloadType::Msgpack => message
+            .value
+            .as_bytes()
+            .and_then(|b| rmp_serde::from_slice::<EventEnvelope>(b).ok()),
+        PayloadType::Bincode => message
+            .value
+            .as_bytes()
+            .and_then(|b| bincode::deserialize::<EventEnvelope>(b).ok()),
+    }
+}
+
+#[async_trait]
+impl EventBroker for RedisBroker {
+    /// Publish an event to a topic
+    async fn publish(&self, id: String, topic: String, event: EventV1) -> Result<()> {
+        redis_kiss::publish(topic, EventEnvelope { id, event })
+            .await
+            .map_err(|_| create_error!(InternalError))
+    }
+
+    /// Create a new subscriber
+    async fn subscriber(&self) -> Result<Box<dyn EventSubscriber>> {
+        let config = RedisConfig::from_url(&REDIS_URI).map_err(|_| create_error!(InternalError))?;
+        let client = fred::types::Builder::from_config(config)
+            .build_subscriber_client()
+            .map_err(|_| create_error!(InternalError))?;
+
+        client
+            .init()
+            .await
+            .map_err(|_| create_error!(InternalError))?;
+
+        // Restore subscriptions if we ever reconnect.
+        client.manage_subscriptions();
+
+        let (sender, receiver) = unbounded();
+
+        let message_s = sender.clone();
+        client.on_message(move |message| {
+            match decode(&message) {
+                Some(EventEnvelope { id, event }) => {
+                    message_s
+                        .unbounded_send(BrokerEvent::Event {
+                            id,
+                            topic: message.channel.to_string(),
+                            event,
+                        })
+                        .ok();
+                }
+                None => {
+                    let err = format!(
+                        "Failed to deserialise an event for {}! Introspection: `{:?}`",
+                        message.channel,
+                        message
+                            .value
+                            .as_string()
+                            .map(|x| x.chars().take(32).collect::<String>())
+                    );
+
+                    error!("{}", err);
+                }
+            }
+
+            Ok(())
+        });
+
+        // Handle Redis connection dropping
+        client.on_error(move |err| {
+            if let RedisErrorKind::Canceled = err.kind() {
+                sender.unbounded_send(BrokerEvent::Interrupted).ok();
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::new(RedisSubscriber {
+            client,
+            receiver: Mutex::new(receiver),
+        }))
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for RedisSubscriber {
+    /// Subscribe to a topic
+    async fn subscribe(&self, topic: String) -> Result<()> {
+        self.client
+            .subscribe(topic)
+            .await
+            .map_err(|_| create_error!(InternalError))
+    }
+
+    /// Subscribe to every topic
+    async fn subscribe_all(&self) -> Result<()> {
+        self.client
+            .psubscribe("*")
+            .await
+            .map_err(|_| create_error!(InternalError))
+    }
+
+    /// Unsubscribe from a topic
+    async fn unsubscribe(&self, topic: String) -> Result<()> {
+        self.client
+            .unsubscribe(topic)
+            .await
+            .map_err(|_| create_error!(InternalError))
+    }
+
+    /// Unsubscribe from every topic
+    async fn unsubscribe_all(&self) -> Result<()> {
+        self.client
+            .unsubscribe_all()
+            .await
+            .map_err(|_| create_error!(InternalError))
+    }
+
+    /// Wait for the next message
+    async fn next(&self) -> Option<BrokerEvent> {
+        self.receiver.lock().await.next().await
+    }
+
+    /// Close the subscriber
+    async fn close(&self) {
+        if let Err(err) = self.client.quit().await {
+            error!("Failed to close subscriber: {err:?}");
+        }
+    }
+}
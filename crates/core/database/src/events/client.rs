@@ -3,13 +3,14 @@ use serde::{Deserialize, Serialize};
 
 use revolt_models::v0::{
     AppendMessage, Channel, Emoji, FieldsChannel, FieldsMember, FieldsRole, FieldsServer,
-    FieldsUser, FieldsWebhook, Member, MemberCompositeKey, Message, PartialChannel, PartialMember,
-    PartialMessage, PartialRole, PartialServer, PartialUser, PartialWebhook, Report, Server, User,
-    UserSettings, Webhook,
+    FieldsUser, FieldsWebhook, Member, MemberCompositeKey, Message, NotificationSettings,
+    PartialChannel, PartialMember, PartialMessage, PartialRole, PartialServer, PartialUser,
+    PartialWebhook, Report, Server, ServerUnread, Unreads, User, UserSettings, Webhook,
 };
 use revolt_result::Error;
+use ulid::Ulid;
 
-use crate::Database;
+use crate::{events::broker::broker, util::cache, Database, Transaction};
 
 /// WebSocket Client Errors
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,6 +32,42 @@ pub enum Ping {
     Number(usize),
 }
 
+/// Member List Group
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberListGroup {
+    /// Role Id, `online` or `offline`
+    pub id: String,
+    /// Number of members in this group
+    pub count: usize,
+}
+
+/// Member List Item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum MemberListItem {
+    /// Group header
+    Group(MemberListGroup),
+    /// Member belonging to the preceding group
+    Member { member: Member, user: User },
+}
+
+/// Member List Operation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op")]
+pub enum MemberListOp {
+    /// Replace an inclusive range of the list
+    Sync {
+        range: (usize, usize),
+        items: Vec<MemberListItem>,
+    },
+    /// Insert an item at the given index
+    Insert { index: usize, item: MemberListItem },
+    /// Replace the item at the given index
+    Update { index: usize, item: MemberListItem },
+    /// Delete the item at the given index
+    Delete { index: usize },
+}
+
 /// Untagged Error
 #[derive(Serialize)]
 #[serde(untagged)]
@@ -57,10 +94,18 @@ pub enum EventV1 {
         channels: Vec<Channel>,
         members: Vec<Member>,
         emojis: Vec<Emoji>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unreads: Option<Unreads>,
     },
 
     /// Ping response
     Pong { data: Ping },
+    /// Result of a command sent by the client
+    CommandResult {
+        nonce: String,
+        message: Option<Message>,
+        error: Option<Error>,
+    },
     /// New message
     Message(Message),
 
@@ -107,6 +152,16 @@ pub enum EventV1 {
     /// Bulk delete messages
     BulkMessageDelete { channel: String, ids: Vec<String> },
 
+    /// Changes to a subscribed member list
+    MemberListUpdate {
+        /// Channel Id
+        id: String,
+        member_count: usize,
+        online_count: usize,
+        groups: Vec<MemberListGroup>,
+        ops: Vec<MemberListOp>,
+    },
+
     /// New server
     ServerCreate {
         id: String,
@@ -154,13 +209,17 @@ pub enum EventV1 {
         id: String,
         data: PartialUser,
         clear: Vec<FieldsUser>,
-        event_id: Option<String>,
     },
 
     /// Relationship with another user changed
     UserRelationship { id: String, user: User },
     /// Settings updated remotely
     UserSettingsUpdate { id: String, update: UserSettings },
+    /// Notification preferences updated
+    NotificationSettingsUpdate {
+        id: String,
+        settings: NotificationSettings,
+    },
 
     /// User has been platform banned or deleted their account
     ///
@@ -212,6 +271,15 @@ pub enum EventV1 {
         message_id: String,
     },
 
+    /// Number of unread messages in a channel changed for the current user
+    ChannelUnreadUpdate {
+        id: String,
+        count: u32,
+        mentions: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        server: Option<ServerUnread>,
+    },
+
     /// New webhook
     WebhookCreate(Webhook),
 
@@ -232,26 +300,53 @@ pub enum EventV1 {
 impl EventV1 {
     /// Publish helper wrapper
     pub async fn p(self, channel: String) {
-        #[cfg(not(debug_assertions))]
-        redis_kiss::p(channel, self).await;
+        self.p_many(vec![channel]).await;
+    }
 
-        #[cfg(debug_assertions)]
-        info!("Publishing event to {channel}: {self:?}");
+    /// Publish the event to several topics under a single event id
+    ///
+    /// Subscribers which receive it on more than one topic will only handle it once.
+    pub async fn p_many(self, topics: Vec<String>) {
+        self.p_with_id(Ulid::new().to_string(), topics).await;
+    }
+
+    /// Publish the event to several topics under an existing event id
+    ///
+    /// Returns false if the event could not be published to every topic.
+    pub async fn p_with_id(self, id: String, topics: Vec<String>) -> bool {
+        cache::invalidate(&self);
+
+        let broker = broker().await;
+        for topic in topics {
+            #[cfg(debug_assertions)]
+            info!("Publishing event {id} to {topic}: {self:?}");
+
+            if let Err(err) = broker.publish(id.clone(), topic, self.clone()).await {
+                error!("Failed to publish event {id}: {err:?}");
+                return false;
+            }
+        }
 
-        #[cfg(debug_assertions)]
-        redis_kiss::publish(channel, self).await.unwrap();
+        true
     }
 
-    /// Publish user event
-    pub async fn p_user(self, id: String, db: &Database) {
-        self.clone().p(id.clone()).await;
+    /// Topics a user event is published to
+    async fn user_topics(id: String, db: &Database) -> Vec<String> {
+        let mut topics = vec![id.clone()];
 
         // TODO: this should be captured by member list in the future and not immediately fanned out to users
         if let Ok(members) = db.fetch_all_memberships(&id).await {
             for member in members {
-                self.clone().server(member.id.server).await;
+                topics.push(format!("{}u", member.id.server));
             }
         }
+
+        topics
+    }
+
+    /// Publish user event
+    pub async fn p_user(self, id: String, db: &Database) {
+        self.p_many(EventV1::user_topics(id, db).await).await;
     }
 
     /// Publish private event
@@ -268,4 +363,28 @@ impl EventV1 {
     pub async fn global(self) {
         self.p("global".to_string()).await;
     }
+
+    /// Publish event through the outbox
+    ///
+    /// The event is written as part of the transaction and only
+    /// published once the transaction has been committed.
+    pub async fn p_outbox(self, tx: &Transaction, channel: String) {
+        self.p_many_outbox(tx, vec![channel]).await;
+    }
+
+    /// Publish event to several topics through the outbox
+    pub async fn p_many_outbox(self, tx: &Transaction, topics: Vec<String>) {
+        tx.publish(self, topics);
+    }
+
+    /// Publish user event through the outbox
+    pub async fn p_user_outbox(self, id: String, tx: &Transaction) {
+        let topics = EventV1::user_topics(id, tx).await;
+        self.p_many_outbox(tx, topics).await;
+    }
+
+    /// Publish private event through the outbox
+    pub async fn private_outbox(self, tx: &Transaction, id: String) {
+        self.p_outbox(tx, format!("{id}!")).await;
+    }
 }
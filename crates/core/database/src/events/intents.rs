@@ -0,0 +1,116 @@
+use std::fmt;
+
+/// Gateway intent definitions
+///
+/// Each intent enables delivery of one class of events to a connection.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u32)]
+pub enum GatewayIntent {
+    /// Server, role, server channel and emoji events
+    Servers = 1 << 0,
+    /// Member join, leave and update events for other users
+    ///
+    /// This is a privileged intent.
+    ServerMembers = 1 << 1,
+    /// Message events in server channels
+    ServerMessages = 1 << 2,
+    /// Channel and message events in direct messages and groups
+    DirectMessages = 1 << 3,
+    /// Message reaction events
+    MessageReactions = 1 << 4,
+    /// Typing indicator events
+    Typing = 1 << 5,
+    /// Presence and status changes of other users
+    ///
+    /// This is a privileged intent.
+    Presence = 1 << 6,
+    /// Webhook events
+    Webhooks = 1 << 7,
+}
+
+impl fmt::Display for GatewayIntent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Every intent that may be requested
+pub const ALL_INTENTS: u32 = GatewayIntent::Servers as u32
+    | GatewayIntent::ServerMembers as u32
+    | GatewayIntent::ServerMessages as u32
+    | GatewayIntent::DirectMessages as u32
+    | GatewayIntent::MessageReactions as u32
+    | GatewayIntent::Typing as u32
+    | GatewayIntent::Presence as u32
+    | GatewayIntent::Webhooks as u32;
+
+/// Intents which bots must be explicitly granted
+pub const PRIVILEGED_INTENTS: u32 =
+    GatewayIntent::ServerMembers as u32 | GatewayIntent::Presence as u32;
+
+/// Set of intents requested by a connection
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct GatewayIntents(pub u32);
+
+impl Default for GatewayIntents {
+    fn default() -> Self {
+        GatewayIntents(ALL_INTENTS)
+    }
+}
+
+impl GatewayIntents {
+    /// Resolve the intents for a connection
+    ///
+    /// If the client did not request anything in particular, every intent is
+    /// enabled except for privileged intents which have not been granted.
+    /// Explicitly requesting a privileged intent which has not been granted
+    /// will return the offending bits as an error.
+    pub fn resolve(requested: Option<u32>, granted_privileged: u32) -> Result<Self, u32> {
+        let denied = PRIVILEGED_INTENTS & !granted_privileged;
+        match requested {
+            Some(requested) => {
+                if requested & denied != 0 {
+                    Err(requested & denied)
+                } else {
+                    Ok(GatewayIntents(requested & ALL_INTENTS))
+                }
+            }
+            None => Ok(GatewayIntents(ALL_INTENTS & !denied)),
+        }
+    }
+
+    /// Check whether an intent was requested
+    pub fn has(&self, intent: GatewayIntent) -> bool {
+        self.0 & intent as u32 == intent as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GatewayIntent, GatewayIntents, ALL_INTENTS, PRIVILEGED_INTENTS};
+
+    #[test]
+    fn resolve_defaults_to_granted_intents() {
+        let intents = GatewayIntents::resolve(None, PRIVILEGED_INTENTS).unwrap();
+        assert_eq!(intents.0, ALL_INTENTS);
+
+        let intents = GatewayIntents::resolve(None, 0).unwrap();
+        assert!(intents.has(GatewayIntent::ServerMessages));
+        assert!(!intents.has(GatewayIntent::ServerMembers));
+        assert!(!intents.has(GatewayIntent::Presence));
+    }
+
+    #[test]
+    fn resolve_rejects_privileged_intents() {
+        let requested = GatewayIntent::Servers as u32 | GatewayIntent::Presence as u32;
+        assert_eq!(
+            GatewayIntents::resolve(Some(requested), 0),
+            Err(GatewayIntent::Presence as u32)
+        );
+
+        let intents =
+            GatewayIntents::resolve(Some(requested), GatewayIntent::Presence as u32).unwrap();
+        assert!(intents.has(GatewayIntent::Presence));
+        assert!(!intents.has(GatewayIntent::Typing));
+    }
+}
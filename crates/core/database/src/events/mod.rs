@@ -1,2 +1,4 @@
+pub mod broker;
 pub mod client;
+pub mod intents;
 pub mod server;
@@ -1,3 +1,4 @@
+use revolt_models::v0;
 use serde::Deserialize;
 
 use super::client::Ping;
@@ -5,9 +6,40 @@ use super::client::Ping;
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
-    Authenticate { token: String },
+    Authenticate {
+        token: String,
+        intents: Option<u32>,
+    },
     BeginTyping { channel: String },
     EndTyping { channel: String },
     Subscribe { server_id: String },
+    SubscribeMemberList {
+        channel: String,
+        ranges: Vec<(usize, usize)>,
+    },
     Ping { data: Ping, responded: Option<()> },
+    Idle { idle: bool },
+    SetActivities { activities: Vec<v0::Activity> },
+    SendMessage {
+        nonce: String,
+        channel: String,
+        data: v0::DataMessageSend,
+    },
+    EditMessage {
+        nonce: String,
+        channel: String,
+        message: String,
+        data: v0::DataEditMessage,
+    },
+    Ack {
+        nonce: String,
+        channel: String,
+        message: String,
+    },
+    React {
+        nonce: String,
+        channel: String,
+        message: String,
+        emoji: String,
+    },
 }
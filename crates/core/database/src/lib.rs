@@ -80,6 +80,7 @@ mod models;
 pub mod util;
 pub use models::*;
 
+pub mod actions;
 pub mod events;
 pub mod tasks;
 
@@ -1,3 +1,5 @@
+use iso8601_timestamp::Timestamp;
+
 auto_derived!(
     /// Document representing migration information
     pub struct MigrationInfo {
@@ -7,10 +9,113 @@ auto_derived!(
         /// Current database revision
         pub revision: i32,
     }
+
+    /// Record of a registered migration having been applied
+    pub struct MigrationRecord {
+        /// Migration Id
+        #[serde(rename = "_id")]
+        pub id: String,
+        /// Description of the migration at the time it was applied
+        pub description: String,
+        /// Time at which the migration was applied
+        pub applied_at: Timestamp,
+        /// How long the migration took to apply in milliseconds
+        pub duration_ms: i64,
+        /// Number of documents affected by the migration
+        pub affected: i64,
+    }
 );
 
+/// Direction to run a migration in
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MigrationDirection {
+    Up,
+    Down,
+}
+
+/// Migration registered to run against every database driver
+///
+/// Each driver implements the steps for a migration by matching on its id.
+#[derive(Debug)]
+pub struct Migration {
+    /// Unique Id, migrations are applied in order of their position in [`MIGRATIONS`]
+    pub id: &'static str,
+    /// Description of what the migration does
+    pub description: &'static str,
+    /// Whether the migration can be reverted
+    pub reversible: bool,
+}
+
+/// Registered migrations, in the order they should be applied
+///
+/// On MongoDB these come after the legacy migrations, which are registered
+/// by the driver. New migrations must be appended to the end.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        id: "0001_outbox_events",
+        description: "Add collection `outbox_events` for the event outbox.",
+        reversible: true,
+    },
+    Migration {
+        id: "0002_file_parents",
+        description: "Replace the parent id fields on `attachments` with a typed `parent`.",
+        reversible: true,
+    },
+    Migration {
+        id: "0003_jobs",
+        description: "Add collection `jobs` for the background job queue.",
+        reversible: true,
+    },
+    Migration {
+        id: "0004_notification_settings",
+        description: "Add collection `notification_settings` for notification preferences.",
+        reversible: true,
+    },
+    Migration {
+        id: "0005_unified_push_subscriptions",
+        description: "Add collection `unified_push_subscriptions` for UnifiedPush endpoints.",
+        reversible: true,
+    },
+    Migration {
+        id: "0006_push_windows",
+        description: "Add collection `push_windows` for coalescing push notifications.",
+        reversible: true,
+    },
+    Migration {
+        id: "0007_invites",
+        description: "Add collection `invites` for registration invites on SQL databases.",
+        reversible: true,
+    },
+];
+
+/// Status of a registered migration
+#[derive(Debug)]
+pub struct MigrationStatus {
+    /// Registered migration
+    pub migration: &'static Migration,
+    /// Record of the migration, if it has been applied
+    pub record: Option<MigrationRecord>,
+}
+
+/// Outcome of applying or reverting a migration
+#[derive(Debug)]
+pub struct MigrationReport {
+    /// Migration Id
+    pub id: &'static str,
+    /// Direction the migration was run in
+    pub direction: MigrationDirection,
+    /// Whether this was a dry run which changed nothing
+    pub dry_run: bool,
+    /// Number of documents which were (or would be) affected
+    pub affected: i64,
+    /// How long the migration took to run in milliseconds
+    pub duration_ms: i64,
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::{MigrationDirection, MIGRATIONS};
+
     #[async_std::test]
     async fn migrate() {
         database_test!(|db| async move {
@@ -19,6 +124,67 @@ mod tests {
 
             // Migrate the existing database
             db.migrate_database().await.unwrap();
+
+            // Every registered migration has been applied
+            let status = db.migration_status().await.unwrap();
+            assert_eq!(status.len(), db.migrations().len());
+            assert!(status.iter().all(|status| status.record.is_some()));
+        });
+    }
+
+    #[async_std::test]
+    async fn revert_and_reapply() {
+        database_test!(|db| async move {
+            db.migrate_database().await.unwrap();
+
+            let latest = MIGRATIONS.last().unwrap();
+            assert!(db.apply_migration(latest.id, false).await.is_err());
+
+            // A dry run does not change the history
+            let report = db.revert_migration(latest.id, true).await.unwrap();
+            assert!(report.dry_run);
+            assert_eq!(report.direction, MigrationDirection::Down);
+            assert!(db
+                .migration_status()
+                .await
+                .unwrap()
+                .last()
+                .unwrap()
+                .record
+                .is_some());
+
+            db.revert_migration(latest.id, false).await.unwrap();
+            assert!(db
+                .migration_status()
+                .await
+                .unwrap()
+                .last()
+                .unwrap()
+                .record
+                .is_none());
+
+            let reports = db.apply_pending_migrations(false).await.unwrap();
+            assert_eq!(reports.len(), 1);
+            assert_eq!(reports[0].id, latest.id);
+
+            let record = db
+                .migration_status()
+                .await
+                .unwrap()
+                .pop()
+                .unwrap()
+                .record
+                .unwrap();
+            assert_eq!(record.id, latest.id);
+        });
+    }
+
+    #[async_std::test]
+    async fn unknown_migration() {
+        database_test!(|db| async move {
+            db.migrate_database().await.unwrap();
+            assert!(db.apply_migration("unknown", true).await.is_err());
+            assert!(db.revert_migration("unknown", true).await.is_err());
         });
     }
 }
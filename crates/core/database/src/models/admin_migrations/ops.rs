@@ -1,5 +1,18 @@
+use std::time::Instant;
+
+use iso8601_timestamp::Timestamp;
+use revolt_result::Result;
+
+use crate::{
+    Migration, MigrationDirection, MigrationRecord, MigrationReport, MigrationStatus, MIGRATIONS,
+};
+
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractMigrations: Sync + Send {
@@ -8,5 +21,180 @@ pub trait AbstractMigrations: Sync + Send {
     async fn drop_database(&self);
 
     /// Migrate the database
+    ///
+    /// Brings the database up to the latest revision then applies any pending registered migrations.
     async fn migrate_database(&self) -> Result<(), ()>;
+
+    /// Migrations which apply to this database, in the order they should be applied
+    fn migrations(&self) -> Vec<&'static Migration> {
+        MIGRATIONS.iter().collect()
+    }
+
+    /// Find a migration which applies to this database by its id
+    fn find_migration(&self, id: &str) -> Option<&'static Migration> {
+        self.migrations()
+            .into_iter()
+            .find(|migration| migration.id == id)
+    }
+
+    /// Fetch records of all applied migrations
+    async fn fetch_migration_history(&self) -> Result<Vec<MigrationRecord>>;
+
+    /// Insert a record of an applied migration
+    async fn insert_migration_record(&self, record: &MigrationRecord) -> Result<()>;
+
+    /// Delete the record of a reverted migration
+    async fn delete_migration_record(&self, id: &str) -> Result<()>;
+
+    /// Run the steps of a registered migration in the given direction
+    ///
+    /// Returns the number of documents which were (or, for a dry run, would be) affected.
+    async fn run_migration(
+        &self,
+        id: &str,
+        direction: MigrationDirection,
+        dry_run: bool,
+    ) -> Result<i64>;
+
+    /// Fetch the status of every registered migration
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        let mut history = self.fetch_migration_history().await?;
+        Ok(self
+            .migrations()
+            .into_iter()
+            .map(|migration| MigrationStatus {
+                migration,
+                record: history
+                    .iter()
+                    .position(|record| record.id == migration.id)
+                    .map(|index| history.swap_remove(index)),
+            })
+            .collect())
+    }
+
+    /// Apply a registered migration
+    ///
+    /// Every migration registered before it must already be applied.
+    async fn apply_migration(&self, id: &str, dry_run: bool) -> Result<MigrationReport> {
+        let migration = self
+            .find_migration(id)
+            .ok_or_else(|| create_error!(NotFound))?;
+        for status in self.migration_status().await? {
+            if status.migration.id == migration.id {
+                if status.record.is_some() {
+                    return Err(create_error!(NoEffect));
+                }
+
+                break;
+            }
+
+            if status.record.is_none() {
+                return Err(create_error!(InvalidOperation));
+            }
+        }
+
+        if dry_run {
+            info!(
+                "Checking migration [{}]: {}",
+                migration.id, migration.description
+            );
+        } else {
+            info!(
+                "Running migration [{}]: {}",
+                migration.id, migration.description
+            );
+        }
+
+        let start = Instant::now();
+        let affected = self
+            .run_migration(migration.id, MigrationDirection::Up, dry_run)
+            .await?;
+        let duration_ms = start.elapsed().as_millis() as i64;
+
+        if !dry_run {
+            self.insert_migration_record(&MigrationRecord {
+                id: migration.id.to_string(),
+                description: migration.description.to_string(),
+                applied_at: Timestamp::now_utc(),
+                duration_ms,
+                affected,
+            })
+            .await?;
+        }
+
+        Ok(MigrationReport {
+            id: migration.id,
+            direction: MigrationDirection::Up,
+            dry_run,
+            affected,
+            duration_ms,
+        })
+    }
+
+    /// Revert a registered migration
+    ///
+    /// Only the most recently applied migration may be reverted.
+    async fn revert_migration(&self, id: &str, dry_run: bool) -> Result<MigrationReport> {
+        let migration = self
+            .find_migration(id)
+            .ok_or_else(|| create_error!(NotFound))?;
+        if !migration.reversible {
+            return Err(create_error!(InvalidOperation));
+        }
+
+        let latest = self
+            .migration_status()
+            .await?
+            .into_iter()
+            .rev()
+            .find(|status| status.record.is_some());
+
+        match latest {
+            Some(status) if status.migration.id == migration.id => {}
+            _ => return Err(create_error!(InvalidOperation)),
+        }
+
+        if dry_run {
+            info!("Checking revert of migration [{}].", migration.id);
+        } else {
+            info!("Reverting migration [{}].", migration.id);
+        }
+
+        let start = Instant::now();
+        let affected = self
+            .run_migration(migration.id, MigrationDirection::Down, dry_run)
+            .await?;
+        let duration_ms = start.elapsed().as_millis() as i64;
+
+        if !dry_run {
+            self.delete_migration_record(migration.id).await?;
+        }
+
+        Ok(MigrationReport {
+            id: migration.id,
+            direction: MigrationDirection::Down,
+            dry_run,
+            affected,
+            duration_ms,
+        })
+    }
+
+    /// Apply every pending registered migration in order
+    ///
+    /// A dry run only checks the first pending migration, as later
+    /// migrations may depend on changes made by earlier ones.
+    async fn apply_pending_migrations(&self, dry_run: bool) -> Result<Vec<MigrationReport>> {
+        let mut reports = vec![];
+        for status in self.migration_status().await? {
+            if status.record.is_none() {
+                reports.push(self.apply_migration(status.migration.id, dry_run).await?);
+
+                if dry_run {
+                    break;
+                }
+            }
+        }
+
+        Ok(reports)
+    }
 }
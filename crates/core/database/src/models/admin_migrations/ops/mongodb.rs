@@ -1,9 +1,151 @@
-use crate::MongoDb;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use iso8601_timestamp::Timestamp;
+use revolt_result::Result;
+use ulid::Ulid;
+
+use crate::{
+    mongodb::{
+        bson::{doc, DateTime, Document},
+        error::{Error, ErrorKind, WriteFailure},
+        options::FindOneAndUpdateOptions,
+    },
+    Migration, MigrationDirection, MigrationRecord, MongoDb, MIGRATIONS,
+};
+
+use self::scripts::LEGACY_MIGRATIONS;
 
 use super::AbstractMigrations;
 
 mod init;
 mod scripts;
+mod units;
+
+static COL: &str = "migration_history";
+static LOCK_COL: &str = "migration_lock";
+
+/// How long the migration lock is held for if it stops being renewed
+const LOCK_LEASE: Duration = Duration::from_secs(60);
+
+/// Lock held by the replica which is migrating the database
+///
+/// The lease is renewed in the background until the lock is dropped.
+struct MigrationLock {
+    holder: String,
+    held: Arc<AtomicBool>,
+}
+
+/// Time at which a lease taken now expires
+fn lease_end() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + LOCK_LEASE.as_millis() as i64)
+}
+
+/// Whether an operation failed because a document with the same id already exists
+fn is_duplicate_key(err: &Error) -> bool {
+    match &*err.kind {
+        ErrorKind::Write(WriteFailure::WriteError(err)) => err.code == 11000,
+        ErrorKind::Command(err) => err.code == 11000,
+        _ => false,
+    }
+}
+
+impl MigrationLock {
+    /// Take the migration lock, waiting for any other replica to finish migrating
+    async fn acquire(db: &MongoDb) -> Result<MigrationLock> {
+        let holder = Ulid::new().to_string();
+        loop {
+            // Upserting fails on the duplicate id while the lock is held elsewhere.
+            let result = db
+                .col::<Document>(LOCK_COL)
+                .find_one_and_update(
+                    doc! {
+                        "_id": "migrations",
+                        "locked_until": {
+                            "$lt": DateTime::now()
+                        }
+                    },
+                    doc! {
+                        "$set": {
+                            "holder": &holder,
+                            "locked_until": lease_end()
+                        }
+                    },
+                    FindOneAndUpdateOptions::builder().upsert(true).build(),
+                )
+                .await;
+
+            match result {
+                Ok(_) => break,
+                Err(err) if is_duplicate_key(&err) => {
+                    info!("Waiting for another replica to finish migrating.");
+                    async_std::task::sleep(Duration::from_secs(1)).await;
+                }
+                Err(_) => return Err(create_database_error!("find_one_and_update", LOCK_COL)),
+            }
+        }
+
+        let held = Arc::new(AtomicBool::new(true));
+        let lock = MigrationLock {
+            holder: holder.clone(),
+            held: held.clone(),
+        };
+
+        let db = db.clone();
+        async_std::task::spawn(async move {
+            loop {
+                async_std::task::sleep(LOCK_LEASE / 3).await;
+                if !held.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                db.col::<Document>(LOCK_COL)
+                    .update_one(
+                        doc! {
+                            "_id": "migrations",
+                            "holder": &holder
+                        },
+                        doc! {
+                            "$set": {
+                                "locked_until": lease_end()
+                            }
+                        },
+                        None,
+                    )
+                    .await
+                    .ok();
+            }
+        });
+
+        Ok(lock)
+    }
+
+    /// Release the lock so other replicas can go ahead
+    async fn release(self, db: &MongoDb) {
+        self.held.store(false, Ordering::Relaxed);
+        db.col::<Document>(LOCK_COL)
+            .delete_one(
+                doc! {
+                    "_id": "migrations",
+                    "holder": &self.holder
+                },
+                None,
+            )
+            .await
+            .ok();
+    }
+}
+
+impl Drop for MigrationLock {
+    fn drop(&mut self) {
+        self.held.store(false, Ordering::Relaxed);
+    }
+}
 
 #[async_trait]
 impl AbstractMigrations for MongoDb {
@@ -14,20 +156,114 @@ impl AbstractMigrations for MongoDb {
     }
 
     /// Migrate the database
+    ///
+    /// Creates the database if it doesn't exist yet then applies any pending migrations.
+    /// Replicas starting at the same time take turns, so migrations never run concurrently.
     async fn migrate_database(&self) -> Result<(), ()> {
         info!("Migrating the database.");
 
+        let lock = MigrationLock::acquire(self)
+            .await
+            .expect("Failed to take the migration lock.");
+
+        // Taking the lock creates the database, so check for a collection made by initialisation.
         let list = self
-            .list_database_names(None, None)
+            .db()
+            .list_collection_names(None)
             .await
-            .expect("Failed to fetch database names.");
+            .expect("Failed to fetch collection names.");
 
-        if list.iter().any(|x| x == &self.1) {
-            scripts::migrate_database(self).await;
-        } else {
+        if !list.iter().any(|x| x == "migrations") {
             init::create_database(self).await;
         }
 
+        self.apply_pending_migrations(false)
+            .await
+            .expect("Failed to apply migrations.");
+
+        lock.release(self).await;
         Ok(())
     }
+
+    /// Migrations which apply to this database, in the order they should be applied
+    fn migrations(&self) -> Vec<&'static Migration> {
+        LEGACY_MIGRATIONS
+            .iter()
+            .map(|(_, migration)| migration)
+            .chain(MIGRATIONS)
+            .collect()
+    }
+
+    /// Fetch records of all applied migrations
+    ///
+    /// Legacy migrations the database was already past when they were registered
+    /// have no record of their own, so they are reported as applied at the epoch.
+    async fn fetch_migration_history(&self) -> Result<Vec<MigrationRecord>> {
+        let mut history: Vec<MigrationRecord> = query!(self, find, COL, doc! {})?;
+        let revision = scripts::fetch_revision(self).await?;
+        for (legacy_revision, migration) in LEGACY_MIGRATIONS {
+            if *legacy_revision < revision
+                && !history.iter().any(|record| record.id == migration.id)
+            {
+                history.push(MigrationRecord {
+                    id: migration.id.to_string(),
+                    description: migration.description.to_string(),
+                    applied_at: Timestamp::UNIX_EPOCH,
+                    duration_ms: 0,
+                    affected: 0,
+                });
+            }
+        }
+
+        Ok(history)
+    }
+
+    /// Insert a record of an applied migration
+    ///
+    /// If another process recorded the migration first, it has already been applied.
+    async fn insert_migration_record(&self, record: &MigrationRecord) -> Result<()> {
+        match self
+            .col::<MigrationRecord>(COL)
+            .insert_one(record, None)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if is_duplicate_key(&err) => {
+                info!("Migration [{}] was already recorded.", record.id);
+                Ok(())
+            }
+            Err(_) => Err(create_database_error!("insert_one", COL)),
+        }
+    }
+
+    /// Delete the record of a reverted migration
+    async fn delete_migration_record(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Run the steps of a registered migration in the given direction
+    async fn run_migration(
+        &self,
+        id: &str,
+        direction: MigrationDirection,
+        dry_run: bool,
+    ) -> Result<i64> {
+        if LEGACY_MIGRATIONS
+            .iter()
+            .any(|(_, migration)| migration.id == id)
+        {
+            // Legacy migrations can't be previewed, so a dry run reports nothing affected.
+            if direction == MigrationDirection::Down {
+                return Err(create_error!(InvalidOperation));
+            }
+
+            if !dry_run {
+                scripts::run_legacy_migration(self, id).await?;
+            }
+
+            return Ok(0);
+        }
+
+        units::run_migration(self, id, direction, dry_run).await
+    }
 }
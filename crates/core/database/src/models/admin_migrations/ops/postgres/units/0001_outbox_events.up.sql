@@ -0,0 +1,9 @@
+-- Events waiting to be published, written alongside the change they describe
+
+CREATE TABLE IF NOT EXISTS outbox_events (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS outbox_events_claimed_until
+    ON outbox_events (((data->>'claimed_until')::bigint));
@@ -0,0 +1,25 @@
+-- Restore the parent id fields on attachments from their typed parent
+
+UPDATE attachments
+SET data = data || jsonb_build_object('message_id', data->'parent'->>'id')
+WHERE data->'parent'->>'type' = 'Message';
+
+UPDATE attachments
+SET data = data || jsonb_build_object('user_id', data->'parent'->>'id')
+WHERE data->'parent'->>'type' IN ('UserAvatar', 'UserBackground', 'WebhookAvatar');
+
+UPDATE attachments
+SET data = data || jsonb_build_object('user_id', data->'parent'->>'user')
+WHERE data->'parent'->>'type' = 'MemberAvatar';
+
+UPDATE attachments
+SET data = data || jsonb_build_object('object_id', data->'parent'->>'id')
+WHERE data->'parent'->>'type' IN ('ChannelIcon', 'ServerIcon', 'Emoji');
+
+UPDATE attachments
+SET data = data || jsonb_build_object('server_id', data->'parent'->>'id')
+WHERE data->'parent'->>'type' = 'ServerBanner';
+
+UPDATE attachments
+SET data = data - 'parent' - 'orphaned_at'
+WHERE data ?| ARRAY['parent', 'orphaned_at'];
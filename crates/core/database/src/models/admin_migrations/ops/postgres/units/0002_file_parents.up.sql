@@ -0,0 +1,75 @@
+-- Replace the parent id fields on attachments with a typed parent
+
+UPDATE attachments
+SET data = data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'Message', 'id', data->>'message_id')
+)
+WHERE data ? 'message_id';
+
+-- The legacy fields don't distinguish between e.g. user and webhook avatars,
+-- so everything else is found from the objects using the files.
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'UserAvatar', 'id', u.data->>'_id')
+)
+FROM users u
+WHERE u.data->'avatar'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'UserBackground', 'id', u.data->>'_id')
+)
+FROM users u
+WHERE u.data->'profile'->'background'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object(
+        'type', 'MemberAvatar',
+        'server', m.data->'_id'->>'server',
+        'user', m.data->'_id'->>'user'
+    )
+)
+FROM server_members m
+WHERE m.data->'avatar'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'ChannelIcon', 'id', c.data->>'_id')
+)
+FROM channels c
+WHERE c.data->'icon'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'ServerIcon', 'id', s.data->>'_id')
+)
+FROM servers s
+WHERE s.data->'icon'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'ServerBanner', 'id', s.data->>'_id')
+)
+FROM servers s
+WHERE s.data->'banner'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'WebhookAvatar', 'id', w.data->>'_id')
+)
+FROM channel_webhooks w
+WHERE w.data->'avatar'->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+-- Emoji files share their id with the emoji.
+UPDATE attachments a
+SET data = a.data || jsonb_build_object(
+    'parent', jsonb_build_object('type', 'Emoji', 'id', e.data->>'_id')
+)
+FROM emojis e
+WHERE e.data->>'_id' = a.data->>'_id' AND NOT a.data ? 'parent';
+
+UPDATE attachments
+SET data = data - 'message_id' - 'user_id' - 'server_id' - 'object_id'
+WHERE data ?| ARRAY['message_id', 'user_id', 'server_id', 'object_id'];
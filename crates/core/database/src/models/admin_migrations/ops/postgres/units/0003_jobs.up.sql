@@ -0,0 +1,9 @@
+-- Background jobs waiting to be processed, or dead-lettered after failing too often
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS jobs_available
+    ON jobs ((data->>'queue'), ((data->>'dead')::boolean), ((data->>'available_at')::bigint));
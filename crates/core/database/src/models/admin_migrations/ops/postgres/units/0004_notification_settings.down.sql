@@ -0,0 +1,5 @@
+-- Count the preferences being discarded, then remove them entirely
+
+DELETE FROM notification_settings;
+
+DROP TABLE notification_settings;
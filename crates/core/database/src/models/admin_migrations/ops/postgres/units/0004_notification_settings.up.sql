@@ -0,0 +1,6 @@
+-- Notification preferences for each user, keyed by their id
+
+CREATE TABLE IF NOT EXISTS notification_settings (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Count the subscriptions being discarded, then remove them entirely
+
+DELETE FROM unified_push_subscriptions;
+
+DROP TABLE unified_push_subscriptions;
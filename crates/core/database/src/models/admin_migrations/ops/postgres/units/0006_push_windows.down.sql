@@ -0,0 +1,5 @@
+-- Discard any notifications still being held back, then remove them entirely
+
+DELETE FROM push_windows;
+
+DROP TABLE push_windows;
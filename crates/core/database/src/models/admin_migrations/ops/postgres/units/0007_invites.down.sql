@@ -0,0 +1,3 @@
+-- Remove registration invites, including any which haven't been claimed
+
+DROP TABLE invites;
@@ -0,0 +1,6 @@
+-- Registration invites kept for Authifier, keyed by the invite code
+
+CREATE TABLE IF NOT EXISTS invites (
+    id JSONB PRIMARY KEY,
+    data JSONB NOT NULL
+);
@@ -1,4 +1,6 @@
-use crate::ReferenceDb;
+use revolt_result::Result;
+
+use crate::{MigrationDirection, MigrationRecord, ReferenceDb};
 
 use super::AbstractMigrations;
 
@@ -10,7 +12,119 @@ impl AbstractMigrations for ReferenceDb {
 
     /// Migrate the database
     async fn migrate_database(&self) -> Result<(), ()> {
-        // Here you would do your typical migrations if this was a real database.
+        // There is no schema to bring up to date, but registered
+        // migrations still run so that they can be tested.
+        self.apply_pending_migrations(false)
+            .await
+            .expect("Failed to apply migrations.");
+
         Ok(())
     }
+
+    /// Fetch records of all applied migrations
+    async fn fetch_migration_history(&self) -> Result<Vec<MigrationRecord>> {
+        let migration_history = self.migration_history.lock().await;
+        Ok(migration_history.values().cloned().collect())
+    }
+
+    /// Insert a record of an applied migration
+    async fn insert_migration_record(&self, record: &MigrationRecord) -> Result<()> {
+        let mut migration_history = self.migration_history.lock().await;
+        if migration_history.contains_key(&record.id) {
+            Err(create_database_error!("insert", "migration_history"))
+        } else {
+            migration_history.insert(record.id.to_string(), record.clone());
+            Ok(())
+        }
+    }
+
+    /// Delete the record of a reverted migration
+    async fn delete_migration_record(&self, id: &str) -> Result<()> {
+        let mut migration_history = self.migration_history.lock().await;
+        if migration_history.remove(id).is_some() {
+            Ok(())
+        } else {
+            Err(create_error!(NotFound))
+        }
+    }
+
+    /// Run the steps of a registered migration in the given direction
+    async fn run_migration(
+        &self,
+        id: &str,
+        direction: MigrationDirection,
+        dry_run: bool,
+    ) -> Result<i64> {
+        match (id, direction) {
+            ("0001_outbox_events", MigrationDirection::Up) => Ok(0),
+            ("0001_outbox_events", MigrationDirection::Down) => {
+                let mut outbox_events = self.outbox_events.lock().await;
+                let affected = outbox_events.len() as i64;
+                if !dry_run {
+                    outbox_events.clear();
+                }
+
+                Ok(affected)
+            }
+            // Files held in memory never had the legacy parent fields.
+            ("0002_file_parents", MigrationDirection::Up) => Ok(0),
+            ("0002_file_parents", MigrationDirection::Down) => {
+                let mut files = self.files.lock().await;
+                let mut affected = 0;
+                for file in files.values_mut() {
+                    if file.parent.is_some() {
+                        affected += 1;
+                        if !dry_run {
+                            file.parent = None;
+                            file.orphaned_at = None;
+                        }
+                    }
+                }
+
+                Ok(affected)
+            }
+            ("0003_jobs", MigrationDirection::Up) => Ok(0),
+            ("0003_jobs", MigrationDirection::Down) => {
+                let mut jobs = self.jobs.lock().await;
+                let affected = jobs.len() as i64;
+                if !dry_run {
+                    jobs.clear();
+                }
+
+                Ok(affected)
+            }
+            ("0004_notification_settings", MigrationDirection::Up) => Ok(0),
+            ("0004_notification_settings", MigrationDirection::Down) => {
+                let mut notification_settings = self.notification_settings.lock().await;
+                let affected = notification_settings.len() as i64;
+                if !dry_run {
+                    notification_settings.clear();
+                }
+
+                Ok(affected)
+            }
+            ("0005_unified_push_subscriptions", MigrationDirection::Up) => Ok(0),
+            ("0005_unified_push_subscriptions", MigrationDirection::Down) => {
+                let mut unified_push_subscriptions = self.unified_push_subscriptions.lock().await;
+                let affected = unified_push_subscriptions.len() as i64;
+                if !dry_run {
+                    unified_push_subscriptions.clear();
+                }
+
+                Ok(affected)
+            }
+            ("0006_push_windows", MigrationDirection::Up) => Ok(0),
+            ("0006_push_windows", MigrationDirection::Down) => {
+                let mut push_windows = self.push_windows.lock().await;
+                let affected = push_windows.len() as i64;
+                if !dry_run {
+                    push_windows.clear();
+                }
+
+                Ok(affected)
+            }
+            ("0007_invites", _) => Ok(0),
+            _ => Err(create_error!(NotFound)),
+        }
+    }
 }
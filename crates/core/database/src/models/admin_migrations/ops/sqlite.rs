@@ -0,0 +1,77 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::{MigrationDirection, MigrationRecord, SqliteDb};
+
+use super::AbstractMigrations;
+
+mod scripts;
+mod units;
+
+static COL: &str = "migration_history";
+
+#[async_trait]
+impl AbstractMigrations for SqliteDb {
+    #[cfg(test)]
+    /// Drop the database
+    ///
+    /// Tables only exist once migrated, so the schema is recreated straight away.
+    async fn drop_database(&self) {
+        // Dropping the search table also drops its shadow tables.
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table'
+             AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'messages_search_%'",
+        )
+        .fetch_all(&self.0)
+        .await
+        .unwrap_or_default();
+
+        for table in tables {
+            sqlx::query(&format!(r#"DROP TABLE IF EXISTS "{table}""#))
+                .execute(&self.0)
+                .await
+                .ok();
+        }
+
+        self.migrate_database().await.ok();
+    }
+
+    /// Migrate the database
+    ///
+    /// Brings the database up to the latest revision then applies any pending registered migrations.
+    async fn migrate_database(&self) -> Result<(), ()> {
+        info!("Migrating the database.");
+        scripts::migrate_database(self).await;
+
+        self.apply_pending_migrations(false)
+            .await
+            .expect("Failed to apply migrations.");
+
+        Ok(())
+    }
+
+    /// Fetch records of all applied migrations
+    async fn fetch_migration_history(&self) -> Result<Vec<MigrationRecord>> {
+        query!(self, find, COL, json!({}))
+    }
+
+    /// Insert a record of an applied migration
+    async fn insert_migration_record(&self, record: &MigrationRecord) -> Result<()> {
+        query!(self, insert_one, COL, &record)
+    }
+
+    /// Delete the record of a reverted migration
+    async fn delete_migration_record(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Run the steps of a registered migration in the given direction
+    async fn run_migration(
+        &self,
+        id: &str,
+        direction: MigrationDirection,
+        dry_run: bool,
+    ) -> Result<i64> {
+        units::run_migration(self, id, direction, dry_run).await
+    }
+}
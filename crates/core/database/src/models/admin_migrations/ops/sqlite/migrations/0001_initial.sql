@@ -0,0 +1,138 @@
+-- Every collection is a table of JSON documents keyed by their `_id`,
+-- the key is the JSON encoding of the `_id` with object keys sorted.
+
+CREATE TABLE accounts (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE channels (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE messages (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE servers (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE server_members (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE server_bans (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE channel_invites (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE channel_unreads (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE channel_webhooks (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE attachments (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE user_settings (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE safety_reports (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE safety_snapshots (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE safety_strikes (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE bots (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE ratelimit_events (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE emojis (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+-- Usernames are unique per discriminator regardless of case
+CREATE INDEX users_username ON users (lower(json_extract(data, '$.username')));
+CREATE UNIQUE INDEX users_username_discriminator
+    ON users (lower(json_extract(data, '$.username')), json_extract(data, '$.discriminator'));
+
+-- Message history is paged by id within a channel
+CREATE INDEX messages_channel_id ON messages (json_extract(data, '$.channel'), id);
+CREATE INDEX messages_author ON messages (json_extract(data, '$.author'));
+
+-- Composite keys are looked up by either half
+CREATE INDEX server_members_server ON server_members (json_extract(id, '$.server'));
+CREATE INDEX server_members_user ON server_members (json_extract(id, '$.user'));
+CREATE INDEX channel_unreads_user ON channel_unreads (json_extract(id, '$.user'));
+
+CREATE INDEX channels_server ON channels (json_extract(data, '$.server'));
+CREATE INDEX channel_invites_channel ON channel_invites (json_extract(data, '$.channel'));
+CREATE INDEX channel_webhooks_channel ON channel_webhooks (json_extract(data, '$.channel_id'));
+CREATE INDEX attachments_message ON attachments (json_extract(data, '$.message_id'));
+CREATE INDEX attachments_object ON attachments (json_extract(data, '$.object_id'));
+CREATE INDEX emojis_parent ON emojis (json_extract(data, '$.parent.id'));
+CREATE UNIQUE INDEX bots_token ON bots (json_extract(data, '$.token'));
+CREATE INDEX sessions_token ON sessions (json_extract(data, '$.token'));
+
+-- Message content is searched through a full text index kept in sync by triggers
+CREATE VIRTUAL TABLE messages_search USING fts5 (content);
+
+CREATE TRIGGER messages_search_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_search (rowid, content)
+    VALUES (new.rowid, coalesce(json_extract(new.data, '$.content'), ''));
+END;
+
+CREATE TRIGGER messages_search_update AFTER UPDATE OF data ON messages BEGIN
+    UPDATE messages_search
+    SET content = coalesce(json_extract(new.data, '$.content'), '')
+    WHERE rowid = new.rowid;
+END;
+
+CREATE TRIGGER messages_search_delete AFTER DELETE ON messages BEGIN
+    DELETE FROM messages_search WHERE rowid = old.rowid;
+END;
@@ -0,0 +1,65 @@
+use sqlx::Executor;
+
+use crate::SqliteDb;
+
+/// Migration scripts in order, the revision is the position in this list
+///
+/// This list is no longer extended, add new migrations to `crate::MIGRATIONS` instead.
+static MIGRATIONS: &[&str] = &[include_str!("migrations/0001_initial.sql")];
+
+pub const LATEST_REVISION: i32 = MIGRATIONS.len() as i32;
+
+pub async fn migrate_database(db: &SqliteDb) {
+    // Taking the write lock up front means only one process migrates at a time.
+    let mut conn = db
+        .begin_immediate()
+        .await
+        .expect("Failed to start migration.");
+
+    let result = async {
+        (&mut *conn)
+            .execute(
+                "CREATE TABLE IF NOT EXISTS migrations (
+                    id INTEGER PRIMARY KEY,
+                    revision INTEGER NOT NULL
+                )",
+            )
+            .await?;
+
+        (&mut *conn)
+            .execute(
+                "CREATE TABLE IF NOT EXISTS migration_history (
+                    id TEXT PRIMARY KEY,
+                    data TEXT NOT NULL
+                )",
+            )
+            .await?;
+
+        let revision: i32 = sqlx::query_scalar("SELECT revision FROM migrations WHERE id = 0")
+            .fetch_optional(&mut *conn)
+            .await?
+            .unwrap_or_default();
+
+        for (index, script) in MIGRATIONS.iter().enumerate().skip(revision as usize) {
+            info!("Running migration [revision {}].", index + 1);
+            (&mut *conn).execute(*script).await?;
+        }
+
+        if revision < LATEST_REVISION {
+            sqlx::query(
+                "INSERT INTO migrations (id, revision) VALUES (0, ?)
+                 ON CONFLICT (id) DO UPDATE SET revision = excluded.revision",
+            )
+            .bind(LATEST_REVISION)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    SqliteDb::finish(conn, result)
+        .await
+        .unwrap_or_else(|err| panic!("Failed to run migrations: {err:?}"));
+}
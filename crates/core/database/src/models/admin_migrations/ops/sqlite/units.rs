@@ -0,0 +1,85 @@
+use revolt_result::Result;
+use sqlx::Executor;
+
+use crate::{MigrationDirection, SqliteDb};
+
+/// Registered migrations as scripts to apply and revert them
+static UNITS: &[(&str, &str, Option<&str>)] = &[
+    (
+        "0001_outbox_events",
+        include_str!("units/0001_outbox_events.up.sql"),
+        Some(include_str!("units/0001_outbox_events.down.sql")),
+    ),
+    (
+        "0002_file_parents",
+        include_str!("units/0002_file_parents.up.sql"),
+        Some(include_str!("units/0002_file_parents.down.sql")),
+    ),
+    (
+        "0003_jobs",
+        include_str!("units/0003_jobs.up.sql"),
+        Some(include_str!("units/0003_jobs.down.sql")),
+    ),
+    (
+        "0004_notification_settings",
+        include_str!("units/0004_notification_settings.up.sql"),
+        Some(include_str!("units/0004_notification_settings.down.sql")),
+    ),
+    (
+        "0005_unified_push_subscriptions",
+        include_str!("units/0005_unified_push_subscriptions.up.sql"),
+        Some(include_str!(
+            "units/0005_unified_push_subscriptions.down.sql"
+        )),
+    ),
+    (
+        "0006_push_windows",
+        include_str!("units/0006_push_windows.up.sql"),
+        Some(include_str!("units/0006_push_windows.down.sql")),
+    ),
+    (
+        "0007_invites",
+        include_str!("units/0007_invites.up.sql"),
+        Some(include_str!("units/0007_invites.down.sql")),
+    ),
+];
+
+/// Run the steps of a registered migration
+///
+/// Scripts run in a transaction, which is rolled back for a dry run.
+pub async fn run_migration(
+    db: &SqliteDb,
+    id: &str,
+    direction: MigrationDirection,
+    dry_run: bool,
+) -> Result<i64> {
+    let (_, up, down) = UNITS
+        .iter()
+        .find(|(unit, _, _)| *unit == id)
+        .ok_or_else(|| create_error!(NotFound))?;
+
+    let script = match direction {
+        MigrationDirection::Up => up,
+        MigrationDirection::Down => down
+            .as_ref()
+            .ok_or_else(|| create_error!(InvalidOperation))?,
+    };
+
+    let mut conn = db
+        .begin_immediate()
+        .await
+        .map_err(|_| create_database_error!("begin", "migrations"))?;
+
+    let result = (&mut *conn).execute(*script).await;
+    let affected = match result {
+        Ok(result) if !dry_run => SqliteDb::finish(conn, Ok(result.rows_affected())).await,
+        Ok(result) => (&mut *conn)
+            .execute("ROLLBACK")
+            .await
+            .map(|_| result.rows_affected()),
+        Err(err) => SqliteDb::finish(conn, Err(err)).await,
+    }
+    .map_err(|_| create_database_error!("migrate", id))?;
+
+    Ok(affected as i64)
+}
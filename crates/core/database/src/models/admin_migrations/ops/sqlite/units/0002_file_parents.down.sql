@@ -0,0 +1,26 @@
+-- Restore the parent id fields on attachments from their typed parent
+
+UPDATE attachments
+SET data = json_set(data, '$.message_id', json_extract(data, '$.parent.id'))
+WHERE json_extract(data, '$.parent.type') = 'Message';
+
+UPDATE attachments
+SET data = json_set(data, '$.user_id', json_extract(data, '$.parent.id'))
+WHERE json_extract(data, '$.parent.type') IN ('UserAvatar', 'UserBackground', 'WebhookAvatar');
+
+UPDATE attachments
+SET data = json_set(data, '$.user_id', json_extract(data, '$.parent.user'))
+WHERE json_extract(data, '$.parent.type') = 'MemberAvatar';
+
+UPDATE attachments
+SET data = json_set(data, '$.object_id', json_extract(data, '$.parent.id'))
+WHERE json_extract(data, '$.parent.type') IN ('ChannelIcon', 'ServerIcon', 'Emoji');
+
+UPDATE attachments
+SET data = json_set(data, '$.server_id', json_extract(data, '$.parent.id'))
+WHERE json_extract(data, '$.parent.type') = 'ServerBanner';
+
+UPDATE attachments
+SET data = json_remove(data, '$.parent', '$.orphaned_at')
+WHERE json_type(data, '$.parent') IS NOT NULL
+OR json_type(data, '$.orphaned_at') IS NOT NULL;
@@ -0,0 +1,86 @@
+-- Replace the parent id fields on attachments with a typed parent
+
+UPDATE attachments
+SET data = json_set(
+    data, '$.parent', json_object('type', 'Message', 'id', json_extract(data, '$.message_id'))
+)
+WHERE json_type(data, '$.message_id') IS NOT NULL;
+
+-- The legacy fields don't distinguish between e.g. user and webhook avatars,
+-- so everything else is found from the objects using the files.
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'UserAvatar', 'id', json_extract(u.data, '$._id'))
+)
+FROM users u
+WHERE json_extract(u.data, '$.avatar._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'UserBackground', 'id', json_extract(u.data, '$._id'))
+)
+FROM users u
+WHERE json_extract(u.data, '$.profile.background._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object(
+        'type', 'MemberAvatar',
+        'server', json_extract(m.data, '$._id.server'),
+        'user', json_extract(m.data, '$._id.user')
+    )
+)
+FROM server_members m
+WHERE json_extract(m.data, '$.avatar._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'ChannelIcon', 'id', json_extract(c.data, '$._id'))
+)
+FROM channels c
+WHERE json_extract(c.data, '$.icon._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'ServerIcon', 'id', json_extract(s.data, '$._id'))
+)
+FROM servers s
+WHERE json_extract(s.data, '$.icon._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'ServerBanner', 'id', json_extract(s.data, '$._id'))
+)
+FROM servers s
+WHERE json_extract(s.data, '$.banner._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'WebhookAvatar', 'id', json_extract(w.data, '$._id'))
+)
+FROM channel_webhooks w
+WHERE json_extract(w.data, '$.avatar._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+-- Emoji files share their id with the emoji.
+UPDATE attachments
+SET data = json_set(
+    attachments.data, '$.parent', json_object('type', 'Emoji', 'id', json_extract(e.data, '$._id'))
+)
+FROM emojis e
+WHERE json_extract(e.data, '$._id') = json_extract(attachments.data, '$._id')
+AND json_type(attachments.data, '$.parent') IS NULL;
+
+UPDATE attachments
+SET data = json_remove(data, '$.message_id', '$.user_id', '$.server_id', '$.object_id')
+WHERE json_type(data, '$.message_id') IS NOT NULL
+OR json_type(data, '$.user_id') IS NOT NULL
+OR json_type(data, '$.server_id') IS NOT NULL
+OR json_type(data, '$.object_id') IS NOT NULL;
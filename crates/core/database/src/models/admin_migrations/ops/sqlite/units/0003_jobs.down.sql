@@ -0,0 +1,5 @@
+-- Count the jobs being discarded, then remove the queue entirely
+
+DELETE FROM jobs;
+
+DROP TABLE jobs;
@@ -0,0 +1,13 @@
+-- Background jobs waiting to be processed, or dead-lettered after failing too often
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS jobs_available
+    ON jobs (
+        json_extract(data, '$.queue'),
+        json_extract(data, '$.dead'),
+        json_extract(data, '$.available_at')
+    );
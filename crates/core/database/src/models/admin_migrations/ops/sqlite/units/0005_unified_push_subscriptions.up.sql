@@ -0,0 +1,9 @@
+-- UnifiedPush endpoints registered by sessions, keyed by the session id
+
+CREATE TABLE IF NOT EXISTS unified_push_subscriptions (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS unified_push_subscriptions_user
+    ON unified_push_subscriptions (json_extract(data, '$.user'));
@@ -0,0 +1,9 @@
+-- Push notifications being coalesced, keyed by channel and user
+
+CREATE TABLE IF NOT EXISTS push_windows (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS push_windows_channel
+    ON push_windows (json_extract(data, '$._id.channel'));
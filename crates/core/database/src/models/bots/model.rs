@@ -39,6 +39,9 @@ auto_derived_partial!(
         /// Enum of bot flags
         #[serde(skip_serializing_if = "Option::is_none")]
         pub flags: Option<i32>,
+        /// Bitfield of privileged gateway intents this bot may request
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub privileged_intents: Option<i32>,
     },
     "PartialBot"
 );
@@ -65,6 +68,7 @@ impl Default for Bot {
             terms_of_service_url: Default::default(),
             privacy_policy_url: Default::default(),
             flags: Default::default(),
+            privileged_intents: Default::default(),
         }
     }
 }
@@ -3,7 +3,11 @@ use revolt_result::Result;
 use crate::Invite;
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractChannelInvites: Sync + Send {
@@ -1,3 +1,10 @@
+use std::collections::HashMap;
+
+use revolt_models::v0;
+use revolt_result::Result;
+
+use crate::{Channel, Database, NotificationSettings};
+
 auto_derived!(
     /// Channel Unread
     pub struct ChannelUnread {
@@ -11,6 +18,10 @@ auto_derived!(
         /// Array of message ids that mention the user
         #[serde(skip_serializing_if = "Option::is_none")]
         pub mentions: Option<Vec<String>>,
+        /// Whether the user has been sent push notifications about this
+        /// channel since they last read it
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub notified: bool,
     }
 
     /// Composite primary key consisting of channel and user id
@@ -22,3 +33,473 @@ auto_derived!(
         pub user: String,
     }
 );
+
+/// Unread messages are only counted up to this number, which clients show as "99+"
+pub const UNREAD_COUNT_LIMIT: usize = 100;
+
+/// Number of unread messages somewhere
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnreadCount {
+    /// Unread messages, up to [UNREAD_COUNT_LIMIT]
+    pub count: usize,
+    /// Unread messages which mention the user
+    pub mentions: usize,
+}
+
+/// Unread counts across a user's channels
+#[derive(Debug, Clone, Default)]
+pub struct UnreadCounts {
+    /// User Id
+    user: String,
+    /// Counts for channels with anything unread
+    channels: HashMap<String, UnreadCount>,
+    /// Last message read in each channel
+    last_read: HashMap<String, String>,
+    /// Unread messages in each channel which mention the user
+    mentions: HashMap<String, Vec<String>>,
+    /// Server each server channel belongs to
+    servers: HashMap<String, String>,
+    /// Preferences deciding which channels count towards their server
+    settings: NotificationSettings,
+}
+
+/// Server a channel belongs to, if any
+fn channel_server(channel: &Channel) -> Option<&str> {
+    match channel {
+        Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => Some(server),
+        _ => None,
+    }
+}
+
+/// Id of the latest message in a channel, if it is tracked
+fn last_message_id(channel: &Channel) -> Option<&str> {
+    match channel {
+        Channel::DirectMessage {
+            last_message_id, ..
+        }
+        | Channel::Group {
+            last_message_id, ..
+        }
+        | Channel::TextChannel {
+            last_message_id, ..
+        } => last_message_id.as_deref(),
+        _ => None,
+    }
+}
+
+impl UnreadCounts {
+    /// Count unread messages in the given channels
+    ///
+    /// Message ids are ordered by time, so channels whose last message is no
+    /// newer than the last one read are skipped without looking at their messages.
+    pub async fn fetch(
+        db: &Database,
+        user_id: &str,
+        channels: &[Channel],
+        unreads: &[ChannelUnread],
+    ) -> Result<UnreadCounts> {
+        let mut counts = UnreadCounts {
+            user: user_id.to_string(),
+            settings: db.fetch_notification_settings(user_id).await?,
+            ..Default::default()
+        };
+
+        for unread in unreads {
+            let channel = &unread.id.channel;
+            if let Some(last_id) = &unread.last_id {
+                counts.last_read.insert(channel.clone(), last_id.clone());
+            }
+
+            if let Some(mentions) = unread
+                .mentions
+                .as_ref()
+                .filter(|mentions| !mentions.is_empty())
+            {
+                counts.mentions.insert(channel.clone(), mentions.clone());
+            }
+        }
+
+        let mut unread_channels = vec![];
+        for channel in channels {
+            let id = channel.id();
+            if let Some(server) = channel_server(channel) {
+                counts.servers.insert(id.clone(), server.to_string());
+            }
+
+            let Some(last_message_id) = last_message_id(channel) else {
+                continue;
+            };
+
+            let last_id = counts.last_read.get(&id).cloned();
+            if last_id
+                .as_deref()
+                .is_some_and(|last_id| last_id >= last_message_id)
+            {
+                continue;
+            }
+
+            unread_channels.push((id, last_id));
+        }
+
+        for (id, count) in db
+            .count_unread_messages_in_channels(&unread_channels, user_id, UNREAD_COUNT_LIMIT)
+            .await?
+        {
+            let mentions = counts.mentions.get(&id).map_or(0, Vec::len);
+            counts.set(id, UnreadCount { count, mentions });
+        }
+
+        Ok(counts)
+    }
+
+    /// Record the count for a channel, forgetting it if nothing is unread
+    fn set(&mut self, channel: String, count: UnreadCount) {
+        if count == UnreadCount::default() {
+            self.channels.remove(&channel);
+        } else {
+            self.channels.insert(channel, count);
+        }
+    }
+
+    /// Replace the preferences deciding which channels are muted
+    pub fn set_settings(&mut self, settings: NotificationSettings) {
+        self.settings = settings;
+    }
+
+    /// Count a new message sent in a channel
+    pub fn add_message(&mut self, channel: &Channel, message_id: &str, mentioned: bool) {
+        let id = channel.id();
+        if let Some(server) = channel_server(channel) {
+            self.servers.insert(id.clone(), server.to_string());
+        }
+
+        if mentioned {
+            self.mentions
+                .entry(id.clone())
+                .or_default()
+                .push(message_id.to_string());
+        }
+
+        let count = self.channels.entry(id).or_default();
+        count.count = (count.count + 1).min(UNREAD_COUNT_LIMIT);
+        if mentioned {
+            count.mentions += 1;
+        }
+    }
+
+    /// Recount a channel after the user has read up to the given message
+    pub async fn acknowledge(
+        &mut self,
+        db: &Database,
+        channel: &Channel,
+        message_id: &str,
+    ) -> Result<()> {
+        let id = channel.id();
+        let count = db
+            .count_unread_messages(&id, &self.user, Some(message_id), UNREAD_COUNT_LIMIT)
+            .await?;
+
+        // Acknowledging a channel clears its mentions
+        self.last_read.insert(id.clone(), message_id.to_string());
+        self.mentions.remove(&id);
+        self.set(id, UnreadCount { count, mentions: 0 });
+        Ok(())
+    }
+
+    /// Recount a channel after messages were deleted from it
+    ///
+    /// Returns whether the count may have changed, deleting messages the user
+    /// has already read leaves it as it was.
+    pub async fn delete_messages(
+        &mut self,
+        db: &Database,
+        channel: &Channel,
+        message_ids: &[String],
+    ) -> Result<bool> {
+        let id = channel.id();
+        let last_id = self.last_read.get(&id).cloned();
+        if self.channel(&id) == UnreadCount::default()
+            || message_ids.iter().all(|message_id| {
+                last_id
+                    .as_ref()
+                    .is_some_and(|last_id| message_id <= last_id)
+            })
+        {
+            return Ok(false);
+        }
+
+        let count = db
+            .count_unread_messages(&id, &self.user, last_id.as_deref(), UNREAD_COUNT_LIMIT)
+            .await?;
+
+        let mentions = match self.mentions.get_mut(&id) {
+            Some(mentions) => {
+                mentions.retain(|mention| !message_ids.contains(mention));
+                mentions.len()
+            }
+            None => 0,
+        };
+
+        self.set(id, UnreadCount { count, mentions });
+        Ok(true)
+    }
+
+    /// Stop counting a channel which was deleted or can no longer be seen
+    pub fn remove_channel(&mut self, id: &str) {
+        self.channels.remove(id);
+        self.servers.remove(id);
+        self.last_read.remove(id);
+        self.mentions.remove(id);
+    }
+
+    /// Unread messages in a channel
+    pub fn channel(&self, id: &str) -> UnreadCount {
+        self.channels.get(id).copied().unwrap_or_default()
+    }
+
+    /// Unread messages across every server, leaving out muted channels
+    pub fn servers(&self) -> HashMap<String, UnreadCount> {
+        let mut servers = HashMap::<String, UnreadCount>::new();
+        for (channel, count) in &self.channels {
+            let Some(server) = self.servers.get(channel) else {
+                continue;
+            };
+
+            if self.settings.is_muted(Some(server), channel) {
+                continue;
+            }
+
+            let total = servers.entry(server.clone()).or_default();
+            total.count = (total.count + count.count).min(UNREAD_COUNT_LIMIT);
+            total.mentions += count.mentions;
+        }
+
+        servers
+    }
+
+    /// Unread messages across a server's channels, leaving out muted channels
+    pub fn server(&self, id: &str) -> v0::ServerUnread {
+        let count = self.servers().remove(id).unwrap_or_default();
+        v0::ServerUnread {
+            id: id.to_string(),
+            count: count.count as u32,
+            mentions: count.mentions as u32,
+        }
+    }
+
+    /// Unread state of each channel alongside its count, with totals for each server
+    pub fn summarise(&self, unreads: Vec<ChannelUnread>) -> v0::Unreads {
+        let mut channels: Vec<v0::ChannelUnread> = unreads
+            .into_iter()
+            .map(|unread| {
+                let count = self.channel(&unread.id.channel).count as u32;
+                v0::ChannelUnread {
+                    count: Some(count),
+                    ..unread.into()
+                }
+            })
+            .collect();
+
+        // Channels which have never been read don't have an entry yet
+        for (channel, count) in &self.channels {
+            if !channels.iter().any(|unread| &unread.id.channel == channel) {
+                channels.push(v0::ChannelUnread {
+                    id: v0::ChannelCompositeKey {
+                        channel: channel.clone(),
+                        user: self.user.clone(),
+                    },
+                    last_id: None,
+                    mentions: vec![],
+                    count: Some(count.count as u32),
+                });
+            }
+        }
+
+        let mut servers: Vec<v0::ServerUnread> = self
+            .servers()
+            .into_iter()
+            .map(|(id, count)| v0::ServerUnread {
+                id,
+                count: count.count as u32,
+                mentions: count.mentions as u32,
+            })
+            .collect();
+        servers.sort_by(|a, b| a.id.cmp(&b.id));
+
+        v0::Unreads { channels, servers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ulid::Ulid;
+
+    use crate::{
+        Channel, ChannelCompositeKey, ChannelUnread, Message, NotificationLevel,
+        NotificationOverride, NotificationSettings, UnreadCount, UnreadCounts,
+    };
+
+    fn id(index: u64) -> String {
+        Ulid::from_parts(1_700_000_000_000 + index, 0).to_string()
+    }
+
+    fn text_channel(id: &str, last_message_id: Option<String>) -> Channel {
+        Channel::TextChannel {
+            id: id.to_string(),
+            server: "server".to_string(),
+            name: id.to_string(),
+            description: None,
+            icon: None,
+            last_message_id,
+            default_permissions: None,
+            role_permissions: Default::default(),
+            nsfw: false,
+        }
+    }
+
+    fn unread(channel: &str, last_id: u64, mentions: Option<Vec<String>>) -> ChannelUnread {
+        ChannelUnread {
+            id: ChannelCompositeKey {
+                channel: channel.to_string(),
+                user: "user".to_string(),
+            },
+            last_id: Some(id(last_id)),
+            mentions,
+            notified: false,
+        }
+    }
+
+    #[async_std::test]
+    async fn count_unreads() {
+        database_test!(|db| async move {
+            for (index, channel, author) in [
+                (0, "general", "other"),
+                (1, "general", "other"),
+                (2, "general", "user"),
+                (3, "general", "other"),
+                (4, "general", "other"),
+                (5, "muted", "other"),
+                (6, "muted", "other"),
+                (7, "read", "other"),
+            ] {
+                db.insert_message(&Message {
+                    id: id(index),
+                    channel: channel.to_string(),
+                    author: author.to_string(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            }
+
+            let mut settings = NotificationSettings {
+                id: "user".to_string(),
+                ..Default::default()
+            };
+            settings.channels.insert(
+                "muted".to_string(),
+                NotificationOverride {
+                    level: Some(NotificationLevel::None),
+                    ..Default::default()
+                },
+            );
+            settings.set(&db).await.unwrap();
+
+            let general = text_channel("general", Some(id(4)));
+            let channels = [
+                general.clone(),
+                text_channel("muted", Some(id(6))),
+                text_channel("read", Some(id(7))),
+                Channel::DirectMessage {
+                    id: "dm".to_string(),
+                    active: true,
+                    recipients: vec![],
+                    last_message_id: None,
+                },
+            ];
+            let unreads = vec![
+                unread("general", 1, Some(vec![id(3)])),
+                unread("read", 7, None),
+            ];
+
+            // Own messages aren't unread, and muted channels aren't part of their server
+            let mut counts = UnreadCounts::fetch(&db, "user", &channels, &unreads)
+                .await
+                .unwrap();
+            assert_eq!(
+                counts.channel("general"),
+                UnreadCount {
+                    count: 2,
+                    mentions: 1
+                }
+            );
+            assert_eq!(counts.channel("muted").count, 2);
+            assert_eq!(counts.channel("read"), UnreadCount::default());
+            assert_eq!(counts.server("server").count, 2);
+            assert_eq!(counts.server("server").mentions, 1);
+
+            // Deleting an unread message takes it and its mention out of the count
+            db.delete_messages("general", &[id(3)]).await.unwrap();
+            assert!(counts
+                .delete_messages(&db, &general, &[id(3)])
+                .await
+                .unwrap());
+            assert_eq!(
+                counts.channel("general"),
+                UnreadCount {
+                    count: 1,
+                    mentions: 0
+                }
+            );
+            assert!(!counts
+                .delete_messages(&db, &general, &[id(0)])
+                .await
+                .unwrap());
+
+            counts.add_message(&general, &id(8), true);
+            assert_eq!(
+                counts.channel("general"),
+                UnreadCount {
+                    count: 2,
+                    mentions: 1
+                }
+            );
+
+            // Reading part of a channel leaves the rest unread
+            counts.acknowledge(&db, &general, &id(3)).await.unwrap();
+            assert_eq!(
+                counts.channel("general"),
+                UnreadCount {
+                    count: 1,
+                    mentions: 0
+                }
+            );
+
+            let summary = counts.summarise(unreads);
+            let mut channels: Vec<(&str, Option<u32>)> = summary
+                .channels
+                .iter()
+                .map(|unread| (unread.id.channel.as_str(), unread.count))
+                .collect();
+            channels.sort();
+            assert_eq!(
+                channels,
+                [("general", Some(1)), ("muted", Some(2)), ("read", Some(0))]
+            );
+            assert_eq!(summary.servers.len(), 1);
+            assert_eq!(summary.servers[0].count, 1);
+        });
+    }
+
+    #[test]
+    fn counts_are_capped() {
+        let channel = text_channel("general", None);
+        let mut counts = UnreadCounts::default();
+        for _ in 0..150 {
+            counts.add_message(&channel, "message", false);
+        }
+
+        assert_eq!(counts.channel("general").count, super::UNREAD_COUNT_LIMIT);
+        assert_eq!(counts.server("server").count, 100);
+    }
+}
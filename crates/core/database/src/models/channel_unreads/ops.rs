@@ -3,7 +3,11 @@ use revolt_result::Result;
 use crate::ChannelUnread;
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractChannelUnreads: Sync + Send {
@@ -26,6 +30,17 @@ pub trait AbstractChannelUnreads: Sync + Send {
         message_ids: &[String],
     ) -> Result<()>;
 
+    /// Record that a user was sent a push notification about a channel.
+    async fn mark_unread_notified(&self, channel_id: &str, user_id: &str) -> Result<()>;
+
+    /// Find which of the given channels a user was sent push notifications
+    /// about since they last read them, clearing the record.
+    async fn take_notified_unreads(
+        &self,
+        user_id: &str,
+        channel_ids: &[String],
+    ) -> Result<Vec<String>>;
+
     /// Fetch all channel unreads for a user.
     async fn fetch_unreads(&self, user_id: &str) -> Result<Vec<ChannelUnread>>;
 }
@@ -105,6 +105,65 @@ impl AbstractChannelUnreads for MongoDb {
             .map_err(|_| create_database_error!("update_one", COL))
     }
 
+    /// Record that a user was sent a push notification about a channel.
+    async fn mark_unread_notified(&self, channel_id: &str, user_id: &str) -> Result<()> {
+        self.col::<Document>(COL)
+            .update_one(
+                doc! {
+                    "_id.channel": channel_id,
+                    "_id.user": user_id,
+                },
+                doc! {
+                    "$set": {
+                        "notified": true
+                    }
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Find which of the given channels a user was sent push notifications
+    /// about since they last read them, clearing the record.
+    async fn take_notified_unreads(
+        &self,
+        user_id: &str,
+        channel_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let filter = doc! {
+            "_id.channel": {
+                "$in": channel_ids
+            },
+            "_id.user": user_id,
+            "notified": true
+        };
+
+        let unreads: Vec<ChannelUnread> = query!(self, find, COL, filter.clone())?;
+        if unreads.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.col::<Document>(COL)
+            .update_many(
+                filter,
+                doc! {
+                    "$unset": {
+                        "notified": 1_i32
+                    }
+                },
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("update_many", COL))?;
+
+        Ok(unreads
+            .into_iter()
+            .map(|unread| unread.id.channel)
+            .collect())
+    }
+
     /// Fetch all channel unreads for a user.
     async fn fetch_unreads(&self, user_id: &str) -> Result<Vec<ChannelUnread>> {
         query!(
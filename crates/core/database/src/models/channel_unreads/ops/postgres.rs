@@ -0,0 +1,139 @@
+use revolt_result::Result;
+use serde_json::{json, Value};
+use ulid::Ulid;
+
+use crate::ChannelUnread;
+use crate::PostgresDb;
+
+use super::AbstractChannelUnreads;
+
+static COL: &str = "channel_unreads";
+
+#[async_trait]
+impl AbstractChannelUnreads for PostgresDb {
+    /// Acknowledge a message.
+    async fn acknowledge_message(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        self.upsert_one(
+            COL,
+            json!({
+                "channel": channel_id,
+                "user": user_id
+            }),
+            |unread| {
+                if let Some(unread) = unread.as_object_mut() {
+                    unread.remove("mentions");
+                    unread.insert("last_id".to_owned(), json!(message_id));
+                }
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Acknowledge many channels.
+    async fn acknowledge_channels(&self, user_id: &str, channel_ids: &[String]) -> Result<()> {
+        let current_time = Ulid::new().to_string();
+
+        sqlx::query(
+            "INSERT INTO channel_unreads (id, data)
+             SELECT key, jsonb_build_object('_id', key, 'last_id', $2::text)
+             FROM (
+                SELECT jsonb_build_object('channel', channel, 'user', $1::text) AS key
+                FROM unnest($3::text[]) AS channel
+             ) AS keys
+             ON CONFLICT (id) DO UPDATE SET data = EXCLUDED.data",
+        )
+        .bind(user_id)
+        .bind(&current_time)
+        .bind(channel_ids)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Add a mention.
+    async fn add_mention_to_unread<'a>(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        message_ids: &[String],
+    ) -> Result<()> {
+        self.upsert_one(
+            COL,
+            json!({
+                "channel": channel_id,
+                "user": user_id
+            }),
+            |unread| {
+                if let Some(unread) = unread.as_object_mut() {
+                    let mentions = unread
+                        .entry("mentions")
+                        .or_insert_with(|| Value::Array(vec![]));
+
+                    if let Some(mentions) = mentions.as_array_mut() {
+                        mentions.extend(message_ids.iter().map(|id| json!(id)));
+                    }
+                }
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Record that a user was sent a push notification about a channel.
+    async fn mark_unread_notified(&self, channel_id: &str, user_id: &str) -> Result<()> {
+        self.upsert_one(
+            COL,
+            json!({
+                "channel": channel_id,
+                "user": user_id
+            }),
+            |unread| {
+                unread["notified"] = json!(true);
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Find which of the given channels a user was sent push notifications
+    /// about since they last read them, clearing the record.
+    async fn take_notified_unreads(
+        &self,
+        user_id: &str,
+        channel_ids: &[String],
+    ) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "UPDATE channel_unreads SET data = data - 'notified'
+             WHERE data->'_id'->>'user' = $1
+               AND data->'_id'->>'channel' = ANY($2)
+               AND data ? 'notified'
+             RETURNING data->'_id'->>'channel'",
+        )
+        .bind(user_id)
+        .bind(channel_ids)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Fetch all channel unreads for a user.
+    async fn fetch_unreads(&self, user_id: &str) -> Result<Vec<ChannelUnread>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "_id": {
+                    "user": user_id
+                }
+            })
+        )
+    }
+}
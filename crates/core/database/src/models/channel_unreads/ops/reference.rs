@@ -30,6 +30,7 @@ impl AbstractChannelUnreads for ReferenceDb {
                     id: key,
                     last_id: Some(message_id.to_string()),
                     mentions: None,
+                    notified: false,
                 },
             );
         }
@@ -63,7 +64,10 @@ impl AbstractChannelUnreads for ReferenceDb {
         };
 
         if let Some(unread) = unreads.get_mut(&key) {
-            unread.mentions.replace(message_ids.to_vec());
+            unread
+                .mentions
+                .get_or_insert_with(Vec::new)
+                .extend(message_ids.iter().cloned());
         } else {
             unreads.insert(
                 key.clone(),
@@ -71,6 +75,7 @@ impl AbstractChannelUnreads for ReferenceDb {
                     id: key,
                     last_id: None,
                     mentions: Some(message_ids.to_vec()),
+                    notified: false,
                 },
             );
         }
@@ -78,6 +83,49 @@ impl AbstractChannelUnreads for ReferenceDb {
         Ok(())
     }
 
+    /// Record that a user was sent a push notification about a channel.
+    async fn mark_unread_notified(&self, channel_id: &str, user_id: &str) -> Result<()> {
+        let mut unreads = self.channel_unreads.lock().await;
+        let key = ChannelCompositeKey {
+            channel: channel_id.to_string(),
+            user: user_id.to_string(),
+        };
+
+        unreads
+            .entry(key.clone())
+            .or_insert_with(|| ChannelUnread {
+                id: key,
+                last_id: None,
+                mentions: None,
+                notified: false,
+            })
+            .notified = true;
+
+        Ok(())
+    }
+
+    /// Find which of the given channels a user was sent push notifications
+    /// about since they last read them, clearing the record.
+    async fn take_notified_unreads(
+        &self,
+        user_id: &str,
+        channel_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let mut unreads = self.channel_unreads.lock().await;
+        Ok(unreads
+            .values_mut()
+            .filter(|unread| {
+                unread.notified
+                    && unread.id.user == user_id
+                    && channel_ids.contains(&unread.id.channel)
+            })
+            .map(|unread| {
+                unread.notified = false;
+                unread.id.channel.clone()
+            })
+            .collect())
+    }
+
     /// Fetch all channel unreads for a user.
     async fn fetch_unreads(&self, user_id: &str) -> Result<Vec<ChannelUnread>> {
         let unreads = self.channel_unreads.lock().await;
@@ -0,0 +1,138 @@
+use revolt_result::Result;
+use serde_json::{json, Value};
+use ulid::Ulid;
+
+use crate::ChannelUnread;
+use crate::SqliteDb;
+
+use super::AbstractChannelUnreads;
+
+static COL: &str = "channel_unreads";
+
+#[async_trait]
+impl AbstractChannelUnreads for SqliteDb {
+    /// Acknowledge a message.
+    async fn acknowledge_message(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        self.upsert_one(
+            COL,
+            json!({
+                "channel": channel_id,
+                "user": user_id
+            }),
+            |unread| {
+                if let Some(unread) = unread.as_object_mut() {
+                    unread.remove("mentions");
+                    unread.insert("last_id".to_owned(), json!(message_id));
+                }
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Acknowledge many channels.
+    async fn acknowledge_channels(&self, user_id: &str, channel_ids: &[String]) -> Result<()> {
+        let current_time = Ulid::new().to_string();
+
+        // Keys are built in sorted order to match stored document keys.
+        sqlx::query(
+            "INSERT INTO channel_unreads (id, data)
+             SELECT json_object('channel', value, 'user', ?1),
+                json_object('_id', json_object('channel', value, 'user', ?1), 'last_id', ?2)
+             FROM json_each(?3) WHERE TRUE
+             ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+        )
+        .bind(user_id)
+        .bind(&current_time)
+        .bind(serde_json::to_string(channel_ids).expect("strings always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Add a mention.
+    async fn add_mention_to_unread<'a>(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        message_ids: &[String],
+    ) -> Result<()> {
+        self.upsert_one(
+            COL,
+            json!({
+                "channel": channel_id,
+                "user": user_id
+            }),
+            |unread| {
+                if let Some(unread) = unread.as_object_mut() {
+                    let mentions = unread
+                        .entry("mentions")
+                        .or_insert_with(|| Value::Array(vec![]));
+
+                    if let Some(mentions) = mentions.as_array_mut() {
+                        mentions.extend(message_ids.iter().map(|id| json!(id)));
+                    }
+                }
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Record that a user was sent a push notification about a channel.
+    async fn mark_unread_notified(&self, channel_id: &str, user_id: &str) -> Result<()> {
+        self.upsert_one(
+            COL,
+            json!({
+                "channel": channel_id,
+                "user": user_id
+            }),
+            |unread| {
+                unread["notified"] = json!(true);
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Find which of the given channels a user was sent push notifications
+    /// about since they last read them, clearing the record.
+    async fn take_notified_unreads(
+        &self,
+        user_id: &str,
+        channel_ids: &[String],
+    ) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "UPDATE channel_unreads SET data = json_remove(data, '$.notified')
+             WHERE json_extract(data, '$._id.user') = ?1
+               AND json_extract(data, '$._id.channel') IN (SELECT value FROM json_each(?2))
+               AND json_extract(data, '$.notified')
+             RETURNING json_extract(data, '$._id.channel')",
+        )
+        .bind(user_id)
+        .bind(serde_json::to_string(channel_ids).expect("strings always serialise"))
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Fetch all channel unreads for a user.
+    async fn fetch_unreads(&self, user_id: &str) -> Result<Vec<ChannelUnread>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "_id": {
+                    "user": user_id
+                }
+            })
+        )
+    }
+}
@@ -53,17 +53,18 @@ impl Default for Webhook {
 #[allow(clippy::disallowed_methods)]
 impl Webhook {
     pub async fn create(&self, db: &Database) -> Result<()> {
-        db.insert_webhook(self).await?;
+        let tx = db.begin().await?;
+        tx.insert_webhook(self).await?;
 
         // Avoid leaking the token to people who receive the event
         let mut webhook = self.clone();
         webhook.token = None;
 
         EventV1::WebhookCreate(webhook.into())
-            .p(self.channel_id.clone())
+            .p_outbox(&tx, self.channel_id.clone())
             .await;
 
-        Ok(())
+        tx.commit().await
     }
 
     pub fn assert_token(&self, token: &str) -> Result<()> {
@@ -80,13 +81,16 @@ impl Webhook {
         mut partial: PartialWebhook,
         remove: Vec<FieldsWebhook>,
     ) -> Result<()> {
+        let files = self.file_ids();
         for field in &remove {
             self.remove_field(field)
         }
 
         self.apply_options(partial.clone());
 
-        db.update_webhook(&self.id, &partial, &remove).await?;
+        let tx = db.begin().await?;
+        tx.update_webhook(&self.id, &partial, &remove).await?;
+        File::detach_unused(&tx, files, self.file_ids()).await?;
 
         partial.token = None; // Avoid leaking the token to people who receive the event
 
@@ -95,10 +99,15 @@ impl Webhook {
             data: partial.into(),
             remove: remove.into_iter().map(|v| v.into()).collect(),
         }
-        .p(self.channel_id.clone())
+        .p_outbox(&tx, self.channel_id.clone())
         .await;
 
-        Ok(())
+        tx.commit().await
+    }
+
+    /// Ids of the files used by this webhook
+    fn file_ids(&self) -> Vec<String> {
+        self.avatar.iter().map(|file| file.id.to_string()).collect()
     }
 
     pub fn remove_field(&mut self, field: &FieldsWebhook) {
@@ -108,15 +117,17 @@ impl Webhook {
     }
 
     pub async fn delete(&self, db: &Database) -> Result<()> {
-        db.delete_webhook(&self.id).await?;
+        let tx = db.begin().await?;
+        tx.delete_webhook(&self.id).await?;
+        File::detach_unused(&tx, self.file_ids(), vec![]).await?;
 
         EventV1::WebhookDelete {
             id: self.id.clone(),
         }
-        .p(self.channel_id.clone())
+        .p_outbox(&tx, self.channel_id.clone())
         .await;
 
-        Ok(())
+        tx.commit().await
     }
 }
 
@@ -179,14 +179,14 @@ impl Channel {
 
         let event = EventV1::ChannelCreate(self.clone().into());
         match self {
-            Self::SavedMessages { user, .. } => event.private(user.clone()).await,
+            Self::SavedMessages { user, .. } => event.private_outbox(db, user.clone()).await,
             Self::DirectMessage { recipients, .. } | Self::Group { recipients, .. } => {
                 for recipient in recipients {
-                    event.clone().private(recipient.clone()).await;
+                    event.clone().private_outbox(db, recipient.clone()).await;
                 }
             }
             Self::TextChannel { server, .. } | Self::VoiceChannel { server, .. } => {
-                event.p(server.clone()).await;
+                event.p_outbox(db, server.clone()).await;
             }
         }
 
@@ -232,12 +232,13 @@ impl Channel {
             },
         };
 
-        db.insert_channel(&channel).await?;
+        let tx = db.begin().await?;
+        tx.insert_channel(&channel).await?;
 
         if update_server {
             server
                 .update(
-                    db,
+                    &tx,
                     PartialServer {
                         channels: Some([server.channels.clone(), [id].into()].concat()),
                         ..Default::default()
@@ -247,10 +248,11 @@ impl Channel {
                 .await?;
 
             EventV1::ChannelCreate(channel.clone().into())
-                .p(server.id.clone())
+                .p_outbox(&tx, server.id.clone())
                 .await;
         }
 
+        tx.commit().await?;
         Ok(channel)
     }
 
@@ -286,13 +288,15 @@ impl Channel {
             nsfw: data.nsfw.unwrap_or(false),
         };
 
-        db.insert_channel(&channel).await?;
+        let tx = db.begin().await?;
+        tx.insert_channel(&channel).await?;
 
         let event = EventV1::ChannelCreate(channel.clone().into());
         for recipient in recipients {
-            event.clone().private(recipient).await;
+            event.clone().private_outbox(&tx, recipient).await;
         }
 
+        tx.commit().await?;
         Ok(channel)
     }
 
@@ -318,17 +322,19 @@ impl Channel {
                 }
             };
 
-            db.insert_channel(&channel).await?;
+            let tx = db.begin().await?;
+            tx.insert_channel(&channel).await?;
 
             match &channel {
                 Channel::DirectMessage { .. } => {
                     let event = EventV1::ChannelCreate(channel.clone().into());
-                    event.clone().private(user_a.id.clone()).await;
-                    event.private(user_b.id.clone()).await;
+                    event.clone().private_outbox(&tx, user_a.id.clone()).await;
+                    event.private_outbox(&tx, user_b.id.clone()).await;
                 }
                 _ => {}
             };
 
+            tx.commit().await?;
             Ok(channel)
         }
     }
@@ -357,15 +363,22 @@ impl Channel {
 
         match &self {
             Channel::Group { id, .. } => {
-                db.add_user_to_group(id, &user.id).await?;
+                let tx = db.begin().await?;
+                tx.add_user_to_group(id, &user.id).await?;
 
                 EventV1::ChannelGroupJoin {
                     id: id.to_string(),
                     user: user.id.to_string(),
                 }
-                .p(id.to_string())
+                .p_outbox(&tx, id.to_string())
                 .await;
 
+                EventV1::ChannelCreate(self.clone().into())
+                    .private_outbox(&tx, user.id.to_string())
+                    .await;
+
+                tx.commit().await?;
+
                 SystemMessage::UserAdded {
                     id: user.id.to_string(),
                     by: by_id.to_string(),
@@ -385,10 +398,6 @@ impl Channel {
                 .await
                 .ok();
 
-                EventV1::ChannelCreate(self.clone().into())
-                    .private(user.id.to_string())
-                    .await;
-
                 Ok(())
             }
             _ => Err(create_error!(InvalidOperation)),
@@ -447,7 +456,8 @@ impl Channel {
                 role_permissions,
                 ..
             } => {
-                db.set_channel_role_permission(id, role_id, permissions)
+                let tx = db.begin().await?;
+                tx.set_channel_role_permission(id, role_id, permissions)
                     .await?;
 
                 role_permissions.insert(role_id.to_string(), permissions);
@@ -461,10 +471,10 @@ impl Channel {
                     .into(),
                     clear: vec![],
                 }
-                .p(server.clone())
+                .p_outbox(&tx, server.clone())
                 .await;
 
-                Ok(())
+                tx.commit().await
             }
             _ => Err(create_error!(InvalidOperation)),
         }
@@ -477,6 +487,7 @@ impl Channel {
         partial: PartialChannel,
         remove: Vec<FieldsChannel>,
     ) -> Result<()> {
+        let files = self.file_ids();
         for field in &remove {
             self.remove_field(field);
         }
@@ -484,20 +495,39 @@ impl Channel {
         self.apply_options(partial.clone());
 
         let id = self.id().to_string();
-        db.update_channel(&id, &partial, remove.clone()).await?;
+        let tx = db.begin().await?;
+        tx.update_channel(&id, &partial, remove.clone()).await?;
+        File::detach_unused(&tx, files, self.file_ids()).await?;
 
         EventV1::ChannelUpdate {
             id: id.clone(),
             data: partial.into(),
             clear: remove.into_iter().map(|v| v.into()).collect(),
         }
-        .p(match self {
-            Self::TextChannel { server, .. } | Self::VoiceChannel { server, .. } => server.clone(),
-            _ => id,
-        })
+        .p_outbox(
+            &tx,
+            match self {
+                Self::TextChannel { server, .. } | Self::VoiceChannel { server, .. } => {
+                    server.clone()
+                }
+                _ => id,
+            },
+        )
         .await;
 
-        Ok(())
+        tx.commit().await
+    }
+
+    /// Ids of the files used by this channel
+    fn file_ids(&self) -> Vec<String> {
+        match self {
+            Self::Group { icon, .. }
+            | Self::TextChannel { icon, .. }
+            | Self::VoiceChannel { icon, .. } => {
+                icon.iter().map(|file| file.id.to_string()).collect()
+            }
+            _ => vec![],
+        }
     }
 
     /// Remove a field from Channel object
@@ -630,7 +660,7 @@ impl Channel {
     }
 
     /// Acknowledge a message
-    pub async fn ack(&self, user: &str, message: &str) -> Result<()> {
+    pub async fn ack(&self, db: &Database, user: &str, message: &str) -> Result<()> {
         EventV1::ChannelAck {
             id: self.id().to_string(),
             user: user.to_string(),
@@ -640,6 +670,7 @@ impl Channel {
         .await;
 
         crate::tasks::ack::queue(
+            db,
             self.id().to_string(),
             user.to_string(),
             AckEvent::AckMessage {
@@ -702,13 +733,16 @@ impl Channel {
                     }
                 }
 
+                let tx = db.begin().await?;
                 EventV1::ChannelGroupLeave {
                     id: id.to_string(),
                     user: user.id.to_string(),
                 }
-                .p(id.to_string())
+                .p_outbox(&tx, id.to_string())
                 .await;
 
+                tx.commit().await?;
+
                 if !silent {
                     if let Some(by) = by_id {
                         SystemMessage::UserRemove {
@@ -746,11 +780,16 @@ impl Channel {
     /// Delete a channel
     pub async fn delete(&self, db: &Database) -> Result<()> {
         let id = self.id().to_string();
-        EventV1::ChannelDelete { id: id.clone() }.p(id).await;
+        let tx = db.begin().await?;
+        EventV1::ChannelDelete { id: id.clone() }
+            .p_outbox(&tx, id)
+            .await;
         // TODO: missing functionality:
         // - group invites
         // - channels list / categories list on server
-        db.delete_channel(self).await
+        tx.delete_channel(self).await?;
+        File::detach_unused(&tx, self.file_ids(), vec![]).await?;
+        tx.commit().await
     }
 }
 
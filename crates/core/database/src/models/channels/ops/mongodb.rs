@@ -259,12 +259,6 @@ impl AbstractChannels for MongoDb {
                 .map_err(|_| create_database_error!("update_one", "servers"))?;
         }
 
-        // Delete associated attachments
-        self.delete_many_attachments(doc! {
-            "object_id": &id
-        })
-        .await?;
-
         // Delete the channel itself
         query!(self, delete_one_by_id, COL, &channel.id()).map(|_| ())
     }
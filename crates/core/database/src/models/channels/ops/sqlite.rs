@@ -0,0 +1,206 @@
+use super::AbstractChannels;
+use crate::{
+    push_filter, remove_path, set_path, AbstractServers, Channel, FieldsChannel, IntoDocumentPath,
+    PartialChannel, SqliteDb,
+};
+use revolt_permissions::OverrideField;
+use revolt_result::Result;
+use serde_json::{json, Value};
+use sqlx::QueryBuilder;
+
+static COL: &str = "channels";
+
+#[async_trait]
+impl AbstractChannels for SqliteDb {
+    /// Insert a new channel in the database
+    async fn insert_channel(&self, channel: &Channel) -> Result<()> {
+        query!(self, insert_one, COL, &channel)
+    }
+
+    /// Fetch a channel from the database
+    async fn fetch_channel(&self, channel_id: &str) -> Result<Channel> {
+        query!(self, find_one_by_id, COL, channel_id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch all channels from the database
+    async fn fetch_channels<'a>(&self, ids: &'a [String]) -> Result<Vec<Channel>> {
+        query!(self, find_in, COL, "json_extract(data, '$._id')", ids)
+    }
+
+    /// Fetch all direct messages for a user
+    async fn find_direct_messages(&self, user_id: &str) -> Result<Vec<Channel>> {
+        let mut query = QueryBuilder::new(
+            "SELECT data FROM channels
+             WHERE (json_extract(data, '$.channel_type') IN ('DirectMessage', 'Group')",
+        );
+
+        push_filter(&mut query, &json!({ "recipients": [user_id] }));
+        query.push(") OR (TRUE");
+        push_filter(
+            &mut query,
+            &json!({
+                "channel_type": "SavedMessages",
+                "user": user_id
+            }),
+        );
+        query.push(")");
+
+        self.find_by_query(query)
+            .await
+            .map_err(|_| create_database_error!("find", COL))
+    }
+
+    // Fetch saved messages channel
+    async fn find_saved_messages_channel(&self, user_id: &str) -> Result<Channel> {
+        query!(
+            self,
+            find_one,
+            COL,
+            json!({
+                "channel_type": "SavedMessages",
+                "user": user_id
+            })
+        )?
+        .ok_or_else(|| create_error!(InternalError))
+    }
+
+    // Fetch direct message channel (DM or Saved Messages)
+    async fn find_direct_message_channel(&self, user_a: &str, user_b: &str) -> Result<Channel> {
+        let filter = if user_a == user_b {
+            json!({
+                "channel_type": "SavedMessages",
+                "user": user_a
+            })
+        } else {
+            json!({
+                "channel_type": "DirectMessage",
+                "recipients": [user_a, user_b]
+            })
+        };
+
+        query!(self, find_one, COL, filter)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Insert a user to a group
+    async fn add_user_to_group(&self, channel: &str, user: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": channel }), |channel| {
+            if let Some(recipients) = channel.get_mut("recipients").and_then(Value::as_array_mut) {
+                recipients.push(json!(user));
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "channel"))
+    }
+
+    /// Insert channel role permissions
+    async fn set_channel_role_permission(
+        &self,
+        channel: &str,
+        role: &str,
+        permissions: OverrideField,
+    ) -> Result<()> {
+        let permissions = serde_json::to_value(permissions)
+            .map_err(|_| create_database_error!("to_value", "permissions"))?;
+
+        self.modify_one(COL, json!({ "_id": channel }), |channel| {
+            set_path(channel, &format!("role_permissions.{role}"), permissions)
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "channel"))
+    }
+
+    // Update channel
+    async fn update_channel(
+        &self,
+        id: &str,
+        channel: &PartialChannel,
+        remove: Vec<FieldsChannel>,
+    ) -> Result<()> {
+        query!(
+            self,
+            update_one_by_id,
+            COL,
+            id,
+            channel,
+            remove.iter().map(|x| x as &dyn IntoDocumentPath).collect(),
+            None
+        )
+        .map(|_| ())
+    }
+
+    // Remove a user from a group
+    async fn remove_user_from_group(&self, channel: &str, user: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": channel }), |channel| {
+            if let Some(recipients) = channel.get_mut("recipients").and_then(Value::as_array_mut) {
+                recipients.retain(|recipient| recipient != user);
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "channels"))
+    }
+
+    // Delete a channel
+    async fn delete_channel(&self, channel: &Channel) -> Result<()> {
+        let id = channel.id().to_string();
+        let server_id = match channel {
+            Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
+                Some(server)
+            }
+            _ => None,
+        };
+
+        // Delete invites and unreads.
+        self.delete_associated_channel_objects(&[id.clone()])
+            .await?;
+
+        // Delete messages.
+        self.delete_bulk_messages(&[id.clone()]).await?;
+
+        // Remove from server object.
+        if let Some(server) = server_id {
+            let server = self.fetch_server(server).await?;
+            self.modify_one("servers", json!({ "_id": server.id }), |server| {
+                if let Some(channels) = server.get_mut("channels").and_then(Value::as_array_mut) {
+                    channels.retain(|channel| channel != &id);
+                }
+
+                for key in ["user_joined", "user_left", "user_kicked", "user_banned"] {
+                    if server.pointer(&format!("/system_messages/{key}")) == Some(&json!(id)) {
+                        remove_path(server, &format!("system_messages.{key}"));
+                    }
+                }
+            })
+            .await
+            .map_err(|_| create_database_error!("update_one", "servers"))?;
+        }
+
+        // Delete the channel itself
+        query!(self, delete_one_by_id, COL, &id).map(|_| ())
+    }
+}
+
+impl SqliteDb {
+    pub async fn delete_associated_channel_objects(&self, ids: &[String]) -> Result<()> {
+        for (table, expression) in [
+            // Delete all invites to these channels.
+            ("channel_invites", "json_extract(data, '$.channel')"),
+            // Delete unread message objects on channels.
+            ("channel_unreads", "json_extract(id, '$.channel')"),
+            // Delete all webhooks on this channel.
+            ("channel_webhooks", "json_extract(data, '$.channel_id')"),
+        ] {
+            sqlx::query(&format!(
+                "DELETE FROM {table} WHERE {expression} IN (SELECT value FROM json_each(?))"
+            ))
+            .bind(serde_json::to_string(ids).expect("strings always serialise"))
+            .execute(&self.0)
+            .await
+            .map_err(|_| create_database_error!("delete_many", table))?;
+        }
+
+        Ok(())
+    }
+}
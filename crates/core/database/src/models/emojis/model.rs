@@ -55,24 +55,27 @@ impl Emoji {
 
     /// Create an emoji
     pub async fn create(&self, db: &Database) -> Result<()> {
-        db.insert_emoji(self).await?;
+        let tx = db.begin().await?;
+        tx.insert_emoji(self).await?;
 
         EventV1::EmojiCreate(self.clone().into())
-            .p(self.parent().to_string())
+            .p_outbox(&tx, self.parent().to_string())
             .await;
 
-        Ok(())
+        tx.commit().await
     }
 
     /// Delete an emoji
     pub async fn delete(self, db: &Database) -> Result<()> {
+        let tx = db.begin().await?;
         EventV1::EmojiDelete {
             id: self.id.to_string(),
         }
-        .p(self.parent().to_string())
+        .p_outbox(&tx, self.parent().to_string())
         .await;
 
-        db.detach_emoji(&self).await
+        tx.detach_emoji(&self).await?;
+        tx.commit().await
     }
 
     /// Check whether we can use a given emoji
@@ -0,0 +1,52 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::Emoji;
+use crate::PostgresDb;
+
+use super::AbstractEmojis;
+
+static COL: &str = "emojis";
+
+#[async_trait]
+impl AbstractEmojis for PostgresDb {
+    /// Insert emoji into database.
+    async fn insert_emoji(&self, emoji: &Emoji) -> Result<()> {
+        query!(self, insert_one, COL, &emoji)
+    }
+
+    /// Fetch an emoji by its id
+    async fn fetch_emoji(&self, id: &str) -> Result<Emoji> {
+        query!(self, find_one_by_id, COL, id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch emoji by their parent id
+    async fn fetch_emoji_by_parent_id(&self, parent_id: &str) -> Result<Vec<Emoji>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "parent": {
+                    "id": parent_id
+                }
+            })
+        )
+    }
+
+    /// Fetch emoji by their parent ids
+    async fn fetch_emoji_by_parent_ids(&self, parent_ids: &[String]) -> Result<Vec<Emoji>> {
+        query!(self, find_in, COL, "data->'parent'->>'id'", parent_ids)
+    }
+
+    /// Detach an emoji by its id
+    async fn detach_emoji(&self, emoji: &Emoji) -> Result<()> {
+        sqlx::query("UPDATE emojis SET data = jsonb_set(data, '{parent}', $1) WHERE id = $2")
+            .bind(sqlx::types::Json(json!({ "type": "Detached" })))
+            .bind(sqlx::types::Json(&emoji.id))
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+}
@@ -0,0 +1,57 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::Emoji;
+use crate::SqliteDb;
+
+use super::AbstractEmojis;
+
+static COL: &str = "emojis";
+
+#[async_trait]
+impl AbstractEmojis for SqliteDb {
+    /// Insert emoji into database.
+    async fn insert_emoji(&self, emoji: &Emoji) -> Result<()> {
+        query!(self, insert_one, COL, &emoji)
+    }
+
+    /// Fetch an emoji by its id
+    async fn fetch_emoji(&self, id: &str) -> Result<Emoji> {
+        query!(self, find_one_by_id, COL, id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch emoji by their parent id
+    async fn fetch_emoji_by_parent_id(&self, parent_id: &str) -> Result<Vec<Emoji>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "parent": {
+                    "id": parent_id
+                }
+            })
+        )
+    }
+
+    /// Fetch emoji by their parent ids
+    async fn fetch_emoji_by_parent_ids(&self, parent_ids: &[String]) -> Result<Vec<Emoji>> {
+        query!(
+            self,
+            find_in,
+            COL,
+            "json_extract(data, '$.parent.id')",
+            parent_ids
+        )
+    }
+
+    /// Detach an emoji by its id
+    async fn detach_emoji(&self, emoji: &Emoji) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": &emoji.id }), |emoji| {
+            emoji["parent"] = json!({ "type": "Detached" });
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+}
@@ -26,24 +26,42 @@ auto_derived_partial!(
         #[serde(skip_serializing_if = "Option::is_none")]
         pub reported: Option<bool>,
 
-        // TODO: migrate this mess to having:
-        // - author_id
-        // - parent: Parent { Message(id), User(id), etc }
+        /// Object this file is attached to
         #[serde(skip_serializing_if = "Option::is_none")]
-        pub message_id: Option<String>,
+        pub parent: Option<FileParent>,
+        /// Time (in milliseconds since the epoch) at which this file was found without a parent
         #[serde(skip_serializing_if = "Option::is_none")]
-        pub user_id: Option<String>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        pub server_id: Option<String>,
-
-        /// Id of the object this file is associated with
-        #[serde(skip_serializing_if = "Option::is_none")]
-        pub object_id: Option<String>,
+        pub orphaned_at: Option<i64>,
     },
     "PartialFile"
 );
 
 auto_derived!(
+    /// Object which a file is attached to
+    #[serde(tag = "type")]
+    pub enum FileParent {
+        /// File is attached to a message or one of its embeds
+        Message { id: String },
+        /// File is a user's avatar
+        UserAvatar { id: String },
+        /// File is a user's profile background
+        UserBackground { id: String },
+        /// File is a server member's avatar
+        MemberAvatar { server: String, user: String },
+        /// File is a channel's icon
+        ChannelIcon { id: String },
+        /// File is a server's icon
+        ServerIcon { id: String },
+        /// File is a server's banner
+        ServerBanner { id: String },
+        /// File is an emoji
+        Emoji { id: String },
+        /// File is a webhook's avatar
+        WebhookAvatar { id: String },
+        /// File is evidence kept for a report
+        ReportEvidence { id: String },
+    }
+
     /// Metadata associated with a file
     #[serde(tag = "type")]
     #[derive(Default)]
@@ -62,46 +80,304 @@ auto_derived!(
     }
 );
 
+#[allow(clippy::disallowed_methods)]
 impl File {
+    /// Create a new file
+    ///
+    /// Files start out without a parent and are swept up if nothing uses them.
+    pub async fn create(&self, db: &Database) -> Result<()> {
+        db.insert_attachment(self).await
+    }
+
     /// Use a file for a message attachment
     pub async fn use_attachment(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "attachments", "message", parent)
-            .await
+        db.find_and_use_attachment(
+            id,
+            "attachments",
+            &FileParent::Message {
+                id: parent.to_string(),
+            },
+        )
+        .await
     }
 
     /// Use a file for a user profile background
     pub async fn use_background(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "backgrounds", "user", parent)
-            .await
+        db.find_and_use_attachment(
+            id,
+            "backgrounds",
+            &FileParent::UserBackground {
+                id: parent.to_string(),
+            },
+        )
+        .await
     }
 
     /// Use a file for a user avatar
     pub async fn use_avatar(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "avatars", "user", parent)
-            .await
+        db.find_and_use_attachment(
+            id,
+            "avatars",
+            &FileParent::UserAvatar {
+                id: parent.to_string(),
+            },
+        )
+        .await
     }
 
-    /// Use a file for an icon
+    /// Use a file for a server member avatar
+    pub async fn use_member_avatar(
+        db: &Database,
+        id: &str,
+        server: &str,
+        user: &str,
+    ) -> Result<File> {
+        db.find_and_use_attachment(
+            id,
+            "avatars",
+            &FileParent::MemberAvatar {
+                server: server.to_string(),
+                user: user.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Use a file for a webhook avatar
+    pub async fn use_webhook_avatar(db: &Database, id: &str, parent: &str) -> Result<File> {
+        db.find_and_use_attachment(
+            id,
+            "avatars",
+            &FileParent::WebhookAvatar {
+                id: parent.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Use a file for a channel icon
     pub async fn use_icon(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "icons", "object", parent)
-            .await
+        db.find_and_use_attachment(
+            id,
+            "icons",
+            &FileParent::ChannelIcon {
+                id: parent.to_string(),
+            },
+        )
+        .await
     }
 
     /// Use a file for a server icon
     pub async fn use_server_icon(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "icons", "object", parent)
-            .await
+        db.find_and_use_attachment(
+            id,
+            "icons",
+            &FileParent::ServerIcon {
+                id: parent.to_string(),
+            },
+        )
+        .await
     }
 
     /// Use a file for a server banner
     pub async fn use_banner(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "banners", "server", parent)
-            .await
+        db.find_and_use_attachment(
+            id,
+            "banners",
+            &FileParent::ServerBanner {
+                id: parent.to_string(),
+            },
+        )
+        .await
     }
 
     /// Use a file for an emoji
     pub async fn use_emoji(db: &Database, id: &str, parent: &str) -> Result<File> {
-        db.find_and_use_attachment(id, "emojis", "object", parent)
+        db.find_and_use_attachment(
+            id,
+            "emojis",
+            &FileParent::Emoji {
+                id: parent.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Detach any of the files used before an update which are no longer used after it
+    ///
+    /// Detached files are marked as deleted and can't be used again.
+    pub async fn detach_unused(
+        db: &Database,
+        before: Vec<String>,
+        after: Vec<String>,
+    ) -> Result<()> {
+        let ids: Vec<String> = before
+            .into_iter()
+            .filter(|id| !after.contains(id))
+            .collect();
+
+        if ids.is_empty() {
+            Ok(())
+        } else {
+            db.detach_attachments(&ids).await
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::disallowed_methods)]
+mod tests {
+    use crate::{FieldsUser, File, FileParent, Metadata, PartialUser, User};
+
+    fn file(id: &str, tag: &str) -> File {
+        File {
+            id: id.to_string(),
+            tag: tag.to_string(),
+            filename: "file.png".to_string(),
+            metadata: Metadata::Image {
+                width: 64,
+                height: 64,
+            },
+            content_type: "image/png".to_string(),
+            size: 256,
+            deleted: None,
+            reported: None,
+            parent: None,
+            orphaned_at: None,
+        }
+    }
+
+    #[async_std::test]
+    async fn detach_on_update() {
+        database_test!(|db| async move {
+            let mut user = User::create(&db, "Uploader".to_string(), None, None)
+                .await
+                .unwrap();
+
+            db.insert_attachment(&file("first", "avatars"))
+                .await
+                .unwrap();
+            db.insert_attachment(&file("second", "avatars"))
+                .await
+                .unwrap();
+
+            let avatar = File::use_avatar(&db, "first", &user.id).await.unwrap();
+            assert_eq!(
+                avatar.parent,
+                Some(FileParent::UserAvatar {
+                    id: user.id.clone()
+                })
+            );
+
+            // Files may only be used by one object at a time
+            assert!(File::use_background(&db, "first", &user.id).await.is_err());
+            assert!(File::use_avatar(&db, "first", &user.id).await.is_err());
+
+            user.update(
+                &db,
+                PartialUser {
+                    avatar: Some(avatar),
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+            .unwrap();
+
+            // Replacing the avatar deletes the old one
+            let avatar = File::use_avatar(&db, "second", &user.id).await.unwrap();
+            user.update(
+                &db,
+                PartialUser {
+                    avatar: Some(avatar),
+                    ..Default::default()
+                },
+                vec![],
+            )
             .await
+            .unwrap();
+
+            assert!(File::use_avatar(&db, "second", &user.id).await.is_err());
+            assert!(File::use_avatar(&db, "first", &user.id).await.is_err());
+            assert_eq!(
+                db.fetch_attachment("avatars", "first")
+                    .await
+                    .unwrap()
+                    .deleted,
+                Some(true)
+            );
+
+            // Removing the avatar deletes it
+            user.update(&db, Default::default(), vec![FieldsUser::Avatar])
+                .await
+                .unwrap();
+
+            assert!(File::use_avatar(&db, "second", &user.id).await.is_err());
+            assert_eq!(
+                db.fetch_attachment("avatars", "second")
+                    .await
+                    .unwrap()
+                    .deleted,
+                Some(true)
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn sweep_orphans() {
+        database_test!(|db| async move {
+            db.insert_attachment(&file("unused", "attachments"))
+                .await
+                .unwrap();
+            db.insert_attachment(&file("used", "attachments"))
+                .await
+                .unwrap();
+            db.insert_attachment(&File {
+                reported: Some(true),
+                ..file("reported", "attachments")
+            })
+            .await
+            .unwrap();
+
+            File::use_attachment(&db, "used", "message").await.unwrap();
+
+            // Orphans are first found, then deleted once the grace period has passed
+            assert_eq!(db.sweep_orphaned_attachments(1000, 0).await.unwrap(), 0);
+            assert_eq!(db.sweep_orphaned_attachments(2000, 1500).await.unwrap(), 1);
+            assert!(File::use_attachment(&db, "unused", "message")
+                .await
+                .is_err());
+
+            // Reported files are never deleted
+            File::use_attachment(&db, "reported", "message")
+                .await
+                .unwrap();
+
+            // Deleting the message deletes its files straight away
+            db.detach_attachments_from(&[FileParent::Message {
+                id: "message".to_string(),
+            }])
+            .await
+            .unwrap();
+
+            assert_eq!(
+                db.fetch_attachment("attachments", "used")
+                    .await
+                    .unwrap()
+                    .deleted,
+                Some(true)
+            );
+            assert!(File::use_attachment(&db, "used", "other").await.is_err());
+
+            // Files which were used are not swept up again, reported files are kept
+            assert_eq!(db.sweep_orphaned_attachments(3000, 2500).await.unwrap(), 0);
+            assert_eq!(
+                db.fetch_attachment("attachments", "reported")
+                    .await
+                    .unwrap()
+                    .deleted,
+                None
+            );
+        });
     }
 }
@@ -1,30 +1,54 @@
 use revolt_result::Result;
 
-use crate::File;
+use crate::{File, FileParent};
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractAttachments: Sync + Send {
     /// Insert attachment into database.
     async fn insert_attachment(&self, attachment: &File) -> Result<()>;
 
-    /// Find an attachment by its details and mark it as used by a given parent.
+    /// Fetch an attachment by its tag and id.
+    async fn fetch_attachment(&self, tag: &str, id: &str) -> Result<File>;
+
+    /// Find an unused attachment by its details and mark it as used by a given parent.
     async fn find_and_use_attachment(
         &self,
         id: &str,
         tag: &str,
-        parent_type: &str,
-        parent_id: &str,
+        parent: &FileParent,
     ) -> Result<File>;
 
-    /// Mark an attachment as having been reported.
-    async fn mark_attachment_as_reported(&self, id: &str) -> Result<()>;
+    /// Mark an attachment as having been reported, keeping it as evidence for the given report.
+    async fn mark_attachment_as_reported(&self, id: &str, report_id: &str) -> Result<()>;
 
     /// Mark an attachment as having been deleted.
     async fn mark_attachment_as_deleted(&self, id: &str) -> Result<()>;
 
     /// Mark multiple attachments as having been deleted.
     async fn mark_attachments_as_deleted(&self, ids: &[String]) -> Result<()>;
+
+    /// Detach attachments from whatever they are used by.
+    ///
+    /// Detached attachments are marked as deleted so they can't be used again,
+    /// reported attachments are left as they are.
+    async fn detach_attachments(&self, ids: &[String]) -> Result<()>;
+
+    /// Detach all attachments used by any of the given parents.
+    ///
+    /// Detached attachments are marked as deleted, as above.
+    async fn detach_attachments_from(&self, parents: &[FileParent]) -> Result<()>;
+
+    /// Sweep attachments which have never been used by anything.
+    ///
+    /// Unused attachments are stamped with the current time when first found,
+    /// then marked as deleted once they have been unused since before the cutoff.
+    /// Reported attachments are never touched. Returns the number of attachments deleted.
+    async fn sweep_orphaned_attachments(&self, now: i64, cutoff: i64) -> Result<i64>;
 }
@@ -1,8 +1,9 @@
 use bson::Document;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
 use revolt_result::Result;
 
-use crate::File;
 use crate::MongoDb;
+use crate::{File, FileParent};
 
 use super::AbstractAttachments;
 
@@ -15,49 +16,57 @@ impl AbstractAttachments for MongoDb {
         query!(self, insert_one, COL, &attachment).map(|_| ())
     }
 
-    /// Find an attachment by its details and mark it as used by a given parent.
+    /// Fetch an attachment by its tag and id.
+    async fn fetch_attachment(&self, tag: &str, id: &str) -> Result<File> {
+        query!(self, find_one_by_id, COL, id)?
+            .filter(|file: &File| file.tag == tag)
+            .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Find an unused attachment by its details and mark it as used by a given parent.
     async fn find_and_use_attachment(
         &self,
         id: &str,
         tag: &str,
-        parent_type: &str,
-        parent_id: &str,
+        parent: &FileParent,
     ) -> Result<File> {
-        let key = format!("{parent_type}_id");
-        let file = query!(
-            self,
-            find_one,
-            COL,
-            doc! {
-                "_id": id,
-                "tag": tag,
-                &key: {
-                    "$exists": false
-                }
-            }
-        )?
-        .ok_or_else(|| create_error!(NotFound))?;
-
-        self.col::<Document>(COL)
-            .update_one(
+        let parent = bson::to_bson(parent).map_err(|_| create_database_error!("to_bson", COL))?;
+        self.col::<File>(COL)
+            .find_one_and_update(
                 doc! {
-                    "_id": id
+                    "_id": id,
+                    "tag": tag,
+                    "parent": {
+                        "$exists": false
+                    },
+                    "deleted": {
+                        "$ne": true
+                    }
                 },
                 doc! {
                     "$set": {
-                        key: parent_id
+                        "parent": parent
+                    },
+                    "$unset": {
+                        "orphaned_at": 1_i32
                     }
                 },
-                None,
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
             )
             .await
-            .map_err(|_| create_database_error!("update_one", COL))?;
-
-        Ok(file)
+            .map_err(|_| create_database_error!("find_one_and_update", COL))?
+            .ok_or_else(|| create_error!(NotFound))
     }
 
-    /// Mark an attachment as having been reported.
-    async fn mark_attachment_as_reported(&self, id: &str) -> Result<()> {
+    /// Mark an attachment as having been reported, keeping it as evidence for the given report.
+    async fn mark_attachment_as_reported(&self, id: &str, report_id: &str) -> Result<()> {
+        let parent = bson::to_bson(&FileParent::ReportEvidence {
+            id: report_id.to_string(),
+        })
+        .map_err(|_| create_database_error!("to_bson", COL))?;
+
         self.col::<Document>(COL)
             .update_one(
                 doc! {
@@ -65,7 +74,11 @@ impl AbstractAttachments for MongoDb {
                 },
                 doc! {
                     "$set": {
-                        "reported": true
+                        "reported": true,
+                        "parent": parent
+                    },
+                    "$unset": {
+                        "orphaned_at": 1_i32
                     }
                 },
                 None,
@@ -97,7 +110,7 @@ impl AbstractAttachments for MongoDb {
     /// Mark multiple attachments as having been deleted.
     async fn mark_attachments_as_deleted(&self, ids: &[String]) -> Result<()> {
         self.col::<Document>(COL)
-            .update_one(
+            .update_many(
                 doc! {
                     "_id": {
                         "$in": ids
@@ -112,12 +125,100 @@ impl AbstractAttachments for MongoDb {
             )
             .await
             .map(|_| ())
-            .map_err(|_| create_database_error!("update_one", COL))
+            .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Detach attachments from whatever they are used by.
+    async fn detach_attachments(&self, ids: &[String]) -> Result<()> {
+        self.detach_many_attachments(doc! {
+            "_id": {
+                "$in": ids
+            }
+        })
+        .await
+    }
+
+    /// Detach all attachments used by any of the given parents.
+    async fn detach_attachments_from(&self, parents: &[FileParent]) -> Result<()> {
+        let parents = bson::to_bson(parents).map_err(|_| create_database_error!("to_bson", COL))?;
+
+        self.detach_many_attachments(doc! {
+            "parent": {
+                "$in": parents
+            }
+        })
+        .await
+    }
+
+    /// Sweep attachments which have never been used by anything.
+    async fn sweep_orphaned_attachments(&self, now: i64, cutoff: i64) -> Result<i64> {
+        self.col::<Document>(COL)
+            .update_many(
+                doc! {
+                    "parent": {
+                        "$exists": false
+                    },
+                    "orphaned_at": {
+                        "$exists": false
+                    },
+                    "deleted": {
+                        "$ne": true
+                    },
+                    "reported": {
+                        "$ne": true
+                    }
+                },
+                doc! {
+                    "$set": {
+                        "orphaned_at": now
+                    }
+                },
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("update_many", COL))?;
+
+        self.col::<Document>(COL)
+            .update_many(
+                doc! {
+                    "parent": {
+                        "$exists": false
+                    },
+                    "orphaned_at": {
+                        "$lt": cutoff
+                    },
+                    "deleted": {
+                        "$ne": true
+                    },
+                    "reported": {
+                        "$ne": true
+                    }
+                },
+                doc! {
+                    "$set": {
+                        "deleted": true
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|result| result.modified_count as i64)
+            .map_err(|_| create_database_error!("update_many", COL))
     }
 }
 
 impl MongoDb {
-    pub async fn delete_many_attachments(&self, projection: Document) -> Result<()> {
+    /// Detach all unreported attachments matching the given projection
+    ///
+    /// Detached attachments are marked as deleted.
+    pub async fn detach_many_attachments(&self, mut projection: Document) -> Result<()> {
+        projection.insert(
+            "reported",
+            doc! {
+                "$ne": true
+            },
+        );
+
         self.col::<Document>(COL)
             .update_many(
                 projection,
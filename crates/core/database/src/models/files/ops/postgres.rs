@@ -0,0 +1,153 @@
+use revolt_result::Result;
+use serde_json::json;
+use sqlx::types::Json;
+
+use crate::PostgresDb;
+use crate::{File, FileParent};
+
+use super::AbstractAttachments;
+
+static COL: &str = "attachments";
+
+#[async_trait]
+impl AbstractAttachments for PostgresDb {
+    /// Insert attachment into database.
+    async fn insert_attachment(&self, attachment: &File) -> Result<()> {
+        query!(self, insert_one, COL, &attachment)
+    }
+
+    /// Fetch an attachment by its tag and id.
+    async fn fetch_attachment(&self, tag: &str, id: &str) -> Result<File> {
+        query!(self, find_one_by_id, COL, id)?
+            .filter(|file: &File| file.tag == tag)
+            .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Find an unused attachment by its details and mark it as used by a given parent.
+    async fn find_and_use_attachment(
+        &self,
+        id: &str,
+        tag: &str,
+        parent: &FileParent,
+    ) -> Result<File> {
+        let Json(file) = sqlx::query_scalar::<_, Json<File>>(
+            r#"UPDATE attachments
+               SET data = (data - 'orphaned_at') || jsonb_build_object('parent', $1::jsonb)
+               WHERE id = $2 AND data @> $3 AND NOT data ? 'parent'
+               AND NOT data @> '{"deleted": true}'
+               RETURNING data"#,
+        )
+        .bind(Json(parent))
+        .bind(Json(id))
+        .bind(Json(json!({ "tag": tag })))
+        .fetch_optional(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))?
+        .ok_or_else(|| create_error!(NotFound))?;
+
+        Ok(file)
+    }
+
+    /// Mark an attachment as having been reported, keeping it as evidence for the given report.
+    async fn mark_attachment_as_reported(&self, id: &str, report_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE attachments
+             SET data = (data - 'orphaned_at') || jsonb_build_object('reported', true, 'parent', $1::jsonb)
+             WHERE id = $2",
+        )
+        .bind(Json(FileParent::ReportEvidence {
+            id: report_id.to_string(),
+        }))
+        .bind(Json(id))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Mark an attachment as having been deleted.
+    async fn mark_attachment_as_deleted(&self, id: &str) -> Result<()> {
+        sqlx::query(r#"UPDATE attachments SET data = data || '{"deleted": true}' WHERE id = $1"#)
+            .bind(Json(id))
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Mark multiple attachments as having been deleted.
+    async fn mark_attachments_as_deleted(&self, ids: &[String]) -> Result<()> {
+        sqlx::query(
+            r#"UPDATE attachments SET data = data || '{"deleted": true}' WHERE data->>'_id' = ANY($1)"#,
+        )
+        .bind(ids)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Detach attachments from whatever they are used by.
+    async fn detach_attachments(&self, ids: &[String]) -> Result<()> {
+        self.detach_many_attachments("data->>'_id'", ids).await
+    }
+
+    /// Detach all attachments used by any of the given parents.
+    async fn detach_attachments_from(&self, parents: &[FileParent]) -> Result<()> {
+        sqlx::query(
+            r#"UPDATE attachments SET data = data || '{"deleted": true}'
+               WHERE data->'parent' IN (SELECT jsonb_array_elements($1::jsonb))
+               AND NOT data @> '{"reported": true}'"#,
+        )
+        .bind(Json(parents))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Sweep attachments which have never been used by anything.
+    async fn sweep_orphaned_attachments(&self, now: i64, cutoff: i64) -> Result<i64> {
+        sqlx::query(
+            r#"UPDATE attachments SET data = data || jsonb_build_object('orphaned_at', $1::bigint)
+               WHERE NOT data ? 'parent' AND NOT data ? 'orphaned_at'
+               AND NOT data @> '{"deleted": true}' AND NOT data @> '{"reported": true}'"#,
+        )
+        .bind(now)
+        .execute(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?;
+
+        sqlx::query(
+            r#"UPDATE attachments SET data = data || '{"deleted": true}'
+               WHERE NOT data ? 'parent' AND (data->>'orphaned_at')::bigint < $1
+               AND NOT data @> '{"deleted": true}' AND NOT data @> '{"reported": true}'"#,
+        )
+        .bind(cutoff)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() as i64)
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+}
+
+impl PostgresDb {
+    /// Detach unreported attachments where the given expression matches any of the values
+    ///
+    /// Detached attachments are marked as deleted.
+    pub async fn detach_many_attachments(
+        &self,
+        expression: &'static str,
+        values: &[String],
+    ) -> Result<()> {
+        sqlx::query(&format!(
+            r#"UPDATE attachments SET data = data || '{{"deleted": true}}'
+               WHERE {expression} = ANY($1) AND NOT data @> '{{"reported": true}}'"#
+        ))
+        .bind(values)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+}
@@ -1,7 +1,7 @@
 use revolt_result::Result;
 
-use crate::File;
 use crate::ReferenceDb;
+use crate::{File, FileParent};
 
 use super::AbstractAttachments;
 
@@ -18,25 +18,28 @@ impl AbstractAttachments for ReferenceDb {
         }
     }
 
-    /// Find an attachment by its details and mark it as used by a given parent.
+    /// Fetch an attachment by its tag and id.
+    async fn fetch_attachment(&self, tag: &str, id: &str) -> Result<File> {
+        let files = self.files.lock().await;
+        files
+            .get(id)
+            .filter(|file| file.tag == tag)
+            .cloned()
+            .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Find an unused attachment by its details and mark it as used by a given parent.
     async fn find_and_use_attachment(
         &self,
         id: &str,
         tag: &str,
-        parent_type: &str,
-        parent_id: &str,
+        parent: &FileParent,
     ) -> Result<File> {
         let mut files = self.files.lock().await;
         if let Some(file) = files.get_mut(id) {
-            if file.tag == tag {
-                match parent_type {
-                    "message" => file.message_id = Some(parent_id.to_owned()),
-                    "user" => file.user_id = Some(parent_id.to_owned()),
-                    "object" => file.object_id = Some(parent_id.to_owned()),
-                    "server" => file.server_id = Some(parent_id.to_owned()),
-                    _ => unreachable!(),
-                }
-
+            if file.tag == tag && file.parent.is_none() && file.deleted != Some(true) {
+                file.parent = Some(parent.clone());
+                file.orphaned_at = None;
                 Ok(file.clone())
             } else {
                 Err(create_error!(NotFound))
@@ -46,11 +49,15 @@ impl AbstractAttachments for ReferenceDb {
         }
     }
 
-    /// Mark an attachment as having been reported.
-    async fn mark_attachment_as_reported(&self, id: &str) -> Result<()> {
+    /// Mark an attachment as having been reported, keeping it as evidence for the given report.
+    async fn mark_attachment_as_reported(&self, id: &str, report_id: &str) -> Result<()> {
         let mut files = self.files.lock().await;
         if let Some(file) = files.get_mut(id) {
             file.reported = Some(true);
+            file.parent = Some(FileParent::ReportEvidence {
+                id: report_id.to_string(),
+            });
+            file.orphaned_at = None;
             Ok(())
         } else {
             Err(create_error!(NotFound))
@@ -80,10 +87,59 @@ impl AbstractAttachments for ReferenceDb {
 
         for id in ids {
             if let Some(file) = files.get_mut(id) {
-                file.reported = Some(true);
+                file.deleted = Some(true);
             }
         }
 
         Ok(())
     }
+
+    /// Detach attachments from whatever they are used by.
+    async fn detach_attachments(&self, ids: &[String]) -> Result<()> {
+        let mut files = self.files.lock().await;
+        for id in ids {
+            if let Some(file) = files.get_mut(id) {
+                if file.reported != Some(true) {
+                    file.deleted = Some(true);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Detach all attachments used by any of the given parents.
+    async fn detach_attachments_from(&self, parents: &[FileParent]) -> Result<()> {
+        let mut files = self.files.lock().await;
+        for file in files.values_mut() {
+            if file.reported != Some(true)
+                && file
+                    .parent
+                    .as_ref()
+                    .is_some_and(|parent| parents.contains(parent))
+            {
+                file.deleted = Some(true);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sweep attachments which have never been used by anything.
+    async fn sweep_orphaned_attachments(&self, now: i64, cutoff: i64) -> Result<i64> {
+        let mut files = self.files.lock().await;
+        let mut deleted = 0;
+        for file in files.values_mut() {
+            if file.parent.is_some() || file.deleted == Some(true) || file.reported == Some(true) {
+                continue;
+            }
+
+            if *file.orphaned_at.get_or_insert(now) < cutoff {
+                file.deleted = Some(true);
+                deleted += 1;
+            }
+        }
+
+        Ok(deleted)
+    }
 }
@@ -0,0 +1,157 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::SqliteDb;
+use crate::{document_key, File, FileParent};
+
+use super::AbstractAttachments;
+
+static COL: &str = "attachments";
+
+#[async_trait]
+impl AbstractAttachments for SqliteDb {
+    /// Insert attachment into database.
+    async fn insert_attachment(&self, attachment: &File) -> Result<()> {
+        query!(self, insert_one, COL, &attachment)
+    }
+
+    /// Fetch an attachment by its tag and id.
+    async fn fetch_attachment(&self, tag: &str, id: &str) -> Result<File> {
+        query!(self, find_one_by_id, COL, id)?
+            .filter(|file: &File| file.tag == tag)
+            .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Find an unused attachment by its details and mark it as used by a given parent.
+    async fn find_and_use_attachment(
+        &self,
+        id: &str,
+        tag: &str,
+        parent: &FileParent,
+    ) -> Result<File> {
+        let file: String = sqlx::query_scalar(
+            "UPDATE attachments SET data = json_set(json_remove(data, '$.orphaned_at'), '$.parent', json(?1))
+             WHERE id = ?2 AND json_extract(data, '$.tag') = ?3
+             AND json_type(data, '$.parent') IS NULL
+             AND json_type(data, '$.deleted') IS NOT 'true'
+             RETURNING data",
+        )
+        .bind(serde_json::to_string(parent).expect("parents always serialise"))
+        .bind(document_key(&json!(id)))
+        .bind(tag)
+        .fetch_optional(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))?
+        .ok_or_else(|| create_error!(NotFound))?;
+
+        serde_json::from_str(&file).map_err(|_| create_database_error!("from_str", COL))
+    }
+
+    /// Mark an attachment as having been reported, keeping it as evidence for the given report.
+    async fn mark_attachment_as_reported(&self, id: &str, report_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE attachments
+             SET data = json_set(json_remove(data, '$.orphaned_at'), '$.reported', json('true'), '$.parent', json(?1))
+             WHERE id = ?2",
+        )
+        .bind(
+            serde_json::to_string(&FileParent::ReportEvidence {
+                id: report_id.to_string(),
+            })
+            .expect("parents always serialise"),
+        )
+        .bind(document_key(&json!(id)))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Mark an attachment as having been deleted.
+    async fn mark_attachment_as_deleted(&self, id: &str) -> Result<()> {
+        self.mark_attachments_as_deleted(&[id.to_owned()]).await
+    }
+
+    /// Mark multiple attachments as having been deleted.
+    async fn mark_attachments_as_deleted(&self, ids: &[String]) -> Result<()> {
+        sqlx::query(
+            "UPDATE attachments SET data = json_set(data, '$.deleted', json('true'))
+             WHERE json_extract(data, '$._id') IN (SELECT value FROM json_each(?))",
+        )
+        .bind(serde_json::to_string(ids).expect("strings always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Detach attachments from whatever they are used by.
+    async fn detach_attachments(&self, ids: &[String]) -> Result<()> {
+        self.detach_many_attachments("json_extract(data, '$._id')", ids)
+            .await
+    }
+
+    /// Detach all attachments used by any of the given parents.
+    async fn detach_attachments_from(&self, parents: &[FileParent]) -> Result<()> {
+        sqlx::query(
+            "UPDATE attachments SET data = json_set(data, '$.deleted', json('true'))
+             WHERE json_extract(data, '$.parent') IN (SELECT json(value) FROM json_each(?))
+             AND json_type(data, '$.reported') IS NOT 'true'",
+        )
+        .bind(serde_json::to_string(parents).expect("parents always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Sweep attachments which have never been used by anything.
+    async fn sweep_orphaned_attachments(&self, now: i64, cutoff: i64) -> Result<i64> {
+        sqlx::query(
+            "UPDATE attachments SET data = json_set(data, '$.orphaned_at', ?)
+             WHERE json_type(data, '$.parent') IS NULL
+             AND json_type(data, '$.orphaned_at') IS NULL
+             AND json_type(data, '$.deleted') IS NOT 'true'
+             AND json_type(data, '$.reported') IS NOT 'true'",
+        )
+        .bind(now)
+        .execute(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?;
+
+        sqlx::query(
+            "UPDATE attachments SET data = json_set(data, '$.deleted', json('true'))
+             WHERE json_type(data, '$.parent') IS NULL
+             AND json_extract(data, '$.orphaned_at') < ?
+             AND json_type(data, '$.deleted') IS NOT 'true'
+             AND json_type(data, '$.reported') IS NOT 'true'",
+        )
+        .bind(cutoff)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() as i64)
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+}
+
+impl SqliteDb {
+    /// Detach unreported attachments where the given expression matches any of the values
+    ///
+    /// Detached attachments are marked as deleted.
+    pub async fn detach_many_attachments(
+        &self,
+        expression: &'static str,
+        values: &[String],
+    ) -> Result<()> {
+        sqlx::query(&format!(
+            "UPDATE attachments SET data = json_set(data, '$.deleted', json('true'))
+             WHERE {expression} IN (SELECT value FROM json_each(?))
+             AND json_type(data, '$.reported') IS NOT 'true'"
+        ))
+        .bind(serde_json::to_string(values).expect("strings always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+}
@@ -0,0 +1,418 @@
+use std::time::Duration;
+
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+use serde::{Deserialize, Deserializer};
+use ulid::Ulid;
+
+use crate::{
+    outbox_time,
+    tasks::{self, ack::AckEvent, apple_notifications::ApnTask},
+    Database,
+};
+
+/// Queue which a job is processed from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum JobQueue {
+    Ack,
+    LastMessageId,
+    ProcessEmbeds,
+    WebPush,
+    PushCoalesce,
+    PushDigest,
+    AppleNotifications,
+}
+
+impl JobQueue {
+    /// Every queue jobs may be placed on
+    pub const ALL: [JobQueue; 7] = [
+        JobQueue::Ack,
+        JobQueue::LastMessageId,
+        JobQueue::ProcessEmbeds,
+        JobQueue::WebPush,
+        JobQueue::PushCoalesce,
+        JobQueue::PushDigest,
+        JobQueue::AppleNotifications,
+    ];
+
+    /// Name of the queue as it is stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobQueue::Ack => "ack",
+            JobQueue::LastMessageId => "last_message_id",
+            JobQueue::ProcessEmbeds => "process_embeds",
+            JobQueue::WebPush => "web_push",
+            JobQueue::PushCoalesce => "push_coalesce",
+            JobQueue::PushDigest => "push_digest",
+            JobQueue::AppleNotifications => "apple_notifications",
+        }
+    }
+}
+
+/// Work to be carried out by a job
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum JobPayload {
+    /// Update a user's unread state in a channel
+    Ack {
+        channel: String,
+        user: String,
+        event: AckEvent,
+    },
+    /// Update the latest message in a channel
+    LastMessageId {
+        channel: String,
+        id: String,
+        is_dm: bool,
+    },
+    /// Generate embeds for the links in a message
+    ProcessEmbeds {
+        channel: String,
+        id: String,
+        content: String,
+    },
+    /// Send a push notification about a message to the sessions of the given users
+    WebPush {
+        channel: String,
+        server: Option<String>,
+        /// Id of the message author
+        #[serde(default)]
+        author: String,
+        recipients: Vec<String>,
+        /// Recipients which the message mentions
+        mentions: Vec<String>,
+        /// Roles which the message mentions
+        #[serde(default)]
+        role_mentions: Vec<String>,
+        /// Whether the message mentions everyone in the channel
+        #[serde(default)]
+        mentions_everyone: bool,
+        payload: PushNotification,
+    },
+    /// Push notification to users, gathered with others about the same channel
+    PushCoalesce {
+        /// Users to notify, jobs queued before notifications were batched name one `user`
+        #[serde(alias = "user", deserialize_with = "one_or_many")]
+        users: Vec<String>,
+        channel: String,
+        payload: PushNotification,
+    },
+    /// Summarise the push notifications held back for users in a channel
+    PushWindowClose { channel: String, users: Vec<String> },
+    /// Remove push notifications about a channel which a user has since read
+    PushDismiss { user: String, channel: String },
+    /// Push notification held back until the end of a user's quiet hours
+    PushDigest {
+        user: String,
+        payload: PushNotification,
+    },
+    /// Send a notification through the Apple Push Notification service
+    AppleNotification(ApnTask),
+}
+
+impl JobPayload {
+    /// Queue this payload is processed from
+    pub fn queue(&self) -> JobQueue {
+        match self {
+            JobPayload::Ack { .. } => JobQueue::Ack,
+            JobPayload::LastMessageId { .. } => JobQueue::LastMessageId,
+            JobPayload::ProcessEmbeds { .. } => JobQueue::ProcessEmbeds,
+            JobPayload::WebPush { .. } => JobQueue::WebPush,
+            JobPayload::PushCoalesce { .. }
+            | JobPayload::PushWindowClose { .. }
+            | JobPayload::PushDismiss { .. } => JobQueue::PushCoalesce,
+            JobPayload::PushDigest { .. } => JobQueue::PushDigest,
+            JobPayload::AppleNotification(_) => JobQueue::AppleNotifications,
+        }
+    }
+}
+
+/// Accept either a single string or a list of them
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Background job waiting to be processed
+///
+/// Jobs are kept in the database until a worker completes them, so they
+/// survive restarts and are retried with exponential backoff if they fail.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Job {
+    /// Unique Id, jobs are processed in order of their Id
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Queue this job is processed from
+    pub queue: JobQueue,
+    /// Work to carry out
+    pub payload: JobPayload,
+    /// Number of times this job has been claimed by a worker
+    #[serde(default)]
+    pub attempts: i32,
+    /// Time (in milliseconds since the epoch) from which the job may be claimed
+    ///
+    /// While a worker holds the job, this is when its claim expires.
+    /// Once the job is dead-lettered, this is when it was buried.
+    #[serde(default)]
+    pub available_at: i64,
+    /// Id of the claim currently held on this job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim: Option<String>,
+    /// Error encountered by the last failed attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Whether the job failed too many times and was dead-lettered
+    #[serde(default)]
+    pub dead: bool,
+}
+
+/// How long to wait before retrying a job which has been attempted the given number of times
+pub fn job_backoff(attempts: i32, base: Duration, max: Duration) -> Duration {
+    base.saturating_mul(2_u32.saturating_pow(attempts.max(1) as u32 - 1))
+        .min(max)
+}
+
+#[allow(clippy::disallowed_methods)]
+impl Job {
+    /// Add a job to its queue and wake up a worker
+    pub async fn create(db: &Database, payload: JobPayload) -> Result<()> {
+        Job::schedule(db, payload, outbox_time()).await
+    }
+
+    /// Add a job to its queue which may not be claimed until the given time
+    pub async fn schedule(db: &Database, payload: JobPayload, available_at: i64) -> Result<()> {
+        let queue = payload.queue();
+        db.insert_job(&Job {
+            id: Ulid::new().to_string(),
+            queue,
+            payload,
+            attempts: 0,
+            available_at,
+            claim: None,
+            last_error: None,
+            dead: false,
+        })
+        .await?;
+
+        tasks::jobs::wake(queue);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{outbox_time, tasks::ack::AckEvent, Job, JobPayload, JobQueue};
+
+    fn ack(channel: &str) -> JobPayload {
+        JobPayload::Ack {
+            channel: channel.to_string(),
+            user: "user".to_string(),
+            event: AckEvent::AckMessage {
+                id: "message".to_string(),
+            },
+        }
+    }
+
+    #[async_std::test]
+    async fn claim_by_queue() {
+        database_test!(|db| async move {
+            Job::create(&db, ack("a")).await.unwrap();
+            Job::create(&db, ack("b")).await.unwrap();
+            Job::create(
+                &db,
+                JobPayload::LastMessageId {
+                    channel: "c".to_string(),
+                    id: "message".to_string(),
+                    is_dm: false,
+                },
+            )
+            .await
+            .unwrap();
+
+            let claimed = db
+                .claim_jobs(JobQueue::Ack, 1, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+            assert_eq!(claimed[0].attempts, 1);
+
+            // Claimed jobs are not handed out again until the lease expires
+            let remaining = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(remaining.len(), 1);
+            assert_ne!(remaining[0].id, claimed[0].id);
+
+            assert!(db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap()
+                .is_empty());
+
+            // Other queues are unaffected
+            let claimed = db
+                .claim_jobs(JobQueue::LastMessageId, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+
+            db.delete_jobs(&[claimed[0].id.clone()]).await.unwrap();
+        });
+    }
+
+    #[async_std::test]
+    async fn retry_then_dead_letter() {
+        database_test!(|db| async move {
+            Job::create(&db, ack("a")).await.unwrap();
+
+            let claimed = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+
+            db.retry_job(&claimed[0], "failed", outbox_time())
+                .await
+                .unwrap();
+            let retried = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(retried.len(), 1);
+            assert_eq!(retried[0].id, claimed[0].id);
+            assert_eq!(retried[0].attempts, 2);
+            assert_eq!(retried[0].last_error.as_deref(), Some("failed"));
+
+            // Dead-lettered jobs are never handed out again
+            db.bury_job(&retried[0], "gave up").await.unwrap();
+            async_std::task::sleep(Duration::from_millis(5)).await;
+            assert!(db
+                .claim_jobs(JobQueue::Ack, 10, Duration::ZERO)
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[async_std::test]
+    async fn sweep_buried_jobs() {
+        database_test!(|db| async move {
+            Job::create(&db, ack("a")).await.unwrap();
+            Job::create(&db, ack("b")).await.unwrap();
+
+            let claimed = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 2);
+
+            let buried_before = outbox_time();
+            db.bury_job(&claimed[0], "gave up").await.unwrap();
+
+            // Only dead-lettered jobs buried before the cutoff are deleted
+            assert_eq!(db.delete_buried_jobs(buried_before).await.unwrap(), 0);
+            async_std::task::sleep(Duration::from_millis(5)).await;
+            assert_eq!(db.delete_buried_jobs(outbox_time()).await.unwrap(), 1);
+
+            // The job still being worked on is left alone
+            db.retry_job(&claimed[1], "failed", outbox_time())
+                .await
+                .unwrap();
+            let remaining = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(remaining.len(), 1);
+            assert_eq!(remaining[0].id, claimed[1].id);
+        });
+    }
+
+    #[async_std::test]
+    async fn wait_for_backoff() {
+        database_test!(|db| async move {
+            Job::create(&db, ack("a")).await.unwrap();
+
+            let claimed = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::ZERO)
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+
+            // A failed job is not retried until its backoff has passed
+            db.retry_job(&claimed[0], "failed", outbox_time() + 60_000)
+                .await
+                .unwrap();
+            async_std::task::sleep(Duration::from_millis(5)).await;
+            assert!(db
+                .claim_jobs(JobQueue::Ack, 10, Duration::ZERO)
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[async_std::test]
+    async fn scheduled_jobs_wait() {
+        database_test!(|db| async move {
+            Job::schedule(&db, ack("a"), outbox_time() + 60_000)
+                .await
+                .unwrap();
+            Job::schedule(&db, ack("b"), outbox_time() - 1)
+                .await
+                .unwrap();
+
+            let claimed = db
+                .claim_jobs(JobQueue::Ack, 10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+            assert!(
+                matches!(&claimed[0].payload, JobPayload::Ack { channel, .. } if channel == "b")
+            );
+        });
+    }
+
+    #[test]
+    fn push_coalesce_for_one_user() {
+        let payload: JobPayload = serde_json::from_value(serde_json::json!({
+            "type": "PushCoalesce",
+            "user": "user",
+            "channel": "channel",
+            "payload": {
+                "author": "author",
+                "icon": "icon",
+                "body": "body",
+                "tag": "channel",
+                "timestamp": 0,
+                "url": "url"
+            }
+        }))
+        .unwrap();
+
+        assert!(matches!(&payload, JobPayload::PushCoalesce { users, .. } if users == &["user"]));
+    }
+
+    #[test]
+    fn backoff_doubles_until_capped() {
+        let base = Duration::from_secs(5);
+        let max = Duration::from_secs(60);
+        assert_eq!(super::job_backoff(1, base, max), Duration::from_secs(5));
+        assert_eq!(super::job_backoff(2, base, max), Duration::from_secs(10));
+        assert_eq!(super::job_backoff(4, base, max), Duration::from_secs(40));
+        assert_eq!(super::job_backoff(5, base, max), Duration::from_secs(60));
+        assert_eq!(super::job_backoff(100, base, max), Duration::from_secs(60));
+    }
+}
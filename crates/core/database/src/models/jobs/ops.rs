@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+
+use crate::{Job, JobQueue};
+
+mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[async_trait]
+pub trait AbstractJobs: Sync + Send {
+    /// Insert a new job
+    async fn insert_job(&self, job: &Job) -> Result<()>;
+
+    /// Claim the oldest available jobs on a queue for the given lease
+    ///
+    /// Every claim counts as an attempt, jobs whose lease expires without
+    /// being completed or retried are claimed again.
+    async fn claim_jobs(&self, queue: JobQueue, limit: i64, lease: Duration) -> Result<Vec<Job>>;
+
+    /// Delete completed jobs
+    async fn delete_jobs(&self, ids: &[String]) -> Result<()>;
+
+    /// Release a claimed job to be retried once the given time has passed
+    async fn retry_job(&self, job: &Job, error: &str, available_at: i64) -> Result<()>;
+
+    /// Dead-letter a claimed job so it is never attempted again
+    async fn bury_job(&self, job: &Job, error: &str) -> Result<()>;
+
+    /// Delete dead-lettered jobs which were buried before the given time
+    async fn delete_buried_jobs(&self, before: i64) -> Result<i64>;
+}
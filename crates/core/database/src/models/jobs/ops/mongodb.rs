@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use bson::Document;
+use mongodb::options::FindOptions;
+use revolt_result::Result;
+use ulid::Ulid;
+
+use crate::{outbox_time, DocumentId, Job, JobQueue, MongoDb};
+
+use super::AbstractJobs;
+
+static COL: &str = "jobs";
+
+#[async_trait]
+impl AbstractJobs for MongoDb {
+    /// Insert a new job
+    async fn insert_job(&self, job: &Job) -> Result<()> {
+        query!(self, insert_one, COL, &job).map(|_| ())
+    }
+
+    /// Claim the oldest available jobs on a queue for the given lease
+    async fn claim_jobs(&self, queue: JobQueue, limit: i64, lease: Duration) -> Result<Vec<Job>> {
+        let now = outbox_time();
+        let available = doc! {
+            "queue": queue.as_str(),
+            "dead": false,
+            "available_at": {
+                "$lte": now
+            }
+        };
+
+        let ids = self
+            .find_with_options::<_, DocumentId>(
+                COL,
+                available.clone(),
+                FindOptions::builder()
+                    .projection(doc! { "_id": 1_i32 })
+                    .sort(doc! { "_id": 1_i32 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await
+            .map_err(|_| create_database_error!("find", COL))?
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<String>>();
+
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Another worker may claim some of these first, so only
+        // return the jobs which were tagged with our claim.
+        let claim = Ulid::new().to_string();
+        let mut filter = available;
+        filter.insert("_id", doc! { "$in": ids });
+
+        self.col::<Document>(COL)
+            .update_many(
+                filter,
+                doc! {
+                    "$set": {
+                        "available_at": now + lease.as_millis() as i64,
+                        "claim": &claim
+                    },
+                    "$inc": {
+                        "attempts": 1_i32
+                    }
+                },
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("update_many", COL))?;
+
+        self.find_with_options(
+            COL,
+            doc! {
+                "claim": claim
+            },
+            FindOptions::builder().sort(doc! { "_id": 1_i32 }).build(),
+        )
+        .await
+        .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Delete completed jobs
+    async fn delete_jobs(&self, ids: &[String]) -> Result<()> {
+        self.col::<Document>(COL)
+            .delete_many(
+                doc! {
+                    "_id": {
+                        "$in": ids
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Release a claimed job to be retried once the given time has passed
+    async fn retry_job(&self, job: &Job, error: &str, available_at: i64) -> Result<()> {
+        self.col::<Document>(COL)
+            .update_one(
+                doc! {
+                    "_id": &job.id,
+                    "claim": job.claim.clone()
+                },
+                doc! {
+                    "$set": {
+                        "available_at": available_at,
+                        "last_error": error
+                    },
+                    "$unset": {
+                        "claim": 1_i32
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Dead-letter a claimed job so it is never attempted again
+    async fn bury_job(&self, job: &Job, error: &str) -> Result<()> {
+        self.col::<Document>(COL)
+            .update_one(
+                doc! {
+                    "_id": &job.id,
+                    "claim": job.claim.clone()
+                },
+                doc! {
+                    "$set": {
+                        "dead": true,
+                        "available_at": outbox_time(),
+                        "last_error": error
+                    },
+                    "$unset": {
+                        "claim": 1_i32
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete dead-lettered jobs which were buried before the given time
+    async fn delete_buried_jobs(&self, before: i64) -> Result<i64> {
+        self.col::<Document>(COL)
+            .delete_many(
+                doc! {
+                    "dead": true,
+                    "available_at": {
+                        "$lt": before
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|result| result.deleted_count as i64)
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
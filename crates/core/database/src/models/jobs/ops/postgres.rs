@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+use sqlx::types::Json;
+use ulid::Ulid;
+
+use super::AbstractJobs;
+use crate::{outbox_time, Job, JobQueue, PostgresDb};
+
+static COL: &str = "jobs";
+
+#[async_trait]
+impl AbstractJobs for PostgresDb {
+    /// Insert a new job
+    async fn insert_job(&self, job: &Job) -> Result<()> {
+        query!(self, insert_one, COL, &job)
+    }
+
+    /// Claim the oldest available jobs on a queue for the given lease
+    async fn claim_jobs(&self, queue: JobQueue, limit: i64, lease: Duration) -> Result<Vec<Job>> {
+        let now = outbox_time();
+
+        // Rows being claimed by another worker are skipped rather than waited on.
+        let mut jobs = sqlx::query_scalar::<_, Json<Job>>(
+            r#"UPDATE jobs
+               SET data = data || jsonb_build_object(
+                   'available_at', $2::bigint,
+                   'claim', $3::text,
+                   'attempts', (data->>'attempts')::int + 1
+               )
+               WHERE id IN (
+                   SELECT id FROM jobs
+                   WHERE data->>'queue' = $4
+                     AND (data->>'dead')::boolean = false
+                     AND (data->>'available_at')::bigint <= $1
+                   ORDER BY (data->>'_id') COLLATE "C"
+                   LIMIT $5
+                   FOR UPDATE SKIP LOCKED
+               )
+               RETURNING data"#,
+        )
+        .bind(now)
+        .bind(now + lease.as_millis() as i64)
+        .bind(Ulid::new().to_string())
+        .bind(queue.as_str())
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?
+        .into_iter()
+        .map(|Json(job)| job)
+        .collect::<Vec<Job>>();
+
+        jobs.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(jobs)
+    }
+
+    /// Delete completed jobs
+    async fn delete_jobs(&self, ids: &[String]) -> Result<()> {
+        sqlx::query("DELETE FROM jobs WHERE data->>'_id' = ANY($1)")
+            .bind(ids)
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Release a claimed job to be retried once the given time has passed
+    async fn retry_job(&self, job: &Job, error: &str, available_at: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs
+             SET data = (data - 'claim')
+                 || jsonb_build_object('available_at', $3::bigint, 'last_error', $4::text)
+             WHERE data->>'_id' = $1 AND data->>'claim' = $2",
+        )
+        .bind(&job.id)
+        .bind(&job.claim)
+        .bind(available_at)
+        .bind(error)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Dead-letter a claimed job so it is never attempted again
+    async fn bury_job(&self, job: &Job, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs
+             SET data = (data - 'claim')
+                 || jsonb_build_object('dead', true, 'available_at', $4::bigint, 'last_error', $3::text)
+             WHERE data->>'_id' = $1 AND data->>'claim' = $2",
+        )
+        .bind(&job.id)
+        .bind(&job.claim)
+        .bind(error)
+        .bind(outbox_time())
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete dead-lettered jobs which were buried before the given time
+    async fn delete_buried_jobs(&self, before: i64) -> Result<i64> {
+        sqlx::query(
+            "DELETE FROM jobs
+             WHERE (data->>'dead')::boolean = true AND (data->>'available_at')::bigint < $1",
+        )
+        .bind(before)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() as i64)
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+use ulid::Ulid;
+
+use super::AbstractJobs;
+use crate::{outbox_time, Job, JobQueue, ReferenceDb};
+
+#[async_trait]
+impl AbstractJobs for ReferenceDb {
+    /// Insert a new job
+    async fn insert_job(&self, job: &Job) -> Result<()> {
+        let mut jobs = self.jobs.lock().await;
+        if jobs.contains_key(&job.id) {
+            Err(create_database_error!("insert", "job"))
+        } else {
+            jobs.insert(job.id.to_string(), job.clone());
+            Ok(())
+        }
+    }
+
+    /// Claim the oldest available jobs on a queue for the given lease
+    async fn claim_jobs(&self, queue: JobQueue, limit: i64, lease: Duration) -> Result<Vec<Job>> {
+        let mut jobs = self.jobs.lock().await;
+        let now = outbox_time();
+        let claim = Ulid::new().to_string();
+
+        let mut available: Vec<&mut Job> = jobs
+            .values_mut()
+            .filter(|job| job.queue == queue && !job.dead && job.available_at <= now)
+            .collect();
+
+        available.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(available
+            .into_iter()
+            .take(limit as usize)
+            .map(|job| {
+                job.available_at = now + lease.as_millis() as i64;
+                job.claim = Some(claim.clone());
+                job.attempts += 1;
+                job.clone()
+            })
+            .collect())
+    }
+
+    /// Delete completed jobs
+    async fn delete_jobs(&self, ids: &[String]) -> Result<()> {
+        let mut jobs = self.jobs.lock().await;
+        for id in ids {
+            jobs.remove(id);
+        }
+
+        Ok(())
+    }
+
+    /// Release a claimed job to be retried once the given time has passed
+    async fn retry_job(&self, job: &Job, error: &str, available_at: i64) -> Result<()> {
+        let mut jobs = self.jobs.lock().await;
+        if let Some(existing) = jobs.get_mut(&job.id) {
+            if existing.claim == job.claim {
+                existing.available_at = available_at;
+                existing.claim = None;
+                existing.last_error = Some(error.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dead-letter a claimed job so it is never attempted again
+    async fn bury_job(&self, job: &Job, error: &str) -> Result<()> {
+        let mut jobs = self.jobs.lock().await;
+        if let Some(existing) = jobs.get_mut(&job.id) {
+            if existing.claim == job.claim {
+                existing.dead = true;
+                existing.available_at = outbox_time();
+                existing.claim = None;
+                existing.last_error = Some(error.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete dead-lettered jobs which were buried before the given time
+    async fn delete_buried_jobs(&self, before: i64) -> Result<i64> {
+        let mut jobs = self.jobs.lock().await;
+        let count = jobs.len();
+        jobs.retain(|_, job| !job.dead || job.available_at >= before);
+        Ok((count - jobs.len()) as i64)
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+use ulid::Ulid;
+
+use super::AbstractJobs;
+use crate::{outbox_time, Job, JobQueue, SqliteDb};
+
+static COL: &str = "jobs";
+
+#[async_trait]
+impl AbstractJobs for SqliteDb {
+    /// Insert a new job
+    async fn insert_job(&self, job: &Job) -> Result<()> {
+        query!(self, insert_one, COL, &job)
+    }
+
+    /// Claim the oldest available jobs on a queue for the given lease
+    async fn claim_jobs(&self, queue: JobQueue, limit: i64, lease: Duration) -> Result<Vec<Job>> {
+        let now = outbox_time();
+        let mut jobs = sqlx::query_scalar::<_, String>(
+            "UPDATE jobs
+             SET data = json_set(
+                 data,
+                 '$.available_at', ?,
+                 '$.claim', ?,
+                 '$.attempts', json_extract(data, '$.attempts') + 1
+             )
+             WHERE id IN (
+                 SELECT id FROM jobs
+                 WHERE json_extract(data, '$.queue') = ?
+                   AND json_extract(data, '$.dead') = 0
+                   AND json_extract(data, '$.available_at') <= ?
+                 ORDER BY id
+                 LIMIT ?
+             )
+             RETURNING data",
+        )
+        .bind(now + lease.as_millis() as i64)
+        .bind(Ulid::new().to_string())
+        .bind(queue.as_str())
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?
+        .into_iter()
+        .filter_map(|job| serde_json::from_str(&job).ok())
+        .collect::<Vec<Job>>();
+
+        jobs.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(jobs)
+    }
+
+    /// Delete completed jobs
+    async fn delete_jobs(&self, ids: &[String]) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM jobs
+             WHERE json_extract(data, '$._id') IN (SELECT value FROM json_each(?))",
+        )
+        .bind(serde_json::to_string(ids).expect("strings always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Release a claimed job to be retried once the given time has passed
+    async fn retry_job(&self, job: &Job, error: &str, available_at: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs
+             SET data = json_set(json_remove(data, '$.claim'), '$.available_at', ?, '$.last_error', ?)
+             WHERE json_extract(data, '$._id') = ? AND json_extract(data, '$.claim') = ?",
+        )
+        .bind(available_at)
+        .bind(error)
+        .bind(&job.id)
+        .bind(&job.claim)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Dead-letter a claimed job so it is never attempted again
+    async fn bury_job(&self, job: &Job, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs
+             SET data = json_set(
+                 json_remove(data, '$.claim'),
+                 '$.dead', json('true'),
+                 '$.available_at', ?,
+                 '$.last_error', ?
+             )
+             WHERE json_extract(data, '$._id') = ? AND json_extract(data, '$.claim') = ?",
+        )
+        .bind(outbox_time())
+        .bind(error)
+        .bind(&job.id)
+        .bind(&job.claim)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete dead-lettered jobs which were buried before the given time
+    async fn delete_buried_jobs(&self, before: i64) -> Result<i64> {
+        sqlx::query(
+            "DELETE FROM jobs
+             WHERE json_extract(data, '$.dead') = 1 AND json_extract(data, '$.available_at') < ?",
+        )
+        .bind(before)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() as i64)
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
@@ -5,7 +5,8 @@ use iso8601_timestamp::Timestamp;
 use revolt_config::{config, FeaturesLimits};
 use revolt_models::v0::{
     self, BulkMessageResponse, DataMessageSend, Embed, MessageAuthor, MessageFlags, MessageSort,
-    MessageWebhook, PushNotification, ReplyIntent, SendableEmbed, Text, RE_MENTION,
+    MessageWebhook, PushNotification, ReplyIntent, SendableEmbed, Text, RE_EVERYONE, RE_MENTION,
+    RE_ROLE_MENTION,
 };
 use revolt_permissions::{ChannelPermission, PermissionValue};
 use revolt_result::Result;
@@ -16,7 +17,7 @@ use crate::{
     events::client::EventV1,
     tasks::{self, ack::AckEvent},
     util::idempotency::IdempotencyKey,
-    Channel, Database, Emoji, File, User,
+    Channel, Database, Emoji, File, FileParent, User,
 };
 
 auto_derived_partial!(
@@ -53,6 +54,9 @@ auto_derived_partial!(
         /// Array of user ids mentioned in this message
         #[serde(skip_serializing_if = "Option::is_none")]
         pub mentions: Option<Vec<String>>,
+        /// Array of role ids mentioned in this message
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub role_mentions: Option<Vec<String>>,
         /// Array of message ids this message is replying to
         #[serde(skip_serializing_if = "Option::is_none")]
         pub replies: Option<Vec<String>>,
@@ -200,6 +204,7 @@ impl Default for Message {
             edited: None,
             embeds: None,
             mentions: None,
+            role_mentions: None,
             replies: None,
             reactions: Default::default(),
             interactions: Default::default(),
@@ -209,6 +214,17 @@ impl Default for Message {
     }
 }
 
+/// Kinds of mention a message is allowed to contain
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllowedMentions {
+    /// Users, either mentioned directly or by replying to them
+    pub users: bool,
+    /// Roles in the server the message is sent in
+    pub roles: bool,
+    /// Everyone who can see the channel, using @everyone
+    pub everyone: bool,
+}
+
 #[allow(clippy::disallowed_methods)]
 impl Message {
     /// Create message from API data
@@ -223,7 +239,7 @@ impl Message {
         limits: FeaturesLimits,
         mut idempotency: IdempotencyKey,
         generate_embeds: bool,
-        allow_mentions: bool,
+        allowed_mentions: AllowedMentions,
     ) -> Result<Message> {
         let config = config().await;
 
@@ -292,14 +308,39 @@ impl Message {
 
         // Parse mentions in message.
         let mut mentions = HashSet::new();
-        if allow_mentions {
-            if let Some(content) = &data.content {
+        let mut role_mentions = HashSet::new();
+        if let Some(content) = &data.content {
+            if allowed_mentions.users {
                 for capture in RE_MENTION.captures_iter(content) {
                     if let Some(mention) = capture.get(1) {
                         mentions.insert(mention.as_str().to_string());
                     }
                 }
             }
+
+            // Roles and @everyone only mean something within a server
+            if let Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } =
+                &channel
+            {
+                if allowed_mentions.roles {
+                    for capture in RE_ROLE_MENTION.captures_iter(content) {
+                        if let Some(mention) = capture.get(1) {
+                            role_mentions.insert(mention.as_str().to_string());
+                        }
+                    }
+
+                    if !role_mentions.is_empty() {
+                        let server = db.fetch_server(server).await?;
+                        role_mentions.retain(|id| server.roles.contains_key(id));
+                    }
+                }
+
+                if allowed_mentions.everyone && RE_EVERYONE.is_match(content) {
+                    message.flags = Some(
+                        message.flags.unwrap_or_default() | MessageFlags::MentionsEveryone as i32,
+                    );
+                }
+            }
         }
 
         // Verify replies are valid.
@@ -326,6 +367,12 @@ impl Message {
             message.mentions.replace(mentions.into_iter().collect());
         }
 
+        if !role_mentions.is_empty() {
+            message
+                .role_mentions
+                .replace(role_mentions.into_iter().collect());
+        }
+
         if !replies.is_empty() {
             message
                 .replies
@@ -355,10 +402,7 @@ impl Message {
         }
 
         for attachment_id in data.attachments.as_deref().unwrap_or_default() {
-            attachments.push(
-                db.find_and_use_attachment(attachment_id, "attachments", "message", &message_id)
-                    .await?,
-            );
+            attachments.push(File::use_attachment(db, attachment_id, &message_id).await?);
         }
 
         if !attachments.is_empty() {
@@ -393,20 +437,25 @@ impl Message {
         is_dm: bool,
         generate_embeds: bool,
     ) -> Result<()> {
-        db.insert_message(self).await?;
+        let tx = db.begin().await?;
+        tx.insert_message(self).await?;
 
         // Fan out events
         EventV1::Message(self.clone().into_model(user, member))
-            .p(self.channel.to_string())
+            .p_outbox(&tx, self.channel.to_string())
             .await;
 
+        tx.commit().await?;
+
         // Update last_message_id
-        tasks::last_message_id::queue(self.channel.to_string(), self.id.to_string(), is_dm).await;
+        tasks::last_message_id::queue(db, self.channel.to_string(), self.id.to_string(), is_dm)
+            .await;
 
         // Add mentions for affected users
         if let Some(mentions) = &self.mentions {
             for user in mentions {
                 tasks::ack::queue(
+                    db,
                     self.channel.to_string(),
                     user.to_string(),
                     AckEvent::AddMention {
@@ -421,6 +470,7 @@ impl Message {
         if generate_embeds {
             if let Some(content) = &self.content {
                 tasks::process_embeds::queue(
+                    db,
                     self.channel.to_string(),
                     self.id.to_string(),
                     content.clone(),
@@ -454,14 +504,30 @@ impl Message {
         if !self.has_suppressed_notifications() {
             // Push out Web Push notifications
             crate::tasks::web_push::queue(
+                db,
+                channel.id(),
+                match channel {
+                    Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
+                        Some(server.clone())
+                    }
+                    _ => None,
+                },
+                self.author.clone(),
                 {
                     match channel {
                         Channel::DirectMessage { recipients, .. }
                         | Channel::Group { recipients, .. } => recipients.clone(),
-                        Channel::TextChannel { .. } => self.mentions.clone().unwrap_or_default(),
+                        Channel::TextChannel { .. } | Channel::VoiceChannel { .. } => {
+                            self.mentions.clone().unwrap_or_default()
+                        }
                         _ => vec![],
                     }
                 },
+                crate::tasks::web_push::Mentions {
+                    users: self.mentions.clone().unwrap_or_default(),
+                    roles: self.role_mentions.clone().unwrap_or_default(),
+                    everyone: self.mentions_everyone(),
+                },
                 PushNotification::from(
                     self.clone().into_model(None, None),
                     Some(author),
@@ -484,10 +550,7 @@ impl Message {
         })?;
 
         let media = if let Some(id) = embed.media {
-            Some(
-                db.find_and_use_attachment(&id, "attachments", "message", &self.id)
-                    .await?,
-            )
+            Some(File::use_attachment(db, &id, &self.id).await?)
         } else {
             None
         };
@@ -502,6 +565,13 @@ impl Message {
         }))
     }
 
+    /// Whether this message mentions everyone in the channel
+    pub fn mentions_everyone(&self) -> bool {
+        self.flags.is_some_and(|flags| {
+            flags & MessageFlags::MentionsEveryone as i32 == MessageFlags::MentionsEveryone as i32
+        })
+    }
+
     /// Whether this message has suppressed notifications
     pub fn has_suppressed_notifications(&self) -> bool {
         if let Some(flags) = self.flags {
@@ -514,18 +584,42 @@ impl Message {
 
     /// Update message data
     pub async fn update(&mut self, db: &Database, partial: PartialMessage) -> Result<()> {
+        let files = self.file_ids();
         self.apply_options(partial.clone());
-        db.update_message(&self.id, &partial).await?;
+
+        let tx = db.begin().await?;
+        tx.update_message(&self.id, &partial).await?;
+        File::detach_unused(&tx, files, self.file_ids()).await?;
 
         EventV1::MessageUpdate {
             id: self.id.clone(),
             channel: self.channel.clone(),
             data: partial.into(),
         }
-        .p(self.channel.clone())
+        .p_outbox(&tx, self.channel.clone())
         .await;
 
-        Ok(())
+        tx.commit().await
+    }
+
+    /// Ids of the files used by this message and its embeds
+    fn file_ids(&self) -> Vec<String> {
+        self.attachments
+            .iter()
+            .flatten()
+            .map(|file| file.id.to_string())
+            .chain(
+                self.embeds
+                    .iter()
+                    .flatten()
+                    .filter_map(|embed| match embed {
+                        Embed::Text(Text {
+                            media: Some(file), ..
+                        }) => Some(file.id.to_string()),
+                        _ => None,
+                    }),
+            )
+            .collect()
     }
 
     /// Helper function to fetch many messages with users
@@ -607,17 +701,18 @@ impl Message {
         channel: String,
         append: AppendMessage,
     ) -> Result<()> {
-        db.append_message(&id, &append).await?;
+        let tx = db.begin().await?;
+        tx.append_message(&id, &append).await?;
 
         EventV1::MessageAppend {
             id,
             channel: channel.to_string(),
             append: append.into(),
         }
-        .p(channel)
+        .p_outbox(&tx, channel)
         .await;
 
-        Ok(())
+        tx.commit().await
     }
 
     /// Convert sendable embed to text embed and attach to message
@@ -627,11 +722,7 @@ impl Message {
         embed: v0::SendableEmbed,
     ) -> Result<()> {
         let media: Option<v0::File> = if let Some(id) = embed.media {
-            Some(
-                db.find_and_use_attachment(&id, "attachments", "message", &self.id)
-                    .await?
-                    .into(),
-            )
+            Some(File::use_attachment(db, &id, &self.id).await?.into())
         } else {
             None
         };
@@ -675,17 +766,19 @@ impl Message {
         }
 
         // Send reaction event
+        let tx = db.begin().await?;
         EventV1::MessageReact {
             id: self.id.to_string(),
             channel_id: self.channel.to_string(),
             user_id: user.id.to_string(),
             emoji_id: emoji.to_string(),
         }
-        .p(self.channel.to_string())
+        .p_outbox(&tx, self.channel.to_string())
         .await;
 
         // Add emoji
-        db.add_reaction(&self.id, emoji, &user.id).await
+        tx.add_reaction(&self.id, emoji, &user.id).await?;
+        tx.commit().await
     }
 
     /// Validate the sum of content of a message is under threshold
@@ -714,24 +807,21 @@ impl Message {
 
     /// Delete a message
     pub async fn delete(self, db: &Database) -> Result<()> {
-        let file_ids: Vec<String> = self
-            .attachments
-            .map(|files| files.iter().map(|file| file.id.to_string()).collect())
-            .unwrap_or_default();
-
-        if !file_ids.is_empty() {
-            db.mark_attachments_as_deleted(&file_ids).await?;
-        }
-
-        db.delete_message(&self.id).await?;
+        let tx = db.begin().await?;
+        tx.delete_message(&self.id).await?;
+        tx.detach_attachments_from(&[FileParent::Message {
+            id: self.id.to_string(),
+        }])
+        .await?;
 
         EventV1::MessageDelete {
             id: self.id,
             channel: self.channel.clone(),
         }
-        .p(self.channel)
+        .p_outbox(&tx, self.channel)
         .await;
-        Ok(())
+
+        tx.commit().await
     }
 
     /// Bulk delete messages
@@ -744,14 +834,24 @@ impl Message {
             .map(|msg| msg.id)
             .collect::<Vec<String>>();
 
-        db.delete_messages(channel, &valid_ids).await?;
+        let tx = db.begin().await?;
+        tx.delete_messages(channel, &valid_ids).await?;
+        tx.detach_attachments_from(
+            &valid_ids
+                .iter()
+                .map(|id| FileParent::Message { id: id.to_string() })
+                .collect::<Vec<_>>(),
+        )
+        .await?;
+
         EventV1::BulkMessageDelete {
             channel: channel.to_string(),
             ids: valid_ids,
         }
-        .p(channel.to_string())
+        .p_outbox(&tx, channel.to_string())
         .await;
-        Ok(())
+
+        tx.commit().await
     }
 
     /// Remove a reaction from a message
@@ -768,37 +868,42 @@ impl Message {
         };
 
         // Send reaction event
+        let tx = db.begin().await?;
         EventV1::MessageUnreact {
             id: self.id.to_string(),
             channel_id: self.channel.to_string(),
             user_id: user.to_string(),
             emoji_id: emoji.to_string(),
         }
-        .p(self.channel.to_string())
+        .p_outbox(&tx, self.channel.to_string())
         .await;
 
         if empty {
             // If empty, remove the reaction entirely
-            db.clear_reaction(&self.id, emoji).await
+            tx.clear_reaction(&self.id, emoji).await?;
         } else {
             // Otherwise only remove that one reaction
-            db.remove_reaction(&self.id, emoji, user).await
+            tx.remove_reaction(&self.id, emoji, user).await?;
         }
+
+        tx.commit().await
     }
 
     /// Remove a reaction from a message
     pub async fn clear_reaction(&self, db: &Database, emoji: &str) -> Result<()> {
         // Send reaction event
+        let tx = db.begin().await?;
         EventV1::MessageRemoveReaction {
             id: self.id.to_string(),
             channel_id: self.channel.to_string(),
             emoji_id: emoji.to_string(),
         }
-        .p(self.channel.to_string())
+        .p_outbox(&tx, self.channel.to_string())
         .await;
 
         // Write to database
-        db.clear_reaction(&self.id, emoji).await
+        tx.clear_reaction(&self.id, emoji).await?;
+        tx.commit().await
     }
 }
 
@@ -1,9 +1,15 @@
+use std::collections::HashMap;
+
 use revolt_result::Result;
 
 use crate::{AppendMessage, Message, MessageQuery, PartialMessage};
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractMessages: Sync + Send {
@@ -39,4 +45,23 @@ pub trait AbstractMessages: Sync + Send {
 
     /// Delete messages from a channel by their ids and corresponding channel id
     async fn delete_messages(&self, channel: &str, ids: &[String]) -> Result<()>;
+
+    /// Count messages in a channel sent after the given message by anyone
+    /// other than the given user, stopping once `limit` have been found
+    async fn count_unread_messages(
+        &self,
+        channel: &str,
+        user: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<usize>;
+
+    /// Count unread messages in each of the given channels at once, given the
+    /// last message read in each, leaving out channels with nothing unread
+    async fn count_unread_messages_in_channels(
+        &self,
+        channels: &[(String, Option<String>)],
+        user: &str,
+        limit: usize,
+    ) -> Result<HashMap<String, usize>>;
 }
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use bson::{to_bson, Document};
-use futures::try_join;
-use mongodb::options::FindOptions;
+use futures::{try_join, StreamExt};
+use mongodb::options::{CountOptions, FindOptions};
 use revolt_models::v0::MessageSort;
 use revolt_result::Result;
 
@@ -166,7 +168,7 @@ impl AbstractMessages for MongoDb {
         self.find_with_options(
             COL,
             doc! {
-                "ids": {
+                "_id": {
                     "$in": ids
                 }
             },
@@ -294,19 +296,55 @@ impl AbstractMessages for MongoDb {
             .map(|_| ())
             .map_err(|_| create_database_error!("delete_many", COL))
     }
+
+    /// Count messages in a channel sent after the given message by anyone
+    /// other than the given user, stopping once `limit` have been found
+    async fn count_unread_messages(
+        &self,
+        channel: &str,
+        user: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<usize> {
+        let mut filter = doc! {
+            "channel": channel,
+            "author": {
+                "$ne": user
+            }
+        };
+
+        if let Some(after) = after {
+            filter.insert("_id", doc! { "$gt": after });
+        }
+
+        self.col::<Document>(COL)
+            .count_documents(filter, CountOptions::builder().limit(limit as u64).build())
+            .await
+            .map(|count| count as usize)
+            .map_err(|_| create_database_error!("count_documents", COL))
+    }
 }
 
 impl MongoDb {
     pub async fn delete_bulk_messages(&self, projection: Document) -> Result<()> {
         let mut for_attachments = projection.clone();
         for_attachments.insert(
-            "attachments",
-            doc! {
-                "$exists": 1_i32
-            },
+            "$or",
+            [
+                doc! {
+                    "attachments": {
+                        "$exists": 1_i32
+                    }
+                },
+                doc! {
+                    "embeds": {
+                        "$exists": 1_i32
+                    }
+                },
+            ],
         );
 
-        // Check if there are any attachments we need to delete.
+        // Check if there are any attachments we need to detach.
         let message_ids_with_attachments = self
             .find_with_options::<_, DocumentId>(
                 COL,
@@ -321,24 +359,15 @@ impl MongoDb {
             .map(|x| x.id)
             .collect::<Vec<String>>();
 
-        // If we found any, mark them as deleted.
+        // If we found any, detach them.
         if !message_ids_with_attachments.is_empty() {
-            self.col::<Document>("attachments")
-                .update_many(
-                    doc! {
-                        "message_id": {
-                            "$in": message_ids_with_attachments
-                        }
-                    },
-                    doc! {
-                        "$set": {
-                            "deleted": true
-                        }
-                    },
-                    None,
-                )
-                .await
-                .map_err(|_| create_database_error!("update_many", "attachments"))?;
+            self.detach_many_attachments(doc! {
+                "parent.type": "Message",
+                "parent.id": {
+                    "$in": message_ids_with_attachments
+                }
+            })
+            .await?;
         }
 
         // And then delete said messages.
@@ -348,4 +377,66 @@ impl MongoDb {
             .map(|_| ())
             .map_err(|_| create_database_error!("delete_many", COL))
     }
+
+    /// Count unread messages in each of the given channels at once, given the
+    /// last message read in each, leaving out channels with nothing unread
+    async fn count_unread_messages_in_channels(
+        &self,
+        channels: &[(String, Option<String>)],
+        user: &str,
+        limit: usize,
+    ) -> Result<HashMap<String, usize>> {
+        if channels.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let unread: Vec<Document> = channels
+            .iter()
+            .map(|(channel, after)| match after {
+                Some(after) => doc! {
+                    "channel": channel,
+                    "_id": {
+                        "$gt": after
+                    }
+                },
+                None => doc! {
+                    "channel": channel
+                },
+            })
+            .collect();
+
+        Ok(self
+            .col::<Document>(COL)
+            .aggregate(
+                vec![
+                    doc! {
+                        "$match": {
+                            "$or": unread,
+                            "author": {
+                                "$ne": user
+                            }
+                        }
+                    },
+                    doc! {
+                        "$group": {
+                            "_id": "$channel",
+                            "count": {
+                                "$sum": 1_i32
+                            }
+                        }
+                    },
+                ],
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("aggregate", COL))?
+            .filter_map(|doc| async { doc.ok() })
+            .filter_map(|doc| async move {
+                let channel = doc.get_str("_id").ok()?.to_string();
+                let count = doc.get_i32("count").ok()? as usize;
+                Some((channel, count.min(limit)))
+            })
+            .collect()
+            .await)
+    }
 }
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use futures::try_join;
+use revolt_models::v0::MessageSort;
+use revolt_result::Result;
+use serde_json::{json, Value};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::{
+    AbstractAttachments, AppendMessage, FileParent, Message, MessageFilter, MessageQuery,
+    MessageTimePeriod, PartialMessage, PostgresDb,
+};
+
+use super::AbstractMessages;
+
+static COL: &str = "messages";
+
+/// Expression used to search message content, must match the index
+static SEARCH_VECTOR: &str = "to_tsvector('english', coalesce(data->>'content', ''))";
+
+/// Expression used to order messages by their id
+static ORDER_BY_ID: &str = r#"(data->>'_id') COLLATE "C""#;
+
+/// Start a query for messages matching the given filter
+fn select_messages<'a>(filter: &MessageFilter) -> QueryBuilder<'a, Postgres> {
+    let mut query = QueryBuilder::new("SELECT data FROM messages WHERE TRUE");
+
+    if let Some(channel) = &filter.channel {
+        query
+            .push(" AND data->>'channel' = ")
+            .push_bind(channel.clone());
+    }
+
+    if let Some(author) = &filter.author {
+        query
+            .push(" AND data->>'author' = ")
+            .push_bind(author.clone());
+    }
+
+    if let Some(search) = &filter.query {
+        query
+            .push(format!(
+                " AND {SEARCH_VECTOR} @@ websearch_to_tsquery('english', "
+            ))
+            .push_bind(search.clone())
+            .push(")");
+    }
+
+    query
+}
+
+#[async_trait]
+impl AbstractMessages for PostgresDb {
+    /// Insert a new message into the database
+    async fn insert_message(&self, message: &Message) -> Result<()> {
+        query!(self, insert_one, COL, &message)
+    }
+
+    /// Fetch a message by its id
+    async fn fetch_message(&self, id: &str) -> Result<Message> {
+        query!(self, find_one_by_id, COL, id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch multiple messages by given query
+    async fn fetch_messages(&self, query: MessageQuery) -> Result<Vec<Message>> {
+        // 1. Find query limit
+        let limit = query.limit.unwrap_or(50);
+
+        // 2. Apply message time period
+        match query.time_period {
+            MessageTimePeriod::Relative { nearby } => {
+                // 2.1. Prepare queries
+                let mut newer_messages = select_messages(&query.filter);
+                newer_messages
+                    .push(format!(" AND {ORDER_BY_ID} >= "))
+                    .push_bind(nearby.clone())
+                    .push(format!(" ORDER BY {ORDER_BY_ID} ASC LIMIT "))
+                    .push_bind(limit / 2 + 1);
+
+                let mut older_messages = select_messages(&query.filter);
+                older_messages
+                    .push(format!(" AND {ORDER_BY_ID} < "))
+                    .push_bind(nearby)
+                    .push(format!(" ORDER BY {ORDER_BY_ID} DESC LIMIT "))
+                    .push_bind(limit / 2);
+
+                // 2.2. Execute in both directions
+                let (a, b) = try_join!(
+                    self.find_by_query::<Message>(newer_messages),
+                    self.find_by_query::<Message>(older_messages)
+                )
+                .map_err(|_| create_database_error!("find", COL))?;
+
+                Ok([a, b].concat())
+            }
+            MessageTimePeriod::Absolute {
+                before,
+                after,
+                sort,
+            } => {
+                let mut messages = select_messages(&query.filter);
+
+                // 2.1. Apply message ID filter
+                if let Some(before) = before {
+                    messages
+                        .push(format!(" AND {ORDER_BY_ID} < "))
+                        .push_bind(before);
+                }
+
+                if let Some(after) = after {
+                    messages
+                        .push(format!(" AND {ORDER_BY_ID} > "))
+                        .push_bind(after);
+                }
+
+                // 2.2. Execute with given message sort
+                match sort.unwrap_or(MessageSort::Latest) {
+                    // Sort by relevance, fallback to latest
+                    MessageSort::Relevance => {
+                        if let Some(search) = query.filter.query {
+                            messages
+                                .push(format!(
+                                    " ORDER BY ts_rank({SEARCH_VECTOR}, websearch_to_tsquery('english', "
+                                ))
+                                .push_bind(search)
+                                .push(format!(")) DESC, {ORDER_BY_ID} DESC"));
+                        } else {
+                            messages.push(format!(" ORDER BY {ORDER_BY_ID} DESC"));
+                        }
+                    }
+                    // Sort by latest first
+                    MessageSort::Latest => {
+                        messages.push(format!(" ORDER BY {ORDER_BY_ID} DESC"));
+                    }
+                    // Sort by oldest first
+                    MessageSort::Oldest => {
+                        messages.push(format!(" ORDER BY {ORDER_BY_ID} ASC"));
+                    }
+                }
+
+                messages.push(" LIMIT ").push_bind(limit);
+
+                self.find_by_query(messages)
+                    .await
+                    .map_err(|_| create_database_error!("find", COL))
+            }
+        }
+    }
+
+    /// Fetch multiple messages by given IDs
+    async fn fetch_messages_by_id(&self, ids: &[String]) -> Result<Vec<Message>> {
+        query!(self, find_in, COL, "data->>'_id'", ids)
+    }
+
+    /// Update a given message with new information
+    async fn update_message(&self, id: &str, message: &PartialMessage) -> Result<()> {
+        query!(self, update_one_by_id, COL, id, message, vec![], None).map(|_| ())
+    }
+
+    /// Append information to a given message
+    async fn append_message(&self, id: &str, append: &AppendMessage) -> Result<()> {
+        let Some(embeds) = &append.embeds else {
+            return Ok(());
+        };
+
+        if embeds.is_empty() {
+            return Ok(());
+        }
+
+        let embeds: Vec<Value> = embeds
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| create_database_error!("to_value", "embeds"))?;
+
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(message) = message.as_object_mut() {
+                let existing = message
+                    .entry("embeds")
+                    .or_insert_with(|| Value::Array(vec![]));
+
+                if let Some(existing) = existing.as_array_mut() {
+                    existing.extend(embeds);
+                }
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Add a new reaction to a message
+    async fn add_reaction(&self, id: &str, emoji: &str, user: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(message) = message.as_object_mut() {
+                let reactions = message.entry("reactions").or_insert_with(|| json!({}));
+
+                if let Some(reactions) = reactions.as_object_mut() {
+                    let users = reactions
+                        .entry(emoji)
+                        .or_insert_with(|| Value::Array(vec![]));
+
+                    if let Some(users) = users.as_array_mut() {
+                        if !users.iter().any(|existing| existing == user) {
+                            users.push(json!(user));
+                        }
+                    }
+                }
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Remove a reaction from a message
+    async fn remove_reaction(&self, id: &str, emoji: &str, user: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(users) = message
+                .get_mut("reactions")
+                .and_then(|reactions| reactions.get_mut(emoji))
+                .and_then(Value::as_array_mut)
+            {
+                users.retain(|existing| existing != user);
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Remove reaction from a message
+    async fn clear_reaction(&self, id: &str, emoji: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(reactions) = message.get_mut("reactions").and_then(Value::as_object_mut) {
+                reactions.remove(emoji);
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete a message from the database by its id
+    async fn delete_message(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Delete messages from a channel by their ids and corresponding channel id
+    async fn delete_messages(&self, channel: &str, ids: &[String]) -> Result<()> {
+        sqlx::query("DELETE FROM messages WHERE data->>'channel' = $1 AND data->>'_id' = ANY($2)")
+            .bind(channel)
+            .bind(ids)
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Count messages in a channel sent after the given message by anyone
+    /// other than the given user, stopping once `limit` have been found
+    async fn count_unread_messages(
+        &self,
+        channel: &str,
+        user: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<usize> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT COUNT(*) FROM (SELECT 1 FROM messages WHERE data->>'channel' = ",
+        );
+
+        query
+            .push_bind(channel.to_string())
+            .push(" AND data->>'author' != ")
+            .push_bind(user.to_string());
+
+        if let Some(after) = after {
+            query
+                .push(format!(" AND {ORDER_BY_ID} > "))
+                .push_bind(after.to_string());
+        }
+
+        query
+            .push(" LIMIT ")
+            .push_bind(limit as i64)
+            .push(") AS unread");
+
+        query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.0)
+            .await
+            .map(|count| count as usize)
+            .map_err(|_| create_database_error!("count_documents", COL))
+    }
+}
+
+impl PostgresDb {
+    /// Delete all messages in the given channels, detaching their attachments
+    pub async fn delete_bulk_messages(&self, channels: &[String]) -> Result<()> {
+        // Check if there are any attachments we need to detach.
+        let message_ids_with_attachments: Vec<String> = sqlx::query_scalar(
+            "SELECT data->>'_id' FROM messages
+             WHERE data->>'channel' = ANY($1) AND (data ? 'attachments' OR data ? 'embeds')",
+        )
+        .bind(channels)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("find_many", "attachments"))?;
+
+        // If we found any, detach them.
+        if !message_ids_with_attachments.is_empty() {
+            self.detach_attachments_from(
+                &message_ids_with_attachments
+                    .into_iter()
+                    .map(|id| FileParent::Message { id })
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+        }
+
+        // And then delete said messages.
+        sqlx::query("DELETE FROM messages WHERE data->>'channel' = ANY($1)")
+            .bind(channels)
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Count unread messages in each of the given channels at once, given the
+    /// last message read in each, leaving out channels with nothing unread
+    async fn count_unread_messages_in_channels(
+        &self,
+        channels: &[(String, Option<String>)],
+        user: &str,
+        limit: usize,
+    ) -> Result<HashMap<String, usize>> {
+        let (ids, after): (Vec<String>, Vec<Option<String>>) = channels.iter().cloned().unzip();
+
+        sqlx::query_as::<_, (String, i64)>(&format!(
+            "SELECT since.channel, unread.count
+             FROM unnest($1::text[], $2::text[]) AS since(channel, after)
+             CROSS JOIN LATERAL (
+                SELECT COUNT(*) AS count FROM (
+                    SELECT 1 FROM messages
+                    WHERE data->>'channel' = since.channel
+                      AND data->>'author' != $3
+                      AND (since.after IS NULL OR {ORDER_BY_ID} > since.after)
+                    LIMIT $4
+                ) AS unread
+             ) AS unread
+             WHERE unread.count > 0"
+        ))
+        .bind(ids)
+        .bind(after)
+        .bind(user)
+        .bind(limit as i64)
+        .fetch_all(&self.0)
+        .await
+        .map(|counts| {
+            counts
+                .into_iter()
+                .map(|(channel, count)| (channel, count as usize))
+                .collect()
+        })
+        .map_err(|_| create_database_error!("count_documents", COL))
+    }
+}
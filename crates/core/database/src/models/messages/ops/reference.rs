@@ -1,8 +1,10 @@
-use futures::future::try_join_all;
+use std::collections::HashMap;
+
 use indexmap::IndexSet;
+use revolt_models::v0::MessageSort;
 use revolt_result::Result;
 
-use crate::{AppendMessage, Message, MessageQuery, PartialMessage, ReferenceDb};
+use crate::{AppendMessage, Message, MessageQuery, MessageTimePeriod, PartialMessage, ReferenceDb};
 
 use super::AbstractMessages;
 
@@ -31,7 +33,12 @@ impl AbstractMessages for ReferenceDb {
     /// Fetch multiple messages by given query
     async fn fetch_messages(&self, query: MessageQuery) -> Result<Vec<Message>> {
         let messages = self.messages.lock().await;
-        let matched_messages = messages
+        let search = query
+            .filter
+            .query
+            .as_ref()
+            .map(|query| query.to_lowercase());
+        let mut matched_messages: Vec<Message> = messages
             .values()
             .filter(|message| {
                 if let Some(channel) = &query.filter.channel {
@@ -46,9 +53,9 @@ impl AbstractMessages for ReferenceDb {
                     }
                 }
 
-                if let Some(query) = &query.filter.query {
+                if let Some(search) = &search {
                     if let Some(content) = &message.content {
-                        if !content.to_lowercase().contains(query) {
+                        if !content.to_lowercase().contains(search) {
                             return false;
                         }
                     } else {
@@ -61,125 +68,56 @@ impl AbstractMessages for ReferenceDb {
             .cloned()
             .collect();
 
-        // FIXME: sorting, etc (will be required for tests)
-
-        Ok(matched_messages)
-
-        /*
-        // 2. Find query limit
+        // 1. Find query limit
         let limit = query.limit.unwrap_or(50);
 
-        // 3. Apply message time period
+        // 2. Apply message time period
+        matched_messages.sort_by(|a, b| a.id.cmp(&b.id));
         match query.time_period {
             MessageTimePeriod::Relative { nearby } => {
-                // 3.1. Prepare filters
-                let mut older_message_filter = filter.clone();
-                let mut newer_message_filter = filter;
-
-                older_message_filter.insert(
-                    "_id",
-                    doc! {
-                        "$lt": &nearby
-                    },
-                );
-
-                newer_message_filter.insert(
-                    "_id",
-                    doc! {
-                        "$gte": &nearby
-                    },
-                );
-
-                // 3.2. Execute in both directions
-                let (a, b) = try_join!(
-                    self.find_with_options::<_, Message>(
-                        COL,
-                        newer_message_filter,
-                        FindOptions::builder()
-                            .limit(limit / 2 + 1)
-                            .sort(doc! {
-                                "_id": 1_i32
-                            })
-                            .build(),
-                    ),
-                    self.find_with_options::<_, Message>(
-                        COL,
-                        older_message_filter,
-                        FindOptions::builder()
-                            .limit(limit / 2)
-                            .sort(doc! {
-                                "_id": -1_i32
-                            })
-                            .build(),
-                    )
-                )
-                .map_err(|_| create_database_error!("find", COL))?;
-
-                Ok([a, b].concat())
+                let newer_messages = matched_messages
+                    .iter()
+                    .filter(|message| message.id >= nearby)
+                    .take((limit / 2 + 1) as usize);
+
+                let older_messages = matched_messages
+                    .iter()
+                    .rev()
+                    .filter(|message| message.id < nearby)
+                    .take((limit / 2) as usize);
+
+                Ok(newer_messages.chain(older_messages).cloned().collect())
             }
             MessageTimePeriod::Absolute {
                 before,
                 after,
                 sort,
             } => {
-                // 3.1. Apply message ID filter
-                if let Some(doc) = match (before, after) {
-                    (Some(before), Some(after)) => Some(doc! {
-                        "$lt": before,
-                        "$gt": after
-                    }),
-                    (Some(before), _) => Some(doc! {
-                        "$lt": before
-                    }),
-                    (_, Some(after)) => Some(doc! {
-                        "$gt": after
-                    }),
-                    _ => None,
-                } {
-                    filter.insert("_id", doc);
+                // 2.1. Apply message ID filter
+                matched_messages.retain(|message| {
+                    before.as_ref().map_or(true, |before| &message.id < before)
+                        && after.as_ref().map_or(true, |after| &message.id > after)
+                });
+
+                // 2.2. Apply message sort, relevance falls back to latest
+                if !matches!(sort, Some(MessageSort::Oldest)) {
+                    matched_messages.reverse();
                 }
 
-                // 3.2. Execute with given message sort
-                self.find_with_options(
-                    COL,
-                    filter,
-                    FindOptions::builder()
-                        .limit(limit)
-                        .sort(match sort.unwrap_or(MessageSort::Latest) {
-                            // Sort by relevance, fallback to latest
-                            MessageSort::Relevance => {
-                                if is_search_query {
-                                    doc! {
-                                        "score": {
-                                            "$meta": "textScore"
-                                        }
-                                    }
-                                } else {
-                                    doc! {
-                                        "_id": -1_i32
-                                    }
-                                }
-                            }
-                            // Sort by latest first
-                            MessageSort::Latest => doc! {
-                                "_id": -1_i32
-                            },
-                            // Sort by oldest first
-                            MessageSort::Oldest => doc! {
-                                "_id": 1_i32
-                            },
-                        })
-                        .build(),
-                )
-                .await
-                .map_err(|_| create_database_error!("find", COL))
+                matched_messages.truncate(limit as usize);
+                Ok(matched_messages)
             }
-        }*/
+        }
     }
 
     /// Fetch multiple messages by given IDs
     async fn fetch_messages_by_id(&self, ids: &[String]) -> Result<Vec<Message>> {
-        try_join_all(ids.iter().map(|id| self.fetch_message(id))).await
+        let messages = self.messages.lock().await;
+        Ok(ids
+            .iter()
+            .filter_map(|id| messages.get(id))
+            .cloned()
+            .collect())
     }
 
     /// Update a given message with new information
@@ -271,8 +209,59 @@ impl AbstractMessages for ReferenceDb {
         self.messages
             .lock()
             .await
-            .retain(|id, message| message.channel != channel && !ids.contains(id));
+            .retain(|id, message| message.channel != channel || !ids.contains(id));
 
         Ok(())
     }
+
+    /// Count messages in a channel sent after the given message by anyone
+    /// other than the given user, stopping once `limit` have been found
+    async fn count_unread_messages(
+        &self,
+        channel: &str,
+        user: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<usize> {
+        Ok(self
+            .messages
+            .lock()
+            .await
+            .values()
+            .filter(|message| {
+                message.channel == channel
+                    && message.author != user
+                    && after.map_or(true, |after| message.id.as_str() > after)
+            })
+            .take(limit)
+            .count())
+    }
+
+    /// Count unread messages in each of the given channels at once, given the
+    /// last message read in each, leaving out channels with nothing unread
+    async fn count_unread_messages_in_channels(
+        &self,
+        channels: &[(String, Option<String>)],
+        user: &str,
+        limit: usize,
+    ) -> Result<HashMap<String, usize>> {
+        let last_read: HashMap<&str, Option<&str>> = channels
+            .iter()
+            .map(|(channel, after)| (channel.as_str(), after.as_deref()))
+            .collect();
+
+        let mut counts = HashMap::<String, usize>::new();
+        for message in self.messages.lock().await.values() {
+            let Some(after) = last_read.get(message.channel.as_str()) else {
+                continue;
+            };
+
+            if message.author != user && after.map_or(true, |after| message.id.as_str() > after) {
+                let count = counts.entry(message.channel.clone()).or_default();
+                *count = (*count + 1).min(limit);
+            }
+        }
+
+        Ok(counts)
+    }
 }
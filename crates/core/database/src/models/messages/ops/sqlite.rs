@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use futures::try_join;
+use revolt_models::v0::MessageSort;
+use revolt_result::Result;
+use serde_json::{json, Value};
+use sqlx::{QueryBuilder, Sqlite};
+
+use crate::{
+    document_key, AbstractAttachments, AppendMessage, FileParent, Message, MessageFilter,
+    MessageQuery, MessageTimePeriod, PartialMessage, SqliteDb,
+};
+
+use super::AbstractMessages;
+
+static COL: &str = "messages";
+
+/// Expression used to order messages by their id
+///
+/// Keys are stored as JSON strings, so bound ids must go through [message_key].
+static ORDER_BY_ID: &str = "messages.id";
+
+/// Key a message id is stored under
+fn message_key(id: String) -> String {
+    document_key(&Value::String(id))
+}
+
+/// Convert a user search into an FTS5 query
+///
+/// Every term is quoted so that user input can't use the query syntax,
+/// all of the terms must be present for a message to match.
+fn search_terms(search: &str) -> String {
+    search
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Start a query for messages matching the given filter
+fn select_messages<'a>(filter: &MessageFilter) -> QueryBuilder<'a, Sqlite> {
+    let mut query = if let Some(search) = &filter.query {
+        let mut query = QueryBuilder::new(
+            "SELECT messages.data FROM messages
+             JOIN messages_search ON messages_search.rowid = messages.rowid
+             WHERE messages_search MATCH ",
+        );
+
+        query.push_bind(search_terms(search));
+        query
+    } else {
+        QueryBuilder::new("SELECT messages.data FROM messages WHERE TRUE")
+    };
+
+    if let Some(channel) = &filter.channel {
+        query
+            .push(" AND json_extract(messages.data, '$.channel') = ")
+            .push_bind(channel.clone());
+    }
+
+    if let Some(author) = &filter.author {
+        query
+            .push(" AND json_extract(messages.data, '$.author') = ")
+            .push_bind(author.clone());
+    }
+
+    query
+}
+
+#[async_trait]
+impl AbstractMessages for SqliteDb {
+    /// Insert a new message into the database
+    async fn insert_message(&self, message: &Message) -> Result<()> {
+        query!(self, insert_one, COL, &message)
+    }
+
+    /// Fetch a message by its id
+    async fn fetch_message(&self, id: &str) -> Result<Message> {
+        query!(self, find_one_by_id, COL, id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch multiple messages by given query
+    async fn fetch_messages(&self, query: MessageQuery) -> Result<Vec<Message>> {
+        // 1. Find query limit
+        let limit = query.limit.unwrap_or(50);
+
+        // 2. Apply message time period
+        match query.time_period {
+            MessageTimePeriod::Relative { nearby } => {
+                // 2.1. Prepare queries
+                let mut newer_messages = select_messages(&query.filter);
+                newer_messages
+                    .push(format!(" AND {ORDER_BY_ID} >= "))
+                    .push_bind(message_key(nearby.clone()))
+                    .push(format!(" ORDER BY {ORDER_BY_ID} ASC LIMIT "))
+                    .push_bind(limit / 2 + 1);
+
+                let mut older_messages = select_messages(&query.filter);
+                older_messages
+                    .push(format!(" AND {ORDER_BY_ID} < "))
+                    .push_bind(message_key(nearby))
+                    .push(format!(" ORDER BY {ORDER_BY_ID} DESC LIMIT "))
+                    .push_bind(limit / 2);
+
+                // 2.2. Execute in both directions
+                let (a, b) = try_join!(
+                    self.find_by_query::<Message>(newer_messages),
+                    self.find_by_query::<Message>(older_messages)
+                )
+                .map_err(|_| create_database_error!("find", COL))?;
+
+                Ok([a, b].concat())
+            }
+            MessageTimePeriod::Absolute {
+                before,
+                after,
+                sort,
+            } => {
+                let mut messages = select_messages(&query.filter);
+
+                // 2.1. Apply message ID filter
+                if let Some(before) = before {
+                    messages
+                        .push(format!(" AND {ORDER_BY_ID} < "))
+                        .push_bind(message_key(before));
+                }
+
+                if let Some(after) = after {
+                    messages
+                        .push(format!(" AND {ORDER_BY_ID} > "))
+                        .push_bind(message_key(after));
+                }
+
+                // 2.2. Execute with given message sort
+                match sort.unwrap_or(MessageSort::Latest) {
+                    // Sort by relevance, fallback to latest
+                    MessageSort::Relevance => {
+                        if query.filter.query.is_some() {
+                            // Lower scores are better matches
+                            messages.push(format!(
+                                " ORDER BY bm25(messages_search) ASC, {ORDER_BY_ID} DESC"
+                            ));
+                        } else {
+                            messages.push(format!(" ORDER BY {ORDER_BY_ID} DESC"));
+                        }
+                    }
+                    // Sort by latest first
+                    MessageSort::Latest => {
+                        messages.push(format!(" ORDER BY {ORDER_BY_ID} DESC"));
+                    }
+                    // Sort by oldest first
+                    MessageSort::Oldest => {
+                        messages.push(format!(" ORDER BY {ORDER_BY_ID} ASC"));
+                    }
+                }
+
+                messages.push(" LIMIT ").push_bind(limit);
+
+                self.find_by_query(messages)
+                    .await
+                    .map_err(|_| create_database_error!("find", COL))
+            }
+        }
+    }
+
+    /// Fetch multiple messages by given IDs
+    async fn fetch_messages_by_id(&self, ids: &[String]) -> Result<Vec<Message>> {
+        query!(self, find_in, COL, "json_extract(data, '$._id')", ids)
+    }
+
+    /// Update a given message with new information
+    async fn update_message(&self, id: &str, message: &PartialMessage) -> Result<()> {
+        query!(self, update_one_by_id, COL, id, message, vec![], None).map(|_| ())
+    }
+
+    /// Append information to a given message
+    async fn append_message(&self, id: &str, append: &AppendMessage) -> Result<()> {
+        let Some(embeds) = &append.embeds else {
+            return Ok(());
+        };
+
+        if embeds.is_empty() {
+            return Ok(());
+        }
+
+        let embeds: Vec<Value> = embeds
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| create_database_error!("to_value", "embeds"))?;
+
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(message) = message.as_object_mut() {
+                let existing = message
+                    .entry("embeds")
+                    .or_insert_with(|| Value::Array(vec![]));
+
+                if let Some(existing) = existing.as_array_mut() {
+                    existing.extend(embeds);
+                }
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Add a new reaction to a message
+    async fn add_reaction(&self, id: &str, emoji: &str, user: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(message) = message.as_object_mut() {
+                let reactions = message.entry("reactions").or_insert_with(|| json!({}));
+
+                if let Some(reactions) = reactions.as_object_mut() {
+                    let users = reactions
+                        .entry(emoji)
+                        .or_insert_with(|| Value::Array(vec![]));
+
+                    if let Some(users) = users.as_array_mut() {
+                        if !users.iter().any(|existing| existing == user) {
+                            users.push(json!(user));
+                        }
+                    }
+                }
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Remove a reaction from a message
+    async fn remove_reaction(&self, id: &str, emoji: &str, user: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(users) = message
+                .get_mut("reactions")
+                .and_then(|reactions| reactions.get_mut(emoji))
+                .and_then(Value::as_array_mut)
+            {
+                users.retain(|existing| existing != user);
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Remove reaction from a message
+    async fn clear_reaction(&self, id: &str, emoji: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |message| {
+            if let Some(reactions) = message.get_mut("reactions").and_then(Value::as_object_mut) {
+                reactions.remove(emoji);
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete a message from the database by its id
+    async fn delete_message(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Delete messages from a channel by their ids and corresponding channel id
+    async fn delete_messages(&self, channel: &str, ids: &[String]) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM messages WHERE json_extract(data, '$.channel') = ?
+             AND json_extract(data, '$._id') IN (SELECT value FROM json_each(?))",
+        )
+        .bind(channel)
+        .bind(serde_json::to_string(ids).expect("strings always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Count messages in a channel sent after the given message by anyone
+    /// other than the given user, stopping once `limit` have been found
+    async fn count_unread_messages(
+        &self,
+        channel: &str,
+        user: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<usize> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT COUNT(*) FROM (SELECT 1 FROM messages WHERE json_extract(data, '$.channel') = ",
+        );
+
+        query
+            .push_bind(channel.to_string())
+            .push(" AND json_extract(data, '$.author') != ")
+            .push_bind(user.to_string());
+
+        if let Some(after) = after {
+            query
+                .push(format!(" AND {ORDER_BY_ID} > "))
+                .push_bind(message_key(after.to_string()));
+        }
+
+        query.push(" LIMIT ").push_bind(limit as i64).push(")");
+
+        query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.0)
+            .await
+            .map(|count| count as usize)
+            .map_err(|_| create_database_error!("count_documents", COL))
+    }
+}
+
+impl SqliteDb {
+    /// Delete all messages in the given channels, detaching their attachments
+    pub async fn delete_bulk_messages(&self, channels: &[String]) -> Result<()> {
+        // Check if there are any attachments we need to detach.
+        let channels = serde_json::to_string(channels).expect("strings always serialise");
+        let message_ids_with_attachments: Vec<String> = sqlx::query_scalar(
+            "SELECT json_extract(data, '$._id') FROM messages
+             WHERE json_extract(data, '$.channel') IN (SELECT value FROM json_each(?))
+             AND (json_type(data, '$.attachments') IS NOT NULL
+                  OR json_type(data, '$.embeds') IS NOT NULL)",
+        )
+        .bind(&channels)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("find_many", "attachments"))?;
+
+        // If we found any, detach them.
+        if !message_ids_with_attachments.is_empty() {
+            self.detach_attachments_from(
+                &message_ids_with_attachments
+                    .into_iter()
+                    .map(|id| FileParent::Message { id })
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+        }
+
+        // And then delete said messages.
+        sqlx::query(
+            "DELETE FROM messages
+             WHERE json_extract(data, '$.channel') IN (SELECT value FROM json_each(?))",
+        )
+        .bind(channels)
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+
+    /// Count unread messages in each of the given channels at once, given the
+    /// last message read in each, leaving out channels with nothing unread
+    async fn count_unread_messages_in_channels(
+        &self,
+        channels: &[(String, Option<String>)],
+        user: &str,
+        limit: usize,
+    ) -> Result<HashMap<String, usize>> {
+        let channels: Vec<Value> = channels
+            .iter()
+            .map(|(channel, after)| {
+                json!({
+                    "channel": channel,
+                    "after": after.clone().map(message_key)
+                })
+            })
+            .collect();
+
+        sqlx::query_as::<_, (String, i64)>(&format!(
+            "SELECT channel, count FROM (
+                SELECT json_extract(since.value, '$.channel') AS channel, (
+                    SELECT COUNT(*) FROM (
+                        SELECT 1 FROM messages
+                        WHERE json_extract(data, '$.channel') = json_extract(since.value, '$.channel')
+                          AND json_extract(data, '$.author') != ?2
+                          AND (
+                              json_extract(since.value, '$.after') IS NULL
+                              OR {ORDER_BY_ID} > json_extract(since.value, '$.after')
+                          )
+                        LIMIT ?3
+                    )
+                ) AS count
+                FROM json_each(?1) AS since
+            ) WHERE count > 0"
+        ))
+        .bind(Value::Array(channels).to_string())
+        .bind(user)
+        .bind(limit as i64)
+        .fetch_all(&self.0)
+        .await
+        .map(|counts| {
+            counts
+                .into_iter()
+                .map(|(channel, count)| (channel, count as usize))
+                .collect()
+        })
+        .map_err(|_| create_database_error!("count_documents", COL))
+    }
+}
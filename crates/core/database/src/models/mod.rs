@@ -6,13 +6,18 @@ mod channel_webhooks;
 mod channels;
 mod emojis;
 mod files;
+mod jobs;
 mod messages;
+mod notification_settings;
+mod outbox_events;
+mod push_windows;
 mod ratelimit_events;
 mod safety_reports;
 mod safety_snapshots;
 mod server_bans;
 mod server_members;
 mod servers;
+mod unified_push_subscriptions;
 mod user_settings;
 mod users;
 
@@ -24,13 +29,18 @@ pub use channel_webhooks::*;
 pub use channels::*;
 pub use emojis::*;
 pub use files::*;
+pub use jobs::*;
 pub use messages::*;
+pub use notification_settings::*;
+pub use outbox_events::*;
+pub use push_windows::*;
 pub use ratelimit_events::*;
 pub use safety_reports::*;
 pub use safety_snapshots::*;
 pub use server_bans::*;
 pub use server_members::*;
 pub use servers::*;
+pub use unified_push_subscriptions::*;
 pub use user_settings::*;
 pub use users::*;
 
@@ -47,13 +57,18 @@ pub trait AbstractDatabase:
     + channel_webhooks::AbstractWebhooks
     + emojis::AbstractEmojis
     + files::AbstractAttachments
+    + jobs::AbstractJobs
     + messages::AbstractMessages
+    + notification_settings::AbstractNotificationSettings
+    + outbox_events::AbstractOutboxEvents
+    + push_windows::AbstractPushWindows
     + ratelimit_events::AbstractRatelimitEvents
     + safety_reports::AbstractReport
     + safety_snapshots::AbstractSnapshot
     + server_bans::AbstractServerBans
     + server_members::AbstractServerMembers
     + servers::AbstractServers
+    + unified_push_subscriptions::AbstractUnifiedPushSubscriptions
     + user_settings::AbstractUserSettings
     + users::AbstractUsers
 {
@@ -62,6 +77,12 @@ pub trait AbstractDatabase:
 impl AbstractDatabase for ReferenceDb {}
 impl AbstractDatabase for MongoDb {}
 
+#[cfg(feature = "postgres")]
+impl AbstractDatabase for crate::PostgresDb {}
+
+#[cfg(feature = "sqlite")]
+impl AbstractDatabase for crate::SqliteDb {}
+
 impl std::ops::Deref for Database {
     type Target = dyn AbstractDatabase;
 
@@ -69,6 +90,10 @@ impl std::ops::Deref for Database {
         match &self {
             Database::Reference(dummy) => dummy,
             Database::MongoDb(mongo) => mongo,
+            #[cfg(feature = "postgres")]
+            Database::Postgres(postgres) => postgres,
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(sqlite) => sqlite,
         }
     }
 }
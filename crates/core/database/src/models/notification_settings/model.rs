@@ -0,0 +1,735 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use iso8601_timestamp::Timestamp;
+use revolt_models::v0;
+use revolt_result::{create_error, Result};
+
+use crate::{events::client::EventV1, outbox_time, Database};
+
+auto_derived!(
+    /// What a user wants to be notified about
+    pub enum NotificationLevel {
+        /// Every message
+        All,
+        /// Only messages which mention the user
+        Mentions,
+        /// Nothing
+        None,
+    }
+
+    /// Notification preferences for a server or channel
+    #[derive(Default)]
+    pub struct NotificationOverride {
+        /// What to be notified about, inherited from the server if not set
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub level: Option<NotificationLevel>,
+        /// Time until which all notifications are muted
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub muted_until: Option<Timestamp>,
+        /// Whether to ignore @everyone mentions
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub suppress_everyone: bool,
+        /// Whether to ignore role mentions
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub suppress_roles: bool,
+    }
+
+    /// Day of the week
+    pub enum Weekday {
+        Monday,
+        Tuesday,
+        Wednesday,
+        Thursday,
+        Friday,
+        Saturday,
+        Sunday,
+    }
+
+    /// Period of the day during which push notifications are held back
+    ///
+    /// Windows which end at or before the time they start run into the next day.
+    pub struct QuietHoursWindow {
+        /// Days on which the window starts
+        pub days: Vec<Weekday>,
+        /// Minute of the day at which the window starts
+        pub start: u16,
+        /// Minute of the day at which the window ends
+        pub end: u16,
+    }
+
+    /// Schedule during which push notifications are held back
+    pub struct QuietHours {
+        /// IANA time zone the schedule is in, e.g. `Europe/London`
+        pub timezone: String,
+        /// Windows making up the schedule
+        #[serde(default)]
+        pub windows: Vec<QuietHoursWindow>,
+        /// Whether to send a summary of held back notifications once a window
+        /// ends rather than dropping them
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub digest: bool,
+        /// Whether direct messages from friends are let through
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub allow_friends: bool,
+        /// Users whose direct messages are let through
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub allow_users: Vec<String>,
+    }
+
+    /// Subscription to email digests of missed activity
+    pub struct EmailDigest {
+        /// Token which unsubscribes the user, prefixed with their Id
+        pub token: String,
+        /// Time (in milliseconds since the epoch) up to which activity has been sent
+        pub last_sent: i64,
+    }
+
+    /// User's notification preferences
+    #[derive(Default)]
+    pub struct NotificationSettings {
+        /// User Id
+        #[serde(rename = "_id")]
+        pub id: String,
+        /// Preferences for servers, applying to all of their channels
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        pub servers: HashMap<String, NotificationOverride>,
+        /// Preferences for individual channels, taking precedence over their server
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        pub channels: HashMap<String, NotificationOverride>,
+        /// Schedule during which push notifications are held back
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub quiet_hours: Option<QuietHours>,
+        /// Subscription to email digests, if the user opted in
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub email_digest: Option<EmailDigest>,
+        /// Whether push notifications leave out who sent a message and what it says
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub hide_content: bool,
+    }
+);
+
+/// Why a user would be notified about a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationTrigger {
+    /// Message was sent in a channel the user is in
+    Message,
+    /// Message mentions the user
+    Mention,
+    /// Message mentions everyone in the channel
+    Everyone,
+    /// Message mentions a role the user has
+    Role,
+}
+
+/// When a push notification should be delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushDelivery {
+    /// Deliver immediately
+    Now,
+    /// Hold back until the given time and deliver as part of a digest
+    Until(DateTime<Utc>),
+    /// Don't deliver at all
+    Never,
+}
+
+impl Weekday {
+    /// Whether this is the same day as a chrono weekday
+    fn is(&self, day: chrono::Weekday) -> bool {
+        matches!(
+            (self, day),
+            (Weekday::Monday, chrono::Weekday::Mon)
+                | (Weekday::Tuesday, chrono::Weekday::Tue)
+                | (Weekday::Wednesday, chrono::Weekday::Wed)
+                | (Weekday::Thursday, chrono::Weekday::Thu)
+                | (Weekday::Friday, chrono::Weekday::Fri)
+                | (Weekday::Saturday, chrono::Weekday::Sat)
+                | (Weekday::Sunday, chrono::Weekday::Sun)
+        )
+    }
+}
+
+impl QuietHours {
+    /// Check the schedule describes real times
+    pub fn validate(&self) -> Result<()> {
+        if self.timezone.parse::<Tz>().is_err() {
+            return Err(create_error!(FailedValidation {
+                error: format!("unknown time zone {}", self.timezone)
+            }));
+        }
+
+        if self
+            .windows
+            .iter()
+            .any(|window| window.start >= 24 * 60 || window.end >= 24 * 60)
+        {
+            return Err(create_error!(FailedValidation {
+                error: "quiet hours must be given in minutes since midnight".to_string()
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Whether direct messages from a user are let through
+    pub fn allows(&self, author: &str, friend: bool) -> bool {
+        (self.allow_friends && friend) || self.allow_users.iter().any(|id| id == author)
+    }
+
+    /// Time at which the window covering the given moment ends, if any
+    pub fn window_end(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let tz: Tz = self.timezone.parse().ok()?;
+        let local = now.with_timezone(&tz).naive_local();
+        let today = local.date();
+        let yesterday = today.pred_opt()?;
+
+        let at = |date: NaiveDate, minute: u16| {
+            NaiveTime::from_hms_opt(minute as u32 / 60, minute as u32 % 60, 0)
+                .map(|time| date.and_time(time))
+        };
+
+        let mut end = None;
+        for window in &self.windows {
+            let wraps = window.end <= window.start;
+
+            // Window started today
+            if window.days.iter().any(|day| day.is(today.weekday())) {
+                let start = at(today, window.start);
+                let finish = at(if wraps { today.succ_opt()? } else { today }, window.end);
+
+                if let (Some(start), Some(finish)) = (start, finish) {
+                    if start <= local && local < finish {
+                        end = end.max(Some(finish));
+                    }
+                }
+            }
+
+            // Window started yesterday and runs into today
+            if wraps && window.days.iter().any(|day| day.is(yesterday.weekday())) {
+                if let Some(finish) = at(today, window.end) {
+                    if local < finish {
+                        end = end.max(Some(finish));
+                    }
+                }
+            }
+        }
+
+        // Clocks going forward may skip the end time entirely
+        end.and_then(|end| {
+            tz.from_local_datetime(&end).earliest().or_else(|| {
+                tz.from_local_datetime(&(end + Duration::hours(1)))
+                    .earliest()
+            })
+        })
+        .map(|end| end.with_timezone(&Utc))
+    }
+}
+
+impl NotificationSettings {
+    /// Whether the user wants to be notified about a message in a channel
+    pub fn should_notify(
+        &self,
+        server: Option<&str>,
+        channel: &str,
+        trigger: NotificationTrigger,
+    ) -> bool {
+        // Channel preferences come first as they are the most specific
+        let overrides: Vec<&NotificationOverride> = [
+            self.channels.get(channel),
+            server.and_then(|server| self.servers.get(server)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let now = Timestamp::now_utc();
+        if overrides
+            .iter()
+            .any(|o| o.muted_until.is_some_and(|until| *until > *now))
+        {
+            return false;
+        }
+
+        match trigger {
+            NotificationTrigger::Everyone if overrides.iter().any(|o| o.suppress_everyone) => {
+                return false
+            }
+            NotificationTrigger::Role if overrides.iter().any(|o| o.suppress_roles) => {
+                return false
+            }
+            _ => {}
+        }
+
+        match overrides
+            .iter()
+            .find_map(|o| o.level.as_ref())
+            .unwrap_or(&NotificationLevel::All)
+        {
+            NotificationLevel::All => true,
+            NotificationLevel::Mentions => trigger != NotificationTrigger::Message,
+            NotificationLevel::None => false,
+        }
+    }
+
+    /// Whether the user has muted a channel, either directly or through its server
+    pub fn is_muted(&self, server: Option<&str>, channel: &str) -> bool {
+        !self.should_notify(server, channel, NotificationTrigger::Mention)
+    }
+
+    /// When a push notification should be delivered given the user's
+    /// quiet hours and whether they are busy
+    ///
+    /// `direct` holds the author and whether they are a friend for
+    /// messages sent outside of a server.
+    pub fn push_delivery(
+        &self,
+        busy: bool,
+        direct: Option<(&str, bool)>,
+        now: DateTime<Utc>,
+    ) -> PushDelivery {
+        let Some(quiet_hours) = &self.quiet_hours else {
+            return if busy {
+                PushDelivery::Never
+            } else {
+                PushDelivery::Now
+            };
+        };
+
+        if direct.is_some_and(|(author, friend)| quiet_hours.allows(author, friend)) {
+            return PushDelivery::Now;
+        }
+
+        if busy {
+            return PushDelivery::Never;
+        }
+
+        match quiet_hours.window_end(now) {
+            Some(end) if quiet_hours.digest => PushDelivery::Until(end),
+            Some(_) => PushDelivery::Never,
+            None => PushDelivery::Now,
+        }
+    }
+
+    /// Apply changes to the user's preferences
+    pub fn apply(&mut self, data: v0::DataEditNotificationSettings) -> Result<()> {
+        for (id, value) in data.servers {
+            if let Some(value) = value {
+                self.servers.insert(id, value.into());
+            } else {
+                self.servers.remove(&id);
+            }
+        }
+
+        for (id, value) in data.channels {
+            if let Some(value) = value {
+                self.channels.insert(id, value.into());
+            } else {
+                self.channels.remove(&id);
+            }
+        }
+
+        if let Some(fields) = data.remove {
+            for field in fields {
+                match field {
+                    v0::FieldsNotificationSettings::QuietHours => self.quiet_hours = None,
+                }
+            }
+        }
+
+        if let Some(quiet_hours) = data.quiet_hours {
+            let quiet_hours: QuietHours = quiet_hours.into();
+            quiet_hours.validate()?;
+            self.quiet_hours = Some(quiet_hours);
+        }
+
+        match data.email_digest {
+            Some(true) if self.email_digest.is_none() => {
+                self.email_digest = Some(EmailDigest {
+                    token: format!("{}.{}", self.id, nanoid::nanoid!(32)),
+                    // Only activity from now on is sent
+                    last_sent: outbox_time(),
+                });
+            }
+            Some(false) => self.email_digest = None,
+            _ => {}
+        }
+
+        if let Some(hide_content) = data.hide_content {
+            self.hide_content = hide_content;
+        }
+
+        Ok(())
+    }
+
+    /// Unsubscribe a user from email digests using the token sent with them
+    pub async fn unsubscribe_email_digest(db: &Database, token: &str) -> Result<()> {
+        let (id, _) = token
+            .split_once('.')
+            .ok_or_else(|| create_error!(NotFound))?;
+
+        let mut settings = db.fetch_notification_settings(id).await?;
+        if settings
+            .email_digest
+            .as_ref()
+            .is_none_or(|digest| digest.token != token)
+        {
+            return Err(create_error!(NotFound));
+        }
+
+        settings.email_digest = None;
+        settings.set(db).await
+    }
+
+    /// Save the user's preferences and send them to their other sessions
+    pub async fn set(&self, db: &Database) -> Result<()> {
+        let tx = db.begin().await?;
+        tx.set_notification_settings(self).await?;
+
+        EventV1::NotificationSettingsUpdate {
+            id: self.id.to_string(),
+            settings: self.clone().into(),
+        }
+        .private_outbox(&tx, self.id.to_string())
+        .await;
+
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use iso8601_timestamp::{Duration, Timestamp};
+    use revolt_models::v0;
+
+    use crate::{
+        NotificationLevel, NotificationOverride, NotificationSettings, NotificationTrigger,
+        PushDelivery, QuietHours, QuietHoursWindow, Weekday,
+    };
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn weeknights() -> QuietHours {
+        QuietHours {
+            timezone: "Europe/London".to_string(),
+            windows: vec![QuietHoursWindow {
+                days: vec![
+                    Weekday::Monday,
+                    Weekday::Tuesday,
+                    Weekday::Wednesday,
+                    Weekday::Thursday,
+                    Weekday::Friday,
+                ],
+                start: 22 * 60,
+                end: 7 * 60,
+            }],
+            digest: true,
+            allow_friends: true,
+            allow_users: vec!["partner".to_string()],
+        }
+    }
+
+    #[test]
+    fn channel_overrides_server() {
+        let mut settings = NotificationSettings::default();
+        settings.servers.insert(
+            "server".to_string(),
+            NotificationOverride {
+                level: Some(NotificationLevel::None),
+                ..Default::default()
+            },
+        );
+        settings.channels.insert(
+            "channel".to_string(),
+            NotificationOverride {
+                level: Some(NotificationLevel::Mentions),
+                ..Default::default()
+            },
+        );
+
+        let server = Some("server");
+        assert!(!settings.should_notify(server, "other", NotificationTrigger::Mention));
+        assert!(settings.should_notify(server, "channel", NotificationTrigger::Mention));
+        assert!(!settings.should_notify(server, "channel", NotificationTrigger::Message));
+
+        // Everything else notifies by default
+        assert!(settings.should_notify(None, "dm", NotificationTrigger::Message));
+    }
+
+    #[test]
+    fn mute_until() {
+        let mut settings = NotificationSettings::default();
+        settings.servers.insert(
+            "muted".to_string(),
+            NotificationOverride {
+                muted_until: Some(Timestamp::now_utc() + Duration::hours(1)),
+                ..Default::default()
+            },
+        );
+        settings.servers.insert(
+            "expired".to_string(),
+            NotificationOverride {
+                muted_until: Some(Timestamp::now_utc() - Duration::hours(1)),
+                ..Default::default()
+            },
+        );
+
+        assert!(!settings.should_notify(Some("muted"), "channel", NotificationTrigger::Mention));
+        assert!(settings.should_notify(Some("expired"), "channel", NotificationTrigger::Mention));
+    }
+
+    #[test]
+    fn suppress_mass_mentions() {
+        let mut settings = NotificationSettings::default();
+        settings.servers.insert(
+            "server".to_string(),
+            NotificationOverride {
+                suppress_everyone: true,
+                ..Default::default()
+            },
+        );
+
+        let server = Some("server");
+        assert!(!settings.should_notify(server, "channel", NotificationTrigger::Everyone));
+        assert!(settings.should_notify(server, "channel", NotificationTrigger::Role));
+        assert!(settings.should_notify(server, "channel", NotificationTrigger::Mention));
+    }
+
+    #[test]
+    fn quiet_hours_window() {
+        let quiet_hours = weeknights();
+
+        // Friday 2026-10-16 22:00 BST until Saturday 07:00 BST
+        assert_eq!(quiet_hours.window_end(at("2026-10-16T20:59:00Z")), None);
+        assert_eq!(
+            quiet_hours.window_end(at("2026-10-16T21:00:00Z")),
+            Some(at("2026-10-17T06:00:00Z"))
+        );
+        assert_eq!(
+            quiet_hours.window_end(at("2026-10-17T05:59:00Z")),
+            Some(at("2026-10-17T06:00:00Z"))
+        );
+        assert_eq!(quiet_hours.window_end(at("2026-10-17T06:00:00Z")), None);
+
+        // Nothing starts on a Saturday night
+        assert_eq!(quiet_hours.window_end(at("2026-10-17T22:00:00Z")), None);
+
+        // Time zone offsets follow daylight saving time
+        assert_eq!(
+            quiet_hours.window_end(at("2026-11-02T22:30:00Z")),
+            Some(at("2026-11-03T07:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn quiet_hours_validation() {
+        assert!(weeknights().validate().is_ok());
+
+        let mut quiet_hours = weeknights();
+        quiet_hours.timezone = "Mars/Olympus_Mons".to_string();
+        assert!(quiet_hours.validate().is_err());
+
+        let mut quiet_hours = weeknights();
+        quiet_hours.windows[0].end = 24 * 60;
+        assert!(quiet_hours.validate().is_err());
+    }
+
+    #[test]
+    fn push_delivery() {
+        let quiet = at("2026-10-16T23:00:00Z");
+        let awake = at("2026-10-16T12:00:00Z");
+        let end = at("2026-10-17T06:00:00Z");
+
+        let mut settings = NotificationSettings::default();
+        assert_eq!(
+            settings.push_delivery(false, None, quiet),
+            PushDelivery::Now
+        );
+        assert_eq!(
+            settings.push_delivery(true, None, awake),
+            PushDelivery::Never
+        );
+
+        settings.quiet_hours = Some(weeknights());
+        assert_eq!(
+            settings.push_delivery(false, None, awake),
+            PushDelivery::Now
+        );
+        assert_eq!(
+            settings.push_delivery(false, None, quiet),
+            PushDelivery::Until(end)
+        );
+        assert_eq!(
+            settings.push_delivery(true, None, awake),
+            PushDelivery::Never
+        );
+
+        // Exceptions only apply to direct messages
+        assert_eq!(
+            settings.push_delivery(false, Some(("friend", true)), quiet),
+            PushDelivery::Now
+        );
+        assert_eq!(
+            settings.push_delivery(true, Some(("partner", false)), awake),
+            PushDelivery::Now
+        );
+        assert_eq!(
+            settings.push_delivery(false, Some(("stranger", false)), quiet),
+            PushDelivery::Until(end)
+        );
+
+        settings.quiet_hours.as_mut().unwrap().digest = false;
+        assert_eq!(
+            settings.push_delivery(false, None, quiet),
+            PushDelivery::Never
+        );
+    }
+
+    #[async_std::test]
+    async fn crud() {
+        database_test!(|db| async move {
+            let mut settings = NotificationSettings {
+                id: "user".to_string(),
+                ..Default::default()
+            };
+
+            settings.channels.insert(
+                "channel".to_string(),
+                NotificationOverride {
+                    level: Some(NotificationLevel::None),
+                    ..Default::default()
+                },
+            );
+            settings.quiet_hours = Some(weeknights());
+
+            settings.set(&db).await.unwrap();
+            assert_eq!(
+                db.fetch_notification_settings("user").await.unwrap(),
+                settings
+            );
+
+            settings.channels.clear();
+            settings.set(&db).await.unwrap();
+            assert_eq!(
+                db.fetch_notification_settings("user").await.unwrap(),
+                settings
+            );
+
+            assert_eq!(
+                db.fetch_many_notification_settings(&["user".to_string(), "other".to_string()])
+                    .await
+                    .unwrap(),
+                vec![settings]
+            );
+
+            // Users without any preferences have the defaults
+            assert_eq!(
+                db.fetch_notification_settings("other").await.unwrap(),
+                NotificationSettings {
+                    id: "other".to_string(),
+                    ..Default::default()
+                }
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn hide_content() {
+        database_test!(|db| async move {
+            let mut settings = NotificationSettings {
+                id: "user".to_string(),
+                ..Default::default()
+            };
+
+            settings
+                .apply(v0::DataEditNotificationSettings {
+                    hide_content: Some(true),
+                    ..Default::default()
+                })
+                .unwrap();
+            settings.set(&db).await.unwrap();
+            assert!(
+                db.fetch_notification_settings("user")
+                    .await
+                    .unwrap()
+                    .hide_content
+            );
+
+            // Leaving the field out keeps the current choice
+            settings
+                .apply(v0::DataEditNotificationSettings::default())
+                .unwrap();
+            assert!(settings.hide_content);
+
+            settings
+                .apply(v0::DataEditNotificationSettings {
+                    hide_content: Some(false),
+                    ..Default::default()
+                })
+                .unwrap();
+            settings.set(&db).await.unwrap();
+            assert!(
+                !db.fetch_notification_settings("user")
+                    .await
+                    .unwrap()
+                    .hide_content
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn email_digest_subscription() {
+        database_test!(|db| async move {
+            let mut settings = NotificationSettings {
+                id: "user".to_string(),
+                ..Default::default()
+            };
+
+            settings
+                .apply(v0::DataEditNotificationSettings {
+                    email_digest: Some(true),
+                    ..Default::default()
+                })
+                .unwrap();
+            settings.set(&db).await.unwrap();
+
+            let token = settings.email_digest.as_ref().unwrap().token.clone();
+            assert!(token.starts_with("user."));
+
+            // Only one sender can claim a digest window
+            let previous = settings.email_digest.as_ref().unwrap().last_sent;
+            assert!(db.claim_email_digest("user", previous, 1234).await.unwrap());
+            assert!(!db.claim_email_digest("user", previous, 5678).await.unwrap());
+            let subscribers = db.fetch_email_digest_subscribers().await.unwrap();
+            assert_eq!(subscribers.len(), 1);
+            assert_eq!(
+                subscribers[0].email_digest.as_ref().unwrap().last_sent,
+                1234
+            );
+
+            // Tokens are only accepted for the user they were issued to
+            assert!(
+                NotificationSettings::unsubscribe_email_digest(&db, "user.wrong")
+                    .await
+                    .is_err()
+            );
+            assert!(
+                NotificationSettings::unsubscribe_email_digest(&db, "other.token")
+                    .await
+                    .is_err()
+            );
+
+            NotificationSettings::unsubscribe_email_digest(&db, &token)
+                .await
+                .unwrap();
+            assert!(db
+                .fetch_email_digest_subscribers()
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+}
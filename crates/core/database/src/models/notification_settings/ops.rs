@@ -0,0 +1,36 @@
+use revolt_result::Result;
+
+use crate::NotificationSettings;
+
+mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[async_trait]
+pub trait AbstractNotificationSettings: Sync + Send {
+    /// Fetch a user's notification preferences, defaulting to none
+    async fn fetch_notification_settings(&self, id: &str) -> Result<NotificationSettings>;
+
+    /// Fetch the notification preferences of any of the given users which have some
+    async fn fetch_many_notification_settings(
+        &self,
+        ids: &[String],
+    ) -> Result<Vec<NotificationSettings>>;
+
+    /// Replace a user's notification preferences
+    async fn set_notification_settings(&self, settings: &NotificationSettings) -> Result<()>;
+
+    /// Delete a user's notification preferences
+    async fn delete_notification_settings(&self, id: &str) -> Result<()>;
+
+    /// Fetch the notification preferences of every user subscribed to email digests
+    async fn fetch_email_digest_subscribers(&self) -> Result<Vec<NotificationSettings>>;
+
+    /// Move the time up to which activity has been sent in a user's email digests
+    ///
+    /// Only applies if it is still `previous`, returns whether it did.
+    async fn claim_email_digest(&self, id: &str, previous: i64, last_sent: i64) -> Result<bool>;
+}
@@ -0,0 +1,92 @@
+use mongodb::options::ReplaceOptions;
+use revolt_result::Result;
+
+use crate::{MongoDb, NotificationSettings};
+
+use super::AbstractNotificationSettings;
+
+static COL: &str = "notification_settings";
+
+#[async_trait]
+impl AbstractNotificationSettings for MongoDb {
+    /// Fetch a user's notification preferences, defaulting to none
+    async fn fetch_notification_settings(&self, id: &str) -> Result<NotificationSettings> {
+        Ok(
+            query!(self, find_one_by_id, COL, id)?.unwrap_or_else(|| NotificationSettings {
+                id: id.to_string(),
+                ..Default::default()
+            }),
+        )
+    }
+
+    /// Fetch the notification preferences of any of the given users which have some
+    async fn fetch_many_notification_settings(
+        &self,
+        ids: &[String],
+    ) -> Result<Vec<NotificationSettings>> {
+        query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "_id": {
+                    "$in": ids
+                }
+            }
+        )
+    }
+
+    /// Replace a user's notification preferences
+    async fn set_notification_settings(&self, settings: &NotificationSettings) -> Result<()> {
+        self.col::<NotificationSettings>(COL)
+            .replace_one(
+                doc! {
+                    "_id": &settings.id
+                },
+                settings,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("replace_one", COL))
+    }
+
+    /// Delete a user's notification preferences
+    async fn delete_notification_settings(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Fetch the notification preferences of every user subscribed to email digests
+    async fn fetch_email_digest_subscribers(&self) -> Result<Vec<NotificationSettings>> {
+        query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "email_digest": {
+                    "$exists": true
+                }
+            }
+        )
+    }
+
+    /// Move the time up to which activity has been sent in a user's email digests
+    async fn claim_email_digest(&self, id: &str, previous: i64, last_sent: i64) -> Result<bool> {
+        self.col::<NotificationSettings>(COL)
+            .update_one(
+                doc! {
+                    "_id": id,
+                    "email_digest.last_sent": previous
+                },
+                doc! {
+                    "$set": {
+                        "email_digest.last_sent": last_sent
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|result| result.matched_count == 1)
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+}
@@ -0,0 +1,69 @@
+use revolt_result::Result;
+use serde_json::json;
+use sqlx::QueryBuilder;
+
+use crate::{NotificationSettings, PostgresDb};
+
+use super::AbstractNotificationSettings;
+
+static COL: &str = "notification_settings";
+
+#[async_trait]
+impl AbstractNotificationSettings for PostgresDb {
+    /// Fetch a user's notification preferences, defaulting to none
+    async fn fetch_notification_settings(&self, id: &str) -> Result<NotificationSettings> {
+        Ok(
+            query!(self, find_one_by_id, COL, id)?.unwrap_or_else(|| NotificationSettings {
+                id: id.to_string(),
+                ..Default::default()
+            }),
+        )
+    }
+
+    /// Fetch the notification preferences of any of the given users which have some
+    async fn fetch_many_notification_settings(
+        &self,
+        ids: &[String],
+    ) -> Result<Vec<NotificationSettings>> {
+        query!(self, find_in, COL, "data->>'_id'", ids)
+    }
+
+    /// Replace a user's notification preferences
+    async fn set_notification_settings(&self, settings: &NotificationSettings) -> Result<()> {
+        self.upsert_one(COL, json!(settings.id), |document| {
+            *document = json!(settings);
+        })
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete a user's notification preferences
+    async fn delete_notification_settings(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Fetch the notification preferences of every user subscribed to email digests
+    async fn fetch_email_digest_subscribers(&self) -> Result<Vec<NotificationSettings>> {
+        self.find_by_query(QueryBuilder::new(format!(
+            "SELECT data FROM {COL} WHERE data ? 'email_digest'"
+        )))
+        .await
+        .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Move the time up to which activity has been sent in a user's email digests
+    async fn claim_email_digest(&self, id: &str, previous: i64, last_sent: i64) -> Result<bool> {
+        self.modify_one(
+            COL,
+            json!({
+                "_id": id,
+                "email_digest": {
+                    "last_sent": previous
+                }
+            }),
+            |document| document["email_digest"]["last_sent"] = json!(last_sent),
+        )
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+}
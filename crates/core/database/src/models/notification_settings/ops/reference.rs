@@ -0,0 +1,70 @@
+use revolt_result::Result;
+
+use super::AbstractNotificationSettings;
+use crate::{NotificationSettings, ReferenceDb};
+
+#[async_trait]
+impl AbstractNotificationSettings for ReferenceDb {
+    /// Fetch a user's notification preferences, defaulting to none
+    async fn fetch_notification_settings(&self, id: &str) -> Result<NotificationSettings> {
+        let notification_settings = self.notification_settings.lock().await;
+        Ok(notification_settings
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| NotificationSettings {
+                id: id.to_string(),
+                ..Default::default()
+            }))
+    }
+
+    /// Fetch the notification preferences of any of the given users which have some
+    async fn fetch_many_notification_settings(
+        &self,
+        ids: &[String],
+    ) -> Result<Vec<NotificationSettings>> {
+        let notification_settings = self.notification_settings.lock().await;
+        Ok(ids
+            .iter()
+            .filter_map(|id| notification_settings.get(id).cloned())
+            .collect())
+    }
+
+    /// Replace a user's notification preferences
+    async fn set_notification_settings(&self, settings: &NotificationSettings) -> Result<()> {
+        let mut notification_settings = self.notification_settings.lock().await;
+        notification_settings.insert(settings.id.to_string(), settings.clone());
+        Ok(())
+    }
+
+    /// Delete a user's notification preferences
+    async fn delete_notification_settings(&self, id: &str) -> Result<()> {
+        let mut notification_settings = self.notification_settings.lock().await;
+        notification_settings.remove(id);
+        Ok(())
+    }
+
+    /// Fetch the notification preferences of every user subscribed to email digests
+    async fn fetch_email_digest_subscribers(&self) -> Result<Vec<NotificationSettings>> {
+        let notification_settings = self.notification_settings.lock().await;
+        Ok(notification_settings
+            .values()
+            .filter(|settings| settings.email_digest.is_some())
+            .cloned()
+            .collect())
+    }
+
+    /// Move the time up to which activity has been sent in a user's email digests
+    async fn claim_email_digest(&self, id: &str, previous: i64, last_sent: i64) -> Result<bool> {
+        let mut notification_settings = self.notification_settings.lock().await;
+        match notification_settings
+            .get_mut(id)
+            .and_then(|settings| settings.email_digest.as_mut())
+        {
+            Some(digest) if digest.last_sent == previous => {
+                digest.last_sent = last_sent;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use revolt_result::Result;
+use ulid::Ulid;
+
+use crate::{events::client::EventV1, tasks, Database};
+
+/// Event waiting to be published by the outbox relay
+///
+/// Events are written in the same transaction as the change they
+/// describe, so they are published if and only if the change commits.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEvent {
+    /// Unique Id, also used by subscribers to de-duplicate the event
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Topics to publish the event to
+    pub topics: Vec<String>,
+    /// Event to publish
+    pub event: EventV1,
+    /// Time (in milliseconds since the epoch) until which a relay has claimed this event
+    #[serde(default)]
+    pub claimed_until: i64,
+    /// Id of the claim currently held on this event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim: Option<String>,
+}
+
+/// Current time in milliseconds since the epoch
+pub fn outbox_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_millis() as i64
+}
+
+#[allow(clippy::disallowed_methods)]
+impl OutboxEvent {
+    /// Create a new unclaimed event
+    pub fn new(event: EventV1, topics: Vec<String>) -> OutboxEvent {
+        OutboxEvent {
+            id: Ulid::new().to_string(),
+            topics,
+            event,
+            claimed_until: 0,
+            claim: None,
+        }
+    }
+
+    /// Write an event to the outbox and wake up the relay
+    pub async fn create(db: &Database, event: EventV1, topics: Vec<String>) -> Result<()> {
+        db.insert_outbox_event(&OutboxEvent::new(event, topics))
+            .await?;
+
+        tasks::outbox::wake();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{events::client::EventV1, OutboxEvent};
+
+    #[async_std::test]
+    async fn claim_in_order() {
+        database_test!(|db| async move {
+            OutboxEvent::create(&db, EventV1::Logout, vec!["a".to_string()])
+                .await
+                .unwrap();
+            OutboxEvent::create(&db, EventV1::Authenticated, vec!["b".to_string()])
+                .await
+                .unwrap();
+
+            let claimed = db
+                .claim_outbox_events(1, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+            assert_eq!(claimed[0].topics, vec!["a".to_string()]);
+
+            // Claimed events are not handed out again until the lease expires
+            let claimed = db
+                .claim_outbox_events(10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+            assert_eq!(claimed[0].topics, vec!["b".to_string()]);
+
+            assert!(db
+                .claim_outbox_events(10, Duration::from_secs(60))
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[async_std::test]
+    async fn publish_on_commit() {
+        database_test!(|db| async move {
+            let tx = db.begin().await.unwrap();
+            EventV1::Logout.p_outbox(&tx, "a".to_string()).await;
+            drop(tx);
+
+            assert!(db
+                .claim_outbox_events(10, Duration::from_secs(60))
+                .await
+                .unwrap()
+                .is_empty());
+
+            let tx = db.begin().await.unwrap();
+            EventV1::Logout.p_outbox(&tx, "a".to_string()).await;
+            tx.commit().await.unwrap();
+
+            let claimed = db
+                .claim_outbox_events(10, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert_eq!(claimed.len(), 1);
+            assert_eq!(claimed[0].event, EventV1::Logout);
+        });
+    }
+
+    #[async_std::test]
+    async fn retry_until_deleted() {
+        database_test!(|db| async move {
+            OutboxEvent::create(&db, EventV1::Logout, vec!["a".to_string()])
+                .await
+                .unwrap();
+
+            let claimed = db.claim_outbox_events(10, Duration::ZERO).await.unwrap();
+            assert_eq!(claimed.len(), 1);
+
+            // The lease expired without the event being deleted
+            async_std::task::sleep(Duration::from_millis(5)).await;
+            let retried = db.claim_outbox_events(10, Duration::ZERO).await.unwrap();
+            assert_eq!(retried.len(), 1);
+            assert_eq!(retried[0].id, claimed[0].id);
+
+            db.delete_outbox_events(&[claimed[0].id.clone()])
+                .await
+                .unwrap();
+
+            async_std::task::sleep(Duration::from_millis(5)).await;
+            assert!(db
+                .claim_outbox_events(10, Duration::ZERO)
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+}
@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+
+use crate::OutboxEvent;
+
+mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[async_trait]
+pub trait AbstractOutboxEvents: Sync + Send {
+    /// Insert a new event into the outbox
+    async fn insert_outbox_event(&self, event: &OutboxEvent) -> Result<()>;
+
+    /// Claim the oldest unclaimed events for the given lease
+    ///
+    /// Events whose lease has expired without being deleted are claimed again.
+    async fn claim_outbox_events(&self, limit: i64, lease: Duration) -> Result<Vec<OutboxEvent>>;
+
+    /// Delete published events from the outbox
+    async fn delete_outbox_events(&self, ids: &[String]) -> Result<()>;
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use bson::Document;
+use mongodb::options::FindOptions;
+use revolt_result::Result;
+use ulid::Ulid;
+
+use crate::{outbox_time, DocumentId, MongoDb, OutboxEvent};
+
+use super::AbstractOutboxEvents;
+
+static COL: &str = "outbox_events";
+
+#[async_trait]
+impl AbstractOutboxEvents for MongoDb {
+    /// Insert a new event into the outbox
+    async fn insert_outbox_event(&self, event: &OutboxEvent) -> Result<()> {
+        query!(self, insert_one, COL, &event).map(|_| ())
+    }
+
+    /// Claim the oldest unclaimed events for the given lease
+    async fn claim_outbox_events(&self, limit: i64, lease: Duration) -> Result<Vec<OutboxEvent>> {
+        let now = outbox_time();
+        let ids = self
+            .find_with_options::<_, DocumentId>(
+                COL,
+                doc! {
+                    "claimed_until": {
+                        "$lt": now
+                    }
+                },
+                FindOptions::builder()
+                    .projection(doc! { "_id": 1_i32 })
+                    .sort(doc! { "_id": 1_i32 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await
+            .map_err(|_| create_database_error!("find", COL))?
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<String>>();
+
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Another relay may claim some of these first, so only
+        // return the events which were tagged with our claim.
+        let claim = Ulid::new().to_string();
+        self.col::<Document>(COL)
+            .update_many(
+                doc! {
+                    "_id": {
+                        "$in": ids
+                    },
+                    "claimed_until": {
+                        "$lt": now
+                    }
+                },
+                doc! {
+                    "$set": {
+                        "claimed_until": now + lease.as_millis() as i64,
+                        "claim": &claim
+                    }
+                },
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("update_many", COL))?;
+
+        self.find_with_options(
+            COL,
+            doc! {
+                "claim": claim
+            },
+            FindOptions::builder().sort(doc! { "_id": 1_i32 }).build(),
+        )
+        .await
+        .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Delete published events from the outbox
+    async fn delete_outbox_events(&self, ids: &[String]) -> Result<()> {
+        self.col::<Document>(COL)
+            .delete_many(
+                doc! {
+                    "_id": {
+                        "$in": ids
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
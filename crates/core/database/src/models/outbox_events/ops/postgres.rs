@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+use sqlx::types::Json;
+use ulid::Ulid;
+
+use super::AbstractOutboxEvents;
+use crate::{outbox_time, OutboxEvent, PostgresDb};
+
+static COL: &str = "outbox_events";
+
+#[async_trait]
+impl AbstractOutboxEvents for PostgresDb {
+    /// Insert a new event into the outbox
+    async fn insert_outbox_event(&self, event: &OutboxEvent) -> Result<()> {
+        query!(self, insert_one, COL, &event)
+    }
+
+    /// Claim the oldest unclaimed events for the given lease
+    async fn claim_outbox_events(&self, limit: i64, lease: Duration) -> Result<Vec<OutboxEvent>> {
+        let now = outbox_time();
+
+        // Rows being claimed by another relay are skipped rather than waited on.
+        let mut events = sqlx::query_scalar::<_, Json<OutboxEvent>>(
+            r#"UPDATE outbox_events
+               SET data = data || jsonb_build_object('claimed_until', $2::bigint, 'claim', $3::text)
+               WHERE id IN (
+                   SELECT id FROM outbox_events
+                   WHERE (data->>'claimed_until')::bigint < $1
+                   ORDER BY (data->>'_id') COLLATE "C"
+                   LIMIT $4
+                   FOR UPDATE SKIP LOCKED
+               )
+               RETURNING data"#,
+        )
+        .bind(now)
+        .bind(now + lease.as_millis() as i64)
+        .bind(Ulid::new().to_string())
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?
+        .into_iter()
+        .map(|Json(event)| event)
+        .collect::<Vec<OutboxEvent>>();
+
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(events)
+    }
+
+    /// Delete published events from the outbox
+    async fn delete_outbox_events(&self, ids: &[String]) -> Result<()> {
+        sqlx::query("DELETE FROM outbox_events WHERE data->>'_id' = ANY($1)")
+            .bind(ids)
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+use ulid::Ulid;
+
+use super::AbstractOutboxEvents;
+use crate::{outbox_time, OutboxEvent, ReferenceDb};
+
+#[async_trait]
+impl AbstractOutboxEvents for ReferenceDb {
+    /// Insert a new event into the outbox
+    async fn insert_outbox_event(&self, event: &OutboxEvent) -> Result<()> {
+        let mut outbox_events = self.outbox_events.lock().await;
+        if outbox_events.contains_key(&event.id) {
+            Err(create_database_error!("insert", "outbox_event"))
+        } else {
+            outbox_events.insert(event.id.to_string(), event.clone());
+            Ok(())
+        }
+    }
+
+    /// Claim the oldest unclaimed events for the given lease
+    async fn claim_outbox_events(&self, limit: i64, lease: Duration) -> Result<Vec<OutboxEvent>> {
+        let mut outbox_events = self.outbox_events.lock().await;
+        let now = outbox_time();
+        let claim = Ulid::new().to_string();
+
+        let mut events: Vec<&mut OutboxEvent> = outbox_events
+            .values_mut()
+            .filter(|event| event.claimed_until < now)
+            .collect();
+
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(events
+            .into_iter()
+            .take(limit as usize)
+            .map(|event| {
+                event.claimed_until = now + lease.as_millis() as i64;
+                event.claim = Some(claim.clone());
+                event.clone()
+            })
+            .collect())
+    }
+
+    /// Delete published events from the outbox
+    async fn delete_outbox_events(&self, ids: &[String]) -> Result<()> {
+        let mut outbox_events = self.outbox_events.lock().await;
+        for id in ids {
+            outbox_events.remove(id);
+        }
+
+        Ok(())
+    }
+}
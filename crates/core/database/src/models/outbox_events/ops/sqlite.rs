@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use revolt_result::Result;
+use ulid::Ulid;
+
+use super::AbstractOutboxEvents;
+use crate::{outbox_time, OutboxEvent, SqliteDb};
+
+static COL: &str = "outbox_events";
+
+#[async_trait]
+impl AbstractOutboxEvents for SqliteDb {
+    /// Insert a new event into the outbox
+    async fn insert_outbox_event(&self, event: &OutboxEvent) -> Result<()> {
+        query!(self, insert_one, COL, &event)
+    }
+
+    /// Claim the oldest unclaimed events for the given lease
+    async fn claim_outbox_events(&self, limit: i64, lease: Duration) -> Result<Vec<OutboxEvent>> {
+        let now = outbox_time();
+        let mut events = sqlx::query_scalar::<_, String>(
+            "UPDATE outbox_events
+             SET data = json_set(data, '$.claimed_until', ?, '$.claim', ?)
+             WHERE id IN (
+                 SELECT id FROM outbox_events
+                 WHERE json_extract(data, '$.claimed_until') < ?
+                 ORDER BY id
+                 LIMIT ?
+             )
+             RETURNING data",
+        )
+        .bind(now + lease.as_millis() as i64)
+        .bind(Ulid::new().to_string())
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?
+        .into_iter()
+        .filter_map(|event| serde_json::from_str(&event).ok())
+        .collect::<Vec<OutboxEvent>>();
+
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(events)
+    }
+
+    /// Delete published events from the outbox
+    async fn delete_outbox_events(&self, ids: &[String]) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM outbox_events
+             WHERE json_extract(data, '$._id') IN (SELECT value FROM json_each(?))",
+        )
+        .bind(serde_json::to_string(ids).expect("strings always serialise"))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
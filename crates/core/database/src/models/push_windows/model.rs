@@ -0,0 +1,24 @@
+use revolt_models::v0::PushNotification;
+
+use crate::ChannelCompositeKey;
+
+auto_derived!(
+    /// Push notifications being coalesced for a user in a channel
+    ///
+    /// The notification which opens a window is sent straight away, any which
+    /// arrive before it closes are held back and summarised in one notification.
+    pub struct PushWindow {
+        /// Channel and user the window belongs to
+        #[serde(rename = "_id")]
+        pub id: ChannelCompositeKey,
+        /// Time (in milliseconds since the epoch) at which the window closes
+        pub closes_at: i64,
+        /// Number of messages notified about since the first window opened
+        pub count: i64,
+        /// Number of notifications held back until the window closes
+        pub pending: i64,
+        /// Latest notification held back
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub latest: Option<PushNotification>,
+    }
+);
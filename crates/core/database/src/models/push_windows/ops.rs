@@ -0,0 +1,42 @@
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+
+use crate::PushWindow;
+
+mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[async_trait]
+pub trait AbstractPushWindows: Sync + Send {
+    /// Hold a notification back for each of the given users with a window open in a channel
+    ///
+    /// Windows closing at `closes_at` are opened for the other users, replacing
+    /// any which should have closed before `stale_before`. Returns the users
+    /// whose windows were opened, who should be notified straight away.
+    async fn hold_push_notification(
+        &self,
+        channel: &str,
+        users: &[String],
+        notification: &PushNotification,
+        closes_at: i64,
+        stale_before: i64,
+    ) -> Result<Vec<String>>;
+
+    /// Close the windows of the given users in a channel
+    ///
+    /// Windows which held notifications back count them and stay open until
+    /// `closes_at`, the rest are removed. Returns the windows which stayed open.
+    async fn close_push_windows(
+        &self,
+        channel: &str,
+        users: &[String],
+        closes_at: i64,
+    ) -> Result<Vec<PushWindow>>;
+
+    /// Remove a user's window in a channel, discarding any notifications it held back
+    async fn delete_push_window(&self, channel: &str, user: &str) -> Result<()>;
+}
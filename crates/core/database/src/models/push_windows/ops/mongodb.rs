@@ -0,0 +1,192 @@
+use bson::Document;
+use mongodb::{error::ErrorKind, options::InsertManyOptions};
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+use ulid::Ulid;
+
+use crate::{ChannelCompositeKey, MongoDb, PushWindow};
+
+use super::AbstractPushWindows;
+
+static COL: &str = "push_windows";
+
+#[async_trait]
+impl AbstractPushWindows for MongoDb {
+    /// Hold a notification back for each of the given users with a window open in a channel
+    async fn hold_push_notification(
+        &self,
+        channel: &str,
+        users: &[String],
+        notification: &PushNotification,
+        closes_at: i64,
+        stale_before: i64,
+    ) -> Result<Vec<String>> {
+        let mut users = users.to_vec();
+        users.sort();
+        users.dedup();
+
+        self.col::<Document>(COL)
+            .delete_many(
+                doc! {
+                    "_id.channel": channel,
+                    "_id.user": {
+                        "$in": users.as_slice()
+                    },
+                    "closes_at": {
+                        "$lt": stale_before
+                    }
+                },
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("delete_many", COL))?;
+
+        // Opening fails on the duplicate id for users who already have a window.
+        let windows: Vec<PushWindow> = users
+            .iter()
+            .map(|user| PushWindow {
+                id: ChannelCompositeKey {
+                    channel: channel.to_string(),
+                    user: user.to_string(),
+                },
+                closes_at,
+                count: 1,
+                pending: 0,
+                latest: None,
+            })
+            .collect();
+
+        let existing: Vec<usize> = match self
+            .col::<PushWindow>(COL)
+            .insert_many(windows, InsertManyOptions::builder().ordered(false).build())
+            .await
+        {
+            Ok(_) => vec![],
+            Err(err) => match &*err.kind {
+                ErrorKind::BulkWrite(failure)
+                    if failure.write_concern_error.is_none()
+                        && failure
+                            .write_errors
+                            .iter()
+                            .flatten()
+                            .all(|err| err.code == 11000) =>
+                {
+                    failure
+                        .write_errors
+                        .iter()
+                        .flatten()
+                        .map(|err| err.index)
+                        .collect()
+                }
+                _ => return Err(create_database_error!("insert_many", COL)),
+            },
+        };
+
+        let (held, opened): (Vec<(usize, &String)>, Vec<(usize, &String)>) = users
+            .iter()
+            .enumerate()
+            .partition(|(index, _)| existing.contains(index));
+
+        if !held.is_empty() {
+            let held: Vec<String> = held.into_iter().map(|(_, user)| user.clone()).collect();
+            self.col::<Document>(COL)
+                .update_many(
+                    doc! {
+                        "_id.channel": channel,
+                        "_id.user": {
+                            "$in": held
+                        }
+                    },
+                    doc! {
+                        "$inc": {
+                            "pending": 1_i64
+                        },
+                        "$set": {
+                            "latest": bson::to_bson(notification)
+                                .map_err(|_| create_database_error!("to_bson", COL))?
+                        }
+                    },
+                    None,
+                )
+                .await
+                .map_err(|_| create_database_error!("update_many", COL))?;
+        }
+
+        Ok(opened.into_iter().map(|(_, user)| user.clone()).collect())
+    }
+
+    /// Close the windows of the given users in a channel
+    async fn close_push_windows(
+        &self,
+        channel: &str,
+        users: &[String],
+        closes_at: i64,
+    ) -> Result<Vec<PushWindow>> {
+        self.col::<Document>(COL)
+            .delete_many(
+                doc! {
+                    "_id.channel": channel,
+                    "_id.user": {
+                        "$in": users
+                    },
+                    "pending": 0_i64
+                },
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("delete_many", COL))?;
+
+        // Mark the windows being closed so exactly those can be returned.
+        let closing = Ulid::new().to_string();
+        self.col::<Document>(COL)
+            .update_many(
+                doc! {
+                    "_id.channel": channel,
+                    "_id.user": {
+                        "$in": users
+                    },
+                    "pending": {
+                        "$gt": 0_i64
+                    }
+                },
+                vec![doc! {
+                    "$set": {
+                        "closes_at": closes_at,
+                        "count": {
+                            "$add": ["$count", "$pending"]
+                        },
+                        "pending": 0_i64,
+                        "closing": closing.as_str()
+                    }
+                }],
+                None,
+            )
+            .await
+            .map_err(|_| create_database_error!("update_many", COL))?;
+
+        query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "_id.channel": channel,
+                "closing": closing
+            }
+        )
+    }
+
+    /// Remove a user's window in a channel, discarding any notifications it held back
+    async fn delete_push_window(&self, channel: &str, user: &str) -> Result<()> {
+        self.col::<Document>(COL)
+            .delete_one(
+                doc! {
+                    "_id.channel": channel,
+                    "_id.user": user
+                },
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_one", COL))
+    }
+}
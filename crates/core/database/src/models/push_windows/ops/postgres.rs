@@ -0,0 +1,114 @@
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+use serde_json::json;
+use sqlx::types::Json;
+
+use crate::{PostgresDb, PushWindow};
+
+use super::AbstractPushWindows;
+
+static COL: &str = "push_windows";
+
+#[async_trait]
+impl AbstractPushWindows for PostgresDb {
+    /// Hold a notification back for each of the given users with a window open in a channel
+    async fn hold_push_notification(
+        &self,
+        channel: &str,
+        users: &[String],
+        notification: &PushNotification,
+        closes_at: i64,
+        stale_before: i64,
+    ) -> Result<Vec<String>> {
+        // Windows are only ever opened with nothing held back.
+        let windows = sqlx::query_as::<_, (String, bool)>(
+            "INSERT INTO push_windows (id, data)
+             SELECT key, jsonb_build_object(
+                 '_id', key, 'closes_at', $3::bigint, 'count', 1, 'pending', 0
+             )
+             FROM (
+                SELECT DISTINCT jsonb_build_object('channel', $1::text, 'user', recipient) AS key
+                FROM unnest($2::text[]) AS recipient
+             ) AS keys
+             ON CONFLICT (id) DO UPDATE SET data = CASE
+                 WHEN (push_windows.data->>'closes_at')::bigint < $5 THEN EXCLUDED.data
+                 ELSE push_windows.data || jsonb_build_object(
+                     'pending', (push_windows.data->>'pending')::bigint + 1,
+                     'latest', $4::jsonb
+                 )
+             END
+             RETURNING data->'_id'->>'user', (data->>'pending')::bigint = 0",
+        )
+        .bind(channel)
+        .bind(users)
+        .bind(closes_at)
+        .bind(Json(notification))
+        .bind(stale_before)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?;
+
+        Ok(windows
+            .into_iter()
+            .filter(|(_, opened)| *opened)
+            .map(|(user, _)| user)
+            .collect())
+    }
+
+    /// Close the windows of the given users in a channel
+    async fn close_push_windows(
+        &self,
+        channel: &str,
+        users: &[String],
+        closes_at: i64,
+    ) -> Result<Vec<PushWindow>> {
+        sqlx::query(
+            "DELETE FROM push_windows
+             WHERE data->'_id'->>'channel' = $1
+               AND data->'_id'->>'user' = ANY($2)
+               AND (data->>'pending')::bigint = 0",
+        )
+        .bind(channel)
+        .bind(users)
+        .execute(&self.0)
+        .await
+        .map_err(|_| create_database_error!("delete_many", COL))?;
+
+        // Windows opened again since being removed above are left to close on their own.
+        sqlx::query_scalar::<_, Json<PushWindow>>(
+            "UPDATE push_windows
+             SET data = data || jsonb_build_object(
+                 'closes_at', $3::bigint,
+                 'count', (data->>'count')::bigint + (data->>'pending')::bigint,
+                 'pending', 0
+             )
+             WHERE data->'_id'->>'channel' = $1
+               AND data->'_id'->>'user' = ANY($2)
+               AND (data->>'pending')::bigint > 0
+             RETURNING data",
+        )
+        .bind(channel)
+        .bind(users)
+        .bind(closes_at)
+        .fetch_all(&self.0)
+        .await
+        .map(|windows| windows.into_iter().map(|Json(window)| window).collect())
+        .map_err(|_| create_database_error!("update_many", COL))
+    }
+
+    /// Remove a user's window in a channel, discarding any notifications it held back
+    async fn delete_push_window(&self, channel: &str, user: &str) -> Result<()> {
+        query!(
+            self,
+            delete_one,
+            COL,
+            json!({
+                "_id": {
+                    "channel": channel,
+                    "user": user
+                }
+            })
+        )
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,98 @@
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+
+use crate::{ChannelCompositeKey, PushWindow, ReferenceDb};
+
+use super::AbstractPushWindows;
+
+#[async_trait]
+impl AbstractPushWindows for ReferenceDb {
+    /// Hold a notification back for each of the given users with a window open in a channel
+    async fn hold_push_notification(
+        &self,
+        channel: &str,
+        users: &[String],
+        notification: &PushNotification,
+        closes_at: i64,
+        stale_before: i64,
+    ) -> Result<Vec<String>> {
+        let mut users = users.to_vec();
+        users.sort();
+        users.dedup();
+
+        let mut push_windows = self.push_windows.lock().await;
+        let mut opened = vec![];
+        for user in users {
+            let key = ChannelCompositeKey {
+                channel: channel.to_string(),
+                user: user.clone(),
+            };
+
+            match push_windows.get_mut(&key) {
+                Some(window) if window.closes_at >= stale_before => {
+                    window.pending += 1;
+                    window.latest = Some(notification.clone());
+                }
+                _ => {
+                    push_windows.insert(
+                        key.clone(),
+                        PushWindow {
+                            id: key,
+                            closes_at,
+                            count: 1,
+                            pending: 0,
+                            latest: None,
+                        },
+                    );
+
+                    opened.push(user);
+                }
+            }
+        }
+
+        Ok(opened)
+    }
+
+    /// Close the windows of the given users in a channel
+    async fn close_push_windows(
+        &self,
+        channel: &str,
+        users: &[String],
+        closes_at: i64,
+    ) -> Result<Vec<PushWindow>> {
+        let mut push_windows = self.push_windows.lock().await;
+        let mut open = vec![];
+        for user in users {
+            let key = ChannelCompositeKey {
+                channel: channel.to_string(),
+                user: user.to_string(),
+            };
+
+            match push_windows.get_mut(&key) {
+                Some(window) if window.pending > 0 => {
+                    window.count += window.pending;
+                    window.pending = 0;
+                    window.closes_at = closes_at;
+                    open.push(window.clone());
+                }
+                Some(_) => {
+                    push_windows.remove(&key);
+                }
+                None => {}
+            }
+        }
+
+        Ok(open)
+    }
+
+    /// Remove a user's window in a channel, discarding any notifications it held back
+    async fn delete_push_window(&self, channel: &str, user: &str) -> Result<()> {
+        let mut push_windows = self.push_windows.lock().await;
+        push_windows.remove(&ChannelCompositeKey {
+            channel: channel.to_string(),
+            user: user.to_string(),
+        });
+
+        Ok(())
+    }
+}
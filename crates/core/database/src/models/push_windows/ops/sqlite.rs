@@ -0,0 +1,119 @@
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::{PushWindow, SqliteDb};
+
+use super::AbstractPushWindows;
+
+static COL: &str = "push_windows";
+
+#[async_trait]
+impl AbstractPushWindows for SqliteDb {
+    /// Hold a notification back for each of the given users with a window open in a channel
+    async fn hold_push_notification(
+        &self,
+        channel: &str,
+        users: &[String],
+        notification: &PushNotification,
+        closes_at: i64,
+        stale_before: i64,
+    ) -> Result<Vec<String>> {
+        // Keys are built in sorted order to match stored document keys.
+        // Windows are only ever opened with nothing held back.
+        let windows = sqlx::query_as::<_, (String, bool)>(
+            "INSERT INTO push_windows (id, data)
+             SELECT json_object('channel', ?1, 'user', value),
+                json_object(
+                    '_id', json_object('channel', ?1, 'user', value),
+                    'closes_at', ?3, 'count', 1, 'pending', 0
+                )
+             FROM (SELECT DISTINCT value FROM json_each(?2)) WHERE TRUE
+             ON CONFLICT (id) DO UPDATE SET data = CASE
+                 WHEN json_extract(push_windows.data, '$.closes_at') < ?5 THEN excluded.data
+                 ELSE json_set(
+                     push_windows.data,
+                     '$.pending', json_extract(push_windows.data, '$.pending') + 1,
+                     '$.latest', json(?4)
+                 )
+             END
+             RETURNING json_extract(data, '$._id.user'), json_extract(data, '$.pending') = 0",
+        )
+        .bind(channel)
+        .bind(serde_json::to_string(users).expect("strings always serialise"))
+        .bind(closes_at)
+        .bind(json!(notification).to_string())
+        .bind(stale_before)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?;
+
+        Ok(windows
+            .into_iter()
+            .filter(|(_, opened)| *opened)
+            .map(|(user, _)| user)
+            .collect())
+    }
+
+    /// Close the windows of the given users in a channel
+    async fn close_push_windows(
+        &self,
+        channel: &str,
+        users: &[String],
+        closes_at: i64,
+    ) -> Result<Vec<PushWindow>> {
+        let users = serde_json::to_string(users).expect("strings always serialise");
+
+        sqlx::query(
+            "DELETE FROM push_windows
+             WHERE json_extract(data, '$._id.channel') = ?1
+               AND json_extract(data, '$._id.user') IN (SELECT value FROM json_each(?2))
+               AND json_extract(data, '$.pending') = 0",
+        )
+        .bind(channel)
+        .bind(&users)
+        .execute(&self.0)
+        .await
+        .map_err(|_| create_database_error!("delete_many", COL))?;
+
+        // Windows opened again since being removed above are left to close on their own.
+        Ok(sqlx::query_scalar::<_, String>(
+            "UPDATE push_windows
+             SET data = json_set(
+                 data,
+                 '$.closes_at', ?3,
+                 '$.count', json_extract(data, '$.count') + json_extract(data, '$.pending'),
+                 '$.pending', 0
+             )
+             WHERE json_extract(data, '$._id.channel') = ?1
+               AND json_extract(data, '$._id.user') IN (SELECT value FROM json_each(?2))
+               AND json_extract(data, '$.pending') > 0
+             RETURNING data",
+        )
+        .bind(channel)
+        .bind(&users)
+        .bind(closes_at)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("update_many", COL))?
+        .into_iter()
+        .filter_map(|window| serde_json::from_str(&window).ok())
+        .collect())
+    }
+
+    /// Remove a user's window in a channel, discarding any notifications it held back
+    async fn delete_push_window(&self, channel: &str, user: &str) -> Result<()> {
+        query!(
+            self,
+            delete_one,
+            COL,
+            json!({
+                "_id": {
+                    "channel": channel,
+                    "user": user
+                }
+            })
+        )
+        .map(|_| ())
+    }
+}
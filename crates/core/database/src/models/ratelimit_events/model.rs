@@ -20,6 +20,7 @@ auto_derived!(
     /// Event type
     pub enum RatelimitEventType {
         DiscriminatorChange,
+        ActivityUpdate,
     }
 );
 
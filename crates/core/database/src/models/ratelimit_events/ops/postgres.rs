@@ -0,0 +1,41 @@
+use std::time::{Duration, SystemTime};
+
+use super::AbstractRatelimitEvents;
+use crate::{PostgresDb, RatelimitEvent, RatelimitEventType};
+use revolt_result::Result;
+use serde_json::json;
+use sqlx::types::Json;
+use ulid::Ulid;
+
+static COL: &str = "ratelimit_events";
+
+#[async_trait]
+impl AbstractRatelimitEvents for PostgresDb {
+    /// Insert a new ratelimit event
+    async fn insert_ratelimit_event(&self, event: &RatelimitEvent) -> Result<()> {
+        query!(self, insert_one, COL, &event)
+    }
+
+    /// Count number of events in given duration and check if we've hit the limit
+    async fn has_ratelimited(
+        &self,
+        target_id: &str,
+        event_type: RatelimitEventType,
+        period: Duration,
+        count: usize,
+    ) -> Result<bool> {
+        sqlx::query_scalar::<_, i64>(
+            r#"SELECT COUNT(*) FROM ratelimit_events
+               WHERE data @> $1 AND (data->>'_id') COLLATE "C" >= $2"#,
+        )
+        .bind(Json(json!({
+            "target_id": target_id,
+            "event_type": event_type.to_string()
+        })))
+        .bind(Ulid::from_datetime(SystemTime::now() - period).to_string())
+        .fetch_one(&self.0)
+        .await
+        .map(|c| c as usize >= count)
+        .map_err(|_| create_database_error!("count_documents", COL))
+    }
+}
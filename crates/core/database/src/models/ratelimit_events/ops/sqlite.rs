@@ -0,0 +1,49 @@
+use std::time::{Duration, SystemTime};
+
+use super::AbstractRatelimitEvents;
+use crate::{document_key, RatelimitEvent, RatelimitEventType, SqliteDb};
+use revolt_result::Result;
+use serde_json::json;
+use ulid::Ulid;
+
+static COL: &str = "ratelimit_events";
+
+#[async_trait]
+impl AbstractRatelimitEvents for SqliteDb {
+    /// Insert a new ratelimit event
+    async fn insert_ratelimit_event(&self, event: &RatelimitEvent) -> Result<()> {
+        query!(self, insert_one, COL, &event)
+    }
+
+    /// Count number of events in given duration and check if we've hit the limit
+    async fn has_ratelimited(
+        &self,
+        target_id: &str,
+        event_type: RatelimitEventType,
+        period: Duration,
+        count: usize,
+    ) -> Result<bool> {
+        let mut query = self.select(
+            "COUNT(*)",
+            COL,
+            &json!({
+                "target_id": target_id,
+                "event_type": event_type.to_string()
+            }),
+        );
+
+        query
+            .push(" AND id >= ")
+            .push_bind(document_key(&json!(Ulid::from_datetime(
+                SystemTime::now() - period
+            )
+            .to_string())));
+
+        query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.0)
+            .await
+            .map(|c| c as usize >= count)
+            .map_err(|_| create_database_error!("count_documents", COL))
+    }
+}
@@ -3,7 +3,11 @@ use revolt_result::Result;
 use crate::Report;
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractReport: Sync + Send {
@@ -0,0 +1,16 @@
+use revolt_result::Result;
+
+use crate::Report;
+use crate::SqliteDb;
+
+use super::AbstractReport;
+
+static COL: &str = "safety_reports";
+
+#[async_trait]
+impl AbstractReport for SqliteDb {
+    /// Insert a new report into the database
+    async fn insert_report(&self, report: &Report) -> Result<()> {
+        query!(self, insert_one, COL, &report)
+    }
+}
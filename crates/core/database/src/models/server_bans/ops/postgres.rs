@@ -0,0 +1,63 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::PostgresDb;
+use crate::{MemberCompositeKey, ServerBan};
+
+use super::AbstractServerBans;
+
+static COL: &str = "server_bans";
+
+#[async_trait]
+impl AbstractServerBans for PostgresDb {
+    /// Insert new ban into database
+    async fn insert_ban(&self, ban: &ServerBan) -> Result<()> {
+        query!(self, insert_one, COL, &ban)
+    }
+
+    /// Fetch a server ban by server and user id
+    async fn fetch_ban(&self, server_id: &str, user_id: &str) -> Result<ServerBan> {
+        query!(
+            self,
+            find_one,
+            COL,
+            json!({
+                "_id": {
+                    "server": server_id,
+                    "user": user_id
+                }
+            })
+        )?
+        .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch all bans in a server
+    async fn fetch_bans(&self, server_id: &str) -> Result<Vec<ServerBan>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "_id": {
+                    "server": server_id
+                }
+            })
+        )
+    }
+
+    /// Delete a ban from the database
+    async fn delete_ban(&self, id: &MemberCompositeKey) -> Result<()> {
+        query!(
+            self,
+            delete_one,
+            COL,
+            json!({
+                "_id": {
+                    "server": &id.server,
+                    "user": &id.user
+                }
+            })
+        )
+        .map(|_| ())
+    }
+}
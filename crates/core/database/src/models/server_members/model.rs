@@ -98,14 +98,15 @@ impl Member {
             ..Default::default()
         };
 
-        db.insert_member(&member).await?;
+        let tx = db.begin().await?;
+        tx.insert_member(&member).await?;
 
         let should_fetch = channels.is_none();
         let mut channels = channels.unwrap_or_default();
 
         if should_fetch {
-            let query = DatabasePermissionQuery::new(db, user).server(server);
-            let existing_channels = db.fetch_channels(&server.channels).await?;
+            let query = DatabasePermissionQuery::new(&tx, user).server(server);
+            let existing_channels = tx.fetch_channels(&server.channels).await?;
 
             for channel in existing_channels {
                 let mut channel_query = query.clone().channel(&channel);
@@ -119,13 +120,13 @@ impl Member {
             }
         }
 
-        let emojis = db.fetch_emoji_by_parent_id(&server.id).await?;
+        let emojis = tx.fetch_emoji_by_parent_id(&server.id).await?;
 
         EventV1::ServerMemberJoin {
             id: server.id.clone(),
             user: user.id.clone(),
         }
-        .p(server.id.clone())
+        .p_outbox(&tx, server.id.clone())
         .await;
 
         EventV1::ServerCreate {
@@ -138,9 +139,11 @@ impl Member {
                 .collect(),
             emojis: emojis.into_iter().map(|emoji| emoji.into()).collect(),
         }
-        .private(user.id.clone())
+        .private_outbox(&tx, user.id.clone())
         .await;
 
+        tx.commit().await?;
+
         if let Some(id) = server
             .system_messages
             .as_ref()
@@ -165,23 +168,31 @@ impl Member {
         partial: PartialMember,
         remove: Vec<FieldsMember>,
     ) -> Result<()> {
+        let files = self.file_ids();
         for field in &remove {
             self.remove_field(field);
         }
 
         self.apply_options(partial.clone());
 
-        db.update_member(&self.id, &partial, remove.clone()).await?;
+        let tx = db.begin().await?;
+        tx.update_member(&self.id, &partial, remove.clone()).await?;
+        File::detach_unused(&tx, files, self.file_ids()).await?;
 
         EventV1::ServerMemberUpdate {
             id: self.id.clone().into(),
             data: partial.into(),
             clear: remove.into_iter().map(|field| field.into()).collect(),
         }
-        .p(self.id.server.clone())
+        .p_outbox(&tx, self.id.server.clone())
         .await;
 
-        Ok(())
+        tx.commit().await
+    }
+
+    /// Ids of the files used by this member
+    fn file_ids(&self) -> Vec<String> {
+        self.avatar.iter().map(|file| file.id.to_string()).collect()
     }
 
     pub fn remove_field(&mut self, field: &FieldsMember) {
@@ -224,15 +235,19 @@ impl Member {
         intention: RemovalIntention,
         silent: bool,
     ) -> Result<()> {
-        db.delete_member(&self.id).await?;
+        let tx = db.begin().await?;
+        tx.delete_member(&self.id).await?;
+        File::detach_unused(&tx, self.file_ids(), vec![]).await?;
 
         EventV1::ServerMemberLeave {
             id: self.id.server.to_string(),
             user: self.id.user.to_string(),
         }
-        .p(self.id.server.to_string())
+        .p_outbox(&tx, self.id.server.to_string())
         .await;
 
+        tx.commit().await?;
+
         if !silent {
             if let Some(id) = server
                 .system_messages
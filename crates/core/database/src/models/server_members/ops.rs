@@ -3,7 +3,11 @@ use revolt_result::Result;
 use crate::{FieldsMember, Member, MemberCompositeKey, PartialMember};
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractServerMembers: Sync + Send {
@@ -16,6 +20,15 @@ pub trait AbstractServerMembers: Sync + Send {
     /// Fetch all members in a server
     async fn fetch_all_members<'a>(&self, server_id: &str) -> Result<Vec<Member>>;
 
+    /// Fetch a page of members in a server ordered by user id, starting
+    /// after the given user
+    async fn fetch_members_page<'a>(
+        &self,
+        server_id: &str,
+        after: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Member>>;
+
     /// Fetch all memberships for a user
     async fn fetch_all_memberships<'a>(&self, user_id: &str) -> Result<Vec<Member>>;
 
@@ -1,4 +1,5 @@
 use futures::StreamExt;
+use mongodb::options::FindOptions;
 use revolt_result::Result;
 
 use crate::{FieldsMember, Member, MemberCompositeKey, PartialMember};
@@ -52,6 +53,34 @@ impl AbstractServerMembers for MongoDb {
             .await)
     }
 
+    /// Fetch a page of members in a server ordered by user id, starting
+    /// after the given user
+    async fn fetch_members_page<'a>(
+        &self,
+        server_id: &str,
+        after: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Member>> {
+        let mut filter = doc! {
+            "_id.server": server_id
+        };
+
+        if let Some(after) = after {
+            filter.insert("_id.user", doc! { "$gt": after });
+        }
+
+        self.find_with_options(
+            COL,
+            filter,
+            FindOptions::builder()
+                .sort(doc! { "_id.user": 1_i32 })
+                .limit(limit)
+                .build(),
+        )
+        .await
+        .map_err(|_| create_database_error!("find", COL))
+    }
+
     /// Fetch all memberships for a user
     async fn fetch_all_memberships<'a>(&self, user_id: &str) -> Result<Vec<Member>> {
         Ok(self
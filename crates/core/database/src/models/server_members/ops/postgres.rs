@@ -0,0 +1,175 @@
+use revolt_result::Result;
+use serde_json::json;
+
+use crate::{FieldsMember, Member, MemberCompositeKey, PartialMember};
+use crate::{IntoDocumentPath, PostgresDb};
+
+use super::AbstractServerMembers;
+
+static COL: &str = "server_members";
+
+#[async_trait]
+impl AbstractServerMembers for PostgresDb {
+    /// Insert a new server member into the database
+    async fn insert_member(&self, member: &Member) -> Result<()> {
+        query!(self, insert_one, COL, &member)
+    }
+
+    /// Fetch a server member by their id
+    async fn fetch_member(&self, server_id: &str, user_id: &str) -> Result<Member> {
+        query!(
+            self,
+            find_one,
+            COL,
+            json!({
+                "_id": {
+                    "server": server_id,
+                    "user": user_id
+                }
+            })
+        )?
+        .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch all members in a server
+    async fn fetch_all_members<'a>(&self, server_id: &str) -> Result<Vec<Member>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "_id": {
+                    "server": server_id
+                }
+            })
+        )
+    }
+
+    /// Fetch a page of members in a server ordered by user id, starting
+    /// after the given user
+    async fn fetch_members_page<'a>(
+        &self,
+        server_id: &str,
+        after: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Member>> {
+        let mut query =
+            sqlx::QueryBuilder::new("SELECT data FROM server_members WHERE id->>'server' = ");
+
+        query.push_bind(server_id.to_owned());
+
+        if let Some(after) = after {
+            query
+                .push(" AND (id->>'user') COLLATE \"C\" > ")
+                .push_bind(after.to_owned());
+        }
+
+        query
+            .push(" ORDER BY (id->>'user') COLLATE \"C\" LIMIT ")
+            .push_bind(limit);
+
+        self.find_by_query(query)
+            .await
+            .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Fetch all memberships for a user
+    async fn fetch_all_memberships<'a>(&self, user_id: &str) -> Result<Vec<Member>> {
+        query!(
+            self,
+            find,
+            COL,
+            json!({
+                "_id": {
+                    "user": user_id
+                }
+            })
+        )
+    }
+
+    /// Fetch multiple members by their ids
+    async fn fetch_members<'a>(&self, server_id: &str, ids: &'a [String]) -> Result<Vec<Member>> {
+        let mut query =
+            sqlx::QueryBuilder::new("SELECT data FROM server_members WHERE id->>'server' = ");
+
+        query
+            .push_bind(server_id.to_owned())
+            .push(" AND id->>'user' = ANY(")
+            .push_bind(ids.to_vec())
+            .push(")");
+
+        self.find_by_query(query)
+            .await
+            .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Fetch member count of a server
+    async fn fetch_member_count(&self, server_id: &str) -> Result<usize> {
+        query!(
+            self,
+            count_documents,
+            COL,
+            json!({
+                "_id": {
+                    "server": server_id
+                }
+            })
+        )
+        .map(|c| c as usize)
+    }
+
+    /// Fetch server count of a user
+    async fn fetch_server_count(&self, user_id: &str) -> Result<usize> {
+        query!(
+            self,
+            count_documents,
+            COL,
+            json!({
+                "_id": {
+                    "user": user_id
+                }
+            })
+        )
+        .map(|c| c as usize)
+    }
+
+    /// Update information for a server member
+    async fn update_member(
+        &self,
+        id: &MemberCompositeKey,
+        partial: &PartialMember,
+        remove: Vec<FieldsMember>,
+    ) -> Result<()> {
+        query!(
+            self,
+            update_one,
+            COL,
+            json!({
+                "_id": {
+                    "server": &id.server,
+                    "user": &id.user
+                }
+            }),
+            partial,
+            remove.iter().map(|x| x as &dyn IntoDocumentPath).collect(),
+            None
+        )
+        .map(|_| ())
+    }
+
+    /// Delete a server member by their id
+    async fn delete_member(&self, id: &MemberCompositeKey) -> Result<()> {
+        query!(
+            self,
+            delete_one,
+            COL,
+            json!({
+                "_id": {
+                    "server": &id.server,
+                    "user": &id.user
+                }
+            })
+        )
+        .map(|_| ())
+    }
+}
@@ -40,6 +40,29 @@ impl AbstractServerMembers for ReferenceDb {
             .collect())
     }
 
+    /// Fetch a page of members in a server ordered by user id, starting
+    /// after the given user
+    async fn fetch_members_page<'a>(
+        &self,
+        server_id: &str,
+        after: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Member>> {
+        let server_members = self.server_members.lock().await;
+        let mut members: Vec<Member> = server_members
+            .values()
+            .filter(|member| {
+                member.id.server == server_id
+                    && after.is_none_or(|after| member.id.user.as_str() > after)
+            })
+            .cloned()
+            .collect();
+
+        members.sort_by(|a, b| a.id.user.cmp(&b.id.user));
+        members.truncate(limit as usize);
+        Ok(members)
+    }
+
     /// Fetch all memberships for a user
     async fn fetch_all_memberships<'a>(&self, user_id: &str) -> Result<Vec<Member>> {
         let server_members = self.server_members.lock().await;
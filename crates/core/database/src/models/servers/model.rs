@@ -5,7 +5,7 @@ use revolt_permissions::{OverrideField, DEFAULT_PERMISSION_SERVER};
 use revolt_result::Result;
 use ulid::Ulid;
 
-use crate::{events::client::EventV1, Channel, Database, File, User};
+use crate::{events::client::EventV1, Channel, Database, File, FileParent, User};
 
 auto_derived_partial!(
     /// Server
@@ -187,34 +187,72 @@ impl Server {
         partial: PartialServer,
         remove: Vec<FieldsServer>,
     ) -> Result<()> {
+        let files = self.file_ids();
         for field in &remove {
             self.remove_field(field);
         }
 
         self.apply_options(partial.clone());
 
-        db.update_server(&self.id, &partial, remove.clone()).await?;
+        let tx = db.begin().await?;
+        tx.update_server(&self.id, &partial, remove.clone()).await?;
+        File::detach_unused(&tx, files, self.file_ids()).await?;
 
         EventV1::ServerUpdate {
             id: self.id.clone(),
             data: partial.into(),
             clear: remove.into_iter().map(|v| v.into()).collect(),
         }
-        .p(self.id.clone())
+        .p_outbox(&tx, self.id.clone())
         .await;
 
-        Ok(())
+        tx.commit().await
     }
 
     /// Delete a server
     pub async fn delete(self, db: &Database) -> Result<()> {
+        let tx = db.begin().await?;
         EventV1::ServerDelete {
             id: self.id.clone(),
         }
-        .p(self.id.clone())
+        .p_outbox(&tx, self.id.clone())
         .await;
 
-        db.delete_server(&self.id).await
+        // Channels and emojis are deleted along with the server.
+        let mut parents = vec![
+            FileParent::ServerIcon {
+                id: self.id.clone(),
+            },
+            FileParent::ServerBanner {
+                id: self.id.clone(),
+            },
+        ];
+
+        parents.extend(
+            self.channels
+                .iter()
+                .map(|id| FileParent::ChannelIcon { id: id.to_string() }),
+        );
+
+        parents.extend(
+            tx.fetch_emoji_by_parent_id(&self.id)
+                .await?
+                .into_iter()
+                .map(|emoji| FileParent::Emoji { id: emoji.id }),
+        );
+
+        tx.delete_server(&self.id).await?;
+        tx.detach_attachments_from(&parents).await?;
+        tx.commit().await
+    }
+
+    /// Ids of the files used by this server
+    fn file_ids(&self) -> Vec<String> {
+        self.icon
+            .iter()
+            .chain(&self.banner)
+            .map(|file| file.id.to_string())
+            .collect()
     }
 
     /// Remove a field from Server
@@ -270,7 +308,8 @@ impl Role {
     /// Create a role
     pub async fn create(&self, db: &Database, server_id: &str) -> Result<String> {
         let role_id = Ulid::new().to_string();
-        db.insert_role(server_id, &role_id, self).await?;
+        let tx = db.begin().await?;
+        tx.insert_role(server_id, &role_id, self).await?;
 
         EventV1::ServerRoleUpdate {
             id: server_id.to_string(),
@@ -278,9 +317,10 @@ impl Role {
             data: self.clone().into_optional().into(),
             clear: vec![],
         }
-        .p(server_id.to_string())
+        .p_outbox(&tx, server_id.to_string())
         .await;
 
+        tx.commit().await?;
         Ok(role_id)
     }
 
@@ -299,7 +339,8 @@ impl Role {
 
         self.apply_options(partial.clone());
 
-        db.update_role(server_id, role_id, &partial, remove.clone())
+        let tx = db.begin().await?;
+        tx.update_role(server_id, role_id, &partial, remove.clone())
             .await?;
 
         EventV1::ServerRoleUpdate {
@@ -308,10 +349,10 @@ impl Role {
             data: partial.into(),
             clear: vec![],
         }
-        .p(server_id.to_string())
+        .p_outbox(&tx, server_id.to_string())
         .await;
 
-        Ok(())
+        tx.commit().await
     }
 
     /// Remove field from Role
@@ -323,14 +364,16 @@ impl Role {
 
     /// Delete a role
     pub async fn delete(self, db: &Database, server_id: &str, role_id: &str) -> Result<()> {
+        let tx = db.begin().await?;
         EventV1::ServerRoleDelete {
             id: server_id.to_string(),
             role_id: role_id.to_string(),
         }
-        .p(server_id.to_string())
+        .p_outbox(&tx, server_id.to_string())
         .await;
 
-        db.delete_role(server_id, role_id).await
+        tx.delete_role(server_id, role_id).await?;
+        tx.commit().await
     }
 }
 
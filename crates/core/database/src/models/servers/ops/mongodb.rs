@@ -251,9 +251,9 @@ impl MongoDb {
                 .map_err(|_| create_database_error!("delete_many", with))?;
         }
 
-        // Update many attachments with parent id.
-        self.delete_many_attachments(doc! {
-            "object_id": &server_id
+        // Detach avatars of the deleted members.
+        self.detach_many_attachments(doc! {
+            "parent.server": &server_id
         })
         .await?;
 
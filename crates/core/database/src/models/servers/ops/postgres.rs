@@ -0,0 +1,179 @@
+use revolt_result::Result;
+use serde_json::{json, Value};
+
+use crate::{remove_path, set_path, IntoDocumentPath, PostgresDb};
+use crate::{FieldsRole, FieldsServer, PartialRole, PartialServer, Role, Server};
+
+use super::AbstractServers;
+
+static COL: &str = "servers";
+
+#[async_trait]
+impl AbstractServers for PostgresDb {
+    /// Insert a new server into database
+    async fn insert_server(&self, server: &Server) -> Result<()> {
+        query!(self, insert_one, COL, &server)
+    }
+
+    /// Fetch a server by its id
+    async fn fetch_server(&self, id: &str) -> Result<Server> {
+        query!(self, find_one_by_id, COL, id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch a servers by their ids
+    async fn fetch_servers<'a>(&self, ids: &'a [String]) -> Result<Vec<Server>> {
+        query!(self, find_in, COL, "data->>'_id'", ids)
+    }
+
+    /// Update a server with new information
+    async fn update_server(
+        &self,
+        id: &str,
+        partial: &PartialServer,
+        remove: Vec<FieldsServer>,
+    ) -> Result<()> {
+        query!(
+            self,
+            update_one_by_id,
+            COL,
+            id,
+            partial,
+            remove.iter().map(|x| x as &dyn IntoDocumentPath).collect(),
+            None
+        )
+        .map(|_| ())
+    }
+
+    /// Delete a server by its id
+    async fn delete_server(&self, id: &str) -> Result<()> {
+        self.delete_associated_server_objects(id).await?;
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Insert a new role into server object
+    async fn insert_role(&self, server_id: &str, role_id: &str, role: &Role) -> Result<()> {
+        let role =
+            serde_json::to_value(role).map_err(|_| create_database_error!("to_value", "role"))?;
+
+        self.modify_one(COL, json!({ "_id": server_id }), |server| {
+            set_path(server, &format!("roles.{role_id}"), role)
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "server"))
+    }
+
+    /// Update an existing role on a server
+    async fn update_role(
+        &self,
+        server_id: &str,
+        role_id: &str,
+        partial: &PartialRole,
+        remove: Vec<FieldsRole>,
+    ) -> Result<()> {
+        query!(
+            self,
+            update_one_by_id,
+            COL,
+            server_id,
+            partial,
+            remove.iter().map(|x| x as &dyn IntoDocumentPath).collect(),
+            "roles.".to_owned() + role_id + "."
+        )
+        .map(|_| ())
+    }
+
+    /// Delete a role from a server
+    ///
+    /// Also updates channels and members.
+    async fn delete_role(&self, server_id: &str, role_id: &str) -> Result<()> {
+        self.modify_many(
+            "server_members",
+            json!({
+                "_id": {
+                    "server": server_id
+                },
+                "roles": [role_id]
+            }),
+            |member| {
+                if let Some(roles) = member.get_mut("roles").and_then(Value::as_array_mut) {
+                    roles.retain(|role| role != role_id);
+                }
+            },
+        )
+        .await
+        .map_err(|_| create_database_error!("update_many", "server_members"))?;
+
+        let path = format!("role_permissions.{role_id}");
+        self.modify_many("channels", json!({ "server": server_id }), |channel| {
+            remove_path(channel, &path)
+        })
+        .await
+        .map_err(|_| create_database_error!("update_many", "channels"))?;
+
+        self.modify_one(COL, json!({ "_id": server_id }), |server| {
+            remove_path(server, &format!("roles.{role_id}"))
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "servers"))
+    }
+}
+
+impl PostgresDb {
+    pub async fn delete_associated_server_objects(&self, server_id: &str) -> Result<()> {
+        // Find all channels
+        let channels: Vec<String> =
+            sqlx::query_scalar("SELECT data->>'_id' FROM channels WHERE data @> $1")
+                .bind(sqlx::types::Json(json!({ "server": server_id })))
+                .fetch_all(&self.0)
+                .await
+                .map_err(|_| create_database_error!("find", "channels"))?;
+
+        // Check if there are any attachments we need to delete.
+        self.delete_bulk_messages(&channels).await?;
+
+        // Delete all emoji.
+        query!(
+            self,
+            delete_many,
+            "emojis",
+            json!({
+                "parent": {
+                    "id": server_id
+                }
+            })
+        )?;
+
+        // Delete all channels.
+        query!(
+            self,
+            delete_many,
+            "channels",
+            json!({
+                "server": server_id
+            })
+        )?;
+
+        // Delete any associated objects, e.g. unreads and invites.
+        self.delete_associated_channel_objects(&channels).await?;
+
+        // Delete members and bans.
+        for with in ["server_members", "server_bans"] {
+            query!(
+                self,
+                delete_many,
+                with,
+                json!({
+                    "_id": {
+                        "server": server_id
+                    }
+                })
+            )?;
+        }
+
+        // Detach avatars of the deleted members.
+        self.detach_many_attachments("data->'parent'->>'server'", &[server_id.to_owned()])
+            .await
+    }
+}
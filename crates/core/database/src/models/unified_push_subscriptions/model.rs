@@ -0,0 +1,266 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use revolt_models::v0;
+use revolt_result::{create_error, Result};
+
+use crate::Database;
+
+auto_derived!(
+    /// UnifiedPush endpoint registered by a session
+    ///
+    /// Notifications are delivered with a plain HTTP POST to the endpoint,
+    /// typically a distributor such as ntfy which passes them on to the device.
+    pub struct UnifiedPushSubscription {
+        /// Id of the session this endpoint belongs to
+        #[serde(rename = "_id")]
+        pub id: String,
+        /// Id of the user the session belongs to
+        pub user: String,
+        /// URL notifications are posted to
+        pub endpoint: String,
+        /// Keys to encrypt notifications with, sent as plain JSON if not set
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub keys: Option<UnifiedPushKeys>,
+        /// Number of deliveries in a row which have failed
+        #[serde(default)]
+        pub failures: i32,
+    }
+
+    /// Keys used to encrypt notifications as described by RFC 8291
+    pub struct UnifiedPushKeys {
+        /// Public key of the receiver (P-256, uncompressed, base64url)
+        pub p256dh: String,
+        /// Authentication secret (base64url)
+        pub auth: String,
+    }
+);
+
+#[allow(clippy::disallowed_methods)]
+impl UnifiedPushSubscription {
+    /// Register a session's UnifiedPush endpoint
+    ///
+    /// Any Web Push subscription on the session should be removed by the caller,
+    /// as a session only receives notifications through one service.
+    pub async fn create(
+        db: &Database,
+        session_id: String,
+        user_id: String,
+        data: v0::DataUnifiedPushSubscription,
+    ) -> Result<UnifiedPushSubscription> {
+        let subscription = UnifiedPushSubscription {
+            id: session_id,
+            user: user_id,
+            endpoint: data.endpoint,
+            keys: match (data.p256dh, data.auth) {
+                (Some(p256dh), Some(auth)) => Some(UnifiedPushKeys { p256dh, auth }),
+                (None, None) => None,
+                _ => {
+                    return Err(create_error!(FailedValidation {
+                        error: "`p256dh` and `auth` must be given together".to_string()
+                    }))
+                }
+            },
+            failures: 0,
+        };
+
+        subscription.validate()?;
+        db.set_unified_push_subscription(&subscription).await?;
+        Ok(subscription)
+    }
+
+    /// Check that the endpoint is a web URL and any keys are well-formed
+    ///
+    /// Notifications are only sent in the clear over HTTPS.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.endpoint.starts_with("https://") || self.endpoint.starts_with("http://"))
+            || self.endpoint.len() > 2048
+        {
+            return Err(create_error!(FailedValidation {
+                error: "`endpoint` must be an HTTP(S) URL".to_string()
+            }));
+        }
+
+        if self.keys.is_none() && !self.endpoint.starts_with("https://") {
+            return Err(create_error!(FailedValidation {
+                error: "`endpoint` must use HTTPS unless `p256dh` and `auth` are given"
+                    .to_string()
+            }));
+        }
+
+        if let Some(keys) = &self.keys {
+            let decoded_length = |key: &str| {
+                URL_SAFE_NO_PAD
+                    .decode(key.trim_end_matches('='))
+                    .map(|key| key.len())
+            };
+
+            if decoded_length(&keys.p256dh).ok() != Some(65) {
+                return Err(create_error!(FailedValidation {
+                    error: "`p256dh` must be an uncompressed P-256 public key".to_string()
+                }));
+            }
+
+            if decoded_length(&keys.auth).ok() != Some(16) {
+                return Err(create_error!(FailedValidation {
+                    error: "`auth` must be a 16 byte secret".to_string()
+                }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revolt_models::v0::DataUnifiedPushSubscription;
+
+    use crate::UnifiedPushSubscription;
+
+    /// Receiver keys from the example in RFC 8291
+    static P256DH: &str =
+        "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4";
+    static AUTH: &str = "BTBZMqHH6r4Tts7J_aSIgg";
+
+    fn data(
+        endpoint: &str,
+        p256dh: Option<&str>,
+        auth: Option<&str>,
+    ) -> DataUnifiedPushSubscription {
+        DataUnifiedPushSubscription {
+            endpoint: endpoint.to_string(),
+            p256dh: p256dh.map(str::to_string),
+            auth: auth.map(str::to_string),
+        }
+    }
+
+    #[async_std::test]
+    async fn validation() {
+        database_test!(|db| async move {
+            let create = |data| {
+                UnifiedPushSubscription::create(
+                    &db,
+                    "session".to_string(),
+                    "user".to_string(),
+                    data,
+                )
+            };
+
+            assert!(create(data("https://ntfy.sh/upABC?up=1", None, None))
+                .await
+                .is_ok());
+            assert!(
+                create(data("https://ntfy.sh/upABC", Some(P256DH), Some(AUTH)))
+                    .await
+                    .is_ok()
+            );
+
+            assert!(
+                create(data("http://ntfy.sh/upABC", Some(P256DH), Some(AUTH)))
+                    .await
+                    .is_ok()
+            );
+
+            assert!(create(data("http://ntfy.sh/upABC", None, None))
+                .await
+                .is_err());
+            assert!(create(data("fcm", None, None)).await.is_err());
+            assert!(create(data("ftp://example.com", None, None)).await.is_err());
+            assert!(create(data("https://ntfy.sh/upABC", Some(P256DH), None))
+                .await
+                .is_err());
+            assert!(
+                create(data("https://ntfy.sh/upABC", Some(AUTH), Some(AUTH)))
+                    .await
+                    .is_err()
+            );
+            assert!(create(data(
+                "https://ntfy.sh/upABC",
+                Some(P256DH),
+                Some("not base64!")
+            ))
+            .await
+            .is_err());
+        });
+    }
+
+    #[async_std::test]
+    async fn crud() {
+        database_test!(|db| async move {
+            for (session, user) in [("a", "alice"), ("b", "alice"), ("c", "bob")] {
+                UnifiedPushSubscription::create(
+                    &db,
+                    session.to_string(),
+                    user.to_string(),
+                    data(&format!("https://push.example.com/{session}"), None, None),
+                )
+                .await
+                .unwrap();
+            }
+
+            let users = ["alice".to_string(), "bob".to_string()];
+            let mut subscriptions = db.fetch_unified_push_subscriptions(&users).await.unwrap();
+            subscriptions.sort_by(|a, b| a.id.cmp(&b.id));
+            assert_eq!(
+                subscriptions
+                    .iter()
+                    .map(|sub| sub.id.as_str())
+                    .collect::<Vec<_>>(),
+                ["a", "b", "c"]
+            );
+
+            // Registering again replaces the endpoint and resets failures
+            db.set_unified_push_failures("a", 3).await.unwrap();
+            let mut subscriptions = db
+                .fetch_unified_push_subscriptions(&["alice".to_string()])
+                .await
+                .unwrap();
+            subscriptions.sort_by(|a, b| a.id.cmp(&b.id));
+            assert_eq!(subscriptions[0].failures, 3);
+
+            UnifiedPushSubscription::create(
+                &db,
+                "a".to_string(),
+                "alice".to_string(),
+                data("https://push.example.com/new", None, None),
+            )
+            .await
+            .unwrap();
+
+            let mut subscriptions = db
+                .fetch_unified_push_subscriptions(&["alice".to_string()])
+                .await
+                .unwrap();
+            subscriptions.sort_by(|a, b| a.id.cmp(&b.id));
+            assert_eq!(subscriptions.len(), 2);
+            assert_eq!(subscriptions[0].endpoint, "https://push.example.com/new");
+            assert_eq!(subscriptions[0].failures, 0);
+
+            db.delete_unified_push_subscription("c").await.unwrap();
+            assert!(db
+                .fetch_unified_push_subscriptions(&["bob".to_string()])
+                .await
+                .unwrap()
+                .is_empty());
+
+            // Signing out everywhere else keeps the current session's endpoint
+            db.delete_unified_push_subscriptions("alice", Some("b"))
+                .await
+                .unwrap();
+            let subscriptions = db
+                .fetch_unified_push_subscriptions(&["alice".to_string()])
+                .await
+                .unwrap();
+            assert_eq!(subscriptions.len(), 1);
+            assert_eq!(subscriptions[0].id, "b");
+
+            db.delete_unified_push_subscriptions("alice", None)
+                .await
+                .unwrap();
+            assert!(db
+                .fetch_unified_push_subscriptions(&["alice".to_string()])
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+}
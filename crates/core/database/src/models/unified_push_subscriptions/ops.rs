@@ -0,0 +1,39 @@
+use revolt_result::Result;
+
+use crate::UnifiedPushSubscription;
+
+mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[async_trait]
+pub trait AbstractUnifiedPushSubscriptions: Sync + Send {
+    /// Register a session's UnifiedPush endpoint, replacing any it already had
+    async fn set_unified_push_subscription(
+        &self,
+        subscription: &UnifiedPushSubscription,
+    ) -> Result<()>;
+
+    /// Fetch the UnifiedPush endpoints of every session belonging to the given users
+    async fn fetch_unified_push_subscriptions(
+        &self,
+        users: &[String],
+    ) -> Result<Vec<UnifiedPushSubscription>>;
+
+    /// Record how many deliveries in a row have failed for a session's endpoint
+    async fn set_unified_push_failures(&self, id: &str, failures: i32) -> Result<()>;
+
+    /// Delete a session's UnifiedPush endpoint
+    async fn delete_unified_push_subscription(&self, id: &str) -> Result<()>;
+
+    /// Delete the UnifiedPush endpoints of every session belonging to a user,
+    /// optionally keeping the given session's
+    async fn delete_unified_push_subscriptions(
+        &self,
+        user: &str,
+        except: Option<&str>,
+    ) -> Result<()>;
+}
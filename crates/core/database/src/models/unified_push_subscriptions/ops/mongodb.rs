@@ -0,0 +1,92 @@
+use mongodb::options::ReplaceOptions;
+use revolt_result::Result;
+
+use crate::{MongoDb, UnifiedPushSubscription};
+
+use super::AbstractUnifiedPushSubscriptions;
+
+static COL: &str = "unified_push_subscriptions";
+
+#[async_trait]
+impl AbstractUnifiedPushSubscriptions for MongoDb {
+    /// Register a session's UnifiedPush endpoint, replacing any it already had
+    async fn set_unified_push_subscription(
+        &self,
+        subscription: &UnifiedPushSubscription,
+    ) -> Result<()> {
+        self.col::<UnifiedPushSubscription>(COL)
+            .replace_one(
+                doc! {
+                    "_id": &subscription.id
+                },
+                subscription,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("replace_one", COL))
+    }
+
+    /// Fetch the UnifiedPush endpoints of every session belonging to the given users
+    async fn fetch_unified_push_subscriptions(
+        &self,
+        users: &[String],
+    ) -> Result<Vec<UnifiedPushSubscription>> {
+        query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "user": {
+                    "$in": users
+                }
+            }
+        )
+    }
+
+    /// Record how many deliveries in a row have failed for a session's endpoint
+    async fn set_unified_push_failures(&self, id: &str, failures: i32) -> Result<()> {
+        self.col::<UnifiedPushSubscription>(COL)
+            .update_one(
+                doc! {
+                    "_id": id
+                },
+                doc! {
+                    "$set": {
+                        "failures": failures
+                    }
+                },
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete a session's UnifiedPush endpoint
+    async fn delete_unified_push_subscription(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Delete the UnifiedPush endpoints of every session belonging to a user,
+    /// optionally keeping the given session's
+    async fn delete_unified_push_subscriptions(
+        &self,
+        user: &str,
+        except: Option<&str>,
+    ) -> Result<()> {
+        let mut filter = doc! {
+            "user": user
+        };
+
+        if let Some(except) = except {
+            filter.insert("_id", doc! { "$ne": except });
+        }
+
+        self.col::<UnifiedPushSubscription>(COL)
+            .delete_many(filter, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
@@ -0,0 +1,65 @@
+use revolt_result::Result;
+use serde_json::json;
+use sqlx::types::Json;
+
+use crate::{PostgresDb, UnifiedPushSubscription};
+
+use super::AbstractUnifiedPushSubscriptions;
+
+static COL: &str = "unified_push_subscriptions";
+
+#[async_trait]
+impl AbstractUnifiedPushSubscriptions for PostgresDb {
+    /// Register a session's UnifiedPush endpoint, replacing any it already had
+    async fn set_unified_push_subscription(
+        &self,
+        subscription: &UnifiedPushSubscription,
+    ) -> Result<()> {
+        self.upsert_one(COL, json!(subscription.id), |document| {
+            *document = json!(subscription);
+        })
+        .await
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Fetch the UnifiedPush endpoints of every session belonging to the given users
+    async fn fetch_unified_push_subscriptions(
+        &self,
+        users: &[String],
+    ) -> Result<Vec<UnifiedPushSubscription>> {
+        query!(self, find_in, COL, "data->>'user'", users)
+    }
+
+    /// Record how many deliveries in a row have failed for a session's endpoint
+    async fn set_unified_push_failures(&self, id: &str, failures: i32) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": id }), |document| {
+            document["failures"] = json!(failures);
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete a session's UnifiedPush endpoint
+    async fn delete_unified_push_subscription(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Delete the UnifiedPush endpoints of every session belonging to a user,
+    /// optionally keeping the given session's
+    async fn delete_unified_push_subscriptions(
+        &self,
+        user: &str,
+        except: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(&format!(
+            "DELETE FROM {COL} WHERE data->>'user' = $1 AND ($2::JSONB IS NULL OR id <> $2)"
+        ))
+        .bind(user)
+        .bind(except.map(Json))
+        .execute(&self.0)
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("delete_many", COL))
+    }
+}
@@ -0,0 +1,60 @@
+use revolt_result::Result;
+
+use super::AbstractUnifiedPushSubscriptions;
+use crate::{ReferenceDb, UnifiedPushSubscription};
+
+#[async_trait]
+impl AbstractUnifiedPushSubscriptions for ReferenceDb {
+    /// Register a session's UnifiedPush endpoint, replacing any it already had
+    async fn set_unified_push_subscription(
+        &self,
+        subscription: &UnifiedPushSubscription,
+    ) -> Result<()> {
+        let mut unified_push_subscriptions = self.unified_push_subscriptions.lock().await;
+        unified_push_subscriptions.insert(subscription.id.to_string(), subscription.clone());
+        Ok(())
+    }
+
+    /// Fetch the UnifiedPush endpoints of every session belonging to the given users
+    async fn fetch_unified_push_subscriptions(
+        &self,
+        users: &[String],
+    ) -> Result<Vec<UnifiedPushSubscription>> {
+        let unified_push_subscriptions = self.unified_push_subscriptions.lock().await;
+        Ok(unified_push_subscriptions
+            .values()
+            .filter(|subscription| users.contains(&subscription.user))
+            .cloned()
+            .collect())
+    }
+
+    /// Record how many deliveries in a row have failed for a session's endpoint
+    async fn set_unified_push_failures(&self, id: &str, failures: i32) -> Result<()> {
+        let mut unified_push_subscriptions = self.unified_push_subscriptions.lock().await;
+        if let Some(subscription) = unified_push_subscriptions.get_mut(id) {
+            subscription.failures = failures;
+        }
+
+        Ok(())
+    }
+
+    /// Delete a session's UnifiedPush endpoint
+    async fn delete_unified_push_subscription(&self, id: &str) -> Result<()> {
+        let mut unified_push_subscriptions = self.unified_push_subscriptions.lock().await;
+        unified_push_subscriptions.remove(id);
+        Ok(())
+    }
+
+    /// Delete the UnifiedPush endpoints of every session belonging to a user,
+    /// optionally keeping the given session's
+    async fn delete_unified_push_subscriptions(
+        &self,
+        user: &str,
+        except: Option<&str>,
+    ) -> Result<()> {
+        let mut unified_push_subscriptions = self.unified_push_subscriptions.lock().await;
+        unified_push_subscriptions
+            .retain(|id, subscription| subscription.user != user || except == Some(id.as_str()));
+        Ok(())
+    }
+}
@@ -14,15 +14,16 @@ pub trait UserSettingsImpl {
 #[async_trait]
 impl UserSettingsImpl for UserSettings {
     async fn set(self, db: &Database, user: &str) -> Result<()> {
-        db.set_user_settings(user, &self).await?;
+        let tx = db.begin().await?;
+        tx.set_user_settings(user, &self).await?;
 
         EventV1::UserSettingsUpdate {
             id: user.to_string(),
             update: self,
         }
-        .private(user.to_string())
+        .private_outbox(&tx, user.to_string())
         .await;
 
-        Ok(())
+        tx.commit().await
     }
 }
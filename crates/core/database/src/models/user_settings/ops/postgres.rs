@@ -0,0 +1,49 @@
+use revolt_result::Result;
+use serde_json::{json, Value};
+
+use crate::PostgresDb;
+use crate::UserSettings;
+
+use super::AbstractUserSettings;
+
+static COL: &str = "user_settings";
+
+#[async_trait]
+impl AbstractUserSettings for PostgresDb {
+    /// Fetch a subset of user settings
+    async fn fetch_user_settings(&'_ self, id: &str, filter: &'_ [String]) -> Result<UserSettings> {
+        let settings: Option<Value> = query!(self, find_one_by_id, COL, id)?;
+
+        Ok(settings
+            .map(|settings| {
+                filter
+                    .iter()
+                    .filter_map(|key| {
+                        settings
+                            .get(key)
+                            .and_then(|value| serde_json::from_value(value.clone()).ok())
+                            .map(|value| (key.to_owned(), value))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Update a subset of user settings
+    async fn set_user_settings(&self, id: &str, settings: &UserSettings) -> Result<()> {
+        self.upsert_one(COL, json!(id), |document| {
+            if let Some(document) = document.as_object_mut() {
+                for (key, data) in settings {
+                    document.insert(key.to_owned(), json!([data.0, data.1]));
+                }
+            }
+        })
+        .await
+        .map_err(|_| create_database_error!("update_one", "user_settings"))
+    }
+
+    /// Delete all user settings
+    async fn delete_user_settings(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+}
@@ -2,13 +2,24 @@ use std::{collections::HashSet, str::FromStr, time::Duration};
 
 use crate::{events::client::EventV1, Database, File, RatelimitEvent};
 
+use iso8601_timestamp::Timestamp;
 use once_cell::sync::Lazy;
 use rand::seq::SliceRandom;
 use revolt_config::{config, FeaturesLimits};
 use revolt_models::v0;
-use revolt_presence::filter_online;
+use revolt_presence::{filter_idle, filter_online};
 use revolt_result::{create_error, Result};
 use ulid::Ulid;
+use validator::Validate;
+
+/// Maximum number of activities a user may have at once
+const MAX_ACTIVITIES: usize = 5;
+
+/// Maximum number of activity updates per user within [`ACTIVITY_UPDATE_PERIOD`]
+const ACTIVITY_UPDATE_LIMIT: usize = 5;
+
+/// Period over which activity updates are rate limited
+const ACTIVITY_UPDATE_PERIOD: Duration = Duration::from_secs(20);
 
 auto_derived_partial!(
     /// # User
@@ -49,6 +60,13 @@ auto_derived_partial!(
         /// Bot information
         #[serde(skip_serializing_if = "Option::is_none")]
         pub bot: Option<BotInformation>,
+
+        /// Time at which the user was last online
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub last_seen: Option<Timestamp>,
+        /// Whether the user shares when they were last online
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub share_last_seen: bool,
     },
     "PartialUser"
 );
@@ -105,6 +123,31 @@ auto_derived!(
         /// Current presence option
         #[serde(skip_serializing_if = "Option::is_none")]
         pub presence: Option<Presence>,
+        /// Activities the user is currently engaged in
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        pub activities: Vec<Activity>,
+    }
+
+    /// Type of activity
+    pub enum ActivityType {
+        Playing,
+        Listening,
+        Watching,
+    }
+
+    /// Activity the user is engaged in
+    pub struct Activity {
+        /// Type of activity
+        #[serde(rename = "type")]
+        pub activity_type: ActivityType,
+        /// Name of the game, song or show
+        pub name: String,
+        /// Additional details
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub details: Option<String>,
+        /// Time at which the activity started
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub started_at: Option<Timestamp>,
     }
 
     /// User's profile
@@ -165,6 +208,8 @@ impl Default for User {
             flags: Default::default(),
             privileged: Default::default(),
             bot: Default::default(),
+            last_seen: Default::default(),
+            share_last_seen: Default::default(),
         }
     }
 }
@@ -328,6 +373,7 @@ impl User {
         ids: &[String],
     ) -> Result<Vec<v0::User>> {
         let online_ids = filter_online(ids).await;
+        let idle_ids = filter_idle(ids).await;
 
         Ok(db
             .fetch_users(ids)
@@ -335,7 +381,8 @@ impl User {
             .into_iter()
             .map(|user| {
                 let is_online = online_ids.contains(&user.id);
-                user.into_known(perspective, is_online)
+                let is_idle = idle_ids.contains(&user.id);
+                user.into_known(perspective, is_online, is_idle)
             })
             .collect())
     }
@@ -464,24 +511,25 @@ impl User {
         local: RelationshipStatus,
         remote: RelationshipStatus,
     ) -> Result<()> {
-        target.set_relationship(db, self, remote).await?;
-        self.set_relationship(db, target, local).await?;
+        let tx = db.begin().await?;
+        target.set_relationship(&tx, self, remote).await?;
+        self.set_relationship(&tx, target, local).await?;
 
         EventV1::UserRelationship {
             id: target.id.clone(),
-            user: self.clone().into(db, Some(&*target)).await,
+            user: self.clone().into(&tx, Some(&*target)).await,
         }
-        .private(target.id.clone())
+        .private_outbox(&tx, target.id.clone())
         .await;
 
         EventV1::UserRelationship {
             id: self.id.clone(),
-            user: target.clone().into(db, Some(&*self)).await,
+            user: target.clone().into(&tx, Some(&*self)).await,
         }
-        .private(self.id.clone())
+        .private_outbox(&tx, self.id.clone())
         .await;
 
-        Ok(())
+        tx.commit().await
     }
 
     /// Add another user as a friend
@@ -615,23 +663,39 @@ impl User {
         partial: PartialUser,
         remove: Vec<FieldsUser>,
     ) -> Result<()> {
+        let files = self.file_ids();
         for field in &remove {
             self.remove_field(field);
         }
 
         self.apply_options(partial.clone());
-        db.update_user(&self.id, &partial, remove.clone()).await?;
+
+        let tx = db.begin().await?;
+        tx.update_user(&self.id, &partial, remove.clone()).await?;
+        File::detach_unused(&tx, files, self.file_ids()).await?;
 
         EventV1::UserUpdate {
             id: self.id.clone(),
             data: partial.into(),
             clear: remove.into_iter().map(|v| v.into()).collect(),
-            event_id: Some(Ulid::new().to_string()),
         }
-        .p_user(self.id.clone(), db)
+        .p_user_outbox(self.id.clone(), &tx)
         .await;
 
-        Ok(())
+        tx.commit().await
+    }
+
+    /// Ids of the files used by this user
+    fn file_ids(&self) -> Vec<String> {
+        self.avatar
+            .iter()
+            .chain(
+                self.profile
+                    .as_ref()
+                    .and_then(|profile| profile.background.as_ref()),
+            )
+            .map(|file| file.id.to_string())
+            .collect()
     }
 
     /// Remove a field from User object
@@ -681,4 +745,116 @@ impl User {
         )
         .await
     }
+
+    /// Replace the user's current activities
+    ///
+    /// Updates are rate limited as they are fanned out to everyone who can see the user.
+    pub async fn set_activities(
+        &mut self,
+        db: &Database,
+        activities: Vec<v0::Activity>,
+    ) -> Result<()> {
+        if activities.len() > MAX_ACTIVITIES {
+            return Err(create_error!(FailedValidation {
+                error: format!("at most {MAX_ACTIVITIES} activities may be set")
+            }));
+        }
+
+        for activity in &activities {
+            activity.validate().map_err(|error| {
+                create_error!(FailedValidation {
+                    error: error.to_string()
+                })
+            })?;
+        }
+
+        if db
+            .has_ratelimited(
+                &self.id,
+                crate::RatelimitEventType::ActivityUpdate,
+                ACTIVITY_UPDATE_PERIOD,
+                ACTIVITY_UPDATE_LIMIT,
+            )
+            .await?
+        {
+            return Err(create_error!(ActivityUpdateRatelimited));
+        }
+
+        RatelimitEvent::create(
+            db,
+            self.id.clone(),
+            crate::RatelimitEventType::ActivityUpdate,
+        )
+        .await?;
+
+        let mut status = self.status.clone().unwrap_or_default();
+        status.activities = activities.into_iter().map(Into::into).collect();
+
+        self.update(
+            db,
+            PartialUser {
+                status: Some(status),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+    }
+
+    /// Record that the user was last online just now
+    ///
+    /// This does not publish an event, presence changes are broadcast separately.
+    pub async fn mark_last_seen(&mut self, db: &Database) -> Result<()> {
+        let partial = PartialUser {
+            last_seen: Some(Timestamp::now_utc()),
+            ..Default::default()
+        };
+
+        self.apply_options(partial.clone());
+        db.update_user(&self.id, &partial, vec![]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revolt_models::v0;
+
+    use crate::User;
+
+    #[async_std::test]
+    async fn activities() {
+        database_test!(|db| async move {
+            let mut user = User::create(&db, "Player".to_string(), None, None)
+                .await
+                .unwrap();
+
+            let activity = v0::Activity {
+                activity_type: v0::ActivityType::Playing,
+                name: "Chess".to_string(),
+                details: None,
+                started_at: None,
+            };
+
+            user.set_activities(&db, vec![activity.clone()])
+                .await
+                .unwrap();
+
+            let fetched_user = db.fetch_user(&user.id).await.unwrap();
+            assert_eq!(user, fetched_user);
+            assert_eq!(fetched_user.status.unwrap().activities.len(), 1);
+
+            // Too many activities at once
+            assert!(user
+                .set_activities(&db, vec![activity.clone(); 6])
+                .await
+                .is_err());
+
+            // Too many updates in a short time
+            for _ in 0..4 {
+                user.set_activities(&db, vec![]).await.unwrap();
+            }
+
+            assert!(user.set_activities(&db, vec![activity]).await.is_err());
+        });
+    }
 }
@@ -4,7 +4,11 @@ use revolt_result::Result;
 use crate::{FieldsUser, PartialUser, RelationshipStatus, User};
 
 mod mongodb;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod reference;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 #[async_trait]
 pub trait AbstractUsers: Sync + Send {
@@ -0,0 +1,196 @@
+use authifier::models::Session;
+use revolt_result::Result;
+use serde_json::{json, Value};
+use sqlx::types::Json;
+
+use crate::IntoDocumentPath;
+use crate::PostgresDb;
+use crate::{FieldsUser, PartialUser, RelationshipStatus, User};
+
+use super::AbstractUsers;
+
+static COL: &str = "users";
+
+#[async_trait]
+impl AbstractUsers for PostgresDb {
+    /// Insert a new user into the database
+    async fn insert_user(&self, user: &User) -> Result<()> {
+        query!(self, insert_one, COL, &user)
+    }
+
+    /// Fetch a user from the database
+    async fn fetch_user(&self, id: &str) -> Result<User> {
+        query!(self, find_one_by_id, COL, id)?.ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch a user from the database by their username
+    async fn fetch_user_by_username(&self, username: &str, discriminator: &str) -> Result<User> {
+        let mut query = sqlx::QueryBuilder::new(
+            "SELECT data FROM users WHERE lower(data->>'username') = lower(",
+        );
+
+        query
+            .push_bind(username.to_owned())
+            .push(") AND data->>'discriminator' = ")
+            .push_bind(discriminator.to_owned());
+
+        self.find_by_query(query)
+            .await
+            .map_err(|_| create_database_error!("find_one", COL))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| create_error!(NotFound))
+    }
+
+    /// Fetch a session from the database by token
+    async fn fetch_session_by_token(&self, token: &str) -> Result<Session> {
+        self.find_one::<Session>(
+            "sessions",
+            json!({
+                "token": token
+            }),
+        )
+        .await
+        .map_err(|_| create_database_error!("find_one", "sessions"))?
+        .ok_or_else(|| create_error!(InvalidSession))
+    }
+
+    /// Fetch multiple users by their ids
+    async fn fetch_users<'a>(&self, ids: &'a [String]) -> Result<Vec<User>> {
+        query!(self, find_in, COL, "data->>'_id'", ids)
+    }
+
+    /// Fetch all discriminators in use for a username
+    async fn fetch_discriminators_in_use(&self, username: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT data->>'discriminator' FROM users WHERE lower(data->>'username') = lower($1)",
+        )
+        .bind(username)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Fetch ids of users that both users are friends with
+    async fn fetch_mutual_user_ids(&self, user_a: &str, user_b: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT data->>'_id' FROM users WHERE data @> $1")
+            .bind(Json(json!({
+                "relations": [
+                    { "_id": user_a, "status": "Friend" },
+                    { "_id": user_b, "status": "Friend" }
+                ]
+            })))
+            .fetch_all(&self.0)
+            .await
+            .map_err(|_| create_database_error!("find", COL))
+    }
+
+    /// Fetch ids of channels that both users are in
+    async fn fetch_mutual_channel_ids(&self, user_a: &str, user_b: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT data->>'_id' FROM channels
+             WHERE data->>'channel_type' IN ('Group', 'DirectMessage') AND data @> $1",
+        )
+        .bind(Json(json!({
+            "recipients": [user_a, user_b]
+        })))
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("find", "channels"))
+    }
+
+    /// Fetch ids of servers that both users share
+    async fn fetch_mutual_server_ids(&self, user_a: &str, user_b: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT a.id->>'server' FROM server_members a
+             JOIN server_members b ON a.id->>'server' = b.id->>'server'
+             WHERE a.id->>'user' = $1 AND b.id->>'user' = $2",
+        )
+        .bind(user_a)
+        .bind(user_b)
+        .fetch_all(&self.0)
+        .await
+        .map_err(|_| create_database_error!("aggregate", "server_members"))
+    }
+
+    /// Update a user by their id given some data
+    async fn update_user(
+        &self,
+        id: &str,
+        partial: &PartialUser,
+        remove: Vec<FieldsUser>,
+    ) -> Result<()> {
+        query!(
+            self,
+            update_one_by_id,
+            COL,
+            id,
+            partial,
+            remove.iter().map(|x| x as &dyn IntoDocumentPath).collect(),
+            None
+        )
+        .map(|_| ())
+    }
+
+    /// Set relationship with another user
+    ///
+    /// This should use pull_relationship if relationship is None.
+    async fn set_relationship(
+        &self,
+        user_id: &str,
+        target_id: &str,
+        relationship: &RelationshipStatus,
+    ) -> Result<()> {
+        if let RelationshipStatus::None = relationship {
+            return self.pull_relationship(user_id, target_id).await;
+        }
+
+        self.modify_one(COL, json!({ "_id": user_id }), |user| {
+            if let Some(user) = user.as_object_mut() {
+                let relations = user
+                    .entry("relations")
+                    .or_insert_with(|| Value::Array(vec![]));
+
+                if let Some(relations) = relations.as_array_mut() {
+                    relations.retain(|relation| relation["_id"] != target_id);
+                    relations.push(json!({
+                        "_id": target_id,
+                        "status": format!("{relationship:?}")
+                    }));
+                }
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", "user"))
+    }
+
+    /// Remove relationship with another user
+    async fn pull_relationship(&self, user_id: &str, target_id: &str) -> Result<()> {
+        self.modify_one(COL, json!({ "_id": user_id }), |user| {
+            if let Some(relations) = user.get_mut("relations").and_then(Value::as_array_mut) {
+                relations.retain(|relation| relation["_id"] != target_id);
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Delete a user by their id
+    async fn delete_user(&self, id: &str) -> Result<()> {
+        query!(self, delete_one_by_id, COL, id).map(|_| ())
+    }
+
+    /// Remove push subscription for a session by session id (TODO: remove)
+    async fn remove_push_subscription_by_session_id(&self, session_id: &str) -> Result<()> {
+        self.modify_one("sessions", json!({ "_id": session_id }), |session| {
+            if let Some(session) = session.as_object_mut() {
+                session.remove("subscription");
+            }
+        })
+        .await
+        .map(|_| ())
+        .map_err(|_| create_database_error!("update_one", COL))
+    }
+}
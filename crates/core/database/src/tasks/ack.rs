@@ -1,14 +1,12 @@
 // Queue Type: Debounced
-use crate::Database;
+use crate::{Database, Job, JobPayload, JobQueue};
 
-use deadqueue::limited::Queue;
-use once_cell::sync::Lazy;
 use std::{collections::HashMap, time::Duration};
 
-use super::DelayedTask;
+use super::{jobs, push_coalesce, DelayedTask, DEBOUNCE_HOLD};
 
 /// Enumeration of possible events
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum AckEvent {
     /// Add mentions for a user in a channel
     AddMention {
@@ -23,33 +21,24 @@ pub enum AckEvent {
     },
 }
 
-/// Task information
-struct Data {
-    /// Channel to ack
-    channel: String,
-    /// User to ack for
-    user: String,
-    /// Event
-    event: AckEvent,
-}
-
 #[derive(Debug)]
 struct Task {
     event: AckEvent,
+    /// Jobs merged into this task
+    jobs: Vec<Job>,
 }
 
-static Q: Lazy<Queue<Data>> = Lazy::new(|| Queue::new(10_000));
-
 /// Queue a new task for a worker
-pub async fn queue(channel: String, user: String, event: AckEvent) {
-    Q.try_push(Data {
-        channel,
-        user,
-        event,
-    })
-    .ok();
-
-    info!("Queue is using {} slots from {}.", Q.len(), Q.capacity());
+pub async fn queue(db: &Database, channel: String, user: String, event: AckEvent) {
+    jobs::enqueue(
+        db,
+        JobPayload::Ack {
+            channel,
+            user,
+            event,
+        },
+    )
+    .await;
 }
 
 /// Start a new worker
@@ -68,7 +57,7 @@ pub async fn worker(db: Database) {
         // Commit any due tasks to the database.
         for key in &keys {
             if let Some(task) = tasks.remove(key) {
-                let Task { event } = task.data;
+                let Task { event, jobs } = task.data;
                 let (user, channel) = key;
 
                 if let Err(err) = match &event {
@@ -79,8 +68,27 @@ pub async fn worker(db: Database) {
                     }
                 } {
                     error!("{err:?} for {event:?}. ({user}, {channel})");
+                    for job in &jobs {
+                        jobs::fail(&db, job, &err).await;
+                    }
                 } else {
                     info!("User {user} ack in {channel} with {event:?}");
+
+                    // Clear notifications already delivered to the user's other devices.
+                    if let AckEvent::AckMessage { .. } = &event {
+                        match db.take_notified_unreads(user, &[channel.clone()]).await {
+                            Ok(channels) => {
+                                for channel in channels {
+                                    push_coalesce::dismiss(&db, user.clone(), channel).await;
+                                }
+                            }
+                            Err(err) => error!(
+                                "{err:?} finding notifications to dismiss. ({user}, {channel})"
+                            ),
+                        }
+                    }
+
+                    jobs::complete(&db, &jobs).await;
                 }
             }
         }
@@ -89,12 +97,17 @@ pub async fn worker(db: Database) {
         keys.clear();
 
         // Queue incoming tasks.
-        while let Some(Data {
-            channel,
-            user,
-            mut event,
-        }) = Q.try_pop()
-        {
+        for job in jobs::try_claim(&db, JobQueue::Ack, DEBOUNCE_HOLD).await {
+            let JobPayload::Ack {
+                channel,
+                user,
+                mut event,
+            } = job.payload.clone()
+            else {
+                jobs::fail(&db, &job, "not an ack job").await;
+                continue;
+            };
+
             let key = (user, channel);
             if let Some(task) = tasks.get_mut(&key) {
                 task.delay();
@@ -111,8 +124,16 @@ pub async fn worker(db: Database) {
                         task.data.event = event;
                     }
                 }
+
+                task.data.jobs.push(job);
             } else {
-                tasks.insert(key, DelayedTask::new(Task { event }));
+                tasks.insert(
+                    key,
+                    DelayedTask::new(Task {
+                        event,
+                        jobs: vec![job],
+                    }),
+                );
             }
         }
 
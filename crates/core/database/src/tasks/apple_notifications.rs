@@ -4,17 +4,20 @@ use base64::{
     engine::{self},
     Engine as _,
 };
-use deadqueue::limited::Queue;
-use once_cell::sync::Lazy;
-use revolt_a2::{Client, ClientConfig, DefaultNotificationBuilder};
-use revolt_a2::{Error, ErrorBody, ErrorReason, NotificationBuilder, Response};
-use revolt_config::config;
+use revolt_a2::{Client, ClientConfig, CollapseId, DefaultNotificationBuilder};
+use revolt_a2::{
+    Error, ErrorBody, ErrorReason, NotificationBuilder, NotificationOptions, Priority, PushType,
+    Response,
+};
+use revolt_config::{config, ApiApn};
 use revolt_models::v0::PushNotification;
 
-use crate::Database;
+use crate::{Database, JobPayload, JobQueue};
+
+use super::jobs;
 
 /// Task information
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApnTask {
     /// Session Id
     session_id: String,
@@ -30,6 +33,10 @@ pub struct ApnTask {
 
     /// Thread Id
     thread_id: String,
+
+    /// Whether to remove notifications in the thread rather than show one
+    #[serde(default)]
+    dismiss: bool,
 }
 
 impl ApnTask {
@@ -44,25 +51,41 @@ impl ApnTask {
             title: notification.author.to_string(),
             body: notification.body.to_string(),
             thread_id: notification.tag.to_string(),
+            dismiss: false,
+        }
+    }
+
+    pub fn dismissal(session_id: String, device_token: String, thread_id: &str) -> ApnTask {
+        ApnTask {
+            session_id,
+            device_token,
+            title: String::new(),
+            body: String::new(),
+            thread_id: thread_id.to_string(),
+            dismiss: true,
         }
     }
 }
 
-static Q: Lazy<Queue<ApnTask>> = Lazy::new(|| Queue::new(10_000));
+/// Whether APN keys have been configured
+fn is_configured(apn: &ApiApn) -> bool {
+    !(apn.pkcs8.is_empty() || apn.key_id.is_empty() || apn.team_id.is_empty())
+}
 
 /// Queue a new task for a worker
-pub async fn queue(task: ApnTask) {
-    Q.try_push(task).ok();
-    info!("Queue is using {} slots from {}.", Q.len(), Q.capacity());
+pub async fn queue(db: &Database, task: ApnTask) {
+    // Nothing would ever process the job
+    if !is_configured(&config().await.api.apn) {
+        return;
+    }
+
+    jobs::enqueue(db, JobPayload::AppleNotification(task)).await;
 }
 
 /// Start a new worker
-pub async fn worker(db: Database) {
+pub async fn worker(db: Database, authifier_db: authifier::Database) {
     let config = config().await;
-    if config.api.apn.pkcs8.is_empty()
-        || config.api.apn.key_id.is_empty()
-        || config.api.apn.team_id.is_empty()
-    {
+    if !is_configured(&config.api.apn) {
         eprintln!("Missing APN keys.");
         return;
     }
@@ -80,34 +103,78 @@ pub async fn worker(db: Database) {
     .expect("could not create APN client");
 
     loop {
-        let task = Q.pop().await;
-        let payload = DefaultNotificationBuilder::new()
-            .set_title(&task.title)
-            .set_body(&task.body)
-            .set_thread_id(&task.thread_id)
-            .build(&task.device_token, Default::default());
-
-        if let Err(err) = client.send(payload).await {
-            match err {
-                Error::ResponseError(Response {
-                    error:
-                        Some(ErrorBody {
-                            reason: ErrorReason::BadDeviceToken | ErrorReason::Unregistered,
-                            ..
-                        }),
-                    ..
-                }) => {
-                    if let Err(err) = db
-                        .remove_push_subscription_by_session_id(&task.session_id)
-                        .await
-                    {
+        for job in jobs::claim(&db, JobQueue::AppleNotifications).await {
+            let JobPayload::AppleNotification(task) = &job.payload else {
+                jobs::fail(&db, &job, "not an apple_notifications job").await;
+                continue;
+            };
+
+            let payload = if task.dismiss {
+                // Silent push, the app removes delivered notifications in the thread itself
+                let mut payload = DefaultNotificationBuilder::new()
+                    .set_content_available()
+                    .build(
+                        &task.device_token,
+                        NotificationOptions {
+                            apns_push_type: Some(PushType::Background),
+                            apns_priority: Some(Priority::Normal),
+                            ..Default::default()
+                        },
+                    );
+
+                if let Err(err) = payload.add_custom_data("dismiss", &task.thread_id) {
+                    jobs::fail(&db, &job, err).await;
+                    continue;
+                }
+
+                payload
+            } else {
+                // Newer notifications in a thread replace older ones on the device
+                DefaultNotificationBuilder::new()
+                    .set_title(&task.title)
+                    .set_body(&task.body)
+                    .set_thread_id(&task.thread_id)
+                    .build(
+                        &task.device_token,
+                        NotificationOptions {
+                            apns_collapse_id: CollapseId::new(&task.thread_id).ok(),
+                            ..Default::default()
+                        },
+                    )
+            };
+
+            if let Err(err) = client.send(payload).await {
+                match err {
+                    Error::ResponseError(Response {
+                        error:
+                            Some(ErrorBody {
+                                reason: ErrorReason::BadDeviceToken | ErrorReason::Unregistered,
+                                ..
+                            }),
+                        ..
+                    }) => {
+                        // Sessions belong to Authifier, which may not keep them in our database.
+                        let removed = match authifier_db.find_session(&task.session_id).await {
+                            Ok(mut session) => {
+                                session.subscription = None;
+                                authifier_db.save_session(&session).await
+                            }
+                            Err(err) => Err(err),
+                        };
+
+                        if let Err(err) = removed {
+                            error!("Failed to remove APN subscription! {:?}", err);
+                        }
+                    }
+                    err => {
                         revolt_config::capture_error(&err);
+                        jobs::fail(&db, &job, err).await;
+                        continue;
                     }
                 }
-                err => {
-                    revolt_config::capture_error(&err);
-                }
             }
+
+            jobs::complete(&db, &[job]).await;
         }
     }
 }
@@ -0,0 +1,749 @@
+// Queue Type: Polled
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use authifier::models::EmailVerification;
+use lettre::{
+    message::{
+        header::{Header, HeaderName, HeaderValue},
+        Mailbox, MultiPart,
+    },
+    transport::smtp::authentication::Credentials,
+    SmtpTransport, Transport,
+};
+use revolt_config::{config, ApiSmtp};
+use revolt_models::v0::MessageSort;
+use revolt_presence::filter_online;
+use revolt_result::Result;
+use ulid::Ulid;
+
+use crate::{
+    outbox_time, Channel, ChannelUnread, Database, Message, MessageFilter, MessageQuery,
+    MessageTimePeriod, NotificationSettings, NotificationTrigger, RelationshipStatus, User,
+};
+
+/// Most characters of a message to include in a digest
+const EXCERPT_LENGTH: usize = 200;
+
+/// Key of the synced client setting which holds muted servers and channels
+const SYNCED_NOTIFICATIONS: &str = "notifications";
+
+/// Message included in a digest
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    author: String,
+    channel: String,
+    excerpt: String,
+    url: String,
+}
+
+/// Rendered digest email
+#[derive(Debug)]
+struct Rendered {
+    subject: String,
+    text: String,
+    html: String,
+    unsubscribe: String,
+}
+
+/// `List-Unsubscribe` header pointing at a user's unsubscribe link (RFC 2369)
+#[derive(Debug, Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ListUnsubscribe(
+            s.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string(),
+        ))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), format!("<{}>", self.0))
+    }
+}
+
+/// `List-Unsubscribe-Post` header allowing mail clients to unsubscribe in one click (RFC 8058)
+#[derive(Debug, Clone)]
+struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_: &str) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ListUnsubscribePost)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
+
+/// Servers and channels a user muted in their synced client settings
+#[derive(Debug, Default)]
+struct SyncedMutes {
+    /// Notification state of each server
+    servers: HashMap<String, String>,
+    /// Notification state of each channel, taking precedence over their server
+    channels: HashMap<String, String>,
+}
+
+impl SyncedMutes {
+    /// Parse the value of the synced notification setting
+    fn parse(value: &str) -> SyncedMutes {
+        #[derive(Deserialize, Default)]
+        struct Synced {
+            #[serde(default)]
+            server: HashMap<String, String>,
+            #[serde(default)]
+            channel: HashMap<String, String>,
+        }
+
+        let synced: Synced = serde_json::from_str(value).unwrap_or_default();
+        SyncedMutes {
+            servers: synced.server,
+            channels: synced.channel,
+        }
+    }
+
+    /// Whether a channel is muted
+    fn is_muted(&self, server: Option<&str>, channel: &str) -> bool {
+        self.channels
+            .get(channel)
+            .or_else(|| server.and_then(|server| self.servers.get(server)))
+            .is_some_and(|state| state == "muted" || state == "none")
+    }
+}
+
+/// Escape text for use in HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Shorten message content to fit in a digest
+fn excerpt(message: &Message) -> Option<String> {
+    match &message.content {
+        Some(content) if !content.trim().is_empty() => {
+            let content = content.trim();
+            if content.chars().count() > EXCERPT_LENGTH {
+                Some(format!(
+                    "{}…",
+                    content.chars().take(EXCERPT_LENGTH).collect::<String>()
+                ))
+            } else {
+                Some(content.to_string())
+            }
+        }
+        _ if message
+            .attachments
+            .as_ref()
+            .is_some_and(|attachments| !attachments.is_empty()) =>
+        {
+            Some("Sent an attachment.".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Render a digest from its entries
+fn render(entries: &[Entry], app: &str, unsubscribe: &str) -> Rendered {
+    let count = if entries.len() == 1 {
+        "1 message".to_string()
+    } else {
+        format!("{} messages", entries.len())
+    };
+
+    let fill = |template: &str, entries: &str, escape: fn(&str) -> String| {
+        template
+            .replace("{{count}}", &count)
+            .replace("{{app}}", &escape(app))
+            .replace("{{unsubscribe}}", &escape(unsubscribe))
+            .replace("{{entries}}", entries)
+    };
+
+    let entry = |template: &str, entry: &Entry, escape: fn(&str) -> String| {
+        template
+            .replace("{{author}}", &escape(&entry.author))
+            .replace("{{channel}}", &escape(&entry.channel))
+            .replace("{{excerpt}}", &escape(&entry.excerpt))
+            .replace("{{url}}", &escape(&entry.url))
+    };
+
+    let plain = |text: &str| text.to_string();
+    let text_entries: String = entries
+        .iter()
+        .map(|e| entry(include_str!("templates/entry.txt"), e, plain))
+        .collect();
+    let html_entries: String = entries
+        .iter()
+        .map(|e| entry(include_str!("templates/entry.html"), e, escape_html))
+        .collect();
+
+    Rendered {
+        subject: format!("You missed {count} on Revolt"),
+        text: fill(include_str!("templates/digest.txt"), &text_entries, plain),
+        html: fill(
+            include_str!("templates/digest.html"),
+            &html_entries,
+            escape_html,
+        ),
+        unsubscribe: unsubscribe.to_string(),
+    }
+}
+
+/// Gather the mentions and direct messages a user hasn't read since the given time
+///
+/// Returns the messages, oldest first, along with the channels they were sent in.
+async fn collect(
+    db: &Database,
+    user: &User,
+    settings: &NotificationSettings,
+    since: i64,
+    limit: usize,
+) -> Result<(Vec<Message>, HashMap<String, Channel>)> {
+    let after = Ulid::from_parts(since as u64, 0).to_string();
+    let unreads: HashMap<String, ChannelUnread> = db
+        .fetch_unreads(&user.id)
+        .await?
+        .into_iter()
+        .map(|unread| (unread.id.channel.clone(), unread))
+        .collect();
+
+    let mut channels: HashMap<String, Channel> = db
+        .find_direct_messages(&user.id)
+        .await?
+        .into_iter()
+        .map(|channel| (channel.id(), channel))
+        .collect();
+
+    // Mentions which haven't been acknowledged
+    let mentions: Vec<String> = unreads
+        .values()
+        .flat_map(|unread| unread.mentions.iter().flatten())
+        .filter(|id| **id > after)
+        .cloned()
+        .collect();
+
+    let mut messages = if mentions.is_empty() {
+        vec![]
+    } else {
+        db.fetch_messages_by_id(&mentions).await?
+    };
+
+    // Direct messages which haven't been read
+    for channel in channels.values() {
+        let (Channel::DirectMessage {
+            id,
+            last_message_id,
+            ..
+        }
+        | Channel::Group {
+            id,
+            last_message_id,
+            ..
+        }) = channel
+        else {
+            continue;
+        };
+
+        let read = match unreads.get(id).and_then(|unread| unread.last_id.as_ref()) {
+            Some(last_id) if *last_id > after => last_id.clone(),
+            _ => after.clone(),
+        };
+
+        if last_message_id.as_ref().is_none_or(|last| *last <= read) {
+            continue;
+        }
+
+        messages.extend(
+            db.fetch_messages(MessageQuery {
+                limit: Some(limit as i64),
+                filter: MessageFilter {
+                    channel: Some(id.clone()),
+                    ..Default::default()
+                },
+                time_period: MessageTimePeriod::Absolute {
+                    before: None,
+                    after: Some(read),
+                    sort: Some(MessageSort::Latest),
+                },
+            })
+            .await?,
+        );
+    }
+
+    // Mentions may be in server channels we don't know about yet
+    let missing: Vec<String> = messages
+        .iter()
+        .map(|message| message.channel.clone())
+        .filter(|channel| !channels.contains_key(channel))
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+
+    if !missing.is_empty() {
+        channels.extend(
+            db.fetch_channels(&missing)
+                .await?
+                .into_iter()
+                .map(|channel| (channel.id(), channel)),
+        );
+    }
+
+    let mutes = db
+        .fetch_user_settings(&user.id, &[SYNCED_NOTIFICATIONS.to_string()])
+        .await?
+        .get(SYNCED_NOTIFICATIONS)
+        .map(|(_, value)| SyncedMutes::parse(value))
+        .unwrap_or_default();
+
+    let mut seen = HashSet::new();
+    messages.retain(|message| {
+        if message.author == user.id || !seen.insert(message.id.clone()) {
+            return false;
+        }
+
+        if matches!(
+            user.relationship_with(&message.author),
+            RelationshipStatus::Blocked | RelationshipStatus::BlockedOther
+        ) {
+            return false;
+        }
+
+        let server = match channels.get(&message.channel) {
+            Some(Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. }) => {
+                Some(server.as_str())
+            }
+            _ => None,
+        };
+
+        let mut triggers = vec![NotificationTrigger::Message];
+        if message
+            .mentions
+            .as_ref()
+            .is_some_and(|mentions| mentions.contains(&user.id))
+        {
+            triggers.push(NotificationTrigger::Mention);
+        }
+
+        if server.is_some() && message.mentions_everyone() {
+            triggers.push(NotificationTrigger::Everyone);
+        }
+
+        !mutes.is_muted(server, &message.channel)
+            && triggers
+                .into_iter()
+                .any(|trigger| settings.should_notify(server, &message.channel, trigger))
+    });
+
+    // Keep the most recent messages
+    messages.sort_by(|a, b| a.id.cmp(&b.id));
+    if messages.len() > limit {
+        messages.drain(..messages.len() - limit);
+    }
+
+    Ok((messages, channels))
+}
+
+/// Describe messages for a digest
+async fn describe(
+    db: &Database,
+    messages: &[Message],
+    channels: &HashMap<String, Channel>,
+    app: &str,
+) -> Result<Vec<Entry>> {
+    let author_ids: Vec<String> = messages
+        .iter()
+        .map(|message| message.author.clone())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+
+    let authors: HashMap<String, String> = db
+        .fetch_users(&author_ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user.display_name.unwrap_or(user.username)))
+        .collect();
+
+    Ok(messages
+        .iter()
+        .filter_map(|message| {
+            Some(Entry {
+                author: match &message.webhook {
+                    Some(webhook) => webhook.name.clone(),
+                    None => authors
+                        .get(&message.author)
+                        .cloned()
+                        .unwrap_or_else(|| "Someone".to_string()),
+                },
+                channel: match channels.get(&message.channel) {
+                    Some(
+                        Channel::TextChannel { name, .. } | Channel::VoiceChannel { name, .. },
+                    ) => {
+                        format!("#{name}")
+                    }
+                    Some(Channel::Group { name, .. }) => name.clone(),
+                    _ => "a direct message".to_string(),
+                },
+                excerpt: excerpt(message)?,
+                url: format!("{app}/channel/{}/{}", message.channel, message.id),
+            })
+        })
+        .collect())
+}
+
+/// Send an email through the configured SMTP server
+async fn send(smtp: ApiSmtp, to: String, rendered: Rendered) -> std::result::Result<(), String> {
+    let mut email = lettre::Message::builder()
+        .from(
+            smtp.from_address
+                .parse::<Mailbox>()
+                .map_err(|err| err.to_string())?,
+        )
+        .to(to.parse::<Mailbox>().map_err(|err| err.to_string())?)
+        .subject(rendered.subject)
+        .header(ListUnsubscribe(rendered.unsubscribe))
+        .header(ListUnsubscribePost);
+
+    if let Some(reply_to) = &smtp.reply_to {
+        email = email.reply_to(reply_to.parse::<Mailbox>().map_err(|err| err.to_string())?);
+    }
+
+    let email = email
+        .multipart(MultiPart::alternative_plain_html(
+            rendered.text,
+            rendered.html,
+        ))
+        .map_err(|err| err.to_string())?;
+
+    async_std::task::spawn_blocking(move || {
+        let mut transport = if smtp.use_tls.unwrap_or(true) {
+            SmtpTransport::relay(&smtp.host).map_err(|err| err.to_string())?
+        } else {
+            SmtpTransport::builder_dangerous(&smtp.host)
+        };
+
+        if let Some(port) = smtp.port {
+            transport = transport.port(port as u16);
+        }
+
+        if !smtp.username.is_empty() {
+            transport = transport.credentials(Credentials::new(smtp.username, smtp.password));
+        }
+
+        transport
+            .build()
+            .send(&email)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Send a digest to every subscribed user who has been offline for long enough
+async fn send_digests(db: &Database, authifier_db: &authifier::Database) -> Result<()> {
+    let config = config().await;
+    let now = outbox_time();
+    let threshold = (config.api.email_digest.offline_threshold * 1000) as i64;
+
+    // Users are sent at most one digest per threshold
+    let subscribers: Vec<NotificationSettings> = db
+        .fetch_email_digest_subscribers()
+        .await?
+        .into_iter()
+        .filter(|settings| {
+            settings
+                .email_digest
+                .as_ref()
+                .is_some_and(|digest| now - digest.last_sent >= threshold)
+        })
+        .collect();
+
+    if subscribers.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<String> = subscribers
+        .iter()
+        .map(|settings| settings.id.clone())
+        .collect();
+    let online_ids = filter_online(&ids).await;
+    let users: HashMap<String, User> = db
+        .fetch_users(&ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id.clone(), user))
+        .collect();
+
+    for settings in subscribers {
+        let (Some(digest), Some(user)) = (&settings.email_digest, users.get(&settings.id)) else {
+            continue;
+        };
+
+        if online_ids.contains(&user.id) {
+            continue;
+        }
+
+        // Anything sent while the user was last online has been seen
+        let last_seen = user
+            .last_seen
+            .as_ref()
+            .map(|timestamp| (timestamp.assume_utc().unix_timestamp_nanos() / 1_000_000) as i64)
+            .unwrap_or_default();
+
+        if now - last_seen < threshold {
+            continue;
+        }
+
+        let (messages, channels) = collect(
+            db,
+            user,
+            &settings,
+            digest.last_sent.max(last_seen),
+            config.api.email_digest.max_messages,
+        )
+        .await?;
+
+        let entries = describe(db, &messages, &channels, &config.hosts.app).await?;
+        if entries.is_empty() {
+            continue;
+        }
+
+        let account = match authifier_db.find_account(&user.id).await {
+            Ok(account) => account,
+            Err(err) => {
+                error!("Failed to fetch account for {}: {err:?}", user.id);
+                continue;
+            }
+        };
+
+        if account.disabled || matches!(account.verification, EmailVerification::Pending { .. }) {
+            continue;
+        }
+
+        let rendered = render(
+            &entries,
+            &config.hosts.app,
+            &format!(
+                "{}/sync/notifications/unsubscribe/{}",
+                config.hosts.api, digest.token
+            ),
+        );
+
+        // Claim this window so no other worker sends the same digest
+        if !db
+            .claim_email_digest(&user.id, digest.last_sent, now)
+            .await?
+        {
+            continue;
+        }
+
+        match send(config.api.smtp.clone(), account.email, rendered).await {
+            Ok(_) => info!(
+                "Sent email digest of {} messages to {}.",
+                entries.len(),
+                user.id
+            ),
+            Err(err) => {
+                error!("Failed to send email digest to {}: {err}", user.id);
+
+                // Release the window to be retried on the next run
+                db.claim_email_digest(&user.id, now, digest.last_sent)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Start a new worker
+pub async fn worker(db: Database, authifier_db: authifier::Database) {
+    if config().await.api.smtp.host.is_empty() {
+        info!("No SMTP server configured, email digests are disabled.");
+        return;
+    }
+
+    loop {
+        if let Err(err) = send_digests(&db, &authifier_db).await {
+            error!("Failed to send email digests: {err:?}");
+        }
+
+        async_std::task::sleep(Duration::from_secs(
+            config().await.api.email_digest.interval,
+        ))
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use revolt_config::ApiSmtp;
+
+    use crate::Message;
+
+    use super::{excerpt, render, send, Entry, SyncedMutes};
+
+    /// Accept one SMTP connection on a local port, returning the port and the transcript
+    fn smtp_sink() -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            stream.write_all(b"220 localhost\r\n").unwrap();
+
+            let mut transcript = String::new();
+            let mut line = String::new();
+            let mut in_data = false;
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                transcript.push_str(&line);
+
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        stream.write_all(b"250 OK\r\n").unwrap();
+                        tx.send(transcript.clone()).unwrap();
+                    }
+                } else if line.starts_with("DATA") {
+                    in_data = true;
+                    stream.write_all(b"354 Go ahead\r\n").unwrap();
+                } else if line.starts_with("QUIT") {
+                    stream.write_all(b"221 Bye\r\n").ok();
+                    break;
+                } else {
+                    stream.write_all(b"250 OK\r\n").unwrap();
+                }
+
+                line.clear();
+            }
+        });
+
+        (port, rx)
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                author: "alice".to_string(),
+                channel: "#general".to_string(),
+                excerpt: "<b>hey</b> @you".to_string(),
+                url: "https://app/channel/a/1".to_string(),
+            },
+            Entry {
+                author: "bob".to_string(),
+                channel: "a direct message".to_string(),
+                excerpt: "are you around?".to_string(),
+                url: "https://app/channel/b/2".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn synced_mutes() {
+        let mutes = SyncedMutes::parse(
+            r#"{"server":{"muted":"muted","quiet":"mention"},"channel":{"loud":"all","off":"none"}}"#,
+        );
+
+        assert!(mutes.is_muted(Some("muted"), "channel"));
+        assert!(!mutes.is_muted(Some("quiet"), "channel"));
+        assert!(mutes.is_muted(None, "off"));
+        // Channel preferences take precedence over their server
+        assert!(!mutes.is_muted(Some("muted"), "loud"));
+
+        // Unreadable settings mute nothing
+        assert!(!SyncedMutes::parse("not json").is_muted(Some("muted"), "off"));
+    }
+
+    #[test]
+    fn excerpts() {
+        let mut message = Message {
+            content: Some(format!("  {}  ", "a".repeat(300))),
+            ..Default::default()
+        };
+        assert_eq!(
+            excerpt(&message),
+            Some(format!("{}…", "a".repeat(super::EXCERPT_LENGTH)))
+        );
+
+        message.content = None;
+        assert_eq!(excerpt(&message), None);
+    }
+
+    #[test]
+    fn render_digest() {
+        let rendered = render(&entries(), "https://app", "https://api/unsubscribe/token");
+
+        assert_eq!(rendered.subject, "You missed 2 messages on Revolt");
+        assert!(rendered
+            .text
+            .contains("alice in #general:\n<b>hey</b> @you\nhttps://app/channel/a/1"));
+        assert!(rendered.text.contains("https://api/unsubscribe/token"));
+
+        // Message content is escaped in HTML
+        assert!(rendered.html.contains("&lt;b&gt;hey&lt;/b&gt; @you"));
+        assert!(!rendered.html.contains("<b>hey</b>"));
+        assert!(rendered.html.contains("href=\"https://app/channel/b/2\""));
+    }
+
+    #[async_std::test]
+    async fn send_to_smtp_sink() {
+        let (port, transcript) = smtp_sink();
+        let smtp = ApiSmtp {
+            host: "127.0.0.1".to_string(),
+            username: String::new(),
+            password: String::new(),
+            from_address: "Revolt <noreply@revolt.chat>".to_string(),
+            reply_to: None,
+            port: Some(port as i32),
+            use_tls: Some(false),
+        };
+
+        send(
+            smtp,
+            "user@example.com".to_string(),
+            render(&entries(), "https://app", "https://api/unsubscribe/token"),
+        )
+        .await
+        .unwrap();
+
+        let transcript = transcript.recv().unwrap();
+        assert!(transcript.contains("RCPT TO:<user@example.com>"));
+        assert!(transcript.contains("Subject: You missed 2 messages on Revolt"));
+        assert!(transcript.contains("Content-Type: multipart/alternative"));
+        assert!(transcript.contains("List-Unsubscribe: <https://api/unsubscribe/token>"));
+        assert!(transcript.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+    }
+}
@@ -0,0 +1,27 @@
+// Queue Type: Polled
+use crate::Database;
+
+use revolt_config::config;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Start a new worker
+pub async fn worker(db: Database) {
+    loop {
+        let config = config().await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_millis() as i64;
+
+        // Files are only deleted once they have gone unused for the whole grace period,
+        // so anything detached by mistake can be attached again in the meantime.
+        let cutoff = now - (config.api.files.orphan_grace_period * 1000) as i64;
+        match db.sweep_orphaned_attachments(now, cutoff).await {
+            Ok(0) => {}
+            Ok(deleted) => info!("Marked {deleted} orphaned files as deleted."),
+            Err(err) => error!("Failed to sweep orphaned files: {err:?}"),
+        }
+
+        async_std::task::sleep(Duration::from_secs(config.api.files.sweep_interval)).await;
+    }
+}
@@ -0,0 +1,25 @@
+// Queue Type: Polled
+use crate::{outbox_time, Database};
+
+use revolt_config::config;
+use std::time::Duration;
+
+/// How often to sweep for expired dead-lettered jobs
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Start a new worker
+pub async fn worker(db: Database) {
+    loop {
+        let config = config().await;
+
+        // Dead-lettered jobs are kept for a while so failures can be looked into.
+        let cutoff = outbox_time() - (config.api.workers.dead_job_retention * 1000) as i64;
+        match db.delete_buried_jobs(cutoff).await {
+            Ok(0) => {}
+            Ok(deleted) => info!("Deleted {deleted} expired dead-lettered jobs."),
+            Err(err) => error!("Failed to sweep dead-lettered jobs: {err:?}"),
+        }
+
+        async_std::task::sleep(SWEEP_INTERVAL).await;
+    }
+}
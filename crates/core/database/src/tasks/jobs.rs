@@ -0,0 +1,168 @@
+// Queue Type: Polled
+use crate::{job_backoff, outbox_time, Database, Job, JobPayload, JobQueue};
+
+use deadqueue::limited::Queue;
+use once_cell::sync::Lazy;
+use revolt_config::config;
+use std::{collections::HashMap, fmt::Debug, time::Duration};
+
+/// How long to wait between checks when nothing has woken us up
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of jobs, labelled by queue and what happened to them
+#[cfg(feature = "prometheus")]
+pub static JOBS: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::IntCounterVec::new(
+        prometheus::Opts::new("revolt_jobs_total", "Number of background jobs"),
+        &["queue", "outcome"],
+    )
+    .expect("valid metric")
+});
+
+static WAKE: Lazy<HashMap<JobQueue, Queue<()>>> = Lazy::new(|| {
+    JobQueue::ALL
+        .into_iter()
+        .map(|queue| (queue, Queue::new(1)))
+        .collect()
+});
+
+/// Record what happened to a job
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+fn record(queue: JobQueue, outcome: &str) {
+    #[cfg(feature = "prometheus")]
+    JOBS.with_label_values(&[queue.as_str(), outcome]).inc();
+}
+
+/// Let workers on a queue know there are new jobs
+pub fn wake(queue: JobQueue) {
+    WAKE[&queue].try_push(()).ok();
+}
+
+/// Add a new job to its queue
+pub async fn enqueue(db: &Database, payload: JobPayload) {
+    let queue = payload.queue();
+    match Job::create(db, payload).await {
+        Ok(_) => record(queue, "enqueued"),
+        Err(err) => error!("Failed to enqueue {} job: {err:?}", queue.as_str()),
+    }
+}
+
+/// Add a new job to its queue which won't be processed until the given time
+pub async fn schedule(db: &Database, payload: JobPayload, available_at: i64) {
+    let queue = payload.queue();
+    match Job::schedule(db, payload, available_at).await {
+        Ok(_) => record(queue, "scheduled"),
+        Err(err) => error!("Failed to schedule {} job: {err:?}", queue.as_str()),
+    }
+}
+
+/// Claim any available jobs on a queue, holding them for the visibility timeout plus `hold`
+///
+/// Jobs which have already been attempted too many times (e.g. because
+/// they crashed their worker) are dead-lettered rather than returned.
+pub async fn try_claim(db: &Database, queue: JobQueue, hold: Duration) -> Vec<Job> {
+    let config = config().await.api.workers;
+    let lease = Duration::from_secs(config.visibility_timeout) + hold;
+
+    let jobs = match db.claim_jobs(queue, config.batch_size, lease).await {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            error!("Failed to claim {} jobs: {err:?}", queue.as_str());
+            return vec![];
+        }
+    };
+
+    let mut claimed = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        if job.attempts > config.max_attempts {
+            bury(db, &job, "claimed too many times without completing").await;
+        } else {
+            claimed.push(job);
+        }
+    }
+
+    claimed
+}
+
+/// Wait until jobs are available on a queue and claim them
+pub async fn claim(db: &Database, queue: JobQueue) -> Vec<Job> {
+    loop {
+        let jobs = try_claim(db, queue, Duration::ZERO).await;
+        if !jobs.is_empty() {
+            return jobs;
+        }
+
+        wait(queue, POLL_INTERVAL).await;
+    }
+}
+
+/// Wait until workers on a queue are woken up, or the timeout passes
+pub async fn wait(queue: JobQueue, timeout: Duration) {
+    async_std::future::timeout(timeout, WAKE[&queue].pop())
+        .await
+        .ok();
+}
+
+/// Remove jobs which were processed successfully
+pub async fn complete(db: &Database, jobs: &[Job]) {
+    let Some(queue) = jobs.first().map(|job| job.queue) else {
+        return;
+    };
+
+    let ids: Vec<String> = jobs.iter().map(|job| job.id.clone()).collect();
+    if let Err(err) = db.delete_jobs(&ids).await {
+        // Jobs will be processed again once their lease expires.
+        error!("Failed to complete {} jobs: {err:?}", queue.as_str());
+    } else {
+        for _ in jobs {
+            record(queue, "completed");
+        }
+    }
+}
+
+/// Schedule a failed job to be retried, or dead-letter it if it has been attempted too many times
+pub async fn fail(db: &Database, job: &Job, error: impl Debug) {
+    let config = config().await.api.workers;
+    let error = format!("{error:?}");
+
+    if job.attempts >= config.max_attempts {
+        bury(db, job, &error).await;
+        return;
+    }
+
+    let backoff = job_backoff(
+        job.attempts,
+        Duration::from_secs(config.backoff_base),
+        Duration::from_secs(config.backoff_max),
+    );
+
+    warn!(
+        "{} job {} failed on attempt {}, retrying in {backoff:?}: {error}",
+        job.queue.as_str(),
+        job.id,
+        job.attempts
+    );
+
+    match db
+        .retry_job(job, &error, outbox_time() + backoff.as_millis() as i64)
+        .await
+    {
+        Ok(_) => record(job.queue, "retried"),
+        Err(err) => error!("Failed to retry {} job: {err:?}", job.queue.as_str()),
+    }
+}
+
+/// Dead-letter a job so it is never attempted again
+async fn bury(db: &Database, job: &Job, error: &str) {
+    error!(
+        "{} job {} failed after {} attempts, giving up: {error}",
+        job.queue.as_str(),
+        job.id,
+        job.attempts
+    );
+
+    match db.bury_job(job, error).await {
+        Ok(_) => record(job.queue, "dead"),
+        Err(err) => error!("Failed to dead-letter {} job: {err:?}", job.queue.as_str()),
+    }
+}
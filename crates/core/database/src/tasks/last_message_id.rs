@@ -1,21 +1,9 @@
 // Queue Type: Debounced
-use deadqueue::limited::Queue;
-use once_cell::sync::Lazy;
 use std::{collections::HashMap, time::Duration};
 
-use crate::{Database, PartialChannel};
+use crate::{Database, Job, JobPayload, JobQueue, PartialChannel};
 
-use super::DelayedTask;
-
-/// Task information
-struct Data {
-    /// Channel to update
-    channel: String,
-    /// Latest message ID
-    id: String,
-    /// Whether the channel is a DM
-    is_dm: bool,
-}
+use super::{jobs, DelayedTask, DEBOUNCE_HOLD};
 
 /// Task information
 #[derive(Debug)]
@@ -24,14 +12,13 @@ struct Task {
     id: String,
     /// Whether the channel is a DM
     is_dm: bool,
+    /// Jobs merged into this task
+    jobs: Vec<Job>,
 }
 
-static Q: Lazy<Queue<Data>> = Lazy::new(|| Queue::new(10_000));
-
 /// Queue a new task for a worker
-pub async fn queue(channel: String, id: String, is_dm: bool) {
-    Q.try_push(Data { channel, id, is_dm }).ok();
-    info!("Queue is using {} slots from {}.", Q.len(), Q.capacity());
+pub async fn queue(db: &Database, channel: String, id: String, is_dm: bool) {
+    jobs::enqueue(db, JobPayload::LastMessageId { channel, id, is_dm }).await;
 }
 
 /// Start a new worker
@@ -50,7 +37,7 @@ pub async fn worker(db: Database) {
         // Commit any due tasks to the database.
         for key in &keys {
             if let Some(task) = tasks.remove(key) {
-                let Task { id, is_dm, .. } = task.data;
+                let Task { id, is_dm, jobs } = task.data;
 
                 let mut channel = PartialChannel {
                     last_message_id: Some(id.to_string()),
@@ -62,8 +49,16 @@ pub async fn worker(db: Database) {
                 }
 
                 match db.update_channel(key, &channel, vec![]).await {
-                    Ok(_) => info!("Updated last_message_id for {key} to {id}."),
-                    Err(err) => error!("Failed to update last_message_id with {err:?}!"),
+                    Ok(_) => {
+                        info!("Updated last_message_id for {key} to {id}.");
+                        jobs::complete(&db, &jobs).await;
+                    }
+                    Err(err) => {
+                        error!("Failed to update last_message_id with {err:?}!");
+                        for job in &jobs {
+                            jobs::fail(&db, job, &err).await;
+                        }
+                    }
                 }
             }
         }
@@ -72,12 +67,29 @@ pub async fn worker(db: Database) {
         keys.clear();
 
         // Queue incoming tasks.
-        while let Some(Data { channel, id, is_dm }) = Q.try_pop() {
+        for job in jobs::try_claim(&db, JobQueue::LastMessageId, DEBOUNCE_HOLD).await {
+            let JobPayload::LastMessageId { channel, id, is_dm } = job.payload.clone() else {
+                jobs::fail(&db, &job, "not a last_message_id job").await;
+                continue;
+            };
+
             if let Some(task) = tasks.get_mut(&channel) {
-                task.data.id = id;
+                // Retried jobs may arrive out of order, message IDs sort by time.
+                if id > task.data.id {
+                    task.data.id = id;
+                }
+
+                task.data.jobs.push(job);
                 task.delay();
             } else {
-                tasks.insert(channel, DelayedTask::new(Task { id, is_dm }));
+                tasks.insert(
+                    channel,
+                    DelayedTask::new(Task {
+                        id,
+                        is_dm,
+                        jobs: vec![job],
+                    }),
+                );
             }
         }
 
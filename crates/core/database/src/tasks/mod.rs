@@ -3,25 +3,40 @@
 use crate::Database;
 
 use async_std::task;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 const WORKER_COUNT: usize = 5;
 
 pub mod ack;
 pub mod apple_notifications;
+pub mod email_digest;
+pub mod file_sweeper;
+pub mod job_sweeper;
+pub mod jobs;
 pub mod last_message_id;
+pub mod outbox;
 pub mod process_embeds;
+pub mod push_coalesce;
+pub mod push_digest;
+pub mod unfurl;
+pub mod unified_push;
 pub mod web_push;
 
 /// Spawn background workers
 pub async fn start_workers(db: Database, authifier_db: authifier::Database) {
-    task::spawn(apple_notifications::worker(db.clone()));
+    task::spawn(apple_notifications::worker(db.clone(), authifier_db.clone()));
+    task::spawn(outbox::worker(db.clone()));
+    task::spawn(file_sweeper::worker(db.clone()));
+    task::spawn(job_sweeper::worker(db.clone()));
+    task::spawn(push_coalesce::worker(db.clone(), authifier_db.clone()));
+    task::spawn(push_digest::worker(db.clone(), authifier_db.clone()));
+    task::spawn(email_digest::worker(db.clone(), authifier_db.clone()));
 
     for _ in 0..WORKER_COUNT {
         task::spawn(ack::worker(db.clone()));
         task::spawn(last_message_id::worker(db.clone()));
         task::spawn(process_embeds::worker(db.clone()));
-        task::spawn(web_push::worker(authifier_db.clone()));
+        task::spawn(web_push::worker(db.clone()));
     }
 }
 
@@ -33,10 +48,13 @@ pub struct DelayedTask<T> {
 }
 
 /// Commit to database every 30 seconds if the task is particularly active.
-static EXPIRE_CONSTANT: u64 = 30;
+const EXPIRE_CONSTANT: u64 = 30;
 
 /// Otherwise, commit to database after 5 seconds.
-static SAVE_CONSTANT: u64 = 5;
+const SAVE_CONSTANT: u64 = 5;
+
+/// How long debounced workers may hold onto jobs before running them
+pub const DEBOUNCE_HOLD: Duration = Duration::from_secs(EXPIRE_CONSTANT + 1);
 
 impl<T> DelayedTask<T> {
     /// Create a new delayed task
@@ -0,0 +1,67 @@
+// Queue Type: Polled
+use crate::Database;
+
+use deadqueue::limited::Queue;
+use once_cell::sync::Lazy;
+use std::time::Duration;
+
+/// Maximum number of events to claim at once
+const BATCH: i64 = 100;
+
+/// How long a relay may hold onto events before another may retry them
+const LEASE: Duration = Duration::from_secs(30);
+
+/// How long to wait between checks when nothing has woken us up
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static WAKE: Lazy<Queue<()>> = Lazy::new(|| Queue::new(1));
+
+/// Let the relay know there are new events to publish
+pub fn wake() {
+    WAKE.try_push(()).ok();
+}
+
+/// Start a new worker
+pub async fn worker(db: Database) {
+    loop {
+        let events = match db.claim_outbox_events(BATCH, LEASE).await {
+            Ok(events) => events,
+            Err(err) => {
+                error!("Failed to claim outbox events: {err:?}");
+                async_std::task::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let claimed = events.len();
+        let mut published = vec![];
+        for event in events {
+            if !event
+                .event
+                .clone()
+                .p_with_id(event.id.clone(), event.topics.clone())
+                .await
+            {
+                // Leave the rest claimed, they'll be retried once the lease expires.
+                break;
+            }
+
+            published.push(event.id);
+        }
+
+        if !published.is_empty() {
+            if let Err(err) = db.delete_outbox_events(&published).await {
+                // Events will be published again, subscribers de-duplicate them.
+                error!("Failed to delete published outbox events: {err:?}");
+            }
+        }
+
+        if claimed as i64 == BATCH && published.len() == claimed {
+            continue;
+        }
+
+        async_std::future::timeout(POLL_INTERVAL, WAKE.pop())
+            .await
+            .ok();
+    }
+}
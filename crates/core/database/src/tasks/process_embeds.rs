@@ -1,4 +1,6 @@
-use crate::{models::Message, AppendMessage, Database};
+use crate::{models::Message, AppendMessage, Database, JobPayload, JobQueue};
+
+use super::{jobs, unfurl::Unfurler};
 
 use futures::future::join_all;
 use linkify::{LinkFinder, LinkKind};
@@ -8,74 +10,84 @@ use revolt_result::Result;
 
 use async_lock::Semaphore;
 use async_std::task::spawn;
-use deadqueue::limited::Queue;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use revolt_models::v0::Embed;
 use std::{collections::HashSet, sync::Arc};
 
-use isahc::prelude::*;
-
-/// Task information
-#[derive(Debug)]
-struct EmbedTask {
-    /// Channel we're processing the event in
-    channel: String,
-    /// ID of the message we're processing
-    id: String,
-    /// Content of the message
-    content: String,
-}
-
-static Q: Lazy<Queue<EmbedTask>> = Lazy::new(|| Queue::new(10_000));
+/// Unfurler shared between all workers so they share its cache
+static UNFURLER: OnceCell<Unfurler> = OnceCell::new();
 
 /// Queue a new task for a worker
-pub async fn queue(channel: String, id: String, content: String) {
-    Q.try_push(EmbedTask {
-        channel,
-        id,
-        content,
-    })
-    .ok();
-
-    info!("Queue is using {} slots from {}.", Q.len(), Q.capacity());
+pub async fn queue(db: &Database, channel: String, id: String, content: String) {
+    jobs::enqueue(
+        db,
+        JobPayload::ProcessEmbeds {
+            channel,
+            id,
+            content,
+        },
+    )
+    .await;
 }
 
 /// Start a new worker
 pub async fn worker(db: Database) {
+    let config = config().await;
     let semaphore = Arc::new(Semaphore::new(
-        config().await.api.workers.max_concurrent_connections,
+        config.api.workers.max_concurrent_connections,
     ));
 
-    loop {
-        let task = Q.pop().await;
-        let db = db.clone();
-        let semaphore = semaphore.clone();
+    let unfurler = UNFURLER.get_or_init(|| Unfurler::new(config.api.embeds));
 
-        spawn(async move {
-            let config = config().await;
-            let embeds = generate(
-                task.content,
-                &config.hosts.january,
-                config.features.limits.global.message_embeds,
-                semaphore,
-            )
-            .await;
-
-            if let Ok(embeds) = embeds {
-                if let Err(err) = Message::append(
-                    &db,
-                    task.id,
-                    task.channel,
-                    AppendMessage {
-                        embeds: Some(embeds),
-                    },
+    loop {
+        // Finish each batch before claiming more, so jobs aren't held past their lease.
+        let mut tasks = Vec::new();
+        for job in jobs::claim(&db, JobQueue::ProcessEmbeds).await {
+            let db = db.clone();
+            let semaphore = semaphore.clone();
+
+            tasks.push(spawn(async move {
+                let JobPayload::ProcessEmbeds {
+                    channel,
+                    id,
+                    content,
+                } = job.payload.clone()
+                else {
+                    jobs::fail(&db, &job, "not a process_embeds job").await;
+                    return;
+                };
+
+                let config = config().await;
+                let embeds = generate(
+                    content,
+                    unfurler,
+                    config.features.limits.global.message_embeds,
+                    semaphore,
                 )
-                .await
-                {
-                    error!("Encountered an error appending to message: {:?}", err);
+                .await;
+
+                if let Ok(embeds) = embeds {
+                    if let Err(err) = Message::append(
+                        &db,
+                        id,
+                        channel,
+                        AppendMessage {
+                            embeds: Some(embeds),
+                        },
+                    )
+                    .await
+                    {
+                        error!("Encountered an error appending to message: {:?}", err);
+                        jobs::fail(&db, &job, err).await;
+                        return;
+                    }
                 }
-            }
-        });
+
+                jobs::complete(&db, &[job]).await;
+            }));
+        }
+
+        join_all(tasks).await;
     }
 }
 
@@ -84,7 +96,7 @@ static RE_IGNORED: Lazy<Regex> = Lazy::new(|| Regex::new("(<http.+>)").unwrap())
 
 pub async fn generate(
     content: String,
-    host: &str,
+    unfurler: &'static Unfurler,
     max_embeds: usize,
     semaphore: Arc<Semaphore>,
 ) -> Result<Vec<Embed>> {
@@ -132,26 +144,13 @@ pub async fn generate(
         return Err(create_error!(LabelMe));
     }
 
-    // TODO: batch request to january
     let mut tasks = Vec::new();
 
     for link in links {
         let semaphore = semaphore.clone();
-        let host = host.to_string();
         tasks.push(spawn(async move {
-            let guard = semaphore.acquire().await;
-
-            if let Ok(mut response) = isahc::get_async(format!(
-                "{host}/embed?url={}",
-                url_escape::encode_component(&link)
-            ))
-            .await
-            {
-                drop(guard);
-                response.json::<Embed>().await.ok()
-            } else {
-                None
-            }
+            let _guard = semaphore.acquire().await;
+            unfurler.unfurl(&link).await
         }));
     }
 
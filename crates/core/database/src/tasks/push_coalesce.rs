@@ -0,0 +1,355 @@
+// Queue Type: Polled
+use std::{collections::HashSet, time::Duration};
+
+use revolt_config::config;
+use revolt_models::v0::PushNotification;
+use revolt_result::Result;
+
+use crate::{outbox_time, Channel, Database, JobPayload, JobQueue};
+
+use super::{jobs, web_push::PushClients};
+
+/// Windows which should have closed this long ago are assumed to have been abandoned
+const STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Queue a notification for users, to be merged with others in the same channel
+pub async fn queue(db: &Database, channel: String, users: Vec<String>, payload: PushNotification) {
+    jobs::enqueue(
+        db,
+        JobPayload::PushCoalesce {
+            users,
+            channel,
+            payload,
+        },
+    )
+    .await;
+}
+
+/// Remove notifications a user was sent about a channel, now that they've read it
+pub async fn dismiss(db: &Database, user: String, channel: String) {
+    jobs::enqueue(db, JobPayload::PushDismiss { user, channel }).await;
+}
+
+/// Describe where messages were sent, for the title of a summary
+fn location(channel: &Channel, latest: &PushNotification) -> Option<String> {
+    match channel {
+        Channel::TextChannel { name, .. } | Channel::VoiceChannel { name, .. } => {
+            Some(format!("in #{name}"))
+        }
+        Channel::Group { name, .. } => Some(format!("in {name}")),
+        Channel::DirectMessage { .. } => Some(format!("from {}", latest.author)),
+        Channel::SavedMessages { .. } => None,
+    }
+}
+
+/// Summarise a burst of messages in a channel, replacing any notification already shown for it
+fn summarise(count: usize, latest: &PushNotification, location: Option<&str>) -> PushNotification {
+    PushNotification {
+        author: match location {
+            Some(location) => format!("{count} new messages {location}"),
+            None => format!("{count} new messages"),
+        },
+        icon: latest.icon.clone(),
+        image: None,
+        body: format!("{}: {}", latest.author, latest.body),
+        tag: latest.tag.clone(),
+        timestamp: latest.timestamp,
+        url: latest.url.clone(),
+    }
+}
+
+/// Leave out who sent the messages and what they said, for users who hide content
+pub(super) fn conceal(
+    notification: PushNotification,
+    count: usize,
+    logo: &str,
+) -> PushNotification {
+    PushNotification {
+        author: if count == 1 {
+            "New message".to_string()
+        } else {
+            format!("{count} new messages")
+        },
+        icon: logo.to_string(),
+        image: None,
+        body: String::new(),
+        ..notification
+    }
+}
+
+/// State shared while processing jobs
+struct Coalescer {
+    db: Database,
+    authifier_db: authifier::Database,
+    clients: PushClients,
+    logo: String,
+    window: Duration,
+}
+
+impl Coalescer {
+    /// Send a notification to users about a channel, concealing it from any who hide content
+    ///
+    /// Sends aren't retried, as that would notify everyone else again.
+    async fn notify(
+        &self,
+        channel: &str,
+        users: &[String],
+        notification: &PushNotification,
+        count: usize,
+    ) {
+        let hidden: HashSet<String> = match self.db.fetch_many_notification_settings(users).await {
+            Ok(settings) => settings
+                .into_iter()
+                .filter(|settings| settings.hide_content)
+                .map(|settings| settings.id)
+                .collect(),
+            Err(err) => {
+                error!("Failed to fetch notification settings for {channel}: {err:?}");
+                return;
+            }
+        };
+
+        let (concealed, shown): (Vec<String>, Vec<String>) = users
+            .iter()
+            .cloned()
+            .partition(|user| hidden.contains(user));
+
+        for (users, notification) in [
+            (shown, notification.clone()),
+            (concealed, conceal(notification.clone(), count, &self.logo)),
+        ] {
+            if users.is_empty() {
+                continue;
+            }
+
+            if let Err(err) = self
+                .clients
+                .send(&self.db, &self.authifier_db, &users, &notification)
+                .await
+            {
+                error!("Failed to send push notifications about {channel}: {err:?}");
+                continue;
+            }
+
+            // Remember to dismiss the notification once the channel is read
+            for user in &users {
+                if let Err(err) = self.db.mark_unread_notified(channel, user).await {
+                    error!("Failed to record notification for {user} in {channel}: {err:?}");
+                }
+            }
+        }
+    }
+
+    /// Notify users about a message straight away, unless they have a window open
+    /// in the channel which holds it back
+    async fn hold(
+        &self,
+        channel: &str,
+        users: &[String],
+        payload: &PushNotification,
+    ) -> Result<()> {
+        let now = outbox_time();
+        let closes_at = now + self.window.as_millis() as i64;
+        let opened = self
+            .db
+            .hold_push_notification(
+                channel,
+                users,
+                payload,
+                closes_at,
+                now - STALE_AFTER.as_millis() as i64,
+            )
+            .await?;
+
+        if opened.is_empty() {
+            return Ok(());
+        }
+
+        jobs::schedule(
+            &self.db,
+            JobPayload::PushWindowClose {
+                channel: channel.to_string(),
+                users: opened.clone(),
+            },
+            closes_at,
+        )
+        .await;
+
+        self.notify(channel, &opened, payload, 1).await;
+        Ok(())
+    }
+
+    /// Summarise anything held back by windows which have closed
+    ///
+    /// Windows stay open for as long as messages keep arriving.
+    async fn close(&self, channel: &str, users: &[String]) -> Result<()> {
+        let closes_at = outbox_time() + self.window.as_millis() as i64;
+        let windows = self
+            .db
+            .close_push_windows(channel, users, closes_at)
+            .await?;
+
+        if windows.is_empty() {
+            return Ok(());
+        }
+
+        jobs::schedule(
+            &self.db,
+            JobPayload::PushWindowClose {
+                channel: channel.to_string(),
+                users: windows
+                    .iter()
+                    .map(|window| window.id.user.clone())
+                    .collect(),
+            },
+            closes_at,
+        )
+        .await;
+
+        // Users who missed the same messages are sent the same summary.
+        let mut summaries: Vec<(usize, PushNotification, Vec<String>)> = vec![];
+        for window in windows {
+            let Some(latest) = window.latest else {
+                continue;
+            };
+
+            let count = window.count as usize;
+            match summaries
+                .iter_mut()
+                .find(|(other_count, other, _)| *other_count == count && *other == latest)
+            {
+                Some((_, _, users)) => users.push(window.id.user),
+                None => summaries.push((count, latest, vec![window.id.user])),
+            }
+        }
+
+        let channel_info = self.db.fetch_channel(channel).await.ok();
+        for (count, latest, users) in summaries {
+            let location = channel_info
+                .as_ref()
+                .and_then(|channel| location(channel, &latest));
+
+            let summary = summarise(count, &latest, location.as_deref());
+            self.notify(channel, &users, &summary, count).await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Start a new worker
+pub async fn worker(db: Database, authifier_db: authifier::Database) {
+    let config = config().await;
+    let coalescer = Coalescer {
+        db,
+        authifier_db,
+        clients: PushClients::new().await,
+        logo: format!("{}/assets/logo.png", config.hosts.app),
+        window: Duration::from_secs(config.api.push.coalesce_window),
+    };
+
+    loop {
+        for job in jobs::claim(&coalescer.db, JobQueue::PushCoalesce).await {
+            let result = match &job.payload {
+                JobPayload::PushCoalesce {
+                    users,
+                    channel,
+                    payload,
+                } => coalescer
+                    .hold(channel, users, payload)
+                    .await
+                    .map_err(|err| format!("{err:?}")),
+                JobPayload::PushWindowClose { channel, users } => coalescer
+                    .close(channel, users)
+                    .await
+                    .map_err(|err| format!("{err:?}")),
+                JobPayload::PushDismiss { user, channel } => {
+                    // The user has read anything still being held back.
+                    match coalescer.db.delete_push_window(channel, user).await {
+                        Ok(()) => coalescer
+                            .clients
+                            .dismiss(&coalescer.db, &coalescer.authifier_db, user, channel)
+                            .await
+                            .map_err(|err| format!("{err:?}")),
+                        Err(err) => Err(format!("{err:?}")),
+                    }
+                }
+                _ => Err("not a push_coalesce job".to_string()),
+            };
+
+            match result {
+                Ok(()) => jobs::complete(&coalescer.db, &[job]).await,
+                Err(err) => jobs::fail(&coalescer.db, &job, err).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revolt_models::v0::PushNotification;
+
+    use crate::Channel;
+
+    use super::{conceal, location, summarise};
+
+    fn notification(author: &str, body: &str) -> PushNotification {
+        PushNotification {
+            author: author.to_string(),
+            icon: format!("{author}.png"),
+            image: Some("cat.png".to_string()),
+            body: body.to_string(),
+            tag: "channel".to_string(),
+            timestamp: 1,
+            url: "/channel/channel/message".to_string(),
+        }
+    }
+
+    #[test]
+    fn summary_describes_location() {
+        let latest = notification("alice", "hello");
+
+        let text = Channel::TextChannel {
+            id: "channel".to_string(),
+            server: "server".to_string(),
+            name: "general".to_string(),
+            description: None,
+            icon: None,
+            last_message_id: None,
+            default_permissions: None,
+            role_permissions: Default::default(),
+            nsfw: false,
+        };
+        assert_eq!(location(&text, &latest).as_deref(), Some("in #general"));
+
+        let dm = Channel::DirectMessage {
+            id: "channel".to_string(),
+            active: true,
+            recipients: vec![],
+            last_message_id: None,
+        };
+        assert_eq!(location(&dm, &latest).as_deref(), Some("from alice"));
+
+        let summary = summarise(5, &latest, Some("in #general"));
+        assert_eq!(summary.author, "5 new messages in #general");
+        assert_eq!(summary.body, "alice: hello");
+        assert_eq!(summary.tag, latest.tag);
+        assert_eq!(summary.image, None);
+        assert_eq!(summarise(2, &latest, None).author, "2 new messages");
+    }
+
+    #[test]
+    fn concealed_notifications_say_nothing() {
+        let hidden = conceal(notification("alice", "a secret"), 1, "logo.png");
+        assert_eq!(hidden.author, "New message");
+        assert_eq!(hidden.body, "");
+        assert_eq!(hidden.icon, "logo.png");
+        assert_eq!(hidden.image, None);
+        assert_eq!(hidden.tag, "channel");
+
+        let summary = summarise(3, &notification("alice", "a secret"), Some("in #general"));
+        let hidden = conceal(summary, 3, "logo.png");
+        assert_eq!(hidden.author, "3 new messages");
+        assert_eq!(hidden.body, "");
+    }
+}
@@ -0,0 +1,159 @@
+// Queue Type: Polled
+use std::collections::HashMap;
+
+use revolt_config::config;
+use revolt_models::v0::PushNotification;
+use revolt_presence::filter_online;
+
+use crate::{Database, Job, JobPayload, JobQueue};
+
+use super::{jobs, push_coalesce::conceal, web_push::PushClients};
+
+/// How many authors to name in a digest before summarising the rest
+const NAMED_AUTHORS: usize = 3;
+
+/// Hold back a notification for a user until the given time (in milliseconds since the epoch)
+pub async fn queue(db: &Database, user: String, payload: PushNotification, until: i64) {
+    jobs::schedule(db, JobPayload::PushDigest { user, payload }, until).await;
+}
+
+/// Summarise notifications held back during a user's quiet hours
+fn summarise(notifications: &[&PushNotification]) -> Option<PushNotification> {
+    let latest = notifications.iter().max_by_key(|n| n.timestamp)?;
+    if notifications.len() == 1 {
+        return Some((*latest).clone());
+    }
+
+    let mut authors: Vec<&str> = vec![];
+    for notification in notifications {
+        if !authors.contains(&notification.author.as_str()) {
+            authors.push(&notification.author);
+        }
+    }
+
+    let from = if authors.len() > NAMED_AUTHORS {
+        format!(
+            "{} and {} others",
+            authors[..NAMED_AUTHORS].join(", "),
+            authors.len() - NAMED_AUTHORS
+        )
+    } else {
+        authors.join(", ")
+    };
+
+    Some(PushNotification {
+        author: format!("{} new messages", notifications.len()),
+        icon: latest.icon.clone(),
+        image: None,
+        body: format!("From {from}"),
+        tag: "digest".to_string(),
+        timestamp: latest.timestamp,
+        url: latest.url.clone(),
+    })
+}
+
+/// Start a new worker
+pub async fn worker(db: Database, authifier_db: authifier::Database) {
+    let clients = PushClients::new().await;
+    let logo = format!("{}/assets/logo.png", config().await.hosts.app);
+
+    loop {
+        // Notifications held back by the same window become available together
+        let mut digests = HashMap::<String, Vec<Job>>::new();
+        for job in jobs::claim(&db, JobQueue::PushDigest).await {
+            if let JobPayload::PushDigest { user, .. } = &job.payload {
+                digests.entry(user.clone()).or_default().push(job);
+            } else {
+                jobs::fail(&db, &job, "not a push_digest job").await;
+            }
+        }
+
+        // Users who have come back online will see what they missed anyway
+        let users: Vec<String> = digests.keys().cloned().collect();
+        let online_ids = filter_online(&users).await;
+
+        for (user, held) in digests {
+            let notifications: Vec<&PushNotification> = held
+                .iter()
+                .filter_map(|job| match &job.payload {
+                    JobPayload::PushDigest { payload, .. } => Some(payload),
+                    _ => None,
+                })
+                .collect();
+
+            if !online_ids.contains(&user) {
+                if let Some(mut payload) = summarise(&notifications) {
+                    match db.fetch_notification_settings(&user).await {
+                        Ok(settings) if settings.hide_content => {
+                            payload = conceal(payload, notifications.len(), &logo);
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            for job in &held {
+                                jobs::fail(&db, job, &err).await;
+                            }
+
+                            continue;
+                        }
+                    }
+
+                    if let Err(err) = clients
+                        .send(&db, &authifier_db, &[user.clone()], &payload)
+                        .await
+                    {
+                        for job in &held {
+                            jobs::fail(&db, job, &err).await;
+                        }
+
+                        continue;
+                    }
+                }
+            }
+
+            jobs::complete(&db, &held).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revolt_models::v0::PushNotification;
+
+    use super::summarise;
+
+    fn notification(author: &str, timestamp: u64) -> PushNotification {
+        PushNotification {
+            author: author.to_string(),
+            icon: format!("{author}.png"),
+            image: None,
+            body: "hello".to_string(),
+            tag: "channel".to_string(),
+            timestamp,
+            url: format!("/{timestamp}"),
+        }
+    }
+
+    #[test]
+    fn single_notification_is_unchanged() {
+        let single = notification("alice", 1);
+        assert_eq!(summarise(&[&single]), Some(single));
+        assert_eq!(summarise(&[]), None);
+    }
+
+    #[test]
+    fn digest_names_authors() {
+        let notifications = [
+            notification("alice", 1),
+            notification("bob", 3),
+            notification("alice", 2),
+        ];
+        let digest = summarise(&notifications.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(digest.author, "3 new messages");
+        assert_eq!(digest.body, "From alice, bob");
+        assert_eq!(digest.url, "/3");
+
+        let notifications = ["a", "b", "c", "d", "e"].map(|author| notification(author, 1));
+        let digest = summarise(&notifications.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(digest.body, "From a, b, c and 2 others");
+    }
+}
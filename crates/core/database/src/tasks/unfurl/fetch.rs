@@ -0,0 +1,179 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use async_std::net::ToSocketAddrs;
+use futures::AsyncReadExt;
+use isahc::{
+    config::{Configurable, RedirectPolicy, ResolveMap},
+    http::header,
+    HttpClient,
+};
+use url::{Host, Url};
+
+use super::Unfurler;
+
+static USER_AGENT: &str = "Mozilla/5.0 (compatible; Revoltbot/1.0; +https://github.com/revoltchat)";
+
+/// Page fetched from a remote server
+pub struct Page {
+    /// URL of the page, after following redirects
+    pub url: Url,
+    /// Content type of the page, without any parameters
+    pub content_type: String,
+    /// Body of the page, cut off at the size limit
+    pub body: Vec<u8>,
+}
+
+impl Unfurler {
+    /// Fetch a page, following redirects
+    ///
+    /// Every hop must use HTTP or HTTPS, is checked against the domain lists
+    /// and must resolve to a public address.
+    pub async fn fetch(&self, url: Url) -> Option<Page> {
+        self.fetch_from(url, 0).await
+    }
+
+    /// Fetch a page starting from the given byte offset, following redirects
+    ///
+    /// Servers which ignore the range send the page from the start instead.
+    pub async fn fetch_from(&self, mut url: Url, offset: u64) -> Option<Page> {
+        for _ in 0..=self.config.max_redirects {
+            if !matches!(url.scheme(), "http" | "https") || !self.is_allowed(&url) {
+                return None;
+            }
+
+            // Connect to the address we checked, rather than letting it be resolved again
+            let address = resolve_public(&url, self.allow_private_addresses).await?;
+            let mut client = HttpClient::builder()
+                .timeout(Duration::from_secs(self.config.timeout))
+                .redirect_policy(RedirectPolicy::None)
+                .proxy(None)
+                .default_header(header::USER_AGENT, USER_AGENT);
+
+            if offset > 0 {
+                client = client.default_header(
+                    header::RANGE,
+                    format!(
+                        "bytes={offset}-{}",
+                        offset + self.config.max_size as u64 - 1
+                    ),
+                );
+            }
+
+            if let Some(Host::Domain(domain)) = url.host() {
+                client = client.dns_resolve(ResolveMap::new().add(
+                    domain,
+                    url.port_or_known_default()?,
+                    address,
+                ));
+            }
+
+            let mut response = client.build().ok()?.get_async(url.as_str()).await.ok()?;
+            if response.status().is_redirection() {
+                let location = response.headers().get(header::LOCATION)?.to_str().ok()?;
+                url = url.join(location).ok()?;
+                continue;
+            }
+
+            if !response.status().is_success() {
+                return None;
+            }
+
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(';').next())
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+
+            let mut body = vec![];
+            response
+                .body_mut()
+                .take(self.config.max_size as u64)
+                .read_to_end(&mut body)
+                .await
+                .ok()?;
+
+            return Some(Page {
+                url,
+                content_type,
+                body,
+            });
+        }
+
+        None
+    }
+}
+
+/// Resolve the address to connect to for a URL
+///
+/// Fails if the host resolves to any address which is not publicly routable,
+/// unless private addresses are allowed.
+pub async fn resolve_public(url: &Url, allow_private_addresses: bool) -> Option<IpAddr> {
+    let port = url.port_or_known_default()?;
+    let addresses: Vec<IpAddr> = match url.host()? {
+        Host::Domain(domain) => (domain, port)
+            .to_socket_addrs()
+            .await
+            .ok()?
+            .map(|address| address.ip())
+            .collect(),
+        Host::Ipv4(ip) => vec![ip.into()],
+        Host::Ipv6(ip) => vec![ip.into()],
+    };
+
+    if allow_private_addresses || addresses.iter().copied().all(is_public) {
+        addresses.first().copied()
+    } else {
+        None
+    }
+}
+
+/// Check whether an address is publicly routable
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_v4(ip);
+            }
+
+            let segments = ip.segments();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local (fc00::/7)
+                || (segments[0] & 0xfe00) == 0xfc00
+                // Link local (fe80::/10)
+                || (segments[0] & 0xffc0) == 0xfe80
+                // Documentation (2001:db8::/32)
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+                // Translated IPv4 addresses (64:ff9b::/96)
+                || ip.octets()[..12] == Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0).octets()[..12])
+        }
+    }
+}
+
+/// Check whether an IPv4 address is publicly routable
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network" (0.0.0.0/8)
+        || a == 0
+        // Shared address space (100.64.0.0/10)
+        || (a == 100 && (b & 0xc0) == 64)
+        // Protocol assignments (192.0.0.0/24)
+        || ip.octets()[..3] == [192, 0, 0]
+        // Benchmarking (198.18.0.0/15)
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved (240.0.0.0/4)
+        || a >= 240)
+}
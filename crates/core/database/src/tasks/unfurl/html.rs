@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use scraper::{Html, Selector};
+use serde::Deserialize;
+use url::Url;
+
+/// Image referenced by a page
+#[derive(Debug, PartialEq, Eq)]
+pub struct PageImage {
+    pub url: String,
+    pub width: Option<isize>,
+    pub height: Option<isize>,
+    /// Whether the page asks for the image to be shown large
+    pub large: bool,
+}
+
+/// Video referenced by a page
+#[derive(Debug, PartialEq, Eq)]
+pub struct PageVideo {
+    pub url: String,
+    pub width: Option<isize>,
+    pub height: Option<isize>,
+}
+
+/// Metadata found in a web page
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub icon_url: Option<String>,
+    pub colour: Option<String>,
+    pub image: Option<PageImage>,
+    pub video: Option<PageVideo>,
+    /// Where to find the oEmbed description of this page
+    pub oembed: Option<Url>,
+}
+
+/// oEmbed description of a page
+#[derive(Deserialize, Debug, Default)]
+pub struct OEmbed {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub title: Option<String>,
+    pub provider_name: Option<String>,
+    pub url: Option<String>,
+    pub width: Option<isize>,
+    pub height: Option<isize>,
+    pub thumbnail_url: Option<String>,
+    pub thumbnail_width: Option<isize>,
+    pub thumbnail_height: Option<isize>,
+}
+
+/// Resolve a link found in a page against the page's URL
+fn resolve(base: &Url, link: &str) -> Option<Url> {
+    base.join(link)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+impl PageMetadata {
+    /// Parse OpenGraph, Twitter card and general metadata from a page
+    pub fn parse(html: &str, base: &Url) -> PageMetadata {
+        let document = Html::parse_document(html);
+        let selector = |selector| Selector::parse(selector).expect("valid selector");
+
+        // Properties are taken from the first tag to define them
+        let mut properties = HashMap::new();
+        for element in document.select(&selector("meta[content]")) {
+            let element = element.value();
+            if let Some(key) = element.attr("property").or_else(|| element.attr("name")) {
+                properties
+                    .entry(key.to_ascii_lowercase())
+                    .or_insert_with(|| element.attr("content").unwrap_or_default().trim());
+            }
+        }
+
+        let property = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| properties.get(*key))
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+        };
+
+        let dimension = |key| property(&[key]).and_then(|value| value.parse().ok());
+        let link = |selector_str| {
+            document
+                .select(&selector(selector_str))
+                .find_map(|element| element.value().attr("href"))
+                .and_then(|href| resolve(base, href))
+        };
+
+        PageMetadata {
+            title: property(&["og:title", "twitter:title"]).or_else(|| {
+                document
+                    .select(&selector("title"))
+                    .next()
+                    .map(|title| title.text().collect::<String>().trim().to_string())
+                    .filter(|title| !title.is_empty())
+            }),
+            description: property(&["og:description", "twitter:description", "description"]),
+            site_name: property(&["og:site_name"]),
+            icon_url: link("link[rel~=\"icon\"][href]").map(|url| url.to_string()),
+            colour: property(&["theme-color"]),
+            image: property(&[
+                "og:image:secure_url",
+                "og:image",
+                "og:image:url",
+                "twitter:image",
+                "twitter:image:src",
+            ])
+            .and_then(|url| resolve(base, &url))
+            .map(|url| PageImage {
+                url: url.to_string(),
+                width: dimension("og:image:width"),
+                height: dimension("og:image:height"),
+                large: property(&["twitter:card"]).as_deref() == Some("summary_large_image"),
+            }),
+            video: property(&["og:video:secure_url", "og:video", "og:video:url"])
+                .and_then(|url| resolve(base, &url))
+                .map(|url| PageVideo {
+                    url: url.to_string(),
+                    width: dimension("og:video:width"),
+                    height: dimension("og:video:height"),
+                }),
+            oembed: link("link[type=\"application/json+oembed\"][href]"),
+        }
+    }
+
+    /// Fill in anything missing from this page's metadata using its oEmbed description
+    pub fn merge(&mut self, oembed: OEmbed, base: &Url) {
+        self.title = self.title.take().or(oembed.title);
+        self.site_name = self.site_name.take().or(oembed.provider_name);
+
+        if self.image.is_none() {
+            let (url, width, height) = if oembed.kind.as_deref() == Some("photo") {
+                (oembed.url, oembed.width, oembed.height)
+            } else {
+                (
+                    oembed.thumbnail_url,
+                    oembed.thumbnail_width,
+                    oembed.thumbnail_height,
+                )
+            };
+
+            self.image = url
+                .and_then(|url| resolve(base, &url))
+                .map(|url| PageImage {
+                    url: url.to_string(),
+                    width,
+                    height,
+                    large: false,
+                });
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! Generate embeds for links without relying on an external service
+
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use revolt_config::ApiEmbeds;
+use revolt_models::v0::{Embed, Image, ImageSize, Video, WebsiteMetadata};
+use url::Url;
+
+mod fetch;
+mod html;
+
+pub(super) use fetch::resolve_public;
+use html::{OEmbed, PageMetadata};
+
+/// Embed generated for a link alongside the time it was generated
+struct Entry {
+    embed: Option<Embed>,
+    generated_at: Instant,
+}
+
+/// Generates embeds for links, caching them for a while
+pub struct Unfurler {
+    config: ApiEmbeds,
+    cache: Option<Mutex<LruCache<String, Entry>>>,
+    /// Whether links may point at private addresses, only used to test against a local server
+    allow_private_addresses: bool,
+}
+
+impl Unfurler {
+    /// Create a new unfurler
+    pub fn new(config: ApiEmbeds) -> Unfurler {
+        Unfurler {
+            cache: NonZeroUsize::new(config.cache_capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+            config,
+            allow_private_addresses: false,
+        }
+    }
+
+    /// Generate an embed for a link, if it has one
+    pub async fn unfurl(&self, link: &str) -> Option<Embed> {
+        let url = normalize(link)?;
+        let ttl = Duration::from_secs(self.config.cache_ttl);
+
+        if let Some(cache) = &self.cache {
+            if let Some(entry) = cache.lock().unwrap().get(url.as_str()) {
+                if entry.generated_at.elapsed() < ttl {
+                    return entry.embed.clone();
+                }
+            }
+        }
+
+        let embed = self.generate(url.clone()).await;
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().put(
+                url.to_string(),
+                Entry {
+                    embed: embed.clone(),
+                    generated_at: Instant::now(),
+                },
+            );
+        }
+
+        embed
+    }
+
+    /// Check whether a link points at a domain we may embed
+    fn is_allowed(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        let matches = |domain: &String| {
+            let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+            host == domain || host.ends_with(&format!(".{domain}"))
+        };
+
+        (self.config.allowed_domains.is_empty() || self.config.allowed_domains.iter().any(matches))
+            && !self.config.denied_domains.iter().any(matches)
+    }
+
+    /// Generate an embed for a link
+    async fn generate(&self, url: Url) -> Option<Embed> {
+        let page = self.fetch(url.clone()).await?;
+        match page.content_type.split('/').next() {
+            Some("image") => {
+                let size = imagesize::blob_size(&page.body).ok()?;
+                Some(Embed::Image(Image {
+                    url: url.to_string(),
+                    width: size.width as isize,
+                    height: size.height as isize,
+                    size: ImageSize::Large,
+                }))
+            }
+            Some("video") => {
+                let (width, height) = match video_dimensions(&page.body) {
+                    Some(dimensions) => Some(dimensions),
+                    // Files not optimised for streaming keep their track headers at the end
+                    None if page.body.len() == self.config.max_size => {
+                        let offset = unread_box_offset(&page.body)?;
+                        let rest = self.fetch_from(page.url, offset).await?;
+                        video_dimensions(&rest.body)
+                    }
+                    None => None,
+                }?;
+
+                Some(Embed::Video(Video {
+                    url: url.to_string(),
+                    width,
+                    height,
+                }))
+            }
+            _ if matches!(
+                page.content_type.as_str(),
+                "text/html" | "application/xhtml+xml"
+            ) =>
+            {
+                let mut metadata =
+                    PageMetadata::parse(&String::from_utf8_lossy(&page.body), &page.url);
+
+                if let Some(oembed) = metadata.oembed.take() {
+                    if let Some(oembed) = self
+                        .fetch(oembed)
+                        .await
+                        .and_then(|page| serde_json::from_slice::<OEmbed>(&page.body).ok())
+                    {
+                        metadata.merge(oembed, &page.url);
+                    }
+                }
+
+                self.website(url, page.url, metadata).await
+            }
+            _ => None,
+        }
+    }
+
+    /// Build an embed for a web page from its metadata
+    async fn website(&self, original: Url, url: Url, metadata: PageMetadata) -> Option<Embed> {
+        let image = match metadata.image {
+            Some(image) => {
+                let size = match (image.width, image.height) {
+                    (Some(width), Some(height)) => Some((width, height)),
+                    // Pages don't always say how large their image is, so check it ourselves
+                    _ => self
+                        .fetch(Url::parse(&image.url).ok()?)
+                        .await
+                        .and_then(|page| imagesize::blob_size(&page.body).ok())
+                        .map(|size| (size.width as isize, size.height as isize)),
+                };
+
+                size.map(|(width, height)| Image {
+                    url: image.url,
+                    width,
+                    height,
+                    size: if image.large {
+                        ImageSize::Large
+                    } else {
+                        ImageSize::Preview
+                    },
+                })
+            }
+            None => None,
+        };
+
+        let video = metadata.video.and_then(|video| {
+            Some(Video {
+                url: video.url,
+                width: video.width?,
+                height: video.height?,
+            })
+        });
+
+        if metadata.title.is_none()
+            && metadata.description.is_none()
+            && image.is_none()
+            && video.is_none()
+        {
+            return None;
+        }
+
+        Some(Embed::Website(WebsiteMetadata {
+            url: Some(url.to_string()),
+            original_url: Some(original.to_string()),
+            special: None,
+            title: metadata.title,
+            description: metadata.description,
+            image,
+            video,
+            site_name: metadata.site_name,
+            icon_url: metadata.icon_url,
+            colour: metadata.colour,
+        }))
+    }
+}
+
+/// Normalise a link so that equivalent links share a cache entry
+fn normalize(link: &str) -> Option<Url> {
+    let mut url = Url::parse(link).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    url.set_fragment(None);
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+
+    Some(url)
+}
+
+/// Find the dimensions of the first video track in an MP4 or QuickTime file
+fn video_dimensions(data: &[u8]) -> Option<(isize, isize)> {
+    let read = |offset: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize)
+    };
+
+    // Track headers end with the track's width and height as 16.16 fixed point numbers,
+    // audio tracks have both set to zero so keep looking until a video track turns up
+    let mut offset = 0;
+    while let Some(position) = data
+        .get(offset..)?
+        .windows(4)
+        .position(|window| window == b"tkhd")
+    {
+        let start = (offset + position).checked_sub(4)?;
+        let end = start + read(start)?;
+        if let (Some(width), Some(height)) = (read(end.checked_sub(8)?), read(end - 4)) {
+            if width >> 16 > 0 && height >> 16 > 0 {
+                return Some(((width >> 16) as isize, (height >> 16) as isize));
+            }
+        }
+
+        offset += position + 4;
+    }
+
+    None
+}
+
+/// Find the offset of the first top-level box of an MP4 or QuickTime file
+/// which starts after the end of the data read so far
+///
+/// Returns nothing if the data doesn't start like an MP4 file.
+fn unread_box_offset(data: &[u8]) -> Option<u64> {
+    if data.get(4..8)? != b"ftyp" {
+        return None;
+    }
+
+    let read = |offset: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+
+    let mut offset = 0u64;
+    while offset < data.len() as u64 {
+        let size = match read(offset as usize)? {
+            // Box extends to the end of the file
+            0 => return None,
+            // Size is given as a 64-bit number after the type
+            1 => (read(offset as usize + 8)? as u64) << 32 | read(offset as usize + 12)? as u64,
+            size => size as u64,
+        };
+
+        if size < 8 {
+            return None;
+        }
+
+        offset = offset.checked_add(size)?;
+    }
+
+    Some(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{IpAddr, TcpListener},
+        thread,
+    };
+
+    use revolt_config::config;
+    use revolt_models::v0::{Embed, Image, ImageSize, Video, WebsiteMetadata};
+
+    use super::{fetch::is_public, unread_box_offset, video_dimensions, Unfurler};
+
+    /// 1x1 transparent PNG
+    static PNG: [u8; 33] = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f,
+        0x15, 0xc4, 0x89,
+    ];
+
+    /// Build a track header box with the given dimensions
+    fn track_header(width: u32, height: u32) -> Vec<u8> {
+        let mut tkhd = 92u32.to_be_bytes().to_vec();
+        tkhd.extend_from_slice(b"tkhd");
+        tkhd.extend_from_slice(&[0; 76]);
+        tkhd.extend_from_slice(&(width << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(height << 16).to_be_bytes());
+        tkhd
+    }
+
+    /// Build an MP4 file with its movie box after the media data
+    fn unoptimised_video() -> Vec<u8> {
+        let mut video = 16u32.to_be_bytes().to_vec();
+        video.extend_from_slice(b"ftypisom\0\0\0\0");
+        video.extend_from_slice(&308u32.to_be_bytes());
+        video.extend_from_slice(b"mdat");
+        video.extend_from_slice(&[0; 300]);
+        video.extend_from_slice(&100u32.to_be_bytes());
+        video.extend_from_slice(b"moov");
+        video.extend(track_header(1280, 720));
+        video
+    }
+
+    /// Serve a fixed set of pages on a local port, returning its address
+    fn fixture_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let base = address.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(&stream).lines().map(Result::unwrap);
+                let request = lines.next().unwrap_or_default();
+                let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+
+                // Read the rest of the request so the connection closes cleanly
+                let mut range = None;
+                for line in lines.by_ref() {
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    if let Some((start, end)) = line
+                        .to_ascii_lowercase()
+                        .strip_prefix("range: bytes=")
+                        .and_then(|range| range.split_once('-'))
+                    {
+                        range = Some((
+                            start.parse::<usize>().unwrap(),
+                            end.parse::<usize>().unwrap(),
+                        ));
+                    }
+                }
+
+                drop(lines);
+
+                let (status, content_type, body): (&str, &str, Vec<u8>) = match path.as_str() {
+                    "/page" => (
+                        "200 OK",
+                        "text/html; charset=utf-8",
+                        format!(
+                            r##"<html><head>
+                                <title>Fallback Title</title>
+                                <meta property="og:title" content="Fixture Page">
+                                <meta name="twitter:card" content="summary_large_image">
+                                <meta name="description" content="A page for testing.">
+                                <meta property="og:image" content="/image.png">
+                                <meta name="theme-color" content="#ff4654">
+                                <link rel="shortcut icon" href="/favicon.ico">
+                                <link rel="alternate" type="application/json+oembed" href="{base}/oembed">
+                            </head></html>"##
+                        )
+                        .into_bytes(),
+                    ),
+                    "/oembed" => (
+                        "200 OK",
+                        "application/json",
+                        br#"{"type": "rich", "title": "oEmbed Title", "provider_name": "Fixtures"}"#
+                            .to_vec(),
+                    ),
+                    "/image.png" => ("200 OK", "image/png", PNG.to_vec()),
+                    "/video.mp4" => match range {
+                        Some((start, end)) => {
+                            let video = unoptimised_video();
+                            let end = (end + 1).min(video.len());
+                            ("206 Partial Content", "video/mp4", video[start..end].to_vec())
+                        }
+                        None => ("200 OK", "video/mp4", unoptimised_video()),
+                    },
+                    "/redirect" | "/redirect-ftp" => ("302 Found", "text/plain", vec![]),
+                    _ => ("404 Not Found", "text/plain", vec![]),
+                };
+
+                let location = if path == "/redirect-ftp" {
+                    format!("ftp://{}/page", &base["http://".len()..])
+                } else {
+                    "/page".to_string()
+                };
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nLocation: {location}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+
+        address
+    }
+
+    async fn unfurler() -> Unfurler {
+        Unfurler {
+            allow_private_addresses: true,
+            ..Unfurler::new(config().await.api.embeds)
+        }
+    }
+
+    #[async_std::test]
+    async fn unfurl_pages() {
+        let server = fixture_server();
+        let unfurler = unfurler().await;
+
+        let embed = Embed::Website(WebsiteMetadata {
+            url: Some(format!("{server}/page")),
+            original_url: Some(format!("{server}/page")),
+            special: None,
+            title: Some("Fixture Page".to_string()),
+            description: Some("A page for testing.".to_string()),
+            image: Some(Image {
+                url: format!("{server}/image.png"),
+                width: 1,
+                height: 1,
+                size: ImageSize::Large,
+            }),
+            video: None,
+            site_name: Some("Fixtures".to_string()),
+            icon_url: Some(format!("{server}/favicon.ico")),
+            colour: Some("#ff4654".to_string()),
+        });
+
+        assert_eq!(
+            unfurler.unfurl(&format!("{server}/page#anchor")).await,
+            Some(embed)
+        );
+
+        assert_eq!(
+            unfurler.unfurl(&format!("{server}/image.png")).await,
+            Some(Embed::Image(Image {
+                url: format!("{server}/image.png"),
+                width: 1,
+                height: 1,
+                size: ImageSize::Large,
+            }))
+        );
+
+        // Redirects are followed, but the original link is kept
+        let Some(Embed::Website(metadata)) = unfurler.unfurl(&format!("{server}/redirect")).await
+        else {
+            panic!("expected a website embed");
+        };
+
+        assert_eq!(metadata.url, Some(format!("{server}/page")));
+        assert_eq!(metadata.original_url, Some(format!("{server}/redirect")));
+
+        assert_eq!(unfurler.unfurl(&format!("{server}/missing")).await, None);
+        assert_eq!(unfurler.unfurl("ftp://example.com/file").await, None);
+
+        // Redirects must stay on the web too
+        assert_eq!(
+            unfurler.unfurl(&format!("{server}/redirect-ftp")).await,
+            None
+        );
+    }
+
+    #[async_std::test]
+    async fn unfurl_unoptimised_video() {
+        let server = fixture_server();
+        let mut config = config().await.api.embeds;
+
+        // Only part of the media data is read, so the track header has to be fetched separately
+        config.max_size = 128;
+        let unfurler = Unfurler {
+            allow_private_addresses: true,
+            ..Unfurler::new(config)
+        };
+
+        assert_eq!(
+            unfurler.unfurl(&format!("{server}/video.mp4")).await,
+            Some(Embed::Video(Video {
+                url: format!("{server}/video.mp4"),
+                width: 1280,
+                height: 720,
+            }))
+        );
+    }
+
+    #[async_std::test]
+    async fn refuse_private_addresses() {
+        let server = fixture_server();
+        let unfurler = Unfurler::new(config().await.api.embeds);
+        assert_eq!(unfurler.unfurl(&format!("{server}/page")).await, None);
+
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse::<IpAddr>().unwrap()), "{ip}");
+        }
+
+        for ip in [
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "100.64.0.1",
+        ] {
+            assert!(!is_public(ip.parse::<IpAddr>().unwrap()), "{ip}");
+        }
+
+        for ip in ["1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse::<IpAddr>().unwrap()), "{ip}");
+        }
+    }
+
+    #[async_std::test]
+    async fn filter_domains() {
+        let server = fixture_server();
+        let mut config = config().await.api.embeds;
+
+        config.denied_domains = vec!["127.0.0.1".to_string()];
+        let unfurler = Unfurler {
+            allow_private_addresses: true,
+            ..Unfurler::new(config.clone())
+        };
+
+        assert_eq!(unfurler.unfurl(&format!("{server}/page")).await, None);
+
+        config.denied_domains = vec![];
+        config.allowed_domains = vec!["example.com".to_string()];
+        let unfurler = Unfurler {
+            allow_private_addresses: true,
+            ..Unfurler::new(config)
+        };
+
+        assert!(unfurler.is_allowed(&"https://cdn.example.com/".parse().unwrap()));
+        assert!(!unfurler.is_allowed(&"https://notexample.com/".parse().unwrap()));
+        assert_eq!(unfurler.unfurl(&format!("{server}/page")).await, None);
+    }
+
+    #[test]
+    fn find_video_dimensions() {
+        let mut video = b"....ftypisom".to_vec();
+        video.extend(track_header(0, 0));
+        video.extend(track_header(1280, 720));
+
+        assert_eq!(video_dimensions(&video), Some((1280, 720)));
+        assert_eq!(video_dimensions(b"not a video"), None);
+
+        let video = unoptimised_video();
+        assert_eq!(video_dimensions(&video[..128]), None);
+        assert_eq!(unread_box_offset(&video[..128]), Some(324));
+        assert_eq!(unread_box_offset(b"not a video"), None);
+    }
+}
@@ -0,0 +1,416 @@
+//! Deliver push notifications to UnifiedPush endpoints
+use std::time::Duration;
+
+use futures::StreamExt;
+use isahc::{
+    config::{Configurable, RedirectPolicy, ResolveMap},
+    http::{header, StatusCode},
+    HttpClient, Request,
+};
+use revolt_config::config;
+use url::{Host, Url};
+use web_push::{ContentEncoding, SubscriptionInfo, WebPushMessageBuilder};
+
+use crate::{Database, UnifiedPushSubscription};
+
+use super::unfurl::resolve_public;
+
+/// How long (in seconds) distributors should hold onto a notification for an offline device
+const TTL: u32 = 60 * 60 * 24;
+
+/// Outcome of posting a notification to an endpoint
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// The endpoint accepted the notification
+    Delivered,
+    /// The endpoint no longer exists
+    Gone,
+    /// The notification was not accepted, but may be in future
+    Failed(String),
+}
+
+/// Delivers notifications to UnifiedPush endpoints with a plain HTTP POST
+pub struct UnifiedPush {
+    timeout: Duration,
+    max_failures: i32,
+    concurrency: usize,
+    /// Whether endpoints may point at private addresses, only used to test against a local server
+    allow_private_addresses: bool,
+}
+
+impl UnifiedPush {
+    /// Create a new sender from the configuration
+    pub async fn new() -> UnifiedPush {
+        let config = config().await;
+
+        UnifiedPush {
+            timeout: Duration::from_secs(config.api.unified_push.timeout),
+            max_failures: config.api.unified_push.max_failures,
+            concurrency: config.api.unified_push.concurrency,
+            allow_private_addresses: false,
+        }
+    }
+
+    /// Send a message to each endpoint, keeping track of which ones fail
+    ///
+    /// Endpoints which are gone, or have failed too many times in a row, are unsubscribed.
+    pub async fn send(
+        &self,
+        db: &Database,
+        subscriptions: Vec<UnifiedPushSubscription>,
+        payload: &[u8],
+    ) {
+        futures::stream::iter(subscriptions)
+            .for_each_concurrent(self.concurrency, |subscription| async move {
+                self.handle(db, &subscription, payload).await
+            })
+            .await;
+    }
+
+    /// Send a message to one endpoint and record the outcome
+    async fn handle(&self, db: &Database, subscription: &UnifiedPushSubscription, payload: &[u8]) {
+        let result = match self.deliver(subscription, payload).await {
+            Delivery::Delivered => {
+                info!("Sent UnifiedPush notification to {:?}.", subscription.id);
+
+                if subscription.failures > 0 {
+                    db.set_unified_push_failures(&subscription.id, 0).await
+                } else {
+                    Ok(())
+                }
+            }
+            Delivery::Gone => {
+                info!(
+                    "UnifiedPush endpoint for {:?} is gone, unsubscribing.",
+                    subscription.id
+                );
+
+                db.delete_unified_push_subscription(&subscription.id).await
+            }
+            Delivery::Failed(err) => {
+                let failures = subscription.failures + 1;
+                if failures >= self.max_failures {
+                    warn!(
+                        "UnifiedPush endpoint for {:?} failed {} times, unsubscribing! {}",
+                        subscription.id, failures, err
+                    );
+
+                    db.delete_unified_push_subscription(&subscription.id).await
+                } else {
+                    error!(
+                        "Hit error sending UnifiedPush to {:?}! {}",
+                        subscription.id, err
+                    );
+
+                    db.set_unified_push_failures(&subscription.id, failures)
+                        .await
+                }
+            }
+        };
+
+        if let Err(err) = result {
+            error!(
+                "Failed to update UnifiedPush subscription {:?}! {:?}",
+                subscription.id, err
+            );
+        }
+    }
+
+    /// Post a notification to an endpoint, encrypting it if the endpoint has keys
+    pub async fn deliver(
+        &self,
+        subscription: &UnifiedPushSubscription,
+        payload: &[u8],
+    ) -> Delivery {
+        let Ok(url) = Url::parse(&subscription.endpoint) else {
+            return Delivery::Gone;
+        };
+
+        // Never send a notification in the clear over plain HTTP
+        if subscription.keys.is_none() && url.scheme() != "https" {
+            return Delivery::Gone;
+        }
+
+        // Connect to the address we checked, rather than letting it be resolved again
+        let Some(address) = resolve_public(&url, self.allow_private_addresses).await else {
+            return Delivery::Failed("endpoint does not resolve to a public address".to_string());
+        };
+
+        let mut client = HttpClient::builder()
+            .timeout(self.timeout)
+            .redirect_policy(RedirectPolicy::None)
+            .proxy(None);
+
+        if let (Some(Host::Domain(domain)), Some(port)) = (url.host(), url.port_or_known_default())
+        {
+            client = client.dns_resolve(ResolveMap::new().add(domain, port, address));
+        }
+
+        let client = match client.build() {
+            Ok(client) => client,
+            Err(err) => return Delivery::Failed(err.to_string()),
+        };
+
+        let request = Request::post(url.as_str()).header("TTL", TTL.to_string());
+        let request = match &subscription.keys {
+            Some(keys) => {
+                let info = SubscriptionInfo::new(&subscription.endpoint, &keys.p256dh, &keys.auth);
+                let mut builder = WebPushMessageBuilder::new(&info);
+                builder.set_ttl(TTL);
+                builder.set_payload(ContentEncoding::Aes128Gcm, payload);
+
+                let encrypted = match builder.build() {
+                    Ok(message) => message.payload,
+                    Err(err) => return Delivery::Failed(format!("{err:?}")),
+                };
+
+                let Some(encrypted) = encrypted else {
+                    return Delivery::Failed("nothing to send".to_string());
+                };
+
+                encrypted
+                    .crypto_headers
+                    .into_iter()
+                    .fold(request, |request, (name, value)| {
+                        request.header(name, value)
+                    })
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .header(header::CONTENT_ENCODING, "aes128gcm")
+                    .body(encrypted.content)
+            }
+            None => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(payload.to_vec()),
+        };
+
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => return Delivery::Failed(err.to_string()),
+        };
+
+        match client.send_async(request).await {
+            Ok(response) => match response.status() {
+                status if status.is_success() => Delivery::Delivered,
+                StatusCode::NOT_FOUND | StatusCode::GONE => Delivery::Gone,
+                status => Delivery::Failed(format!("endpoint responded with {status}")),
+            },
+            Err(err) => Delivery::Failed(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc::{self, Receiver},
+        thread,
+        time::Duration,
+    };
+
+    use revolt_models::v0::PushNotification;
+
+    use crate::{UnifiedPushKeys, UnifiedPushSubscription};
+
+    use super::{Delivery, UnifiedPush};
+
+    /// Receiver keys from the example in RFC 8291
+    static P256DH: &str =
+        "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4";
+    static AUTH: &str = "BTBZMqHH6r4Tts7J_aSIgg";
+
+    /// Request received by the local push server
+    struct Received {
+        path: String,
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    /// Accept pushes on a local port, responding with the status named by
+    /// the path (e.g. `/410`), returning its address and the requests it receives
+    fn push_server() -> (String, Receiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+
+                    headers.insert(name.to_ascii_lowercase(), value.to_string());
+                }
+
+                let length = headers
+                    .get("content-length")
+                    .and_then(|length| length.parse().ok())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                drop(reader);
+
+                let status = path.trim_start_matches('/').parse().unwrap_or(200);
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+
+                sender
+                    .send(Received {
+                        path,
+                        headers,
+                        body,
+                    })
+                    .unwrap();
+            }
+        });
+
+        (address, receiver)
+    }
+
+    fn client() -> UnifiedPush {
+        UnifiedPush {
+            timeout: Duration::from_secs(5),
+            max_failures: 3,
+            concurrency: 4,
+            allow_private_addresses: true,
+        }
+    }
+
+    fn subscription(id: &str, endpoint: String, failures: i32) -> UnifiedPushSubscription {
+        UnifiedPushSubscription {
+            id: id.to_string(),
+            user: "user".to_string(),
+            endpoint,
+            keys: Some(UnifiedPushKeys {
+                p256dh: P256DH.to_string(),
+                auth: AUTH.to_string(),
+            }),
+            failures,
+        }
+    }
+
+    fn notification() -> PushNotification {
+        PushNotification {
+            author: "alice".to_string(),
+            icon: "alice.png".to_string(),
+            image: None,
+            body: "a secret message".to_string(),
+            tag: "channel".to_string(),
+            timestamp: 0,
+            url: "/channel".to_string(),
+        }
+    }
+
+    #[async_std::test]
+    async fn refuse_plain_http() {
+        database_test!(|db| async move {
+            let (address, receiver) = push_server();
+            let mut sub = subscription("session", format!("{address}/200"), 0);
+            sub.keys = None;
+            db.set_unified_push_subscription(&sub).await.unwrap();
+
+            let payload = serde_json::to_vec(&notification()).unwrap();
+            assert_eq!(client().deliver(&sub, &payload).await, Delivery::Gone);
+
+            client().send(&db, vec![sub], &payload).await;
+            assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+            assert!(db
+                .fetch_unified_push_subscriptions(&["user".to_string()])
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[async_std::test]
+    async fn deliver_encrypted() {
+        let (address, receiver) = push_server();
+        let sub = subscription("session", format!("{address}/201"), 0);
+
+        let payload = serde_json::to_vec(&notification()).unwrap();
+        assert_eq!(client().deliver(&sub, &payload).await, Delivery::Delivered);
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received.path, "/201");
+        assert!(received.headers.contains_key("ttl"));
+        assert_eq!(received.headers["content-encoding"], "aes128gcm");
+        assert!(!received
+            .body
+            .windows(b"a secret message".len())
+            .any(|window| window == b"a secret message"));
+
+        // RFC 8188 header: salt, record size, then the sender's uncompressed public key
+        assert_eq!(received.body[20], 65);
+        assert_eq!(received.body[21], 0x04);
+        assert!(received.body.len() > 86 + payload.len());
+    }
+
+    #[async_std::test]
+    async fn unsubscribe_dead_endpoints() {
+        database_test!(|db| async move {
+            let (address, receiver) = push_server();
+            let subscriptions = vec![
+                subscription("recovered", format!("{address}/200"), 2),
+                subscription("gone", format!("{address}/410"), 0),
+                subscription("flaky", format!("{address}/503"), 0),
+                subscription("dead", format!("{address}/500"), 2),
+            ];
+
+            for sub in &subscriptions {
+                db.set_unified_push_subscription(sub).await.unwrap();
+            }
+
+            client().send(&db, subscriptions, b"{}").await;
+            for _ in 0..4 {
+                receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            }
+
+            let mut remaining = db
+                .fetch_unified_push_subscriptions(&["user".to_string()])
+                .await
+                .unwrap();
+            remaining.sort_by(|a, b| a.id.cmp(&b.id));
+
+            assert_eq!(
+                remaining
+                    .iter()
+                    .map(|sub| (sub.id.as_str(), sub.failures))
+                    .collect::<Vec<_>>(),
+                [("flaky", 1), ("recovered", 0)]
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn refuse_private_endpoints() {
+        let (address, receiver) = push_server();
+        let sub = subscription("session", format!("{address}/200"), 0);
+
+        let client = UnifiedPush {
+            allow_private_addresses: false,
+            ..client()
+        };
+
+        assert!(matches!(
+            client.deliver(&sub, b"{}").await,
+            Delivery::Failed(_)
+        ));
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
@@ -1,80 +1,411 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use authifier::Database;
 use base64::{
     engine::{self},
     Engine as _,
 };
-use deadqueue::limited::Queue;
+use chrono::Utc;
 use fcm::FcmError;
-use once_cell::sync::Lazy;
 use revolt_config::config;
-use revolt_models::v0::PushNotification;
+use revolt_models::v0::{PushDismissal, PushNotification};
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
 use revolt_presence::filter_online;
+use revolt_result::Result;
 use serde_json::json;
 use web_push::{
     ContentEncoding, IsahcWebPushClient, SubscriptionInfo, SubscriptionKeys, VapidSignatureBuilder,
     WebPushClient, WebPushMessageBuilder,
 };
 
-use super::apple_notifications;
+use crate::{
+    util::permissions::DatabasePermissionQuery, Database, JobPayload, JobQueue, Member,
+    NotificationSettings, NotificationTrigger, Presence, PushDelivery, RelationshipStatus, User,
+};
 
-/// Task information
-#[derive(Debug)]
-struct PushTask {
-    /// User IDs of the targets that are to receive this notification
-    recipients: Vec<String>,
-    /// Push Notification
-    payload: PushNotification,
-}
+use super::{apple_notifications, jobs, push_coalesce, push_digest, unified_push::UnifiedPush};
 
-static Q: Lazy<Queue<PushTask>> = Lazy::new(|| Queue::new(10_000));
+/// Who a message mentions
+#[derive(Default)]
+pub struct Mentions {
+    /// Users mentioned directly
+    pub users: Vec<String>,
+    /// Roles whose members are mentioned
+    pub roles: Vec<String>,
+    /// Whether everyone who can see the channel is mentioned
+    pub everyone: bool,
+}
 
 /// Queue a new task for a worker
-pub async fn queue(recipients: Vec<String>, payload: PushNotification) {
-    if recipients.is_empty() {
-        return;
-    }
+pub async fn queue(
+    db: &Database,
+    channel: String,
+    server: Option<String>,
+    author: String,
+    recipients: Vec<String>,
+    mentions: Mentions,
+    payload: PushNotification,
+) {
+    // Members mentioned through roles or @everyone are found by the worker
+    let mass_mention = server.is_some() && (mentions.everyone || !mentions.roles.is_empty());
 
     let online_ids = filter_online(&recipients).await;
     let recipients = (&recipients.into_iter().collect::<HashSet<String>>() - &online_ids)
         .into_iter()
         .collect::<Vec<String>>();
 
-    Q.try_push(PushTask {
-        recipients,
-        payload,
-    })
-    .ok();
+    if recipients.is_empty() && !mass_mention {
+        return;
+    }
 
-    info!("Queue is using {} slots from {}.", Q.len(), Q.capacity());
+    jobs::enqueue(
+        db,
+        JobPayload::WebPush {
+            channel,
+            server,
+            author,
+            recipients,
+            mentions: mentions.users,
+            role_mentions: mentions.roles,
+            mentions_everyone: mentions.everyone,
+            payload,
+        },
+    )
+    .await;
 }
 
-/// Start a new worker
-pub async fn worker(db: Database) {
-    let config = config().await;
+/// How many members to look through at once when expanding mentions
+const MENTIONED_MEMBERS_PAGE_SIZE: i64 = 1_000;
 
-    let web_push_client = IsahcWebPushClient::new().unwrap();
-    let fcm_client = if config.api.fcm.api_key.is_empty() {
-        None
-    } else {
-        Some(fcm::Client::new())
+/// Find the members of a server who a message mentions through roles or @everyone
+///
+/// Only members who can see the channel are included, and anyone who is
+/// online is left out as they will see the message as it arrives.
+async fn mentioned_members(
+    db: &Database,
+    channel: &str,
+    server: Option<&str>,
+    author: &str,
+    roles: &[String],
+    everyone: bool,
+) -> Result<Vec<(String, NotificationTrigger)>> {
+    let Some(server) = server else {
+        return Ok(vec![]);
     };
 
-    let web_push_private_key = engine::general_purpose::URL_SAFE_NO_PAD
-        .decode(config.api.vapid.private_key)
-        .expect("valid `VAPID_PRIVATE_KEY`");
+    if roles.is_empty() && !everyone {
+        return Ok(vec![]);
+    }
+
+    let channel = db.fetch_channel(channel).await?;
+    let server = db.fetch_server(server).await?;
 
+    // Members with the same roles can all see the channel or none can
+    let mut visible_by_roles: HashMap<Vec<String>, bool> = HashMap::new();
+    let mut mentioned: Vec<(String, NotificationTrigger)> = vec![];
+    let mut after: Option<String> = None;
     loop {
-        let task = Q.pop().await;
-
-        if let Ok(sessions) = db.find_sessions_with_subscription(&task.recipients).await {
-            for session in sessions {
-                if let Some(sub) = session.subscription {
-                    if sub.endpoint == "fcm" {
-                        // Use Firebase Cloud Messaging
-                        if let Some(client) = &fcm_client {
-                            let PushNotification {
+        let page = db
+            .fetch_members_page(&server.id, after.as_deref(), MENTIONED_MEMBERS_PAGE_SIZE)
+            .await?;
+
+        let Some(last) = page.last() else {
+            break;
+        };
+
+        after = Some(last.id.user.clone());
+
+        let members: HashMap<&str, &Member> = page
+            .iter()
+            .filter(|member| member.id.user != author)
+            .filter(|member| everyone || member.roles.iter().any(|role| roles.contains(role)))
+            .map(|member| (member.id.user.as_str(), member))
+            .collect();
+
+        let ids: Vec<String> = members.keys().map(|id| id.to_string()).collect();
+        let online_ids = filter_online(&ids).await;
+        let ids: Vec<String> = ids
+            .into_iter()
+            .filter(|id| !online_ids.contains(id))
+            .collect();
+
+        let users = if ids.is_empty() {
+            vec![]
+        } else {
+            db.fetch_users(&ids).await?
+        };
+
+        for user in &users {
+            let Some(member) = members.get(user.id.as_str()) else {
+                continue;
+            };
+
+            let visible = if user.privileged || user.id == server.owner {
+                true
+            } else {
+                let mut member_roles = member.roles.clone();
+                member_roles.sort();
+
+                if let Some(visible) = visible_by_roles.get(&member_roles) {
+                    *visible
+                } else {
+                    let mut query = DatabasePermissionQuery::new(db, user)
+                        .channel(&channel)
+                        .server(&server)
+                        .member(member);
+
+                    let visible = calculate_channel_permissions(&mut query)
+                        .await
+                        .has_channel_permission(ChannelPermission::ViewChannel);
+
+                    visible_by_roles.insert(member_roles, visible);
+                    visible
+                }
+            };
+
+            if !visible {
+                continue;
+            }
+
+            if everyone {
+                mentioned.push((user.id.clone(), NotificationTrigger::Everyone));
+            }
+
+            if member.roles.iter().any(|role| roles.contains(role)) {
+                mentioned.push((user.id.clone(), NotificationTrigger::Role));
+            }
+        }
+
+        if (page.len() as i64) < MENTIONED_MEMBERS_PAGE_SIZE {
+            break;
+        }
+    }
+
+    Ok(mentioned)
+}
+
+/// Recipients of a push notification, sorted by when they should receive it
+#[derive(Default)]
+struct Recipients {
+    /// Users to notify now
+    now: Vec<String>,
+    /// Users to notify once their quiet hours end, with when they end
+    later: Vec<(String, i64)>,
+}
+
+/// Work out every reason each user would be notified about a message
+fn triggers(
+    recipients: &[String],
+    mentions: &[String],
+    mentioned_members: Vec<(String, NotificationTrigger)>,
+) -> HashMap<String, Vec<NotificationTrigger>> {
+    let mut triggers: HashMap<String, Vec<NotificationTrigger>> = HashMap::new();
+    for id in recipients {
+        triggers
+            .entry(id.clone())
+            .or_default()
+            .push(if mentions.contains(id) {
+                NotificationTrigger::Mention
+            } else {
+                NotificationTrigger::Message
+            });
+    }
+
+    for (id, trigger) in mentioned_members {
+        triggers.entry(id).or_default().push(trigger);
+    }
+
+    triggers
+}
+
+/// Sort recipients by their notification preferences, quiet hours and presence
+///
+/// Users are notified if any of their triggers passes their preferences.
+async fn filter_recipients(
+    db: &Database,
+    channel: &str,
+    server: Option<&str>,
+    author: &str,
+    triggers: &HashMap<String, Vec<NotificationTrigger>>,
+) -> Result<Recipients> {
+    let mut recipients: Vec<String> = triggers.keys().cloned().collect();
+    recipients.sort();
+
+    let settings: HashMap<String, NotificationSettings> = db
+        .fetch_many_notification_settings(&recipients)
+        .await?
+        .into_iter()
+        .map(|settings| (settings.id.clone(), settings))
+        .collect();
+
+    let users: HashMap<String, User> = db
+        .fetch_users(&recipients)
+        .await?
+        .into_iter()
+        .map(|user| (user.id.clone(), user))
+        .collect();
+
+    let defaults = NotificationSettings::default();
+    let now = Utc::now();
+
+    let mut sorted = Recipients::default();
+    for id in &recipients {
+        let settings = settings.get(id).unwrap_or(&defaults);
+        let user = users.get(id);
+
+        if !triggers[id]
+            .iter()
+            .any(|trigger| settings.should_notify(server, channel, *trigger))
+        {
+            continue;
+        }
+
+        let busy = user
+            .and_then(|user| user.status.as_ref())
+            .and_then(|status| status.presence.as_ref())
+            == Some(&Presence::Busy);
+
+        let direct = server.is_none().then(|| {
+            (
+                author,
+                user.is_some_and(|user| {
+                    user.relationship_with(author) == RelationshipStatus::Friend
+                }),
+            )
+        });
+
+        match settings.push_delivery(busy, direct, now) {
+            PushDelivery::Now => sorted.now.push(id.clone()),
+            PushDelivery::Until(end) => sorted.later.push((id.clone(), end.timestamp_millis())),
+            PushDelivery::Never => {}
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// Clients used to deliver push notifications
+pub(super) struct PushClients {
+    web_push: IsahcWebPushClient,
+    fcm: Option<fcm::Client>,
+    fcm_api_key: String,
+    web_push_private_key: Vec<u8>,
+    unified_push: UnifiedPush,
+}
+
+/// Message delivered to a user's devices
+#[derive(Clone, Copy)]
+enum PushMessage<'a> {
+    /// Show a notification
+    Notification(&'a PushNotification),
+    /// Remove notifications which have been read elsewhere
+    Dismissal(&'a PushDismissal),
+}
+
+impl PushMessage<'_> {
+    /// Body to deliver to services which pass the message on as is
+    fn to_json(self) -> String {
+        match self {
+            PushMessage::Notification(notification) => json!(notification).to_string(),
+            PushMessage::Dismissal(dismissal) => json!(dismissal).to_string(),
+        }
+    }
+}
+
+impl PushClients {
+    /// Set up clients for every configured push service
+    pub(super) async fn new() -> PushClients {
+        let config = config().await;
+
+        PushClients {
+            web_push: IsahcWebPushClient::new().unwrap(),
+            fcm: if config.api.fcm.api_key.is_empty() {
+                None
+            } else {
+                Some(fcm::Client::new())
+            },
+            fcm_api_key: config.api.fcm.api_key,
+            web_push_private_key: engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(config.api.vapid.private_key)
+                .expect("valid `VAPID_PRIVATE_KEY`"),
+            unified_push: UnifiedPush::new().await,
+        }
+    }
+
+    /// Send a notification to every subscribed session of the given users
+    ///
+    /// Individual sends aren't retried, as that would notify everyone else again.
+    pub(super) async fn send(
+        &self,
+        db: &Database,
+        authifier_db: &authifier::Database,
+        recipients: &[String],
+        payload: &PushNotification,
+    ) -> Result<(), authifier::Error> {
+        self.deliver(
+            db,
+            authifier_db,
+            recipients,
+            PushMessage::Notification(payload),
+        )
+        .await
+    }
+
+    /// Ask every subscribed session of a user to remove notifications with the given tag
+    pub(super) async fn dismiss(
+        &self,
+        db: &Database,
+        authifier_db: &authifier::Database,
+        user: &str,
+        tag: &str,
+    ) -> Result<(), authifier::Error> {
+        self.deliver(
+            db,
+            authifier_db,
+            &[user.to_string()],
+            PushMessage::Dismissal(&PushDismissal {
+                dismiss: tag.to_string(),
+            }),
+        )
+        .await
+    }
+
+    /// Deliver a message to every subscribed session of the given users
+    async fn deliver(
+        &self,
+        db: &Database,
+        authifier_db: &authifier::Database,
+        recipients: &[String],
+        message: PushMessage<'_>,
+    ) -> Result<(), authifier::Error> {
+        if recipients.is_empty() {
+            return Ok(());
+        }
+
+        let payload = message.to_json();
+
+        match db.fetch_unified_push_subscriptions(recipients).await {
+            Ok(subscriptions) => {
+                self.unified_push
+                    .send(db, subscriptions, payload.as_bytes())
+                    .await
+            }
+            Err(err) => error!("Failed to fetch UnifiedPush subscriptions! {:?}", err),
+        }
+
+        let sessions = authifier_db
+            .find_sessions_with_subscription(recipients)
+            .await?;
+
+        for session in sessions {
+            if let Some(sub) = session.subscription {
+                if sub.endpoint == "fcm" {
+                    // Use Firebase Cloud Messaging
+                    if let Some(client) = &self.fcm {
+                        let mut message_builder =
+                            fcm::MessageBuilder::new(&self.fcm_api_key, &sub.auth);
+
+                        match message {
+                            PushMessage::Notification(PushNotification {
                                 author,
                                 icon,
                                 image: _,
@@ -82,93 +413,230 @@ pub async fn worker(db: Database) {
                                 tag,
                                 timestamp: _,
                                 url: _,
-                            } = &task.payload;
-
-                            let mut notification = fcm::NotificationBuilder::new();
-                            notification.title(author);
-                            notification.icon(icon);
-                            notification.body(body);
-                            notification.tag(tag);
-                            // TODO: expand support for fields
-                            let notification = notification.finalize();
-
-                            let mut message_builder =
-                                fcm::MessageBuilder::new(&config.api.fcm.api_key, &sub.auth);
-                            message_builder.notification(notification);
-
-                            if let Err(err) = client.send(message_builder.finalize()).await {
-                                error!("Failed to send FCM notification! {:?}", err);
-                            } else {
-                                info!("Sent FCM notification to {:?}.", session.id);
+                            }) => {
+                                let mut notification = fcm::NotificationBuilder::new();
+                                notification.title(author);
+                                notification.icon(icon);
+                                notification.body(body);
+                                notification.tag(tag);
+                                // TODO: expand support for fields
+                                message_builder.notification(notification.finalize());
                             }
+                            PushMessage::Dismissal(dismissal) => {
+                                // Data messages are handed to the app without being shown
+                                if let Err(err) = message_builder.data(&json!(dismissal)) {
+                                    error!("Failed to build FCM dismissal! {:?}", err);
+                                    continue;
+                                }
+                            }
+                        }
+
+                        if let Err(err) = client.send(message_builder.finalize()).await {
+                            error!("Failed to send FCM notification! {:?}", err);
                         } else {
-                            info!("No FCM token was specified!");
+                            info!("Sent FCM notification to {:?}.", session.id);
                         }
-                    } else if sub.endpoint == "apn" {
-                        apple_notifications::queue(
-                            apple_notifications::ApnTask::from_notification(
-                                session.id,
-                                sub.auth,
-                                &task.payload,
-                            ),
-                        )
-                        .await;
                     } else {
-                        // Use Web Push Standard
-                        let subscription = SubscriptionInfo {
-                            endpoint: sub.endpoint,
-                            keys: SubscriptionKeys {
-                                auth: sub.auth,
-                                p256dh: sub.p256dh,
-                            },
-                        };
-
-                        match VapidSignatureBuilder::from_pem(
-                            std::io::Cursor::new(&web_push_private_key),
-                            &subscription,
-                        ) {
-                            Ok(sig_builder) => match sig_builder.build() {
-                                Ok(signature) => {
-                                    let mut builder = WebPushMessageBuilder::new(&subscription);
-                                    builder.set_vapid_signature(signature);
-
-                                    let payload = json!(task.payload).to_string();
-                                    builder
-                                        .set_payload(ContentEncoding::AesGcm, payload.as_bytes());
-
-                                    match builder.build() {
-                                        Ok(msg) => match web_push_client.send(msg).await {
-                                            Ok(_) => {
-                                                info!(
-                                                    "Sent Web Push notification to {:?}.",
-                                                    session.id
-                                                )
-                                            }
-                                            Err(err) => {
-                                                error!("Hit error sending Web Push! {:?}", err)
-                                            }
-                                        },
+                        info!("No FCM token was specified!");
+                    }
+                } else if sub.endpoint == "apn" {
+                    apple_notifications::queue(
+                        db,
+                        match message {
+                            PushMessage::Notification(notification) => {
+                                apple_notifications::ApnTask::from_notification(
+                                    session.id,
+                                    sub.auth,
+                                    notification,
+                                )
+                            }
+                            PushMessage::Dismissal(dismissal) => {
+                                apple_notifications::ApnTask::dismissal(
+                                    session.id,
+                                    sub.auth,
+                                    &dismissal.dismiss,
+                                )
+                            }
+                        },
+                    )
+                    .await;
+                } else {
+                    // Use Web Push Standard
+                    let subscription = SubscriptionInfo {
+                        endpoint: sub.endpoint,
+                        keys: SubscriptionKeys {
+                            auth: sub.auth,
+                            p256dh: sub.p256dh,
+                        },
+                    };
+
+                    match VapidSignatureBuilder::from_pem(
+                        std::io::Cursor::new(&self.web_push_private_key),
+                        &subscription,
+                    ) {
+                        Ok(sig_builder) => match sig_builder.build() {
+                            Ok(signature) => {
+                                let mut builder = WebPushMessageBuilder::new(&subscription);
+                                builder.set_vapid_signature(signature);
+                                builder.set_payload(ContentEncoding::AesGcm, payload.as_bytes());
+
+                                match builder.build() {
+                                    Ok(msg) => match self.web_push.send(msg).await {
+                                        Ok(_) => {
+                                            info!("Sent Web Push notification to {:?}.", session.id)
+                                        }
                                         Err(err) => {
-                                            error!(
-                                                "Failed to build message for {}! {:?}",
-                                                session.user_id, err
-                                            )
+                                            error!("Hit error sending Web Push! {:?}", err)
                                         }
+                                    },
+                                    Err(err) => {
+                                        error!(
+                                            "Failed to build message for {}! {:?}",
+                                            session.user_id, err
+                                        )
                                     }
                                 }
-                                Err(err) => error!(
-                                    "Failed to build signature for {}! {:?}",
-                                    session.user_id, err
-                                ),
-                            },
+                            }
                             Err(err) => error!(
-                                "Failed to create signature builder for {}! {:?}",
+                                "Failed to build signature for {}! {:?}",
                                 session.user_id, err
                             ),
-                        }
+                        },
+                        Err(err) => error!(
+                            "Failed to create signature builder for {}! {:?}",
+                            session.user_id, err
+                        ),
                     }
                 }
             }
         }
+
+        Ok(())
+    }
+}
+
+/// Start a new worker
+pub async fn worker(db: Database) {
+    loop {
+        for job in jobs::claim(&db, JobQueue::WebPush).await {
+            let JobPayload::WebPush {
+                channel,
+                server,
+                author,
+                recipients,
+                mentions,
+                role_mentions,
+                mentions_everyone,
+                payload,
+            } = &job.payload
+            else {
+                jobs::fail(&db, &job, "not a web_push job").await;
+                continue;
+            };
+
+            let mentioned = match mentioned_members(
+                &db,
+                channel,
+                server.as_deref(),
+                author,
+                role_mentions,
+                *mentions_everyone,
+            )
+            .await
+            {
+                Ok(mentioned) => mentioned,
+                Err(err) => {
+                    jobs::fail(&db, &job, err).await;
+                    continue;
+                }
+            };
+
+            // Preferences are checked here, rather than when queued, so that
+            // changes are honoured by every push service (including APN).
+            let triggers = triggers(recipients, mentions, mentioned);
+            let recipients =
+                match filter_recipients(&db, channel, server.as_deref(), author, &triggers).await {
+                    Ok(recipients) => recipients,
+                    Err(err) => {
+                        jobs::fail(&db, &job, err).await;
+                        continue;
+                    }
+                };
+
+            if !recipients.now.is_empty() {
+                push_coalesce::queue(&db, channel.clone(), recipients.now, payload.clone()).await;
+            }
+
+            for (user, until) in recipients.later {
+                push_digest::queue(&db, user, payload.clone(), until).await;
+            }
+
+            jobs::complete(&db, &[job]).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NotificationOverride, NotificationSettings, NotificationTrigger, User};
+
+    use super::{filter_recipients, triggers};
+
+    #[async_std::test]
+    async fn suppressed_mentions() {
+        database_test!(|db| async move {
+            let mut ids = vec![];
+            for username in ["everyone", "suppressed", "role", "mentioned"] {
+                ids.push(
+                    User::create(&db, username.to_string(), None, None)
+                        .await
+                        .unwrap()
+                        .id,
+                );
+            }
+
+            let [everyone, suppressed, role, mentioned] = ids.as_slice() else {
+                unreachable!()
+            };
+
+            // Both users ignore @everyone in the server, but only one was also mentioned by role
+            for id in [suppressed, role] {
+                let mut settings = NotificationSettings {
+                    id: id.clone(),
+                    ..Default::default()
+                };
+
+                settings.servers.insert(
+                    "server".to_string(),
+                    NotificationOverride {
+                        suppress_everyone: true,
+                        ..Default::default()
+                    },
+                );
+
+                settings.set(&db).await.unwrap();
+            }
+
+            let triggers = triggers(
+                &[mentioned.clone()],
+                &[mentioned.clone()],
+                vec![
+                    (everyone.clone(), NotificationTrigger::Everyone),
+                    (suppressed.clone(), NotificationTrigger::Everyone),
+                    (role.clone(), NotificationTrigger::Everyone),
+                    (role.clone(), NotificationTrigger::Role),
+                    (mentioned.clone(), NotificationTrigger::Everyone),
+                ],
+            );
+
+            let recipients = filter_recipients(&db, "channel", Some("server"), "author", &triggers)
+                .await
+                .unwrap();
+
+            let mut expected = vec![everyone.clone(), role.clone(), mentioned.clone()];
+            expected.sort();
+            assert_eq!(recipients.now, expected);
+            assert!(recipients.later.is_empty());
+        });
     }
 }
@@ -34,6 +34,7 @@ impl From<crate::Bot> for Bot {
             terms_of_service_url: value.terms_of_service_url,
             privacy_policy_url: value.privacy_policy_url,
             flags: value.flags.unwrap_or_default() as u32,
+            privileged_intents: value.privileged_intents.unwrap_or_default() as u32,
         }
     }
 }
@@ -89,6 +90,7 @@ impl From<crate::ChannelUnread> for ChannelUnread {
             id: value.id.into(),
             last_id: value.last_id,
             mentions: value.mentions.unwrap_or_default(),
+            count: None,
         }
     }
 }
@@ -387,6 +389,24 @@ impl From<EmojiParent> for crate::EmojiParent {
 
 impl From<crate::File> for File {
     fn from(value: crate::File) -> Self {
+        // Fill in the fields which were stored before files had a parent
+        let (message_id, user_id, server_id, object_id) = match value.parent.clone() {
+            Some(crate::FileParent::Message { id }) => (Some(id), None, None, None),
+            Some(
+                crate::FileParent::UserAvatar { id }
+                | crate::FileParent::UserBackground { id }
+                | crate::FileParent::WebhookAvatar { id },
+            ) => (None, Some(id), None, None),
+            Some(crate::FileParent::MemberAvatar { user, .. }) => (None, Some(user), None, None),
+            Some(
+                crate::FileParent::ChannelIcon { id }
+                | crate::FileParent::ServerIcon { id }
+                | crate::FileParent::Emoji { id },
+            ) => (None, None, None, Some(id)),
+            Some(crate::FileParent::ServerBanner { id }) => (None, None, Some(id), None),
+            Some(crate::FileParent::ReportEvidence { .. }) | None => (None, None, None, None),
+        };
+
         File {
             id: value.id,
             tag: value.tag,
@@ -396,10 +416,11 @@ impl From<crate::File> for File {
             size: value.size,
             deleted: value.deleted,
             reported: value.reported,
-            message_id: value.message_id,
-            user_id: value.user_id,
-            server_id: value.server_id,
-            object_id: value.object_id,
+            parent: value.parent.map(Into::into),
+            message_id,
+            user_id,
+            server_id,
+            object_id,
         }
     }
 }
@@ -415,10 +436,46 @@ impl From<File> for crate::File {
             size: value.size,
             deleted: value.deleted,
             reported: value.reported,
-            message_id: value.message_id,
-            user_id: value.user_id,
-            server_id: value.server_id,
-            object_id: value.object_id,
+            parent: value.parent.map(Into::into),
+            orphaned_at: None,
+        }
+    }
+}
+
+impl From<crate::FileParent> for FileParent {
+    fn from(value: crate::FileParent) -> Self {
+        match value {
+            crate::FileParent::Message { id } => FileParent::Message { id },
+            crate::FileParent::UserAvatar { id } => FileParent::UserAvatar { id },
+            crate::FileParent::UserBackground { id } => FileParent::UserBackground { id },
+            crate::FileParent::MemberAvatar { server, user } => {
+                FileParent::MemberAvatar { server, user }
+            }
+            crate::FileParent::ChannelIcon { id } => FileParent::ChannelIcon { id },
+            crate::FileParent::ServerIcon { id } => FileParent::ServerIcon { id },
+            crate::FileParent::ServerBanner { id } => FileParent::ServerBanner { id },
+            crate::FileParent::Emoji { id } => FileParent::Emoji { id },
+            crate::FileParent::WebhookAvatar { id } => FileParent::WebhookAvatar { id },
+            crate::FileParent::ReportEvidence { id } => FileParent::ReportEvidence { id },
+        }
+    }
+}
+
+impl From<FileParent> for crate::FileParent {
+    fn from(value: FileParent) -> Self {
+        match value {
+            FileParent::Message { id } => crate::FileParent::Message { id },
+            FileParent::UserAvatar { id } => crate::FileParent::UserAvatar { id },
+            FileParent::UserBackground { id } => crate::FileParent::UserBackground { id },
+            FileParent::MemberAvatar { server, user } => {
+                crate::FileParent::MemberAvatar { server, user }
+            }
+            FileParent::ChannelIcon { id } => crate::FileParent::ChannelIcon { id },
+            FileParent::ServerIcon { id } => crate::FileParent::ServerIcon { id },
+            FileParent::ServerBanner { id } => crate::FileParent::ServerBanner { id },
+            FileParent::Emoji { id } => crate::FileParent::Emoji { id },
+            FileParent::WebhookAvatar { id } => crate::FileParent::WebhookAvatar { id },
+            FileParent::ReportEvidence { id } => crate::FileParent::ReportEvidence { id },
         }
     }
 }
@@ -477,6 +534,7 @@ impl crate::Message {
             edited: self.edited,
             embeds: self.embeds,
             mentions: self.mentions,
+            role_mentions: self.role_mentions,
             replies: self.replies,
             reactions: self.reactions,
             interactions: self.interactions.into(),
@@ -504,6 +562,7 @@ impl From<crate::PartialMessage> for PartialMessage {
             edited: value.edited,
             embeds: value.embeds,
             mentions: value.mentions,
+            role_mentions: value.role_mentions,
             replies: value.replies,
             reactions: value.reactions,
             interactions: value.interactions.map(Into::into),
@@ -585,6 +644,148 @@ impl From<Masquerade> for crate::Masquerade {
     }
 }
 
+impl From<crate::NotificationLevel> for NotificationLevel {
+    fn from(value: crate::NotificationLevel) -> Self {
+        match value {
+            crate::NotificationLevel::All => NotificationLevel::All,
+            crate::NotificationLevel::Mentions => NotificationLevel::Mentions,
+            crate::NotificationLevel::None => NotificationLevel::None,
+        }
+    }
+}
+
+impl From<NotificationLevel> for crate::NotificationLevel {
+    fn from(value: NotificationLevel) -> Self {
+        match value {
+            NotificationLevel::All => crate::NotificationLevel::All,
+            NotificationLevel::Mentions => crate::NotificationLevel::Mentions,
+            NotificationLevel::None => crate::NotificationLevel::None,
+        }
+    }
+}
+
+impl From<crate::NotificationOverride> for NotificationOverride {
+    fn from(value: crate::NotificationOverride) -> Self {
+        NotificationOverride {
+            level: value.level.map(|level| level.into()),
+            muted_until: value.muted_until,
+            suppress_everyone: value.suppress_everyone,
+            suppress_roles: value.suppress_roles,
+        }
+    }
+}
+
+impl From<NotificationOverride> for crate::NotificationOverride {
+    fn from(value: NotificationOverride) -> Self {
+        crate::NotificationOverride {
+            level: value.level.map(|level| level.into()),
+            muted_until: value.muted_until,
+            suppress_everyone: value.suppress_everyone,
+            suppress_roles: value.suppress_roles,
+        }
+    }
+}
+
+impl From<crate::NotificationSettings> for NotificationSettings {
+    fn from(value: crate::NotificationSettings) -> Self {
+        NotificationSettings {
+            servers: value
+                .servers
+                .into_iter()
+                .map(|(id, value)| (id, value.into()))
+                .collect(),
+            channels: value
+                .channels
+                .into_iter()
+                .map(|(id, value)| (id, value.into()))
+                .collect(),
+            quiet_hours: value.quiet_hours.map(|quiet_hours| quiet_hours.into()),
+            email_digest: value.email_digest.is_some(),
+            hide_content: value.hide_content,
+        }
+    }
+}
+
+impl From<crate::Weekday> for Weekday {
+    fn from(value: crate::Weekday) -> Self {
+        match value {
+            crate::Weekday::Monday => Weekday::Monday,
+            crate::Weekday::Tuesday => Weekday::Tuesday,
+            crate::Weekday::Wednesday => Weekday::Wednesday,
+            crate::Weekday::Thursday => Weekday::Thursday,
+            crate::Weekday::Friday => Weekday::Friday,
+            crate::Weekday::Saturday => Weekday::Saturday,
+            crate::Weekday::Sunday => Weekday::Sunday,
+        }
+    }
+}
+
+impl From<Weekday> for crate::Weekday {
+    fn from(value: Weekday) -> Self {
+        match value {
+            Weekday::Monday => crate::Weekday::Monday,
+            Weekday::Tuesday => crate::Weekday::Tuesday,
+            Weekday::Wednesday => crate::Weekday::Wednesday,
+            Weekday::Thursday => crate::Weekday::Thursday,
+            Weekday::Friday => crate::Weekday::Friday,
+            Weekday::Saturday => crate::Weekday::Saturday,
+            Weekday::Sunday => crate::Weekday::Sunday,
+        }
+    }
+}
+
+impl From<crate::QuietHoursWindow> for QuietHoursWindow {
+    fn from(value: crate::QuietHoursWindow) -> Self {
+        QuietHoursWindow {
+            days: value.days.into_iter().map(|day| day.into()).collect(),
+            start: value.start,
+            end: value.end,
+        }
+    }
+}
+
+impl From<QuietHoursWindow> for crate::QuietHoursWindow {
+    fn from(value: QuietHoursWindow) -> Self {
+        crate::QuietHoursWindow {
+            days: value.days.into_iter().map(|day| day.into()).collect(),
+            start: value.start,
+            end: value.end,
+        }
+    }
+}
+
+impl From<crate::QuietHours> for QuietHours {
+    fn from(value: crate::QuietHours) -> Self {
+        QuietHours {
+            timezone: value.timezone,
+            windows: value
+                .windows
+                .into_iter()
+                .map(|window| window.into())
+                .collect(),
+            digest: value.digest,
+            allow_friends: value.allow_friends,
+            allow_users: value.allow_users,
+        }
+    }
+}
+
+impl From<QuietHours> for crate::QuietHours {
+    fn from(value: QuietHours) -> Self {
+        crate::QuietHours {
+            timezone: value.timezone,
+            windows: value
+                .windows
+                .into_iter()
+                .map(|window| window.into())
+                .collect(),
+            digest: value.digest,
+            allow_friends: value.allow_friends,
+            allow_users: value.allow_users,
+        }
+    }
+}
+
 impl From<crate::Report> for Report {
     fn from(value: crate::Report) -> Self {
         Report {
@@ -947,6 +1148,19 @@ impl From<FieldsRole> for crate::FieldsRole {
     }
 }
 
+/// Show idle users as idle unless they've chosen a different presence
+fn effective_status(status: Option<crate::UserStatus>, is_idle: bool) -> Option<UserStatus> {
+    let mut status: Option<UserStatus> = status.map(|status| status.into());
+    if is_idle {
+        let status = status.get_or_insert_with(Default::default);
+        if matches!(status.presence, None | Some(Presence::Online)) {
+            status.presence = Some(Presence::Idle);
+        }
+    }
+
+    status
+}
+
 impl crate::User {
     pub async fn into<'a, P>(self, db: &Database, perspective: P) -> User
     where
@@ -982,6 +1196,11 @@ impl crate::User {
             (RelationshipStatus::None, false)
         };
 
+        let is_online = can_see_profile
+            && revolt_presence::is_online(&self.id).await
+            && !self.is_invisible();
+        let is_idle = is_online && revolt_presence::is_idle(&self.id).await;
+
         User {
             username: self.username,
             discriminator: self.discriminator,
@@ -1001,17 +1220,10 @@ impl crate::User {
                 vec![]
             },
             badges: self.badges.unwrap_or_default() as u32,
-            online: can_see_profile
-                && revolt_presence::is_online(&self.id).await
-                && !matches!(
-                    self.status,
-                    Some(crate::UserStatus {
-                        presence: Some(crate::Presence::Invisible),
-                        ..
-                    })
-                ),
+            online: is_online,
+            idle: is_idle,
             status: if can_see_profile {
-                self.status.map(|status| status.into())
+                effective_status(self.status, is_idle)
             } else {
                 None
             },
@@ -1019,12 +1231,18 @@ impl crate::User {
             privileged: self.privileged,
             bot: self.bot.map(|bot| bot.into()),
             relationship,
+            last_seen: if can_see_profile && self.share_last_seen {
+                self.last_seen
+            } else {
+                None
+            },
+            share_last_seen: can_see_profile && self.share_last_seen,
             id: self.id,
         }
     }
 
     /// Convert user object into user model assuming mutual connection
-    pub fn into_known<'a, P>(self, perspective: P, is_online: bool) -> User
+    pub fn into_known<'a, P>(self, perspective: P, is_online: bool, is_idle: bool) -> User
     where
         P: Into<Option<&'a crate::User>>,
     {
@@ -1054,6 +1272,9 @@ impl crate::User {
             (RelationshipStatus::None, false)
         };
 
+        let is_online = can_see_profile && is_online && !self.is_invisible();
+        let is_idle = is_online && is_idle;
+
         User {
             username: self.username,
             discriminator: self.discriminator,
@@ -1073,17 +1294,10 @@ impl crate::User {
                 vec![]
             },
             badges: self.badges.unwrap_or_default() as u32,
-            online: can_see_profile
-                && is_online
-                && !matches!(
-                    self.status,
-                    Some(crate::UserStatus {
-                        presence: Some(crate::Presence::Invisible),
-                        ..
-                    })
-                ),
+            online: is_online,
+            idle: is_idle,
             status: if can_see_profile {
-                self.status.map(|status| status.into())
+                effective_status(self.status, is_idle)
             } else {
                 None
             },
@@ -1091,12 +1305,20 @@ impl crate::User {
             privileged: self.privileged,
             bot: self.bot.map(|bot| bot.into()),
             relationship,
+            last_seen: if can_see_profile && self.share_last_seen {
+                self.last_seen
+            } else {
+                None
+            },
+            share_last_seen: can_see_profile && self.share_last_seen,
             id: self.id,
         }
     }
 
     /// Convert user object into user model without presence information
     pub fn into_known_static<'a>(self, is_online: bool) -> User {
+        let is_online = is_online && !self.is_invisible();
+
         User {
             username: self.username,
             discriminator: self.discriminator,
@@ -1104,24 +1326,28 @@ impl crate::User {
             avatar: self.avatar.map(|file| file.into()),
             relations: vec![],
             badges: self.badges.unwrap_or_default() as u32,
-            online: is_online
-                && !matches!(
-                    self.status,
-                    Some(crate::UserStatus {
-                        presence: Some(crate::Presence::Invisible),
-                        ..
-                    })
-                ),
+            online: is_online,
+            idle: false,
             status: self.status.map(|status| status.into()),
             flags: self.flags.unwrap_or_default() as u32,
             privileged: self.privileged,
             bot: self.bot.map(|bot| bot.into()),
             relationship: RelationshipStatus::None, // events client will populate this from cache
+            last_seen: if self.share_last_seen {
+                self.last_seen
+            } else {
+                None
+            },
+            share_last_seen: self.share_last_seen,
             id: self.id,
         }
     }
 
     pub async fn into_self(self, force_online: bool) -> User {
+        let is_online = (force_online || revolt_presence::is_online(&self.id).await)
+            && !self.is_invisible();
+        let is_idle = is_online && revolt_presence::is_idle(&self.id).await;
+
         User {
             username: self.username,
             discriminator: self.discriminator,
@@ -1137,23 +1363,30 @@ impl crate::User {
                 })
                 .unwrap_or_default(),
             badges: self.badges.unwrap_or_default() as u32,
-            online: (force_online || revolt_presence::is_online(&self.id).await)
-                && !matches!(
-                    self.status,
-                    Some(crate::UserStatus {
-                        presence: Some(crate::Presence::Invisible),
-                        ..
-                    })
-                ),
+            online: is_online,
+            idle: is_idle,
             status: self.status.map(|status| status.into()),
             flags: self.flags.unwrap_or_default() as u32,
             privileged: self.privileged,
             bot: self.bot.map(|bot| bot.into()),
             relationship: RelationshipStatus::User,
+            last_seen: self.last_seen,
+            share_last_seen: self.share_last_seen,
             id: self.id,
         }
     }
 
+    /// Whether the user has chosen to appear offline
+    fn is_invisible(&self) -> bool {
+        matches!(
+            self.status,
+            Some(crate::UserStatus {
+                presence: Some(crate::Presence::Invisible),
+                ..
+            })
+        )
+    }
+
     pub fn as_author_for_system(&self) -> MessageAuthor {
         MessageAuthor::System {
             username: &self.username,
@@ -1177,6 +1410,8 @@ impl From<User> for crate::User {
             flags: Some(value.flags as i32),
             privileged: value.privileged,
             bot: value.bot.map(Into::into),
+            last_seen: value.last_seen,
+            share_last_seen: value.share_last_seen,
         }
     }
 }
@@ -1201,7 +1436,31 @@ impl From<crate::PartialUser> for PartialUser {
             bot: value.bot.map(|bot| bot.into()),
             relationship: None,
             online: None,
+            idle: None,
+            last_seen: value.last_seen,
+            share_last_seen: value.share_last_seen,
+            id: value.id,
+        }
+    }
+}
+
+impl From<PartialUser> for crate::PartialUser {
+    fn from(value: PartialUser) -> crate::PartialUser {
+        crate::PartialUser {
             id: value.id,
+            username: value.username,
+            discriminator: value.discriminator,
+            display_name: value.display_name,
+            avatar: value.avatar.map(|file| file.into()),
+            relations: None,
+            badges: value.badges.map(|badges| badges as i32),
+            status: value.status.map(|status| status.into()),
+            profile: None,
+            flags: value.flags.map(|flags| flags as i32),
+            privileged: value.privileged,
+            bot: value.bot.map(|bot| bot.into()),
+            last_seen: value.last_seen,
+            share_last_seen: value.share_last_seen,
         }
     }
 }
@@ -1284,6 +1543,11 @@ impl From<crate::UserStatus> for UserStatus {
         UserStatus {
             text: value.text,
             presence: value.presence.map(|presence| presence.into()),
+            activities: value
+                .activities
+                .into_iter()
+                .map(|activity| activity.into())
+                .collect(),
         }
     }
 }
@@ -1293,6 +1557,53 @@ impl From<UserStatus> for crate::UserStatus {
         crate::UserStatus {
             text: value.text,
             presence: value.presence.map(|presence| presence.into()),
+            activities: value
+                .activities
+                .into_iter()
+                .map(|activity| activity.into())
+                .collect(),
+        }
+    }
+}
+
+impl From<crate::ActivityType> for ActivityType {
+    fn from(value: crate::ActivityType) -> Self {
+        match value {
+            crate::ActivityType::Playing => ActivityType::Playing,
+            crate::ActivityType::Listening => ActivityType::Listening,
+            crate::ActivityType::Watching => ActivityType::Watching,
+        }
+    }
+}
+
+impl From<ActivityType> for crate::ActivityType {
+    fn from(value: ActivityType) -> crate::ActivityType {
+        match value {
+            ActivityType::Playing => crate::ActivityType::Playing,
+            ActivityType::Listening => crate::ActivityType::Listening,
+            ActivityType::Watching => crate::ActivityType::Watching,
+        }
+    }
+}
+
+impl From<crate::Activity> for Activity {
+    fn from(value: crate::Activity) -> Self {
+        Activity {
+            activity_type: value.activity_type.into(),
+            name: value.name,
+            details: value.details,
+            started_at: value.started_at,
+        }
+    }
+}
+
+impl From<Activity> for crate::Activity {
+    fn from(value: Activity) -> crate::Activity {
+        crate::Activity {
+            activity_type: value.activity_type.into(),
+            name: value.name,
+            details: value.details,
+            started_at: value.started_at,
         }
     }
 }
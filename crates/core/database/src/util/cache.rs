@@ -0,0 +1,352 @@
+use std::{
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use once_cell::sync::OnceCell;
+use revolt_result::Result;
+
+use crate::{
+    events::{
+        broker::{broker, BrokerEvent},
+        client::EventV1,
+    },
+    Channel, Database, Member, MemberCompositeKey, Server, User,
+};
+
+#[cfg(feature = "prometheus")]
+use once_cell::sync::Lazy;
+
+/// Number of cache lookups, labelled by entity and whether they hit
+#[cfg(feature = "prometheus")]
+pub static CACHE_REQUESTS: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::IntCounterVec::new(
+        prometheus::Opts::new(
+            "revolt_entity_cache_requests_total",
+            "Number of entity cache lookups",
+        ),
+        &["entity", "result"],
+    )
+    .expect("valid metric")
+});
+
+static CACHE: OnceCell<EntityCache> = OnceCell::new();
+
+/// Cached object alongside the time it was fetched
+struct Entry<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+/// Bounded map of cached objects which expire after some time
+struct EntityMap<K: Hash + Eq, T> {
+    #[cfg_attr(not(feature = "prometheus"), allow(dead_code))]
+    name: &'static str,
+    map: Mutex<LruCache<K, Entry<T>>>,
+}
+
+impl<K: Hash + Eq, T: Clone> EntityMap<K, T> {
+    fn new(name: &'static str, capacity: NonZeroUsize) -> Self {
+        EntityMap {
+            name,
+            map: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Get an object if it is cached and has not expired
+    fn get(&self, key: &K, ttl: Duration) -> Option<T> {
+        let mut map = self.map.lock().expect("cache lock poisoned");
+        let value = match map.get(key) {
+            Some(entry) if entry.fetched_at.elapsed() < ttl => Some(entry.value.clone()),
+            Some(_) => {
+                map.pop(key);
+                None
+            }
+            None => None,
+        };
+
+        #[cfg(feature = "prometheus")]
+        CACHE_REQUESTS
+            .with_label_values(&[self.name, if value.is_some() { "hit" } else { "miss" }])
+            .inc();
+
+        value
+    }
+
+    fn put(&self, key: K, value: T) {
+        self.map.lock().expect("cache lock poisoned").put(
+            key,
+            Entry {
+                value,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    fn remove(&self, key: &K) {
+        self.map.lock().expect("cache lock poisoned").pop(key);
+    }
+
+    /// Remove every object matching a predicate
+    fn remove_where<F: Fn(&K, &T) -> bool>(&self, f: F)
+    where
+        K: Clone,
+    {
+        let mut map = self.map.lock().expect("cache lock poisoned");
+        let keys: Vec<K> = map
+            .iter()
+            .filter(|(key, entry)| f(key, &entry.value))
+            .map(|(key, _)| key)
+            .cloned()
+            .collect();
+
+        for key in keys {
+            map.pop(&key);
+        }
+    }
+
+    fn clear(&self) {
+        self.map.lock().expect("cache lock poisoned").clear();
+    }
+}
+
+/// Read-through cache of frequently fetched objects
+///
+/// Objects are evicted whenever an event describing a change to them is
+/// published or received from the broker, the time-to-live only exists to
+/// bound staleness from writes which don't produce an event.
+struct EntityCache {
+    ttl: Duration,
+    /// Incremented on every invalidation, so that objects fetched
+    /// before an invalidation don't get cached after it.
+    generation: AtomicU64,
+    users: EntityMap<String, User>,
+    servers: EntityMap<String, Server>,
+    channels: EntityMap<String, Channel>,
+    members: EntityMap<MemberCompositeKey, Member>,
+}
+
+/// Enable the entity cache and keep it up to date with events from the broker
+///
+/// Does nothing if the capacity is zero or the cache is already enabled.
+pub fn enable(capacity: usize, ttl: Duration) {
+    let Some(capacity) = NonZeroUsize::new(capacity) else {
+        return;
+    };
+
+    let cache = EntityCache {
+        ttl,
+        generation: AtomicU64::new(0),
+        users: EntityMap::new("user", capacity),
+        servers: EntityMap::new("server", capacity),
+        channels: EntityMap::new("channel", capacity),
+        members: EntityMap::new("member", capacity),
+    };
+
+    if CACHE.set(cache).is_ok() {
+        async_std::task::spawn(listen());
+    }
+}
+
+/// Evict objects as events are received from the broker
+async fn listen() {
+    let subscriber = broker()
+        .await
+        .subscriber()
+        .await
+        .expect("Failed to create a subscriber");
+
+    subscriber
+        .subscribe_all()
+        .await
+        .expect("Failed to subscribe to events");
+
+    while let Some(message) = subscriber.next().await {
+        match message {
+            BrokerEvent::Event { event, .. } => invalidate(&event),
+            // We may have missed events, so nothing can be trusted.
+            BrokerEvent::Interrupted => clear(),
+        }
+    }
+
+    error!("Event broker subscriber closed unexpectedly!");
+    clear();
+}
+
+/// Evict any objects changed by an event
+pub fn invalidate(event: &EventV1) {
+    let Some(cache) = CACHE.get() else {
+        return;
+    };
+
+    cache.generation.fetch_add(1, Ordering::SeqCst);
+
+    match event {
+        EventV1::Bulk { v } => {
+            for event in v {
+                invalidate(event);
+            }
+        }
+        EventV1::ServerUpdate { id, .. }
+        | EventV1::ServerRoleUpdate { id, .. }
+        | EventV1::ServerRoleDelete { id, .. } => cache.servers.remove(id),
+        EventV1::ServerDelete { id } => {
+            cache.servers.remove(id);
+            cache.members.remove_where(|key, _| &key.server == id);
+            cache.channels.remove_where(|_, channel| match channel {
+                Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
+                    server == id
+                }
+                _ => false,
+            });
+        }
+        EventV1::ServerMemberUpdate { id, .. } => {
+            cache.members.remove(&MemberCompositeKey::from(id.clone()))
+        }
+        EventV1::ServerMemberJoin { id, user } | EventV1::ServerMemberLeave { id, user } => {
+            cache.members.remove(&MemberCompositeKey {
+                server: id.clone(),
+                user: user.clone(),
+            })
+        }
+        EventV1::ChannelCreate(channel) => match channel {
+            // Server keeps track of its channels.
+            revolt_models::v0::Channel::TextChannel { server, .. }
+            | revolt_models::v0::Channel::VoiceChannel { server, .. } => {
+                cache.servers.remove(server)
+            }
+            _ => {}
+        },
+        EventV1::ChannelDelete { id } => {
+            cache.channels.remove(id);
+            cache
+                .servers
+                .remove_where(|_, server| server.channels.contains(id));
+        }
+        EventV1::ChannelUpdate { id, .. }
+        | EventV1::ChannelGroupJoin { id, .. }
+        | EventV1::ChannelGroupLeave { id, .. } => cache.channels.remove(id),
+        EventV1::UserUpdate { id, .. } => cache.users.remove(id),
+        EventV1::UserRelationship { id, user } => {
+            cache.users.remove(id);
+            cache.users.remove(&user.id);
+        }
+        EventV1::UserPlatformWipe { user_id, .. } => {
+            cache.users.remove(user_id);
+            cache.members.remove_where(|key, _| &key.user == user_id);
+        }
+        _ => {}
+    }
+}
+
+/// Evict every cached object
+pub fn clear() {
+    if let Some(cache) = CACHE.get() {
+        cache.generation.fetch_add(1, Ordering::SeqCst);
+        cache.users.clear();
+        cache.servers.clear();
+        cache.channels.clear();
+        cache.members.clear();
+    }
+}
+
+/// Fetch an object through the cache, if it is enabled
+async fn read_through<K, T, F>(
+    map: impl Fn(&EntityCache) -> &EntityMap<K, T>,
+    key: K,
+    fetch: F,
+) -> Result<T>
+where
+    K: Hash + Eq,
+    T: Clone,
+    F: std::future::Future<Output = Result<T>>,
+{
+    let Some(cache) = CACHE.get() else {
+        return fetch.await;
+    };
+
+    if let Some(value) = map(cache).get(&key, cache.ttl) {
+        return Ok(value);
+    }
+
+    let generation = cache.generation.load(Ordering::SeqCst);
+    let value = fetch.await?;
+    if cache.generation.load(Ordering::SeqCst) == generation {
+        map(cache).put(key, value.clone());
+    }
+
+    Ok(value)
+}
+
+/// Fetch a user, using the cache if possible
+pub async fn fetch_user(db: &Database, id: &str) -> Result<User> {
+    read_through(|cache| &cache.users, id.to_string(), db.fetch_user(id)).await
+}
+
+/// Fetch a server, using the cache if possible
+pub async fn fetch_server(db: &Database, id: &str) -> Result<Server> {
+    read_through(|cache| &cache.servers, id.to_string(), db.fetch_server(id)).await
+}
+
+/// Fetch a channel, using the cache if possible
+pub async fn fetch_channel(db: &Database, id: &str) -> Result<Channel> {
+    read_through(
+        |cache| &cache.channels,
+        id.to_string(),
+        db.fetch_channel(id),
+    )
+    .await
+}
+
+/// Fetch a server member, using the cache if possible
+pub async fn fetch_member(db: &Database, server: &str, user: &str) -> Result<Member> {
+    read_through(
+        |cache| &cache.members,
+        MemberCompositeKey {
+            server: server.to_string(),
+            user: user.to_string(),
+        },
+        db.fetch_member(server, user),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, time::Duration};
+
+    use super::EntityMap;
+
+    #[test]
+    fn expire_after_ttl() {
+        let map = EntityMap::new("test", NonZeroUsize::new(10).unwrap());
+        map.put("a".to_string(), 1);
+
+        assert_eq!(map.get(&"a".to_string(), Duration::from_secs(60)), Some(1));
+        assert_eq!(map.get(&"a".to_string(), Duration::ZERO), None);
+
+        // Expired objects are evicted
+        assert_eq!(map.get(&"a".to_string(), Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn remove_matching() {
+        let map = EntityMap::new("test", NonZeroUsize::new(10).unwrap());
+        map.put("a".to_string(), 1);
+        map.put("b".to_string(), 2);
+        map.put("c".to_string(), 3);
+
+        map.remove_where(|_, value| value % 2 == 1);
+
+        let ttl = Duration::from_secs(60);
+        assert_eq!(map.get(&"a".to_string(), ttl), None);
+        assert_eq!(map.get(&"b".to_string(), ttl), Some(2));
+        assert_eq!(map.get(&"c".to_string(), ttl), None);
+    }
+}
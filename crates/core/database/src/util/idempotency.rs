@@ -3,7 +3,7 @@ use std::num::NonZeroUsize;
 use revolt_result::{create_error, Result};
 
 #[cfg(feature = "rocket-impl")]
-use revolt_result::Error;
+use revolt_result::{Error, ErrorType};
 
 use async_std::sync::Mutex;
 use once_cell::sync::Lazy;
@@ -34,6 +34,23 @@ impl IdempotencyKey {
         Ok(())
     }
 
+    /// Create an idempotency key from a client-provided value
+    pub async fn from_nonce(key: String) -> Result<IdempotencyKey> {
+        if key.len() > 64 {
+            return Err(create_error!(FailedValidation {
+                error: "idempotency key too long".to_string(),
+            }));
+        }
+
+        let mut cache = TOKEN_CACHE.lock().await;
+        if cache.get(&key).is_some() {
+            return Err(create_error!(DuplicateNonce));
+        }
+
+        cache.put(key.clone(), ());
+        Ok(IdempotencyKey { key })
+    }
+
     pub fn into_key(self) -> String {
         self.key
     }
@@ -97,23 +114,18 @@ impl<'r> FromRequest<'r> for IdempotencyKey {
             .next()
             .map(|k| k.to_string())
         {
-            if key.len() > 64 {
-                return Outcome::Failure((
-                    Status::BadRequest,
-                    create_error!(FailedValidation {
-                        error: "idempotency key too long".to_string(),
-                    }),
-                ));
-            }
-
-            let idempotency = IdempotencyKey { key };
-            let mut cache = TOKEN_CACHE.lock().await;
-            if cache.get(&idempotency.key).is_some() {
-                return Outcome::Failure((Status::Conflict, create_error!(DuplicateNonce)));
-            }
-
-            cache.put(idempotency.key.clone(), ());
-            return Outcome::Success(idempotency);
+            return match IdempotencyKey::from_nonce(key).await {
+                Ok(idempotency) => Outcome::Success(idempotency),
+                Err(error) => {
+                    let status = if let ErrorType::DuplicateNonce = error.error_type {
+                        Status::Conflict
+                    } else {
+                        Status::BadRequest
+                    };
+
+                    Outcome::Failure((status, error))
+                }
+            };
         }
 
         Outcome::Success(IdempotencyKey {
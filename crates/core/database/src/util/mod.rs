@@ -1,5 +1,7 @@
 pub mod bridge;
+pub mod cache;
 pub mod idempotency;
 pub mod permissions;
+pub mod ratelimit;
 pub mod reference;
 pub mod test_fixtures;
@@ -5,7 +5,7 @@ use revolt_permissions::{
     RelationshipStatus, DEFAULT_PERMISSION_DIRECT_MESSAGE,
 };
 
-use crate::{Channel, Database, Member, Server, User};
+use crate::{util::cache, Channel, Database, Member, Server, User};
 
 /// Permissions calculator
 #[derive(Clone)]
@@ -126,10 +126,8 @@ impl PermissionQuery for DatabasePermissionQuery<'_> {
         if let Some(server) = &self.server {
             if self.member.is_some() {
                 true
-            } else if let Ok(member) = self
-                .database
-                .fetch_member(&server.id, &self.perspective.id)
-                .await
+            } else if let Ok(member) =
+                cache::fetch_member(self.database, &server.id, &self.perspective.id).await
             {
                 self.member = Some(Cow::Owned(member));
                 true
@@ -332,7 +330,7 @@ impl PermissionQuery for DatabasePermissionQuery<'_> {
                         .find(|recipient| recipient != &&self.perspective.id)
                         .expect("Missing recipient for DM");
 
-                    if let Ok(user) = self.database.fetch_user(recipient_id).await {
+                    if let Ok(user) = cache::fetch_user(self.database, recipient_id).await {
                         self.user.replace(Cow::Owned(user));
                     }
                 }
@@ -367,7 +365,7 @@ impl PermissionQuery for DatabasePermissionQuery<'_> {
                         }
                     }
 
-                    if let Ok(server) = self.database.fetch_server(server).await {
+                    if let Ok(server) = cache::fetch_server(self.database, server).await {
                         self.server.replace(Cow::Owned(server));
                     }
                 }
@@ -0,0 +1,160 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::ops::Add;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+/// Ratelimit Bucket
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    used: u8,
+    reset: u128,
+}
+
+static MAP: Lazy<DashMap<u64, Entry>> = Lazy::new(DashMap::new);
+
+/// Get the current time from Unix Epoch as a Duration
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards...")
+}
+
+impl Entry {
+    /// Find bucket by its key
+    pub fn from(key: u64) -> Entry {
+        MAP.get(&key).map(|x| *x).unwrap_or_else(|| Entry {
+            used: 0,
+            reset: now().add(Duration::from_secs(10)).as_millis(),
+        })
+    }
+
+    /// Deduct one unit from the bucket and save
+    pub fn deduct(&mut self) {
+        let current_time = now().as_millis();
+        if current_time > self.reset {
+            self.used = 1;
+            self.reset = now().add(Duration::from_secs(10)).as_millis();
+        } else {
+            self.used += 1;
+        }
+    }
+
+    /// Save information
+    pub fn save(self, key: u64) {
+        MAP.insert(key, self);
+    }
+
+    /// Get remaining units in the bucket
+    pub fn get_remaining(&self, limit: u8) -> u8 {
+        if now().as_millis() > self.reset {
+            limit
+        } else {
+            limit - self.used
+        }
+    }
+
+    /// Get how long bucket has until reset
+    pub fn left_until_reset(&self) -> u128 {
+        self.reset.saturating_sub(now().as_millis())
+    }
+}
+
+/// Resolve per-bucket limits
+pub fn resolve_bucket_limit(bucket: &str) -> u8 {
+    match bucket {
+        "user_edit" => 2,
+        "users" => 20,
+        "bots" => 10,
+        "messaging" => 10,
+        "channels" => 15,
+        "servers" => 5,
+        "auth" => 15,
+        "auth_delete" => 255,
+        "default_avatar" => 255,
+        "file_upload" => 10,
+        "files" => 255,
+        "swagger" => 100,
+        "safety" => 15,
+        "safety_report" => 3,
+        _ => 20,
+    }
+}
+
+/// State of a ratelimit bucket after an attempt to use it
+///
+/// Buckets are shared by every caller in this process, so actions
+/// taken over the API and over the events server draw from the same
+/// allowance as long as they use the same identifier and bucket.
+#[derive(Clone, Copy, Debug)]
+pub struct Ratelimit {
+    pub key: u64,
+    pub limit: u8,
+    pub remaining: u8,
+    pub reset: u128,
+}
+
+impl Ratelimit {
+    /// Deduct one unit from the bucket for an identifier
+    ///
+    /// Optionally, include a resource id to hash against.
+    pub fn deduct(
+        identifier: &str,
+        (bucket, resource): (&str, Option<&str>),
+    ) -> Result<Ratelimit, Ratelimit> {
+        let mut key = DefaultHasher::new();
+        key.write(identifier.as_bytes());
+        key.write(bucket.as_bytes());
+
+        if let Some(id) = resource {
+            key.write(id.as_bytes());
+        }
+
+        let key = key.finish();
+        let limit = resolve_bucket_limit(bucket);
+        let mut entry = Entry::from(key);
+
+        let remaining = entry.get_remaining(limit);
+        let reset = entry.left_until_reset();
+        let mut ratelimit = Ratelimit {
+            key,
+            limit,
+            remaining,
+            reset,
+        };
+        if remaining == 0 {
+            return Err(ratelimit);
+        }
+
+        entry.deduct();
+        entry.save(key);
+        ratelimit.remaining -= 1;
+        ratelimit.reset = entry.left_until_reset();
+
+        Ok(ratelimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_bucket_limit, Ratelimit};
+
+    #[test]
+    fn exhausts_bucket_per_identifier_and_resource() {
+        let limit = resolve_bucket_limit("messaging");
+        for remaining in (0..limit).rev() {
+            let ratelimit = Ratelimit::deduct("session", ("messaging", Some("channel"))).unwrap();
+            assert_eq!(ratelimit.remaining, remaining);
+        }
+
+        let ratelimit = Ratelimit::deduct("session", ("messaging", Some("channel"))).unwrap_err();
+        assert_eq!(ratelimit.remaining, 0);
+        assert!(ratelimit.reset > 0);
+
+        // Other resources and identifiers have their own allowance.
+        assert!(Ratelimit::deduct("session", ("messaging", Some("other"))).is_ok());
+        assert!(Ratelimit::deduct("other", ("messaging", Some("channel"))).is_ok());
+    }
+}
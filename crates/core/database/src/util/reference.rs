@@ -10,7 +10,8 @@ use schemars::{
 };
 
 use crate::{
-    Bot, Channel, Database, Emoji, Invite, Member, Message, Server, ServerBan, User, Webhook,
+    util::cache, Bot, Channel, Database, Emoji, Invite, Member, Message, Server, ServerBan, User,
+    Webhook,
 };
 
 /// Reference to some object in the database
@@ -43,7 +44,7 @@ impl Reference {
 
     /// Fetch channel from Ref
     pub async fn as_channel(&self, db: &Database) -> Result<Channel> {
-        db.fetch_channel(&self.id).await
+        cache::fetch_channel(db, &self.id).await
     }
 
     /// Fetch invite from Ref or create invite to server if discoverable
@@ -86,17 +87,17 @@ impl Reference {
 
     /// Fetch member from Ref
     pub async fn as_member(&self, db: &Database, server: &str) -> Result<Member> {
-        db.fetch_member(server, &self.id).await
+        cache::fetch_member(db, server, &self.id).await
     }
 
     /// Fetch server from Ref
     pub async fn as_server(&self, db: &Database) -> Result<Server> {
-        db.fetch_server(&self.id).await
+        cache::fetch_server(db, &self.id).await
     }
 
     /// Fetch user from Ref
     pub async fn as_user(&self, db: &Database) -> Result<User> {
-        db.fetch_user(&self.id).await
+        cache::fetch_user(db, &self.id).await
     }
 
     /// Fetch webhook from Ref
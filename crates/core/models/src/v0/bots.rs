@@ -54,6 +54,12 @@ auto_derived!(
             serde(skip_serializing_if = "crate::if_zero_u32", default)
         )]
         pub flags: u32,
+        /// Bitfield of privileged gateway intents this bot may request
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_zero_u32", default)
+        )]
+        pub privileged_intents: u32,
     }
 
     /// Optional fields on bot object
@@ -131,6 +137,8 @@ auto_derived!(
         /// Interactions URL
         #[cfg_attr(feature = "validator", validate(length(min = 1, max = 2048)))]
         pub interactions_url: Option<String>,
+        /// Bitfield of privileged gateway intents to opt into
+        pub privileged_intents: Option<u32>,
         /// Fields to remove from bot object
         #[cfg_attr(feature = "validator", validate(length(min = 1)))]
         pub remove: Option<Vec<FieldsBot>>,
@@ -1,3 +1,6 @@
+#[cfg(feature = "rocket")]
+use rocket::FromForm;
+
 auto_derived!(
     /// Channel Unread
     pub struct ChannelUnread {
@@ -14,6 +17,11 @@ auto_derived!(
             serde(skip_serializing_if = "Vec::is_empty", default)
         )]
         pub mentions: Vec<String>,
+        /// Number of unread messages, if requested
+        ///
+        /// Counting stops at 100, which should be shown as "99+".
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub count: Option<u32>,
     }
 
     /// Composite primary key consisting of channel and user id
@@ -24,4 +32,45 @@ auto_derived!(
         /// User Id
         pub user: String,
     }
+
+    /// Unread messages across a server's channels, leaving out muted channels
+    pub struct ServerUnread {
+        /// Server Id
+        #[serde(rename = "_id")]
+        pub id: String,
+        /// Number of unread messages
+        ///
+        /// Counting stops at 100, which should be shown as "99+".
+        pub count: u32,
+        /// Number of unread mentions
+        pub mentions: u32,
+    }
+
+    /// Unread state of a user's channels with counts for each server
+    pub struct Unreads {
+        /// Unread state of each channel
+        pub channels: Vec<ChannelUnread>,
+        /// Servers with unread messages
+        pub servers: Vec<ServerUnread>,
+    }
+
+    /// Unreads Response
+    #[serde(untagged)]
+    pub enum UnreadsResponse {
+        JustUnreads(
+            /// Unread state of each channel
+            Vec<ChannelUnread>,
+        ),
+        UnreadsWithCounts(
+            /// Unread state of each channel with counts for each server
+            Unreads,
+        ),
+    }
+
+    /// Options for fetching unreads
+    #[cfg_attr(feature = "rocket", derive(FromForm))]
+    pub struct OptionsFetchUnreads {
+        /// Whether to count unread messages in each channel and server
+        pub include_counts: Option<bool>,
+    }
 );
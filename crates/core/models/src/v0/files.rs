@@ -22,9 +22,12 @@ auto_derived!(
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub reported: Option<bool>,
 
-        // TODO: migrate this mess to having:
-        // - author_id
-        // - parent: Parent { Message(id), User(id), etc }
+        /// Object this file is attached to
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub parent: Option<FileParent>,
+
+        // Deprecated in favour of `parent`, which these are derived
+        // from. Kept until the next API version.
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub message_id: Option<String>,
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
@@ -37,6 +40,31 @@ auto_derived!(
         pub object_id: Option<String>,
     }
 
+    /// Object which a file is attached to
+    #[cfg_attr(feature = "serde", serde(tag = "type"))]
+    pub enum FileParent {
+        /// File is attached to a message or one of its embeds
+        Message { id: String },
+        /// File is a user's avatar
+        UserAvatar { id: String },
+        /// File is a user's profile background
+        UserBackground { id: String },
+        /// File is a server member's avatar
+        MemberAvatar { server: String, user: String },
+        /// File is a channel's icon
+        ChannelIcon { id: String },
+        /// File is a server's icon
+        ServerIcon { id: String },
+        /// File is a server's banner
+        ServerBanner { id: String },
+        /// File is an emoji
+        Emoji { id: String },
+        /// File is a webhook's avatar
+        WebhookAvatar { id: String },
+        /// File is evidence kept for a report
+        ReportEvidence { id: String },
+    }
+
     /// Metadata associated with a file
     #[cfg_attr(feature = "serde", serde(tag = "type"))]
     #[derive(Default)]
@@ -18,6 +18,11 @@ use super::{Embed, File, Member, MessageWebhook, User, Webhook, RE_COLOUR};
 pub static RE_MENTION: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"<@([0-9A-HJKMNP-TV-Z]{26})>").unwrap());
 
+pub static RE_ROLE_MENTION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<%([0-9A-HJKMNP-TV-Z]{26})>").unwrap());
+
+pub static RE_EVERYONE: Lazy<Regex> = Lazy::new(|| Regex::new(r"@everyone\b").unwrap());
+
 auto_derived_partial!(
     /// Message
     pub struct Message {
@@ -58,6 +63,9 @@ auto_derived_partial!(
         /// Array of user ids mentioned in this message
         #[serde(skip_serializing_if = "Option::is_none")]
         pub mentions: Option<Vec<String>>,
+        /// Array of role ids mentioned in this message
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub role_mentions: Option<Vec<String>>,
         /// Array of message ids this message is replying to
         #[serde(skip_serializing_if = "Option::is_none")]
         pub replies: Option<Vec<String>>,
@@ -202,6 +210,12 @@ auto_derived!(
         pub url: String,
     }
 
+    /// Push message asking a device to remove notifications it has shown
+    pub struct PushDismissal {
+        /// Tag of the notifications to remove, usually the channel ID
+        pub dismiss: String,
+    }
+
     /// Representation of a text embed before it is sent.
     #[derive(Default)]
     #[cfg_attr(feature = "validator", derive(Validate))]
@@ -354,6 +368,8 @@ auto_derived!(
     pub enum MessageFlags {
         /// Message will not send push / desktop notifications
         SuppressNotifications = 1,
+        /// Message mentions everyone in the channel, only set by the server
+        MentionsEveryone = 2,
     }
 );
 
@@ -7,6 +7,7 @@ mod embeds;
 mod emojis;
 mod files;
 mod messages;
+mod notification_settings;
 mod safety_reports;
 mod server_bans;
 mod server_members;
@@ -23,6 +24,7 @@ pub use embeds::*;
 pub use emojis::*;
 pub use files::*;
 pub use messages::*;
+pub use notification_settings::*;
 pub use safety_reports::*;
 pub use server_bans::*;
 pub use server_members::*;
@@ -0,0 +1,161 @@
+use iso8601_timestamp::Timestamp;
+use std::collections::HashMap;
+
+auto_derived!(
+    /// What a user wants to be notified about
+    pub enum NotificationLevel {
+        /// Every message
+        All,
+        /// Only messages which mention the user
+        Mentions,
+        /// Nothing
+        None,
+    }
+
+    /// Notification preferences for a server or channel
+    #[derive(Default)]
+    pub struct NotificationOverride {
+        /// What to be notified about, inherited from the server if not set
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub level: Option<NotificationLevel>,
+        /// Time until which all notifications are muted
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub muted_until: Option<Timestamp>,
+        /// Whether to ignore @everyone mentions
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub suppress_everyone: bool,
+        /// Whether to ignore role mentions
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub suppress_roles: bool,
+    }
+
+    /// Day of the week
+    pub enum Weekday {
+        Monday,
+        Tuesday,
+        Wednesday,
+        Thursday,
+        Friday,
+        Saturday,
+        Sunday,
+    }
+
+    /// Period of the day during which push notifications are held back
+    ///
+    /// Windows which end at or before the time they start run into the next day.
+    pub struct QuietHoursWindow {
+        /// Days on which the window starts
+        pub days: Vec<Weekday>,
+        /// Minute of the day at which the window starts
+        pub start: u16,
+        /// Minute of the day at which the window ends
+        pub end: u16,
+    }
+
+    /// Schedule during which push notifications are held back
+    ///
+    /// Pushes are also held back whenever the user is busy, in which case
+    /// the exceptions still apply but no digest is sent.
+    pub struct QuietHours {
+        /// IANA time zone the schedule is in, e.g. `Europe/London`
+        pub timezone: String,
+        /// Windows making up the schedule
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub windows: Vec<QuietHoursWindow>,
+        /// Whether to send a summary of held back notifications once a window
+        /// ends rather than dropping them
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub digest: bool,
+        /// Whether direct messages from friends are let through
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub allow_friends: bool,
+        /// Users whose direct messages are let through
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Vec::is_empty", default)
+        )]
+        pub allow_users: Vec<String>,
+    }
+
+    /// User's notification preferences
+    #[derive(Default)]
+    pub struct NotificationSettings {
+        /// Preferences for servers, applying to all of their channels
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub servers: HashMap<String, NotificationOverride>,
+        /// Preferences for individual channels, taking precedence over their server
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub channels: HashMap<String, NotificationOverride>,
+        /// Schedule during which push notifications are held back
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub quiet_hours: Option<QuietHours>,
+        /// Whether the user is sent email digests of mentions and direct
+        /// messages they missed while offline
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub email_digest: bool,
+        /// Whether push notifications only say there is a new message,
+        /// leaving out who sent it and what it says
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub hide_content: bool,
+    }
+
+    /// Optional fields on notification settings
+    pub enum FieldsNotificationSettings {
+        QuietHours,
+    }
+
+    /// Changes to a user's notification preferences
+    ///
+    /// Setting a server or channel to `null` removes its preferences.
+    #[derive(Default)]
+    pub struct DataEditNotificationSettings {
+        /// Servers to change preferences for
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub servers: HashMap<String, Option<NotificationOverride>>,
+        /// Channels to change preferences for
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub channels: HashMap<String, Option<NotificationOverride>>,
+        /// New quiet hours schedule
+        pub quiet_hours: Option<QuietHours>,
+        /// Whether to send email digests
+        pub email_digest: Option<bool>,
+        /// Whether to leave message content out of push notifications
+        pub hide_content: Option<bool>,
+        /// Fields to remove
+        pub remove: Option<Vec<FieldsNotificationSettings>>,
+    }
+);
+
+auto_derived!(
+    /// UnifiedPush endpoint to deliver a session's notifications to
+    #[cfg_attr(feature = "validator", derive(validator::Validate))]
+    pub struct DataUnifiedPushSubscription {
+        /// URL given to the app by its UnifiedPush distributor
+        #[cfg_attr(feature = "validator", validate(url, length(max = 2048)))]
+        pub endpoint: String,
+        /// Public key to encrypt notifications for (P-256, base64url)
+        ///
+        /// Notifications are sent as plain JSON unless both keys are given.
+        pub p256dh: Option<String>,
+        /// Authentication secret to encrypt notifications with (base64url)
+        pub auth: Option<String>,
+    }
+);
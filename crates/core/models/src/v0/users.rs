@@ -1,3 +1,4 @@
+use iso8601_timestamp::Timestamp;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
@@ -75,6 +76,23 @@ auto_derived_partial!(
         pub relationship: RelationshipStatus,
         /// Whether this user is currently online
         pub online: bool,
+        /// Whether all of this user's sessions are idle
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub idle: bool,
+        /// Time at which this user was last online
+        ///
+        /// Only present if the user has chosen to share it.
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub last_seen: Option<Timestamp>,
+        /// Whether this user shares when they were last online
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub share_last_seen: bool,
     },
     "PartialUser"
 );
@@ -144,6 +162,41 @@ auto_derived!(
         /// Current presence option
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub presence: Option<Presence>,
+        /// Activities the user is currently engaged in
+        #[validate(length(min = 0, max = 5))]
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Vec::is_empty", default)
+        )]
+        pub activities: Vec<Activity>,
+    }
+
+    /// Type of activity
+    pub enum ActivityType {
+        /// Playing a game
+        Playing,
+        /// Listening to music or audio
+        Listening,
+        /// Watching a video or stream
+        Watching,
+    }
+
+    /// Activity the user is engaged in
+    #[cfg_attr(feature = "validator", derive(Validate))]
+    pub struct Activity {
+        /// Type of activity
+        #[cfg_attr(feature = "serde", serde(rename = "type"))]
+        pub activity_type: ActivityType,
+        /// Name of the game, song or show
+        #[validate(length(min = 1, max = 128))]
+        pub name: String,
+        /// Additional details
+        #[validate(length(min = 1, max = 128))]
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub details: Option<String>,
+        /// Time at which the activity started
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub started_at: Option<Timestamp>,
     }
 
     /// User's profile
@@ -240,6 +293,9 @@ auto_derived!(
         /// Enum of user flags
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub flags: Option<i32>,
+        /// Whether to share when you were last online
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub share_last_seen: Option<bool>,
 
         /// Fields to remove from user object
         #[cfg_attr(feature = "validator", validate(length(min = 1)))]
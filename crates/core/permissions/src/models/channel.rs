@@ -91,7 +91,12 @@ pub enum ChannelPermission {
     MoveMembers = 1 << 35,
 
     // * Misc. permissions
-    // % Bits 36 to 52: free area
+    /// Mention everyone in a channel with @everyone
+    MentionEveryone = 1 << 36,
+    /// Mention roles
+    MentionRoles = 1 << 37,
+
+    // % Bits 38 to 52: free area
     // % Bits 53 to 64: do not use
 
     // * Grant all permissions
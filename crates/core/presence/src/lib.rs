@@ -3,13 +3,17 @@ extern crate log;
 
 use once_cell::sync::Lazy;
 use rand::Rng;
-use redis_kiss::{get_connection, AsyncCommands};
-use std::collections::HashSet;
+use redis_kiss::{get_connection, AsyncCommands, Conn};
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 mod operations;
 use operations::{
-    __add_to_set_string, __add_to_set_u32, __delete_key, __get_set_members_as_string,
-    __get_set_size, __remove_from_set_string, __remove_from_set_u32,
+    __add_to_set_string, __add_to_set_u32, __add_to_sorted_set, __delete_key,
+    __get_set_members_as_string, __get_set_size, __get_sorted_set_members_until,
+    __is_set_member, __remove_from_set_string, __remove_from_set_u32, __remove_from_sorted_set,
 };
 
 pub static REGION_ID: Lazy<u16> = Lazy::new(|| {
@@ -21,9 +25,54 @@ pub static REGION_ID: Lazy<u16> = Lazy::new(|| {
 
 pub static REGION_KEY: Lazy<String> = Lazy::new(|| format!("region{}", &*REGION_ID));
 pub static ONLINE_SET: &str = "online";
+pub static IDLE_SET: &str = "idle";
+pub static SESSIONS_KEY: &str = "sessions";
+
+/// How long a session is kept alive without a heartbeat
+pub const SESSION_TTL: Duration = Duration::from_secs(90);
 
 pub static FLAG_BITS: u32 = 0b1;
 
+/// Current time as seconds since the UNIX epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+}
+
+/// Entry for a session in the sessions sorted set
+fn session_entry(region_key: &str, user_id: &str, session_id: u32) -> String {
+    format!("{region_key}:{user_id}:{session_id}")
+}
+
+/// Key for the set of a user's idle sessions
+fn idle_key(user_id: &str) -> String {
+    format!("{user_id}:idle")
+}
+
+/// Work out whether a user is idle across all of their sessions
+///
+/// A user is only idle if every one of their sessions is idle,
+/// returns the new state if it has changed.
+async fn update_idle(conn: &mut Conn, user_id: &str) -> Option<bool> {
+    let sessions = __get_set_size(conn, user_id).await;
+    let idle_sessions = __get_set_size(conn, &idle_key(user_id)).await;
+    let is_idle = sessions > 0 && idle_sessions >= sessions;
+
+    if is_idle == __is_set_member(conn, IDLE_SET, user_id).await {
+        return None;
+    }
+
+    if is_idle {
+        __add_to_set_string(conn, IDLE_SET, user_id).await;
+    } else {
+        __remove_from_set_string(conn, IDLE_SET, user_id).await;
+    }
+
+    Some(is_idle)
+}
+
 /// Create a new presence session, returns the ID of this session
 pub async fn create_session(user_id: &str, flags: u8) -> (bool, u32) {
     info!("Creating a presence session for {user_id} with flags {flags}");
@@ -42,6 +91,13 @@ pub async fn create_session(user_id: &str, flags: u8) -> (bool, u32) {
         __add_to_set_u32(&mut conn, user_id, session_id).await;
         __add_to_set_string(&mut conn, ONLINE_SET, user_id).await;
         __add_to_set_string(&mut conn, &REGION_KEY, &format!("{user_id}:{session_id}")).await;
+        __add_to_sorted_set(
+            &mut conn,
+            SESSIONS_KEY,
+            &session_entry(&REGION_KEY, user_id, session_id),
+            now() + SESSION_TTL.as_secs(),
+        )
+        .await;
         info!("Created session for {user_id}, assigned them a session ID of {session_id}.");
 
         (was_empty, session_id)
@@ -51,22 +107,63 @@ pub async fn create_session(user_id: &str, flags: u8) -> (bool, u32) {
     }
 }
 
+/// Keep an existing presence session alive
+///
+/// Sessions which aren't refreshed within [`SESSION_TTL`] are removed by
+/// [`sweep_expired_sessions`]. If the session has already been swept, it is
+/// restored and this returns whether the user has just come back online.
+pub async fn heartbeat_session(user_id: &str, session_id: u32) -> bool {
+    if let Ok(mut conn) = get_connection().await {
+        let was_empty = __get_set_size(&mut conn, user_id).await == 0;
+
+        __add_to_set_u32(&mut conn, user_id, session_id).await;
+        __add_to_set_string(&mut conn, ONLINE_SET, user_id).await;
+        __add_to_set_string(&mut conn, &REGION_KEY, &format!("{user_id}:{session_id}")).await;
+        __add_to_sorted_set(
+            &mut conn,
+            SESSIONS_KEY,
+            &session_entry(&REGION_KEY, user_id, session_id),
+            now() + SESSION_TTL.as_secs(),
+        )
+        .await;
+        update_idle(&mut conn, user_id).await;
+
+        was_empty
+    } else {
+        // Fail through
+        false
+    }
+}
+
 /// Delete existing presence session
 pub async fn delete_session(user_id: &str, session_id: u32) -> bool {
-    delete_session_internal(user_id, session_id, false).await
+    delete_session_internal(&REGION_KEY, user_id, session_id, false).await
 }
 
 /// Delete existing presence session (but also choose whether to skip region)
-async fn delete_session_internal(user_id: &str, session_id: u32, skip_region: bool) -> bool {
+async fn delete_session_internal(
+    region_key: &str,
+    user_id: &str,
+    session_id: u32,
+    skip_region: bool,
+) -> bool {
     info!("Deleting presence session for {user_id} with id {session_id}");
 
     if let Ok(mut conn) = get_connection().await {
         // Remove the session
         __remove_from_set_u32(&mut conn, user_id, session_id).await;
+        __remove_from_set_u32(&mut conn, &idle_key(user_id), session_id).await;
+        update_idle(&mut conn, user_id).await;
+        __remove_from_sorted_set(
+            &mut conn,
+            SESSIONS_KEY,
+            &session_entry(region_key, user_id, session_id),
+        )
+        .await;
 
         // Remove from the region
         if !skip_region {
-            __remove_from_set_string(&mut conn, &REGION_KEY, &format!("{user_id}:{session_id}"))
+            __remove_from_set_string(&mut conn, region_key, &format!("{user_id}:{session_id}"))
                 .await;
         }
 
@@ -84,6 +181,47 @@ async fn delete_session_internal(user_id: &str, session_id: u32, skip_region: bo
     }
 }
 
+/// Mark whether a presence session is idle
+///
+/// Returns whether the user is now idle if this changed it.
+pub async fn set_session_idle(user_id: &str, session_id: u32, idle: bool) -> Option<bool> {
+    let mut conn = get_connection().await.ok()?;
+    if idle {
+        __add_to_set_u32(&mut conn, &idle_key(user_id), session_id).await;
+    } else {
+        __remove_from_set_u32(&mut conn, &idle_key(user_id), session_id).await;
+    }
+
+    update_idle(&mut conn, user_id).await
+}
+
+/// Check whether a given user ID is idle
+pub async fn is_idle(user_id: &str) -> bool {
+    if let Ok(mut conn) = get_connection().await {
+        __is_set_member(&mut conn, IDLE_SET, user_id).await
+    } else {
+        false
+    }
+}
+
+/// Check whether a set of users is idle, returns a set of the idle user IDs
+pub async fn filter_idle(user_ids: &'_ [String]) -> HashSet<String> {
+    if user_ids.is_empty() {
+        HashSet::new()
+    } else if let Ok(mut conn) = get_connection().await {
+        let members = __get_set_members_as_string(&mut conn, IDLE_SET).await;
+        let members: HashSet<&String> = members.iter().collect();
+
+        user_ids
+            .iter()
+            .filter(|id| members.contains(id))
+            .cloned()
+            .collect()
+    } else {
+        HashSet::new()
+    }
+}
+
 /// Check whether a given user ID is online
 pub async fn is_online(user_id: &str) -> bool {
     if let Ok(mut conn) = get_connection().await {
@@ -160,10 +298,50 @@ pub async fn filter_online(user_ids: &'_ [String]) -> HashSet<String> {
     }
 }
 
+/// Remove any sessions which have not sent a heartbeat in time
+///
+/// This cleans up after nodes which crashed or were never restarted,
+/// returns the IDs of users who went offline as a result.
+pub async fn sweep_expired_sessions() -> Vec<String> {
+    sweep_sessions_until(now()).await
+}
+
+/// Remove any sessions which expire at or before the given time
+async fn sweep_sessions_until(time: u64) -> Vec<String> {
+    let mut offline = vec![];
+    let Ok(mut conn) = get_connection().await else {
+        return offline;
+    };
+
+    for entry in __get_sorted_set_members_until(&mut conn, SESSIONS_KEY, time).await {
+        // Only whoever removes the entry gets to clean up the session,
+        // so multiple nodes can sweep at the same time.
+        if !__remove_from_sorted_set(&mut conn, SESSIONS_KEY, &entry).await {
+            continue;
+        }
+
+        let parts = entry.split(':').collect::<Vec<&str>>();
+        if let (Some(region_key), Some(user_id), Some(session_id)) =
+            (parts.first(), parts.get(1), parts.get(2))
+        {
+            if let Ok(session_id) = session_id.parse() {
+                if delete_session_internal(region_key, user_id, session_id, false).await {
+                    offline.push(user_id.to_string());
+                }
+            }
+        }
+    }
+
+    offline
+}
+
 /// Reset any stale presence data
-pub async fn clear_region(region_id: Option<&str>) {
+///
+/// Returns the IDs of users who went offline as a result.
+pub async fn clear_region(region_id: Option<&str>) -> Vec<String> {
     let region_id = region_id.unwrap_or(&*REGION_KEY);
     let mut conn = get_connection().await.expect("Redis connection");
+    let mut offline = vec![];
 
     let sessions = __get_set_members_as_string(&mut conn, region_id).await;
     if !sessions.is_empty() {
@@ -172,13 +350,14 @@ pub async fn clear_region(region_id: Option<&str>) {
             sessions.len()
         );
 
-        // Iterate and delete each session, this will
-        // also send out any relevant events.
+        // Iterate and delete each session.
         for session in sessions {
             let parts = session.split(':').collect::<Vec<&str>>();
             if let (Some(user_id), Some(session_id)) = (parts.first(), parts.get(1)) {
                 if let Ok(session_id) = session_id.parse() {
-                    delete_session_internal(user_id, session_id, true).await;
+                    if delete_session_internal(region_id, user_id, session_id, true).await {
+                        offline.push(user_id.to_string());
+                    }
                 }
             }
         }
@@ -188,11 +367,17 @@ pub async fn clear_region(region_id: Option<&str>) {
 
         info!("Clean up complete.");
     }
+
+    offline
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{clear_region, create_session, delete_session, filter_online, is_online};
+    use crate::{
+        clear_region, create_session, delete_session, filter_idle, filter_online,
+        heartbeat_session, is_idle, is_online, now, set_session_idle, sweep_sessions_until,
+        SESSION_TTL,
+    };
     use rand::Rng;
 
     #[async_std::test]
@@ -241,6 +426,55 @@ mod tests {
         assert!(!is_online(&user_id).await);
 
         let user_ids = filter_online(&[user_id.to_string(), other_id.to_string()]).await;
-        assert!(user_ids.is_empty())
+        assert!(user_ids.is_empty());
+
+        // Create a session and keep it alive
+        let (first_session, session_id) = create_session(&user_id, 0).await;
+        assert!(first_session);
+        assert!(!heartbeat_session(&user_id, session_id).await);
+
+        // Sessions are kept until they expire
+        assert!(!sweep_sessions_until(now()).await.contains(&user_id));
+        assert!(is_online(&user_id).await);
+
+        // Then swept once they stop sending heartbeats
+        let expired = now() + SESSION_TTL.as_secs() + 1;
+        assert!(sweep_sessions_until(expired).await.contains(&user_id));
+        assert!(!is_online(&user_id).await);
+
+        // A late heartbeat brings the session back
+        assert!(heartbeat_session(&user_id, session_id).await);
+        assert!(is_online(&user_id).await);
+
+        assert!(delete_session(&user_id, session_id).await);
+    }
+
+    #[async_std::test]
+    async fn it_tracks_idle() {
+        let user_id = rand::thread_rng().gen::<u32>().to_string();
+
+        // Create two sessions for the same user
+        let (_, first) = create_session(&user_id, 0).await;
+        let (_, second) = create_session(&user_id, 0).await;
+        assert!(!is_idle(&user_id).await);
+
+        // The most active session wins
+        assert_eq!(set_session_idle(&user_id, first, true).await, None);
+        assert!(!is_idle(&user_id).await);
+
+        assert_eq!(set_session_idle(&user_id, second, true).await, Some(true));
+        assert!(is_idle(&user_id).await);
+        assert!(filter_idle(&[user_id.to_string()]).await.contains(&user_id));
+
+        assert_eq!(set_session_idle(&user_id, first, false).await, Some(false));
+        assert!(!is_idle(&user_id).await);
+
+        // Removing the active session leaves only idle ones
+        delete_session(&user_id, first).await;
+        assert!(is_idle(&user_id).await);
+
+        // Users who go offline are no longer idle
+        delete_session(&user_id, second).await;
+        assert!(!is_idle(&user_id).await);
     }
 }
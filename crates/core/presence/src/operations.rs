@@ -40,3 +40,30 @@ pub async fn __delete_key(conn: &mut Conn, id: &str) {
         .await
         .expect("could not delete key by id");
 }
+
+/// Add to sorted set (string) with score
+pub async fn __add_to_sorted_set(conn: &mut Conn, key: &str, value: &str, score: u64) {
+    let _: Option<()> = conn.zadd(key, value, score).await.ok();
+}
+
+/// Remove from sorted set (string), returns whether the value was present
+pub async fn __remove_from_sorted_set(conn: &mut Conn, key: &str, value: &str) -> bool {
+    conn.zrem::<_, _, u32>(key, value)
+        .await
+        .map(|removed| removed > 0)
+        .unwrap_or(false)
+}
+
+/// Get sorted set members with a score up to and including max
+pub async fn __get_sorted_set_members_until(conn: &mut Conn, key: &str, max: u64) -> Vec<String> {
+    conn.zrangebyscore::<_, _, _, Vec<String>>(key, "-inf", max)
+        .await
+        .unwrap_or_default()
+}
+
+/// Check whether a value is in a set (string)
+pub async fn __is_set_member(conn: &mut Conn, key: &str, value: &str) -> bool {
+    conn.sismember::<_, _, bool>(key, value)
+        .await
+        .unwrap_or(false)
+}
@@ -54,6 +54,7 @@ pub enum ErrorType {
     UsernameTaken,
     InvalidUsername,
     DiscriminatorChangeRatelimited,
+    ActivityUpdateRatelimited,
     UnknownUser,
     AlreadyFriends,
     AlreadySentRequest,
@@ -110,6 +111,15 @@ pub enum ErrorType {
     ReachedMaximumBots,
     IsBot,
     BotIsPrivate,
+    DisallowedIntents {
+        intents: u32,
+    },
+
+    // ? File related errors
+    FileTooLarge {
+        max: usize,
+    },
+    FileTypeNotAllowed,
 
     // ? User safety related errors
     CannotReportYourself,
@@ -137,6 +147,9 @@ pub enum ErrorType {
     InvalidProperty,
     InvalidSession,
     DuplicateNonce,
+    Ratelimited {
+        retry_after: u128,
+    },
     NotFound,
     NoEffect,
     FailedValidation {
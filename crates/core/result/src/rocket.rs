@@ -20,6 +20,7 @@ impl<'r> Responder<'r, 'static> for Error {
             ErrorType::InvalidUsername => Status::BadRequest,
             ErrorType::UsernameTaken => Status::Conflict,
             ErrorType::DiscriminatorChangeRatelimited => Status::TooManyRequests,
+            ErrorType::ActivityUpdateRatelimited => Status::TooManyRequests,
             ErrorType::AlreadyFriends => Status::Conflict,
             ErrorType::AlreadySentRequest => Status::Conflict,
             ErrorType::Blocked => Status::Conflict,
@@ -55,6 +56,10 @@ impl<'r> Responder<'r, 'static> for Error {
             ErrorType::ReachedMaximumBots => Status::BadRequest,
             ErrorType::IsBot => Status::BadRequest,
             ErrorType::BotIsPrivate => Status::Forbidden,
+            ErrorType::DisallowedIntents { .. } => Status::Forbidden,
+
+            ErrorType::FileTooLarge { .. } => Status::PayloadTooLarge,
+            ErrorType::FileTypeNotAllowed => Status::BadRequest,
 
             ErrorType::CannotReportYourself => Status::BadRequest,
 
@@ -72,6 +77,7 @@ impl<'r> Responder<'r, 'static> for Error {
             ErrorType::InvalidProperty => Status::BadRequest,
             ErrorType::InvalidSession => Status::Unauthorized,
             ErrorType::DuplicateNonce => Status::Conflict,
+            ErrorType::Ratelimited { .. } => Status::TooManyRequests,
             ErrorType::VosoUnavailable => Status::BadRequest,
             ErrorType::NotFound => Status::NotFound,
             ErrorType::NoEffect => Status::Ok,
@@ -0,0 +1,97 @@
+//! Inspect, apply and revert database migrations
+//!
+//! Usage:
+//!   migrations status
+//!   migrations apply [<id>] [--dry-run]
+//!   migrations revert <id> [--dry-run]
+//!
+//! Applying without an id brings the database fully up to date.
+
+use std::process::exit;
+
+use revolt_database::{AbstractMigrations, DatabaseInfo, MigrationDirection, MigrationReport};
+
+const USAGE: &str = "Usage: migrations status | apply [<id>] [--dry-run] | revert <id> [--dry-run]";
+
+/// Print the outcome of running a migration
+fn print_report(report: &MigrationReport) {
+    let action = match (report.direction, report.dry_run) {
+        (MigrationDirection::Up, false) => "Applied",
+        (MigrationDirection::Up, true) => "Would apply",
+        (MigrationDirection::Down, false) => "Reverted",
+        (MigrationDirection::Down, true) => "Would revert",
+    };
+
+    println!(
+        "{action} {} ({} documents affected, took {}ms)",
+        report.id, report.affected, report.duration_ms
+    );
+}
+
+#[async_std::main]
+async fn main() {
+    revolt_config::configure!(api);
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let args: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| *arg != "--dry-run")
+        .collect();
+
+    let db = DatabaseInfo::Auto
+        .connect()
+        .await
+        .expect("Failed to connect to the database.");
+
+    let result = match args.as_slice() {
+        ["status"] => db.migration_status().await.map(|status| {
+            for status in status {
+                match status.record {
+                    Some(record) => println!(
+                        "[applied] {} - {} (at {}, took {}ms, {} documents affected)",
+                        status.migration.id,
+                        status.migration.description,
+                        record.applied_at.format(),
+                        record.duration_ms,
+                        record.affected
+                    ),
+                    None => println!(
+                        "[pending] {} - {}",
+                        status.migration.id, status.migration.description
+                    ),
+                }
+            }
+        }),
+        ["apply"] if dry_run => db
+            .apply_pending_migrations(true)
+            .await
+            .map(|reports| reports.iter().for_each(print_report)),
+        ["apply"] => {
+            db.migrate_database()
+                .await
+                .expect("Failed to migrate the database.");
+
+            println!("Database is up to date.");
+            Ok(())
+        }
+        ["apply", id] => db
+            .apply_migration(id, dry_run)
+            .await
+            .map(|report| print_report(&report)),
+        ["revert", id] => db
+            .revert_migration(id, dry_run)
+            .await
+            .map(|report| print_report(&report)),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(2);
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("Failed: {err:?}");
+        exit(1);
+    }
+}
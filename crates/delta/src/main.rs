@@ -10,6 +10,8 @@ pub mod util;
 
 use revolt_config::config;
 use revolt_database::events::client::EventV1;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+use revolt_database::AuthifierStore;
 use revolt_database::{Database, MongoDb};
 use rocket::{Build, Rocket};
 use rocket_cors::{AllowedOrigins, CorsOptions};
@@ -36,24 +38,81 @@ pub async fn web() -> Rocket<Build> {
     let db = revolt_database::DatabaseInfo::Auto.connect().await.unwrap();
     db.migrate_database().await.unwrap();
 
+    // Cache frequently fetched objects, kept up to date by events
+    revolt_database::util::cache::enable(
+        config.api.cache.capacity,
+        std::time::Duration::from_secs(config.api.cache.ttl),
+    );
+
     // Setup Authifier event channel
     let (sender, receiver) = unbounded();
 
+    // Authifier can only persist accounts and sessions to MongoDB, on SQL
+    // databases they are kept in memory and written back by a store.
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
+    let authifier_store = match &db {
+        Database::Reference(_) | Database::MongoDb(_) => None,
+        _ => Some(
+            AuthifierStore::load(db.clone())
+                .await
+                .expect("Failed to load accounts and sessions!"),
+        ),
+    };
+
     // Setup Authifier
     let authifier = Authifier {
         database: match db.clone() {
             Database::Reference(_) => Default::default(),
-            Database::MongoDb(MongoDb(client, _)) => authifier::Database::MongoDb(
+            Database::MongoDb(MongoDb(client, ..)) => authifier::Database::MongoDb(
                 authifier::database::MongoDb(client.database("revolt")),
             ),
+            #[cfg(any(feature = "postgres", feature = "sqlite"))]
+            _ => authifier_store
+                .as_ref()
+                .map(AuthifierStore::database)
+                .expect("`AuthifierStore`"),
         },
         config: authifier_config().await,
         event_channel: Some(sender),
     };
 
     // Launch a listener for Authifier events
+    let events_db = db.clone();
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
+    let events_store = authifier_store.clone();
     async_std::task::spawn(async move {
         while let Ok(event) = receiver.recv().await {
+            // Store new accounts straight away rather than waiting for the next write
+            #[cfg(any(feature = "postgres", feature = "sqlite"))]
+            if let (AuthifierEvent::CreateAccount { account }, Some(store)) =
+                (&event, &events_store)
+            {
+                store.track_account(&account.id).await;
+                if let Err(err) = store.persist().await {
+                    log::error!("Failed to store accounts and sessions! {:?}", err);
+                }
+            }
+
+            // Stop pushing to sessions which no longer exist
+            let cleanup = match &event {
+                AuthifierEvent::DeleteSession { session_id, .. } => {
+                    events_db.delete_unified_push_subscription(session_id).await
+                }
+                AuthifierEvent::DeleteAllSessions {
+                    user_id,
+                    exclude_session_id,
+                } => {
+                    events_db
+                        .delete_unified_push_subscriptions(user_id, exclude_session_id.as_deref())
+                        .await
+                }
+                _ => Ok(()),
+            };
+
+            if let Err(err) = cleanup {
+                log::error!("Failed to remove UnifiedPush subscriptions! {:?}", err);
+            }
+
             match &event {
                 AuthifierEvent::CreateSession { .. } | AuthifierEvent::CreateAccount { .. } => {
                     EventV1::Auth(event).global().await
@@ -96,9 +155,30 @@ pub async fn web() -> Rocket<Build> {
     )
     .into();
 
+    // Uploads are checked against each user's limits once received
+    let max_upload_size = util::storage::max_upload_size(&config.features.limits) as u64;
+
     // Configure Rocket
     let rocket = rocket::build();
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
+    let rocket = match authifier_store {
+        Some(store) => {
+            async_std::task::spawn(util::authifier_store::worker(store.clone()));
+            rocket.attach(util::authifier_store::AuthifierStoreFairing(store))
+        }
+        None => rocket,
+    };
     let prometheus = PrometheusMetrics::new();
+    prometheus
+        .registry()
+        .register(Box::new(
+            revolt_database::util::cache::CACHE_REQUESTS.clone(),
+        ))
+        .expect("Failed to register cache metrics");
+    prometheus
+        .registry()
+        .register(Box::new(revolt_database::tasks::jobs::JOBS.clone()))
+        .expect("Failed to register job metrics");
 
     routes::mount(config, rocket)
         .attach(prometheus.clone())
@@ -112,7 +192,10 @@ pub async fn web() -> Rocket<Build> {
         .attach(util::ratelimiter::RatelimitFairing)
         .attach(cors)
         .configure(rocket::Config {
-            limits: rocket::data::Limits::default().limit("string", 5.megabytes()),
+            limits: rocket::data::Limits::default()
+                .limit("string", 5.megabytes())
+                .limit("file", max_upload_size.bytes())
+                .limit("data-form", (max_upload_size + 1024 * 1024).bytes()),
             address: Ipv4Addr::new(0, 0, 0, 0).into(),
             ..Default::default()
         })
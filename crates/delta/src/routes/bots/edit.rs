@@ -1,4 +1,6 @@
-use revolt_database::{util::reference::Reference, Database, PartialBot, User};
+use revolt_database::{
+    events::intents::PRIVILEGED_INTENTS, util::reference::Reference, Database, PartialBot, User,
+};
 use revolt_models::v0::{self, DataEditBot};
 use revolt_result::{create_error, Result};
 use rocket::State;
@@ -24,6 +26,12 @@ pub async fn edit_bot(
         })
     })?;
 
+    if let Some(privileged_intents) = data.privileged_intents {
+        if privileged_intents & !PRIVILEGED_INTENTS != 0 {
+            return Err(create_error!(InvalidProperty));
+        }
+    }
+
     let mut bot = target.as_bot(db).await?;
     if bot.owner != user.id {
         return Err(create_error!(NotFound));
@@ -37,6 +45,7 @@ pub async fn edit_bot(
     if data.public.is_none()
         && data.analytics.is_none()
         && data.interactions_url.is_none()
+        && data.privileged_intents.is_none()
         && data.remove.is_none()
     {
         return Ok(Json(v0::BotWithUserResponse {
@@ -49,6 +58,7 @@ pub async fn edit_bot(
         public,
         analytics,
         interactions_url,
+        privileged_intents,
         remove,
         ..
     } = data;
@@ -57,6 +67,7 @@ pub async fn edit_bot(
         public,
         analytics,
         interactions_url,
+        privileged_intents: privileged_intents.map(|v| v as i32),
         ..Default::default()
     };
 
@@ -80,7 +91,7 @@ pub async fn edit_bot(
 #[cfg(test)]
 mod test {
     use crate::{rocket, util::test::TestHarness};
-    use revolt_database::Bot;
+    use revolt_database::{events::intents::PRIVILEGED_INTENTS, Bot};
     use revolt_models::v0::{self, FieldsBot};
     use rocket::http::{ContentType, Header, Status};
 
@@ -115,4 +126,51 @@ mod test {
         assert!(!bot.public);
         assert!(updated_bot.public);
     }
+
+    #[rocket::async_test]
+    async fn edit_bot_privileged_intents() {
+        let harness = TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+
+        let (bot, _) = Bot::create(&harness.db, TestHarness::rand_string(), &user, None)
+            .await
+            .expect("`Bot`");
+
+        let response = harness
+            .client
+            .patch(format!("/bots/{}", bot.id))
+            .header(ContentType::JSON)
+            .body(
+                json!(v0::DataEditBot {
+                    privileged_intents: Some(1 << 31),
+                    ..Default::default()
+                })
+                .to_string(),
+            )
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = harness
+            .client
+            .patch(format!("/bots/{}", bot.id))
+            .header(ContentType::JSON)
+            .body(
+                json!(v0::DataEditBot {
+                    privileged_intents: Some(PRIVILEGED_INTENTS),
+                    ..Default::default()
+                })
+                .to_string(),
+            )
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+
+        let updated_bot: v0::Bot = response.into_json().await.expect("`Bot`");
+        assert_eq!(updated_bot.privileged_intents, PRIVILEGED_INTENTS);
+    }
 }
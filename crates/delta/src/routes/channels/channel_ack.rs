@@ -1,9 +1,5 @@
-use revolt_database::{
-    util::{permissions::DatabasePermissionQuery, reference::Reference},
-    Database, User,
-};
-use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
-use revolt_result::{create_error, Result};
+use revolt_database::{actions::messages, util::reference::Reference, Database, User};
+use revolt_result::Result;
 use rocket::State;
 use rocket_empty::EmptyResponse;
 
@@ -18,18 +14,7 @@ pub async fn ack(
     target: Reference,
     message: Reference,
 ) -> Result<EmptyResponse> {
-    if user.bot.is_some() {
-        return Err(create_error!(IsBot));
-    }
-
-    let channel = target.as_channel(db).await?;
-    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
-    calculate_channel_permissions(&mut query)
-        .await
-        .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
-
-    channel
-        .ack(&user.id, &message.id)
+    messages::ack_message(db, &user, &target, &message)
         .await
         .map(|_| EmptyResponse)
 }
@@ -83,7 +83,6 @@ pub async fn edit(
             id,
             name,
             description,
-            icon,
             nsfw,
             ..
         }
@@ -91,7 +90,6 @@ pub async fn edit(
             id,
             name,
             description,
-            icon,
             nsfw,
             ..
         }
@@ -99,33 +97,20 @@ pub async fn edit(
             id,
             name,
             description,
-            icon,
             nsfw,
             ..
         } => {
             if let Some(fields) = &data.remove {
-                if fields.contains(&v0::FieldsChannel::Icon) {
-                    if let Some(icon) = &icon {
-                        db.mark_attachment_as_deleted(&icon.id).await?;
-                    }
-                }
-
+                // Icons are removed on update, so that the old file gets detached.
                 for field in fields {
-                    match field {
-                        v0::FieldsChannel::Description => {
-                            description.take();
-                        }
-                        v0::FieldsChannel::Icon => {
-                            icon.take();
-                        }
-                        _ => {}
+                    if let v0::FieldsChannel::Description = field {
+                        description.take();
                     }
                 }
             }
 
             if let Some(icon_id) = data.icon {
                 partial.icon = Some(File::use_icon(db, &icon_id, id).await?);
-                *icon = partial.icon.clone();
             }
 
             if let Some(new_name) = data.name {
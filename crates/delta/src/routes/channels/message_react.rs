@@ -1,8 +1,4 @@
-use revolt_database::{
-    util::{permissions::DatabasePermissionQuery, reference::Reference},
-    Database, User,
-};
-use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_database::{actions::messages, util::reference::Reference, Database, User};
 use revolt_result::Result;
 use rocket::State;
 use rocket_empty::EmptyResponse;
@@ -19,18 +15,7 @@ pub async fn react_message(
     msg: Reference,
     emoji: Reference,
 ) -> Result<EmptyResponse> {
-    let channel = target.as_channel(db).await?;
-    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
-    calculate_channel_permissions(&mut query)
-        .await
-        .throw_if_lacking_channel_permission(ChannelPermission::React)?;
-
-    // Fetch relevant message
-    let message = msg.as_message_in_channel(db, &channel.id()).await?;
-
-    // Add the reaction
-    message
-        .add_reaction(db, &user, &emoji.id)
+    messages::react_message(db, &user, &target, &msg, &emoji)
         .await
         .map(|_| EmptyResponse)
 }
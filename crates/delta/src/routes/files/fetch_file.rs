@@ -0,0 +1,265 @@
+use std::ops::Range;
+
+use revolt_database::{Database, File, Metadata};
+use revolt_result::{create_error, Result};
+use rocket::{
+    http::{ContentType, Status},
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+    Request, Response, State,
+};
+
+use crate::util::storage::{FileStream, Storage};
+
+pub static CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Value of the Range header, if one was sent
+pub struct RangeHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RangeHeader(
+            request.headers().get_one("Range").map(str::to_string),
+        ))
+    }
+}
+
+/// Requested part of a file
+#[derive(Debug, PartialEq, Eq)]
+enum Requested {
+    /// Entire file
+    Whole,
+    /// Range of bytes within the file
+    Partial(Range<u64>),
+    /// Range which lies outside of the file
+    Unsatisfiable,
+}
+
+/// Work out which part of a file of the given size a Range header asks for
+///
+/// Only a single range is supported, anything else is served as the whole file.
+fn parse_range(header: Option<&str>, size: u64) -> Requested {
+    let Some((start, end)) = header
+        .and_then(|header| header.strip_prefix("bytes="))
+        .filter(|ranges| !ranges.contains(','))
+        .and_then(|range| range.trim().split_once('-'))
+    else {
+        return Requested::Whole;
+    };
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => start..(end + 1).min(size),
+        (Ok(start), Err(_)) if end.is_empty() => start..size,
+        (Err(_), Ok(suffix)) if start.is_empty() => size.saturating_sub(suffix)..size,
+        _ => return Requested::Whole,
+    };
+
+    if range.start < size && !range.is_empty() {
+        Requested::Partial(range)
+    } else {
+        Requested::Unsatisfiable
+    }
+}
+
+/// File contents being served
+pub enum FileResponse {
+    Contents {
+        file: File,
+        contents: FileStream,
+        range: Option<Range<u64>>,
+    },
+    Unsatisfiable {
+        size: u64,
+    },
+}
+
+impl<'r> Responder<'r, 'static> for FileResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let (file, contents, range) = match self {
+            FileResponse::Contents {
+                file,
+                contents,
+                range,
+            } => (file, contents, range),
+            FileResponse::Unsatisfiable { size } => {
+                return Response::build()
+                    .status(Status::RangeNotSatisfiable)
+                    .raw_header("Content-Range", format!("bytes */{size}"))
+                    .ok()
+            }
+        };
+
+        // Only media is shown inline, anything else could be interpreted by the browser
+        let (content_type, disposition) = match file.metadata {
+            Metadata::Image { .. } | Metadata::Video { .. } | Metadata::Audio => (
+                ContentType::parse_flexible(&file.content_type).unwrap_or(ContentType::Binary),
+                "inline",
+            ),
+            Metadata::Text => (ContentType::Plain, "attachment"),
+            Metadata::File => (ContentType::Binary, "attachment"),
+        };
+
+        let filename = file.filename.replace(['"', '\\', '\r', '\n'], "");
+        let mut response = Response::build();
+        response
+            .header(content_type)
+            .raw_header("Accept-Ranges", "bytes")
+            .raw_header("Cache-Control", CACHE_CONTROL)
+            .raw_header("X-Content-Type-Options", "nosniff")
+            .raw_header(
+                "Content-Disposition",
+                format!("{disposition}; filename=\"{filename}\""),
+            );
+
+        let length = match range {
+            Some(range) => {
+                response.status(Status::PartialContent).raw_header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, file.size),
+                );
+
+                range.end - range.start
+            }
+            None => file.size as u64,
+        };
+
+        response
+            .raw_header("Content-Length", length.to_string())
+            .streamed_body(contents)
+            .ok()
+    }
+}
+
+/// # Fetch File
+///
+/// Serve the contents of an uploaded file, supporting single range requests.
+/// Anything after the id (such as a filename) is ignored.
+#[get("/<tag>/<id>/<_..>")]
+pub async fn fetch_file(
+    db: &State<Database>,
+    storage: &State<Box<dyn Storage>>,
+    tag: &str,
+    id: &str,
+    range: RangeHeader,
+) -> Result<FileResponse> {
+    let file = db.fetch_attachment(tag, id).await?;
+    if file.deleted == Some(true) {
+        return Err(create_error!(NotFound));
+    }
+
+    let range = match parse_range(range.0.as_deref(), file.size as u64) {
+        Requested::Whole => None,
+        Requested::Partial(range) => Some(range),
+        Requested::Unsatisfiable => {
+            return Ok(FileResponse::Unsatisfiable {
+                size: file.size as u64,
+            })
+        }
+    };
+
+    let contents = storage.get(tag, id, range.clone()).await?;
+    Ok(FileResponse::Contents {
+        file,
+        contents,
+        range,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_range, Requested};
+    use crate::{
+        rocket,
+        util::test::{TestHarness, PNG},
+    };
+    use rocket::http::{Header, Status};
+
+    #[rocket::async_test]
+    async fn fetch_file() {
+        let harness = TestHarness::new().await;
+        let (_, session, _) = harness.new_user().await;
+
+        let (content_type, body) = TestHarness::multipart("pixel.png", &PNG);
+        let response = harness
+            .client
+            .post("/files/attachments")
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .header(content_type)
+            .body(body)
+            .dispatch()
+            .await;
+
+        let uploaded: serde_json::Value = response.into_json().await.expect("`UploadedFile`");
+        let id = uploaded["id"].as_str().unwrap();
+
+        let response = harness
+            .client
+            .get(format!("/files/attachments/{id}/pixel.png"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Content-Type"),
+            Some("image/png")
+        );
+        assert_eq!(response.into_bytes().await.expect("contents"), PNG.to_vec());
+
+        let response = harness
+            .client
+            .get(format!("/files/attachments/{id}"))
+            .header(Header::new("Range", "bytes=1-3"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(
+            response.headers().get_one("Content-Range"),
+            Some(format!("bytes 1-3/{}", PNG.len()).as_str())
+        );
+        assert_eq!(
+            response.into_bytes().await.expect("contents"),
+            PNG[1..4].to_vec()
+        );
+
+        let response = harness
+            .client
+            .get(format!("/files/attachments/{id}"))
+            .header(Header::new("Range", "bytes=100-"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse_range(None, 100), Requested::Whole);
+        assert_eq!(
+            parse_range(Some("bytes=0-9"), 100),
+            Requested::Partial(0..10)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=90-"), 100),
+            Requested::Partial(90..100)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-10"), 100),
+            Requested::Partial(90..100)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=50-500"), 100),
+            Requested::Partial(50..100)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=100-"), 100),
+            Requested::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), Requested::Whole);
+        assert_eq!(parse_range(Some("bytes=9-0"), 100), Requested::Whole);
+        assert_eq!(parse_range(Some("items=0-9"), 100), Requested::Whole);
+    }
+}
@@ -0,0 +1,8 @@
+use rocket::Route;
+
+mod fetch_file;
+mod upload_file;
+
+pub fn routes() -> Vec<Route> {
+    routes![upload_file::upload_file, fetch_file::fetch_file]
+}
@@ -0,0 +1,150 @@
+use async_std::io::ReadExt;
+use revolt_database::{Database, File, Metadata, User};
+use revolt_result::{create_error, Result};
+use rocket::{data::Capped, form::Form, fs::TempFile, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::util::{
+    metadata,
+    storage::{size_limit, Storage},
+};
+
+/// Multipart form containing the file to upload
+#[derive(FromForm)]
+pub struct Upload<'r> {
+    file: Capped<TempFile<'r>>,
+}
+
+/// Uploaded file
+#[derive(Serialize)]
+pub struct UploadedFile {
+    /// Id of the new file
+    id: String,
+}
+
+/// # Upload File
+///
+/// Upload a file to a tag, it must then be used within the orphan grace period.
+#[post("/<tag>", data = "<upload>")]
+pub async fn upload_file(
+    db: &State<Database>,
+    storage: &State<Box<dyn Storage>>,
+    user: User,
+    tag: &str,
+    upload: Form<Upload<'_>>,
+) -> Result<Json<UploadedFile>> {
+    let max = size_limit(&user.limits().await, tag).ok_or_else(|| create_error!(NotFound))?;
+    if !upload.file.is_complete() || upload.file.len() as usize > max {
+        return Err(create_error!(FileTooLarge { max }));
+    }
+
+    // Files sent as multipart form fields are always streamed to disk
+    let path = upload
+        .file
+        .path()
+        .ok_or_else(|| create_error!(InvalidOperation))?;
+
+    // Only the start of the file is needed to work out what it is
+    let mut head = Vec::new();
+    async_std::fs::File::open(path)
+        .await
+        .map_err(|_| create_error!(InternalError))?
+        .take(metadata::SNIFF_LENGTH)
+        .read_to_end(&mut head)
+        .await
+        .map_err(|_| create_error!(InternalError))?;
+
+    let (content_type, metadata) = metadata::sniff(path, &head).await;
+    if tag != "attachments" && !matches!(metadata, Metadata::Image { .. }) {
+        return Err(create_error!(FileTypeNotAllowed));
+    }
+
+    let file = File {
+        // Emoji share their id with their file
+        id: if tag == "emojis" {
+            ulid::Ulid::new().to_string()
+        } else {
+            nanoid::nanoid!(42)
+        },
+        tag: tag.to_string(),
+        filename: upload
+            .file
+            .raw_name()
+            .map(|name| name.dangerous_unsafe_unsanitized_raw().as_str().to_string())
+            .unwrap_or_else(|| "file".to_string()),
+        metadata,
+        content_type,
+        size: upload.file.len() as isize,
+        deleted: None,
+        reported: None,
+        parent: None,
+        orphaned_at: None,
+    };
+
+    storage.put(tag, &file.id, path, upload.file.len()).await?;
+    if let Err(error) = file.create(db).await {
+        storage.delete(tag, &file.id).await.ok();
+        return Err(error);
+    }
+
+    Ok(Json(UploadedFile { id: file.id }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        rocket,
+        util::test::{TestHarness, PNG},
+    };
+    use revolt_database::Metadata;
+    use rocket::http::{Header, Status};
+
+    #[rocket::async_test]
+    async fn upload_file() {
+        let harness = TestHarness::new().await;
+        let (_, session, _) = harness.new_user().await;
+
+        let (content_type, body) = TestHarness::multipart("pixel.png", &PNG);
+        let response = harness
+            .client
+            .post("/files/avatars")
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .header(content_type)
+            .body(body)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+
+        let uploaded: serde_json::Value = response.into_json().await.expect("`UploadedFile`");
+        let file = harness
+            .db
+            .fetch_attachment("avatars", uploaded["id"].as_str().unwrap())
+            .await
+            .expect("`File`");
+
+        assert_eq!(file.filename, "pixel.png");
+        assert_eq!(file.content_type, "image/png");
+        assert_eq!(file.size, PNG.len() as isize);
+        assert_eq!(
+            file.metadata,
+            Metadata::Image {
+                width: 1,
+                height: 1
+            }
+        );
+
+        // Only images may be used as avatars
+        let (content_type, body) = TestHarness::multipart("notes.txt", b"hello world");
+        let response = harness
+            .client
+            .post("/files/avatars")
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .header(content_type)
+            .body(body)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
@@ -7,6 +7,7 @@ use rocket::{Build, Rocket};
 mod bots;
 mod channels;
 mod customisation;
+mod files;
 mod invites;
 mod onboard;
 mod push;
@@ -61,6 +62,11 @@ pub fn mount(config: Settings, mut rocket: Rocket<Build>) -> Rocket<Build> {
         };
     }
 
+    // Serve uploads from this node if file storage is configured
+    if let Some(storage) = crate::util::storage::from_config(&config.api.files) {
+        rocket = rocket.manage(storage).mount("/files", files::routes());
+    }
+
     rocket
 }
 
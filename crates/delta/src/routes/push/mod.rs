@@ -2,8 +2,13 @@ use revolt_rocket_okapi::revolt_okapi::openapi3::OpenApi;
 use rocket::Route;
 
 mod subscribe;
+mod unified_push;
 mod unsubscribe;
 
 pub fn routes() -> (Vec<Route>, OpenApi) {
-    openapi_get_routes_spec![subscribe::subscribe, unsubscribe::unsubscribe]
+    openapi_get_routes_spec![
+        subscribe::subscribe,
+        unified_push::subscribe_unified_push,
+        unsubscribe::unsubscribe
+    ]
 }
@@ -2,6 +2,7 @@ use authifier::{
     models::{Session, WebPushSubscription},
     Authifier,
 };
+use revolt_database::Database;
 use revolt_result::{create_database_error, Result};
 use rocket::{serde::json::Json, State};
 use rocket_empty::EmptyResponse;
@@ -11,13 +12,17 @@ use rocket_empty::EmptyResponse;
 /// Create a new Web Push subscription.
 ///
 /// If an existing subscription exists on this session, it will be removed.
+/// This includes any UnifiedPush subscription.
 #[openapi(tag = "Web Push")]
 #[post("/subscribe", data = "<data>")]
 pub async fn subscribe(
     authifier: &State<Authifier>,
+    db: &State<Database>,
     mut session: Session,
     data: Json<WebPushSubscription>,
 ) -> Result<EmptyResponse> {
+    db.delete_unified_push_subscription(&session.id).await?;
+
     session.subscription = Some(data.into_inner());
     session
         .save(authifier)
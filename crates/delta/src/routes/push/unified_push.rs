@@ -0,0 +1,42 @@
+use authifier::{models::Session, Authifier};
+use revolt_database::{Database, UnifiedPushSubscription};
+use revolt_models::v0;
+use revolt_result::{create_database_error, create_error, Result};
+use rocket::{serde::json::Json, State};
+use rocket_empty::EmptyResponse;
+use validator::Validate;
+
+/// # UnifiedPush Subscribe
+///
+/// Deliver notifications for the current session to a UnifiedPush endpoint.
+///
+/// Notifications are posted to the endpoint as JSON, or encrypted as
+/// described by RFC 8291 if keys are given. Any existing Web Push or
+/// UnifiedPush subscription on this session will be removed.
+#[openapi(tag = "Web Push")]
+#[post("/unifiedpush", data = "<data>")]
+pub async fn subscribe_unified_push(
+    authifier: &State<Authifier>,
+    db: &State<Database>,
+    mut session: Session,
+    data: Json<v0::DataUnifiedPushSubscription>,
+) -> Result<EmptyResponse> {
+    let data = data.into_inner();
+    data.validate().map_err(|error| {
+        create_error!(FailedValidation {
+            error: error.to_string()
+        })
+    })?;
+
+    UnifiedPushSubscription::create(db, session.id.clone(), session.user_id.clone(), data).await?;
+
+    if session.subscription.is_some() {
+        session.subscription = None;
+        session
+            .save(authifier)
+            .await
+            .map_err(|_| create_database_error!("save", "session"))?;
+    }
+
+    Ok(EmptyResponse)
+}
@@ -1,5 +1,6 @@
 use authifier::{models::Session, Authifier};
 
+use revolt_database::Database;
 use revolt_result::{create_database_error, Result};
 use rocket_empty::EmptyResponse;
 
@@ -7,13 +8,16 @@ use rocket::State;
 
 /// # Unsubscribe
 ///
-/// Remove the Web Push subscription associated with the current session.
+/// Remove the Web Push or UnifiedPush subscription associated with the current session.
 #[openapi(tag = "Web Push")]
 #[post("/unsubscribe")]
 pub async fn unsubscribe(
     authifier: &State<Authifier>,
+    db: &State<Database>,
     mut session: Session,
 ) -> Result<EmptyResponse> {
+    db.delete_unified_push_subscription(&session.id).await?;
+
     session.subscription = None;
     session
         .save(authifier)
@@ -95,14 +95,14 @@ pub async fn report_content(
         }
     };
 
-    // Mark all the attachments as reported
-    for file in files {
-        db.mark_attachment_as_reported(&file).await?;
-    }
-
     // Generate an id for the report
     let id = Ulid::new().to_string();
 
+    // Mark all the attachments as reported, keeping them as evidence
+    for file in files {
+        db.mark_attachment_as_reported(&file, &id).await?;
+    }
+
     // Insert all new generated snapshots
     for content in snapshots {
         // Save a snapshot of the content
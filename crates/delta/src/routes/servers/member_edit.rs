@@ -131,18 +131,10 @@ pub async fn edit(
         ..Default::default()
     };
 
-    // 1. Remove fields from object
-    if let Some(fields) = &remove {
-        if fields.contains(&v0::FieldsMember::Avatar) {
-            if let Some(avatar) = &member.avatar {
-                db.mark_attachment_as_deleted(&avatar.id).await?;
-            }
-        }
-    }
-
-    // 2. Apply new avatar
+    // 1. Apply new avatar
     if let Some(avatar) = avatar {
-        partial.avatar = Some(File::use_avatar(db, &avatar, &user.id).await?);
+        partial.avatar =
+            Some(File::use_member_avatar(db, &avatar, &member.id.server, &member.id.user).await?);
     }
 
     member
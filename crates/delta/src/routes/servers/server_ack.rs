@@ -1,4 +1,5 @@
 use revolt_database::{
+    tasks,
     util::{permissions::DatabasePermissionQuery, reference::Reference},
     Database, User,
 };
@@ -23,7 +24,14 @@ pub async fn ack(db: &State<Database>, user: User, target: Reference) -> Result<
         return Err(create_error!(NotFound));
     }
 
-    db.acknowledge_channels(&user.id, &server.channels)
-        .await
-        .map(|_| EmptyResponse)
+    let notified = db.take_notified_unreads(&user.id, &server.channels).await?;
+
+    db.acknowledge_channels(&user.id, &server.channels).await?;
+
+    // Clear notifications already delivered to the user's other devices
+    for channel in notified {
+        tasks::push_coalesce::dismiss(db, user.id.clone(), channel).await;
+    }
+
+    Ok(EmptyResponse)
 }
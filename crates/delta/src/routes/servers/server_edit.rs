@@ -95,22 +95,7 @@ pub async fn edit(
         ..Default::default()
     };
 
-    // 1. Remove fields from object
-    if let Some(fields) = &remove {
-        if fields.contains(&v0::FieldsServer::Banner) {
-            if let Some(banner) = &server.banner {
-                db.mark_attachment_as_deleted(&banner.id).await?;
-            }
-        }
-
-        if fields.contains(&v0::FieldsServer::Icon) {
-            if let Some(icon) = &server.icon {
-                db.mark_attachment_as_deleted(&icon.id).await?;
-            }
-        }
-    }
-
-    // 2. Validate changes
+    // 1. Validate changes
     if let Some(system_messages) = &partial.system_messages {
         for id in system_messages.clone().into_channel_ids() {
             if !server.channels.contains(&id) {
@@ -136,16 +121,14 @@ pub async fn edit(
         }
     }
 
-    // 3. Apply new icon
+    // 2. Apply new icon
     if let Some(icon) = icon {
         partial.icon = Some(File::use_server_icon(db, &icon, &server.id).await?);
-        server.icon = partial.icon.clone();
     }
 
-    // 4. Apply new banner
+    // 3. Apply new banner
     if let Some(banner) = banner {
         partial.banner = Some(File::use_banner(db, &banner, &server.id).await?);
-        server.banner = partial.banner.clone();
     }
 
     server
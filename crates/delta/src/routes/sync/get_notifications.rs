@@ -0,0 +1,15 @@
+use revolt_database::{Database, User};
+use revolt_models::v0;
+use revolt_result::Result;
+use rocket::{serde::json::Json, State};
+
+/// # Fetch Notification Settings
+///
+/// Fetch which servers and channels you want to be notified about.
+#[openapi(tag = "Sync")]
+#[get("/notifications")]
+pub async fn fetch(db: &State<Database>, user: User) -> Result<Json<v0::NotificationSettings>> {
+    db.fetch_notification_settings(&user.id)
+        .await
+        .map(|settings| Json(settings.into()))
+}
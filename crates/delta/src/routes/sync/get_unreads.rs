@@ -1,5 +1,10 @@
-use revolt_database::{Database, User};
+use std::collections::HashMap;
+
+use revolt_database::{
+    util::permissions::DatabasePermissionQuery, Channel, Database, UnreadCounts, User,
+};
 use revolt_models::v0;
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
 use revolt_result::Result;
 use rocket::serde::json::Json;
 use rocket::State;
@@ -7,11 +12,64 @@ use rocket::State;
 /// # Fetch Unreads
 ///
 /// Fetch information about unread state on channels.
+///
+/// Unread messages can optionally be counted for each channel and server,
+/// muted channels are left out of the counts for their server.
 #[openapi(tag = "Sync")]
-#[get("/unreads")]
-pub async fn unreads(db: &State<Database>, user: User) -> Result<Json<Vec<v0::ChannelUnread>>> {
-    db.fetch_unreads(&user.id)
-        .await
-        .map(|v| v.into_iter().map(|u| u.into()).collect())
-        .map(Json)
+#[get("/unreads?<options..>")]
+pub async fn unreads(
+    db: &State<Database>,
+    user: User,
+    options: v0::OptionsFetchUnreads,
+) -> Result<Json<v0::UnreadsResponse>> {
+    let unreads = db.fetch_unreads(&user.id).await?;
+    if !options.include_counts.unwrap_or_default() {
+        return Ok(Json(v0::UnreadsResponse::JustUnreads(
+            unreads.into_iter().map(Into::into).collect(),
+        )));
+    }
+
+    // Find every channel the user can see
+    let members = db.fetch_all_memberships(&user.id).await?;
+    let server_ids: Vec<String> = members.iter().map(|m| m.id.server.clone()).collect();
+    let servers = db.fetch_servers(&server_ids).await?;
+
+    let channel_ids: Vec<String> = servers
+        .iter()
+        .flat_map(|server| server.channels.clone())
+        .collect();
+
+    let mut channels = db.find_direct_messages(&user.id).await?;
+    let members: HashMap<&str, _> = members.iter().map(|m| (m.id.server.as_str(), m)).collect();
+    let servers: HashMap<&str, _> = servers.iter().map(|s| (s.id.as_str(), s)).collect();
+
+    for channel in db.fetch_channels(&channel_ids).await? {
+        let (Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. }) = &channel
+        else {
+            continue;
+        };
+
+        let (Some(member), Some(server)) =
+            (members.get(server.as_str()), servers.get(server.as_str()))
+        else {
+            continue;
+        };
+
+        let mut query = DatabasePermissionQuery::new(db, &user)
+            .channel(&channel)
+            .member(member)
+            .server(server);
+
+        if calculate_channel_permissions(&mut query)
+            .await
+            .has_channel_permission(ChannelPermission::ViewChannel)
+        {
+            channels.push(channel);
+        }
+    }
+
+    let counts = UnreadCounts::fetch(db, &user.id, &channels, &unreads).await?;
+    Ok(Json(v0::UnreadsResponse::UnreadsWithCounts(
+        counts.summarise(unreads),
+    )))
 }
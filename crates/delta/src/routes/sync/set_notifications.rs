@@ -0,0 +1,22 @@
+use revolt_database::{Database, User};
+use revolt_models::v0;
+use revolt_result::Result;
+use rocket::{serde::json::Json, State};
+
+/// # Edit Notification Settings
+///
+/// Change which servers and channels you want to be notified about, when, and whether
+/// to receive email digests of what you missed.
+#[openapi(tag = "Sync")]
+#[patch("/notifications", data = "<data>")]
+pub async fn edit(
+    db: &State<Database>,
+    user: User,
+    data: Json<v0::DataEditNotificationSettings>,
+) -> Result<Json<v0::NotificationSettings>> {
+    let mut settings = db.fetch_notification_settings(&user.id).await?;
+    settings.apply(data.into_inner())?;
+    settings.set(db).await?;
+
+    Ok(Json(settings.into()))
+}
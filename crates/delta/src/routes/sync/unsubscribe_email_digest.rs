@@ -0,0 +1,106 @@
+use revolt_database::{Database, NotificationSettings};
+use revolt_result::Result;
+use rocket::{response::content::RawHtml, State};
+
+/// Page asking the user to confirm they want to unsubscribe
+///
+/// The form submits to the current URL, so the token never has to be embedded.
+const CONFIRM_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Unsubscribe from email digests</title></head>
+<body>
+<p>Stop receiving emails about messages you missed on Revolt?</p>
+<form method="post"><button type="submit">Unsubscribe</button></form>
+</body>
+</html>"#;
+
+/// Page shown once the user has been unsubscribed
+const DONE_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Unsubscribed from email digests</title></head>
+<body>
+<p>You will no longer receive email digests. You can turn them back on in your notification settings.</p>
+</body>
+</html>"#;
+
+/// # Confirm Unsubscribing from Email Digests
+///
+/// Show a page confirming the user wants to stop receiving email digests.
+///
+/// Link scanners and prefetchers open links in emails, so this makes no changes.
+#[openapi(skip)]
+#[get("/notifications/unsubscribe/<_token>")]
+pub async fn confirm(_token: String) -> RawHtml<&'static str> {
+    RawHtml(CONFIRM_PAGE)
+}
+
+/// # Unsubscribe from Email Digests
+///
+/// Stop sending email digests using the token included in them.
+///
+/// This is the target of the confirmation page and of one-click unsubscribe
+/// (RFC 8058) from mail clients, so no session is required.
+#[openapi(tag = "Sync")]
+#[post("/notifications/unsubscribe/<token>")]
+pub async fn unsubscribe(db: &State<Database>, token: String) -> Result<RawHtml<&'static str>> {
+    NotificationSettings::unsubscribe_email_digest(db, &token)
+        .await
+        .map(|_| RawHtml(DONE_PAGE))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{rocket, util::test::TestHarness};
+    use revolt_database::NotificationSettings;
+    use revolt_models::v0;
+    use rocket::http::Status;
+
+    #[rocket::async_test]
+    async fn unsubscribe_email_digest() {
+        let harness = TestHarness::new().await;
+        let (_, _, user) = harness.new_user().await;
+
+        let mut settings = NotificationSettings {
+            id: user.id.clone(),
+            ..Default::default()
+        };
+        settings
+            .apply(v0::DataEditNotificationSettings {
+                email_digest: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        settings.set(&harness.db).await.unwrap();
+
+        let url = format!(
+            "/sync/notifications/unsubscribe/{}",
+            settings.email_digest.as_ref().unwrap().token
+        );
+
+        // Opening the link only asks for confirmation
+        let response = harness.client.get(&url).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(harness
+            .db
+            .fetch_notification_settings(&user.id)
+            .await
+            .unwrap()
+            .email_digest
+            .is_some());
+
+        let response = harness
+            .client
+            .post(&url)
+            .body("List-Unsubscribe=One-Click")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(harness
+            .db
+            .fetch_notification_settings(&user.id)
+            .await
+            .unwrap()
+            .email_digest
+            .is_none());
+    }
+}
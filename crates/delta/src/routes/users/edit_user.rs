@@ -53,37 +53,28 @@ pub async fn edit(
         && data.avatar.is_none()
         && data.badges.is_none()
         && data.flags.is_none()
+        && data.share_last_seen.is_none()
         && data.remove.is_none()
     {
         return Ok(Json(user.into_self(false).await));
     }
 
-    // 1. Remove fields from object
-    if let Some(fields) = &data.remove {
-        if fields.contains(&v0::FieldsUser::Avatar) {
-            if let Some(avatar) = &user.avatar {
-                db.mark_attachment_as_deleted(&avatar.id).await?;
-            }
-        }
-
-        if fields.contains(&v0::FieldsUser::ProfileBackground) {
-            if let Some(profile) = &user.profile {
-                if let Some(background) = &profile.background {
-                    db.mark_attachment_as_deleted(&background.id).await?;
-                }
-            }
-        }
+    // 1. Remove fields from a copy of the object, the originals are detached on update
+    let remove: Vec<FieldsUser> = data
+        .remove
+        .map(|v| v.into_iter().map(Into::into).collect())
+        .unwrap_or_default();
 
-        for field in fields {
-            let field: FieldsUser = field.clone().into();
-            user.remove_field(&field);
-        }
+    let mut current = user.clone();
+    for field in &remove {
+        current.remove_field(field);
     }
 
     let mut partial: PartialUser = PartialUser {
         display_name: data.display_name,
         badges: data.badges,
         flags: data.flags,
+        share_last_seen: data.share_last_seen,
         ..Default::default()
     };
 
@@ -94,7 +85,7 @@ pub async fn edit(
 
     // 3. Apply new status
     if let Some(status) = data.status {
-        let mut new_status = user.status.take().unwrap_or_default();
+        let mut new_status = current.status.take().unwrap_or_default();
         if let Some(text) = status.text {
             new_status.text = Some(text);
         }
@@ -108,7 +99,7 @@ pub async fn edit(
 
     // 4. Apply new profile
     if let Some(profile) = data.profile {
-        let mut new_profile = user.profile.take().unwrap_or_default();
+        let mut new_profile = current.profile.take().unwrap_or_default();
         if let Some(content) = profile.content {
             new_profile.content = Some(content);
         }
@@ -120,14 +111,7 @@ pub async fn edit(
         partial.profile = Some(new_profile);
     }
 
-    user.update(
-        db,
-        partial,
-        data.remove
-            .map(|v| v.into_iter().map(Into::into).collect())
-            .unwrap_or_default(),
-    )
-    .await?;
+    user.update(db, partial, remove).await?;
 
     Ok(Json(user.into_self(false).await))
 }
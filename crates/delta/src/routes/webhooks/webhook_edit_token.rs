@@ -1,5 +1,5 @@
 use revolt_database::util::reference::Reference;
-use revolt_database::{Database, PartialWebhook};
+use revolt_database::{Database, File, PartialWebhook};
 use revolt_models::v0::{DataEditWebhook, Webhook};
 use revolt_models::validator::Validate;
 use revolt_result::{create_error, Result};
@@ -34,7 +34,7 @@ pub async fn webhook_edit_token(
         name,
         avatar,
         permissions,
-        remove
+        remove,
     } = data;
 
     let mut partial = PartialWebhook {
@@ -44,10 +44,7 @@ pub async fn webhook_edit_token(
     };
 
     if let Some(avatar) = avatar {
-        let file = db
-            .find_and_use_attachment(&avatar, "avatars", "user", &webhook.id)
-            .await?;
-
+        let file = File::use_webhook_avatar(db, &avatar, &webhook.id).await?;
         partial.avatar = Some(file)
     }
 
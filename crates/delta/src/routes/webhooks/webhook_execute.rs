@@ -1,7 +1,7 @@
 use revolt_config::config;
 use revolt_database::{
     util::{idempotency::IdempotencyKey, reference::Reference},
-    Database, Message,
+    AllowedMentions, Database, Message,
 };
 use revolt_models::v0;
 use revolt_permissions::{ChannelPermission, PermissionValue};
@@ -64,7 +64,11 @@ pub async fn webhook_execute(
             config().await.features.limits.default,
             idempotency,
             true,
-            true,
+            AllowedMentions {
+                users: true,
+                roles: permissions.has_channel_permission(ChannelPermission::MentionRoles),
+                everyone: permissions.has_channel_permission(ChannelPermission::MentionEveryone),
+            },
         )
         .await?
         .into_model(None, None),
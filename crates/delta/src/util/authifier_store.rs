@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use revolt_database::AuthifierStore;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Method;
+use rocket::{Request, Response};
+
+/// Routes which may change accounts or sessions
+static PERSISTED_ROUTES: [&str; 2] = ["/auth/", "/push/"];
+
+/// How often to write back changes made outside of requests, such as by background tasks
+const PERSIST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Write changes to accounts and sessions back to the database before responding
+pub struct AuthifierStoreFairing(pub AuthifierStore);
+
+#[rocket::async_trait]
+impl Fairing for AuthifierStoreFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Authifier Store",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, _: &mut Response<'r>) {
+        let path = request.uri().path();
+        if request.method() == Method::Get
+            || !PERSISTED_ROUTES
+                .iter()
+                .any(|route| path.as_str().starts_with(route))
+        {
+            return;
+        }
+
+        if let Err(err) = self.0.persist().await {
+            log::error!("Failed to store accounts and sessions! {:?}", err);
+        }
+    }
+}
+
+/// Periodically write back changes to accounts and sessions
+pub async fn worker(store: AuthifierStore) {
+    loop {
+        async_std::task::sleep(PERSIST_INTERVAL).await;
+
+        if let Err(err) = store.persist().await {
+            log::error!("Failed to store accounts and sessions! {:?}", err);
+        }
+    }
+}
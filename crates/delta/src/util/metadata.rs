@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use revolt_database::Metadata;
+
+/// How much of the start of a file is read to determine its type
+pub const SNIFF_LENGTH: u64 = 1024 * 1024;
+
+/// Determine the content type and metadata of an uploaded file
+///
+/// `head` holds up to the first [SNIFF_LENGTH] bytes of the file at `path`,
+/// which is only used to probe videos.
+pub async fn sniff(path: &Path, head: &[u8]) -> (String, Metadata) {
+    let content_type = match infer::get(head) {
+        Some(kind) => kind.mime_type().to_string(),
+        None if is_text(head) => "text/plain".to_string(),
+        None => "application/octet-stream".to_string(),
+    };
+
+    let metadata = match content_type.split('/').next() {
+        Some("image") => match imagesize::blob_size(head) {
+            Ok(size) => Metadata::Image {
+                width: size.width as isize,
+                height: size.height as isize,
+            },
+            Err(_) => Metadata::File,
+        },
+        Some("video") => match probe_video(path.to_path_buf()).await {
+            Some((width, height)) => Metadata::Video { width, height },
+            None => Metadata::File,
+        },
+        Some("audio") => Metadata::Audio,
+        Some("text") => Metadata::Text,
+        _ => Metadata::File,
+    };
+
+    (content_type, metadata)
+}
+
+/// Check whether the start of a file is UTF-8 text
+///
+/// A character cut off at the end of the head is still accepted.
+fn is_text(head: &[u8]) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none() && head.len() as u64 == SNIFF_LENGTH,
+    }
+}
+
+/// Find the dimensions of a video using ffprobe, if it is installed
+async fn probe_video(path: PathBuf) -> Option<(isize, isize)> {
+    let output = async_std::task::spawn_blocking(move || {
+        Command::new("ffprobe")
+            .args([
+                "-v",
+                "error",
+                "-select_streams",
+                "v:0",
+                "-show_entries",
+                "stream=width,height",
+                "-of",
+                "csv=p=0:s=x",
+            ])
+            .arg(path)
+            .output()
+    })
+    .await
+    .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    let (width, height) = output.trim().split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use revolt_database::Metadata;
+
+    use super::{sniff, SNIFF_LENGTH};
+
+    #[async_std::test]
+    async fn sniff_content() {
+        // 1x1 transparent PNG
+        let png = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0x1f, 0x15, 0xc4, 0x89,
+        ];
+
+        let path = Path::new("unused");
+        assert_eq!(
+            sniff(path, &png).await,
+            (
+                "image/png".to_string(),
+                Metadata::Image {
+                    width: 1,
+                    height: 1
+                }
+            )
+        );
+
+        assert_eq!(
+            sniff(path, b"hello world").await,
+            ("text/plain".to_string(), Metadata::Text)
+        );
+
+        assert_eq!(
+            sniff(path, &[0xff, 0xfe, 0x00, 0x81]).await,
+            ("application/octet-stream".to_string(), Metadata::File)
+        );
+
+        // Text cut off in the middle of a character is only accepted at the end of the head
+        let mut head = b"a".to_vec();
+        head.extend("é".repeat(SNIFF_LENGTH as usize / 2 - 1).bytes());
+        head.push("é".as_bytes()[0]);
+        assert_eq!(
+            sniff(path, &head).await,
+            ("text/plain".to_string(), Metadata::Text)
+        );
+        assert_eq!(
+            sniff(path, &head[1..]).await,
+            ("application/octet-stream".to_string(), Metadata::File)
+        );
+    }
+}
@@ -1,2 +1,6 @@
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub mod authifier_store;
+pub mod metadata;
 pub mod ratelimiter;
+pub mod storage;
 pub mod test;
@@ -1,8 +1,3 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::Hasher;
-use std::ops::Add;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
 use authifier::models::Session;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::uri::Origin;
@@ -16,69 +11,7 @@ use revolt_rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
 
 use serde::Serialize;
 
-use dashmap::DashMap;
-use once_cell::sync::Lazy;
-
-/// Ratelimit Bucket
-#[derive(Clone, Copy, Debug)]
-struct Entry {
-    used: u8,
-    reset: u128,
-}
-
-static MAP: Lazy<DashMap<u64, Entry>> = Lazy::new(DashMap::new);
-
-/// Get the current time from Unix Epoch as a Duration
-fn now() -> Duration {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards...")
-}
-
-impl Entry {
-    /// Find bucket by its key
-    pub fn from(key: u64) -> Entry {
-        MAP.get(&key).map(|x| *x).unwrap_or_else(|| Entry {
-            used: 0,
-            reset: now().add(Duration::from_secs(10)).as_millis(),
-        })
-    }
-
-    /// Deduct one unit from the bucket and save
-    pub fn deduct(&mut self) {
-        let current_time = now().as_millis();
-        if current_time > self.reset {
-            self.used = 1;
-            self.reset = now().add(Duration::from_secs(10)).as_millis();
-        } else {
-            self.used += 1;
-        }
-    }
-
-    /// Save information
-    pub fn save(self, key: u64) {
-        MAP.insert(key, self);
-    }
-
-    /// Get remaining units in the bucket
-    pub fn get_remaining(&self, limit: u8) -> u8 {
-        if now().as_millis() > self.reset {
-            limit
-        } else {
-            limit - self.used
-        }
-    }
-
-    /// Get how long bucket has until reset
-    pub fn left_until_reset(&self) -> u128 {
-        let current_time = now().as_millis();
-        if current_time > self.reset {
-            0
-        } else {
-            self.reset - current_time
-        }
-    }
-}
+use revolt_database::util::ratelimit::Ratelimit;
 
 /// Ratelimit Guard
 #[derive(Serialize, Clone, Copy, Debug)]
@@ -125,6 +58,8 @@ fn resolve_bucket<'r>(request: &'r rocket::Request<'_>) -> (&'r str, Option<&'r
                     ("auth", None)
                 }
             }
+            ("files", _, Method::Post) => ("file_upload", None),
+            ("files", _, _) => ("files", None),
             ("swagger", _, _) => ("swagger", None),
             ("safety", Some("report"), _) => ("safety_report", Some("report")),
             ("safety", _, _) => ("safety", None),
@@ -135,25 +70,6 @@ fn resolve_bucket<'r>(request: &'r rocket::Request<'_>) -> (&'r str, Option<&'r
     }
 }
 
-/// Resolve per-bucket limits
-fn resolve_bucket_limit(bucket: &str) -> u8 {
-    match bucket {
-        "user_edit" => 2,
-        "users" => 20,
-        "bots" => 10,
-        "messaging" => 10,
-        "channels" => 15,
-        "servers" => 5,
-        "auth" => 15,
-        "auth_delete" => 255,
-        "default_avatar" => 255,
-        "swagger" => 100,
-        "safety" => 15,
-        "safety_report" => 3,
-        _ => 20,
-    }
-}
-
 /// Find the remote IP of the client
 fn to_ip(request: &'_ rocket::Request<'_>) -> String {
     request
@@ -175,42 +91,21 @@ fn to_real_ip(request: &'_ rocket::Request<'_>) -> String {
     }
 }
 
-impl Ratelimiter {
-    /// Generate guard from identifier and target bucket
-    pub fn from(
-        identifier: &str,
-        (bucket, resource): (&str, Option<&str>),
-    ) -> Result<Ratelimiter, Ratelimiter> {
-        let mut key = DefaultHasher::new();
-        key.write(identifier.as_bytes());
-        key.write(bucket.as_bytes());
-
-        if let Some(id) = resource {
-            key.write(id.as_bytes());
-        }
-
-        let key = key.finish();
-        let limit = resolve_bucket_limit(bucket);
-        let mut entry = Entry::from(key);
-
-        let remaining = entry.get_remaining(limit);
-        let reset = entry.left_until_reset();
-        let mut ratelimiter = Ratelimiter {
+impl From<Ratelimit> for Ratelimiter {
+    fn from(
+        Ratelimit {
+            key,
+            limit,
+            remaining,
+            reset,
+        }: Ratelimit,
+    ) -> Self {
+        Ratelimiter {
             key,
             limit,
             remaining,
             reset,
-        };
-        if remaining == 0 {
-            return Err(ratelimiter);
         }
-
-        entry.deduct();
-        entry.save(key);
-        ratelimiter.remaining -= 1;
-        ratelimiter.reset = entry.left_until_reset();
-
-        Ok(ratelimiter)
     }
 }
 
@@ -229,7 +124,9 @@ impl<'r> FromRequest<'r> for Ratelimiter {
                     to_real_ip(request)
                 };
 
-                Ratelimiter::from(&identifier, resolve_bucket(request))
+                Ratelimit::deduct(&identifier, resolve_bucket(request))
+                    .map(Ratelimiter::from)
+                    .map_err(Ratelimiter::from)
             })
             .await;
 
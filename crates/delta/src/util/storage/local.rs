@@ -0,0 +1,79 @@
+use std::io::{ErrorKind, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use revolt_result::{create_error, Error, Result};
+use rocket::tokio::fs;
+use rocket::tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::{FileStream, Storage};
+
+/// Store files in a directory on the local filesystem
+pub struct LocalStorage {
+    path: PathBuf,
+}
+
+impl LocalStorage {
+    /// Create a new local storage backend in the given directory
+    pub fn new(path: &str) -> LocalStorage {
+        LocalStorage { path: path.into() }
+    }
+
+    /// Find where a file is kept
+    fn path(&self, tag: &str, id: &str) -> PathBuf {
+        self.path.join(tag).join(id)
+    }
+}
+
+/// Convert a filesystem error into an API error
+fn to_error(error: std::io::Error) -> Error {
+    if error.kind() == ErrorKind::NotFound {
+        create_error!(NotFound)
+    } else {
+        error!("Failed to access local file storage: {error:?}");
+        create_error!(InternalError)
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    /// Store a file, streaming its contents from the given path
+    async fn put(&self, tag: &str, id: &str, path: &Path, _size: u64) -> Result<()> {
+        let destination = self.path(tag, id);
+        fs::create_dir_all(
+            destination
+                .parent()
+                .expect("files are always kept under a tag"),
+        )
+        .await
+        .map_err(to_error)?;
+
+        fs::copy(path, destination)
+            .await
+            .map(|_| ())
+            .map_err(to_error)
+    }
+
+    /// Read the contents of a file, optionally only the given range of bytes
+    async fn get(&self, tag: &str, id: &str, range: Option<Range<u64>>) -> Result<FileStream> {
+        let mut file = fs::File::open(self.path(tag, id)).await.map_err(to_error)?;
+
+        if let Some(range) = range {
+            file.seek(SeekFrom::Start(range.start))
+                .await
+                .map_err(to_error)?;
+
+            Ok(Box::pin(file.take(range.end - range.start)))
+        } else {
+            Ok(Box::pin(file))
+        }
+    }
+
+    /// Delete a file
+    async fn delete(&self, tag: &str, id: &str) -> Result<()> {
+        match fs::remove_file(self.path(tag, id)).await {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(to_error(error)),
+            _ => Ok(()),
+        }
+    }
+}
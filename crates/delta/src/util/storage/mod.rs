@@ -0,0 +1,71 @@
+use std::ops::Range;
+use std::path::Path;
+use std::pin::Pin;
+
+use revolt_config::{ApiFiles, FeaturesLimits, FeaturesLimitsCollection, FileStorage};
+use revolt_result::Result;
+use rocket::tokio::io::AsyncRead;
+
+mod local;
+mod s3;
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+/// Contents of a stored file, read as they are sent on
+pub type FileStream = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Backend which uploaded files are kept in
+#[rocket::async_trait]
+pub trait Storage: Sync + Send {
+    /// Store a file, streaming its contents from the given path
+    async fn put(&self, tag: &str, id: &str, path: &Path, size: u64) -> Result<()>;
+
+    /// Read the contents of a file, optionally only the given range of bytes
+    async fn get(&self, tag: &str, id: &str, range: Option<Range<u64>>) -> Result<FileStream>;
+
+    /// Delete a file
+    async fn delete(&self, tag: &str, id: &str) -> Result<()>;
+}
+
+/// Create the configured storage backend, if uploads are enabled
+pub fn from_config(config: &ApiFiles) -> Option<Box<dyn Storage>> {
+    match config.storage {
+        FileStorage::Disabled => None,
+        FileStorage::Local => Some(Box::new(LocalStorage::new(&config.local.path))),
+        FileStorage::S3 => Some(Box::new(S3Storage::new(config.s3.clone()))),
+    }
+}
+
+/// Get the largest file a user with the given limits may upload to a tag
+pub fn size_limit(limits: &FeaturesLimits, tag: &str) -> Option<usize> {
+    match tag {
+        "attachments" => Some(limits.attachment_size),
+        "avatars" => Some(limits.avatar_size),
+        "backgrounds" => Some(limits.background_size),
+        "icons" => Some(limits.icon_size),
+        "banners" => Some(limits.banner_size),
+        "emojis" => Some(limits.emoji_size),
+        _ => None,
+    }
+}
+
+/// Get the largest file anyone may upload
+pub fn max_upload_size(limits: &FeaturesLimitsCollection) -> usize {
+    [&limits.new_user, &limits.default]
+        .iter()
+        .copied()
+        .chain(limits.roles.values())
+        .flat_map(|limits| {
+            [
+                limits.attachment_size,
+                limits.avatar_size,
+                limits.background_size,
+                limits.icon_size,
+                limits.banner_size,
+                limits.emoji_size,
+            ]
+        })
+        .max()
+        .unwrap_or_default()
+}
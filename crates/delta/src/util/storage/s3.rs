@@ -0,0 +1,114 @@
+use std::fmt::Debug;
+use std::ops::Range;
+use std::path::Path;
+
+use aws_sdk_s3::{
+    config::{BehaviorVersion, Credentials, Region},
+    error::SdkError,
+    operation::get_object::GetObjectError,
+    primitives::ByteStream,
+    Client,
+};
+use revolt_config::ApiFilesS3;
+use revolt_result::{create_error, Error, Result};
+
+use super::{FileStream, Storage};
+
+/// Store files in a bucket of any S3-compatible API
+pub struct S3Storage {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Storage {
+    /// Create a new S3 storage backend for the given bucket
+    pub fn new(config: ApiFilesS3) -> S3Storage {
+        let credentials = Credentials::new(
+            config.access_key_id,
+            config.secret_access_key,
+            None,
+            None,
+            "revolt",
+        );
+
+        // Buckets are always addressed by path so any endpoint works
+        let client = Client::from_conf(
+            aws_sdk_s3::Config::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .endpoint_url(config.endpoint)
+                .region(Region::new(config.region))
+                .credentials_provider(credentials)
+                .force_path_style(true)
+                .build(),
+        );
+
+        S3Storage {
+            client,
+            bucket: config.bucket,
+        }
+    }
+}
+
+/// Key of a file within the bucket
+fn key(tag: &str, id: &str) -> String {
+    format!("{tag}/{id}")
+}
+
+/// Log an error from the S3 API and convert it into an API error
+fn to_error(error: impl Debug) -> Error {
+    error!("Failed to access S3 file storage: {error:?}");
+    create_error!(InternalError)
+}
+
+#[rocket::async_trait]
+impl Storage for S3Storage {
+    /// Store a file, streaming its contents from the given path
+    async fn put(&self, tag: &str, id: &str, path: &Path, size: u64) -> Result<()> {
+        let body = ByteStream::from_path(path).await.map_err(to_error)?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key(tag, id))
+            .content_length(size as i64)
+            .body(body)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(to_error)
+    }
+
+    /// Read the contents of a file, optionally only the given range of bytes
+    async fn get(&self, tag: &str, id: &str, range: Option<Range<u64>>) -> Result<FileStream> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key(tag, id))
+            .set_range(range.map(|range| format!("bytes={}-{}", range.start, range.end - 1)))
+            .send()
+            .await
+            .map_err(|error| match error {
+                SdkError::ServiceError(error)
+                    if matches!(error.err(), GetObjectError::NoSuchKey(_)) =>
+                {
+                    create_error!(NotFound)
+                }
+                error => to_error(error),
+            })?;
+
+        Ok(Box::pin(output.body.into_async_read()))
+    }
+
+    /// Delete a file
+    async fn delete(&self, tag: &str, id: &str) -> Result<()> {
+        // Deleting a file which doesn't exist succeeds
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key(tag, id))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(to_error)
+    }
+}
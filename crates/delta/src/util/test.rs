@@ -2,18 +2,29 @@ use authifier::{
     models::{Account, Session},
     Authifier,
 };
-use futures::StreamExt;
 use rand::Rng;
-use redis_kiss::redis::aio::PubSub;
-use revolt_database::{events::client::EventV1, Database, User};
+use revolt_database::{
+    events::{
+        broker::{broker, BrokerEvent, EventSubscriber},
+        client::EventV1,
+    },
+    Database, User,
+};
 use revolt_models::v0;
-use rocket::local::asynchronous::Client;
+use rocket::{http::ContentType, local::asynchronous::Client};
+
+/// 1x1 transparent PNG
+pub static PNG: [u8; 33] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89,
+];
 
 pub struct TestHarness {
     pub client: Client,
     authifier: Authifier,
     pub db: Database,
-    sub: PubSub,
+    sub: Box<dyn EventSubscriber>,
     event_buffer: Vec<(String, EventV1)>,
 }
 
@@ -25,11 +36,13 @@ impl TestHarness {
             .await
             .expect("valid rocket instance");
 
-        let mut sub = redis_kiss::open_pubsub_connection()
+        let sub = broker()
+            .await
+            .subscriber()
             .await
-            .expect("`PubSub`");
+            .expect("`EventSubscriber`");
 
-        sub.psubscribe("*").await.unwrap();
+        sub.subscribe_all().await.unwrap();
 
         let db = client
             .rocket()
@@ -37,6 +50,9 @@ impl TestHarness {
             .expect("`Database`")
             .clone();
 
+        // Events are published through the outbox, relay them for this database.
+        async_std::task::spawn(revolt_database::tasks::outbox::worker(db.clone()));
+
         let authifier = client
             .rocket()
             .state::<Authifier>()
@@ -61,6 +77,21 @@ impl TestHarness {
             .collect()
     }
 
+    /// Build a multipart form holding a single file
+    pub fn multipart(filename: &str, contents: &[u8]) -> (ContentType, Vec<u8>) {
+        let mut body = format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(contents);
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+        (
+            ContentType::new("multipart", "form-data").with_params(("boundary", "boundary")),
+            body,
+        )
+    }
+
     pub async fn new_user(&self) -> (Account, Session, User) {
         let account = Account::new(
             &self.authifier,
@@ -99,16 +130,21 @@ impl TestHarness {
             }
         }
 
-        let mut stream = self.sub.on_message();
-        while let Some(item) = stream.next().await {
-            let msg_topic = item.get_channel_name();
-            let payload: EventV1 = redis_kiss::decode_payload(&item).unwrap();
+        while let Some(item) = self.sub.next().await {
+            let BrokerEvent::Event {
+                topic: msg_topic,
+                event: payload,
+                ..
+            } = item
+            else {
+                continue;
+            };
 
             if topic == msg_topic && predicate(&payload) {
                 return payload;
             }
 
-            self.event_buffer.push((msg_topic.to_string(), payload));
+            self.event_buffer.push((msg_topic, payload));
         }
 
         // WARNING: if predicate is never satisfied, this will never return